@@ -0,0 +1,139 @@
+//! A client that discovers the committee and the parameters of a key and then evaluates many queries concurrently.
+//!
+//! - Discovery: fetches the [`oprf_types::api::Committee`] from the nodes and checks that every node uses a registered wallet (skipped if the nodes do not serve the committee), then fetches the [`oprf_types::api::OprfKeyParams`] to learn the threshold, the max message size and the max batch size of the nodes.
+//! - Pooling: a single [`reqwest::Client`] (with its connection pool) and a single [`Connector`] are shared by all requests, and the queries are evaluated as one batch via [`taceo_oprf_client::distributed_oprf_batch`] with at most `OPRF_CONCURRENCY` sessions at the same time, each carrying as many queries as the nodes accept per session.
+//! - TLS: nodes with `https` URLs are connected to via rustls, verifying their certificates against the webpki roots.
+//!
+//! The `auth` of the requests is the [`OprfKeyId`], as expected by the `dev-node` example of `taceo-oprf-service`:
+//!
+//! ```sh
+//! cargo run -p taceo-oprf-service --features test-kit --example dev-node
+//! OPRF_NODES=http://127.0.0.1:4321 OPRF_MODULE=dev cargo run -p taceo-oprf-client --example full-client
+//! ```
+
+use std::{str::FromStr, sync::Arc, time::Instant};
+
+use ark_ff::UniformRand as _;
+use eyre::{Context as _, ContextCompat as _};
+use oprf_core::oprf::BlindingFactor;
+use oprf_types::OprfKeyId;
+use rustls::{ClientConfig, RootCertStore};
+use taceo_oprf_client::{BatchConfig, BatchQuery, Connector, RetryPolicy};
+
+/// Reads the environment variable `name`, falling back to `default`.
+fn env_or<T: FromStr>(name: &str, default: T) -> eyre::Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    std::env::var(name)
+        .ok()
+        .map_or(Ok(default), |value| value.parse())
+        .with_context(|| format!("invalid {name}"))
+}
+
+/// Uses TLS if any of the nodes is served via `https`.
+fn connector(nodes: &[String]) -> Connector {
+    if nodes.iter().any(|node| node.starts_with("https://")) {
+        let mut root_store = RootCertStore::empty();
+        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        Connector::Rustls(Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(root_store)
+                .with_no_client_auth(),
+        ))
+    } else {
+        Connector::Plain
+    }
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    rustls::crypto::aws_lc_rs::default_provider()
+        .install_default()
+        .expect("can install");
+    let nodes = env_or("OPRF_NODES", "http://127.0.0.1:4321".to_owned())?
+        .split(',')
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    let module = env_or("OPRF_MODULE", "dev".to_owned())?;
+    let oprf_key_id = OprfKeyId::from(env_or("OPRF_KEY_ID", 1usize)?);
+    // how many nodes must agree on the discovered committee and key parameters
+    let agreement = env_or("OPRF_AGREEMENT", nodes.len())?;
+    let evaluations = env_or("OPRF_EVALUATIONS", 16usize)?;
+    let concurrency = env_or("OPRF_CONCURRENCY", 4usize)?;
+
+    let client = reqwest::Client::new();
+    let retry_policy = RetryPolicy::default();
+
+    // discovery
+    let committee_urls = taceo_oprf_client::to_committee_url_many(&nodes)?;
+    match taceo_oprf_client::fetch_committee(&committee_urls, agreement, &client, &retry_policy)
+        .await?
+    {
+        Some(committee) => {
+            taceo_oprf_client::check_committee_wallets(&nodes, &committee, &client).await?;
+            println!(
+                "all nodes are registered in the committee of {} nodes",
+                committee.members.len()
+            );
+        }
+        None => println!("nodes do not serve the committee, skipping the wallet check"),
+    }
+    let params_urls = taceo_oprf_client::to_oprf_params_url_many(&nodes)?;
+    let params = taceo_oprf_client::fetch_oprf_key_params(
+        &params_urls,
+        agreement,
+        oprf_key_id,
+        &client,
+        &retry_policy,
+    )
+    .await?
+    .context("nodes do not know the key")?;
+    let threshold = usize::from(params.threshold.get());
+    println!(
+        "key {oprf_key_id} is at epoch {:?} with threshold {threshold}",
+        params.epoch
+    );
+    let auth_size = serde_json::to_vec(&oprf_key_id)?.len();
+    eyre::ensure!(
+        params.accepts_message_size(auth_size),
+        "auth of {auth_size} bytes exceeds the max message size of the nodes"
+    );
+
+    // pooled evaluations as one batch
+    let services = taceo_oprf_client::to_oprf_uri_many(&nodes, &module)?;
+    let connector = connector(&nodes);
+    let start = Instant::now();
+    let mut rng = rand::thread_rng();
+    let queries = (0..evaluations)
+        .map(|_| BatchQuery {
+            query: ark_babyjubjub::Fq::rand(&mut rng),
+            blinding_factor: BlindingFactor::rand(&mut rng),
+        })
+        .collect();
+    let results = taceo_oprf_client::distributed_oprf_batch(
+        &services,
+        threshold,
+        queries,
+        ark_babyjubjub::Fq::from(42u64),
+        oprf_key_id,
+        connector,
+        BatchConfig {
+            concurrency,
+            max_batch_size: params.batch_size(),
+            ..Default::default()
+        },
+    )
+    .await;
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    for err in results.iter().filter_map(|result| result.as_ref().err()) {
+        eprintln!("evaluation failed: {err}");
+    }
+    println!(
+        "{} of {evaluations} evaluations succeeded in {:?}",
+        evaluations - failed,
+        start.elapsed()
+    );
+    eyre::ensure!(failed == 0, "{failed} evaluations failed");
+    Ok(())
+}
@@ -0,0 +1,302 @@
+//! Server-side combination of the distributed OPRF protocol for thin clients.
+//!
+//! An [`Aggregator`] is the reference implementation of a gateway that talks to the OPRF nodes on behalf of thin clients. In contrast to the delegate node of [`crate::delegate_distributed_oprf`], which forwards the commitments and proof shares of the nodes, the aggregator combines the proof shares and verifies the combined `DLog` equality proof itself. It answers with an [`AggregatedOprfResponse`], i.e., only the blinded response and its proof.
+//!
+//! The blinding stays on the client: the client sends the blinded query, and [`aggregated_oprf`] unblinds the response and derives the output. Therefore, the aggregator never learns the query or the output, only the same information as the nodes. A client can verify the returned proof itself (which [`aggregated_oprf`] does), but must still check the returned public key against a trusted source (see [`crate::fetch_oprf_public_key`]) if the aggregator is untrusted.
+//!
+//! With the `aggregator` feature, [`Aggregator::routes`] serves the aggregator at `POST /aggregate`.
+use ark_ec::AffineRepr as _;
+use http::Uri;
+use oprf_core::oprf::{BlindedOprfRequest, BlindedOprfResponse, BlindingFactor};
+use oprf_types::api::{
+    AggregatedOprfResponse, OprfPublicKeyWithEpoch, OprfRequest, SchemaFingerprint,
+};
+use serde::Serialize;
+use tracing::instrument;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    Connector, Error, OutputDomain, VERSION, VerifiableOprfOutput, check_services,
+    delegate_response, distributed_oprf_core_from, oprf_output, sessions::SessionSource,
+    unix_timestamp, verify_dlog_equality,
+};
+
+/// Runs the distributed OPRF protocol against a fixed set of nodes and combines the results, see the [module docs](self).
+#[derive(Clone)]
+pub struct Aggregator {
+    services: Vec<Uri>,
+    threshold: usize,
+    connector: Connector,
+    context: ark_babyjubjub::Fq,
+    require_share_proofs: bool,
+}
+
+impl Aggregator {
+    /// Creates an `Aggregator` for the given nodes.
+    ///
+    /// # Arguments
+    /// - `services`: List of WebSocket URIs of the OPRF nodes to contact (must be unique). See the helper functions [`crate::to_oprf_uri`] and [`crate::to_oprf_uri_many`].
+    /// - `threshold`: Number of nodes required to complete the protocol
+    /// - `connector`: TLS connector configuration for the WebSocket connections
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidThreshold`] or [`Error::NonUniqueServices`] for an invalid set of nodes.
+    pub fn new(services: Vec<Uri>, threshold: usize, connector: Connector) -> Result<Self, Error> {
+        check_services(&services, threshold)?;
+        Ok(Self {
+            services,
+            threshold,
+            connector,
+            context: ark_babyjubjub::Fq::default(),
+            require_share_proofs: false,
+        })
+    }
+
+    /// Verifies the combined proofs with the `context` the OPRF module of the nodes is configured with, see [`crate::OutputDomain`].
+    #[must_use]
+    pub fn with_context(mut self, context: &str) -> Self {
+        self.context = oprf_core::oprf::client::query_context(context).inner();
+        self
+    }
+
+    /// Rejects responses without a [`ShareProof`](oprf_types::api::ShareProof) with [`crate::NodeError::InvalidShareProof`], so that the public shares of the nodes are always checked against the OPRF public key.
+    #[must_use]
+    pub fn require_share_proofs(mut self) -> Self {
+        self.require_share_proofs = true;
+        self
+    }
+
+    /// Evaluates the blinded query of `req` at the nodes, and combines and verifies the result.
+    ///
+    /// `req` must not have a [`OprfRequest::batch`].
+    ///
+    /// # Errors
+    /// See the [`Error`] enum for all potential errors of this function. Returns [`Error::InvalidDLogProof`] if the combined proof cannot be verified.
+    #[instrument(level = "debug", skip_all, fields(request_id = %req.request_id))]
+    pub async fn aggregate<OprfRequestAuth>(
+        &self,
+        req: OprfRequest<OprfRequestAuth>,
+    ) -> Result<AggregatedOprfResponse, Error>
+    where
+        OprfRequestAuth: Clone + Serialize + 'static,
+    {
+        let request_id = req.request_id;
+        let blinded_request = BlindedOprfRequest::new(req.blinded_query);
+        let (oprf_public_key, epoch, challenge, responses) = distributed_oprf_core_from(
+            &SessionSource::Connect {
+                connector: self.connector.clone(),
+                require_share_proofs: self.require_share_proofs,
+            },
+            &self.services,
+            self.threshold,
+            req,
+        )
+        .await?;
+        let blinded_response = challenge.blinded_response();
+        let dlog_proof = verify_dlog_equality(
+            request_id,
+            oprf_public_key,
+            &blinded_request,
+            &responses,
+            challenge,
+            self.context,
+        )?;
+        Ok(AggregatedOprfResponse {
+            blinded_response,
+            dlog_proof,
+            oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch {
+                key: oprf_public_key,
+                epoch,
+            },
+        })
+    }
+}
+
+#[cfg(all(feature = "aggregator", not(target_arch = "wasm32")))]
+mod routes {
+    use axum::{
+        Json, Router,
+        extract::{Query, State},
+        response::{IntoResponse, Response},
+        routing::post,
+    };
+    use http::StatusCode;
+    use oprf_types::api::{OprfRequest, SchemaFingerprint};
+    use serde::{Deserialize, Serialize};
+    use tracing::instrument;
+
+    use super::Aggregator;
+    use crate::Error;
+
+    /// The query parameters of `POST /aggregate`.
+    #[derive(Deserialize)]
+    struct AggregateQuery {
+        schema: Option<SchemaFingerprint>,
+    }
+
+    impl Aggregator {
+        /// Returns an [`axum::Router`] that serves this `Aggregator` at `POST /aggregate`.
+        ///
+        /// The endpoint expects an [`OprfRequest`] without a batch and answers with an [`oprf_types::api::AggregatedOprfResponse`]. Requests announcing a `schema` fingerprint (query parameter) different from [`SchemaFingerprint::CURRENT`] are rejected with `400 Bad Request`.
+        ///
+        /// Errors are reported like the delegate node does, so [`super::aggregated_oprf`] can parse them:
+        /// - A [`Error::ThresholdServiceError`] is returned as `400 Bad Request` with the numeric `error_code` as body.
+        /// - Networking, session, epoch-mismatch, and node-disagreement errors are returned as `503 Service Unavailable`.
+        /// - A proof that cannot be verified or disagreeing public keys are returned as `502 Bad Gateway`.
+        /// - Any other error is returned as `500 Internal Server Error`.
+        pub fn routes<OprfRequestAuth>(self) -> Router
+        where
+            OprfRequestAuth: Serialize + for<'de> Deserialize<'de> + Clone + Send + 'static,
+        {
+            Router::new()
+                .route("/aggregate", post(aggregate_handler::<OprfRequestAuth>))
+                .with_state(self)
+        }
+    }
+
+    #[instrument(level = "info", skip_all, fields(request_id = %req.request_id))]
+    async fn aggregate_handler<OprfRequestAuth>(
+        State(aggregator): State<Aggregator>,
+        Query(query): Query<AggregateQuery>,
+        Json(req): Json<OprfRequest<OprfRequestAuth>>,
+    ) -> Response
+    where
+        OprfRequestAuth: Serialize + for<'de> Deserialize<'de> + Clone + Send + 'static,
+    {
+        if let Some(schema) = query.schema
+            && schema != SchemaFingerprint::CURRENT
+        {
+            tracing::warn!(user_error = true, "schema mismatch: {schema}");
+            return (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "invalid schema, expected: {} got: {schema}",
+                    SchemaFingerprint::CURRENT
+                ),
+            )
+                .into_response();
+        }
+        if !req.batch.is_empty() {
+            tracing::warn!(user_error = true, "batch aggregate request");
+            return (
+                StatusCode::BAD_REQUEST,
+                "batch requests are not supported by the aggregator",
+            )
+                .into_response();
+        }
+
+        match aggregator.aggregate(req).await {
+            Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+            Err(Error::ThresholdServiceError(service_error)) => {
+                tracing::warn!(?service_error, "aggregate request failed: {service_error}");
+                (
+                    StatusCode::BAD_REQUEST,
+                    service_error.error_code.to_string(),
+                )
+                    .into_response()
+            }
+            Err(
+                err @ (Error::Networking(_)
+                | Error::CannotFinishSession(_)
+                | Error::EpochMismatch(_)
+                | Error::NodeErrorDisagreement(_)),
+            ) => {
+                tracing::warn!(?err, "aggregate request failed: {err}");
+                StatusCode::SERVICE_UNAVAILABLE.into_response()
+            }
+            Err(err @ (Error::InvalidDLogProof | Error::InconsistentOprfPublicKeys)) => {
+                tracing::error!(?err, "nodes sent invalid results: {err}");
+                StatusCode::BAD_GATEWAY.into_response()
+            }
+            Err(err) => {
+                tracing::error!(?err, "aggregate request failed: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// Executes the OPRF protocol via an [`Aggregator`] over HTTP.
+///
+/// This function:
+/// 1. Blinds the input query using the provided blinding factor.
+/// 2. Sends the blinded query and authentication information to the aggregator via a single HTTP POST request.
+/// 3. Verifies the combined `DLog` equality proof returned by the aggregator.
+/// 4. Unblinds the combined OPRF response using the blinding factor.
+/// 5. Computes the final OPRF output by hashing the original query and the unblinded response.
+///
+/// # Security Considerations
+/// Same as for [`crate::delegate_distributed_oprf`]: if the aggregator is untrusted, verify the returned public key against a trusted source.
+///
+/// # Arguments
+/// - `service`: URL of the `POST /aggregate` endpoint of the aggregator
+/// - `query`: The OPRF input value to evaluate
+/// - `blinding_factor`: The blinding factor used to blind the query
+/// - `domain`: The domain separator used in the final Poseidon hash to derive the output, and the context of the OPRF module if it has one (see [`OutputDomain`])
+/// - `auth`: Implementation specific authentication request forwarded to the OPRF nodes as part of the request
+/// - `client`: The [`reqwest::Client`] used to send the request to the aggregator
+///
+/// # Errors
+/// See the [`Error`] enum for all potential errors of this function.
+#[instrument(level = "debug", skip_all, fields(request_id = tracing::field::Empty))]
+pub async fn aggregated_oprf<OprfRequestAuth>(
+    service: &Url,
+    query: ark_babyjubjub::Fq,
+    blinding_factor: BlindingFactor,
+    domain: impl Into<OutputDomain>,
+    auth: OprfRequestAuth,
+    client: &reqwest::Client,
+) -> Result<VerifiableOprfOutput, Error>
+where
+    OprfRequestAuth: Clone + Serialize + 'static,
+{
+    tracing::trace!("starting aggregated oprf. my version: {}", VERSION);
+    let domain = domain.into();
+
+    let request_id = Uuid::new_v4();
+    tracing::Span::current().record("request_id", request_id.to_string());
+
+    let blinded_request = domain.blind(query, blinding_factor);
+    let oprf_req = OprfRequest {
+        request_id,
+        blinded_query: blinded_request.blinded_query(),
+        auth,
+        issued_at: Some(unix_timestamp()),
+        batch: Vec::new(),
+    };
+
+    let mut service = service.clone();
+    service
+        .query_pairs_mut()
+        .append_pair("schema", &SchemaFingerprint::CURRENT.to_string());
+    let response = client.post(service).json(&oprf_req).send().await?;
+    let AggregatedOprfResponse {
+        blinded_response,
+        dlog_proof,
+        oprf_pub_key_with_epoch,
+    } = delegate_response(response).await?;
+
+    dlog_proof
+        .verify_with_context(
+            oprf_pub_key_with_epoch.key.inner(),
+            blinded_request.blinded_query(),
+            blinded_response,
+            ark_babyjubjub::EdwardsAffine::generator(),
+            domain.context_input(),
+        )
+        .map_err(|_| Error::InvalidDLogProof)?;
+
+    let blinded_response = BlindedOprfResponse::new(blinded_response);
+    let unblinded_response = blinded_response.unblind_response(&blinding_factor.prepare());
+    Ok(VerifiableOprfOutput {
+        output: oprf_output(domain.domain_separator(), query, unblinded_response),
+        blinded_request: blinded_request.blinded_query(),
+        blinded_response: blinded_response.response(),
+        dlog_proof,
+        unblinded_response,
+        oprf_public_key: oprf_pub_key_with_epoch.key,
+        epoch: oprf_pub_key_with_epoch.epoch,
+        context: domain.context_input(),
+    })
+}
@@ -0,0 +1,267 @@
+//! Evaluation of many queries with a single call.
+//!
+//! [`distributed_oprf_batch`] is the entry point for clients that evaluate more than one query against the same committee. Callers only depend on this function, not on how the queries travel over the wire:
+//!
+//! - With a `max_batch_size` of `1` (see [`BatchConfig`]), every query runs its own sessions with one [`oprf_types::api::OprfRequest`] each, exactly as with [`crate::distributed_oprf`]. This works with every node.
+//! - With a larger `max_batch_size`, up to `max_batch_size` queries share a single session per node: the request carries the further queries in its [`oprf_types::api::OprfRequest::batch`], the node answers with one commitment per query and the client sends one challenge per query in an [`oprf_types::api::OprfBatchChallenge`]. The whole batch takes a single round-trip per round.
+//!
+//! Nodes report how many queries they accept per session in the [`oprf_types::api::OprfKeyParams`] (see [`crate::fetch_oprf_key_params`] and [`oprf_types::api::OprfKeyParams::batch_size`]). Nodes that do not report a max batch size only speak single-query sessions.
+
+use futures::stream::{self, StreamExt as _};
+use http::Uri;
+use oprf_core::{
+    ddlog_equality::shamir::DLogCommitmentsShamir,
+    oprf::{BlindedOprfRequest, BlindingFactor},
+};
+use oprf_types::{
+    api::{OprfBatchChallenge, OprfPublicKeyWithEpoch, OprfRequest, OprfResponse},
+    transcript::{FrameDirection, TranscriptMessage},
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    Connector, Error, FinalizeDistributedOprfArgs, OutputDomain, VerifiableOprfOutput,
+    aggregate_error, agreed_oprf_public_key, check_services,
+    observer::Observation,
+    sessions,
+    sessions::{OprfSessions, SessionSource},
+    transcript::TranscriptCapture,
+    unix_timestamp,
+};
+
+/// A single query of a batch, see [`distributed_oprf_batch`].
+#[derive(Debug, Clone)]
+pub struct BatchQuery {
+    /// The OPRF input value to evaluate
+    pub query: ark_babyjubjub::Fq,
+    /// The blinding factor used to blind the query
+    pub blinding_factor: BlindingFactor,
+}
+
+/// Configuration of [`distributed_oprf_batch`].
+#[derive(Debug, Clone, Copy)]
+pub struct BatchConfig {
+    /// Max number of sessions (i.e., single queries or batches) evaluated at the same time, at least 1.
+    pub concurrency: usize,
+    /// Max number of queries per session, at least 1. Must not exceed the `max_batch_size` of any node, see [`oprf_types::api::OprfKeyParams::batch_size`].
+    pub max_batch_size: usize,
+    /// Rejects responses without a [`ShareProof`](oprf_types::api::ShareProof) with [`crate::NodeError::InvalidShareProof`], so that the public shares of the nodes are always checked against the OPRF public key.
+    pub require_share_proofs: bool,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            max_batch_size: 1,
+            require_share_proofs: false,
+        }
+    }
+}
+
+/// Executes the distributed OPRF protocol for every query of `queries`.
+///
+/// Produces the same outputs as calling [`crate::distributed_oprf`] for each query, but evaluates up to `max_batch_size` queries in a single session per node (see the [module docs](self)). The same `auth` is sent for every session.
+///
+/// # Returns
+/// One result per query, in the order of `queries`. A failed session does not abort the others. If a batch session fails, every query of the batch reports [`Error::BatchSessionFailed`].
+///
+/// # Arguments
+/// - `services`: List of WebSocket URIs of the OPRF nodes to contact (must be unique). See the helper functions [`crate::to_oprf_uri`] and [`crate::to_oprf_uri_many`].
+/// - `threshold`: Number of nodes required to complete the protocol
+/// - `queries`: The queries to evaluate, each with its own blinding factor
+/// - `domain`: The domain separator used in the final Poseidon hash to derive the outputs, and the context of the OPRF module if it has one (see [`OutputDomain`])
+/// - `auth`: Implementation specific authentication request forwarded to each OPRF node as part of the requests
+/// - `connector`: TLS connector configuration for the WebSocket connections
+/// - `config`: The concurrency and the max number of queries per session, see [`BatchConfig`]
+///
+/// # Timeout and Cancellation
+///
+/// Same as [`crate::distributed_oprf`]: no timeouts are applied and dropping the returned future drops all in-flight requests.
+#[instrument(level = "debug", skip_all, fields(queries = queries.len()))]
+pub async fn distributed_oprf_batch<OprfRequestAuth>(
+    services: &[Uri],
+    threshold: usize,
+    queries: Vec<BatchQuery>,
+    domain: impl Into<OutputDomain>,
+    auth: OprfRequestAuth,
+    connector: Connector,
+    config: BatchConfig,
+) -> Vec<Result<VerifiableOprfOutput, Error>>
+where
+    OprfRequestAuth: Clone + Serialize + 'static,
+{
+    let domain = domain.into();
+    let source = SessionSource::Connect {
+        connector,
+        require_share_proofs: config.require_share_proofs,
+    };
+    let concurrency = config.concurrency.max(1);
+    if config.max_batch_size <= 1 {
+        return stream::iter(queries)
+            .map(
+                |BatchQuery {
+                     query,
+                     blinding_factor,
+                 }| {
+                    crate::distributed_oprf_from(
+                        &source,
+                        services,
+                        threshold,
+                        query,
+                        blinding_factor,
+                        domain.clone(),
+                        auth.clone(),
+                    )
+                },
+            )
+            .buffered(concurrency)
+            .collect()
+            .await;
+    }
+    stream::iter(queries.chunks(config.max_batch_size))
+        .map(|batch| async {
+            let num_queries = batch.len();
+            match batch_session(&source, services, threshold, batch, &domain, auth.clone()).await {
+                Ok(results) => results,
+                Err(err) => {
+                    let err = Arc::new(err);
+                    (0..num_queries)
+                        .map(|_| Err(Error::BatchSessionFailed(Arc::clone(&err))))
+                        .collect()
+                }
+            }
+        })
+        .buffered(concurrency)
+        .flat_map(stream::iter)
+        .collect()
+        .await
+}
+
+/// Evaluates all queries of `batch` in a single batch session per node.
+///
+/// Returns one result per query if the sessions finished, the finalization of the queries fails independently.
+#[instrument(level = "debug", skip_all, fields(request_id = tracing::field::Empty))]
+async fn batch_session<OprfRequestAuth>(
+    source: &SessionSource,
+    services: &[Uri],
+    threshold: usize,
+    batch: &[BatchQuery],
+    domain: &OutputDomain,
+    auth: OprfRequestAuth,
+) -> Result<Vec<Result<VerifiableOprfOutput, Error>>, Error>
+where
+    OprfRequestAuth: Clone + Serialize + 'static,
+{
+    check_services(services, threshold)?;
+
+    let request_id = Uuid::new_v4();
+    tracing::Span::current().record("request_id", request_id.to_string());
+    tracing::debug!("starting batch of {} queries", batch.len());
+
+    let blinded_requests = batch
+        .iter()
+        .map(|query| domain.blind(query.query, query.blinding_factor))
+        .collect::<Vec<_>>();
+    let mut blinded_queries = blinded_requests
+        .iter()
+        .map(BlindedOprfRequest::blinded_query);
+    let req = OprfRequest {
+        request_id,
+        blinded_query: blinded_queries
+            .next()
+            .expect("batch has at least one query"),
+        auth,
+        issued_at: Some(unix_timestamp()),
+        batch: blinded_queries.collect(),
+    };
+    let mut transcript = TranscriptCapture::start(&req);
+
+    let observation = Observation::start(request_id, services.len(), threshold);
+    let rounds = async {
+        let sessions = sessions::init_sessions_from(source, request_id, services, threshold, req)
+            .await
+            .map_err(|errors| aggregate_error(threshold, errors))?;
+        for (idx, party_id) in sessions.party_ids.iter().enumerate() {
+            transcript.record_response(|| OprfResponse {
+                commitments: sessions.commitments[idx].clone(),
+                party_id: *party_id,
+                oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch {
+                    key: sessions.oprf_public_keys[idx],
+                    epoch: sessions.epoch,
+                },
+                batch_commitments: sessions.batch_commitments[idx].clone(),
+                share_proof: sessions.share_proofs[idx].clone(),
+                signature: sessions.signatures.get(party_id).cloned(),
+            });
+        }
+        let oprf_public_key = agreed_oprf_public_key(&sessions, source.require_share_proofs())?;
+        let epoch = sessions.epoch;
+
+        let challenge = generate_batch_challenge(&sessions, batch.len());
+        let party_ids = sessions.party_ids.clone();
+        for party_id in &party_ids {
+            transcript.record(FrameDirection::Sent, *party_id, || {
+                TranscriptMessage::BatchChallenge(challenge.clone())
+            });
+        }
+        let responses = sessions::finish_batch_sessions(sessions, challenge.clone())
+            .await
+            .map_err(Error::CannotFinishSession)?;
+        for (party_id, proof_shares) in party_ids.iter().zip(&responses) {
+            transcript.record(FrameDirection::Received, *party_id, || {
+                TranscriptMessage::BatchProofShares(proof_shares.clone())
+            });
+        }
+        Ok::<_, Error>((oprf_public_key, epoch, challenge, responses))
+    }
+    .await;
+    observation.end(rounds.as_ref().map(|(_, epoch, _, _)| *epoch));
+    let (oprf_public_key, epoch, challenge, responses) = rounds?;
+
+    Ok(batch
+        .iter()
+        .zip(blinded_requests)
+        .zip(challenge.challenges)
+        .enumerate()
+        .map(|(idx, ((query, blinded_request), challenge))| {
+            crate::finalize_distributed_oprf(FinalizeDistributedOprfArgs {
+                request_id,
+                query: query.query,
+                blinding_factor: query.blinding_factor,
+                domain_separator: domain.domain_separator(),
+                context: domain.context_input(),
+                blinded_request,
+                challenge,
+                responses: responses
+                    .iter()
+                    .map(|response| response.proof_shares[idx].clone())
+                    .collect(),
+                oprf_public_key,
+                epoch,
+            })
+        })
+        .collect())
+}
+
+/// Generates the challenge of every query of a batch session, like [`crate::generate_challenge_request`] does for a single query.
+fn generate_batch_challenge(sessions: &OprfSessions, num_queries: usize) -> OprfBatchChallenge {
+    let contributing_parties = sessions
+        .party_ids
+        .iter()
+        .map(|id| id.into_inner() + 1)
+        .collect::<Vec<_>>();
+    OprfBatchChallenge {
+        challenges: (0..num_queries)
+            .map(|query| {
+                DLogCommitmentsShamir::combine_commitments(
+                    &sessions.commitments_of(query),
+                    contributing_parties.clone(),
+                )
+            })
+            .collect(),
+    }
+}
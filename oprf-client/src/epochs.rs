@@ -0,0 +1,214 @@
+//! Epoch-change notifications pushed by OPRF nodes.
+//!
+//! Nodes expose the web-socket endpoint `/epoch_notifications` (see [`to_epoch_notifications_uri`]). Clients that keep state across requests, e.g. a cached [`ShareEpoch`] per [`OprfKeyId`], subscribe to this endpoint to learn about reshares without waiting for a request to fail.
+//!
+//! [`KnownEpochs`] is a small shared cache that applies received [`EpochChanged`] notifications and drops outdated entries.
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use http::{Uri, uri::InvalidUri};
+use oprf_types::{OprfKeyId, ShareEpoch, api::EpochChanged};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::EpochNotifications;
+
+/// Builds the WebSocket [`Uri`] of the `/epoch_notifications` endpoint for a given service base URL.
+///
+/// Converts the scheme like [`crate::to_oprf_uri`] does.
+///
+/// # Example
+/// ```
+/// # use taceo_oprf_client::to_epoch_notifications_uri;
+/// let uri = to_epoch_notifications_uri("https://example.com/")?;
+/// assert_eq!(uri.to_string(), "wss://example.com/epoch_notifications");
+/// # Ok::<(), http::uri::InvalidUri>(())
+/// ```
+pub fn to_epoch_notifications_uri(service: &str) -> Result<Uri, InvalidUri> {
+    let ws_base = if service.starts_with("http") {
+        service.replacen("http", "ws", 1)
+    } else {
+        service.to_string()
+    };
+    let ws_base = ws_base.trim_end_matches('/');
+    format!("{ws_base}/epoch_notifications").parse::<Uri>()
+}
+
+/// Shared cache of the last known [`ShareEpoch`] per [`OprfKeyId`].
+///
+/// Cloning is cheap and all clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct KnownEpochs(Arc<RwLock<HashMap<OprfKeyId, ShareEpoch>>>);
+
+impl KnownEpochs {
+    /// Creates an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the cached epoch for `oprf_key_id`, if any.
+    #[must_use]
+    pub fn get(&self, oprf_key_id: OprfKeyId) -> Option<ShareEpoch> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&oprf_key_id)
+            .copied()
+    }
+
+    /// Stores the epoch observed for `oprf_key_id`, e.g. from a finished OPRF session.
+    pub fn insert(&self, oprf_key_id: OprfKeyId, epoch: ShareEpoch) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(oprf_key_id, epoch);
+    }
+
+    /// Drops all cached epochs, e.g. because notifications may have been missed.
+    pub fn clear(&self) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Applies a notification received from a node.
+    ///
+    /// If the cached epoch differs from the notified one, the entry is invalidated and `true` is returned. Notifications for unknown keys or for the already cached epoch are ignored.
+    pub fn apply(&self, notification: EpochChanged) -> bool {
+        let mut epochs = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        match epochs.get(&notification.oprf_key_id) {
+            Some(known) if *known != notification.epoch => {
+                tracing::debug!(
+                    "epoch for {} changed from {known} to {} - invalidating",
+                    notification.oprf_key_id,
+                    notification.epoch
+                );
+                epochs.remove(&notification.oprf_key_id);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use futures::StreamExt as _;
+    use http::Uri;
+    use oprf_types::api::EpochChanged;
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{
+        Connector, MaybeTlsStream, WebSocketStream, tungstenite::protocol::frame::coding::CloseCode,
+    };
+
+    use super::KnownEpochs;
+    use crate::{NodeError, ServiceError};
+
+    /// An open subscription to the `/epoch_notifications` endpoint of a single node.
+    pub struct EpochNotifications {
+        inner: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    }
+
+    impl EpochNotifications {
+        /// Subscribes to the epoch notifications of the node at `endpoint` (see [`super::to_epoch_notifications_uri`]).
+        pub async fn subscribe(endpoint: Uri, connector: Connector) -> Result<Self, NodeError> {
+            let (inner, _) = tokio_tungstenite::connect_async_tls_with_config(
+                endpoint,
+                None,
+                false,
+                Some(connector),
+            )
+            .await?;
+            Ok(Self { inner })
+        }
+
+        /// Waits for the next notification.
+        ///
+        /// Returns `None` if the node closed the subscription normally, e.g. because its lifetime elapsed. Callers should reconnect in that case.
+        pub async fn next(&mut self) -> Option<Result<EpochChanged, NodeError>> {
+            loop {
+                let msg = match self.inner.next().await? {
+                    Ok(msg) => msg,
+                    Err(err) => return Some(Err(err.into())),
+                };
+                return match msg {
+                    tokio_tungstenite::tungstenite::Message::Binary(bytes) => {
+                        Some(ciborium::from_reader(bytes.as_ref()).map_err(|_| {
+                            NodeError::UnexpectedMessage {
+                                reason: "could not parse epoch notification",
+                            }
+                        }))
+                    }
+                    tokio_tungstenite::tungstenite::Message::Close(frame) => match frame {
+                        Some(frame) if frame.code != CloseCode::Normal => {
+                            Some(Err(NodeError::ServiceError(ServiceError {
+                                error_code: u16::from(frame.code),
+                                msg: (!frame.reason.is_empty()).then(|| frame.reason.to_string()),
+                                kind: oprf_types::api::OprfErrorKind::from(u16::from(frame.code)),
+                            })))
+                        }
+                        _ => None,
+                    },
+                    tokio_tungstenite::tungstenite::Message::Ping(_)
+                    | tokio_tungstenite::tungstenite::Message::Pong(_) => continue,
+                    _ => Some(Err(NodeError::UnexpectedMessage {
+                        reason: "non-binary frame received",
+                    })),
+                };
+            }
+        }
+
+        /// Applies all received notifications to `known_epochs` until the subscription ends.
+        ///
+        /// Clears `known_epochs` first, as the epochs may have changed before the subscription without a notification, e.g. while the previous subscription was down or lagged behind. Returns `Ok(())` if the node closed the subscription normally. A subscription that lagged behind is closed with [`oprf_types::api::oprf_error_codes::NOTIFICATIONS_LAGGED`] and must be resubscribed.
+        pub async fn apply_to(mut self, known_epochs: &KnownEpochs) -> Result<(), NodeError> {
+            known_epochs.clear();
+            while let Some(notification) = self.next().await {
+                known_epochs.apply(notification?);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_epochs_invalidates_on_change() {
+        let known = KnownEpochs::new();
+        let key_id = OprfKeyId::from(42usize);
+        let epoch = ShareEpoch::default();
+        assert!(
+            !known.apply(EpochChanged {
+                oprf_key_id: key_id,
+                previous: Some(epoch),
+                epoch: epoch.next()
+            }),
+            "unknown keys are ignored"
+        );
+        known.insert(key_id, epoch);
+        assert!(
+            !known.apply(EpochChanged {
+                oprf_key_id: key_id,
+                previous: Some(epoch),
+                epoch
+            }),
+            "same epoch is not a change"
+        );
+        assert_eq!(known.get(key_id), Some(epoch));
+        assert!(
+            known.apply(EpochChanged {
+                oprf_key_id: key_id,
+                previous: Some(epoch),
+                epoch: epoch.next()
+            }),
+            "new epoch invalidates"
+        );
+        assert_eq!(known.get(key_id), None);
+    }
+}
@@ -0,0 +1,378 @@
+//! Failover across more than `threshold` nodes.
+//!
+//! All runs contact every given node and keep the sessions of the first `threshold` nodes that answer. By default, a node that cannot be reached fails its session for the run, and a node that never answers stalls the run until the caller gives up.
+//!
+//! A [`Failover`] bounds every attempt to open a session with a node by a per-node timeout and retries nodes that could not be reached or timed out (see [`FailoverConfig`]), while the other nodes keep running. The run finishes as soon as `threshold` nodes opened a session, pending attempts and retries at slower nodes are dropped. If too few nodes could be reached, the run fails with [`Error::InsufficientHealthyNodes`].
+//!
+//! Nodes that answered with an error (e.g., a failed authentication) are not retried.
+use std::{pin::pin, time::Duration};
+
+use backon::{BackoffBuilder as _, DefaultSleeper, Sleeper as _};
+use futures::future::{Either, select};
+use http::Uri;
+use oprf_types::{api::OprfRequest, retry::RetryPolicy};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    BlindingFactor, Connector, Error, NodeError, OprfSessions, Resolver, VerifiableOprfOutput,
+    sessions::{self, SessionSource},
+};
+
+/// Configuration of a [`Failover`].
+#[derive(Debug, Clone, Copy)]
+pub struct FailoverConfig {
+    /// Max duration of a single attempt to open a session with a node, including the [`oprf_types::api::OprfResponse`] of the node.
+    pub node_timeout: Duration,
+    /// The delays between two attempts at a node that could not be reached or timed out. A node is contacted at most `max_retries + 1` times per run.
+    pub retry_policy: RetryPolicy,
+    /// Rejects responses without a [`ShareProof`](oprf_types::api::ShareProof) with [`NodeError::InvalidShareProof`], so that the public shares of the nodes are always checked against the OPRF public key. Rejected nodes are not retried.
+    pub require_share_proofs: bool,
+}
+
+impl Default for FailoverConfig {
+    /// A timeout of `5 s` per attempt, 2 retries starting at `250 ms`, share proofs are not required.
+    fn default() -> Self {
+        Self {
+            node_timeout: Duration::from_secs(5),
+            retry_policy: RetryPolicy::new(2, Duration::from_millis(250)),
+            require_share_proofs: false,
+        }
+    }
+}
+
+/// Opens sessions with failover, see the [module docs](self).
+#[derive(Clone)]
+pub struct Failover {
+    pub(crate) connector: Connector,
+    pub(crate) resolver: Option<Resolver>,
+    pub(crate) config: FailoverConfig,
+}
+
+impl Failover {
+    /// Creates a `Failover` that connects to the nodes with the given [`Connector`].
+    #[must_use]
+    pub fn new(connector: Connector, config: FailoverConfig) -> Self {
+        Self {
+            connector,
+            resolver: None,
+            config,
+        }
+    }
+
+    /// Resolves the hosts of the nodes with the given [`Resolver`] instead of the system DNS.
+    #[must_use]
+    pub fn with_resolver(mut self, resolver: Resolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Executes the distributed OPRF protocol like [`crate::distributed_oprf`], but opens the sessions with failover.
+    ///
+    /// `services` may contain more than `threshold` nodes, the protocol is completed with the first `threshold` nodes that opened a session.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::distributed_oprf`]. Returns [`Error::InsufficientHealthyNodes`] if fewer than `threshold` nodes could be reached.
+    pub async fn distributed_oprf<OprfRequestAuth>(
+        &self,
+        services: &[Uri],
+        threshold: usize,
+        query: ark_babyjubjub::Fq,
+        blinding_factor: BlindingFactor,
+        domain: impl Into<crate::OutputDomain>,
+        auth: OprfRequestAuth,
+    ) -> Result<VerifiableOprfOutput, Error>
+    where
+        OprfRequestAuth: Clone + Serialize + 'static,
+    {
+        crate::distributed_oprf_from(
+            &SessionSource::Failover(self.clone()),
+            services,
+            threshold,
+            query,
+            blinding_factor,
+            domain.into(),
+            auth,
+        )
+        .await
+    }
+
+    /// Like [`crate::init_sessions`], but opens the sessions with failover.
+    ///
+    /// On failure, returns the last error of every node. Timed out attempts are reported as [`NodeError::Timeout`].
+    pub async fn init_sessions<OprfRequestAuth: Clone + Serialize + 'static>(
+        &self,
+        request_id: Uuid,
+        oprf_services: &[Uri],
+        threshold: usize,
+        req: OprfRequest<OprfRequestAuth>,
+    ) -> Result<OprfSessions, Vec<NodeError>> {
+        sessions::init_sessions_from(
+            &SessionSource::Failover(self.clone()),
+            request_id,
+            oprf_services,
+            threshold,
+            req,
+        )
+        .await
+    }
+}
+
+/// Calls `attempt` until it succeeds, fails with an error other than [`NodeError::WsError`] or [`NodeError::Timeout`], or the retries of `config` are exhausted. Every call is bounded by the `node_timeout` of `config`.
+pub(crate) async fn with_failover<T, F, Fut>(
+    config: &FailoverConfig,
+    service: &Uri,
+    mut attempt: F,
+) -> Result<T, NodeError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, NodeError>>,
+{
+    let mut delays = config.retry_policy.backoff().build();
+    loop {
+        let timeout = DefaultSleeper::default().sleep(config.node_timeout);
+        let result = match select(pin!(attempt()), pin!(timeout)).await {
+            Either::Left((result, _)) => result,
+            Either::Right(((), _)) => Err(NodeError::Timeout(config.node_timeout)),
+        };
+        match result {
+            Err(err @ (NodeError::WsError(_) | NodeError::Timeout(_))) => {
+                let Some(delay) = delays.next() else {
+                    return Err(err);
+                };
+                tracing::debug!(%err, "cannot open session at {service} - retrying in {delay:?}");
+                DefaultSleeper::default().sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use ark_ff::UniformRand as _;
+    use axum::{
+        Router,
+        extract::{
+            State, WebSocketUpgrade,
+            ws::{Message, WebSocket},
+        },
+        response::IntoResponse,
+        routing::any,
+    };
+    use axum_test::TestServer;
+    use oprf_core::ddlog_equality::shamir::{DLogSessionShamir, DLogShareShamir};
+    use oprf_types::{
+        ShareEpoch,
+        api::{OprfPublicKeyWithEpoch, OprfResponse},
+        crypto::{OprfPublicKey, PartyId},
+    };
+
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    /// A node with the given party ID that does not answer the first `hanging` sessions.
+    struct MockNode {
+        party_id: u16,
+        hanging: usize,
+        sessions: AtomicUsize,
+    }
+
+    async fn handle(node: Arc<MockNode>, mut socket: WebSocket) {
+        let _ = socket.recv().await;
+        if node.sessions.fetch_add(1, Ordering::Relaxed) < node.hanging {
+            std::future::pending::<()>().await;
+        }
+        let response = OprfResponse {
+            commitments: DLogSessionShamir::partial_commitments(
+                rand::random(),
+                DLogShareShamir::from(ark_babyjubjub::Fr::rand(&mut rand::thread_rng())),
+                &mut rand::thread_rng(),
+            )
+            .1,
+            party_id: PartyId::from(node.party_id),
+            oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch {
+                key: OprfPublicKey::new(ark_babyjubjub::EdwardsAffine::default()),
+                epoch: ShareEpoch::default(),
+            },
+            batch_commitments: Vec::new(),
+            share_proof: None,
+            signature: None,
+        };
+        let mut buf = Vec::new();
+        ciborium::into_writer(&response, &mut buf).expect("Can serialize response");
+        socket
+            .send(Message::Binary(buf.into()))
+            .await
+            .expect("Can send response");
+        // keep the session open until the client closes it
+        let _ = socket.recv().await;
+    }
+
+    fn mock_node(party_id: u16, hanging: usize) -> (TestServer, Arc<MockNode>, Uri) {
+        let node = Arc::new(MockNode {
+            party_id,
+            hanging,
+            sessions: AtomicUsize::new(0),
+        });
+        let router = Router::new()
+            .route(
+                "/api/test/oprf",
+                any(
+                    |State(node): State<Arc<MockNode>>, ws: WebSocketUpgrade| async move {
+                        ws.on_upgrade(move |socket| handle(node, socket))
+                            .into_response()
+                    },
+                ),
+            )
+            .with_state(Arc::clone(&node));
+        let server = TestServer::builder()
+            .http_transport()
+            .build(router)
+            .expect("Can build test-server");
+        let uri = crate::to_oprf_uri(
+            server.server_address().expect("Has address").as_str(),
+            "test",
+        )
+        .expect("Is valid URI");
+        (server, node, uri)
+    }
+
+    fn failover(max_retries: usize) -> Failover {
+        Failover::new(
+            Connector::Plain,
+            FailoverConfig {
+                node_timeout: Duration::from_millis(200),
+                retry_policy: RetryPolicy::constant(max_retries, Duration::from_millis(10)),
+                require_share_proofs: false,
+            },
+        )
+    }
+
+    fn request() -> OprfRequest<()> {
+        OprfRequest {
+            request_id: Uuid::new_v4(),
+            blinded_query: ark_babyjubjub::EdwardsAffine::default(),
+            auth: (),
+            issued_at: None,
+            batch: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_timed_out_node() {
+        let (_server0, _, uri0) = mock_node(0, 0);
+        let (_server1, node1, uri1) = mock_node(1, 1);
+        let req = request();
+        let sessions = failover(1)
+            .init_sessions(req.request_id, &[uri0, uri1], 2, req)
+            .await
+            .expect("second attempt at node 1 succeeds");
+        assert_eq!(sessions.party_ids, [PartyId::from(0), PartyId::from(1)]);
+        assert_eq!(
+            node1.sessions.load(Ordering::Relaxed),
+            2,
+            "retried node 1 once"
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_first_threshold_sessions() {
+        let (_server0, _, uri0) = mock_node(0, 0);
+        let (_server1, _, uri1) = mock_node(1, usize::MAX);
+        let (_server2, _, uri2) = mock_node(2, 0);
+        let req = request();
+        let sessions = failover(0)
+            .init_sessions(req.request_id, &[uri0, uri1, uri2], 2, req)
+            .await
+            .expect("two nodes answer");
+        assert_eq!(sessions.party_ids, [PartyId::from(0), PartyId::from(2)]);
+    }
+
+    #[tokio::test]
+    async fn insufficient_healthy_nodes() {
+        let (_server0, _, uri0) = mock_node(0, 0);
+        let (_server1, node1, uri1) = mock_node(1, usize::MAX);
+        let unreachable: Uri = "ws://127.0.0.1:1/api/test/oprf"
+            .parse()
+            .expect("Is valid URI");
+        let err = failover(2)
+            .distributed_oprf(
+                &[uri0, uri1, unreachable],
+                2,
+                ark_babyjubjub::Fq::from(42),
+                BlindingFactor::rand(&mut rand::thread_rng()),
+                ark_babyjubjub::Fq::from(1),
+                (),
+            )
+            .await
+            .expect_err("only one node is healthy");
+        assert!(
+            matches!(
+                err,
+                Error::InsufficientHealthyNodes {
+                    healthy: 1,
+                    threshold: 2
+                }
+            ),
+            "got {err:?}"
+        );
+        assert_eq!(
+            node1.sessions.load(Ordering::Relaxed),
+            3,
+            "contacted node 1 max_retries + 1 times"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolver_replaces_system_dns() {
+        let (_server0, _, uri0) = mock_node(0, 0);
+        let (_server1, _, uri1) = mock_node(1, 0);
+        let addrs = [uri0, uri1]
+            .iter()
+            .map(|uri| {
+                let port = uri.port_u16().expect("Has port");
+                (port, SocketAddr::from(([127, 0, 0, 1], port)))
+            })
+            .collect::<Vec<_>>();
+        let services = addrs
+            .iter()
+            .map(|(port, _)| {
+                format!("ws://node.oprf.invalid:{port}/api/test/oprf")
+                    .parse()
+                    .expect("Is valid URI")
+            })
+            .collect::<Vec<Uri>>();
+        let resolver = Resolver::new(move |host: &str, port: u16| {
+            assert_eq!(host, "node.oprf.invalid", "resolves the host of the URI");
+            addrs
+                .iter()
+                .filter(|(addr_port, _)| *addr_port == port)
+                .map(|(_, addr)| Ok(*addr))
+                .collect()
+        });
+
+        let req = request();
+        assert!(
+            failover(0)
+                .init_sessions(req.request_id, &services, 2, req)
+                .await
+                .is_err(),
+            "system DNS cannot resolve the nodes"
+        );
+        let req = request();
+        let sessions = failover(0)
+            .with_resolver(resolver)
+            .init_sessions(req.request_id, &services, 2, req)
+            .await
+            .expect("resolver resolves the nodes");
+        assert_eq!(sessions.party_ids, [PartyId::from(0), PartyId::from(1)]);
+    }
+}
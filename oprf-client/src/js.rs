@@ -0,0 +1,272 @@
+//! JavaScript bindings of the client (requires the `wasm-bindgen` feature).
+//!
+//! Exposes the distributed OPRF protocol to JavaScript when compiled to `wasm32` (e.g., with `wasm-pack build --features wasm-bindgen`). The protocol is split into the same steps as [`crate::distributed_oprf`], so a browser integration can run each step on its own:
+//! 1. [`blind`] blinds the query with a random blinding factor, bound to the context of the OPRF module if it has one.
+//! 2. [`init`] sends the blinded query to the nodes and collects their commitments.
+//! 3. [`finish`] sends the challenge to the nodes and collects their proof shares.
+//! 4. [`verify`] verifies the combined `DLog` equality proof, unblinds the response and derives the output.
+//!
+//! [`distributed_oprf`] runs all steps at once. Every step consumes the value returned by the previous one.
+//!
+//! Field elements are passed as decimal strings, curve points as `[x, y]` arrays of decimal strings. The `auth` of [`init`] is any JSON-serializable value and is forwarded to the nodes like the `auth` of [`crate::distributed_oprf`].
+//!
+//! The bindings also compile on other targets, but calling them outside of a JavaScript runtime panics.
+use http::Uri;
+use oprf_core::{
+    ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir},
+    oprf::{BlindedOprfRequest, BlindingFactor},
+};
+use oprf_types::{ShareEpoch, api::OprfRequest, crypto::OprfPublicKey};
+use uuid::Uuid;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    Connector, Error, FinalizeDistributedOprfArgs, OprfSessions, OutputDomain,
+    VerifiableOprfOutput, aggregate_error, agreed_oprf_public_key, check_services,
+    finalize_distributed_oprf, generate_challenge_request,
+    sessions::{self, SessionSource},
+    unix_timestamp,
+};
+
+/// A query blinded by [`blind`].
+#[wasm_bindgen]
+pub struct BlindedQuery {
+    query: ark_babyjubjub::Fq,
+    blinding_factor: BlindingFactor,
+    domain: OutputDomain,
+    blinded_request: BlindedOprfRequest,
+}
+
+#[wasm_bindgen]
+impl BlindedQuery {
+    /// The blinded query that is sent to the nodes.
+    #[wasm_bindgen(getter, js_name = blindedQuery)]
+    #[must_use]
+    pub fn blinded_query(&self) -> Vec<String> {
+        point_to_js(self.blinded_request.blinded_query())
+    }
+}
+
+/// The open sessions with the nodes returned by [`init`].
+#[wasm_bindgen]
+pub struct Sessions {
+    request_id: Uuid,
+    blinded: BlindedQuery,
+    inner: OprfSessions,
+    oprf_public_key: OprfPublicKey,
+}
+
+#[wasm_bindgen]
+impl Sessions {
+    /// The id of the OPRF request.
+    #[wasm_bindgen(getter, js_name = requestId)]
+    #[must_use]
+    pub fn request_id(&self) -> String {
+        self.request_id.to_string()
+    }
+
+    /// The epoch of the key the nodes agreed on.
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn epoch(&self) -> u32 {
+        self.inner.epoch.into_inner()
+    }
+}
+
+/// The proof shares of the nodes returned by [`finish`].
+#[wasm_bindgen]
+pub struct ProofShares {
+    request_id: Uuid,
+    blinded: BlindedQuery,
+    challenge: DLogCommitmentsShamir,
+    responses: Vec<DLogProofShareShamir>,
+    oprf_public_key: OprfPublicKey,
+    epoch: ShareEpoch,
+}
+
+/// The verified result of the protocol returned by [`verify`], see [`VerifiableOprfOutput`].
+#[wasm_bindgen]
+pub struct OprfOutput(VerifiableOprfOutput);
+
+#[wasm_bindgen]
+impl OprfOutput {
+    /// The OPRF output.
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn output(&self) -> String {
+        self.0.output.to_string()
+    }
+
+    /// The epoch of the key that was used.
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn epoch(&self) -> u32 {
+        self.0.epoch.into_inner()
+    }
+
+    /// The public key of the OPRF key that was used.
+    #[wasm_bindgen(getter, js_name = oprfPublicKey)]
+    #[must_use]
+    pub fn oprf_public_key(&self) -> Vec<String> {
+        point_to_js(self.0.oprf_public_key.inner())
+    }
+
+    /// The unblinded OPRF response.
+    #[wasm_bindgen(getter, js_name = unblindedResponse)]
+    #[must_use]
+    pub fn unblinded_response(&self) -> Vec<String> {
+        point_to_js(self.0.unblinded_response)
+    }
+}
+
+/// Blinds `query` with a random blinding factor.
+///
+/// The `context` must be set to the context of the OPRF module if it has one, see [`OutputDomain`].
+#[wasm_bindgen]
+pub fn blind(query: &str, context: Option<String>) -> Result<BlindedQuery, JsError> {
+    let query = field_from_js("query", query)?;
+    let mut domain = OutputDomain::default();
+    if let Some(context) = context {
+        domain = domain.with_context(context);
+    }
+    let blinding_factor = BlindingFactor::rand(&mut rand::thread_rng());
+    let blinded_request = domain.blind(query, blinding_factor);
+    Ok(BlindedQuery {
+        query,
+        blinding_factor,
+        domain,
+        blinded_request,
+    })
+}
+
+/// Sends the blinded query to the nodes at `services` (WebSocket URIs, see [`crate::to_oprf_uri`]) and collects the commitments of `threshold` nodes.
+#[wasm_bindgen]
+pub async fn init(
+    services: Vec<String>,
+    threshold: usize,
+    blinded: BlindedQuery,
+    auth: JsValue,
+) -> Result<Sessions, JsError> {
+    let services = services
+        .iter()
+        .map(|service| service.parse::<Uri>())
+        .collect::<Result<Vec<_>, _>>()?;
+    check_services(&services, threshold)?;
+    let auth = js_sys::JSON::stringify(&auth)
+        .map_err(|_| JsError::new("auth is not JSON-serializable"))?;
+    let auth = serde_json::from_str::<serde_json::Value>(&String::from(auth))?;
+
+    let request_id = Uuid::new_v4();
+    let req = OprfRequest {
+        request_id,
+        blinded_query: blinded.blinded_request.blinded_query(),
+        auth,
+        issued_at: Some(unix_timestamp()),
+        batch: Vec::new(),
+    };
+    let sessions = sessions::init_sessions_from(
+        &SessionSource::connect(connector()),
+        request_id,
+        &services,
+        threshold,
+        req,
+    )
+    .await
+    .map_err(|errors| aggregate_error(threshold, errors))?;
+    let oprf_public_key = agreed_oprf_public_key(&sessions, false)?;
+    Ok(Sessions {
+        request_id,
+        blinded,
+        inner: sessions,
+        oprf_public_key,
+    })
+}
+
+/// Sends the challenge to the nodes of `sessions` and collects their proof shares.
+#[wasm_bindgen]
+pub async fn finish(sessions: Sessions) -> Result<ProofShares, JsError> {
+    let Sessions {
+        request_id,
+        blinded,
+        inner: sessions,
+        oprf_public_key,
+    } = sessions;
+    let epoch = sessions.epoch;
+    let challenge = generate_challenge_request(&sessions);
+    let responses = sessions::finish_sessions(sessions, challenge.clone())
+        .await
+        .map_err(Error::CannotFinishSession)?;
+    Ok(ProofShares {
+        request_id,
+        blinded,
+        challenge,
+        responses,
+        oprf_public_key,
+        epoch,
+    })
+}
+
+/// Verifies the combined proof of `shares`, unblinds the response and derives the output with `domain_separator`, see [`finalize_distributed_oprf`].
+#[wasm_bindgen]
+pub fn verify(shares: ProofShares, domain_separator: &str) -> Result<OprfOutput, JsError> {
+    let domain_separator = field_from_js("domain separator", domain_separator)?;
+    let ProofShares {
+        request_id,
+        blinded,
+        challenge,
+        responses,
+        oprf_public_key,
+        epoch,
+    } = shares;
+    let output = finalize_distributed_oprf(FinalizeDistributedOprfArgs {
+        request_id,
+        query: blinded.query,
+        blinding_factor: blinded.blinding_factor,
+        domain_separator,
+        context: blinded.domain.context_input(),
+        blinded_request: blinded.blinded_request,
+        challenge,
+        responses,
+        oprf_public_key,
+        epoch,
+    })?;
+    Ok(OprfOutput(output))
+}
+
+/// Runs all steps of the protocol, see the [module docs](self).
+#[wasm_bindgen(js_name = distributedOprf)]
+pub async fn distributed_oprf(
+    services: Vec<String>,
+    threshold: usize,
+    query: String,
+    domain_separator: String,
+    context: Option<String>,
+    auth: JsValue,
+) -> Result<OprfOutput, JsError> {
+    let blinded = blind(&query, context)?;
+    let sessions = init(services, threshold, blinded, auth).await?;
+    let shares = finish(sessions).await?;
+    verify(shares, &domain_separator)
+}
+
+/// The connector of the WebSocket connections. Browsers configure TLS themselves.
+fn connector() -> Connector {
+    #[cfg(target_arch = "wasm32")]
+    {
+        Connector
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Connector::Plain
+    }
+}
+
+fn field_from_js(name: &str, value: &str) -> Result<ark_babyjubjub::Fq, JsError> {
+    value
+        .parse()
+        .map_err(|()| JsError::new(&format!("{name} is not a decimal field element")))
+}
+
+fn point_to_js(point: ark_babyjubjub::EdwardsAffine) -> Vec<String> {
+    vec![point.x.to_string(), point.y.to_string()]
+}
@@ -23,39 +23,84 @@
 //! This crate provides utility functions for clients of the distributed OPRF protocol.
 //!
 //! Most implementations will only need the [`distributed_oprf`] method, or [`delegate_distributed_oprf`] if a single
-//! delegate node should perform the distributed OPRF protocol on the client's behalf. For more
-//! fine-grained workflows, we expose all necessary functions.
+//! delegate node should perform the distributed OPRF protocol on the client's behalf. Clients evaluating
+//! many queries should use [`distributed_oprf_batch`], which keeps working as nodes move to batch framing.
+//! Thin clients that leave the whole protocol (including the verification of the proof) to a gateway use an [`aggregator`].
+//! To keep working while some nodes are down or slow, contact more than `threshold` nodes with a [`Failover`].
+//! Nodes can bind an OPRF module to a context, which the client then blinds its queries with and verifies the proofs against as part of the [`OutputDomain`].
+//! Deployments with their own service discovery (e.g. Kubernetes-internal DNS) can resolve the hosts of the nodes with a [`Resolver`] instead of the system DNS (see [`resolver`]).
+//! Host applications without a `tracing` subscriber can receive structured events of every run with an [`observer::ClientObserver`].
+//! Requests are sent as binary `cbor` frames on native and `wasm` targets alike, which is smaller and cheaper to parse than the JSON text frames the nodes also accept, and responses are expected as binary `cbor` frames. Enable the `canonical-cbor` feature to encode them canonically (sorted keys, definite lengths), e.g., if the nodes hash or sign requests or require canonical requests.
+//! Nodes can sign their responses with their wallet key. Enable the `signed-responses` feature to check the signatures against the registered wallet addresses with `OprfSessions::verify_signatures` and `verify_response_signature`.
+//! Browser integrations can use the JavaScript bindings of the `wasm-bindgen` feature (see the `js` module).
+//! For more fine-grained workflows, we expose all necessary functions.
 use core::fmt;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use ark_ec::AffineRepr as _;
+use backon::Retryable as _;
 use futures::stream::{FuturesUnordered, StreamExt as _};
 use oprf_core::{
-    ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir},
+    ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir, combine_public_shares},
     dlog_equality::DLogEqualityProof,
-    oprf::{BlindedOprfRequest, BlindedOprfResponse, BlindingFactor},
+    oprf::{BlindedOprfRequest, BlindedOprfResponse, BlindingFactor, QueryContext},
 };
 use oprf_types::{
     OprfKeyId, ShareEpoch,
-    api::{DelegateOprfResponse, OprfErrorKind, OprfPublicKeyWithEpoch, OprfRequest},
-    crypto::OprfPublicKey,
+    api::{
+        Committee, DelegateOprfResponse, OprfErrorKind, OprfKeyParams, OprfPublicKeyWithEpoch,
+        OprfRequest, OprfResponse, SchemaFingerprint,
+    },
+    crypto::{OprfPublicKey, PartyId},
+    transcript::{FrameDirection, TranscriptMessage},
 };
-use serde::Serialize;
+use serde::{Serialize, de::DeserializeOwned};
 use tracing::instrument;
 use url::Url;
 use uuid::Uuid;
 
+pub mod aggregator;
+mod batch;
+mod epochs;
+mod failover;
+#[cfg(feature = "wasm-bindgen")]
+pub mod js;
+pub mod observer;
+#[cfg(not(target_arch = "wasm32"))]
+mod pool;
+#[cfg(not(target_arch = "wasm32"))]
+mod readiness;
+pub mod resolver;
 mod sessions;
+pub mod transcript;
 mod ws;
 
 /// The version of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+pub use batch::{BatchConfig, BatchQuery, distributed_oprf_batch};
+#[cfg(not(target_arch = "wasm32"))]
+pub use epochs::EpochNotifications;
+pub use epochs::{KnownEpochs, to_epoch_notifications_uri};
+pub use failover::{Failover, FailoverConfig};
 pub use http::Uri;
 pub use http::uri::InvalidUri;
+pub use oprf_types::api::{oprf_error_codes, oprf_error_messages};
+pub use oprf_types::retry::RetryPolicy;
+#[cfg(not(target_arch = "wasm32"))]
+pub use pool::{SessionPool, SessionPoolConfig};
+#[cfg(not(target_arch = "wasm32"))]
+pub use readiness::{EpochReadiness, await_epoch};
+pub use resolver::{Resolve, Resolver};
 pub use sessions::OprfSessions;
+use sessions::SessionSource;
 pub use sessions::finish_sessions;
 pub use sessions::init_sessions;
+#[cfg(feature = "signed-responses")]
+pub use sessions::verify_response_signature;
 
 /// WebSocket connector configuration for native targets.
 ///
@@ -76,6 +121,29 @@ pub use tokio_tungstenite::Connector;
 #[derive(Debug, Clone)]
 pub struct Connector;
 
+/// Returns the current unix time in seconds, as used for [`OprfRequest::issued_at`].
+///
+/// [`distributed_oprf`] and [`delegate_distributed_oprf`] populate the timestamp automatically. Use this method if you build the [`OprfRequest`] yourself.
+#[must_use]
+pub fn unix_timestamp() -> u64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs())
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        #[expect(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "milliseconds since epoch are positive and fit into u64"
+        )]
+        let secs = (js_sys::Date::now() / 1000.0) as u64;
+        secs
+    }
+}
+
 /// Builds a WebSocket OPRF [`Uri`] for a given service base URL and authentication module.
 ///
 /// This function:
@@ -100,6 +168,9 @@ pub struct Connector;
 /// # use taceo_oprf_client::to_oprf_uri;
 /// let uri = to_oprf_uri("https://example.com", "issuer")?;
 /// assert_eq!(uri.to_string(), "wss://example.com/api/issuer/oprf");
+/// // a node mounted below a path prefix by a reverse proxy
+/// let uri = to_oprf_uri("https://example.com/oprf-node/", "issuer")?;
+/// assert_eq!(uri.to_string(), "wss://example.com/oprf-node/api/issuer/oprf");
 /// # Ok::<(), InvalidUri>(())
 /// ```
 pub fn to_oprf_uri<Auth: fmt::Display>(service: &str, auth: Auth) -> Result<Uri, InvalidUri> {
@@ -256,6 +327,95 @@ where
         .collect()
 }
 
+/// Builds the OPRF key-parameters info endpoint [`Url`].
+///
+/// Builds the URL for the endpoint exposing the [`OprfKeyParams`].
+/// - Normalizes trailing slashes
+/// - Appends `/oprf_params`
+///
+/// # Arguments
+/// - `service`: Base URL of the service (e.g., `"https://example.com"`)
+///
+/// # Errors
+/// Returns `url::ParseError` when it is not possible to convert to [`Url`].
+///
+/// # Example
+/// ```
+/// # use url::ParseError;
+/// # use taceo_oprf_client::to_oprf_params_url;
+/// let url = to_oprf_params_url("https://example.com/")?;
+/// assert_eq!(url.to_string(), "https://example.com/oprf_params");
+/// # Ok::<(), ParseError>(())
+/// ```
+pub fn to_oprf_params_url(service: &str) -> Result<Url, url::ParseError> {
+    // Remove trailing slash if any
+    let http_base = service.trim_end_matches('/');
+
+    let uri_str = format!("{http_base}/oprf_params");
+    uri_str.parse::<Url>()
+}
+
+/// Builds the OPRF key-parameters info endpoint [`Url`]s for multiple services.
+///
+/// Calls [`to_oprf_params_url`] for each service and collects the results.
+///
+/// # Errors
+/// Returns `url::ParseError` when one of the service cannot be converted to URL.
+pub fn to_oprf_params_url_many<S, I>(services: I) -> Result<Vec<Url>, url::ParseError>
+where
+    S: AsRef<str>,
+    I: IntoIterator<Item = S>,
+{
+    services
+        .into_iter()
+        .map(|s| to_oprf_params_url(s.as_ref()))
+        .collect()
+}
+
+/// Builds the committee endpoint [`Url`] from a base service URL.
+///
+/// - Normalizes trailing slashes
+/// - Appends `/committee`
+///
+/// # Arguments
+/// - `service`: Base URL of the service (e.g., `"https://example.com"`)
+///
+/// # Errors
+/// Returns `url::ParseError` when it is not possible to convert to [`Url`].
+///
+/// # Example
+/// ```
+/// # use url::ParseError;
+/// # use taceo_oprf_client::to_committee_url;
+/// let url = to_committee_url("https://example.com/")?;
+/// assert_eq!(url.to_string(), "https://example.com/committee");
+/// # Ok::<(), ParseError>(())
+/// ```
+pub fn to_committee_url(service: &str) -> Result<Url, url::ParseError> {
+    // Remove trailing slash if any
+    let http_base = service.trim_end_matches('/');
+
+    let uri_str = format!("{http_base}/committee");
+    uri_str.parse::<Url>()
+}
+
+/// Builds the committee endpoint [`Url`]s for multiple services.
+///
+/// Calls [`to_committee_url`] for each service and collects the results.
+///
+/// # Errors
+/// Returns `url::ParseError` when one of the service cannot be converted to URL.
+pub fn to_committee_url_many<S, I>(services: I) -> Result<Vec<Url>, url::ParseError>
+where
+    S: AsRef<str>,
+    I: IntoIterator<Item = S>,
+{
+    services
+        .into_iter()
+        .map(|s| to_committee_url(s.as_ref()))
+        .collect()
+}
+
 /// The error of a single node.
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -277,6 +437,15 @@ pub enum NodeError {
     /// This node sent back the wrapped epoch.
     #[error("ShareEpoch mismatch - got epoch: {0}")]
     EpochMismatch(ShareEpoch),
+    /// The node sent a [`ShareProof`](oprf_types::api::ShareProof) that does not prove its commitments.
+    #[error("Node sent an invalid share proof")]
+    InvalidShareProof,
+    /// The response of the node with the wrapped party id is not signed by the wallet registered for the party id (see [`OprfSessions::verify_signatures`]).
+    #[error("Response of party {0} is not signed by its registered wallet")]
+    InvalidResponseSignature(PartyId),
+    /// The node did not answer within the per-node timeout of a [`Failover`].
+    #[error("Node did not answer within {0:?}")]
+    Timeout(std::time::Duration),
     /// Represents an unknown or unexpected error.
     ///
     /// Primarily included for forward compatibility and future-proofing.
@@ -292,6 +461,11 @@ impl PartialEq for NodeError {
                 lhs == rhs
             }
             (Self::EpochMismatch(lhs), Self::EpochMismatch(rhs)) => lhs == rhs,
+            (Self::Timeout(lhs), Self::Timeout(rhs)) => lhs == rhs,
+            (Self::InvalidShareProof, Self::InvalidShareProof) => true,
+            (Self::InvalidResponseSignature(lhs), Self::InvalidResponseSignature(rhs)) => {
+                lhs == rhs
+            }
             _ => false,
         }
     }
@@ -305,6 +479,8 @@ pub struct ServiceError {
     /// The `close_code` recorded from the close frame.
     pub error_code: u16,
     /// An optional message recorded from the close frame. Intended for debugging and not to show to the user.
+    ///
+    /// Nodes send one of the [`oprf_error_messages`], unless they are configured to send detailed errors.
     pub msg: Option<String>,
     /// The [`OprfErrorKind`] classification derived from the WebSocket close code.
     /// Use this for programmatic error handling instead of matching on raw `error_code`.
@@ -356,12 +532,38 @@ pub enum Error {
     /// OPRF nodes returned different public keys
     #[error("OPRF nodes returned different public keys")]
     InconsistentOprfPublicKeys,
+    /// The public shares of the [`ShareProof`](oprf_types::api::ShareProof)s of the chosen nodes do not combine to the OPRF public key, or share proofs are required but missing
+    #[error("Public shares of the nodes do not combine to the OPRF public key")]
+    InconsistentPublicShares,
+    /// OPRF nodes returned different committees
+    #[error("OPRF nodes returned different committees")]
+    InconsistentCommittee,
+    /// The committee bundle is not signed by the trusted admin or inconsistent
+    #[cfg(feature = "bundle")]
+    #[error(transparent)]
+    InvalidBundle(#[from] oprf_types::bundle::BundleError),
+    /// A node uses a wallet address that is not registered in the committee
+    #[error("Node {service} uses wallet {wallet}, which is not registered in the committee")]
+    UnregisteredNode {
+        /// The base URL of the node
+        service: String,
+        /// The wallet address the node reported
+        wallet: String,
+    },
     /// Threshold many OPRF nodes sent back this [`ServiceError`].
     #[error("Threshold nodes sent back error: {0}")]
     ThresholdServiceError(ServiceError),
     /// Unable to reach threshold many nodes due to networking issues
     #[error("Unable to reach threshold many nodes due to networking issues")]
     Networking(Vec<Box<dyn core::error::Error + Send + Sync + 'static>>),
+    /// Too many nodes could not be reached or did not answer in time, so that fewer than `threshold` nodes remained to complete the protocol.
+    #[error("Only {healthy} nodes are healthy, but threshold is {threshold}")]
+    InsufficientHealthyNodes {
+        /// The number of nodes that answered (with a session or an error)
+        healthy: usize,
+        /// The required threshold
+        threshold: usize,
+    },
     /// Threshold many OPRF nodes sent an unexpected message. This most likely indicates a client version problem
     #[error("Received an unexpected message from threshold many nodes: {reason}")]
     UnexpectedMessage {
@@ -371,6 +573,9 @@ pub enum Error {
     /// One of the OPRF nodes returned an error during finalize (2nd round of the protocol).
     #[error("One of the nodes failed during finalize: {0}")]
     CannotFinishSession(#[source] NodeError),
+    /// The batch session that evaluated this query failed, see [`distributed_oprf_batch`]. Every query of the batch session reports the same error.
+    #[error("Batch session failed: {0}")]
+    BatchSessionFailed(#[source] Arc<Error>),
     /// Represents a disagreement between nodes: no error reached the required threshold for consensus.
     ///
     /// The order of errors in the contained `Vec<NodeError>` does **not** reflect the order of URIs passed to [`distributed_oprf`].
@@ -403,12 +608,14 @@ pub enum Error {
 /// - If `threshold` nodes returned the same `UnexpectedMessage`, returns that consensus.
 /// - If `threshold` nodes returned `WsError`s, collects them into a networking error.
 /// - If `threshold` nodes returned `EpochMismatch`, we return `EpochMismatch` containing all reported epochs.
+/// - If nodes returned `WsError`s or `Timeout`s and fewer than `threshold` nodes remain, returns `InsufficientHealthyNodes`.
 /// - Otherwise, returns `NodeErrorDisagreement`.
 ///
 /// Internal use only.
 fn aggregate_error(threshold: usize, errors: Vec<NodeError>) -> Error {
     let mut service_errors = HashMap::new();
     let mut ws_errors_counters = 0;
+    let mut timeouts = 0;
     let mut unexpected_message = HashMap::new();
     let mut epoch_mismatches = Vec::with_capacity(errors.len());
 
@@ -435,6 +642,9 @@ fn aggregate_error(threshold: usize, errors: Vec<NodeError>) -> Error {
             NodeError::EpochMismatch(epoch) => {
                 epoch_mismatches.push(*epoch);
             }
+            NodeError::Timeout(_) => {
+                timeouts += 1;
+            }
             // we ignore unknown for aggregation
             _ => {}
         }
@@ -457,6 +667,12 @@ fn aggregate_error(threshold: usize, errors: Vec<NodeError>) -> Error {
         );
     }
 
+    let unreachable = ws_errors_counters + timeouts;
+    let healthy = errors.len() - unreachable;
+    if unreachable > 0 && healthy < threshold {
+        return Error::InsufficientHealthyNodes { healthy, threshold };
+    }
+
     Error::NodeErrorDisagreement(errors.into_iter().collect())
 }
 
@@ -477,6 +693,85 @@ pub struct VerifiableOprfOutput {
     pub oprf_public_key: OprfPublicKey,
     /// The `ShareEpoch` which was used.
     pub epoch: ShareEpoch,
+    /// The context of the OPRF module as field element, `0` if the module has none (see [`OutputDomain::context_input`]). It is the last input of the hash to the field of the query and is mixed into the challenge of the `dlog_proof`, so circuits that recompute the blinded request or verify the proof need it as input.
+    pub context: ark_babyjubjub::Fq,
+}
+
+/// The domain of an OPRF output: the domain separator of the output hash and the context of the OPRF module.
+///
+/// A node can bind a module to a context (`OprfServiceBuilder::module_context` of the node). The node mixes the context into the challenge of the `DLog` equality proof, so the proof of a module with context only verifies with the same context, and a blinded query replayed to a module with another context is not answered with a valid proof. The client hashes the query together with the context before mapping it to the curve (see [`oprf_core::oprf::client::blind_query_with_context`]), so the context also changes the output: the same query evaluated by modules with distinct contexts yields unrelated outputs, even under the same key and domain separator. Modules without context are used with the domain separator alone, which converts into an `OutputDomain` without context.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputDomain {
+    domain_separator: ark_babyjubjub::Fq,
+    context: Option<String>,
+}
+
+impl OutputDomain {
+    /// Creates an `OutputDomain` without context.
+    #[must_use]
+    pub fn new(domain_separator: ark_babyjubjub::Fq) -> Self {
+        Self {
+            domain_separator,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the context the OPRF module is configured with.
+    #[must_use]
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    /// The domain separator used in the final Poseidon hash to derive the output.
+    #[must_use]
+    pub fn domain_separator(&self) -> ark_babyjubjub::Fq {
+        self.domain_separator
+    }
+
+    /// The context of the OPRF module, if it has one.
+    #[must_use]
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    /// The [`QueryContext`] of the context, if there is one (see [`oprf_core::oprf::client::query_context`]).
+    #[must_use]
+    pub fn query_context(&self) -> Option<QueryContext> {
+        self.context
+            .as_deref()
+            .map(oprf_core::oprf::client::query_context)
+    }
+
+    /// The context as field element, `0` without context (see [`QueryContext::inner`]).
+    ///
+    /// This is the context the proofs are verified with, see [`verify_dlog_equality`].
+    #[must_use]
+    pub fn context_input(&self) -> ark_babyjubjub::Fq {
+        self.query_context()
+            .as_ref()
+            .map_or_else(ark_babyjubjub::Fq::default, QueryContext::inner)
+    }
+
+    /// Blinds `query` with `blinding_factor`, bound to the context if there is one.
+    pub(crate) fn blind(
+        &self,
+        query: ark_babyjubjub::Fq,
+        blinding_factor: BlindingFactor,
+    ) -> BlindedOprfRequest {
+        match self.query_context() {
+            Some(context) => {
+                oprf_core::oprf::client::blind_query_with_context(query, context, blinding_factor)
+            }
+            None => oprf_core::oprf::client::blind_query(query, blinding_factor),
+        }
+    }
+}
+
+impl From<ark_babyjubjub::Fq> for OutputDomain {
+    fn from(domain_separator: ark_babyjubjub::Fq) -> Self {
+        Self::new(domain_separator)
+    }
 }
 
 /// Executes the distributed OPRF protocol.
@@ -498,7 +793,7 @@ pub struct VerifiableOprfOutput {
 /// - `threshold`: Number of nodes required to complete the protocol
 /// - `query`: The OPRF input value to evaluate
 /// - `blinding_factor`: The blinding factor used to blind the query
-/// - `domain_separator`: Domain separator used in the final Poseidon hash to derive the output
+/// - `domain`: The domain separator used in the final Poseidon hash to derive the output, and the context of the OPRF module if it has one (see [`OutputDomain`])
 /// - `auth`: Implementation specific authentication request forwarded to each OPRF node as part of the request
 /// - `connector`: TLS connector configuration for the WebSocket connections
 ///
@@ -527,19 +822,44 @@ pub struct VerifiableOprfOutput {
 ///
 /// # Errors
 /// See the [`Error`] enum for all potential errors of this function.
+pub async fn distributed_oprf<OprfRequestAuth>(
+    services: &[Uri],
+    threshold: usize,
+    query: ark_babyjubjub::Fq,
+    blinding_factor: BlindingFactor,
+    domain: impl Into<OutputDomain>,
+    auth: OprfRequestAuth,
+    connector: Connector,
+) -> Result<VerifiableOprfOutput, Error>
+where
+    OprfRequestAuth: Clone + Serialize + 'static,
+{
+    distributed_oprf_from(
+        &SessionSource::connect(connector),
+        services,
+        threshold,
+        query,
+        blinding_factor,
+        domain.into(),
+        auth,
+    )
+    .await
+}
+
+/// Like [`distributed_oprf`], but opens the sessions at the given [`SessionSource`].
 #[instrument(level = "debug", skip_all, fields(request_id = tracing::field::Empty))]
 #[allow(
     clippy::missing_panics_doc,
     reason = "Can't really panic due to promises from called method"
 )]
-pub async fn distributed_oprf<OprfRequestAuth>(
+pub(crate) async fn distributed_oprf_from<OprfRequestAuth>(
+    source: &SessionSource,
     services: &[Uri],
     threshold: usize,
     query: ark_babyjubjub::Fq,
     blinding_factor: BlindingFactor,
-    domain_separator: ark_babyjubjub::Fq,
+    domain: OutputDomain,
     auth: OprfRequestAuth,
-    connector: Connector,
 ) -> Result<VerifiableOprfOutput, Error>
 where
     OprfRequestAuth: Clone + Serialize + 'static,
@@ -551,21 +871,24 @@ where
     distributed_oprf_span.record("request_id", request_id.to_string());
     tracing::debug!("starting with request id: {request_id}");
 
-    let blinded_request = oprf_core::oprf::client::blind_query(query, blinding_factor);
+    let blinded_request = domain.blind(query, blinding_factor);
     let oprf_req = OprfRequest {
         request_id,
         blinded_query: blinded_request.blinded_query(),
         auth,
+        issued_at: Some(unix_timestamp()),
+        batch: Vec::new(),
     };
 
     let (oprf_public_key, epoch, challenge, responses) =
-        distributed_oprf_core(services, threshold, oprf_req, connector).await?;
+        distributed_oprf_core_from(source, services, threshold, oprf_req).await?;
 
     finalize_distributed_oprf(FinalizeDistributedOprfArgs {
         request_id,
         query,
         blinding_factor,
-        domain_separator,
+        domain_separator: domain.domain_separator(),
+        context: domain.context_input(),
         blinded_request,
         challenge,
         responses,
@@ -574,6 +897,43 @@ where
     })
 }
 
+/// Executes the OPRF protocol against the node of a single-node (non-threshold) deployment.
+///
+/// Shorthand for [`distributed_oprf`] with a single service and threshold 1. The node answers with the same messages as a node of a threshold committee. With a single party, combining the commitments and proof shares is the identity (the Lagrange coefficient of the only party is 1), so the result is the same [`VerifiableOprfOutput`]. Moving to a threshold committee later only requires calling [`distributed_oprf`] with all nodes and the threshold.
+///
+/// # Arguments
+/// - `service`: WebSocket URI of the OPRF node. See the helper function [`to_oprf_uri`].
+/// - `query`: The OPRF input value to evaluate
+/// - `blinding_factor`: The blinding factor used to blind the query
+/// - `domain`: The domain separator used in the final Poseidon hash to derive the output, and the context of the OPRF module if it has one (see [`OutputDomain`])
+/// - `auth`: Implementation specific authentication request forwarded to the OPRF node as part of the request
+/// - `connector`: TLS connector configuration for the WebSocket connection
+///
+/// # Errors
+/// See the [`Error`] enum for all potential errors of this function.
+pub async fn single_node_oprf<OprfRequestAuth>(
+    service: &Uri,
+    query: ark_babyjubjub::Fq,
+    blinding_factor: BlindingFactor,
+    domain: impl Into<OutputDomain>,
+    auth: OprfRequestAuth,
+    connector: Connector,
+) -> Result<VerifiableOprfOutput, Error>
+where
+    OprfRequestAuth: Clone + Serialize + 'static,
+{
+    distributed_oprf(
+        std::slice::from_ref(service),
+        1,
+        query,
+        blinding_factor,
+        domain,
+        auth,
+        connector,
+    )
+    .await
+}
+
 /// Executes the distributed OPRF protocol via a single delegate node over HTTP.
 ///
 /// Instead of the client directly contacting every OPRF node and driving [`distributed_oprf_core`]
@@ -605,7 +965,7 @@ where
 /// - `service`: URL of the delegate service that will run the distributed OPRF protocol on our behalf
 /// - `query`: The OPRF input value to evaluate
 /// - `blinding_factor`: The blinding factor used to blind the query
-/// - `domain_separator`: Domain separator used in the final Poseidon hash to derive the output
+/// - `domain`: The domain separator used in the final Poseidon hash to derive the output, and the context of the OPRF module if it has one (see [`OutputDomain`])
 /// - `auth`: Implementation specific authentication request forwarded to the delegate service as part of the request
 /// - `client`: The [`reqwest::Client`] used to send the request to the delegate service
 ///
@@ -624,7 +984,7 @@ pub async fn delegate_distributed_oprf<OprfRequestAuth>(
     service: &Url,
     query: ark_babyjubjub::Fq,
     blinding_factor: BlindingFactor,
-    domain_separator: ark_babyjubjub::Fq,
+    domain: impl Into<OutputDomain>,
     auth: OprfRequestAuth,
     client: &reqwest::Client,
 ) -> Result<VerifiableOprfOutput, Error>
@@ -632,53 +992,38 @@ where
     OprfRequestAuth: Clone + Serialize + 'static,
 {
     tracing::trace!("starting distributed oprf. my version: {}", VERSION);
+    let domain = domain.into();
 
     let request_id = Uuid::new_v4();
     let distributed_oprf_span = tracing::Span::current();
     distributed_oprf_span.record("request_id", request_id.to_string());
     tracing::debug!("starting with request id: {request_id}");
 
-    let blinded_request = oprf_core::oprf::client::blind_query(query, blinding_factor);
+    let blinded_request = domain.blind(query, blinding_factor);
     let oprf_req = OprfRequest {
         request_id,
         blinded_query: blinded_request.blinded_query(),
         auth,
+        issued_at: Some(unix_timestamp()),
+        batch: Vec::new(),
     };
 
     // add client version to query params so the delegate service can check for compatibility
     let mut service = service.clone();
-    service.query_pairs_mut().append_pair("version", VERSION);
+    service
+        .query_pairs_mut()
+        .append_pair("version", VERSION)
+        .append_pair("schema", &SchemaFingerprint::CURRENT.to_string());
 
     let response = client.post(service).json(&oprf_req).send().await?;
-    let status = response.status();
-    tracing::debug!("delegate service returned status: {status}");
-
-    let response = if status.is_success() {
-        response.json::<DelegateOprfResponse>().await?
-    } else {
-        let body = response.text().await?;
-
-        // If the delegate service returned a `ServiceError` (with a code), we wrap it in a `ThresholdServiceError`.
-        // If the nodes disagreed on an error, or if a unexpected (e.g. Networking) error occurred, the delegate service will return a generic error. We wrap that in a `DelegateServerError`.
-        if let Ok(error_code) = body.parse::<u16>() {
-            return Err(Error::ThresholdServiceError(ServiceError {
-                error_code,
-                msg: None,
-                kind: error_code.into(),
-            }));
-        }
-
-        return Err(Error::DelegateServerError {
-            status,
-            reason: body,
-        });
-    };
+    let response = delegate_response::<DelegateOprfResponse>(response).await?;
 
     finalize_distributed_oprf(FinalizeDistributedOprfArgs {
         request_id,
         query,
         blinding_factor,
-        domain_separator,
+        domain_separator: domain.domain_separator(),
+        context: domain.context_input(),
         blinded_request,
         challenge: response.challenge,
         responses: response.responses,
@@ -687,6 +1032,31 @@ where
     })
 }
 
+/// Reads the response of a delegate service, e.g. the delegate node of [`delegate_distributed_oprf`] or an [`aggregator`].
+async fn delegate_response<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, Error> {
+    let status = response.status();
+    tracing::debug!("delegate service returned status: {status}");
+    if status.is_success() {
+        return Ok(response.json::<T>().await?);
+    }
+    let body = response.text().await?;
+
+    // If the delegate service returned a `ServiceError` (with a code), we wrap it in a `ThresholdServiceError`.
+    // If the nodes disagreed on an error, or if a unexpected (e.g. Networking) error occurred, the delegate service will return a generic error. We wrap that in a `DelegateServerError`.
+    if let Ok(error_code) = body.parse::<u16>() {
+        return Err(Error::ThresholdServiceError(ServiceError {
+            error_code,
+            msg: None,
+            kind: error_code.into(),
+        }));
+    }
+
+    Err(Error::DelegateServerError {
+        status,
+        reason: body,
+    })
+}
+
 /// Executes the core, network-facing part of the distributed OPRF protocol against a set of nodes.
 ///
 /// This is the lower-level building block used by [`distributed_oprf`] and by [`delegate_distributed_oprf`]'s
@@ -708,7 +1078,7 @@ where
 /// - `services`: List of WebSocket URIs of the OPRF nodes to contact (must be unique). See the helper functions [`to_oprf_uri`] and [`to_oprf_uri_many`].
 /// - `threshold`: Number of nodes required to complete the protocol
 /// - `request_id`: The UUID identifying this OPRF request, forwarded to all nodes
-/// - `req`: The already-blinded [`OprfRequest`] to send to every node
+/// - `req`: The already-blinded [`OprfRequest`] to send to every node, without a [`OprfRequest::batch`] (see [`distributed_oprf_batch`] for batch sessions)
 /// - `connector`: TLS connector configuration for the WebSocket connections
 ///
 /// # Timeout and Cancellation
@@ -717,16 +1087,61 @@ where
 ///
 /// # Errors
 /// See the [`Error`] enum for all potential errors of this function.
+pub async fn distributed_oprf_core<OprfRequestAuth>(
+    services: &[Uri],
+    threshold: usize,
+    req: OprfRequest<OprfRequestAuth>,
+    connector: Connector,
+) -> Result<
+    (
+        OprfPublicKey,
+        ShareEpoch,
+        DLogCommitmentsShamir,
+        Vec<DLogProofShareShamir>,
+    ),
+    Error,
+>
+where
+    OprfRequestAuth: Clone + Serialize + 'static,
+{
+    distributed_oprf_core_from(&SessionSource::connect(connector), services, threshold, req).await
+}
+
+/// Like [`distributed_oprf_core`], but opens the sessions at the given [`SessionSource`].
 #[instrument(level = "debug", skip_all, fields(request_id = %req.request_id))]
+pub(crate) async fn distributed_oprf_core_from<OprfRequestAuth>(
+    source: &SessionSource,
+    services: &[Uri],
+    threshold: usize,
+    req: OprfRequest<OprfRequestAuth>,
+) -> Result<
+    (
+        OprfPublicKey,
+        ShareEpoch,
+        DLogCommitmentsShamir,
+        Vec<DLogProofShareShamir>,
+    ),
+    Error,
+>
+where
+    OprfRequestAuth: Clone + Serialize + 'static,
+{
+    let observation = observer::Observation::start(req.request_id, services.len(), threshold);
+    let result = run_distributed_oprf_core(source, services, threshold, req).await;
+    observation.end(result.as_ref().map(|(_, epoch, _, _)| *epoch));
+    result
+}
+
+/// Runs both rounds of the protocol for [`distributed_oprf_core_from`].
 #[allow(
     clippy::missing_panics_doc,
     reason = "Can't really panic due to promises from called method"
 )]
-pub async fn distributed_oprf_core<OprfRequestAuth>(
+async fn run_distributed_oprf_core<OprfRequestAuth>(
+    source: &SessionSource,
     services: &[Uri],
     threshold: usize,
     req: OprfRequest<OprfRequestAuth>,
-    connector: Connector,
 ) -> Result<
     (
         OprfPublicKey,
@@ -739,6 +1154,58 @@ pub async fn distributed_oprf_core<OprfRequestAuth>(
 where
     OprfRequestAuth: Clone + Serialize + 'static,
 {
+    check_services(services, threshold)?;
+
+    let request_id = req.request_id;
+    let mut transcript = transcript::TranscriptCapture::start(&req);
+
+    tracing::debug!("initializing sessions at {} services", services.len());
+    let sessions = sessions::init_sessions_from(source, request_id, services, threshold, req)
+        .await
+        .map_err(|errors| aggregate_error(threshold, errors))?;
+    for (idx, party_id) in sessions.party_ids.iter().enumerate() {
+        transcript.record_response(|| OprfResponse {
+            commitments: sessions.commitments[idx].clone(),
+            party_id: *party_id,
+            oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch {
+                key: sessions.oprf_public_keys[idx],
+                epoch: sessions.epoch,
+            },
+            batch_commitments: sessions.batch_commitments[idx].clone(),
+            share_proof: sessions.share_proofs[idx].clone(),
+            signature: sessions.signatures.get(party_id).cloned(),
+        });
+    }
+
+    let oprf_public_key = agreed_oprf_public_key(&sessions, source.require_share_proofs())?;
+
+    let epoch = sessions.epoch;
+    tracing::debug!("Will use epoch: {epoch}");
+    tracing::debug!("compute the challenges for the services..");
+    let challenge = generate_challenge_request(&sessions);
+
+    let party_ids = sessions.party_ids.clone();
+    for party_id in &party_ids {
+        transcript.record(FrameDirection::Sent, *party_id, || {
+            TranscriptMessage::Challenge(challenge.clone())
+        });
+    }
+
+    tracing::debug!("finishing the sessions at the remaining services..");
+    let responses = sessions::finish_sessions(sessions, challenge.clone())
+        .await
+        .map_err(Error::CannotFinishSession)?;
+    for (party_id, proof_share) in party_ids.iter().zip(&responses) {
+        transcript.record(FrameDirection::Received, *party_id, || {
+            TranscriptMessage::ProofShare(proof_share.clone())
+        });
+    }
+
+    Ok((oprf_public_key, epoch, challenge, responses))
+}
+
+/// Checks that `threshold` many of the `services` can complete the protocol and that the `services` are unique.
+fn check_services(services: &[Uri], threshold: usize) -> Result<(), Error> {
     if threshold == 0 || threshold > services.len() {
         return Err(Error::InvalidThreshold {
             num_peers: services.len(),
@@ -749,14 +1216,16 @@ where
     if services_dedup.len() != services.len() {
         return Err(Error::NonUniqueServices);
     }
+    Ok(())
+}
 
-    let request_id = req.request_id;
-
-    tracing::debug!("initializing sessions at {} services", services.len());
-    let sessions = sessions::init_sessions(request_id, services, threshold, req, connector)
-        .await
-        .map_err(|errors| aggregate_error(threshold, errors))?;
-
+/// Returns the [`OprfPublicKey`] of the sessions, if all nodes sent the same key.
+///
+/// If all nodes sent a [`ShareProof`](oprf_types::api::ShareProof), their public shares must combine to the key. With `require_share_proofs`, a missing share proof fails with [`Error::InconsistentPublicShares`].
+fn agreed_oprf_public_key(
+    sessions: &OprfSessions,
+    require_share_proofs: bool,
+) -> Result<OprfPublicKey, Error> {
     let oprf_public_key = sessions
         .oprf_public_keys
         .first()
@@ -770,18 +1239,30 @@ where
         tracing::error!("inconsistent OPRF public keys received from nodes");
         return Err(Error::InconsistentOprfPublicKeys);
     }
-
-    let epoch = sessions.epoch;
-    tracing::debug!("Will use epoch: {epoch}");
-    tracing::debug!("compute the challenges for the services..");
-    let challenge = generate_challenge_request(&sessions);
-
-    tracing::debug!("finishing the sessions at the remaining services..");
-    let responses = sessions::finish_sessions(sessions, challenge.clone())
-        .await
-        .map_err(Error::CannotFinishSession)?;
-
-    Ok((oprf_public_key, epoch, challenge, responses))
+    // nodes without share proofs cannot be checked, as the registry does not hold the public shares
+    let public_shares = match sessions
+        .share_proofs
+        .iter()
+        .map(|proof| proof.as_ref().map(|proof| proof.public_share))
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(public_shares) => public_shares,
+        None if require_share_proofs => {
+            tracing::error!("nodes did not send the required share proofs");
+            return Err(Error::InconsistentPublicShares);
+        }
+        None => return Ok(oprf_public_key),
+    };
+    let contributing_parties = sessions
+        .party_ids
+        .iter()
+        .map(|party_id| party_id.into_inner() + 1)
+        .collect::<Vec<_>>();
+    if combine_public_shares(&public_shares, &contributing_parties) != oprf_public_key.inner() {
+        tracing::error!("public shares of the nodes do not combine to the OPRF public key");
+        return Err(Error::InconsistentPublicShares);
+    }
+    Ok(oprf_public_key)
 }
 
 /// Arguments required to finalize the distributed OPRF protocol after the network-facing part has completed.
@@ -794,6 +1275,8 @@ pub struct FinalizeDistributedOprfArgs {
     pub blinding_factor: BlindingFactor,
     /// Domain separator used in the final Poseidon hash to derive the output.
     pub domain_separator: ark_babyjubjub::Fq,
+    /// The context of the OPRF module as field element, `0` without context (see [`OutputDomain::context_input`]). The query must have been blinded with the same context.
+    pub context: ark_babyjubjub::Fq,
     /// The blinded query sent to the OPRF nodes.
     pub blinded_request: BlindedOprfRequest,
     /// The combined `DLog` commitments used to generate the challenge.
@@ -826,6 +1309,7 @@ pub fn finalize_distributed_oprf(
         query,
         blinding_factor,
         domain_separator,
+        context,
         blinded_request,
         challenge,
         responses,
@@ -843,28 +1327,36 @@ pub fn finalize_distributed_oprf(
         &blinded_request,
         &responses,
         challenge.clone(),
+        context,
     )?;
 
-    let digest = poseidon2::bn254::t4::permutation(&[
-        domain_separator,
-        query,
-        unblinded_response.x,
-        unblinded_response.y,
-    ]);
-
-    let output = digest[1];
-
     Ok(VerifiableOprfOutput {
-        output,
+        output: oprf_output(domain_separator, query, unblinded_response),
         blinded_request: blinded_request.blinded_query(),
         blinded_response: blinded_response.response(),
         dlog_proof,
         unblinded_response,
         oprf_public_key,
         epoch,
+        context,
     })
 }
 
+/// Derives the OPRF output by hashing the domain separator, the original query, and the unblinded response.
+fn oprf_output(
+    domain_separator: ark_babyjubjub::Fq,
+    query: ark_babyjubjub::Fq,
+    unblinded_response: ark_babyjubjub::EdwardsAffine,
+) -> ark_babyjubjub::Fq {
+    let digest = poseidon2::bn254::t4::permutation(&[
+        domain_separator,
+        query,
+        unblinded_response.x,
+        unblinded_response.y,
+    ]);
+    digest[1]
+}
+
 /// Combines the [`DLogProofShareShamir`]s of the OPRF nodes and computes the final [`DLogEqualityProof`].
 ///
 /// Verifies the proof and returns an [`Error`] iff the proof is invalid.
@@ -875,6 +1367,7 @@ pub fn finalize_distributed_oprf(
 /// - `blinded_request`: The blinded query sent to the OPRF nodes
 /// - `proofs`: The proof shares collected from each node
 /// - `challenge`: The combined `DLog` commitments used to generate the challenge
+/// - `context`: The context of the OPRF module as field element, `0` if it has none (see [`OutputDomain::context_input`])
 #[instrument(level = "debug", skip_all, fields(request_id = %request_id))]
 pub fn verify_dlog_equality(
     request_id: Uuid,
@@ -882,6 +1375,7 @@ pub fn verify_dlog_equality(
     blinded_request: &BlindedOprfRequest,
     proofs: &[DLogProofShareShamir],
     challenge: DLogCommitmentsShamir,
+    context: ark_babyjubjub::Fq,
 ) -> Result<DLogEqualityProof, Error> {
     let blinded_response = challenge.blinded_response();
     let dlog_proof = challenge.combine_proofs(
@@ -889,13 +1383,15 @@ pub fn verify_dlog_equality(
         proofs,
         oprf_public_key.inner(),
         blinded_request.blinded_query(),
+        context,
     );
     dlog_proof
-        .verify(
+        .verify_with_context(
             oprf_public_key.inner(),
             blinded_request.blinded_query(),
             blinded_response,
             ark_babyjubjub::EdwardsAffine::generator(),
+            context,
         )
         .map_err(|_| Error::InvalidDLogProof)?;
     Ok(dlog_proof)
@@ -916,26 +1412,35 @@ pub fn generate_challenge_request(sessions: &OprfSessions) -> DLogCommitmentsSha
     DLogCommitmentsShamir::combine_commitments(&sessions.commitments, contributing_parties)
 }
 
-/// Fetches the [`OprfPublicKeyWithEpoch`] for the given [`OprfKeyId`] from a single service.
-async fn fetch_oprf_public_key_from_service(
+/// Whether a request to a node failed with an error that may go away on retry.
+fn is_retryable_error(err: &reqwest::Error) -> bool {
+    err.is_timeout()
+        || err.is_connect()
+        || err.status().is_some_and(|status| {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        })
+}
+
+/// Fetches `T` from `url`, retrying transient errors according to `retry_policy`. Returns `None` if the node responds with `404 Not Found`.
+async fn fetch_from_service<T: DeserializeOwned>(
     url: &Url,
-    oprf_key_id: OprfKeyId,
     client: &reqwest::Client,
-) -> Result<Option<OprfPublicKeyWithEpoch>, reqwest::Error> {
-    // Get rid of existing trailing slash, if any, and append a trailing slash to the path segments.
-    let mut url = url.clone();
-    url.path_segments_mut()
-        .expect("url should not be cannot-be-a-base")
-        .pop_if_empty()
-        .push("");
+    retry_policy: &RetryPolicy,
+) -> Result<Option<T>, reqwest::Error> {
+    (|| fetch_from_service_once(url, client))
+        .retry(retry_policy.backoff())
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::debug!(%err, "retrying {url} after {duration:?}");
+        })
+        .await
+}
 
-    let response = client
-        .get(
-            url.join(oprf_key_id.to_string().as_str())
-                .expect("OprfKeyId to_string() should produce a valid URL"),
-        )
-        .send()
-        .await?;
+async fn fetch_from_service_once<T: DeserializeOwned>(
+    url: &Url,
+    client: &reqwest::Client,
+) -> Result<Option<T>, reqwest::Error> {
+    let response = client.get(url.clone()).send().await?;
 
     if response.status() == reqwest::StatusCode::NOT_FOUND {
         return Ok(None);
@@ -943,33 +1448,51 @@ async fn fetch_oprf_public_key_from_service(
 
     let response = response.error_for_status()?;
 
-    Ok(Some(response.json::<OprfPublicKeyWithEpoch>().await?))
+    Ok(Some(response.json::<T>().await?))
 }
 
-/// Fetches the [`OprfPublicKeyWithEpoch`] for a given [`OprfKeyId`] from a set of OPRF nodes,
-/// returning it if `threshold` many nodes agree on the same value.
-/// If `threshold` many nodes return `404 Not Found`, returns `Ok(None)`.
-///
-/// Nodes are queried concurrently via `GET {service}/oprf_pub/{key_id}` (see [`to_oprf_pub_key_url`]).
-///
-/// # Arguments
-/// - `services`: Base URLs (up to `/oprf_pub`) of the OPRF nodes to query (must be unique)
-/// - `threshold`: Number of nodes required to agree on the same [`OprfPublicKeyWithEpoch`]
-/// - `key_id`: The [`OprfKeyId`] to fetch the public key for
-/// - `client`: The [`reqwest::Client`] used to send the requests
-///
-/// # Errors
-/// - [`Error::InvalidThreshold`] if `threshold` is `0` or greater than `services.len()`.
-/// - [`Error::NonUniqueServices`] if `services` contains duplicate URLs.
-/// - [`Error::Networking`] if `threshold` many nodes could not be reached, or returned an unexpected response.
-/// - [`Error::InconsistentOprfPublicKeys`] if no single response reached `threshold` agreement.
-#[instrument(level = "debug", skip(client))]
-pub async fn fetch_oprf_public_key(
+/// Appends the [`OprfKeyId`] to the info endpoint `url` of a service, e.g. `/oprf_pub`.
+fn to_key_info_url(url: &Url, oprf_key_id: OprfKeyId) -> Url {
+    // Get rid of existing trailing slash, if any, and append a trailing slash to the path segments.
+    let mut url = url.clone();
+    url.path_segments_mut()
+        .expect("url should not be cannot-be-a-base")
+        .pop_if_empty()
+        .push("");
+    url.join(oprf_key_id.to_string().as_str())
+        .expect("OprfKeyId to_string() should produce a valid URL")
+}
+
+/// Fetches the info of the given [`OprfKeyId`] from a set of OPRF nodes, returning it if `threshold` many nodes agree on the same value.
+async fn fetch_agreed_key_info<T>(
     urls: &[Url],
     threshold: usize,
     key_id: OprfKeyId,
     client: &reqwest::Client,
-) -> Result<Option<OprfPublicKeyWithEpoch>, Error> {
+    retry_policy: &RetryPolicy,
+) -> Result<Option<T>, Error>
+where
+    T: DeserializeOwned + Clone + Eq + std::hash::Hash,
+{
+    let urls = urls
+        .iter()
+        .map(|url| to_key_info_url(url, key_id))
+        .collect::<Vec<_>>();
+    fetch_agreed(&urls, threshold, client, retry_policy).await
+}
+
+/// Fetches `T` from a set of OPRF nodes, returning it if `threshold` many nodes agree on the same value.
+///
+/// Returns [`Error::InconsistentOprfPublicKeys`] if no value reached `threshold` agreement.
+async fn fetch_agreed<T>(
+    urls: &[Url],
+    threshold: usize,
+    client: &reqwest::Client,
+    retry_policy: &RetryPolicy,
+) -> Result<Option<T>, Error>
+where
+    T: DeserializeOwned + Clone + Eq + std::hash::Hash,
+{
     if threshold == 0 || threshold > urls.len() {
         return Err(Error::InvalidThreshold {
             num_peers: urls.len(),
@@ -984,10 +1507,10 @@ pub async fn fetch_oprf_public_key(
 
     let mut futures: FuturesUnordered<_> = urls
         .iter()
-        .map(|url| fetch_oprf_public_key_from_service(url, key_id, client))
+        .map(|url| fetch_from_service::<T>(url, client, retry_policy))
         .collect();
 
-    let mut agreement: HashMap<OprfPublicKeyWithEpoch, usize> = HashMap::new();
+    let mut agreement: HashMap<T, usize> = HashMap::new();
     let mut not_found_count = 0usize;
     let mut network_errors = Vec::new();
 
@@ -1018,6 +1541,157 @@ pub async fn fetch_oprf_public_key(
     Err(Error::InconsistentOprfPublicKeys)
 }
 
+/// Fetches the [`OprfPublicKeyWithEpoch`] for a given [`OprfKeyId`] from a set of OPRF nodes,
+/// returning it if `threshold` many nodes agree on the same value.
+/// If `threshold` many nodes return `404 Not Found`, returns `Ok(None)`.
+///
+/// Nodes are queried concurrently via `GET {service}/oprf_pub/{key_id}` (see [`to_oprf_pub_key_url`]).
+///
+/// # Arguments
+/// - `services`: Base URLs (up to `/oprf_pub`) of the OPRF nodes to query (must be unique)
+/// - `threshold`: Number of nodes required to agree on the same [`OprfPublicKeyWithEpoch`]
+/// - `key_id`: The [`OprfKeyId`] to fetch the public key for
+/// - `client`: The [`reqwest::Client`] used to send the requests
+/// - `retry_policy`: How often to retry a node that timed out, could not be reached or returned `5xx` or `429 Too Many Requests`, e.g. [`RetryPolicy::default`]
+///
+/// # Errors
+/// - [`Error::InvalidThreshold`] if `threshold` is `0` or greater than `services.len()`.
+/// - [`Error::NonUniqueServices`] if `services` contains duplicate URLs.
+/// - [`Error::Networking`] if `threshold` many nodes could not be reached after all retries, or returned an unexpected response.
+/// - [`Error::InconsistentOprfPublicKeys`] if no single response reached `threshold` agreement.
+#[instrument(level = "debug", skip(client))]
+pub async fn fetch_oprf_public_key(
+    urls: &[Url],
+    threshold: usize,
+    key_id: OprfKeyId,
+    client: &reqwest::Client,
+    retry_policy: &RetryPolicy,
+) -> Result<Option<OprfPublicKeyWithEpoch>, Error> {
+    fetch_agreed_key_info(urls, threshold, key_id, client, retry_policy).await
+}
+
+/// Fetches the [`OprfKeyParams`] (public key, epoch and threshold) for a given [`OprfKeyId`] from a set of OPRF nodes,
+/// returning them if `threshold` many nodes agree on the same value.
+/// If `threshold` many nodes return `404 Not Found`, returns `Ok(None)`.
+///
+/// Keys may have their own threshold, which can differ from the threshold of the node committee. Use the returned threshold as the number of sessions to open with [`distributed_oprf`] for this key.
+///
+/// Nodes are queried concurrently via `GET {service}/oprf_params/{key_id}` (see [`to_oprf_params_url`]).
+///
+/// # Arguments
+/// - `services`: Base URLs (up to `/oprf_params`) of the OPRF nodes to query (must be unique)
+/// - `threshold`: Number of nodes required to agree on the same [`OprfKeyParams`]
+/// - `key_id`: The [`OprfKeyId`] to fetch the parameters for
+/// - `client`: The [`reqwest::Client`] used to send the requests
+/// - `retry_policy`: How often to retry a node that timed out, could not be reached or returned `5xx` or `429 Too Many Requests`, e.g. [`RetryPolicy::default`]
+///
+/// # Errors
+/// - [`Error::InvalidThreshold`] if `threshold` is `0` or greater than `services.len()`.
+/// - [`Error::NonUniqueServices`] if `services` contains duplicate URLs.
+/// - [`Error::Networking`] if `threshold` many nodes could not be reached after all retries, or returned an unexpected response.
+/// - [`Error::InconsistentOprfPublicKeys`] if no single response reached `threshold` agreement.
+#[instrument(level = "debug", skip(client))]
+pub async fn fetch_oprf_key_params(
+    urls: &[Url],
+    threshold: usize,
+    key_id: OprfKeyId,
+    client: &reqwest::Client,
+    retry_policy: &RetryPolicy,
+) -> Result<Option<OprfKeyParams>, Error> {
+    fetch_agreed_key_info(urls, threshold, key_id, client, retry_policy).await
+}
+
+/// Fetches the [`Committee`] (party ids and registered wallet addresses of all nodes) from a set of OPRF nodes,
+/// returning it if `threshold` many nodes agree on the same value.
+/// If `threshold` many nodes return `404 Not Found` (i.e., they do not serve the committee), returns `Ok(None)`.
+///
+/// Nodes are queried concurrently via `GET {service}/committee` (see [`to_committee_url`]). Use [`check_committee_wallets`] to cross-check the committee with the wallets of the nodes.
+///
+/// # Arguments
+/// - `urls`: Committee URLs of the OPRF nodes to query (must be unique)
+/// - `threshold`: Number of nodes required to agree on the same [`Committee`]
+/// - `client`: The [`reqwest::Client`] used to send the requests
+/// - `retry_policy`: How often to retry a node that timed out, could not be reached or returned `5xx` or `429 Too Many Requests`, e.g. [`RetryPolicy::default`]
+///
+/// # Errors
+/// - [`Error::InvalidThreshold`] if `threshold` is `0` or greater than `urls.len()`.
+/// - [`Error::NonUniqueServices`] if `urls` contains duplicate URLs.
+/// - [`Error::Networking`] if `threshold` many nodes could not be reached after all retries, or returned an unexpected response.
+/// - [`Error::InconsistentCommittee`] if no single response reached `threshold` agreement.
+#[instrument(level = "debug", skip(client))]
+pub async fn fetch_committee(
+    urls: &[Url],
+    threshold: usize,
+    client: &reqwest::Client,
+    retry_policy: &RetryPolicy,
+) -> Result<Option<Committee>, Error> {
+    match fetch_agreed(urls, threshold, client, retry_policy).await {
+        Err(Error::InconsistentOprfPublicKeys) => Err(Error::InconsistentCommittee),
+        result => result,
+    }
+}
+
+/// Checks that every node in `services` uses a wallet address registered in the `committee`.
+///
+/// Queries `GET {service}/wallet` of every node concurrently.
+///
+/// # Arguments
+/// - `services`: Base URLs of the OPRF nodes
+/// - `committee`: The [`Committee`], e.g. from [`fetch_committee`]
+/// - `client`: The [`reqwest::Client`] used to send the requests
+///
+/// # Errors
+/// - [`Error::UnregisteredNode`] if a node reports a wallet that is not part of the committee.
+/// - [`Error::Networking`] if a node could not be reached.
+#[instrument(level = "debug", skip(client))]
+pub async fn check_committee_wallets<S: AsRef<str> + fmt::Debug>(
+    services: &[S],
+    committee: &Committee,
+    client: &reqwest::Client,
+) -> Result<(), Error> {
+    let mut futures: FuturesUnordered<_> = services
+        .iter()
+        .map(|service| async move {
+            let service = service.as_ref().trim_end_matches('/');
+            let wallet = client
+                .get(format!("{service}/wallet"))
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            Ok::<_, reqwest::Error>((service.to_owned(), wallet))
+        })
+        .collect();
+    while let Some(result) = futures.next().await {
+        let (service, wallet) = result.map_err(|err| Error::Networking(vec![Box::new(err)]))?;
+        if committee.party_id_of(&wallet).is_none() {
+            return Err(Error::UnregisteredNode { service, wallet });
+        }
+    }
+    Ok(())
+}
+
+/// Discovers the committee from a [`SignedCommitteeBundle`](oprf_types::bundle::SignedCommitteeBundle), e.g. loaded from a file shared with the environment.
+///
+/// Checks that the bundle was signed by the trusted `admin` and that every node of the bundle uses the wallet registered for it (see [`check_committee_wallets`]). Use [`oprf_types::bundle::CommitteeBundle::node_urls`] and the threshold of the returned bundle for the OPRF requests.
+///
+/// # Errors
+/// - [`Error::InvalidBundle`] if the bundle is not signed by `admin` or inconsistent.
+/// - [`Error::UnregisteredNode`] if a node uses a wallet that is not part of the bundle.
+/// - [`Error::Networking`] if a node could not be reached.
+#[cfg(feature = "bundle")]
+#[instrument(level = "debug", skip_all)]
+pub async fn discover_from_bundle(
+    signed: &oprf_types::bundle::SignedCommitteeBundle,
+    admin: oprf_types::bundle::Address,
+    client: &reqwest::Client,
+) -> Result<oprf_types::bundle::CommitteeBundle, Error> {
+    let bundle = signed.verify(admin)?;
+    check_committee_wallets(&bundle.node_urls(), &bundle.committee(), client).await?;
+    Ok(bundle.clone())
+}
+
 #[cfg(test)]
 mod tests {
     use ark_ec::AdditiveGroup;
@@ -1185,6 +1859,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_insufficient_healthy_nodes() {
+        let errors = vec![
+            NodeError::EpochMismatch(ShareEpoch::default()),
+            NodeError::Timeout(std::time::Duration::from_secs(1)),
+            NodeError::WsError(Box::new(std::io::Error::other("ws"))),
+            NodeError::EpochMismatch(ShareEpoch::default()),
+        ];
+
+        let res = aggregate_error(3, errors);
+        assert!(
+            matches!(
+                res,
+                Error::InsufficientHealthyNodes {
+                    healthy: 2,
+                    threshold: 3
+                }
+            ),
+            "Expected InsufficientHealthyNodes - got {res:?}"
+        );
+    }
+
     #[test]
     fn test_epoch_mismatch_counts_nodes_not_distinct_epochs() {
         let epoch_1 = ShareEpoch::from(1u32);
@@ -1239,4 +1935,126 @@ mod tests {
             "Should be Error::NonUniqueServices"
         );
     }
+
+    #[tokio::test]
+    async fn fetch_oprf_public_key_retries_unavailable_node() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        };
+
+        use axum::{Json, Router, http::StatusCode, response::IntoResponse, routing::get};
+
+        let key = OprfPublicKeyWithEpoch {
+            key: OprfPublicKey::from(rand::random::<ark_babyjubjub::EdwardsAffine>()),
+            epoch: ShareEpoch::default(),
+        };
+        let calls = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route(
+            "/oprf_pub/{id}",
+            get({
+                let key = key.clone();
+                let calls = Arc::clone(&calls);
+                move || async move {
+                    if calls.fetch_add(1, Ordering::Relaxed) == 0 {
+                        StatusCode::SERVICE_UNAVAILABLE.into_response()
+                    } else {
+                        Json(key).into_response()
+                    }
+                }
+            }),
+        );
+        let server = axum_test::TestServerBuilder::new()
+            .http_transport()
+            .build(router)
+            .expect("Can build test-server");
+        let urls =
+            to_oprf_pub_key_url_many([server.server_address().expect("Has address").as_str()])
+                .expect("Valid URL");
+
+        let fetched = fetch_oprf_public_key(
+            &urls,
+            1,
+            OprfKeyId::from(42usize),
+            &reqwest::Client::new(),
+            &RetryPolicy::new(2, std::time::Duration::from_millis(10)),
+        )
+        .await
+        .expect("Should succeed on retry");
+        assert_eq!(fetched, Some(key), "should return the key");
+        assert_eq!(calls.load(Ordering::Relaxed), 2, "should retry once");
+
+        calls.store(0, Ordering::Relaxed);
+        let err = fetch_oprf_public_key(
+            &urls,
+            1,
+            OprfKeyId::from(42usize),
+            &reqwest::Client::new(),
+            &RetryPolicy::new(0, std::time::Duration::from_millis(10)),
+        )
+        .await
+        .expect_err("Should fail without retries");
+        assert!(
+            matches!(err, Error::Networking(_)),
+            "should be a networking error"
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_committee_and_check_wallets() {
+        use axum::{Json, Router, routing::get};
+        use oprf_types::{api::CommitteeMember, crypto::PartyId};
+
+        let committee = Committee {
+            members: vec![CommitteeMember {
+                party_id: PartyId(0),
+                address: "0xAbC0000000000000000000000000000000000001".to_owned(),
+            }],
+        };
+        let server = |wallet: &'static str| {
+            let router = Router::new()
+                .route(
+                    "/committee",
+                    get({
+                        let committee = committee.clone();
+                        move || async move { Json(committee) }
+                    }),
+                )
+                .route("/wallet", get(move || async move { wallet }));
+            axum_test::TestServerBuilder::new()
+                .http_transport()
+                .build(router)
+                .expect("Can build test-server")
+        };
+        let registered = server("0xabc0000000000000000000000000000000000001");
+        let unregistered = server("0xabc0000000000000000000000000000000000002");
+        let services = [&registered, &unregistered]
+            .map(|server| server.server_address().expect("Has address").to_string());
+        let client = reqwest::Client::new();
+
+        let fetched = fetch_committee(
+            &to_committee_url_many(&services).expect("Valid URL"),
+            2,
+            &client,
+            &RetryPolicy::default(),
+        )
+        .await
+        .expect("Nodes agree");
+        assert_eq!(
+            fetched.as_ref(),
+            Some(&committee),
+            "should return the committee"
+        );
+
+        check_committee_wallets(&services[..1], &committee, &client)
+            .await
+            .expect("wallet is registered");
+        let err = check_committee_wallets(&services, &committee, &client)
+            .await
+            .expect_err("second wallet is not registered");
+        assert!(
+            matches!(err, Error::UnregisteredNode { ref service, .. } if *service == services[1].trim_end_matches('/')),
+            "should name the unregistered node"
+        );
+    }
 }
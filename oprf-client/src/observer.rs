@@ -0,0 +1,204 @@
+//! Opt-in structured events of the client.
+//!
+//! The client logs with `tracing`. Host applications that do not install a `tracing` subscriber (or want to forward the events to their own logging and metrics stacks) register a [`ClientObserver`] with [`observe_with`] instead.
+//!
+//! After that, every run of [`crate::distributed_oprf_core`] (and therefore [`crate::distributed_oprf`], the [`crate::aggregator`], the [`crate::Failover`] and the session pool) and of [`crate::distributed_oprf_batch`] reports:
+//! - [`ClientObserver::on_session_start`] before the nodes are contacted,
+//! - [`ClientObserver::on_node_response`] for every answer (or error) of a node, in both rounds of the protocol,
+//! - [`ClientObserver::on_complete`] or [`ClientObserver::on_error`] when the run ends.
+//!
+//! The bindings of the `wasm-bindgen` feature run the rounds one by one, so they only report the answers of the nodes.
+//!
+//! The callbacks are called on the task that runs the protocol, so they should return quickly. The authentication part of the request is never reported.
+
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use oprf_types::ShareEpoch;
+use uuid::Uuid;
+
+use crate::{Error, NodeError};
+
+/// The registered observer, unset if no observer was registered.
+static OBSERVER: OnceLock<Arc<dyn ClientObserver>> = OnceLock::new();
+
+/// Registers the observer for the rest of the process.
+///
+/// Returns the provided observer as error if an observer was registered already.
+pub fn observe_with(observer: Arc<dyn ClientObserver>) -> Result<(), Arc<dyn ClientObserver>> {
+    OBSERVER.set(observer)
+}
+
+/// Receives the events of the client, see the [module docs](self).
+///
+/// All methods default to doing nothing, so implementations only override the events they are interested in.
+pub trait ClientObserver: Send + Sync {
+    /// A run of the protocol started.
+    fn on_session_start(&self, _event: &SessionStart) {}
+
+    /// A node answered (or failed to answer) a round of the protocol.
+    fn on_node_response(&self, _event: &NodeResponse<'_>) {}
+
+    /// A run of the protocol failed.
+    fn on_error(&self, _event: &SessionFailed<'_>) {}
+
+    /// A run of the protocol succeeded.
+    fn on_complete(&self, _event: &SessionComplete) {}
+}
+
+/// The start of a run, see [`ClientObserver::on_session_start`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SessionStart {
+    /// The id of the request.
+    pub request_id: Uuid,
+    /// The number of nodes that are contacted.
+    pub services: usize,
+    /// The number of nodes required to complete the protocol.
+    pub threshold: usize,
+}
+
+/// The round of the protocol a [`NodeResponse`] belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodePhase {
+    /// The request was sent and the node answered with its commitments.
+    Init,
+    /// The challenge was sent and the node answered with its proof share.
+    Finish,
+}
+
+/// The answer of a node, see [`ClientObserver::on_node_response`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NodeResponse<'a> {
+    /// The id of the request.
+    pub request_id: Uuid,
+    /// The authority of the node.
+    pub service: &'a str,
+    /// The round of the protocol.
+    pub phase: NodePhase,
+    /// The time from sending the message until the answer (or the error) of the node.
+    pub elapsed: Duration,
+    /// The error of the node, `None` if the node answered.
+    pub error: Option<&'a NodeError>,
+}
+
+/// A failed run, see [`ClientObserver::on_error`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SessionFailed<'a> {
+    /// The id of the request.
+    pub request_id: Uuid,
+    /// The error of the run.
+    pub error: &'a Error,
+    /// The duration of the run.
+    pub elapsed: Duration,
+}
+
+/// A successful run, see [`ClientObserver::on_complete`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SessionComplete {
+    /// The id of the request.
+    pub request_id: Uuid,
+    /// The epoch of the key the nodes agreed on.
+    pub epoch: ShareEpoch,
+    /// The duration of the run.
+    pub elapsed: Duration,
+}
+
+/// A run that is reported to the observer.
+pub(crate) struct Observation {
+    request_id: Uuid,
+    stopwatch: Stopwatch,
+}
+
+impl Observation {
+    /// Reports the start of the run.
+    pub(crate) fn start(request_id: Uuid, services: usize, threshold: usize) -> Self {
+        if let Some(observer) = OBSERVER.get() {
+            observer.on_session_start(&SessionStart {
+                request_id,
+                services,
+                threshold,
+            });
+        }
+        Self {
+            request_id,
+            stopwatch: Stopwatch::start(),
+        }
+    }
+
+    /// Reports the end of the run with the agreed epoch or the error.
+    pub(crate) fn end(self, result: Result<ShareEpoch, &Error>) {
+        let Some(observer) = OBSERVER.get() else {
+            return;
+        };
+        let elapsed = self.stopwatch.elapsed();
+        match result {
+            Ok(epoch) => observer.on_complete(&SessionComplete {
+                request_id: self.request_id,
+                epoch,
+                elapsed,
+            }),
+            Err(error) => observer.on_error(&SessionFailed {
+                request_id: self.request_id,
+                error,
+                elapsed,
+            }),
+        }
+    }
+}
+
+/// Reports the answer of a node in `phase`, that was sent a message when `stopwatch` started.
+pub(crate) fn node_response(
+    request_id: Uuid,
+    service: &str,
+    phase: NodePhase,
+    stopwatch: Stopwatch,
+    error: Option<&NodeError>,
+) {
+    if let Some(observer) = OBSERVER.get() {
+        observer.on_node_response(&NodeResponse {
+            request_id,
+            service,
+            phase,
+            elapsed: stopwatch.elapsed(),
+            error,
+        });
+    }
+}
+
+/// Measures the durations of the events. Browsers have no `std::time::Instant`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    started: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    started_ms: f64,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            started: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            started_ms: js_sys::Date::now(),
+        }
+    }
+
+    pub(crate) fn elapsed(self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.started.elapsed()
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Duration::from_secs_f64(((js_sys::Date::now() - self.started_ms) / 1000.0).max(0.0))
+        }
+    }
+}
@@ -0,0 +1,784 @@
+//! Pooled multiplexed connections to the nodes.
+//!
+//! A [`SessionPool`] keeps web-socket connections to the `/oprf/multiplex` endpoint of every node open and runs the sessions of many OPRF runs on them (see the multiplexing docs of the node). High-throughput clients therefore skip the TCP and TLS handshakes of a new web-socket per session.
+//!
+//! Every connection is driven by its own task, which routes the frames of the node to the sessions by their `request_id`. A connection that carried no session for `idle_timeout` is closed by the pool, a connection that was closed by the node is replaced on the next run.
+//!
+//! The pool also keeps the [`KnownEpochs`] of its caller. After [`SessionPool::subscribe_epoch_notifications`], the pool follows the `/epoch_notifications` endpoints of the nodes and drops the cached epoch of a key as soon as a node reports a reshare, see [`crate::to_epoch_notifications_uri`].
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures::{SinkExt as _, StreamExt as _};
+use http::Uri;
+use oprf_types::api::{MultiplexedFrame, MultiplexedNodeMessage, OprfErrorKind};
+use serde::{Deserialize, Serialize, de::IgnoredAny};
+use tokio::{sync::mpsc, task::AbortHandle};
+use tokio_tungstenite::tungstenite::{self, Bytes, protocol::frame::coding::CloseCode};
+use uuid::Uuid;
+
+use crate::{
+    BlindingFactor, Connector, EpochNotifications, Error, KnownEpochs, NodeError, Resolver,
+    ServiceError, VerifiableOprfOutput,
+};
+
+/// Configuration of a [`SessionPool`].
+#[derive(Debug, Clone)]
+pub struct SessionPoolConfig {
+    /// Maximum number of connections kept open per node.
+    pub max_connections_per_node: usize,
+    /// Number of sessions that share a connection before the pool opens another one.
+    ///
+    /// Must not exceed the `max_multiplexed_sessions` of the nodes, which close further sessions with close code `1013`.
+    pub max_sessions_per_connection: usize,
+    /// Duration after which a connection that carried no session is closed.
+    ///
+    /// Should be below the `session_lifetime` of the nodes, which close idle multiplexed connections after it.
+    pub idle_timeout: Duration,
+    /// Rejects responses without a [`ShareProof`](oprf_types::api::ShareProof) with [`NodeError::InvalidShareProof`], so that the public shares of the nodes are always checked against the OPRF public key.
+    pub require_share_proofs: bool,
+    /// Duration to wait before subscribing to the epoch notifications of a node again after the subscription failed.
+    pub epoch_notifications_retry: Duration,
+}
+
+impl Default for SessionPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections_per_node: 4,
+            max_sessions_per_connection: 32,
+            idle_timeout: Duration::from_secs(20),
+            require_share_proofs: false,
+            epoch_notifications_retry: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Persistent connections to the nodes, reused across [`SessionPool::distributed_oprf`] calls. See the [module docs](self).
+///
+/// Cloning the pool is cheap and shares its connections and [`KnownEpochs`]. The connections and epoch subscriptions are closed once all clones are dropped.
+#[derive(Clone)]
+pub struct SessionPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    connector: Connector,
+    resolver: Option<Resolver>,
+    config: SessionPoolConfig,
+    connections: Mutex<HashMap<Uri, Vec<Connection>>>,
+    known_epochs: KnownEpochs,
+    epoch_subscriptions: Mutex<Vec<(Uri, AbortHandle)>>,
+}
+
+impl Drop for PoolInner {
+    fn drop(&mut self) {
+        for (_, subscription) in self
+            .epoch_subscriptions
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            subscription.abort();
+        }
+    }
+}
+
+impl SessionPool {
+    /// Creates an empty pool that connects to the nodes with the given [`Connector`].
+    #[must_use]
+    pub fn new(connector: Connector, config: SessionPoolConfig) -> Self {
+        Self::with_optional_resolver(connector, config, None)
+    }
+
+    /// Creates an empty pool like [`SessionPool::new`], but resolves the hosts of the nodes with the given [`Resolver`] instead of the system DNS.
+    #[must_use]
+    pub fn with_resolver(
+        connector: Connector,
+        config: SessionPoolConfig,
+        resolver: Resolver,
+    ) -> Self {
+        Self::with_optional_resolver(connector, config, Some(resolver))
+    }
+
+    fn with_optional_resolver(
+        connector: Connector,
+        config: SessionPoolConfig,
+        resolver: Option<Resolver>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(PoolInner {
+                connector,
+                resolver,
+                config,
+                connections: Mutex::new(HashMap::new()),
+                known_epochs: KnownEpochs::new(),
+                epoch_subscriptions: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Executes the distributed OPRF protocol like [`crate::distributed_oprf`], but runs the sessions on the pooled connections.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::distributed_oprf`].
+    pub async fn distributed_oprf<OprfRequestAuth>(
+        &self,
+        services: &[Uri],
+        threshold: usize,
+        query: ark_babyjubjub::Fq,
+        blinding_factor: BlindingFactor,
+        domain: impl Into<crate::OutputDomain>,
+        auth: OprfRequestAuth,
+    ) -> Result<VerifiableOprfOutput, Error>
+    where
+        OprfRequestAuth: Clone + Serialize + 'static,
+    {
+        crate::distributed_oprf_from(
+            &crate::SessionSource::Pool(self.clone()),
+            services,
+            threshold,
+            query,
+            blinding_factor,
+            domain.into(),
+            auth,
+        )
+        .await
+    }
+
+    /// Returns the configuration of the pool.
+    pub(crate) fn config(&self) -> &SessionPoolConfig {
+        &self.inner.config
+    }
+
+    /// Returns the [`KnownEpochs`] of the pool.
+    ///
+    /// Callers store the epoch of a finished run with [`KnownEpochs::insert`]. The entry is dropped once a subscribed node reports another epoch for the key, see [`SessionPool::subscribe_epoch_notifications`].
+    #[must_use]
+    pub fn known_epochs(&self) -> &KnownEpochs {
+        &self.inner.known_epochs
+    }
+
+    /// Follows the `/epoch_notifications` `endpoints` of the nodes (see [`crate::to_epoch_notifications_uri`]) and applies the notifications to [`SessionPool::known_epochs`].
+    ///
+    /// Every endpoint is followed by its own task, which subscribes again when the node closes the subscription and retries after `epoch_notifications_retry` if subscribing fails or the subscription lagged behind. Every (re)subscription clears the known epochs, as notifications may have been missed in between (see [`EpochNotifications::apply_to`]). Endpoints that are already followed are skipped.
+    pub fn subscribe_epoch_notifications(&self, endpoints: impl IntoIterator<Item = Uri>) {
+        let mut subscriptions = self
+            .inner
+            .epoch_subscriptions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for endpoint in endpoints {
+            if subscriptions
+                .iter()
+                .any(|(followed, _)| *followed == endpoint)
+            {
+                continue;
+            }
+            let task = tokio::spawn(follow_epoch_notifications(
+                endpoint.clone(),
+                self.inner.connector.clone(),
+                self.inner.known_epochs.clone(),
+                self.inner.config.epoch_notifications_retry,
+            ));
+            subscriptions.push((endpoint, task.abort_handle()));
+        }
+    }
+
+    /// Returns the number of open connections to `service`.
+    #[must_use]
+    pub fn connections(&self, service: &Uri) -> usize {
+        let connections = self
+            .inner
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        connections.get(service).map_or(0, |connections| {
+            connections
+                .iter()
+                .filter(|connection| !connection.commands.is_closed())
+                .count()
+        })
+    }
+
+    /// Opens the session `request_id` on the least loaded connection to `service`, connecting a new one if all are busy.
+    pub(crate) async fn open(
+        &self,
+        service: &Uri,
+        request_id: Uuid,
+    ) -> Result<PooledSession, NodeError> {
+        let connection = if let Some(connection) = self.idle_connection(service) {
+            connection
+        } else {
+            // connect without holding the lock - concurrent runs may overshoot the limit slightly
+            let connection = self.connect(service).await?;
+            self.inner
+                .connections
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(service.clone())
+                .or_default()
+                .push(connection.clone());
+            connection
+        };
+        let (inbound_tx, inbound) = mpsc::unbounded_channel();
+        connection
+            .commands
+            .send(Command::Open(request_id, inbound_tx))
+            .map_err(|_| connection_closed())?;
+        connection.open_sessions.fetch_add(1, Ordering::Relaxed);
+        Ok(PooledSession {
+            service: service
+                .authority()
+                .map_or_else(|| "unknown authority".to_string(), ToString::to_string),
+            request_id,
+            connection,
+            inbound,
+        })
+    }
+
+    /// Picks the least loaded open connection to `service`. Returns `None` if all are busy and another connection may be opened.
+    fn idle_connection(&self, service: &Uri) -> Option<Connection> {
+        let config = &self.inner.config;
+        let mut connections = self
+            .inner
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let connections = connections.entry(service.clone()).or_default();
+        connections.retain(|connection| !connection.commands.is_closed());
+        let least_loaded = connections
+            .iter()
+            .min_by_key(|connection| connection.open_sessions.load(Ordering::Relaxed))?;
+        if least_loaded.open_sessions.load(Ordering::Relaxed) < config.max_sessions_per_connection
+            || connections.len() >= config.max_connections_per_node
+        {
+            Some(least_loaded.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Opens a new connection to the multiplex endpoint of `service` and spawns its task.
+    async fn connect(&self, service: &Uri) -> Result<Connection, NodeError> {
+        let endpoint = multiplex_endpoint(service)?;
+        tracing::trace!("> opening pooled connection to {endpoint}..");
+        let ws = crate::ws::connect(
+            crate::ws::append_client_version_to_query(&endpoint, None),
+            self.inner.connector.clone(),
+            self.inner.resolver.as_ref(),
+        )
+        .await?;
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let connection = Connection {
+            commands,
+            open_sessions: Arc::new(AtomicUsize::new(0)),
+        };
+        tokio::spawn(drive_connection(
+            ws,
+            command_rx,
+            Arc::clone(&connection.open_sessions),
+            self.inner.config.idle_timeout,
+        ));
+        Ok(connection)
+    }
+}
+
+/// Applies the epoch notifications of the node at `endpoint` to `known_epochs` until the task is aborted.
+async fn follow_epoch_notifications(
+    endpoint: Uri,
+    connector: Connector,
+    known_epochs: KnownEpochs,
+    retry: Duration,
+) {
+    loop {
+        let result = match EpochNotifications::subscribe(endpoint.clone(), connector.clone()).await
+        {
+            Ok(notifications) => notifications.apply_to(&known_epochs).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(()) => tracing::trace!("epoch notifications of {endpoint} closed - resubscribing"),
+            Err(err) => {
+                tracing::warn!("epoch notifications of {endpoint} failed: {err:?}");
+                tokio::time::sleep(retry).await;
+            }
+        }
+    }
+}
+
+/// The `/multiplex` endpoint below the OPRF endpoint `service`.
+fn multiplex_endpoint(service: &Uri) -> Result<Uri, NodeError> {
+    let mut parts = service.clone().into_parts();
+    let path = service.path().trim_end_matches('/');
+    let path_and_query = match service.query() {
+        Some(query) => format!("{path}/multiplex?{query}"),
+        None => format!("{path}/multiplex"),
+    };
+    parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .map_err(|err| NodeError::Unknown(Box::new(err)))?,
+    );
+    Uri::from_parts(parts).map_err(|err| NodeError::Unknown(Box::new(err)))
+}
+
+fn connection_closed() -> NodeError {
+    NodeError::WsError(Box::new(tungstenite::Error::Io(std::io::Error::other(
+        "pooled connection closed by server",
+    ))))
+}
+
+/// A handle to a pooled connection.
+#[derive(Clone)]
+struct Connection {
+    commands: mpsc::UnboundedSender<Command>,
+    open_sessions: Arc<AtomicUsize>,
+}
+
+/// Instructions for the task of a connection.
+enum Command {
+    /// Routes the frames of `request_id` to the sender.
+    Open(Uuid, mpsc::UnboundedSender<Bytes>),
+    /// Sends the frame to the node.
+    Send(tungstenite::Message),
+    /// Stops routing the frames of `request_id`.
+    Close(Uuid),
+}
+
+/// Owns the web-socket of a connection until the node closes it or it was idle for `idle_timeout`.
+async fn drive_connection<S>(
+    mut ws: tokio_tungstenite::WebSocketStream<S>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    open_sessions: Arc<AtomicUsize>,
+    idle_timeout: Duration,
+) where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let mut sessions = HashMap::<Uuid, mpsc::UnboundedSender<Bytes>>::new();
+    let idle = tokio::time::sleep(idle_timeout);
+    tokio::pin!(idle);
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Open(request_id, inbound)) => {
+                    sessions.insert(request_id, inbound);
+                }
+                Some(Command::Send(frame)) => {
+                    if let Err(err) = ws.send(frame).await {
+                        tracing::debug!(%err, "could not send frame on pooled connection");
+                        break;
+                    }
+                }
+                Some(Command::Close(request_id)) => {
+                    sessions.remove(&request_id);
+                    if sessions.is_empty() {
+                        idle.as_mut().reset(tokio::time::Instant::now() + idle_timeout);
+                    }
+                }
+                // the pool was dropped
+                None => break,
+            },
+            frame = ws.next() => match frame {
+                Some(Ok(tungstenite::Message::Binary(bytes))) => {
+                    match ciborium::from_reader::<MultiplexedFrame<IgnoredAny>, _>(bytes.as_ref()) {
+                        Ok(MultiplexedFrame { request_id, .. }) => {
+                            if let Some(session) = sessions.get(&request_id)
+                                && session.send(bytes).is_err()
+                            {
+                                sessions.remove(&request_id);
+                            }
+                        }
+                        Err(err) => {
+                            tracing::debug!(%err, "node sent invalid frame on pooled connection");
+                            break;
+                        }
+                    }
+                }
+                Some(Ok(tungstenite::Message::Ping(_) | tungstenite::Message::Pong(_))) => {}
+                Some(Ok(tungstenite::Message::Close(frame))) => {
+                    tracing::trace!(?frame, "node closed pooled connection");
+                    break;
+                }
+                Some(Ok(_)) => {
+                    tracing::debug!("node sent unexpected frame on pooled connection");
+                    break;
+                }
+                Some(Err(err)) => {
+                    tracing::debug!(%err, "pooled connection failed");
+                    break;
+                }
+                None => break,
+            },
+            () = &mut idle, if open_sessions.load(Ordering::Relaxed) == 0 && sessions.is_empty() => {
+                tracing::trace!("closing idle pooled connection");
+                if let Err(err) = ws.close(None).await {
+                    tracing::trace!(%err, "could not close idle pooled connection");
+                }
+                break;
+            }
+        }
+    }
+    // dropping the receiver marks the connection as closed for the pool, dropping the senders fails the open sessions
+    commands.close();
+}
+
+/// A session on a pooled connection.
+pub(crate) struct PooledSession {
+    pub(crate) service: String,
+    request_id: Uuid,
+    connection: Connection,
+    inbound: mpsc::UnboundedReceiver<Bytes>,
+}
+
+impl PooledSession {
+    /// Hands the provided message to the connection, wrapped in a [`MultiplexedFrame`] of this session.
+    pub(crate) fn send<Msg: Serialize>(&mut self, msg: Msg) -> Result<(), NodeError> {
+        let frame = MultiplexedFrame {
+            request_id: self.request_id,
+            payload: msg,
+        };
+        let buf = crate::ws::encode_cbor(&frame);
+        self.connection
+            .commands
+            .send(Command::Send(tungstenite::Message::binary(buf)))
+            .map_err(|_| connection_closed())
+    }
+
+    /// Attempts to read the provided message from the connection.
+    pub(crate) async fn read<Msg: for<'de> Deserialize<'de>>(&mut self) -> Result<Msg, NodeError> {
+        let bytes = self.inbound.recv().await.ok_or_else(connection_closed)?;
+        let frame = ciborium::from_reader::<MultiplexedFrame<MultiplexedNodeMessage<Msg>>, _>(
+            bytes.as_ref(),
+        )
+        .map_err(|_| NodeError::UnexpectedMessage {
+            reason: "could not parse message from server",
+        })?;
+        match frame.payload {
+            MultiplexedNodeMessage::Message(msg) => Ok(msg),
+            MultiplexedNodeMessage::Close { code, reason } => {
+                tracing::trace!("server closed session {}", self.request_id);
+                if code == u16::from(CloseCode::Normal) {
+                    return Err(NodeError::WsError(Box::new(tungstenite::Error::Io(
+                        std::io::Error::other(
+                            "Server closed session without finishing protocol - EOF",
+                        ),
+                    ))));
+                }
+                Err(NodeError::ServiceError(ServiceError {
+                    error_code: code,
+                    msg: (!reason.is_empty()).then_some(reason),
+                    kind: OprfErrorKind::from(code),
+                }))
+            }
+            _ => Err(NodeError::UnexpectedMessage {
+                reason: "unknown multiplexed message",
+            }),
+        }
+    }
+}
+
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        self.connection
+            .open_sessions
+            .fetch_sub(1, Ordering::Relaxed);
+        if self
+            .connection
+            .commands
+            .send(Command::Close(self.request_id))
+            .is_err()
+        {
+            tracing::trace!("pooled connection already closed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use axum::{
+        Router,
+        extract::{
+            State, WebSocketUpgrade,
+            ws::{CloseFrame, Message, WebSocket},
+        },
+        response::Response,
+        routing::any,
+    };
+    use axum_test::TestServer;
+    use oprf_types::{
+        OprfKeyId, ShareEpoch,
+        api::{EpochChanged, oprf_error_codes, oprf_error_messages},
+    };
+
+    use super::*;
+
+    async fn echo(
+        State(connections): State<Arc<AtomicUsize>>,
+        upgrade: WebSocketUpgrade,
+    ) -> Response {
+        connections.fetch_add(1, Ordering::Relaxed);
+        upgrade.on_upgrade(|mut socket: WebSocket| async move {
+            while let Some(Ok(Message::Binary(bytes))) = socket.recv().await {
+                let frame: MultiplexedFrame<u64> =
+                    ciborium::from_reader(bytes.as_ref()).expect("valid frame");
+                let payload = if frame.payload == 0 {
+                    MultiplexedNodeMessage::Close {
+                        code: 4001,
+                        reason: "zero".to_owned(),
+                    }
+                } else {
+                    MultiplexedNodeMessage::Message(frame.payload)
+                };
+                let mut buf = Vec::new();
+                ciborium::into_writer(
+                    &MultiplexedFrame {
+                        request_id: frame.request_id,
+                        payload,
+                    },
+                    &mut buf,
+                )
+                .expect("can serialize");
+                if socket.send(Message::Binary(buf.into())).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Pushes that key 42 changed to epoch 1 and keeps the subscription open.
+    async fn epoch_notifications(upgrade: WebSocketUpgrade) -> Response {
+        upgrade.on_upgrade(|mut socket: WebSocket| async move {
+            let mut buf = Vec::new();
+            ciborium::into_writer(
+                &EpochChanged {
+                    oprf_key_id: OprfKeyId::from(42usize),
+                    previous: Some(ShareEpoch::default()),
+                    epoch: ShareEpoch::from(1u32),
+                },
+                &mut buf,
+            )
+            .expect("can serialize");
+            if socket.send(Message::Binary(buf.into())).await.is_ok() {
+                while socket.recv().await.is_some() {}
+            }
+        })
+    }
+
+    /// Closes every subscription as lagged behind, counting the subscriptions.
+    async fn lagged_epoch_notifications(
+        State(subscriptions): State<Arc<AtomicUsize>>,
+        upgrade: WebSocketUpgrade,
+    ) -> Response {
+        subscriptions.fetch_add(1, Ordering::Relaxed);
+        upgrade.on_upgrade(|mut socket: WebSocket| async move {
+            let close_frame = CloseFrame {
+                code: oprf_error_codes::NOTIFICATIONS_LAGGED,
+                reason: oprf_error_messages::NOTIFICATIONS_LAGGED.into(),
+            };
+            socket.send(Message::Close(Some(close_frame))).await.ok();
+        })
+    }
+
+    fn server() -> (TestServer, Arc<AtomicUsize>) {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let router = Router::new()
+            .route("/api/test/oprf/multiplex", any(echo))
+            .route("/epoch_notifications", any(epoch_notifications))
+            .with_state(Arc::clone(&connections));
+        let server = TestServer::builder()
+            .http_transport()
+            .build(router)
+            .expect("can build test server");
+        (server, connections)
+    }
+
+    fn service(server: &TestServer) -> Uri {
+        let mut url = server.server_address().expect("has address");
+        url.set_scheme("ws").expect("valid scheme");
+        format!("{url}api/test/oprf").parse().expect("valid uri")
+    }
+
+    #[tokio::test]
+    async fn sessions_share_connections() {
+        let (server, connections) = server();
+        let service = service(&server);
+        let pool = SessionPool::new(
+            Connector::Plain,
+            SessionPoolConfig {
+                max_connections_per_node: 2,
+                max_sessions_per_connection: 2,
+                ..Default::default()
+            },
+        );
+
+        let mut first = pool.open(&service, Uuid::new_v4()).await.expect("can open");
+        let mut second = pool.open(&service, Uuid::new_v4()).await.expect("can open");
+        assert_eq!(
+            connections.load(Ordering::Relaxed),
+            1,
+            "two sessions share a connection"
+        );
+        second.send(2_u64).expect("can send");
+        first.send(1_u64).expect("can send");
+        assert_eq!(
+            first.read::<u64>().await.expect("can read"),
+            1,
+            "first echo"
+        );
+        assert_eq!(
+            second.read::<u64>().await.expect("can read"),
+            2,
+            "second echo"
+        );
+
+        let third = pool.open(&service, Uuid::new_v4()).await.expect("can open");
+        assert_eq!(
+            connections.load(Ordering::Relaxed),
+            2,
+            "a full connection opens another"
+        );
+        drop((first, second, third));
+        let _fourth = pool.open(&service, Uuid::new_v4()).await.expect("can open");
+        assert_eq!(
+            connections.load(Ordering::Relaxed),
+            2,
+            "finished sessions free their connection"
+        );
+        assert_eq!(pool.connections(&service), 2, "two open connections");
+    }
+
+    #[tokio::test]
+    async fn closed_session_is_service_error() {
+        let (server, _) = server();
+        let service = service(&server);
+        let pool = SessionPool::new(Connector::Plain, SessionPoolConfig::default());
+
+        let mut session = pool.open(&service, Uuid::new_v4()).await.expect("can open");
+        session.send(0_u64).expect("can send");
+        let err = session.read::<u64>().await.expect_err("session is closed");
+        let NodeError::ServiceError(err) = err else {
+            panic!("expected service error, got {err:?}");
+        };
+        assert_eq!(err.error_code, 4001, "close code is forwarded");
+        assert_eq!(err.msg.as_deref(), Some("zero"), "reason is forwarded");
+    }
+
+    #[tokio::test]
+    async fn idle_connections_are_evicted() {
+        let (server, connections) = server();
+        let service = service(&server);
+        let pool = SessionPool::new(
+            Connector::Plain,
+            SessionPoolConfig {
+                idle_timeout: Duration::from_millis(100),
+                ..Default::default()
+            },
+        );
+
+        drop(pool.open(&service, Uuid::new_v4()).await.expect("can open"));
+        assert_eq!(pool.connections(&service), 1, "connection is pooled");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(pool.connections(&service), 0, "idle connection is closed");
+
+        drop(pool.open(&service, Uuid::new_v4()).await.expect("can open"));
+        assert_eq!(
+            connections.load(Ordering::Relaxed),
+            2,
+            "evicted connection is replaced"
+        );
+    }
+
+    #[tokio::test]
+    async fn epoch_notifications_invalidate_known_epochs() {
+        let (server, _) = server();
+        let mut url = server.server_address().expect("has address");
+        url.set_scheme("ws").expect("valid scheme");
+        let endpoint = crate::to_epoch_notifications_uri(url.as_str()).expect("valid uri");
+        let pool = SessionPool::new(Connector::Plain, SessionPoolConfig::default());
+        let oprf_key_id = OprfKeyId::from(42usize);
+        pool.known_epochs()
+            .insert(oprf_key_id, ShareEpoch::default());
+
+        pool.subscribe_epoch_notifications([endpoint.clone(), endpoint]);
+        assert_eq!(
+            pool.inner
+                .epoch_subscriptions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .len(),
+            1,
+            "should follow every endpoint once"
+        );
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while pool.known_epochs().get(oprf_key_id).is_some() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("should drop the outdated epoch");
+    }
+
+    #[tokio::test]
+    async fn resubscriptions_clear_known_epochs() {
+        let subscriptions = Arc::new(AtomicUsize::new(0));
+        let router = Router::new()
+            .route("/epoch_notifications", any(lagged_epoch_notifications))
+            .with_state(Arc::clone(&subscriptions));
+        let server = TestServer::builder()
+            .http_transport()
+            .build(router)
+            .expect("can build test server");
+        let mut url = server.server_address().expect("has address");
+        url.set_scheme("ws").expect("valid scheme");
+        let endpoint = crate::to_epoch_notifications_uri(url.as_str()).expect("valid uri");
+
+        let known_epochs = KnownEpochs::new();
+        let oprf_key_id = OprfKeyId::from(42usize);
+        known_epochs.insert(oprf_key_id, ShareEpoch::default());
+        let err = EpochNotifications::subscribe(endpoint.clone(), Connector::Plain)
+            .await
+            .expect("can subscribe")
+            .apply_to(&known_epochs)
+            .await
+            .expect_err("subscription lagged behind");
+        let NodeError::ServiceError(err) = err else {
+            panic!("expected service error, got {err:?}");
+        };
+        assert_eq!(err.error_code, oprf_error_codes::NOTIFICATIONS_LAGGED);
+        assert_eq!(
+            known_epochs.get(oprf_key_id),
+            None,
+            "should drop the epochs known before the subscription"
+        );
+
+        let pool = SessionPool::new(
+            Connector::Plain,
+            SessionPoolConfig {
+                epoch_notifications_retry: Duration::from_millis(10),
+                ..Default::default()
+            },
+        );
+        subscriptions.store(0, Ordering::Relaxed);
+        pool.subscribe_epoch_notifications([endpoint]);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while subscriptions.load(Ordering::Relaxed) < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            pool.known_epochs()
+                .insert(oprf_key_id, ShareEpoch::default());
+            while pool.known_epochs().get(oprf_key_id).is_some() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("should drop the known epochs when resubscribing after a lag");
+    }
+}
@@ -0,0 +1,197 @@
+//! Waiting for a node set to serve an epoch.
+//!
+//! After triggering a key-gen or reshare, orchestration pipelines need to know when the nodes serve the new [`ShareEpoch`] before they switch clients over. [`await_epoch`] polls `GET {service}/oprf_pub/{key_id}` of every node until the node serves the target epoch (or a later one), and reports the readiness of every node once all nodes are ready or the timeout elapsed.
+use std::time::Duration;
+
+use backon::BackoffBuilder as _;
+use futures::future::join_all;
+use oprf_types::{OprfKeyId, ShareEpoch, api::OprfPublicKeyWithEpoch};
+use tokio::time::Instant;
+use tracing::instrument;
+use url::Url;
+
+use crate::{RetryPolicy, fetch_from_service_once, to_key_info_url};
+
+/// The readiness of a single node for the target epoch of [`await_epoch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EpochReadiness {
+    /// The node serves the target epoch or a later one.
+    Ready(ShareEpoch),
+    /// The node serves an older epoch.
+    Pending(ShareEpoch),
+    /// The node does not know the key (yet).
+    NotFound,
+    /// The last request to the node failed, e.g. because it could not be reached.
+    Unreachable(String),
+}
+
+impl EpochReadiness {
+    /// Returns `true` if the node serves the target epoch or a later one.
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        matches!(self, Self::Ready(_))
+    }
+}
+
+/// Polls a set of OPRF nodes until every node serves `epoch` (or a later one) for the given [`OprfKeyId`], or until `timeout` elapsed.
+///
+/// Every node is polled independently via `GET {service}/oprf_pub/{key_id}` (see [`crate::to_oprf_pub_key_url`]). The delays between two polls of a node follow the jittered exponential backoff of `retry_policy`, but polling continues until the timeout regardless of its `max_retries`.
+///
+/// Returns the last observed [`EpochReadiness`] per node, in the order of `urls`. Use [`EpochReadiness::is_ready`] to check whether a node is ready.
+///
+/// # Arguments
+/// - `urls`: Base URLs (up to `/oprf_pub`) of the OPRF nodes to poll
+/// - `key_id`: The [`OprfKeyId`] to check
+/// - `epoch`: The [`ShareEpoch`] the nodes must serve, e.g. the epoch after a triggered reshare
+/// - `timeout`: Max time to wait for all nodes
+/// - `client`: The [`reqwest::Client`] used to send the requests
+/// - `retry_policy`: The delays between two polls, e.g. [`RetryPolicy::default`]
+#[instrument(level = "debug", skip(client))]
+pub async fn await_epoch(
+    urls: &[Url],
+    key_id: OprfKeyId,
+    epoch: ShareEpoch,
+    timeout: Duration,
+    client: &reqwest::Client,
+    retry_policy: &RetryPolicy,
+) -> Vec<(Url, EpochReadiness)> {
+    let deadline = Instant::now() + timeout;
+    join_all(urls.iter().map(|url| async move {
+        let readiness = await_epoch_on_node(
+            &to_key_info_url(url, key_id),
+            epoch,
+            deadline,
+            client,
+            retry_policy,
+        )
+        .await;
+        (url.clone(), readiness)
+    }))
+    .await
+}
+
+/// Polls a single node until it is ready or the `deadline` passed.
+async fn await_epoch_on_node(
+    url: &Url,
+    epoch: ShareEpoch,
+    deadline: Instant,
+    client: &reqwest::Client,
+    retry_policy: &RetryPolicy,
+) -> EpochReadiness {
+    let mut delays = retry_policy.backoff().without_max_times().build();
+    let mut readiness = EpochReadiness::Unreachable("timeout before first response".to_owned());
+    loop {
+        match tokio::time::timeout_at(
+            deadline,
+            fetch_from_service_once::<OprfPublicKeyWithEpoch>(url, client),
+        )
+        .await
+        {
+            Ok(Ok(Some(response))) if response.epoch >= epoch => {
+                return EpochReadiness::Ready(response.epoch);
+            }
+            Ok(Ok(Some(response))) => readiness = EpochReadiness::Pending(response.epoch),
+            Ok(Ok(None)) => readiness = EpochReadiness::NotFound,
+            Ok(Err(err)) => readiness = EpochReadiness::Unreachable(err.to_string()),
+            Err(_) => return readiness,
+        }
+        let delay = delays.next().unwrap_or(Duration::from_secs(1));
+        if Instant::now() + delay >= deadline {
+            return readiness;
+        }
+        tracing::debug!("{url} not ready ({readiness:?}) - polling again in {delay:?}");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use axum::{Json, Router, extract::State, routing::get};
+    use axum_test::TestServer;
+    use oprf_types::crypto::OprfPublicKey;
+
+    use super::*;
+
+    /// Serves an epoch that increases with every request, starting at `0`.
+    fn server(polls: Arc<AtomicU32>) -> TestServer {
+        let router = Router::new()
+            .route(
+                "/oprf_pub/{id}",
+                get(|State(polls): State<Arc<AtomicU32>>| async move {
+                    let epoch = polls.fetch_add(1, Ordering::Relaxed);
+                    Json(OprfPublicKeyWithEpoch {
+                        key: OprfPublicKey::new(ark_babyjubjub::EdwardsAffine::default()),
+                        epoch: ShareEpoch::new(epoch),
+                    })
+                }),
+            )
+            .with_state(polls);
+        TestServer::builder()
+            .http_transport()
+            .build(router)
+            .expect("can build test server")
+    }
+
+    fn url(server: &TestServer) -> Url {
+        server
+            .server_address()
+            .expect("has address")
+            .join("oprf_pub")
+            .expect("valid url")
+    }
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy::new(1, Duration::from_millis(10)).with_max_delay(Duration::from_millis(20))
+    }
+
+    #[tokio::test]
+    async fn await_epoch_until_ready() {
+        let polls = Arc::new(AtomicU32::new(0));
+        let server = server(Arc::clone(&polls));
+        let readiness = await_epoch(
+            &[url(&server)],
+            OprfKeyId::from(42usize),
+            ShareEpoch::new(3),
+            Duration::from_secs(5),
+            &reqwest::Client::new(),
+            &retry_policy(),
+        )
+        .await;
+        assert_eq!(readiness.len(), 1);
+        assert_eq!(readiness[0].1, EpochReadiness::Ready(ShareEpoch::new(3)));
+        assert_eq!(polls.load(Ordering::Relaxed), 4, "polled until epoch 3");
+    }
+
+    #[tokio::test]
+    async fn await_epoch_reports_pending_and_unreachable_nodes() {
+        let server = server(Arc::new(AtomicU32::new(0)));
+        let unreachable = Url::parse("http://127.0.0.1:1/oprf_pub").expect("valid url");
+        let readiness = await_epoch(
+            &[url(&server), unreachable.clone()],
+            OprfKeyId::from(42usize),
+            ShareEpoch::new(u32::MAX),
+            Duration::from_millis(200),
+            &reqwest::Client::new(),
+            &retry_policy(),
+        )
+        .await;
+        assert!(
+            matches!(readiness[0].1, EpochReadiness::Pending(_)),
+            "node serves an older epoch: {:?}",
+            readiness[0].1
+        );
+        assert_eq!(readiness[1].0, unreachable);
+        assert!(
+            matches!(readiness[1].1, EpochReadiness::Unreachable(_)),
+            "node cannot be reached: {:?}",
+            readiness[1].1
+        );
+        assert!(!readiness.iter().any(|(_, readiness)| readiness.is_ready()));
+    }
+}
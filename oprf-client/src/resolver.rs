@@ -0,0 +1,70 @@
+//! Custom host resolution for the web-socket connections to the nodes.
+//!
+//! By default, the client resolves the hosts of the nodes with the system DNS. Kubernetes-internal service discovery or split-horizon DNS may require a different resolution. A [`Resolver`] maps the host of a node to the socket addresses the client connects to instead. The URI of the node stays as is, so the `Host` header and the TLS server name still name the host of the node.
+//!
+//! Use [`crate::Failover::with_resolver`] or [`crate::SessionPool::with_resolver`] to open the sessions with a resolver. Browsers always resolve hosts themselves, so the resolver is ignored on `wasm` targets.
+//!
+//! ```ignore
+//! let resolver = Resolver::new(|host: &str, port: u16| match host {
+//!     "node0.oprf.example" => Ok(vec![SocketAddr::from(([10, 0, 0, 1], port))]),
+//!     _ => Err(std::io::Error::other("unknown node")),
+//! });
+//! let failover = Failover::new(Connector::Plain, FailoverConfig::default()).with_resolver(resolver);
+//! ```
+use std::{fmt, io, net::SocketAddr, sync::Arc};
+
+use futures::future::{BoxFuture, FutureExt as _};
+
+/// Resolves the host of a node to the socket addresses to connect to, see the [module docs](self).
+///
+/// Implemented for closures `Fn(&str, u16) -> io::Result<Vec<SocketAddr>>`. Implement it directly for resolvers that resolve asynchronously, e.g., with a DNS client.
+pub trait Resolve: Send + Sync {
+    /// Resolves `host` to the socket addresses to connect to. `port` is the port of the URI of the node, or the default port of its scheme.
+    ///
+    /// The addresses are tried in order until a connection succeeds.
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>>;
+}
+
+impl<F> Resolve for F
+where
+    F: Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync,
+{
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        futures::future::ready(self(host, port)).boxed()
+    }
+}
+
+/// A shared [`Resolve`] implementation used instead of the system DNS.
+///
+/// Cloning the resolver is cheap.
+#[derive(Clone)]
+pub struct Resolver(Arc<dyn Resolve>);
+
+impl Resolver {
+    /// Creates a resolver from the given [`Resolve`] implementation or closure.
+    #[must_use]
+    pub fn new(resolve: impl Resolve + 'static) -> Self {
+        Self(Arc::new(resolve))
+    }
+
+    /// Resolves `host` to the socket addresses to connect to, see [`Resolve::resolve`].
+    ///
+    /// The brackets of IPv6 hosts (e.g. `[::1]`) are removed.
+    pub(crate) async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addrs = self.0.resolve(host, port).await?;
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("resolver returned no addresses for {host}"),
+            ));
+        }
+        Ok(addrs)
+    }
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resolver").finish_non_exhaustive()
+    }
+}
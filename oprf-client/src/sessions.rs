@@ -5,7 +5,8 @@
 use std::collections::HashMap;
 
 use crate::NodeError;
-use crate::ws::WebSocketSession;
+use crate::observer::{self, NodePhase, Stopwatch};
+use crate::ws::{NodeSession, WebSocketSession};
 
 use futures::stream::{FuturesUnordered, StreamExt};
 use http::Uri;
@@ -14,10 +15,13 @@ use oprf_core::ddlog_equality::shamir::{
 };
 use oprf_types::{
     ShareEpoch,
-    api::{OprfRequest, OprfResponse},
+    api::{
+        OprfBatchChallenge, OprfBatchProofShares, OprfRequest, OprfResponse, ResponseSignature,
+        ShareProof,
+    },
     crypto::{OprfPublicKey, PartyId},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tracing::instrument;
 use uuid::Uuid;
 
@@ -26,52 +30,115 @@ use crate::Connector;
 /// Holds the active OPRF sessions with multiple nodes.
 #[derive(Default)]
 pub struct OprfSessions {
-    pub(super) ws: Vec<WebSocketSession>,
+    pub(super) request_id: Uuid,
+    pub(super) ws: Vec<NodeSession>,
     pub(super) party_ids: Vec<PartyId>,
     pub(super) commitments: Vec<PartialDLogCommitmentsShamir>,
+    pub(super) batch_commitments: Vec<Vec<PartialDLogCommitmentsShamir>>,
     pub(super) oprf_public_keys: Vec<OprfPublicKey>,
+    pub(super) share_proofs: Vec<Option<ShareProof>>,
+    pub(super) signatures: HashMap<PartyId, ResponseSignature>,
     pub(super) epoch: ShareEpoch,
 }
 
 impl OprfSessions {
     /// Creates an empty [`OprfSessions`] with preallocated capacity.
     ///
-    fn with_capacity(epoch: ShareEpoch, capacity: usize) -> Self {
+    fn with_capacity(request_id: Uuid, epoch: ShareEpoch, capacity: usize) -> Self {
         Self {
+            request_id,
             epoch,
             ws: Vec::with_capacity(capacity),
             party_ids: Vec::with_capacity(capacity),
             commitments: Vec::with_capacity(capacity),
+            batch_commitments: Vec::with_capacity(capacity),
             oprf_public_keys: Vec::with_capacity(capacity),
+            share_proofs: Vec::with_capacity(capacity),
+            signatures: HashMap::with_capacity(capacity),
         }
     }
 
     /// Adds a node's response to the sessions.
-    fn push(&mut self, ws: WebSocketSession, response: OprfResponse) -> Result<(), String> {
+    fn push(&mut self, ws: NodeSession, response: OprfResponse) -> Result<(), String> {
         let OprfResponse {
             commitments,
             party_id,
             oprf_pub_key_with_epoch,
+            batch_commitments,
+            share_proof,
+            signature,
         } = response;
         if let Some(position) = self
             .party_ids
             .iter()
             .position(|hay| *hay == response.party_id)
         {
-            return Err(self.ws[position].service.clone());
+            return Err(self.ws[position].service().to_owned());
         }
         self.ws.push(ws);
         self.party_ids.push(party_id);
         self.commitments.push(commitments);
+        self.batch_commitments.push(batch_commitments);
         self.oprf_public_keys.push(oprf_pub_key_with_epoch.key);
+        self.share_proofs.push(share_proof);
+        if let Some(signature) = signature {
+            self.signatures.insert(party_id, signature);
+        }
         Ok(())
     }
 
+    /// Checks that every node of the sessions signed its response with the wallet registered in the `committee` for its party id, e.g., the committee from [`crate::fetch_committee`] or a committee bundle.
+    ///
+    /// Call this before [`finish_sessions`] to make sure that only registered nodes answered. The nodes must be configured to sign their responses.
+    ///
+    /// # Errors
+    /// Returns [`NodeError::InvalidResponseSignature`] for the first node whose response is not signed by its registered wallet.
+    #[cfg(feature = "signed-responses")]
+    pub fn verify_signatures(
+        &self,
+        committee: &oprf_types::api::Committee,
+    ) -> Result<(), NodeError> {
+        for (idx, party_id) in self.party_ids.iter().enumerate() {
+            let message = oprf_types::signed_response::signing_message(
+                self.request_id,
+                &self.commitments[idx],
+                &self.batch_commitments[idx],
+                *party_id,
+                self.epoch,
+            );
+            check_signer(
+                self.signatures.get(party_id),
+                &message,
+                *party_id,
+                committee,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the party ids of the nodes that answered first and hold the sessions, sorted ascending.
+    #[must_use]
+    pub fn party_ids(&self) -> &[PartyId] {
+        &self.party_ids
+    }
+
     /// Returns the number of sessions currently stored.
     fn len(&self) -> usize {
         self.ws.len()
     }
 
+    /// Returns the commitments of every session for the `query`-th query of a batch session, `0` being the `blinded_query` of the request.
+    pub(super) fn commitments_of(&self, query: usize) -> Vec<PartialDLogCommitmentsShamir> {
+        match query.checked_sub(1) {
+            None => self.commitments.clone(),
+            Some(idx) => self
+                .batch_commitments
+                .iter()
+                .map(|commitments| commitments[idx].clone())
+                .collect(),
+        }
+    }
+
     /// Sorts the sessions, party IDs and commitments by party ID in ascending order.
     fn sort_by_party_id(&mut self) {
         let mut combined = self
@@ -79,48 +146,212 @@ impl OprfSessions {
             .drain(..)
             .zip(self.party_ids.drain(..))
             .zip(self.commitments.drain(..))
+            .zip(self.batch_commitments.drain(..))
             .zip(self.oprf_public_keys.drain(..))
-            .map(|(((ws, party_id), commitments), oprf_public_key)| {
-                (ws, party_id, commitments, oprf_public_key)
-            })
+            .zip(self.share_proofs.drain(..))
+            .map(
+                |(
+                    ((((ws, party_id), commitments), batch_commitments), oprf_public_key),
+                    share_proof,
+                )| {
+                    (
+                        ws,
+                        party_id,
+                        commitments,
+                        batch_commitments,
+                        oprf_public_key,
+                        share_proof,
+                    )
+                },
+            )
             .collect::<Vec<_>>();
-        combined.sort_by_key(|(_, party_id, _, _)| *party_id);
-        for (ws, party_id, commitments, oprf_public_key) in combined {
+        combined.sort_by_key(|(_, party_id, _, _, _, _)| *party_id);
+        for (ws, party_id, commitments, batch_commitments, oprf_public_key, share_proof) in combined
+        {
             self.ws.push(ws);
             self.party_ids.push(party_id);
             self.commitments.push(commitments);
+            self.batch_commitments.push(batch_commitments);
             self.oprf_public_keys.push(oprf_public_key);
+            self.share_proofs.push(share_proof);
         }
     }
 }
 
-/// Tries to establish a web-socket connection to the given service. On success sends the provided `req` to the service and reads the [`OprfResponse`].
+/// Where the sessions of a run are opened.
+#[derive(Clone)]
+pub(crate) enum SessionSource {
+    /// A new web-socket connection per session.
+    Connect {
+        connector: Connector,
+        require_share_proofs: bool,
+    },
+    /// A session on a pooled multiplexed connection.
+    #[cfg(not(target_arch = "wasm32"))]
+    Pool(crate::SessionPool),
+    /// A new web-socket connection per session, with per-node timeouts and retries.
+    Failover(crate::Failover),
+}
+
+impl SessionSource {
+    /// A new web-socket connection per session, accepting responses without [`ShareProof`].
+    pub(crate) fn connect(connector: Connector) -> Self {
+        SessionSource::Connect {
+            connector,
+            require_share_proofs: false,
+        }
+    }
+
+    /// Whether responses without a [`ShareProof`] are rejected.
+    pub(crate) fn require_share_proofs(&self) -> bool {
+        match self {
+            SessionSource::Connect {
+                require_share_proofs,
+                ..
+            } => *require_share_proofs,
+            #[cfg(not(target_arch = "wasm32"))]
+            SessionSource::Pool(pool) => pool.config().require_share_proofs,
+            SessionSource::Failover(failover) => failover.config.require_share_proofs,
+        }
+    }
+
+    /// Opens a session for `request_id` with the given service.
+    async fn open(&self, service: Uri, request_id: Uuid) -> Result<NodeSession, NodeError> {
+        match self {
+            SessionSource::Connect { connector, .. } => Ok(NodeSession::Direct(
+                WebSocketSession::new(service, request_id, connector.clone(), None).await?,
+            )),
+            #[cfg(not(target_arch = "wasm32"))]
+            SessionSource::Pool(pool) => {
+                Ok(NodeSession::Pooled(pool.open(&service, request_id).await?))
+            }
+            SessionSource::Failover(failover) => Ok(NodeSession::Direct(
+                WebSocketSession::new(
+                    service,
+                    request_id,
+                    failover.connector.clone(),
+                    failover.resolver.as_ref(),
+                )
+                .await?,
+            )),
+        }
+    }
+}
+
+/// Tries to open a session with the given service. On success sends the provided `req` to the service and reads the [`OprfResponse`].
 ///
-/// Returns the [`WebSocketSession`] and the response on success.
-#[instrument(level = "trace", skip(req, connector))]
+/// Returns the [`NodeSession`] and the response on success. Fails if the response of a batch session does not carry one commitment per query, or if the response carries a [`ShareProof`] that does not verify. Responses without a [`ShareProof`] fail with [`NodeError::InvalidShareProof`] if the `source` requires share proofs.
+#[instrument(level = "trace", skip(req, source))]
 async fn init_session<Auth: Serialize>(
     service: Uri,
     request_id: Uuid,
     req: OprfRequest<Auth>,
-    connector: Connector,
-) -> Result<(WebSocketSession, OprfResponse), NodeError> {
-    let mut session = WebSocketSession::new(service, request_id, connector).await?;
+    source: SessionSource,
+) -> Result<(NodeSession, OprfResponse), NodeError> {
+    let batch_len = req.batch.len();
+    let blinded_queries = req.blinded_queries().collect::<Vec<_>>();
+    let require_share_proofs = source.require_share_proofs();
+    let mut session = source.open(service, request_id).await?;
     session.send(req).await?;
     let response = session.read::<OprfResponse>().await?;
+    if response.batch_commitments.len() != batch_len {
+        return Err(NodeError::UnexpectedMessage {
+            reason: "number of commitments does not match the batch",
+        });
+    }
+    match &response.share_proof {
+        Some(share_proof) => verify_share_proof(share_proof, &response, &blinded_queries)?,
+        None if require_share_proofs => return Err(NodeError::InvalidShareProof),
+        None => {}
+    }
     Ok((session, response))
 }
 
-/// Write the `req` request to the provided [`WebSocketSession`].
+/// Verifies that every commitment of the `response` was computed with the share behind [`ShareProof::public_share`].
+fn verify_share_proof(
+    share_proof: &ShareProof,
+    response: &OprfResponse,
+    blinded_queries: &[ark_babyjubjub::EdwardsAffine],
+) -> Result<(), NodeError> {
+    if share_proof.proofs.len() != blinded_queries.len() {
+        return Err(NodeError::InvalidShareProof);
+    }
+    let commitments = std::iter::once(&response.commitments).chain(&response.batch_commitments);
+    for ((commitments, proof), blinded_query) in
+        commitments.zip(&share_proof.proofs).zip(blinded_queries)
+    {
+        commitments
+            .verify_share_proof(proof, *blinded_query, share_proof.public_share)
+            .map_err(|_| NodeError::InvalidShareProof)?;
+    }
+    Ok(())
+}
+
+/// Checks that the `response` to the session `request_id` is signed by the wallet registered in the `committee` for its party id.
+///
+/// Use this for responses read outside of [`init_sessions`], e.g., from a transcript. See [`OprfSessions::verify_signatures`] for the sessions of a run.
 ///
-/// On success, returns the parsed [`DLogProofShareShamir`].
+/// # Errors
+/// Returns [`NodeError::InvalidResponseSignature`] if the response is not signed, the party id is not part of the `committee` or the response was signed by another wallet.
+#[cfg(feature = "signed-responses")]
+pub fn verify_response_signature(
+    response: &OprfResponse,
+    request_id: Uuid,
+    committee: &oprf_types::api::Committee,
+) -> Result<(), NodeError> {
+    check_signer(
+        response.signature.as_ref(),
+        &response.signing_message(request_id),
+        response.party_id,
+        committee,
+    )
+}
+
+/// Checks that `signature` of `message` was created by the wallet of `party_id` in the `committee`.
+#[cfg(feature = "signed-responses")]
+fn check_signer(
+    signature: Option<&ResponseSignature>,
+    message: &[u8],
+    party_id: PartyId,
+    committee: &oprf_types::api::Committee,
+) -> Result<(), NodeError> {
+    use oprf_types::signed_response::Address;
+
+    let registered = committee
+        .members
+        .iter()
+        .find(|member| member.party_id == party_id)
+        .and_then(|member| member.address.parse::<Address>().ok());
+    let signer = signature.and_then(|signature| signature.recover_signer(message));
+    match (registered, signer) {
+        (Some(registered), Some(signer)) if registered == signer => Ok(()),
+        _ => Err(NodeError::InvalidResponseSignature(party_id)),
+    }
+}
+
+/// Write the `req` request to the provided [`NodeSession`].
+///
+/// On success, returns the parsed response, i.e., the [`DLogProofShareShamir`] or the [`OprfBatchProofShares`] of a batch session.
 #[instrument(level = "trace", skip_all)]
-async fn finish_session(
-    mut session: WebSocketSession,
-    req: DLogCommitmentsShamir,
-) -> Result<DLogProofShareShamir, NodeError> {
-    session.send(req).await?;
-    let resp = session.read().await?;
-    Ok(resp)
+async fn finish_session<Req: Serialize, Resp: for<'de> Deserialize<'de>>(
+    mut session: NodeSession,
+    request_id: Uuid,
+    req: Req,
+) -> Result<Resp, NodeError> {
+    let stopwatch = Stopwatch::start();
+    let result = async {
+        session.send(req).await?;
+        session.read().await
+    }
+    .await;
+    observer::node_response(
+        request_id,
+        session.service(),
+        NodePhase::Finish,
+        stopwatch,
+        result.as_ref().err(),
+    );
+    result
 }
 
 /// Completes all OPRF sessions in parallel by sending the provided [`DLogCommitmentsShamir`] to the open sessions.
@@ -136,15 +367,41 @@ pub async fn finish_sessions(
     sessions: OprfSessions,
     req: DLogCommitmentsShamir,
 ) -> Result<Vec<DLogProofShareShamir>, NodeError> {
+    let request_id = sessions.request_id;
     futures::future::try_join_all(
         sessions
             .ws
             .into_iter()
-            .map(|service| finish_session(service, req.clone())),
+            .map(|service| finish_session(service, request_id, req.clone())),
     )
     .await
 }
 
+/// Like [`finish_sessions`], but sends the [`OprfBatchChallenge`] of a batch session.
+///
+/// Fails fast if any single request errors or if a node does not answer with one proof share per challenge.
+#[instrument(level = "debug", skip_all)]
+pub(crate) async fn finish_batch_sessions(
+    sessions: OprfSessions,
+    req: OprfBatchChallenge,
+) -> Result<Vec<OprfBatchProofShares>, NodeError> {
+    let num_queries = req.challenges.len();
+    let request_id = sessions.request_id;
+    let responses = futures::future::try_join_all(sessions.ws.into_iter().map(|service| {
+        finish_session::<_, OprfBatchProofShares>(service, request_id, req.clone())
+    }))
+    .await?;
+    if responses
+        .iter()
+        .any(|response| response.proof_shares.len() != num_queries)
+    {
+        return Err(NodeError::UnexpectedMessage {
+            reason: "number of proof shares does not match the batch",
+        });
+    }
+    Ok(responses)
+}
+
 /// Initializes new OPRF sessions by opening a web-socket at `/api/{module}/oprf` on a list of nodes, collecting responses until the given `threshold` is met.
 ///
 /// Nodes are queried concurrently. Errors from some services are logged and ignored, unless they prevent reaching the threshold.
@@ -157,17 +414,58 @@ pub async fn init_sessions<OprfRequestAuth: Clone + Serialize + 'static>(
     threshold: usize,
     req: OprfRequest<OprfRequestAuth>,
     connector: Connector,
+) -> Result<OprfSessions, Vec<NodeError>> {
+    init_sessions_from(
+        &SessionSource::connect(connector),
+        request_id,
+        oprf_services,
+        threshold,
+        req,
+    )
+    .await
+}
+
+/// Like [`init_sessions`], but opens the sessions at the given [`SessionSource`].
+pub(crate) async fn init_sessions_from<OprfRequestAuth: Clone + Serialize + 'static>(
+    source: &SessionSource,
+    request_id: Uuid,
+    oprf_services: &[Uri],
+    threshold: usize,
+    req: OprfRequest<OprfRequestAuth>,
 ) -> Result<OprfSessions, Vec<NodeError>> {
     let mut futures: FuturesUnordered<_> = oprf_services
         .iter()
         .map(|service| {
-            let connector = connector.clone();
+            let source = source.clone();
             let req = req.clone();
             let service = service.to_owned();
             async move {
-                init_session(service.clone(), request_id, req, connector)
+                let stopwatch = Stopwatch::start();
+                let result = if let SessionSource::Failover(failover) = &source {
+                    let config = failover.config;
+                    let attempt_service = service.clone();
+                    crate::failover::with_failover(&config, &service, move || {
+                        init_session(
+                            attempt_service.clone(),
+                            request_id,
+                            req.clone(),
+                            source.clone(),
+                        )
+                    })
                     .await
-                    .map_err(|err| (service, err))
+                } else {
+                    init_session(service.clone(), request_id, req, source).await
+                };
+                observer::node_response(
+                    request_id,
+                    service
+                        .authority()
+                        .map_or("unknown service", |authority| authority.as_str()),
+                    NodePhase::Init,
+                    stopwatch,
+                    result.as_ref().err(),
+                );
+                result.map_err(|err| (service, err))
             }
         })
         .collect();
@@ -179,9 +477,9 @@ pub async fn init_sessions<OprfRequestAuth: Clone + Serialize + 'static>(
                 let epoch = resp.oprf_pub_key_with_epoch.epoch;
                 let epoch_session = epoch_session_map
                     .entry(epoch)
-                    .or_insert_with(|| OprfSessions::with_capacity(epoch, threshold));
+                    .or_insert_with(|| OprfSessions::with_capacity(request_id, epoch, threshold));
                 tracing::debug!("received session for epoch: {epoch}");
-                let service = session.service.clone();
+                let service = session.service().to_owned();
                 if let Err(duplicate_service) = epoch_session.push(session, resp) {
                     tracing::warn!("{duplicate_service} and {service} send same Party ID!");
                     continue;
@@ -244,12 +542,16 @@ mod tests {
     use oprf_core::ddlog_equality::shamir::{DLogSessionShamir, DLogShareShamir};
     use oprf_types::{
         ShareEpoch,
-        api::{OprfPublicKeyWithEpoch, OprfResponse},
+        api::{OprfPublicKeyWithEpoch, OprfResponse, ShareProof},
         crypto::{OprfPublicKey, PartyId},
     };
     use uuid::Uuid;
 
-    use crate::{OprfSessions, ws::WebSocketSession};
+    use super::verify_share_proof;
+    use crate::{
+        NodeError, OprfSessions,
+        ws::{NodeSession, WebSocketSession},
+    };
 
     fn ws_handler<C, Fut>(ws: WebSocketUpgrade, callback: C) -> impl IntoResponse
     where
@@ -310,6 +612,9 @@ mod tests {
                 key: OprfPublicKey::from(rand::random::<ark_babyjubjub::EdwardsAffine>()),
                 epoch: ShareEpoch::default(),
             },
+            batch_commitments: Vec::new(),
+            share_proof: None,
+            signature: None,
         }
     }
 
@@ -321,6 +626,7 @@ mod tests {
             should_address.clone(),
             Uuid::new_v4(),
             tokio_tungstenite::Connector::Plain,
+            None,
         )
         .await
         .expect("Can open websocket-session");
@@ -329,18 +635,26 @@ mod tests {
             should_address.clone(),
             Uuid::new_v4(),
             tokio_tungstenite::Connector::Plain,
+            None,
         )
         .await
         .expect("Can open websocket-session");
 
-        let mut oprf_sessions = OprfSessions::with_capacity(ShareEpoch::default(), 2);
+        let mut oprf_sessions =
+            OprfSessions::with_capacity(Uuid::new_v4(), ShareEpoch::default(), 2);
 
         oprf_sessions
-            .push(websocket_session0, oprf_response_with_party_id(0))
+            .push(
+                NodeSession::Direct(websocket_session0),
+                oprf_response_with_party_id(0),
+            )
             .expect("Should work");
 
         let is_address = oprf_sessions
-            .push(websocket_session1, oprf_response_with_party_id(0))
+            .push(
+                NodeSession::Direct(websocket_session1),
+                oprf_response_with_party_id(0),
+            )
             .expect_err("Should not work");
         assert_eq!(
             is_address,
@@ -350,4 +664,31 @@ mod tests {
                 .to_string()
         );
     }
+
+    #[test]
+    fn test_verify_share_proof() {
+        let mut rng = rand::thread_rng();
+        let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+        let blinded_query = rand::random::<ark_babyjubjub::EdwardsAffine>();
+        let mut response = oprf_response_with_party_id(0);
+        response.commitments =
+            DLogSessionShamir::partial_commitments(blinded_query, share.clone(), &mut rng).1;
+        let mut share_proof = ShareProof {
+            public_share: share.public_share(),
+            proofs: vec![share.prove_share(blinded_query, &mut rng)],
+        };
+        verify_share_proof(&share_proof, &response, &[blinded_query]).expect("Should verify");
+
+        assert_eq!(
+            verify_share_proof(&share_proof, &response, &[blinded_query, blinded_query]),
+            Err(NodeError::InvalidShareProof),
+            "Should reject a proof per query mismatch"
+        );
+        share_proof.public_share = rand::random();
+        assert_eq!(
+            verify_share_proof(&share_proof, &response, &[blinded_query]),
+            Err(NodeError::InvalidShareProof),
+            "Should reject a proof for another share"
+        );
+    }
 }
@@ -0,0 +1,95 @@
+//! Opt-in capture of protocol transcripts.
+//!
+//! After calling [`capture_to`], [`crate::distributed_oprf_core`] (and therefore [`crate::distributed_oprf`]) records the messages of every session as a [`Transcript`] and writes it to `{dir}/{request_id}-client.json` when the session ends, no matter if it succeeded. The authentication part of the request is never recorded.
+//!
+//! The transcripts of the client and of the nodes (see the `transcript_dir` option of the node) can be checked with the `transcript-verify` tool of the dev-client.
+
+use std::{path::PathBuf, sync::OnceLock};
+
+use oprf_types::{
+    api::{OprfRequest, OprfResponse},
+    crypto::PartyId,
+    transcript::{FrameDirection, Transcript, TranscriptMessage, TranscriptParticipant},
+};
+
+/// The directory transcripts are written to, unset if capturing transcripts is disabled.
+static TRANSCRIPT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Enables capturing transcripts to the provided directory for the rest of the process.
+///
+/// Returns the provided directory as error if capturing was enabled already.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn capture_to(dir: impl Into<PathBuf>) -> Result<(), PathBuf> {
+    TRANSCRIPT_DIR.set(dir.into())
+}
+
+/// The transcript of a running session. Writes the transcript on drop.
+pub(crate) struct TranscriptCapture {
+    transcript: Option<Transcript>,
+    blinded_query: ark_babyjubjub::EdwardsAffine,
+    issued_at: Option<u64>,
+    batch: Vec<ark_babyjubjub::EdwardsAffine>,
+}
+
+impl TranscriptCapture {
+    /// Starts the transcript of the session for the request, if capturing transcripts is enabled.
+    pub(crate) fn start<Auth>(req: &OprfRequest<Auth>) -> Self {
+        Self {
+            transcript: TRANSCRIPT_DIR
+                .get()
+                .map(|_| Transcript::new(req.request_id, TranscriptParticipant::Client)),
+            blinded_query: req.blinded_query,
+            issued_at: req.issued_at,
+            batch: req.batch.clone(),
+        }
+    }
+
+    /// Records the request sent to the node and its response.
+    pub(crate) fn record_response(&mut self, response: impl FnOnce() -> OprfResponse) {
+        if let Some(transcript) = &mut self.transcript {
+            let response = response();
+            let peer = TranscriptParticipant::Node(response.party_id);
+            transcript.record(
+                FrameDirection::Sent,
+                peer,
+                TranscriptMessage::Request {
+                    blinded_query: self.blinded_query,
+                    issued_at: self.issued_at,
+                    batch: self.batch.clone(),
+                },
+            );
+            transcript.record(
+                FrameDirection::Received,
+                peer,
+                TranscriptMessage::Response(response),
+            );
+        }
+    }
+
+    /// Records a frame exchanged with the node, if capturing transcripts is enabled.
+    pub(crate) fn record(
+        &mut self,
+        direction: FrameDirection,
+        party_id: PartyId,
+        message: impl FnOnce() -> TranscriptMessage,
+    ) {
+        if let Some(transcript) = &mut self.transcript {
+            transcript.record(direction, TranscriptParticipant::Node(party_id), message());
+        }
+    }
+}
+
+impl Drop for TranscriptCapture {
+    fn drop(&mut self) {
+        let (Some(transcript), Some(dir)) = (self.transcript.take(), TRANSCRIPT_DIR.get()) else {
+            return;
+        };
+        let path = dir.join(transcript.file_name());
+        let result = serde_json::to_vec_pretty(&transcript)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(&path, json));
+        if let Err(err) = result {
+            tracing::warn!(%err, "cannot write transcript to {}", path.display());
+        }
+    }
+}
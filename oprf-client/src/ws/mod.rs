@@ -2,7 +2,9 @@
 mod native;
 use http::Uri;
 #[cfg(not(target_arch = "wasm32"))]
-pub(crate) use native::WebSocketSession;
+pub(crate) use native::{WebSocketSession, connect};
+use oprf_types::api::SchemaFingerprint;
+use serde::{Deserialize, Serialize};
 
 #[cfg(target_arch = "wasm32")]
 mod wasm;
@@ -10,14 +12,75 @@ use uuid::Uuid;
 #[cfg(target_arch = "wasm32")]
 pub(crate) use wasm::WebSocketSession;
 
-pub(crate) fn append_client_version_to_query(endpoint: &Uri, request_id: Uuid) -> String {
+use crate::NodeError;
+
+/// A session with a single node, either on its own web-socket or on a pooled multiplexed connection (see [`crate::SessionPool`]).
+#[allow(
+    clippy::large_enum_variant,
+    reason = "a run holds only a handful of sessions"
+)]
+pub(crate) enum NodeSession {
+    Direct(WebSocketSession),
+    #[cfg(not(target_arch = "wasm32"))]
+    Pooled(crate::pool::PooledSession),
+}
+
+impl NodeSession {
+    /// The authority of the node.
+    pub(crate) fn service(&self) -> &str {
+        match self {
+            NodeSession::Direct(session) => &session.service,
+            #[cfg(not(target_arch = "wasm32"))]
+            NodeSession::Pooled(session) => &session.service,
+        }
+    }
+
+    /// Attempts to send the provided message to the node.
+    pub(crate) async fn send<Msg: Serialize>(&mut self, msg: Msg) -> Result<(), NodeError> {
+        match self {
+            NodeSession::Direct(session) => session.send(msg).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            NodeSession::Pooled(session) => session.send(msg),
+        }
+    }
+
+    /// Attempts to read the provided message from the node.
+    pub(crate) async fn read<Msg: for<'de> Deserialize<'de>>(&mut self) -> Result<Msg, NodeError> {
+        match self {
+            NodeSession::Direct(session) => session.read().await,
+            #[cfg(not(target_arch = "wasm32"))]
+            NodeSession::Pooled(session) => session.read().await,
+        }
+    }
+}
+
+/// Encodes `msg` as `cbor`, in canonical encoding with the `canonical-cbor` feature (see `oprf_types::canonical_cbor`).
+pub(crate) fn encode_cbor(msg: &impl Serialize) -> Vec<u8> {
+    #[cfg(feature = "canonical-cbor")]
+    {
+        oprf_types::canonical_cbor::to_vec(msg).expect("Can serialize msg")
+    }
+    #[cfg(not(feature = "canonical-cbor"))]
+    {
+        let mut buf = Vec::new();
+        ciborium::into_writer(msg, &mut buf).expect("Can serialize msg");
+        buf
+    }
+}
+
+/// Appends the client version, the schema fingerprint and the `request_id` (if any) to the query of `endpoint`.
+pub(crate) fn append_client_version_to_query(endpoint: &Uri, request_id: Option<Uuid>) -> String {
     let has_query = endpoint.query().is_some();
     let mut endpoint = endpoint.to_string();
 
     endpoint.push(if has_query { '&' } else { '?' });
     endpoint.push_str("version=");
     endpoint.push_str(crate::VERSION);
-    endpoint.push_str("&request_id=");
-    endpoint.push_str(&request_id.to_string());
+    endpoint.push_str("&schema=");
+    endpoint.push_str(&SchemaFingerprint::CURRENT.to_string());
+    if let Some(request_id) = request_id {
+        endpoint.push_str("&request_id=");
+        endpoint.push_str(&request_id.to_string());
+    }
     endpoint
 }
@@ -4,14 +4,16 @@
 //!
 //! The client does not send close frames. The server drives the teardown: after the protocol completes (or on error/timeout) the server sends a close frame and drains the socket until the client drops.
 
-use crate::{NodeError, ServiceError};
+use crate::{NodeError, ServiceError, resolver::Resolver};
 use futures::{SinkExt, StreamExt};
 use http::Uri;
 use serde::{Deserialize, Serialize};
 use tokio::net::TcpStream;
 use tokio_tungstenite::{
     Connector, MaybeTlsStream, WebSocketStream,
-    tungstenite::{self, protocol::frame::coding::CloseCode},
+    tungstenite::{
+        self, client::IntoClientRequest as _, error::UrlError, protocol::frame::coding::CloseCode,
+    },
 };
 use uuid::Uuid;
 
@@ -21,7 +23,46 @@ impl From<tungstenite::Error> for NodeError {
     }
 }
 
-type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+pub(crate) type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Opens a web-socket at `endpoint`, resolving its host with the `resolver` instead of the system DNS if provided (see [`crate::resolver`]).
+pub(crate) async fn connect(
+    endpoint: String,
+    connector: Connector,
+    resolver: Option<&Resolver>,
+) -> Result<WebSocket, NodeError> {
+    let request = endpoint.into_client_request()?;
+    let Some(resolver) = resolver else {
+        let (ws, _) =
+            tokio_tungstenite::connect_async_tls_with_config(request, None, false, Some(connector))
+                .await?;
+        return Ok(ws);
+    };
+    let host = request
+        .uri()
+        .host()
+        .ok_or(tungstenite::Error::Url(UrlError::NoHostName))?;
+    let port = request
+        .uri()
+        .port_u16()
+        .or_else(|| match request.uri().scheme_str() {
+            Some("wss") => Some(443),
+            Some("ws") => Some(80),
+            _ => None,
+        })
+        .ok_or(tungstenite::Error::Url(UrlError::UnsupportedUrlScheme))?;
+    let addrs = resolver
+        .resolve(host, port)
+        .await
+        .map_err(tungstenite::Error::Io)?;
+    let stream = TcpStream::connect(addrs.as_slice())
+        .await
+        .map_err(tungstenite::Error::Io)?;
+    let (ws, _) =
+        tokio_tungstenite::client_async_tls_with_config(request, stream, None, Some(connector))
+            .await?;
+    Ok(ws)
+}
 
 /// The opened session. Thin wrapper around tungstenite web-socket stream.
 pub(crate) struct WebSocketSession {
@@ -35,16 +76,16 @@ impl WebSocketSession {
         endpoint: Uri,
         request_id: Uuid,
         connector: Connector,
+        resolver: Option<&Resolver>,
     ) -> Result<Self, NodeError> {
         let service = endpoint
             .authority()
             .map_or_else(|| "unknown authority".to_string(), ToString::to_string);
         tracing::trace!("> sending request to {service}..");
-        let (ws, _) = tokio_tungstenite::connect_async_tls_with_config(
-            super::append_client_version_to_query(&endpoint, request_id),
-            None,
-            false,
-            Some(connector),
+        let ws = connect(
+            super::append_client_version_to_query(&endpoint, Some(request_id)),
+            connector,
+            resolver,
         )
         .await?;
         Ok(Self { service, inner: ws })
@@ -52,8 +93,7 @@ impl WebSocketSession {
 
     /// Attempts to send the provided message to the web-socket.
     pub(crate) async fn send<Msg: Serialize>(&mut self, msg: Msg) -> Result<(), NodeError> {
-        let mut buf = Vec::new();
-        ciborium::into_writer(&msg, &mut buf).expect("Can serialize msg");
+        let buf = super::encode_cbor(&msg);
         if let Err(err) = self.inner.send(tungstenite::Message::binary(buf)).await {
             Err(NodeError::WsError(Box::new(err)))
         } else {
@@ -7,6 +7,7 @@
 //!
 //! - **TLS**: Handled transparently by the browser. The [`Connector`] parameter
 //!   is a no-op placeholder for API compatibility.
+//! - **Host resolution**: Handled by the browser, the [`Resolver`] parameter is ignored.
 //! - **Protocol version**: The browser WebSocket API does not support custom HTTP
 //!   headers during the upgrade handshake. The protocol version is sent as a
 //!   query parameter (`?version=<version>`) instead.
@@ -14,7 +15,7 @@
 //!   close frames on errors. The server drives teardown; the browser manages the
 //!   underlying TCP close.
 
-use crate::{Connector, NodeError, ServiceError};
+use crate::{Connector, NodeError, ServiceError, resolver::Resolver};
 use futures::{SinkExt, StreamExt};
 use gloo_net::websocket::{Message, WebSocketError, futures::WebSocket};
 use http::Uri;
@@ -44,13 +45,14 @@ impl WebSocketSession {
         endpoint: Uri,
         request_id: Uuid,
         _connector: Connector,
+        _resolver: Option<&Resolver>,
     ) -> Result<Self, NodeError> {
         let service = endpoint
             .authority()
             .map_or_else(|| "unknown authority".to_string(), ToString::to_string);
         tracing::trace!("> sending request to {service}..");
 
-        let endpoint = super::append_client_version_to_query(&endpoint, request_id);
+        let endpoint = super::append_client_version_to_query(&endpoint, Some(request_id));
         let ws = WebSocket::open(&endpoint).map_err(|e| {
             NodeError::WsError(Box::new(std::io::Error::other(format!(
                 "failed to open {endpoint}: {e:?}"
@@ -67,8 +69,7 @@ impl WebSocketSession {
 
     /// Attempts to send the provided message to the web-socket.
     pub(crate) async fn send<Msg: Serialize>(&mut self, msg: Msg) -> Result<(), NodeError> {
-        let mut buf = Vec::new();
-        ciborium::into_writer(&msg, &mut buf).expect("Can serialize msg");
+        let buf = super::encode_cbor(&msg);
         if let Err(e) = self.write.send(Message::Bytes(buf)).await {
             Err(NodeError::WsError(Box::new(std::io::Error::other(
                 format!("send failed: {e:?}"),
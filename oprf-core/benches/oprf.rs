@@ -1,6 +1,6 @@
 use std::vec;
 
-use ark_babyjubjub::{EdwardsAffine, EdwardsProjective};
+use ark_babyjubjub::{EdwardsAffine, EdwardsProjective, Fq};
 use ark_bn254::Bn254;
 use ark_ec::{CurveGroup, PrimeGroup};
 use ark_ff::{AdditiveGroup, UniformRand};
@@ -157,7 +157,7 @@ fn ddlog_bench(c: &mut Criterion) {
                 let challenge = DLogCommitmentsAdditive::combine_commitments(&[(1, comm)]);
                 (session, challenge)
             },
-            |(session, challenge)| session.challenge(session_id, x.into(), pk, challenge),
+            |(session, challenge)| session.challenge(session_id, x.into(), pk, challenge, Fq::ZERO),
             BatchSize::SmallInput,
         );
     });
@@ -181,7 +181,7 @@ fn ddlog_bench(c: &mut Criterion) {
             },
             |(session, challenge)| {
                 let lagrange = shamir::single_lagrange_from_coeff(1, &participating_parties);
-                session.challenge(session_id, x.into(), pk, challenge, lagrange)
+                session.challenge(session_id, x.into(), pk, challenge, lagrange, Fq::ZERO)
             },
             BatchSize::SmallInput,
         );
@@ -221,12 +221,12 @@ fn ddlog_bench(c: &mut Criterion) {
                     let challenge = DLogCommitmentsAdditive::combine_commitments(&commitments);
                     let responses = sessions
                         .into_iter()
-                        .map(|s| s.challenge(session_id, x.into(), pk, challenge.clone()))
+                        .map(|s| s.challenge(session_id, x.into(), pk, challenge.clone(), Fq::ZERO))
                         .collect::<Vec<_>>();
                     (challenge, responses)
                 },
                 |(challenge, responses)| {
-                    challenge.combine_proofs(session_id, &responses, pk, point)
+                    challenge.combine_proofs(session_id, &responses, pk, point, Fq::ZERO)
                 },
                 BatchSize::SmallInput,
             );
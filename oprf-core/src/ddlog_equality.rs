@@ -17,7 +17,7 @@
 //! Secret randomness is never clonable, and session types deliberately do not implement `Debug` to avoid accidental leakage.
 use crate::{
     dlog_equality::DLogEqualityProof,
-    oprf::{Affine, ScalarField},
+    oprf::{Affine, BaseField, ScalarField},
 };
 use ark_ec::{AffineRepr, CurveGroup};
 use ark_ff::{PrimeField, UniformRand, Zero};
@@ -91,10 +91,15 @@ pub(crate) struct DLogEqualityProofShare(
 ///
 /// This is not `Clone` because it contains secret randomness that may only be used once. We also don't implement `Debug` so we do don't print it by accident.
 /// The `challenge` method consumes the session.
-#[derive(ZeroizeOnDrop)]
+///
+/// Serializable so that a node can persist pending sessions. The serialized form contains the secret randomness and must be protected like the session itself.
+#[derive(Serialize, Deserialize, ZeroizeOnDrop)]
 pub struct DLogEqualitySession {
+    #[serde(with = "ark_serde_compat::field")]
     pub(crate) d: ScalarField,
+    #[serde(with = "ark_serde_compat::field")]
     pub(crate) e: ScalarField,
+    #[serde(with = "babyjubjub::affine")]
     pub(crate) blinded_query: Affine,
 }
 
@@ -106,42 +111,42 @@ impl DLogEqualitySession {
         x_share: ScalarField,
         rng: &mut (impl CryptoRng + Rng),
     ) -> (Self, PartialDLogEqualityCommitments) {
-        let d_share = ScalarField::rand(rng);
-        let e_share = ScalarField::rand(rng);
-        let d1 = (Affine::generator() * d_share).into_affine();
-        let e1 = (Affine::generator() * e_share).into_affine();
-        let d2 = (b * d_share).into_affine();
-        let e2 = (b * e_share).into_affine();
-        let c_share = (b * x_share).into_affine();
-
-        let comm = PartialDLogEqualityCommitments {
-            c: c_share,
-            d1,
-            d2,
-            e1,
-            e2,
-        };
-
         let session = DLogEqualitySession {
-            d: d_share,
-            e: e_share,
+            d: ScalarField::rand(rng),
+            e: ScalarField::rand(rng),
             blinded_query: b,
         };
-
+        let comm = session.commitments(x_share);
         (session, comm)
     }
+
+    /// Computes the commitments of this session again, e.g., to answer a resumed session.
+    ///
+    /// Returns the same commitments as [`DLogEqualitySession::partial_commitments`] for the same `x_share`.
+    #[must_use]
+    pub fn commitments(&self, x_share: ScalarField) -> PartialDLogEqualityCommitments {
+        let b = self.blinded_query;
+        PartialDLogEqualityCommitments {
+            c: (b * x_share).into_affine(),
+            d1: (Affine::generator() * self.d).into_affine(),
+            d2: (b * self.d).into_affine(),
+            e1: (Affine::generator() * self.e).into_affine(),
+            e2: (b * self.e).into_affine(),
+        }
+    }
 }
 
 impl DLogEqualityCommitments {
     /// Combine all parties' proof shares into a single Chaum-Pedersen proof object.
     ///
-    /// Must use the same order of contributing parties as in aggregation
+    /// Must use the same order of contributing parties as in aggregation and the same `context` as the parties.
     pub(crate) fn combine_proofs<'a>(
         self,
         session_id: Uuid,
         proofs: impl Iterator<Item = &'a DLogEqualityProofShare>,
         a: Affine,
         b: Affine,
+        context: BaseField,
     ) -> DLogEqualityProof {
         let mut s = ScalarField::zero();
         for proof in proofs {
@@ -159,7 +164,7 @@ impl DLogEqualityCommitments {
         });
 
         let d = Affine::generator();
-        let e = crate::dlog_equality::challenge_hash(a, b, self.c, d, r1, r2);
+        let e = crate::dlog_equality::challenge_hash(a, b, self.c, d, r1, r2, context);
 
         DLogEqualityProof { e, s }
     }
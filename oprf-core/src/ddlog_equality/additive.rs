@@ -22,7 +22,7 @@ use crate::{
         PartialDLogEqualityCommitments,
     },
     dlog_equality::DLogEqualityProof,
-    oprf::{Affine, Projective, ScalarField},
+    oprf::{Affine, BaseField, Projective, ScalarField},
 };
 use ark_ec::{AffineRepr, CurveGroup};
 use ark_ff::Zero;
@@ -124,7 +124,7 @@ impl DLogCommitmentsAdditive {
 
     /// Combine all parties' proof shares into a single Chaum-Pedersen proof object.
     ///
-    /// Must use the same order of contributing parties as in aggregation
+    /// Must use the same order of contributing parties as in aggregation and the same `context` as the parties (see [`crate::oprf::QueryContext::inner`]), `0` for no context.
     #[must_use]
     pub fn combine_proofs(
         self,
@@ -132,9 +132,10 @@ impl DLogCommitmentsAdditive {
         proofs: &[DLogProofShareAdditive],
         a: Affine,
         b: Affine,
+        context: BaseField,
     ) -> DLogEqualityProof {
         self.0
-            .combine_proofs(session_id, proofs.iter().map(|x| &x.0), a, b)
+            .combine_proofs(session_id, proofs.iter().map(|x| &x.0), a, b, context)
     }
     /// The accumulating party (e.g., the verifier) combines all the shares of all parties.
     /// The returned points are the combined commitments C, R1, R2.
@@ -183,6 +184,7 @@ impl DLogCommitmentsAdditive {
 impl DLogSessionAdditive {
     /// Finalizes a proof share for a given challenge hash and session.
     /// The session and information therein is consumed to prevent reuse of the randomness.
+    /// The proof share is bound to the `context` (see [`crate::oprf::QueryContext::inner`]), `0` for no context.
     #[must_use]
     pub fn challenge(
         self,
@@ -190,6 +192,7 @@ impl DLogSessionAdditive {
         DLogShareAdditive(x_share): DLogShareAdditive,
         a: Affine,
         DLogCommitmentsAdditive(challenge_input): DLogCommitmentsAdditive,
+        context: BaseField,
     ) -> DLogProofShareAdditive {
         // Recombine the two-nonce randomness shares into the full randomness used in the challenge.
         let (r1, r2, b) =
@@ -213,6 +216,7 @@ impl DLogSessionAdditive {
             d,
             r1,
             r2,
+            context,
         );
 
         // The following modular reduction in convert_base_to_scalar is required in rust to perform the scalar multiplications. Using all 254 bits of the base field in a double/add ladder would apply this reduction implicitly. We show in the docs of convert_base_to_scalar why this does not introduce a bias when applied to a uniform element of the base field.
@@ -270,12 +274,18 @@ mod tests {
         // 3) Client challenges all servers
         let mut proofs = Vec::with_capacity(num_parties);
         for (session, x_) in sessions.into_iter().zip(x_shares.iter().cloned()) {
-            let proof = session.challenge(session_id, x_, public_key, challenge.clone());
+            let proof = session.challenge(
+                session_id,
+                x_,
+                public_key,
+                challenge.clone(),
+                BaseField::zero(),
+            );
             proofs.push(proof);
         }
 
         // 4) Client combines all proofs
-        let proof = challenge.combine_proofs(session_id, &proofs, public_key, b);
+        let proof = challenge.combine_proofs(session_id, &proofs, public_key, b, BaseField::zero());
 
         // Verify the result and the proof
         let d = Affine::generator();
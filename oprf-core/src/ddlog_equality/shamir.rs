@@ -20,7 +20,7 @@ use crate::ddlog_equality::{
     DLogEqualityCommitments, DLogEqualityProofShare, DLogEqualitySession,
     PartialDLogEqualityCommitments,
 };
-use crate::dlog_equality::DLogEqualityProof;
+use crate::dlog_equality::{DLogEqualityProof, InvalidProof};
 use ark_ec::CurveGroup;
 use ark_ec::{AffineRepr, VariableBaseMSM};
 use ark_ff::Zero;
@@ -32,6 +32,7 @@ use uuid::Uuid;
 use zeroize::ZeroizeOnDrop;
 
 type ScalarField = ark_babyjubjub::Fr;
+type BaseField = ark_babyjubjub::Fq;
 type Affine = ark_babyjubjub::EdwardsAffine;
 type Projective = ark_babyjubjub::EdwardsProjective;
 
@@ -50,7 +51,10 @@ pub struct DLogShareShamir(#[serde(with = "ark_serde_compat::field")] ScalarFiel
 ///
 /// Stores non-clonable, non-debug secret state for a threshold party during the `DLogEquality` protocol.
 /// Used to generate the commitment shares and construct the proof share for Shamir secret sharing.
-#[derive(ZeroizeOnDrop)]
+///
+/// Serializable so that a node can persist pending sessions, see [`DLogEqualitySession`].
+#[derive(Serialize, Deserialize, ZeroizeOnDrop)]
+#[serde(transparent)]
 pub struct DLogSessionShamir(DLogEqualitySession);
 
 /// Commitment aggregation object for the Shamir `DLogEquality` protocol.
@@ -89,6 +93,53 @@ impl From<DLogShareShamir> for ark_babyjubjub::Fr {
     }
 }
 
+impl DLogShareShamir {
+    /// Returns the public share `G·x_share`, against which proofs of [`DLogShareShamir::prove_share`] are verified.
+    pub fn public_share(&self) -> Affine {
+        (Affine::generator() * self.0).into_affine()
+    }
+
+    /// Creates a Chaum-Pedersen proof that `C=B·x_share` of the [`PartialDLogCommitmentsShamir`] for the `blinded_query` `B` was computed with this share, i.e., that `C` and the [`DLogShareShamir::public_share`] have the same dlog.
+    pub fn prove_share(
+        &self,
+        blinded_query: Affine,
+        rng: &mut (impl CryptoRng + Rng),
+    ) -> DLogEqualityProof {
+        DLogEqualityProof::proof(blinded_query, self.0, rng)
+    }
+}
+
+impl PartialDLogCommitmentsShamir {
+    /// Verifies a proof of [`DLogShareShamir::prove_share`] that these commitments for the `blinded_query` were computed with the share of `public_share`.
+    ///
+    /// # Errors
+    /// Returns an error if proof verification fails.
+    pub fn verify_share_proof(
+        &self,
+        proof: &DLogEqualityProof,
+        blinded_query: Affine,
+        public_share: Affine,
+    ) -> Result<(), InvalidProof> {
+        proof.verify(public_share, blinded_query, self.0.c, Affine::generator())
+    }
+}
+
+/// Combines the public shares (see [`DLogShareShamir::public_share`]) of the `contributing_parties` to the public key with Lagrange interpolation.
+///
+/// The contributing parties are identified like in [`DLogCommitmentsShamir::combine_commitments`].
+///
+/// # Panics
+/// Panics if the number of public shares does not match the number of contributing parties.
+pub fn combine_public_shares(public_shares: &[Affine], contributing_parties: &[u16]) -> Affine {
+    assert_eq!(
+        public_shares.len(),
+        contributing_parties.len(),
+        "Number of public shares must match number of contributing parties"
+    );
+    let lagrange = crate::shamir::lagrange_from_coeff(contributing_parties);
+    Projective::msm_unchecked(public_shares, &lagrange).into_affine()
+}
+
 impl DLogSessionShamir {
     /// Computes `C=B·x_share` and commitments to two random values `d_share` and `e_share`, which will be the shares of the randomness used in the `DlogEqualityProof`.
     /// The result is meant to be sent to one accumulating party (e.g., the verifier) who combines all the shares of all parties and creates the challenge hash.
@@ -100,6 +151,19 @@ impl DLogSessionShamir {
         let (session, comm) = DLogEqualitySession::partial_commitments(b, x_share, rng);
         (Self(session), PartialDLogCommitmentsShamir(comm))
     }
+
+    /// Computes the commitments of this session again, e.g., to answer a resumed session.
+    ///
+    /// Returns the same commitments as [`DLogSessionShamir::partial_commitments`] for the same `x_share`.
+    #[must_use]
+    pub fn commitments(&self, x_share: &DLogShareShamir) -> PartialDLogCommitmentsShamir {
+        PartialDLogCommitmentsShamir(self.0.commitments(x_share.0))
+    }
+
+    /// Returns the blinded query `B` of this session.
+    pub fn blinded_query(&self) -> Affine {
+        self.0.blinded_query
+    }
 }
 
 impl DLogCommitmentsShamir {
@@ -132,7 +196,7 @@ impl DLogCommitmentsShamir {
 
     /// Combine all parties' proof shares into a single Chaum-Pedersen proof object.
     ///
-    /// Must use the same order of contributing parties as in aggregation
+    /// Must use the same order of contributing parties as in aggregation and the same `context` as the parties (see [`crate::oprf::QueryContext::inner`]), `0` for no context.
     #[must_use]
     pub fn combine_proofs(
         self,
@@ -140,9 +204,10 @@ impl DLogCommitmentsShamir {
         proofs: &[DLogProofShareShamir],
         a: Affine,
         b: Affine,
+        context: BaseField,
     ) -> DLogEqualityProof {
         self.0
-            .combine_proofs(session_id, proofs.iter().map(|x| &x.0), a, b)
+            .combine_proofs(session_id, proofs.iter().map(|x| &x.0), a, b, context)
     }
     /// Returns the combined blinded response C=B*x.
     pub fn blinded_response(&self) -> Affine {
@@ -216,6 +281,7 @@ impl DLogCommitmentsShamir {
 impl DLogSessionShamir {
     /// Finalizes a proof share for a given challenge hash and session.
     /// The session and information therein is consumed to prevent reuse of the randomness.
+    /// The proof share is bound to the `context` (see [`crate::oprf::QueryContext::inner`]), `0` for no context.
     ///
     /// Prerequisites:
     /// * The `lagrange_coefficient` is computed from the same set of contributing parties as in the commitments.
@@ -228,6 +294,7 @@ impl DLogSessionShamir {
         a: Affine,
         DLogCommitmentsShamir(challenge_input): DLogCommitmentsShamir,
         lagrange_coefficient: ScalarField,
+        context: BaseField,
     ) -> DLogProofShareShamir {
         // Recombine the two-nonce randomness shares into the full randomness used in the challenge.
         let (r1, r2, b) =
@@ -251,6 +318,7 @@ impl DLogSessionShamir {
             d,
             r1,
             r2,
+            context,
         );
 
         // The following modular reduction in convert_base_to_scalar is required in rust to perform the scalar multiplications. Using all 254 bits of the base field in a double/add ladder would apply this reduction implicitly. We show in the docs of convert_base_to_scalar why this does not introduce a bias when applied to a uniform element of the base field.
@@ -287,7 +355,7 @@ mod tests {
         shares
     }
 
-    fn test_distributed_dlog_equality(num_parties: usize, degree: usize) {
+    fn test_distributed_dlog_equality(num_parties: usize, degree: usize, context: BaseField) {
         let mut rng = rand::thread_rng();
 
         let x = ScalarField::rand(&mut rng);
@@ -347,26 +415,86 @@ mod tests {
                 public_key,
                 challenge.clone(),
                 shamir::single_lagrange_from_coeff(*server_idx, &used_parties),
+                context,
             );
             used_proofs.push(proof);
         }
 
         // 4) Client combines received proof shares
-        let proof = challenge.combine_proofs(session_id, &used_proofs, public_key, b);
+        let proof = challenge.combine_proofs(session_id, &used_proofs, public_key, b, context);
 
         // Verify the result and the proof
         let d = Affine::generator();
         assert_eq!(c, b * x, "Result must be correct");
-        proof.verify(public_key, b, c, d).expect("Can verify proof");
+        proof
+            .verify_with_context(public_key, b, c, d, context)
+            .expect("Can verify proof");
+        if !context.is_zero() {
+            assert!(
+                proof.verify(public_key, b, c, d).is_err(),
+                "proof is bound to the context"
+            );
+        }
+    }
+
+    #[test]
+    fn test_session_commitments_survive_serialization() {
+        let mut rng = rand::thread_rng();
+        let x_share = DLogShareShamir(ScalarField::rand(&mut rng));
+        let b = Affine::rand(&mut rng);
+        let (session, comm) = DLogSessionShamir::partial_commitments(b, x_share.clone(), &mut rng);
+
+        let serialized = serde_json::to_vec(&session).expect("can serialize");
+        let restored: DLogSessionShamir =
+            serde_json::from_slice(&serialized).expect("can deserialize");
+        assert_eq!(restored.blinded_query(), b);
+        assert_eq!(
+            serde_json::to_value(restored.commitments(&x_share)).expect("can serialize"),
+            serde_json::to_value(comm).expect("can serialize"),
+        );
+    }
+
+    #[test]
+    fn test_share_proofs() {
+        let mut rng = rand::thread_rng();
+        let x = ScalarField::rand(&mut rng);
+        let x_shares = share(x, 3, 1, &mut rng);
+        let b = Affine::rand(&mut rng);
+
+        let (_, comm) = DLogSessionShamir::partial_commitments(b, x_shares[0].clone(), &mut rng);
+        let proof = x_shares[0].prove_share(b, &mut rng);
+        comm.verify_share_proof(&proof, b, x_shares[0].public_share())
+            .expect("Can verify proof");
+        assert!(
+            comm.verify_share_proof(&proof, b, x_shares[1].public_share())
+                .is_err(),
+            "Proof must not verify for another share"
+        );
+
+        let public_shares = [x_shares[2].public_share(), x_shares[0].public_share()];
+        assert_eq!(
+            combine_public_shares(&public_shares, &[3, 1]),
+            (Affine::generator() * x).into_affine(),
+            "Public shares must combine to the public key"
+        );
     }
 
     #[test]
     fn test_distributed_dlog_equality_shamir_3_1() {
-        test_distributed_dlog_equality(3, 1);
+        test_distributed_dlog_equality(3, 1, BaseField::zero());
     }
 
     #[test]
     fn test_distributed_dlog_equality_shamir_31_15() {
-        test_distributed_dlog_equality(31, 15);
+        test_distributed_dlog_equality(31, 15, BaseField::zero());
+    }
+
+    #[test]
+    fn test_distributed_dlog_equality_shamir_with_context() {
+        test_distributed_dlog_equality(
+            3,
+            1,
+            crate::oprf::client::query_context("test-module").inner(),
+        );
     }
 }
@@ -8,6 +8,10 @@
 //! - Proof creation given secret
 //! - Proof verification, including group membership and field fit checks
 //! - Uses Poseidon2 for Fiat-Shamir challenge generation
+//! - Optional binding of the challenge to a context (see [`crate::oprf::QueryContext`])
+//!
+//! ## Context
+//! A proof can be bound to a context, e.g., the OPRF module that evaluated the query, so that a response computed in one context does not verify in another one. The context is a field element, for OPRF evaluations the [`crate::oprf::QueryContext`] of the module, and mixed into the challenge hash. The context `0` (see [`DLogEqualityProof::verify`]) yields the same challenge as a proof without context.
 
 use std::fmt;
 
@@ -15,17 +19,20 @@ use ark_ec::{AffineRepr, CurveGroup};
 use ark_ff::{BigInteger, PrimeField, UniformRand, Zero};
 use num_bigint::BigUint;
 use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
 
 /// A Chaum-Pedersen discrete logarithm equality proof.
 ///
 /// Proves in zero-knowledge that two group elements share the same discrete logarithm (i.e., for known base points B and D, prover knows x such that A = x·D and C = x·B), without revealing x. Used to ensure correct OPRF evaluations.
 ///
 /// The verifier needs to check that `s` fits in the base field to avoid malleability attacks.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DLogEqualityProof {
     ///Fiat-Shamir challenge, represented as a field element.
+    #[serde(with = "ark_serde_compat::field")]
     pub(crate) e: BaseField,
     /// Proof response, represented as a scalar. The verifier checks that it fits in the base field to avoid malleability attacks.
+    #[serde(with = "ark_serde_compat::field")]
     pub(crate) s: ScalarField,
 }
 
@@ -82,7 +89,7 @@ impl DLogEqualityProof {
         let a = (Affine::generator() * x).into_affine();
         let c = (b * x).into_affine();
         let d = Affine::generator();
-        let e = challenge_hash(a, b, c, d, r1, r2);
+        let e = challenge_hash(a, b, c, d, r1, r2, BaseField::zero());
 
         // The following modular reduction in convert_base_to_scalar is required in rust to perform the scalar multiplications. Using all 254 bits of the base field in a double/add ladder would apply this reduction implicitly. We show in the docs of convert_base_to_scalar why this does not introduce a bias when applied to a uniform element of the base field.
         let e_ = convert_base_to_scalar(e);
@@ -92,9 +99,26 @@ impl DLogEqualityProof {
 
     /// Takes the Chaum-Pedersen proof e,s and verifies that A=x*D and C=x*B have the same dlog x, given A,B,C,D.
     ///
+    /// Verifies proofs without context, see [`DLogEqualityProof::verify_with_context`].
+    ///
     /// # Errors
     /// Returns an error if proof verification fails.
     pub fn verify(&self, a: Affine, b: Affine, c: Affine, d: Affine) -> Result<(), InvalidProof> {
+        self.verify_with_context(a, b, c, d, BaseField::zero())
+    }
+
+    /// Like [`DLogEqualityProof::verify`], but for a proof bound to the `context` (see [`crate::oprf::QueryContext::inner`]).
+    ///
+    /// # Errors
+    /// Returns an error if proof verification fails, also if the proof was bound to another context.
+    pub fn verify_with_context(
+        &self,
+        a: Affine,
+        b: Affine,
+        c: Affine,
+        d: Affine,
+        context: BaseField,
+    ) -> Result<(), InvalidProof> {
         // All points need to be valid curve elements.
         if [a, b, c, d]
             .iter()
@@ -124,7 +148,7 @@ impl DLogEqualityProof {
         if r_2.is_zero() {
             return Err(InvalidProof);
         }
-        let e = challenge_hash(a, b, c, d, r_1.into_affine(), r_2.into_affine());
+        let e = challenge_hash(a, b, c, d, r_1.into_affine(), r_2.into_affine(), context);
         if e == self.e {
             Ok(())
         } else {
@@ -140,6 +164,7 @@ pub(crate) fn challenge_hash(
     d: Affine,
     r1: Affine,
     r2: Affine,
+    context: BaseField,
 ) -> BaseField {
     let hash_input = [
         DLogEqualityProof::get_dlog_ds(), // Domain separator in capacity of hash
@@ -155,7 +180,7 @@ pub(crate) fn challenge_hash(
         r1.y,
         r2.x,
         r2.y,
-        BaseField::zero(),
+        context, // zero without context
         BaseField::zero(),
         BaseField::zero(),
     ];
@@ -188,4 +213,31 @@ mod tests {
         let invalid_proof = DLogEqualityProof::proof(b2, x, &mut rng);
         assert!(invalid_proof.verify(a, b, c, d).is_err());
     }
+
+    #[test]
+    fn test_challenge_context() {
+        let mut rng = rand::thread_rng();
+        let x = ScalarField::rand(&mut rng);
+        let d = Affine::generator();
+        let a = (d * x).into_affine();
+        let b = Affine::rand(&mut rng);
+        let c = (b * x).into_affine();
+
+        let proof = DLogEqualityProof::proof(b, x, &mut rng);
+        proof
+            .verify_with_context(a, b, c, d, BaseField::zero())
+            .expect("zero context is no context");
+        assert!(
+            proof
+                .verify_with_context(
+                    a,
+                    b,
+                    c,
+                    d,
+                    crate::oprf::client::query_context("module").inner()
+                )
+                .is_err(),
+            "should not verify in another context"
+        );
+    }
 }
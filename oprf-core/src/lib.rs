@@ -27,6 +27,10 @@
 //! - **oprf**: Blinded OPRF protocol types and client/server operations.
 //! - **`dlog_equality`**: Chaum-Pedersen proofs for discrete log equality.
 //! - **shamir**: Shamir polynomial secret sharing over finite fields.
+//!
+//! # Group
+//!
+//! All protocols run over the `BabyJubJub` curve. The group is not a parameter: hashing to the curve, the Poseidon2-based challenges (mirrored by the circom implementation of the proofs) and the key-generation circuits are defined over the BN254 scalar field, which is the base field of `BabyJubJub`. An RFC 9497-style suite over a different group (e.g. ristretto255) would need its own hash-to-group, challenge hash and circuits, and therefore a separate module instead of a curve parameter on the existing types. What ties the protocols to `BabyJubJub` is the field the circuits and the challenge hash are defined over, not the availability of a ristretto255 implementation (e.g. `curve25519-dalek`).
 pub mod ddlog_equality;
 pub mod dlog_equality;
 pub mod keygen;
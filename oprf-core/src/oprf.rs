@@ -107,6 +107,20 @@ impl BlindingFactor {
     }
 }
 
+/// The context of an OPRF module as field element, derived with [`client::query_context`].
+///
+/// The context is hashed together with the query before the query is mapped to the curve (see [`client::blind_query_with_context`]) and mixed into the challenge of the `DLog` equality proof (see [`crate::dlog_equality::DLogEqualityProof::verify_with_context`]). It can only be derived from a context string, so it never takes the values the hash to the curve reserves for queries without context, except with negligible probability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryContext(BaseField);
+
+impl QueryContext {
+    /// Returns the context as field element, e.g., as input of a circuit.
+    #[must_use]
+    pub fn inner(&self) -> BaseField {
+        self.0
+    }
+}
+
 /// Prepared blinding factor, storing the inverse for unblinding.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PreparedBlindingFactor(ScalarField);
@@ -258,4 +272,45 @@ mod tests {
         )
         .expect_err("Should now fail");
     }
+
+    #[test]
+    fn test_oprf_with_context() {
+        let mut rng = rand::thread_rng();
+        let service = OprfServer::new(OprfKey::random(&mut rng));
+        let domain_separator = ark_babyjubjub::Fq::from_be_bytes_mod_order(b"OPRF");
+        let blinding_factor = BlindingFactor::rand(&mut rng);
+        let query = BaseField::from(42);
+        let evaluate = |context| {
+            let blinded_request =
+                oprf::client::blind_query_with_context(query, context, blinding_factor);
+            oprf::client::finalize_query(
+                query,
+                &service.answer_query(&blinded_request),
+                &blinding_factor.prepare(),
+                domain_separator,
+            )
+        };
+
+        let context_a = oprf::client::query_context("a");
+        let context_b = oprf::client::query_context("b");
+        assert_ne!(context_a, context_b);
+        assert_ne!(oprf::client::query_context("").inner(), BaseField::zero());
+        assert_eq!(
+            evaluate(context_a),
+            evaluate(context_a),
+            "same context, same output"
+        );
+        assert_ne!(
+            evaluate(context_a),
+            evaluate(context_b),
+            "contexts separate the outputs"
+        );
+        let without_context = oprf::client::finalize_query(
+            query,
+            &service.answer_query(&oprf::client::blind_query(query, blinding_factor)),
+            &blinding_factor.prepare(),
+            domain_separator,
+        );
+        assert_ne!(evaluate(context_a), without_context);
+    }
 }
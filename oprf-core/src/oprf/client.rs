@@ -9,7 +9,7 @@ use crate::dlog_equality::{DLogEqualityProof, InvalidProof};
 
 use crate::oprf::{
     Affine, BaseField, BlindedOprfRequest, BlindedOprfResponse, BlindingFactor, Curve,
-    PreparedBlindingFactor, mappings,
+    PreparedBlindingFactor, QueryContext, mappings,
 };
 
 /// Blinds a query for the OPRF server using a randomly generated blinding factor.
@@ -30,16 +30,55 @@ use crate::oprf::{
 /// If the provided `blinding_factor` is 0.
 #[must_use]
 pub fn blind_query(query: BaseField, blinding_factor: BlindingFactor) -> BlindedOprfRequest {
+    blind(query, BaseField::zero(), blinding_factor)
+}
+
+/// Like [`blind_query`], but binds the query to the `context` of an OPRF module (see [`query_context`]).
+///
+/// The context is hashed together with the query before the query is mapped to the curve, so the nodes evaluate another point for every context and the outputs of different contexts are unrelated.
+///
+/// # Panics
+/// If the provided `blinding_factor` is 0.
+#[must_use]
+pub fn blind_query_with_context(
+    query: BaseField,
+    context: QueryContext,
+    blinding_factor: BlindingFactor,
+) -> BlindedOprfRequest {
+    blind(query, context.inner(), blinding_factor)
+}
+
+fn blind(
+    query: BaseField,
+    context: BaseField,
+    blinding_factor: BlindingFactor,
+) -> BlindedOprfRequest {
     // The blinding factor shall not be zero. As the chance of getting a zero is negligible we just panic here.
     assert!(
         !blinding_factor.beta().is_zero(),
         "blinding_factor must be != zero"
     );
-    let encoded_input = mappings::encode_to_curve(query);
+    let encoded_input = mappings::encode_to_curve_with_context(query, context);
     let blinded_query = (encoded_input * blinding_factor.beta()).into_affine();
     BlindedOprfRequest(blinded_query)
 }
 
+/// Derives the [`QueryContext`] of an OPRF module from its context string, e.g., the name of the relying party the module serves.
+///
+/// Hashes the `context` with BLAKE3 (domain separated) and reduces the digest into the base field.
+#[must_use]
+pub fn query_context(context: &str) -> QueryContext {
+    use ark_ff::PrimeField as _;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(QUERY_CONTEXT_LABEL);
+    hasher.update(context.as_bytes());
+    QueryContext(BaseField::from_le_bytes_mod_order(
+        hasher.finalize().as_bytes(),
+    ))
+}
+
+const QUERY_CONTEXT_LABEL: &[u8] = b"OPRF_QUERY_CONTEXT";
+
 /// Unblinds an OPRF server response and hashes it to produce the final output for the query. This method is for the non-threshold variant of the OPRF protocol.
 ///
 /// Performs 2Hash-DH: `H(query, unblinded_point)`.
@@ -49,8 +49,13 @@ fn ct_is_square<F: PrimeField>(x: F) -> Choice {
 ///
 /// As mentioned in the RFC, this encoding is non uniformly random in E, as this can only hit about half of the of the curve points.
 pub fn encode_to_curve(input: BaseField) -> Affine {
+    encode_to_curve_with_context(input, BaseField::zero())
+}
+
+/// Like [`encode_to_curve`], but binds the point to a `context`, which is hashed together with the `input`. The context `0` yields the point of [`encode_to_curve`].
+pub fn encode_to_curve_with_context(input: BaseField, context: BaseField) -> Affine {
     // Map the input to a point on the curve using Elligator2
-    let u = hash_to_field(input);
+    let u = hash_to_field(input, context);
     let q = map_to_curve_twisted_edwards(u);
     q.clear_cofactor()
 }
@@ -73,10 +78,11 @@ pub fn hash_to_curve(input: BaseField) -> Affine {
 
 /// An implementation of `hash_to_field` based on [RFC9380](https://www.rfc-editor.org/rfc/rfc9380.html).
 /// Since we use poseidon as the hash function, this automatically ensures the property that the output is a uniformly random field element, without needing to sample extra output and reduce mod p.
-fn hash_to_field(input: BaseField) -> BaseField {
+///
+/// The `context` takes the last element of the state, which is zero without context. Contexts derived with `client::query_context` hit `0` (or the `1` of [`hash_to_field2`]) only with negligible probability.
+fn hash_to_field(input: BaseField, context: BaseField) -> BaseField {
     // hash the input to a field element using poseidon hash
-    let output =
-        poseidon2::bn254::t3::permutation(&[get_hash_to_field_ds(), input, BaseField::zero()]);
+    let output = poseidon2::bn254::t3::permutation(&[get_hash_to_field_ds(), input, context]);
     output[1] // Return the first element of the state as the field element, element 0 is the capacity of the sponge
 }
 
@@ -1,9 +1,139 @@
 //! Utilities for Lagrange interpolation and polynomial evaluation over finite fields.
-//
+//!
 //! Provides functions to compute Lagrange coefficients, evaluate polynomials, and reconstruct secrets from shares.
+//!
+//! # Stable API
+//!
+//! The `try_*` functions ([`try_lagrange_from_coeff`], [`try_single_lagrange_from_coeff`],
+//! [`try_evaluate_poly`] and [`try_reconstruct`]) are the supported entry points for external
+//! tooling such as auditors or independent verifiers. They validate their inputs and return a
+//! [`ShamirError`] instead of panicking.
+//!
+//! The non-`try` variants are kept for the protocol internals, which only call them with inputs
+//! that were already validated. They panic on invalid inputs.
+
+use std::fmt;
 
 use ark_ff::PrimeField;
 
+/// Errors returned by the validated `try_*` functions of this module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShamirError {
+    /// The provided set of party indices is empty.
+    EmptyCoefficients,
+    /// A party index is contained more than once.
+    DuplicateCoefficient,
+    /// A party index is zero. Shares are never evaluated at zero, as this is the secret.
+    ZeroCoefficient,
+    /// The requested party index is not part of the provided set of party indices.
+    MissingCoefficient,
+    /// The provided polynomial has no coefficients.
+    EmptyPolynomial,
+    /// The number of shares does not match the number of Lagrange coefficients.
+    LengthMismatch {
+        /// The number of provided shares.
+        shares: usize,
+        /// The number of provided Lagrange coefficients.
+        lagrange: usize,
+    },
+}
+
+impl fmt::Display for ShamirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShamirError::EmptyCoefficients => f.write_str("party indices must not be empty"),
+            ShamirError::DuplicateCoefficient => f.write_str("party indices must be unique"),
+            ShamirError::ZeroCoefficient => f.write_str("party indices must not be zero"),
+            ShamirError::MissingCoefficient => {
+                f.write_str("party index is not part of the provided party indices")
+            }
+            ShamirError::EmptyPolynomial => f.write_str("polynomial must not be empty"),
+            ShamirError::LengthMismatch { shares, lagrange } => write!(
+                f,
+                "got {shares} shares but {lagrange} lagrange coefficients"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShamirError {}
+
+/// Checks that `coeffs` is non-empty, contains no zero and no duplicate party indices.
+fn validate_coeffs<F: PrimeField + From<T>, T: Copy + Eq>(coeffs: &[T]) -> Result<(), ShamirError> {
+    if coeffs.is_empty() {
+        return Err(ShamirError::EmptyCoefficients);
+    }
+    for (idx, i) in coeffs.iter().enumerate() {
+        if F::from(*i).is_zero() {
+            return Err(ShamirError::ZeroCoefficient);
+        }
+        if coeffs[idx + 1..].contains(i) {
+            return Err(ShamirError::DuplicateCoefficient);
+        }
+    }
+    Ok(())
+}
+
+/// Computes the Lagrange coefficients for the provided party indices.
+///
+/// Validated version of [`lagrange_from_coeff`].
+///
+/// # Errors
+/// Returns a [`ShamirError`] if `coeffs` is empty, contains a zero, or contains duplicates.
+pub fn try_lagrange_from_coeff<F: PrimeField + From<T>, T: Copy + Eq>(
+    coeffs: &[T],
+) -> Result<Vec<F>, ShamirError> {
+    validate_coeffs::<F, T>(coeffs)?;
+    Ok(lagrange_from_coeff(coeffs))
+}
+
+/// Computes the Lagrange coefficient for a specific party identifier.
+///
+/// Validated version of [`single_lagrange_from_coeff`].
+///
+/// # Errors
+/// Returns a [`ShamirError`] if `coeffs` is empty, contains a zero or duplicates, or if `my_id` is not part of `coeffs`.
+pub fn try_single_lagrange_from_coeff<F: PrimeField + From<T>, T: Copy + Eq>(
+    my_id: T,
+    coeffs: &[T],
+) -> Result<F, ShamirError> {
+    validate_coeffs::<F, T>(coeffs)?;
+    if !coeffs.contains(&my_id) {
+        return Err(ShamirError::MissingCoefficient);
+    }
+    Ok(single_lagrange_from_coeff(my_id, coeffs))
+}
+
+/// Evaluates a polynomial at the given point.
+///
+/// Validated version of [`evaluate_poly`].
+///
+/// # Errors
+/// Returns [`ShamirError::EmptyPolynomial`] if `poly` is empty.
+pub fn try_evaluate_poly<F: PrimeField>(poly: &[F], x: F) -> Result<F, ShamirError> {
+    if poly.is_empty() {
+        return Err(ShamirError::EmptyPolynomial);
+    }
+    Ok(evaluate_poly(poly, x))
+}
+
+/// Recovers the secret by combining shares with Lagrange coefficients.
+///
+/// Validated version of [`reconstruct`].
+///
+/// # Errors
+/// Returns [`ShamirError::LengthMismatch`] if `shares` and `lagrange` differ in length.
+pub fn try_reconstruct<F: PrimeField>(shares: &[F], lagrange: &[F]) -> Result<F, ShamirError> {
+    if shares.len() != lagrange.len() {
+        return Err(ShamirError::LengthMismatch {
+            shares: shares.len(),
+            lagrange: lagrange.len(),
+        });
+    }
+    Ok(reconstruct(shares, lagrange))
+}
+
 /// Computes the Lagrange coefficients for the provided party indices.
 ///
 /// # Arguments
@@ -13,6 +143,10 @@ use ark_ff::PrimeField;
 /// # Returns
 ///
 /// Vector of Lagrange coefficients for each party.
+///
+/// # Panics
+/// Might panic if two indices map to the same field element. Duplicate indices silently produce
+/// wrong coefficients. Use [`try_lagrange_from_coeff`] for untrusted input.
 pub fn lagrange_from_coeff<F: PrimeField + From<T>, T: Copy + Eq>(coeffs: &[T]) -> Vec<F> {
     let num = coeffs.len();
     let mut res = Vec::with_capacity(num);
@@ -34,7 +168,8 @@ pub fn lagrange_from_coeff<F: PrimeField + From<T>, T: Copy + Eq>(coeffs: &[T])
 /// The Lagrange coefficient for `my_id`.
 ///
 /// # Panics
-/// Might panic if chosen `T` does not fit into `Primefield`.
+/// Might panic if two indices map to the same field element. Duplicate indices silently produce
+/// a wrong coefficient. Use [`try_single_lagrange_from_coeff`] for untrusted input.
 pub fn single_lagrange_from_coeff<F: PrimeField + From<T>, T: Copy + Eq>(
     my_id: T,
     coeffs: &[T],
@@ -64,7 +199,7 @@ pub fn single_lagrange_from_coeff<F: PrimeField + From<T>, T: Copy + Eq>(
 /// The polynomial evaluated at `x`.
 ///
 /// # Panics
-/// If the provided polynomial is empty.
+/// If the provided polynomial is empty. Use [`try_evaluate_poly`] for untrusted input.
 pub fn evaluate_poly<F: PrimeField>(poly: &[F], x: F) -> F {
     assert!(!poly.is_empty(), "Poly must not be empty");
     let mut iter = poly.iter().rev();
@@ -88,7 +223,7 @@ pub fn evaluate_poly<F: PrimeField>(poly: &[F], x: F) -> F {
 /// The reconstructed secret value.
 ///
 /// # Panics
-/// If provided shares and lagrange coefficients are not same length
+/// If provided shares and lagrange coefficients are not same length. Use [`try_reconstruct`] for untrusted input.
 pub fn reconstruct<F: PrimeField>(shares: &[F], lagrange: &[F]) -> F {
     assert_eq!(
         shares.len(),
@@ -151,3 +286,60 @@ pub(crate) mod test_utils {
         reconstruct_point(&shares, &lagrange)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ark_babyjubjub::Fr;
+    use ark_ff::UniformRand as _;
+
+    use super::*;
+
+    #[test]
+    fn try_lagrange_rejects_invalid_coeffs() {
+        assert_eq!(
+            try_lagrange_from_coeff::<Fr, u64>(&[]),
+            Err(ShamirError::EmptyCoefficients)
+        );
+        assert_eq!(
+            try_lagrange_from_coeff::<Fr, u64>(&[1, 2, 1]),
+            Err(ShamirError::DuplicateCoefficient)
+        );
+        assert_eq!(
+            try_lagrange_from_coeff::<Fr, u64>(&[0, 1]),
+            Err(ShamirError::ZeroCoefficient)
+        );
+        assert_eq!(
+            try_single_lagrange_from_coeff::<Fr, u64>(3, &[1, 2]),
+            Err(ShamirError::MissingCoefficient)
+        );
+    }
+
+    #[test]
+    fn try_evaluate_and_reconstruct() {
+        let mut rng = rand::thread_rng();
+        let poly = (0..3).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let parties = [1u64, 3, 5];
+        let shares = parties
+            .iter()
+            .map(|i| try_evaluate_poly(&poly, Fr::from(*i)))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("poly is not empty");
+        let lagrange = try_lagrange_from_coeff(&parties).expect("valid coeffs");
+        assert_eq!(
+            try_reconstruct(&shares, &lagrange),
+            Ok(poly[0]),
+            "should reconstruct the secret"
+        );
+        assert_eq!(
+            try_evaluate_poly::<Fr>(&[], Fr::from(1)),
+            Err(ShamirError::EmptyPolynomial)
+        );
+        assert_eq!(
+            try_reconstruct(&shares[..2], &lagrange),
+            Err(ShamirError::LengthMismatch {
+                shares: 2,
+                lagrange: 3
+            })
+        );
+    }
+}
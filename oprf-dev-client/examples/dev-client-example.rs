@@ -136,6 +136,7 @@ impl DevClient for ExampleDevClient {
             config.threshold,
             setup.oprf_key_id,
             client,
+            &oprf_client::RetryPolicy::default(),
         )
         .await?
         .context("while fetching OPRF public key from nodes")?;
@@ -177,6 +178,8 @@ impl DevClient for ExampleDevClient {
             request_id,
             blinded_query: blinded_query.blinded_query(),
             auth: ExampleOprfRequestAuth(setup.oprf_key_id),
+            issued_at: Some(oprf_client::unix_timestamp()),
+            batch: Vec::new(),
         };
         Ok(StressTestItem {
             request_id,
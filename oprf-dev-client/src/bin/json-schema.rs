@@ -0,0 +1,68 @@
+//! Writes the JSON Schemas of the wire types, see `oprf_types::schema`.
+//!
+//! The schemas are written to `{out_dir}/v{version}/{name}.json`, where `version` is the protocol version of the client (sent in the `x-taceo-oprf-protocol-version` header). An `index.json` next to them lists the schemas together with the [`SchemaFingerprint`] of the build, so documentation and codegen pipelines can detect schema changes.
+//!
+//! The `auth` field of an [`OprfRequest`] is specific to the authentication module of the node and is described as arbitrary JSON.
+use std::path::PathBuf;
+
+use clap::Parser;
+use eyre::Context as _;
+use oprf_types::{
+    api::{
+        AggregatedOprfResponse, Committee, DelegateOprfResponse, EpochChanged, LoadedOprfKey,
+        ModuleParams, NodeInfo, OprfBatchChallenge, OprfBatchProofShares, OprfRequest,
+        OprfResponse, SchemaFingerprint,
+    },
+    crypto::{SecretGenCiphertexts, SecretGenCommitment},
+    schema::{DLogCommitments, DLogProofShare},
+};
+use schemars::{JsonSchema, Schema, schema_for};
+
+#[derive(Clone, Parser, Debug)]
+struct SchemaConfig {
+    /// The directory the schemas are written to
+    #[clap(long, env = "OPRF_DEV_CLIENT_SCHEMA_DIR", default_value = "schemas")]
+    out_dir: PathBuf,
+}
+
+fn schema<T: JsonSchema>(name: &'static str) -> (&'static str, Schema) {
+    (name, schema_for!(T))
+}
+
+fn main() -> eyre::Result<()> {
+    let config = SchemaConfig::parse();
+    let dir = config.out_dir.join(format!("v{}", oprf_client::VERSION));
+    std::fs::create_dir_all(&dir).with_context(|| format!("cannot create {}", dir.display()))?;
+
+    let schemas = [
+        schema::<OprfRequest<serde_json::Value>>("oprf-request"),
+        schema::<OprfResponse>("oprf-response"),
+        schema::<DLogCommitments>("challenge"),
+        schema::<DLogProofShare>("proof-share"),
+        schema::<OprfBatchChallenge>("batch-challenge"),
+        schema::<OprfBatchProofShares>("batch-proof-shares"),
+        schema::<DelegateOprfResponse>("delegate-oprf-response"),
+        schema::<AggregatedOprfResponse>("aggregated-oprf-response"),
+        schema::<EpochChanged>("epoch-changed"),
+        schema::<Committee>("committee"),
+        schema::<NodeInfo>("node-info"),
+        schema::<LoadedOprfKey>("loaded-oprf-key"),
+        schema::<ModuleParams>("module-params"),
+        schema::<SecretGenCommitment>("secret-gen-commitment"),
+        schema::<SecretGenCiphertexts>("secret-gen-ciphertexts"),
+    ];
+    for (name, schema) in &schemas {
+        let path = dir.join(format!("{name}.json"));
+        std::fs::write(&path, serde_json::to_vec_pretty(schema)?)
+            .with_context(|| format!("cannot write {}", path.display()))?;
+    }
+    let index = serde_json::json!({
+        "protocol_version": oprf_client::VERSION,
+        "schema_fingerprint": SchemaFingerprint::CURRENT,
+        "schemas": schemas.iter().map(|(name, _)| format!("{name}.json")).collect::<Vec<_>>(),
+    });
+    std::fs::write(dir.join("index.json"), serde_json::to_vec_pretty(&index)?)
+        .context("cannot write index")?;
+    println!("wrote {} schemas to {}", schemas.len(), dir.display());
+    Ok(())
+}
@@ -0,0 +1,247 @@
+//! Offline verification of OPRF session transcripts, see `oprf_types::transcript`.
+//!
+//! Takes the transcript files of a single session (the client transcript and any number of node
+//! transcripts) and checks that:
+//! - all transcripts belong to the same session,
+//! - the nodes agreed on the OPRF public key and epoch,
+//! - the challenge of the client is the combination of the received commitments,
+//! - the combined proof verifies if all proof shares were received (with `--context` for a module with context),
+//! - every node sent and received exactly the frames the client recorded for it.
+//!
+//! Exit codes:
+//! - `0` – all checks passed
+//! - `1` – a check failed or a transcript could not be read
+//! - `2` – invalid arguments (reported by clap)
+use std::{path::PathBuf, process::ExitCode};
+
+use ark_ec::AffineRepr as _;
+use clap::Parser;
+use eyre::Context as _;
+use oprf_core::ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir};
+use oprf_types::{
+    api::OprfResponse,
+    crypto::PartyId,
+    transcript::{FrameDirection, Transcript, TranscriptMessage, TranscriptParticipant},
+};
+use serde::Serialize;
+
+const EXIT_CHECK_FAILED: u8 = 1;
+
+#[derive(Clone, Parser, Debug)]
+struct VerifyConfig {
+    /// The transcript files of the session
+    #[clap(required = true)]
+    transcripts: Vec<PathBuf>,
+    /// The context of the OPRF module the session ran on, if it has one
+    #[clap(long)]
+    context: Option<String>,
+}
+
+/// The frames of a session as seen by one side, per node.
+#[derive(Default)]
+struct NodeFrames<'a> {
+    blinded_query: Option<&'a ark_babyjubjub::EdwardsAffine>,
+    response: Option<&'a OprfResponse>,
+    challenge: Option<&'a DLogCommitmentsShamir>,
+    proof_share: Option<&'a DLogProofShareShamir>,
+}
+
+impl<'a> NodeFrames<'a> {
+    fn collect(transcript: &'a Transcript, peer: Option<TranscriptParticipant>) -> Self {
+        let mut frames = Self::default();
+        for frame in &transcript.frames {
+            if peer.is_some_and(|peer| peer != frame.peer) {
+                continue;
+            }
+            match &frame.message {
+                TranscriptMessage::Request { blinded_query, .. } => {
+                    frames.blinded_query = Some(blinded_query);
+                }
+                TranscriptMessage::Response(response) => frames.response = Some(response),
+                TranscriptMessage::Challenge(challenge) => frames.challenge = Some(challenge),
+                TranscriptMessage::ProofShare(proof_share) => {
+                    frames.proof_share = Some(proof_share);
+                }
+                _ => {}
+            }
+        }
+        frames
+    }
+}
+
+/// Compares two messages by their JSON encoding.
+fn same<T: Serialize>(a: &T, b: &T) -> eyre::Result<bool> {
+    Ok(serde_json::to_value(a)? == serde_json::to_value(b)?)
+}
+
+fn load(path: &PathBuf) -> eyre::Result<Transcript> {
+    let json = std::fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+    serde_json::from_slice(&json).with_context(|| format!("cannot parse {}", path.display()))
+}
+
+fn verify_client(client: &Transcript, context: ark_babyjubjub::Fq) -> eyre::Result<()> {
+    let party_ids = client
+        .frames
+        .iter()
+        .filter_map(|frame| match (&frame.direction, &frame.message) {
+            (FrameDirection::Received, TranscriptMessage::Response(response)) => {
+                Some(response.party_id)
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    eyre::ensure!(!party_ids.is_empty(), "client received no responses");
+    let nodes = party_ids
+        .iter()
+        .map(|party_id| {
+            (
+                *party_id,
+                NodeFrames::collect(client, Some(TranscriptParticipant::Node(*party_id))),
+            )
+        })
+        .collect::<Vec<_>>();
+    let responses = nodes
+        .iter()
+        .filter_map(|(_, frames)| frames.response)
+        .collect::<Vec<_>>();
+    let oprf_pub_key_with_epoch = &responses[0].oprf_pub_key_with_epoch;
+    for response in &responses {
+        eyre::ensure!(
+            &response.oprf_pub_key_with_epoch == oprf_pub_key_with_epoch,
+            "node {} answered with {:?}, node {} with {oprf_pub_key_with_epoch:?}",
+            response.party_id,
+            response.oprf_pub_key_with_epoch,
+            responses[0].party_id,
+        );
+    }
+
+    let Some(challenge) = nodes[0].1.challenge else {
+        println!("client: session ended before the challenge was sent");
+        return Ok(());
+    };
+    let expected = DLogCommitmentsShamir::combine_commitments(
+        &responses
+            .iter()
+            .map(|response| response.commitments.clone())
+            .collect::<Vec<_>>(),
+        party_ids.iter().map(|id| id.into_inner() + 1).collect(),
+    );
+    eyre::ensure!(
+        same(challenge, &expected)?,
+        "challenge does not match the combined commitments"
+    );
+    for (party_id, frames) in &nodes {
+        eyre::ensure!(
+            frames
+                .challenge
+                .is_some_and(|sent| same(sent, challenge).unwrap_or(false)),
+            "node {party_id} got a different challenge"
+        );
+    }
+
+    let proof_shares = nodes
+        .iter()
+        .filter_map(|(_, frames)| frames.proof_share.cloned())
+        .collect::<Vec<_>>();
+    if proof_shares.len() != nodes.len() {
+        println!(
+            "client: received {} of {} proof shares",
+            proof_shares.len(),
+            nodes.len()
+        );
+        return Ok(());
+    }
+    let blinded_query = *nodes[0]
+        .1
+        .blinded_query
+        .ok_or_else(|| eyre::eyre!("client transcript contains no request"))?;
+    let oprf_public_key = oprf_pub_key_with_epoch.key.inner();
+    let blinded_response = challenge.blinded_response();
+    challenge
+        .clone()
+        .combine_proofs(
+            client.request_id,
+            &proof_shares,
+            oprf_public_key,
+            blinded_query,
+            context,
+        )
+        .verify_with_context(
+            oprf_public_key,
+            blinded_query,
+            blinded_response,
+            ark_babyjubjub::EdwardsAffine::generator(),
+            context,
+        )
+        .map_err(|_| eyre::eyre!("combined proof does not verify"))?;
+    println!(
+        "client: {} nodes, epoch {}, proof verifies",
+        nodes.len(),
+        oprf_pub_key_with_epoch.epoch
+    );
+    Ok(())
+}
+
+fn verify_node(client: &Transcript, node: &Transcript, party_id: PartyId) -> eyre::Result<()> {
+    let seen_by_client = NodeFrames::collect(client, Some(TranscriptParticipant::Node(party_id)));
+    let seen_by_node = NodeFrames::collect(node, None);
+    eyre::ensure!(
+        seen_by_node.blinded_query == seen_by_client.blinded_query,
+        "node {party_id} received a different blinded query"
+    );
+    eyre::ensure!(
+        same(&seen_by_node.response, &seen_by_client.response)?,
+        "client received a different response from node {party_id}"
+    );
+    eyre::ensure!(
+        same(&seen_by_node.challenge, &seen_by_client.challenge)?,
+        "node {party_id} received a different challenge"
+    );
+    eyre::ensure!(
+        same(&seen_by_node.proof_share, &seen_by_client.proof_share)?,
+        "client received a different proof share from node {party_id}"
+    );
+    println!("node {party_id}: frames match the client transcript");
+    Ok(())
+}
+
+fn verify(config: &VerifyConfig) -> eyre::Result<()> {
+    let transcripts = config
+        .transcripts
+        .iter()
+        .map(load)
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let request_id = transcripts[0].request_id;
+    eyre::ensure!(
+        transcripts.iter().all(|t| t.request_id == request_id),
+        "transcripts belong to different sessions"
+    );
+    let client = transcripts
+        .iter()
+        .find(|t| t.recorded_by == TranscriptParticipant::Client)
+        .ok_or_else(|| eyre::eyre!("no client transcript provided"))?;
+    let context = config
+        .context
+        .as_deref()
+        .map(|context| oprf_core::oprf::client::query_context(context).inner())
+        .unwrap_or_default();
+    verify_client(client, context)?;
+    for transcript in &transcripts {
+        if let TranscriptParticipant::Node(party_id) = transcript.recorded_by {
+            verify_node(client, transcript, party_id)?;
+        }
+    }
+    println!("ok: session {request_id}");
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let config = VerifyConfig::parse();
+    match verify(&config) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("verification failed: {err:?}");
+            ExitCode::from(EXIT_CHECK_FAILED)
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Exports and imports [`SignedCommitteeBundle`]s, see [`oprf_types::bundle`].
+
+use std::path::Path;
+
+use alloy::{
+    primitives::{Address, U256},
+    providers::{DynProvider, Provider as _},
+    signers::{Signer as _, local::PrivateKeySigner},
+};
+use eyre::Context as _;
+use oprf_types::{
+    bundle::{BundleMember, CommitteeBundle, SignedCommitteeBundle},
+    chain::OprfKeyRegistry,
+    crypto::PartyId,
+};
+
+use crate::DevClientConfig;
+
+/// Reads the committee from the `OprfKeyRegistry` and signs it with the `admin` wallet.
+///
+/// `nodes` are the base URLs of the nodes, ordered by party id.
+pub async fn export_bundle(
+    provider: DynProvider,
+    oprf_key_registry: Address,
+    nodes: &[String],
+    admin: &PrivateKeySigner,
+) -> eyre::Result<SignedCommitteeBundle> {
+    let chain_id = provider.get_chain_id().await?;
+    let contract = OprfKeyRegistry::new(oprf_key_registry, provider);
+    let threshold = contract.threshold().call().await?;
+    let num_peers = contract.numPeers().call().await?;
+    if usize::from(num_peers) != nodes.len() {
+        eyre::bail!(
+            "registry has {num_peers} nodes, but {} node URLs are configured",
+            nodes.len()
+        );
+    }
+    let mut members = Vec::with_capacity(nodes.len());
+    for (party_id, url) in (0..num_peers).zip(nodes) {
+        members.push(BundleMember {
+            party_id: PartyId(party_id),
+            address: contract.peerAddresses(U256::from(party_id)).call().await?,
+            url: url.clone(),
+        });
+    }
+    let bundle = CommitteeBundle {
+        chain_id,
+        oprf_key_registry,
+        threshold: threshold.try_into().context("registry threshold is zero")?,
+        members,
+    };
+    bundle.validate()?;
+    let signature = admin.sign_message(&bundle.signing_message()).await?;
+    Ok(SignedCommitteeBundle { bundle, signature })
+}
+
+/// Loads the bundle at `path`, checks it with [`oprf_client::discover_from_bundle`] and uses its nodes, threshold and registry instead of the configured ones.
+pub async fn apply_bundle(
+    mut config: DevClientConfig,
+    path: &Path,
+    admin: Address,
+) -> eyre::Result<DevClientConfig> {
+    let signed: SignedCommitteeBundle = serde_json::from_slice(
+        &std::fs::read(path).with_context(|| format!("while reading {}", path.display()))?,
+    )
+    .context("while parsing committee bundle")?;
+    let bundle = oprf_client::discover_from_bundle(&signed, admin, &reqwest::Client::new())
+        .await
+        .context("while checking committee bundle")?;
+    tracing::info!(
+        "using committee of {} nodes on chain {} from {}",
+        bundle.members.len(),
+        bundle.chain_id,
+        path.display()
+    );
+    config.nodes = bundle
+        .members
+        .into_iter()
+        .map(|member| member.url)
+        .collect();
+    config.threshold = usize::from(bundle.threshold.get());
+    config.oprf_key_registry_contract = bundle.oprf_key_registry;
+    Ok(config)
+}
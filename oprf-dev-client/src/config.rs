@@ -1,7 +1,7 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use alloy::primitives::Address;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use secrecy::SecretString;
 
 #[derive(Clone, Parser, Debug)]
@@ -17,6 +17,22 @@ pub struct StressTestOprfCommand {
     /// Send requests sequentially instead of concurrently
     #[clap(long, env = "OPRF_DEV_CLIENT_SKIP_CHECKS")]
     pub skip_checks: bool,
+
+    /// The wire format of the requests. `alternating` switches between `json` and `cbor` per request
+    #[clap(long, env = "OPRF_DEV_CLIENT_WIRE_FORMAT", value_enum, default_value_t = WireFormats::Cbor)]
+    pub wire_format: WireFormats,
+
+    /// Client protocol versions announced to the nodes, assigned round-robin per request. Every node must accept exactly the versions matching the `version_req` at its `/info`. Defaults to the version of the client
+    #[clap(long, env = "OPRF_DEV_CLIENT_CLIENT_VERSIONS", value_delimiter = ',')]
+    pub client_versions: Vec<semver::Version>,
+}
+
+/// The wire formats of the requests of a stress test, see [`crate::interop`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WireFormats {
+    Json,
+    Cbor,
+    Alternating,
 }
 
 #[derive(Clone, Parser, Debug)]
@@ -42,6 +58,86 @@ pub struct DelegateTestCommand {
     pub delegate_service: Option<String>,
 }
 
+#[derive(Clone, Parser, Debug)]
+pub struct ValidateEventsCommand {
+    /// The first block of the range to replay
+    #[clap(long, env = "OPRF_DEV_CLIENT_FROM_BLOCK", default_value = "0")]
+    pub from_block: u64,
+
+    /// The last block (inclusive) of the range to replay. Defaults to the latest block
+    #[clap(long, env = "OPRF_DEV_CLIENT_TO_BLOCK")]
+    pub to_block: Option<u64>,
+
+    /// Max number of blocks per `eth_getLogs` request. Halved automatically if the provider rejects the range
+    #[clap(
+        long,
+        env = "OPRF_DEV_CLIENT_BLOCK_CHUNK_SIZE",
+        default_value = "10000"
+    )]
+    pub chunk_size: u64,
+
+    /// Number of blocks every request re-fetches from the previous range. Logs seen already are skipped
+    #[clap(long, env = "OPRF_DEV_CLIENT_OVERLAP_BLOCKS", default_value = "2")]
+    pub overlap_blocks: u64,
+}
+
+#[derive(Clone, Parser, Debug)]
+pub struct ExportBundleCommand {
+    /// The file the signed committee bundle is written to
+    #[clap(
+        long,
+        env = "OPRF_DEV_CLIENT_BUNDLE_OUTPUT",
+        default_value = "committee-bundle.json"
+    )]
+    pub output: PathBuf,
+}
+
+#[derive(Clone, Parser, Debug)]
+pub struct GasReportCommand {
+    /// The threshold of the committee
+    #[clap(long, env = "OPRF_DEV_CLIENT_GAS_THRESHOLD", default_value = "2")]
+    pub threshold: u16,
+
+    /// The number of peers of the committee
+    #[clap(long, env = "OPRF_DEV_CLIENT_GAS_NUM_PEERS", default_value = "3")]
+    pub num_peers: u16,
+
+    /// The sender of the estimated calls. Defaults to the address of the TACEO admin wallet
+    #[clap(long, env = "OPRF_DEV_CLIENT_GAS_FROM")]
+    pub from: Option<Address>,
+
+    /// If set, writes the report as JSON to this file
+    #[clap(long, env = "OPRF_DEV_CLIENT_GAS_OUTPUT")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Parser, Debug)]
+pub struct SoakTestCommand {
+    /// How long the load is kept up, e.g. `7days`
+    #[clap(long, env = "OPRF_DEV_CLIENT_SOAK_DURATION", default_value = "1h", value_parser = humantime::parse_duration)]
+    pub duration: Duration,
+
+    /// Number of OPRF runs in flight at the same time
+    #[clap(long, env = "OPRF_DEV_CLIENT_SOAK_CONCURRENCY", default_value = "4")]
+    pub concurrency: usize,
+
+    /// How often the memory stats of the nodes are sampled
+    #[clap(long, env = "OPRF_DEV_CLIENT_SOAK_SAMPLE_INTERVAL", default_value = "1min", value_parser = humantime::parse_duration)]
+    pub sample_interval: Duration,
+
+    /// Max relative growth of the allocated bytes of a node between the first and the final sample before it is reported as leak
+    #[clap(long, env = "OPRF_DEV_CLIENT_SOAK_MAX_GROWTH", default_value = "0.5")]
+    pub max_growth: f64,
+
+    /// The file the samples are written to, one JSON object per node and sample
+    #[clap(
+        long,
+        env = "OPRF_DEV_CLIENT_SOAK_OUTPUT",
+        default_value = "soak-samples.jsonl"
+    )]
+    pub output: PathBuf,
+}
+
 #[derive(Clone, Debug, Subcommand)]
 pub enum Command {
     Test,
@@ -49,7 +145,14 @@ pub enum Command {
     DelegateTest(DelegateTestCommand),
     StressTestOprf(StressTestOprfCommand),
     StressTestKeyGen(StressTestKeyGenCommand),
+    /// Keeps OPRF runs in flight for a long time and samples the memory stats of the nodes (requires nodes with the `jemalloc` feature and `OprfServiceBuilder::memory_stats`)
+    SoakTest(SoakTestCommand),
     ReshareTest(ReshareTest),
+    ValidateEvents(ValidateEventsCommand),
+    /// Reads the committee from the `OprfKeyRegistry` and writes it as committee bundle signed by the TACEO admin wallet. The nodes must be ordered by party id
+    ExportBundle(ExportBundleCommand),
+    /// Estimates gas and calldata size of every key-gen round for a committee and sums them per key-gen and reshare
+    GasReport(GasReportCommand),
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -67,10 +170,24 @@ pub struct DevClientConfig {
     #[clap(long, env = "OPRF_DEV_CLIENT_THRESHOLD", default_value = "2")]
     pub threshold: usize,
 
-    /// The Address of the OprfKeyRegistry contract.
-    #[clap(long, env = "OPRF_DEV_CLIENT_OPRF_KEY_REGISTRY_CONTRACT")]
+    /// The Address of the OprfKeyRegistry contract. Required unless a committee bundle is used
+    #[clap(
+        long,
+        env = "OPRF_DEV_CLIENT_OPRF_KEY_REGISTRY_CONTRACT",
+        required_unless_present = "committee_bundle",
+        default_value_t = Address::ZERO,
+        hide_default_value = true
+    )]
     pub oprf_key_registry_contract: Address,
 
+    /// A committee bundle (see `export-bundle`). If set, its nodes, threshold and OprfKeyRegistry are used instead of the configured ones
+    #[clap(long, env = "OPRF_DEV_CLIENT_COMMITTEE_BUNDLE")]
+    pub committee_bundle: Option<PathBuf>,
+
+    /// The admin that must have signed the committee bundle. Defaults to the address of the TACEO admin wallet
+    #[clap(long, env = "OPRF_DEV_CLIENT_BUNDLE_ADMIN")]
+    pub bundle_admin: Option<Address>,
+
     /// The RPC for chain communication
     #[clap(
         long,
@@ -97,6 +214,10 @@ pub struct DevClientConfig {
     #[clap(long, env = "OPRF_DEV_CLIENT_WAIT_TIME", default_value="2min", value_parser=humantime::parse_duration)]
     pub max_wait_time: Duration,
 
+    /// If set, writes the transcripts of all `distributed_oprf` sessions to this directory. Check them with `transcript-verify`.
+    #[clap(long, env = "OPRF_DEV_CLIENT_TRANSCRIPT_DIR")]
+    pub transcript_dir: Option<PathBuf>,
+
     /// Command
     #[command(subcommand)]
     pub command: Command,
@@ -1,56 +1,247 @@
-use alloy::{primitives::Address, providers::DynProvider};
-use oprf_types::{OprfKeyId, chain::OprfKeyRegistry};
+//! Admin operations on the `OprfKeyRegistry`.
+//!
+//! Every operation is retried within the budget of a [`RetryPolicy`]. A transaction can be mined although its result got lost (e.g., the RPC connection dropped while waiting for the receipt), so before every retry, the registry is checked for the event the operation emits. If it was emitted since the first attempt, the operation is not submitted again and reported as [`AdminOutcome::AlreadyDone`].
 
+use std::{fmt, future::Future};
+
+use alloy::{
+    primitives::{Address, B256, TxHash, U256},
+    providers::{DynProvider, Provider as _},
+    rpc::types::Filter,
+    sol_types::SolEvent,
+};
+use backon::BackoffBuilder as _;
+use eyre::Context as _;
+use oprf_client::RetryPolicy;
+use oprf_types::{
+    OprfKeyId,
+    chain::OprfKeyRegistry::{
+        self, KeyDeletion, OprfKeyRegistryErrors, OprfKeyRegistryInstance, ReshareRound1,
+        SecretGenRound1,
+    },
+};
+
+/// The outcome of an admin operation.
+#[derive(Debug)]
+pub enum AdminOutcome {
+    /// The registry emitted the event of the operation since the first attempt, e.g., because an attempt was mined although its result got lost. Nothing was submitted again.
+    AlreadyDone,
+    /// The transaction of the operation was mined successfully.
+    Submitted {
+        /// The hash of the mined transaction.
+        tx_hash: TxHash,
+        /// The number of submitted attempts, including the successful one.
+        attempts: usize,
+    },
+    /// The operation failed, either because the registry rejected it or because the retry budget is exhausted.
+    Failed {
+        /// The number of submitted attempts.
+        attempts: usize,
+        /// The error of the last attempt.
+        error: eyre::Report,
+    },
+}
+
+impl AdminOutcome {
+    /// Returns an error if the operation [failed](AdminOutcome::Failed).
+    pub fn into_result(self) -> eyre::Result<()> {
+        match self {
+            AdminOutcome::AlreadyDone | AdminOutcome::Submitted { .. } => Ok(()),
+            AdminOutcome::Failed { attempts, error } => {
+                Err(error.wrap_err(format!("failed after {attempts} attempt(s)")))
+            }
+        }
+    }
+}
+
+impl fmt::Display for AdminOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminOutcome::AlreadyDone => f.write_str("already done"),
+            AdminOutcome::Submitted { tx_hash, attempts } => {
+                write!(f, "submitted {tx_hash} after {attempts} attempt(s)")
+            }
+            AdminOutcome::Failed { attempts, error } => {
+                write!(f, "failed after {attempts} attempt(s): {error}")
+            }
+        }
+    }
+}
+
+/// Starts the key-gen of `oprf_key_id`. Already done if the registry emitted `SecretGenRound1` for the key since the first attempt.
 pub async fn init_key_gen(
     provider: DynProvider,
     oprf_key_registry: Address,
     oprf_key_id: OprfKeyId,
-) -> eyre::Result<()> {
-    let oprf_key_registry = OprfKeyRegistry::new(oprf_key_registry, provider);
-    let receipt = oprf_key_registry
-        .initKeyGen(oprf_key_id.into_inner())
-        .send()
-        .await?
-        .get_receipt()
-        .await?;
-    if !receipt.status() {
-        eyre::bail!("failed to init OPRF key gen");
-    }
-    Ok(())
+    retry_policy: &RetryPolicy,
+) -> AdminOutcome {
+    let contract = OprfKeyRegistry::new(oprf_key_registry, provider);
+    submit::<SecretGenRound1, _, _>(
+        "init key-gen",
+        &contract,
+        oprf_key_id,
+        retry_policy,
+        || async {
+            let pending = contract.initKeyGen(oprf_key_id.into_inner()).send().await?;
+            Ok(pending.get_receipt().await?)
+        },
+    )
+    .await
 }
 
+/// Starts a reshare of `oprf_key_id`. Already done if the registry emitted `ReshareRound1` for the key since the first attempt.
 pub async fn init_reshare(
     provider: DynProvider,
     oprf_key_registry: Address,
     oprf_key_id: OprfKeyId,
-) -> eyre::Result<()> {
-    let oprf_key_registry = OprfKeyRegistry::new(oprf_key_registry, provider);
-    let receipt = oprf_key_registry
-        .initReshare(oprf_key_id.into_inner())
-        .send()
-        .await?
-        .get_receipt()
-        .await?;
-    if !receipt.status() {
-        eyre::bail!("failed to init OPRF reshare");
-    }
-    Ok(())
+    retry_policy: &RetryPolicy,
+) -> AdminOutcome {
+    let contract = OprfKeyRegistry::new(oprf_key_registry, provider);
+    submit::<ReshareRound1, _, _>(
+        "init reshare",
+        &contract,
+        oprf_key_id,
+        retry_policy,
+        || async {
+            let pending = contract
+                .initReshare(oprf_key_id.into_inner())
+                .send()
+                .await?;
+            Ok(pending.get_receipt().await?)
+        },
+    )
+    .await
 }
 
+/// Deletes the public key and key material of `oprf_key_id`. Already done if the registry emitted `KeyDeletion` for the key since the first attempt.
 pub async fn delete_oprf_key_material(
     provider: DynProvider,
     oprf_key_registry: Address,
     oprf_key_id: OprfKeyId,
-) -> eyre::Result<()> {
-    let oprf_key_registry = OprfKeyRegistry::new(oprf_key_registry, provider);
-    let receipt = oprf_key_registry
-        .deleteOprfPublicKey(oprf_key_id.into_inner())
-        .send()
-        .await?
-        .get_receipt()
-        .await?;
-    if !receipt.status() {
-        eyre::bail!("failed to delete OPRF pk");
+    retry_policy: &RetryPolicy,
+) -> AdminOutcome {
+    let contract = OprfKeyRegistry::new(oprf_key_registry, provider);
+    submit::<KeyDeletion, _, _>(
+        "delete OPRF key",
+        &contract,
+        oprf_key_id,
+        retry_policy,
+        || async {
+            let pending = contract
+                .deleteOprfPublicKey(oprf_key_id.into_inner())
+                .send()
+                .await?;
+            Ok(pending.get_receipt().await?)
+        },
+    )
+    .await
+}
+
+/// The error of a single attempt.
+enum AttemptError {
+    /// The registry rejected the transaction, retrying does not help.
+    Rejected(eyre::Report),
+    /// The transaction might not have been mined (or its receipt got lost).
+    Transient(eyre::Report),
+}
+
+impl From<alloy::contract::Error> for AttemptError {
+    fn from(err: alloy::contract::Error) -> Self {
+        match err.as_decoded_interface_error::<OprfKeyRegistryErrors>() {
+            Some(revert) => AttemptError::Rejected(eyre::eyre!("registry rejected: {revert}")),
+            None => AttemptError::Transient(err.into()),
+        }
+    }
+}
+
+impl From<alloy::providers::PendingTransactionError> for AttemptError {
+    fn from(err: alloy::providers::PendingTransactionError) -> Self {
+        AttemptError::Transient(err.into())
+    }
+}
+
+/// Submits the transaction of `operation` with `send` until it is mined successfully or the `retry_policy` is exhausted. Before every retry, returns [`AdminOutcome::AlreadyDone`] if the registry emitted `E` for `oprf_key_id` since the first attempt.
+async fn submit<E, F, Fut>(
+    operation: &str,
+    contract: &OprfKeyRegistryInstance<DynProvider>,
+    oprf_key_id: OprfKeyId,
+    retry_policy: &RetryPolicy,
+    send: F,
+) -> AdminOutcome
+where
+    E: SolEvent,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<alloy::rpc::types::TransactionReceipt, AttemptError>>,
+{
+    let from_block = match contract.provider().get_block_number().await {
+        Ok(block) => block,
+        Err(err) => {
+            return AdminOutcome::Failed {
+                attempts: 0,
+                error: eyre::Report::from(err).wrap_err("while fetching the current block"),
+            };
+        }
+    };
+    let mut delays = retry_policy.backoff().build();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let error = match send().await {
+            Ok(receipt) if receipt.status() => {
+                return AdminOutcome::Submitted {
+                    tx_hash: receipt.transaction_hash,
+                    attempts,
+                };
+            }
+            // a reverted transaction was mined, retrying only helps if the revert was transient
+            Ok(receipt) => eyre::eyre!("transaction {} reverted", receipt.transaction_hash),
+            // an earlier attempt might have been mined in the meantime and caused the rejection
+            Err(AttemptError::Rejected(error)) if attempts > 1 => {
+                return match emitted_since::<E>(contract, oprf_key_id, from_block).await {
+                    Ok(true) => already_done(operation, oprf_key_id),
+                    Ok(false) => AdminOutcome::Failed { attempts, error },
+                    Err(err) => {
+                        tracing::warn!(%err, "cannot check whether {operation} of {oprf_key_id} is done");
+                        AdminOutcome::Failed { attempts, error }
+                    }
+                };
+            }
+            Err(AttemptError::Rejected(error)) => return AdminOutcome::Failed { attempts, error },
+            Err(AttemptError::Transient(error)) => error,
+        };
+        let Some(delay) = delays.next() else {
+            return AdminOutcome::Failed { attempts, error };
+        };
+        tracing::warn!(%error, "{operation} of {oprf_key_id} failed - retrying in {delay:?}");
+        tokio::time::sleep(delay).await;
+        match emitted_since::<E>(contract, oprf_key_id, from_block).await {
+            Ok(true) => return already_done(operation, oprf_key_id),
+            Ok(false) => {}
+            Err(error) => return AdminOutcome::Failed { attempts, error },
+        }
     }
-    Ok(())
+}
+
+fn already_done(operation: &str, oprf_key_id: OprfKeyId) -> AdminOutcome {
+    tracing::info!("{operation} of {oprf_key_id} is already done");
+    AdminOutcome::AlreadyDone
+}
+
+/// Whether the registry emitted `E` for `oprf_key_id` since `from_block`.
+async fn emitted_since<E: SolEvent>(
+    contract: &OprfKeyRegistryInstance<DynProvider>,
+    oprf_key_id: OprfKeyId,
+    from_block: u64,
+) -> eyre::Result<bool> {
+    let filter = Filter::new()
+        .address(*contract.address())
+        .event_signature(E::SIGNATURE_HASH)
+        .topic1(B256::from(U256::from(oprf_key_id.into_inner())))
+        .from_block(from_block);
+    let logs = contract
+        .provider()
+        .get_logs(&filter)
+        .await
+        .context("while checking the registry for the event of the operation")?;
+    Ok(!logs.is_empty())
 }
@@ -0,0 +1,313 @@
+//! Gas and calldata estimates for the key-gen and reshare rounds.
+//!
+//! The contributions of the rounds grow with the committee (round 2 carries one ciphertext per peer), so operators budgeting for large committees can build representative calldata for a given threshold and peer count with [`representative_calldata`] and estimate its cost with [`gas_report`].
+
+use alloy::{
+    primitives::{Address, Bytes, U160, U256},
+    providers::{DynProvider, Provider as _},
+    rpc::types::TransactionRequest,
+    sol_types::SolCall as _,
+};
+use oprf_types::chain::{BabyJubJub, OprfKeyGen, OprfKeyRegistry};
+use rand::Rng;
+use serde::Serialize;
+
+use crate::GasReportCommand;
+
+/// Gas every transaction pays before executing any code.
+const TX_BASE_GAS: u64 = 21_000;
+/// Gas per zero byte of calldata.
+const ZERO_BYTE_GAS: u64 = 4;
+/// Gas per non-zero byte of calldata.
+const NON_ZERO_BYTE_GAS: u64 = 16;
+
+/// Representative calldata of the contributions a single peer submits per round.
+#[derive(Debug, Clone)]
+pub struct RoundCalldata {
+    /// `addRound1KeyGenContribution` of a key-gen.
+    pub round1_key_gen: Bytes,
+    /// `addRound1ReshareContribution` of a reshare producer.
+    pub round1_reshare_producer: Bytes,
+    /// `addRound1ReshareContribution` of a reshare consumer (ephemeral public key only).
+    pub round1_reshare_consumer: Bytes,
+    /// `addRound2Contribution` with one ciphertext per peer.
+    pub round2: Bytes,
+    /// `addRound3Contribution`.
+    pub round3: Bytes,
+}
+
+/// Cost of a single contribution.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundCost {
+    /// Size of the calldata in bytes.
+    pub calldata_size: usize,
+    /// Base and calldata gas of the transaction, see [`intrinsic_gas`].
+    pub intrinsic_gas: u64,
+    /// Gas estimated by the provider, `None` if the contract rejected the call.
+    pub estimated_gas: Option<u64>,
+    /// Why the provider could not estimate the call.
+    pub estimate_error: Option<String>,
+}
+
+impl RoundCost {
+    /// The estimated gas, or the intrinsic gas if the provider could not estimate the call.
+    pub fn gas(&self) -> u64 {
+        self.estimated_gas.unwrap_or(self.intrinsic_gas)
+    }
+}
+
+/// Cost of a key-gen and a reshare for a committee, summed over all submitting peers.
+#[derive(Debug, Clone, Serialize)]
+pub struct GasReport {
+    pub threshold: u16,
+    pub num_peers: u16,
+    pub round1_key_gen: RoundCost,
+    pub round1_reshare_producer: RoundCost,
+    pub round1_reshare_consumer: RoundCost,
+    pub round2: RoundCost,
+    pub round3: RoundCost,
+    /// Total gas of a key-gen: every peer submits rounds 1, 2 and 3.
+    pub key_gen_gas: u64,
+    /// Total gas of a reshare: every peer submits rounds 1 and 3, `threshold` producers submit round 2.
+    pub reshare_gas: u64,
+    /// Total calldata of a key-gen in bytes.
+    pub key_gen_calldata_size: usize,
+    /// Total calldata of a reshare in bytes.
+    pub reshare_calldata_size: usize,
+}
+
+fn random_field_element(rng: &mut impl Rng) -> U256 {
+    rng.r#gen::<ark_babyjubjub::Fq>().into()
+}
+
+fn random_point(rng: &mut impl Rng) -> BabyJubJub::Affine {
+    BabyJubJub::Affine::from(rng.r#gen::<ark_babyjubjub::EdwardsAffine>())
+}
+
+/// Builds representative calldata of every round for a committee of `num_peers` peers.
+///
+/// The contributions are filled with random curve points and field elements, so their size and byte distribution match real contributions.
+pub fn representative_calldata(num_peers: u16) -> RoundCalldata {
+    let mut rng = rand::thread_rng();
+    let oprf_key_id = U160::from(rng.r#gen::<u64>());
+    let producer = OprfKeyGen::Round1Contribution {
+        commShare: random_point(&mut rng),
+        commCoeffs: random_field_element(&mut rng),
+        ephPubKey: random_point(&mut rng),
+    };
+    let consumer = OprfKeyGen::Round1Contribution {
+        commShare: BabyJubJub::Affine {
+            x: U256::ZERO,
+            y: U256::ZERO,
+        },
+        commCoeffs: U256::ZERO,
+        ephPubKey: random_point(&mut rng),
+    };
+    let round2 = OprfKeyGen::Round2Contribution {
+        compressedProof: std::array::from_fn(|_| random_field_element(&mut rng)),
+        ciphers: (0..num_peers)
+            .map(|_| OprfKeyGen::SecretGenCiphertext {
+                nonce: random_field_element(&mut rng),
+                cipher: random_field_element(&mut rng),
+                commitment: random_point(&mut rng),
+            })
+            .collect(),
+    };
+    RoundCalldata {
+        round1_key_gen: OprfKeyRegistry::addRound1KeyGenContributionCall {
+            oprfKeyId: oprf_key_id,
+            data: producer.clone(),
+        }
+        .abi_encode()
+        .into(),
+        round1_reshare_producer: OprfKeyRegistry::addRound1ReshareContributionCall {
+            oprfKeyId: oprf_key_id,
+            data: producer,
+        }
+        .abi_encode()
+        .into(),
+        round1_reshare_consumer: OprfKeyRegistry::addRound1ReshareContributionCall {
+            oprfKeyId: oprf_key_id,
+            data: consumer,
+        }
+        .abi_encode()
+        .into(),
+        round2: OprfKeyRegistry::addRound2ContributionCall {
+            oprfKeyId: oprf_key_id,
+            data: round2,
+        }
+        .abi_encode()
+        .into(),
+        round3: OprfKeyRegistry::addRound3ContributionCall {
+            oprfKeyId: oprf_key_id,
+        }
+        .abi_encode()
+        .into(),
+    }
+}
+
+/// The gas a transaction with `calldata` pays before executing any code.
+pub fn intrinsic_gas(calldata: &[u8]) -> u64 {
+    calldata.iter().fold(TX_BASE_GAS, |gas, byte| {
+        gas + if *byte == 0 {
+            ZERO_BYTE_GAS
+        } else {
+            NON_ZERO_BYTE_GAS
+        }
+    })
+}
+
+/// Estimates the gas of calling the `OprfKeyRegistry` with `calldata` from `from`.
+///
+/// The contract only accepts contributions of registered peers in the matching round, so the estimate fails for representative calldata unless the chain state allows the call (e.g., on a fork at the corresponding round).
+pub async fn estimate_gas(
+    provider: &DynProvider,
+    oprf_key_registry: Address,
+    from: Address,
+    calldata: Bytes,
+) -> eyre::Result<u64> {
+    let tx = TransactionRequest::default()
+        .from(from)
+        .to(oprf_key_registry)
+        .input(calldata.into());
+    Ok(provider.estimate_gas(tx).await?)
+}
+
+async fn round_cost(
+    provider: &DynProvider,
+    oprf_key_registry: Address,
+    from: Address,
+    calldata: Bytes,
+) -> RoundCost {
+    let calldata_size = calldata.len();
+    let intrinsic_gas = intrinsic_gas(&calldata);
+    let (estimated_gas, estimate_error) =
+        match estimate_gas(provider, oprf_key_registry, from, calldata).await {
+            Ok(gas) => (Some(gas), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+    RoundCost {
+        calldata_size,
+        intrinsic_gas,
+        estimated_gas,
+        estimate_error,
+    }
+}
+
+/// Estimates the cost of a key-gen and a reshare for a committee of `num_peers` peers with threshold `threshold`.
+///
+/// Falls back to the intrinsic gas for every round the provider cannot estimate, see [`estimate_gas`].
+pub async fn gas_report(
+    provider: &DynProvider,
+    oprf_key_registry: Address,
+    from: Address,
+    threshold: u16,
+    num_peers: u16,
+) -> GasReport {
+    let calldata = representative_calldata(num_peers);
+    let round1_key_gen =
+        round_cost(provider, oprf_key_registry, from, calldata.round1_key_gen).await;
+    let round1_reshare_producer = round_cost(
+        provider,
+        oprf_key_registry,
+        from,
+        calldata.round1_reshare_producer,
+    )
+    .await;
+    let round1_reshare_consumer = round_cost(
+        provider,
+        oprf_key_registry,
+        from,
+        calldata.round1_reshare_consumer,
+    )
+    .await;
+    let round2 = round_cost(provider, oprf_key_registry, from, calldata.round2).await;
+    let round3 = round_cost(provider, oprf_key_registry, from, calldata.round3).await;
+
+    let peers = u64::from(num_peers);
+    let producers = u64::from(threshold);
+    let consumers = peers.saturating_sub(producers);
+    let key_gen_gas = peers * (round1_key_gen.gas() + round2.gas() + round3.gas());
+    let reshare_gas = producers * round1_reshare_producer.gas()
+        + consumers * round1_reshare_consumer.gas()
+        + producers * round2.gas()
+        + peers * round3.gas();
+    let size = |cost: &RoundCost, count: u64| cost.calldata_size * count as usize;
+    let key_gen_calldata_size =
+        size(&round1_key_gen, peers) + size(&round2, peers) + size(&round3, peers);
+    let reshare_calldata_size = size(&round1_reshare_producer, producers)
+        + size(&round1_reshare_consumer, consumers)
+        + size(&round2, producers)
+        + size(&round3, peers);
+    GasReport {
+        threshold,
+        num_peers,
+        round1_key_gen,
+        round1_reshare_producer,
+        round1_reshare_consumer,
+        round2,
+        round3,
+        key_gen_gas,
+        reshare_gas,
+        key_gen_calldata_size,
+        reshare_calldata_size,
+    }
+}
+
+/// Runs [`gas_report`] for the committee of `cmd` and logs the summary.
+pub(crate) async fn report(
+    provider: DynProvider,
+    oprf_key_registry: Address,
+    from: Address,
+    cmd: GasReportCommand,
+) -> eyre::Result<GasReport> {
+    eyre::ensure!(
+        cmd.threshold > 0 && cmd.threshold <= cmd.num_peers,
+        "threshold must be between 1 and the number of peers"
+    );
+    let report = gas_report(
+        &provider,
+        oprf_key_registry,
+        cmd.from.unwrap_or(from),
+        cmd.threshold,
+        cmd.num_peers,
+    )
+    .await;
+    for (name, cost) in [
+        ("round1 key-gen", &report.round1_key_gen),
+        ("round1 reshare producer", &report.round1_reshare_producer),
+        ("round1 reshare consumer", &report.round1_reshare_consumer),
+        ("round2", &report.round2),
+        ("round3", &report.round3),
+    ] {
+        match &cost.estimate_error {
+            None => tracing::info!(
+                "{name}: {} bytes calldata, {} gas",
+                cost.calldata_size,
+                cost.gas()
+            ),
+            Some(err) => tracing::warn!(
+                "{name}: {} bytes calldata, {} intrinsic gas (cannot estimate: {err})",
+                cost.calldata_size,
+                cost.intrinsic_gas
+            ),
+        }
+    }
+    tracing::info!(
+        "key-gen with {} peers: {} gas, {} bytes calldata",
+        report.num_peers,
+        report.key_gen_gas,
+        report.key_gen_calldata_size
+    );
+    tracing::info!(
+        "reshare with {} peers and threshold {}: {} gas, {} bytes calldata",
+        report.num_peers,
+        report.threshold,
+        report.reshare_gas,
+        report.reshare_calldata_size
+    );
+    if let Some(output) = &cmd.output {
+        std::fs::write(output, serde_json::to_vec_pretty(&report)?)?;
+    }
+    Ok(report)
+}
@@ -1,6 +1,7 @@
 use std::time::Duration;
 
-use oprf_types::{OprfKeyId, ShareEpoch, api::OprfPublicKeyWithEpoch, crypto::OprfPublicKey};
+use oprf_client::RetryPolicy;
+use oprf_types::{OprfKeyId, ShareEpoch, crypto::OprfPublicKey};
 use reqwest::StatusCode;
 use tokio::task::JoinSet;
 
@@ -47,46 +48,43 @@ pub async fn services_health_check(
     Ok(())
 }
 
-pub async fn load_oprf_public_key(oprf_key_url: String, epoch: ShareEpoch) -> OprfPublicKey {
-    loop {
-        if let Ok(response) = reqwest::get(&oprf_key_url)
-            .await
-            .and_then(|response| response.error_for_status())
-            && let Ok(material) = response.json::<OprfPublicKeyWithEpoch>().await
-            && material.epoch == epoch
-        {
-            return material.key;
-        }
-        tokio::time::sleep(Duration::from_secs(1)).await;
-    }
-}
-
 pub async fn oprf_public_key_from_services(
     oprf_key_id: OprfKeyId,
     epoch: ShareEpoch,
     services: &[String],
     max_wait_time: Duration,
 ) -> eyre::Result<OprfPublicKey> {
-    let oprf_public_key_checks = services
+    let urls = oprf_client::to_oprf_pub_key_url_many(services)?;
+    let client = reqwest::Client::new();
+    let retry_policy = RetryPolicy::default();
+    let readiness = oprf_client::await_epoch(
+        &urls,
+        oprf_key_id,
+        epoch,
+        max_wait_time,
+        &client,
+        &retry_policy,
+    )
+    .await;
+    let not_ready = readiness
         .iter()
-        .map(|service| load_oprf_public_key(format!("{service}/oprf_pub/{oprf_key_id}"), epoch))
-        .collect::<JoinSet<_>>();
-    match tokio::time::timeout(max_wait_time, oprf_public_key_checks.join_all())
-        .await
-        .map_err(|_| {
-            eyre::eyre!("could not load OPRF material in provided time: {max_wait_time:?}")
-        }) {
-        Ok(mut keys) => {
-            let key = keys.pop().expect("at least one here");
-            if keys.into_iter().all(|other| key == other) {
-                Ok(key)
-            } else {
-                eyre::bail!("keys did not match for all services");
-            }
-        }
-        Err(_) => eyre::bail!("couldn't load OPRF material within time"),
+        .filter(|(_, readiness)| !readiness.is_ready())
+        .map(|(url, readiness)| format!("{url}: {readiness:?}"))
+        .collect::<Vec<_>>();
+    if !not_ready.is_empty() {
+        eyre::bail!(
+            "could not load OPRF material for epoch {epoch} in provided time {max_wait_time:?}: {}",
+            not_ready.join(", ")
+        );
     }
+    let material =
+        oprf_client::fetch_oprf_public_key(&urls, urls.len(), oprf_key_id, &client, &retry_policy)
+            .await
+            .map_err(|err| eyre::eyre!("keys did not match for all services: {err}"))?
+            .ok_or_else(|| eyre::eyre!("services do not know {oprf_key_id}"))?;
+    Ok(material.key)
 }
+
 pub async fn oprf_public_key_not_known_check(health_url: String) {
     loop {
         if let Err(err) = reqwest::get(&health_url)
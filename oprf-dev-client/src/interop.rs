@@ -0,0 +1,357 @@
+//! OPRF runs with selectable wire formats and spoofed client protocol versions.
+//!
+//! The OPRF client always speaks `cbor` and announces its own protocol version. Interop issues often only show up for `json` or for specific versions, so the stress test can drive the sessions itself instead (see [`StressTestOprfCommand::wire_format`] and [`StressTestOprfCommand::client_versions`]):
+//!
+//! - Every request is sent in the wire format selected for it (`json` in `Text`, `cbor` in `Binary` frames) and announces the client version assigned to it (round-robin).
+//! - Every request is sent to all nodes. A node is expected to accept the request iff the version matches the `version_req` it serves at `/info`, and to reject the upgrade otherwise. Any deviation is reported per node and fails the run.
+//! - Requests accepted by at least `threshold` nodes are finished with the first `threshold` of them (ordered by party id), and the combined proof is verified unless checks are skipped.
+
+use std::{collections::BTreeMap, time::Instant};
+
+use eyre::Context as _;
+use futures::{SinkExt as _, StreamExt as _};
+use oprf_client::Connector;
+use oprf_core::ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir};
+use oprf_types::api::{NodeInfo, OprfResponse, SchemaFingerprint};
+use rand::SeedableRng as _;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpStream, task::JoinSet};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream,
+    tungstenite::{self, Message},
+};
+use uuid::Uuid;
+
+use crate::{DevClient, DevClientConfig, StressTestItem, StressTestOprfCommand, WireFormats};
+
+type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// The wire format of a single request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WireFormat {
+    Json,
+    Cbor,
+}
+
+impl WireFormats {
+    /// The wire format of the `n`-th request.
+    pub fn for_request(self, n: usize) -> WireFormat {
+        match self {
+            WireFormats::Json => WireFormat::Json,
+            WireFormats::Cbor => WireFormat::Cbor,
+            WireFormats::Alternating if n.is_multiple_of(2) => WireFormat::Json,
+            WireFormats::Alternating => WireFormat::Cbor,
+        }
+    }
+}
+
+impl WireFormat {
+    fn encode<Msg: Serialize>(self, msg: &Msg) -> eyre::Result<Message> {
+        Ok(match self {
+            WireFormat::Json => Message::text(serde_json::to_string(msg)?),
+            WireFormat::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(msg, &mut buf)?;
+                Message::binary(buf)
+            }
+        })
+    }
+
+    fn decode<Msg: for<'de> Deserialize<'de>>(self, msg: Message) -> eyre::Result<Msg> {
+        match (self, msg) {
+            (WireFormat::Json, Message::Text(text)) => Ok(serde_json::from_str(&text)?),
+            (WireFormat::Cbor, Message::Binary(bytes)) => {
+                Ok(ciborium::from_reader(bytes.as_ref())?)
+            }
+            (_, Message::Close(Some(frame))) => {
+                eyre::bail!("node closed session with {}: {}", frame.code, frame.reason)
+            }
+            (format, msg) => eyre::bail!("unexpected {msg:?} for {format:?} session"),
+        }
+    }
+}
+
+/// Whether a node accepted a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Outcome {
+    Accepted,
+    Rejected,
+}
+
+/// An accepted session waiting for the challenge.
+struct OpenSession {
+    ws: WebSocket,
+    response: OprfResponse,
+}
+
+/// The outcome of a request at a single node.
+struct NodeOutcome {
+    node: String,
+    format: WireFormat,
+    version: Version,
+    expected: Outcome,
+    actual: eyre::Result<Option<OpenSession>>,
+}
+
+impl NodeOutcome {
+    fn outcome(&self) -> Option<Outcome> {
+        match &self.actual {
+            Ok(Some(_)) => Some(Outcome::Accepted),
+            Ok(None) => Some(Outcome::Rejected),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Fetches the client versions every node accepts from its `/info`.
+async fn version_reqs(nodes: &[String]) -> eyre::Result<Vec<VersionReq>> {
+    let client = reqwest::Client::new();
+    let mut version_reqs = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let info = client
+            .get(format!("{}/info", node.trim_end_matches('/')))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("while fetching /info of {node}"))?
+            .json::<NodeInfo>()
+            .await
+            .with_context(|| format!("while parsing /info of {node}"))?;
+        version_reqs.push(
+            VersionReq::parse(&info.version_req)
+                .with_context(|| format!("invalid version_req of {node}"))?,
+        );
+    }
+    Ok(version_reqs)
+}
+
+/// Opens a session at `node` and sends the encoded request `init`. Returns `None` if the node rejected the upgrade.
+async fn open_session(
+    node: &str,
+    module: &str,
+    format: WireFormat,
+    version: &Version,
+    request_id: Uuid,
+    init: Message,
+    connector: Connector,
+) -> eyre::Result<Option<OpenSession>> {
+    let endpoint = format!(
+        "{}?version={version}&schema={}&request_id={request_id}",
+        oprf_client::to_oprf_uri(node, module)?,
+        SchemaFingerprint::CURRENT
+    );
+    let mut ws = match tokio_tungstenite::connect_async_tls_with_config(
+        endpoint,
+        None,
+        false,
+        Some(connector),
+    )
+    .await
+    {
+        Ok((ws, _)) => ws,
+        Err(tungstenite::Error::Http(response))
+            if response.status() == http::StatusCode::BAD_REQUEST =>
+        {
+            tracing::debug!(
+                "{node} rejected version {version}: {}",
+                String::from_utf8_lossy(response.body().as_deref().unwrap_or_default())
+            );
+            return Ok(None);
+        }
+        Err(err) => return Err(err).context("while connecting"),
+    };
+    ws.send(init).await?;
+    let response = read(&mut ws, format).await?;
+    Ok(Some(OpenSession { ws, response }))
+}
+
+async fn read<Msg: for<'de> Deserialize<'de>>(
+    ws: &mut WebSocket,
+    format: WireFormat,
+) -> eyre::Result<Msg> {
+    let msg = ws
+        .next()
+        .await
+        .ok_or_else(|| eyre::eyre!("node closed connection"))??;
+    format.decode(msg)
+}
+
+/// Sends the request to all nodes and finishes it with the first `threshold` nodes that accepted it.
+async fn run_request<Auth: Serialize>(
+    config: &DevClientConfig,
+    version_reqs: &[VersionReq],
+    item: StressTestItem<Auth>,
+    format: WireFormat,
+    version: Version,
+    connector: Connector,
+) -> eyre::Result<(
+    Vec<NodeOutcome>,
+    Option<(Vec<DLogProofShareShamir>, DLogCommitmentsShamir)>,
+)> {
+    let init = format.encode(&item.init_request)?;
+    let mut outcomes = Vec::with_capacity(config.nodes.len());
+    for (node, version_req) in config.nodes.iter().zip(version_reqs) {
+        let actual = open_session(
+            node,
+            &item.auth_module,
+            format,
+            &version,
+            item.request_id,
+            init.clone(),
+            connector.clone(),
+        )
+        .await;
+        outcomes.push(NodeOutcome {
+            node: node.clone(),
+            format,
+            version: version.clone(),
+            expected: if version_req.matches(&version) {
+                Outcome::Accepted
+            } else {
+                Outcome::Rejected
+            },
+            actual,
+        });
+    }
+
+    let mut sessions = outcomes
+        .iter_mut()
+        .filter_map(|outcome| outcome.actual.as_mut().ok()?.as_mut())
+        .collect::<Vec<_>>();
+    if sessions.len() < config.threshold {
+        return Ok((outcomes, None));
+    }
+    sessions.sort_by_key(|session| session.response.party_id.into_inner());
+    sessions.truncate(config.threshold);
+    let challenge = DLogCommitmentsShamir::combine_commitments(
+        &sessions
+            .iter()
+            .map(|session| session.response.commitments.clone())
+            .collect::<Vec<_>>(),
+        sessions
+            .iter()
+            .map(|session| session.response.party_id.into_inner() + 1)
+            .collect(),
+    );
+    let mut proof_shares = Vec::with_capacity(sessions.len());
+    for session in sessions {
+        session.ws.send(format.encode(&challenge)?).await?;
+        proof_shares.push(read(&mut session.ws, format).await?);
+    }
+    Ok((outcomes, Some((proof_shares, challenge))))
+}
+
+/// Runs the stress test with the wire formats and client versions of `cmd`, see the [module docs](self).
+pub async fn stress_test<T: DevClient>(
+    dev_client: T,
+    config: DevClientConfig,
+    cmd: StressTestOprfCommand,
+    setup: T::Setup,
+    connector: Connector,
+) -> eyre::Result<()> {
+    let mut rng = rand_chacha::ChaCha12Rng::from_rng(rand::thread_rng())?;
+    let version_reqs = version_reqs(&config.nodes).await?;
+    let client_versions = if cmd.client_versions.is_empty() {
+        vec![Version::parse(oprf_client::VERSION)?]
+    } else {
+        cmd.client_versions.clone()
+    };
+    let oprf_public_key = dev_client.get_oprf_key(&setup);
+
+    let start = Instant::now();
+    let mut runs = JoinSet::new();
+    for n in 0..cmd.runs {
+        let item = dev_client
+            .prepare_stress_test_item(&setup, &mut rng)
+            .await?;
+        let format = cmd.wire_format.for_request(n);
+        let version = client_versions[n % client_versions.len()].clone();
+        let config = config.clone();
+        let version_reqs = version_reqs.clone();
+        let connector = connector.clone();
+        runs.spawn(async move {
+            let request_id = item.request_id;
+            let blinded_query = item.blinded_query.clone();
+            let result =
+                run_request(&config, &version_reqs, item, format, version, connector).await;
+            (request_id, blinded_query, format, result)
+        });
+        if cmd.sequential {
+            runs.join_next().await;
+        }
+    }
+    let results = runs.join_all().await;
+    tracing::info!("sent {} requests in {:?}", cmd.runs, start.elapsed());
+
+    let mut summary = BTreeMap::<_, usize>::new();
+    let mut failures = 0;
+    for (request_id, blinded_query, format, result) in results {
+        let (outcomes, finished) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                tracing::error!(?err, "{format:?} request {request_id} failed");
+                failures += 1;
+                continue;
+            }
+        };
+        for outcome in &outcomes {
+            let actual = outcome.outcome();
+            *summary
+                .entry((
+                    outcome.node.clone(),
+                    outcome.format,
+                    outcome.version.to_string(),
+                    actual,
+                ))
+                .or_default() += 1;
+            match (&outcome.actual, actual) {
+                (Err(err), _) => {
+                    tracing::error!(?err, "{} failed {request_id}", outcome.node);
+                    failures += 1;
+                }
+                (_, Some(actual)) if actual != outcome.expected => {
+                    tracing::error!(
+                        "{} {actual:?} {:?} request {request_id} with version {}, expected {:?}",
+                        outcome.node,
+                        outcome.format,
+                        outcome.version,
+                        outcome.expected
+                    );
+                    failures += 1;
+                }
+                _ => {}
+            }
+        }
+        if let Some((proof_shares, challenge)) = finished
+            && !cmd.skip_checks
+            && let Err(err) = oprf_client::verify_dlog_equality(
+                request_id,
+                oprf_public_key,
+                &blinded_query,
+                &proof_shares,
+                challenge,
+                // the OPRF modules of the dev nodes have no context
+                ark_babyjubjub::Fq::default(),
+            )
+        {
+            tracing::error!(?err, "{format:?} request {request_id} has invalid proof");
+            failures += 1;
+        }
+    }
+
+    for ((node, format, version, outcome), count) in summary {
+        match outcome {
+            Some(outcome) => tracing::info!(
+                "{node} {outcome:?} {count} {format:?} requests with version {version}"
+            ),
+            None => {
+                tracing::info!("{node} failed {count} {format:?} requests with version {version}")
+            }
+        }
+    }
+    if failures > 0 {
+        eyre::bail!("{failures} unexpected outcomes - see logs");
+    }
+    Ok(())
+}
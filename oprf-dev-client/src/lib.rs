@@ -15,7 +15,7 @@ use alloy::{
     signers::local::PrivateKeySigner,
 };
 use eyre::Context;
-use oprf_client::{Connector, OprfSessions};
+use oprf_client::{Connector, OprfSessions, RetryPolicy};
 use oprf_core::{
     ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir},
     oprf::BlindedOprfRequest,
@@ -28,10 +28,15 @@ use serde::Serialize;
 use tokio::{sync::mpsc, task::JoinSet};
 use uuid::Uuid;
 
+pub mod bundle;
 pub(crate) mod config;
 pub use config::*;
-mod contract;
+pub mod contract;
+pub mod gas;
 pub mod health_checks;
+pub mod interop;
+pub mod soak;
+pub mod validate_events;
 
 #[async_trait::async_trait]
 pub trait DevClient: Send + Sync + 'static {
@@ -105,8 +110,14 @@ pub async fn delete_test(config: DevClientConfig, provider: DynProvider) -> eyre
     )
     .await?;
     tracing::info!("created the key - now delete it..");
-    contract::delete_oprf_key_material(provider, config.oprf_key_registry_contract, oprf_key_id)
-        .await?;
+    contract::delete_oprf_key_material(
+        provider,
+        config.oprf_key_registry_contract,
+        oprf_key_id,
+        &RetryPolicy::default(),
+    )
+    .await
+    .into_result()?;
     tracing::info!("sent delete event - ping nodes to check this works");
     health_checks::assert_key_id_unknown(oprf_key_id, &config.nodes, config.max_wait_time).await?;
     tracing::info!("successfully deleted key-material");
@@ -237,7 +248,14 @@ pub async fn init_key_gen(
     let oprf_key_id_u32: u32 = rand::random();
     let oprf_key_id = OprfKeyId::new(U160::from(oprf_key_id_u32));
     tracing::info!("init OPRF key gen with: {oprf_key_id}");
-    contract::init_key_gen(provider, oprf_key_registry, oprf_key_id).await?;
+    contract::init_key_gen(
+        provider,
+        oprf_key_registry,
+        oprf_key_id,
+        &RetryPolicy::default(),
+    )
+    .await
+    .into_result()?;
     tracing::info!("waiting for key-gen to finish..");
     let oprf_public_key = health_checks::oprf_public_key_from_services(
         oprf_key_id,
@@ -263,7 +281,14 @@ async fn stress_test_key_gen(
         let oprf_key_id_u32: u32 = rand::random();
         let oprf_key_id = OprfKeyId::new(U160::from(oprf_key_id_u32));
         tracing::debug!("init OPRF key gen with: {oprf_key_id}");
-        contract::init_key_gen(provider.clone(), oprf_key_registry, oprf_key_id).await?;
+        contract::init_key_gen(
+            provider.clone(),
+            oprf_key_registry,
+            oprf_key_id,
+            &RetryPolicy::default(),
+        )
+        .await
+        .into_result()?;
         key_gens.spawn({
             let nodes = nodes.to_vec();
             async move {
@@ -285,7 +310,14 @@ async fn stress_test_key_gen(
             .expect("Can join")
             .context("Could not fetch oprf-key-gen")?;
         tracing::debug!("init OPRF reshare for {key_id}");
-        contract::init_reshare(provider.clone(), oprf_key_registry, key_id).await?;
+        contract::init_reshare(
+            provider.clone(),
+            oprf_key_registry,
+            key_id,
+            &RetryPolicy::default(),
+        )
+        .await
+        .into_result()?;
         // do an oprf to check if correct
         reshares.spawn({
             let nodes = nodes.to_vec();
@@ -335,10 +367,14 @@ async fn stress_test<T: DevClient>(
     connector: Connector,
 ) -> eyre::Result<()> {
     let mut rng = rand_chacha::ChaCha12Rng::from_rng(rand::thread_rng())?;
+    if cmd.wire_format != WireFormats::Cbor || !cmd.client_versions.is_empty() {
+        return interop::stress_test(dev_client, config, cmd, setup, connector).await;
+    }
     let StressTestOprfCommand {
         runs,
         sequential,
         skip_checks,
+        ..
     } = cmd;
 
     let mut blinded_requests = HashMap::with_capacity(cmd.runs);
@@ -379,6 +415,8 @@ async fn stress_test<T: DevClient>(
                 &blinded_req,
                 &res,
                 finish_req,
+                // the OPRF modules of the dev nodes have no context
+                ark_babyjubjub::Fq::default(),
             )?;
         }
     }
@@ -434,8 +472,10 @@ async fn reshare_test<T: DevClient>(
         provider.clone(),
         config.oprf_key_registry_contract,
         oprf_key_id,
+        &RetryPolicy::default(),
     )
-    .await?;
+    .await
+    .into_result()?;
     tokio::time::timeout(
         config.max_wait_time,
         wait_for_epoch(&mut rx, acceptance_num, current_epoch.next()),
@@ -447,8 +487,10 @@ async fn reshare_test<T: DevClient>(
         provider.clone(),
         config.oprf_key_registry_contract,
         oprf_key_id,
+        &RetryPolicy::default(),
     )
-    .await?;
+    .await
+    .into_result()?;
     tokio::time::timeout(
         config.max_wait_time,
         wait_for_epoch(&mut rx, acceptance_num, current_epoch.next().next()),
@@ -493,14 +535,32 @@ async fn wait_for_epoch(
 }
 
 pub async fn run<T: DevClient>(config: DevClientConfig, dev_client: T) -> eyre::Result<()> {
-    tracing::info!("health check for all nodes...");
-    health_checks::services_health_check(&config.nodes, Duration::from_secs(5))
-        .await
-        .context("while doing health checks")?;
-
-    tracing::info!("everyone online..");
-
+    if let Some(transcript_dir) = &config.transcript_dir {
+        std::fs::create_dir_all(transcript_dir).context("while creating transcript dir")?;
+        oprf_client::transcript::capture_to(transcript_dir)
+            .map_err(|_| eyre::eyre!("transcripts are captured already"))?;
+    }
     let private_key = PrivateKeySigner::from_str(config.taceo_private_key.expose_secret())?;
+    let config = match config.committee_bundle.clone() {
+        Some(path) => {
+            let admin = config.bundle_admin.unwrap_or(private_key.address());
+            bundle::apply_bundle(config, &path, admin).await?
+        }
+        None => config,
+    };
+    // validating events, exporting the bundle and estimating gas only talk to the chain, so we don't need the nodes for that
+    if !matches!(
+        config.command,
+        Command::ValidateEvents(_) | Command::ExportBundle(_) | Command::GasReport(_)
+    ) {
+        tracing::info!("health check for all nodes...");
+        health_checks::services_health_check(&config.nodes, Duration::from_secs(5))
+            .await
+            .context("while doing health checks")?;
+
+        tracing::info!("everyone online..");
+    }
+
     let wallet = EthereumWallet::from(private_key.clone());
 
     tracing::info!("init rpc provider..");
@@ -543,6 +603,14 @@ pub async fn run<T: DevClient>(config: DevClientConfig, dev_client: T) -> eyre::
             stress_test(dev_client, config, cmd, setup, connector).await?;
             tracing::info!("stress-test successful");
         }
+        Command::SoakTest(cmd) => {
+            tracing::info!("running soak-test for {:?}", cmd.duration);
+            let setup = dev_client
+                .setup_oprf_test(&config, provider.clone())
+                .await?;
+            soak::soak_test(dev_client, config, cmd, setup, connector).await?;
+            tracing::info!("soak-test successful");
+        }
         Command::StressTestKeyGen(cmd) => {
             tracing::info!("running key-gen stress-test");
             stress_test_key_gen(
@@ -571,6 +639,60 @@ pub async fn run<T: DevClient>(config: DevClientConfig, dev_client: T) -> eyre::
             .await?;
             tracing::info!("reshare-test successful");
         }
+        Command::ValidateEvents(cmd) => {
+            tracing::info!("validating events of {}", config.oprf_key_registry_contract);
+            let report =
+                validate_events::validate_events(provider, config.oprf_key_registry_contract, cmd)
+                    .await?;
+            for (name, count) in &report.decoded {
+                tracing::info!("decoded {count} {name} events");
+            }
+            for event in &report.undecodable {
+                tracing::error!(?event, "cannot decode event");
+            }
+            for event in &report.unknown {
+                tracing::error!(?event, "unknown event");
+            }
+            if !report.is_valid() {
+                eyre::bail!(
+                    "found {} undecodable and {} unknown events",
+                    report.undecodable.len(),
+                    report.unknown.len()
+                );
+            }
+            tracing::info!("all events decoded successfully");
+        }
+        Command::ExportBundle(cmd) => {
+            tracing::info!("exporting committee bundle");
+            let signed = bundle::export_bundle(
+                provider,
+                config.oprf_key_registry_contract,
+                &config.nodes,
+                &private_key,
+            )
+            .await?;
+            std::fs::write(&cmd.output, serde_json::to_vec_pretty(&signed)?)
+                .with_context(|| format!("while writing {}", cmd.output.display()))?;
+            tracing::info!(
+                "wrote bundle of {} nodes to {}",
+                signed.bundle.members.len(),
+                cmd.output.display()
+            );
+        }
+        Command::GasReport(cmd) => {
+            tracing::info!(
+                "estimating gas for {} peers with threshold {}",
+                cmd.num_peers,
+                cmd.threshold
+            );
+            gas::report(
+                provider,
+                config.oprf_key_registry_contract,
+                private_key.address(),
+                cmd,
+            )
+            .await?;
+        }
     }
     Ok(())
 }
@@ -0,0 +1,204 @@
+//! Long-running soak test.
+//!
+//! Keeps `concurrency` OPRF runs in flight for `duration` and samples the memory statistics of every node (served at `/debug/memory` by nodes with the `jemalloc` feature, see `OprfServiceBuilder::memory_stats`) every `sample_interval`. Every sample is appended as one JSON line to `output`, so RSS, allocator and subsystem stats can be trended over days.
+//!
+//! After the load stopped, a final sample is taken. Nodes that still report open sessions or grew their allocated bytes by more than `max_growth` between the first and the final sample are reported as suspected leaks.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write as _},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use eyre::Context as _;
+use oprf_client::Connector;
+use oprf_types::api::MemoryStats;
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use crate::{DevClient, DevClientConfig, SoakTestCommand};
+
+/// Time the nodes get to release the sessions of the last runs before the final sample.
+const SETTLE_TIME: Duration = Duration::from_secs(5);
+
+/// A line of the soak-test output.
+#[derive(Debug, Serialize)]
+struct Sample<'a> {
+    elapsed_secs: u64,
+    node: &'a str,
+    successes: u64,
+    failures: u64,
+    /// `None` if the node does not serve memory stats
+    stats: Option<&'a MemoryStats>,
+}
+
+/// Runs the soak test, see the [module docs](self).
+pub async fn soak_test<T: DevClient>(
+    dev_client: T,
+    config: DevClientConfig,
+    cmd: SoakTestCommand,
+    setup: T::Setup,
+    connector: Connector,
+) -> eyre::Result<()> {
+    let file = File::create(&cmd.output)
+        .with_context(|| format!("while creating {}", cmd.output.display()))?;
+    let mut output = BufWriter::new(file);
+    let client = reqwest::Client::new();
+    let start = Instant::now();
+    let deadline = start + cmd.duration;
+    let successes = Arc::new(AtomicU64::new(0));
+    let failures = Arc::new(AtomicU64::new(0));
+
+    let dev_client = Arc::new(dev_client);
+    let config = Arc::new(config);
+    let mut workers = JoinSet::new();
+    for _ in 0..cmd.concurrency.max(1) {
+        let dev_client = Arc::clone(&dev_client);
+        let config = Arc::clone(&config);
+        let setup = setup.clone();
+        let connector = connector.clone();
+        let successes = Arc::clone(&successes);
+        let failures = Arc::clone(&failures);
+        workers.spawn(async move {
+            while Instant::now() < deadline {
+                match dev_client
+                    .run_oprf(&config, setup.clone(), connector.clone())
+                    .await
+                {
+                    Ok(_) => successes.fetch_add(1, Ordering::Relaxed),
+                    Err(err) => {
+                        tracing::warn!("oprf run failed: {err:?}");
+                        failures.fetch_add(1, Ordering::Relaxed)
+                    }
+                };
+            }
+        });
+    }
+
+    // the first sample is taken after one interval under load, so warming up the caches is not reported as growth
+    let mut first = vec![None; config.nodes.len()];
+    let mut interval = tokio::time::interval_at(
+        tokio::time::Instant::now() + cmd.sample_interval,
+        cmd.sample_interval,
+    );
+    while !workers.is_empty() {
+        tokio::select! {
+            _ = interval.tick() => {
+                let samples = sample_nodes(&client, &config.nodes).await;
+                write_samples(&mut output, start, &config.nodes, &samples, &successes, &failures)?;
+                for (first, sample) in first.iter_mut().zip(samples) {
+                    if first.is_none() {
+                        *first = sample;
+                    }
+                }
+                tracing::info!(
+                    "{:?} elapsed, {} successful and {} failed runs",
+                    start.elapsed(),
+                    successes.load(Ordering::Relaxed),
+                    failures.load(Ordering::Relaxed)
+                );
+            }
+            Some(result) = workers.join_next() => {
+                result.context("soak worker panicked")?;
+            }
+        }
+    }
+
+    tokio::time::sleep(SETTLE_TIME).await;
+    let last = sample_nodes(&client, &config.nodes).await;
+    write_samples(
+        &mut output,
+        start,
+        &config.nodes,
+        &last,
+        &successes,
+        &failures,
+    )?;
+    output.flush()?;
+
+    let mut suspected_leaks = 0;
+    for ((node, first), last) in config.nodes.iter().zip(&first).zip(&last) {
+        let (Some(first), Some(last)) = (first, last) else {
+            tracing::warn!("{node} does not serve memory stats");
+            continue;
+        };
+        if last.subsystems.open_sessions > 0 {
+            tracing::warn!(
+                "{node} still has {} open sessions after the load stopped",
+                last.subsystems.open_sessions
+            );
+            suspected_leaks += 1;
+        }
+        let allowed = first.allocator.allocated_bytes as f64 * (1.0 + cmd.max_growth);
+        if last.allocator.allocated_bytes as f64 > allowed {
+            tracing::warn!(
+                "{node} grew from {} to {} allocated bytes",
+                first.allocator.allocated_bytes,
+                last.allocator.allocated_bytes
+            );
+            suspected_leaks += 1;
+        }
+    }
+    tracing::info!(
+        "soak test finished after {:?} with {} successful and {} failed runs, samples written to {}",
+        start.elapsed(),
+        successes.load(Ordering::Relaxed),
+        failures.load(Ordering::Relaxed),
+        cmd.output.display()
+    );
+    eyre::ensure!(
+        suspected_leaks == 0,
+        "found {suspected_leaks} suspected leaks"
+    );
+    Ok(())
+}
+
+/// Fetches the memory stats of all nodes, `None` for nodes that cannot serve them.
+async fn sample_nodes(client: &reqwest::Client, nodes: &[String]) -> Vec<Option<MemoryStats>> {
+    let mut samples = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let result = async {
+            client
+                .get(format!("{node}/debug/memory"))
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<MemoryStats>()
+                .await
+        }
+        .await;
+        samples.push(
+            result
+                .inspect_err(|err| tracing::debug!("cannot sample {node}: {err}"))
+                .ok(),
+        );
+    }
+    samples
+}
+
+fn write_samples(
+    output: &mut impl std::io::Write,
+    start: Instant,
+    nodes: &[String],
+    samples: &[Option<MemoryStats>],
+    successes: &AtomicU64,
+    failures: &AtomicU64,
+) -> eyre::Result<()> {
+    for (node, stats) in nodes.iter().zip(samples) {
+        let sample = Sample {
+            elapsed_secs: start.elapsed().as_secs(),
+            node,
+            successes: successes.load(Ordering::Relaxed),
+            failures: failures.load(Ordering::Relaxed),
+            stats: stats.as_ref(),
+        };
+        serde_json::to_writer(&mut *output, &sample)?;
+        writeln!(output)?;
+    }
+    output.flush()?;
+    Ok(())
+}
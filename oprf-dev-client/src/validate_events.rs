@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use alloy::{
+    primitives::{Address, B256},
+    providers::{DynProvider, Provider as _},
+    rpc::types::{Filter, Log},
+    sol_types::SolEventInterface as _,
+};
+use eyre::Context as _;
+use oprf_types::chain::{
+    OprfKeyRegistry::OprfKeyRegistryEvents,
+    logs::{LogWatermark, is_range_limit_error, normalize_logs},
+};
+
+use crate::ValidateEventsCommand;
+
+/// A log emitted by the registry that could not be handled by the decoders of `oprf_types::chain`.
+#[derive(Debug)]
+pub struct InvalidEvent {
+    pub block_number: Option<u64>,
+    pub tx_hash: Option<B256>,
+    pub topic0: Option<B256>,
+    /// `None` if the topic is not known to the decoders at all.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct EventValidationReport {
+    /// Number of successfully decoded logs per event name.
+    pub decoded: BTreeMap<&'static str, usize>,
+    /// Logs with a known signature whose data could not be decoded.
+    pub undecodable: Vec<InvalidEvent>,
+    /// Logs with a signature unknown to the decoders.
+    pub unknown: Vec<InvalidEvent>,
+}
+
+impl EventValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.undecodable.is_empty() && self.unknown.is_empty()
+    }
+
+    fn record(&mut self, log: &Log) {
+        let topic0 = log.topic0().copied();
+        let name = topic0.and_then(|topic| OprfKeyRegistryEvents::name_by_selector(topic.0));
+        let Some(name) = name else {
+            self.unknown.push(InvalidEvent {
+                block_number: log.block_number,
+                tx_hash: log.transaction_hash,
+                topic0,
+                error: None,
+            });
+            return;
+        };
+        match OprfKeyRegistryEvents::decode_raw_log(log.topics(), &log.data().data) {
+            Ok(_) => *self.decoded.entry(name).or_default() += 1,
+            Err(err) => self.undecodable.push(InvalidEvent {
+                block_number: log.block_number,
+                tx_hash: log.transaction_hash,
+                topic0,
+                error: Some(format!("{name}: {err}")),
+            }),
+        }
+    }
+}
+
+/// Replays the logs of `oprf_key_registry` in the provided block range through the event decoders.
+///
+/// Logs are processed in the same order on every provider: every batch is sorted by block number, transaction index and log index, consecutive requests overlap by `overlap_blocks` and logs seen already are skipped. If the provider rejects a range as too large, the chunk size is halved.
+pub async fn validate_events(
+    provider: DynProvider,
+    oprf_key_registry: Address,
+    cmd: ValidateEventsCommand,
+) -> eyre::Result<EventValidationReport> {
+    let to_block = match cmd.to_block {
+        Some(to_block) => to_block,
+        None => provider
+            .get_block_number()
+            .await
+            .context("while fetching latest block number")?,
+    };
+    if cmd.from_block > to_block {
+        eyre::bail!("from_block {} is after to_block {to_block}", cmd.from_block);
+    }
+    let mut chunk_size = cmd.chunk_size.max(1);
+
+    let mut report = EventValidationReport::default();
+    let mut watermark = LogWatermark::default();
+    let mut start = cmd.from_block;
+    loop {
+        let end = start.saturating_add(chunk_size - 1).min(to_block);
+        let from = start.saturating_sub(cmd.overlap_blocks).max(cmd.from_block);
+        tracing::debug!("fetching logs for blocks {from}..={end}");
+        let filter = Filter::new()
+            .address(oprf_key_registry)
+            .from_block(from)
+            .to_block(end);
+        let logs = match provider.get_logs(&filter).await {
+            Ok(logs) => logs,
+            Err(err) if chunk_size > 1 && is_range_limit_error(&err.to_string()) => {
+                chunk_size /= 2;
+                tracing::warn!(
+                    "provider rejected blocks {from}..={end}, retrying with {chunk_size} blocks per request"
+                );
+                continue;
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("while fetching logs for blocks {from}..={end}"));
+            }
+        };
+        for log in normalize_logs(logs) {
+            if watermark.advance(&log) {
+                report.record(&log);
+            }
+        }
+        if end == to_block {
+            break;
+        }
+        start = end + 1;
+    }
+    Ok(report)
+}
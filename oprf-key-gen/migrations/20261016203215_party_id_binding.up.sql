@@ -0,0 +1,10 @@
+-- The party id the OPRF node last served with, written by the node (see `OprfServiceBuilder::bind_party_id`).
+CREATE TABLE party_id_binding (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE,
+    eth_address TEXT NOT NULL,
+    party_id INTEGER NOT NULL,
+    oprf_key_registry TEXT NOT NULL,
+    chain_id BIGINT NOT NULL,
+
+    CONSTRAINT party_id_binding_singleton CHECK (id = TRUE)
+);
@@ -0,0 +1,10 @@
+-- The public key of every finalized epoch of a key, written by the node (see `OprfServiceBuilder::public_key_history`).
+CREATE TABLE public_key_history (
+    id BYTEA NOT NULL,
+    epoch BIGINT NOT NULL, -- we use BigInt to securly convert from u32 to i64
+    public_key BYTEA NOT NULL,
+
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (id, epoch)
+);
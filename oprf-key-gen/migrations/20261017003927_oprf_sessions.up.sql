@@ -0,0 +1,7 @@
+-- The sessions of the OPRF node, written by the node if it uses the durable `PostgresSessionStore`.
+CREATE TABLE oprf_sessions (
+    id BYTEA PRIMARY KEY,
+    owner BYTEA,
+    session BYTEA,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
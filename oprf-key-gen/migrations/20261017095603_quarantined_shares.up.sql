@@ -0,0 +1,10 @@
+-- Shares of keys the `OprfKeyRegistry` no longer knows, moved out of `shares` by the startup reconciliation of the key-gen (see `orphaned_keys`).
+CREATE TABLE quarantined_shares (
+    id BYTEA PRIMARY KEY NOT NULL,
+    share BYTEA NOT NULL,
+    epoch BIGINT NOT NULL, -- we use BigInt to securly convert from u32 to i64
+    public_key BYTEA NOT NULL,
+    threshold INTEGER,
+
+    quarantined_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
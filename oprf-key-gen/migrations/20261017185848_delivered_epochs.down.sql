@@ -0,0 +1 @@
+DROP TABLE IF EXISTS delivered_epochs;
@@ -0,0 +1,7 @@
+-- The epoch of every key delivered last to the epoch transition hook of the OPRF node, written by the node (see `OprfServiceBuilder::epoch_transition_hook`).
+CREATE TABLE delivered_epochs (
+    id BYTEA PRIMARY KEY,
+    epoch BIGINT NOT NULL, -- we use BigInt to securly convert from u32 to i64
+
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
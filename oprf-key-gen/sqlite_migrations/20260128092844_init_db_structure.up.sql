@@ -0,0 +1,37 @@
+-- %%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+-- %               Address  Singleton                 %
+-- %%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+CREATE TABLE evm_address (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE,
+    address TEXT NOT NULL,
+
+    CONSTRAINT evm_address_singleton CHECK (id = TRUE)
+);
+
+
+-- %%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+-- %                DLog Shares                       %
+-- %%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+CREATE TABLE shares (
+    id BLOB PRIMARY KEY NOT NULL,
+    share BLOB,
+    epoch INTEGER NOT NULL, -- SQLite integers are 64 bit, so every u32 fits
+    public_key BLOB NOT NULL,
+    deleted BOOLEAN NOT NULL DEFAULT FALSE,
+
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT deleted_share_null
+    CHECK (
+        (deleted = FALSE AND share IS NOT NULL)
+     OR (deleted = TRUE  AND share IS NULL)
+    )
+);
+
+CREATE TRIGGER shares_set_updated_at
+AFTER UPDATE ON shares
+FOR EACH ROW
+BEGIN
+    UPDATE shares SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
@@ -0,0 +1,2 @@
+DROP TRIGGER IF EXISTS in_progress_keygens_set_updated_at;
+DROP TABLE IF EXISTS in_progress_keygens;
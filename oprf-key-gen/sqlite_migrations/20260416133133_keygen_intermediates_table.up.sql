@@ -0,0 +1,19 @@
+CREATE TABLE in_progress_keygens (
+    id BLOB NOT NULL,
+    pending_epoch INTEGER NOT NULL,
+    pending_share BLOB,
+    intermediates BLOB NOT NULL,
+
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (id, pending_epoch)
+);
+
+CREATE TRIGGER in_progress_keygens_set_updated_at
+AFTER UPDATE ON in_progress_keygens
+FOR EACH ROW
+BEGIN
+    UPDATE in_progress_keygens SET updated_at = CURRENT_TIMESTAMP
+    WHERE id = NEW.id AND pending_epoch = NEW.pending_epoch;
+END;
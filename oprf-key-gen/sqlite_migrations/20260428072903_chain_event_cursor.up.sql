@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE TABLE chain_cursor (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id = TRUE),
+    block INTEGER NOT NULL,
+    idx INTEGER NOT NULL
+);
+
+INSERT INTO chain_cursor (block, idx)
+VALUES (0, 0);
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE shares DROP COLUMN threshold;
+ALTER TABLE in_progress_keygens DROP COLUMN threshold;
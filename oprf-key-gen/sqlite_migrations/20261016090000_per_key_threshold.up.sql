@@ -0,0 +1,3 @@
+-- The threshold of a key, NULL for keys generated before it was stored per key.
+ALTER TABLE in_progress_keygens ADD COLUMN threshold INTEGER;
+ALTER TABLE shares ADD COLUMN threshold INTEGER;
@@ -0,0 +1,10 @@
+-- The public key of every finalized epoch of a key, written by the node (see `OprfServiceBuilder::public_key_history`).
+CREATE TABLE public_key_history (
+    id BLOB NOT NULL,
+    epoch INTEGER NOT NULL, -- SQLite integers are 64 bit, so every u32 fits
+    public_key BLOB NOT NULL,
+
+    recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (id, epoch)
+);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS oprf_sessions;
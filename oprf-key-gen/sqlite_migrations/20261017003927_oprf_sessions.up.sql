@@ -0,0 +1,7 @@
+-- The sessions of the OPRF node, written by the node if it uses the durable `PostgresSessionStore`.
+CREATE TABLE oprf_sessions (
+    id BLOB PRIMARY KEY,
+    owner BLOB,
+    session BLOB,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
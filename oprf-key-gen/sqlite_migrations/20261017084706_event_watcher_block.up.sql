@@ -0,0 +1,7 @@
+-- The last block the event watchers of the OPRF node handled per contract, written by the node (see `OprfServiceBuilder::public_key_history`).
+CREATE TABLE event_watcher_block (
+    contract TEXT PRIMARY KEY,
+    block INTEGER NOT NULL, -- SQLite integers are 64 bit, we check the conversion from u64 to i64
+
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,10 @@
+-- Shares of keys the `OprfKeyRegistry` no longer knows, moved out of `shares` by the startup reconciliation of the key-gen (see `orphaned_keys`).
+CREATE TABLE quarantined_shares (
+    id BLOB PRIMARY KEY NOT NULL,
+    share BLOB NOT NULL,
+    epoch INTEGER NOT NULL, -- SQLite integers are 64 bit, so every u32 fits
+    public_key BLOB NOT NULL,
+    threshold INTEGER,
+
+    quarantined_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
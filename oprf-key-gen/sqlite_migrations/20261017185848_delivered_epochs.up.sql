@@ -0,0 +1,7 @@
+-- The epoch of every key delivered last to the epoch transition hook of the OPRF node, written by the node (see `OprfServiceBuilder::epoch_transition_hook`).
+CREATE TABLE delivered_epochs (
+    id BLOB PRIMARY KEY,
+    epoch INTEGER NOT NULL, -- SQLite integers are 64 bit, so every u32 fits
+
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
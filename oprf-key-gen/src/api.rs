@@ -2,25 +2,223 @@
 //!
 //! This module defines all HTTP endpoints an OPRF key gen instance must serve to participate in TACEO:OPRF and organizes them into submodules:
 //!
+//! - [`admin`] – List and abort in-progress key-gen/reshare runs (`/keygen/active`, `DELETE /keygen/{oprf_key_id}`), if enabled.
+//! - [`buildinfo`] – Build provenance of the service (`/buildinfo`).
 //! - [`info`] – Info about the service (`/version`, `/wallet`).
+//! - [`crate::metrics::exporter`] – the metrics in the Prometheus text format (`/metrics`), if enabled.
 
 use alloy::primitives::Address;
 use axum::Router;
+use eyre::Context as _;
 use nodes_common::StartedServices;
 
+use crate::{
+    config::OprfKeyGenServiceConfig, metrics, services::key_event_watcher::admin::AdminHandle,
+};
+
+pub(crate) mod admin;
+pub(crate) mod buildinfo;
 pub(crate) mod info;
 
 /// Builds the main API router for the OPRF key gen instance.
 ///
 /// This function sets up:
 ///
-/// - General info about the deployment from [`info`].
+/// - General info about the deployment from [`info`] and the build provenance from [`buildinfo`]. Logs the build info as startup banner.
 /// - Call to `nodes_common::api::routes_with_services`.
+/// - The `/metrics` endpoint, if `metrics_endpoint` is set in the `config`. Installs the Prometheus recorder, see [`metrics::exporter::install`].
+/// - The [`admin`] endpoints sending commands to the key event watchers via the `admin_handle`, if `admin_endpoints` is set in the `config`.
+///
+/// If `root_path` is set in the `config` (e.g. `/oprf-key-gen`), all routes are served below it.
 ///
 /// The returned [`Router`] can be incorporated into another router or be served directly by axum.
-pub fn routes(wallet_address: Address, started_services: StartedServices) -> Router {
+pub fn routes(
+    wallet_address: Address,
+    started_services: StartedServices,
+    admin_handle: AdminHandle,
+    config: &OprfKeyGenServiceConfig,
+) -> eyre::Result<Router> {
+    let root_path = config.root_path.as_deref();
+    eyre::ensure!(
+        root_path.is_none_or(|root_path| {
+            root_path.starts_with('/') && !root_path.ends_with('/') && !root_path.contains("//")
+        }),
+        "root_path must start with `/` and must not end with `/`"
+    );
     let version_str = nodes_common::version_info!();
-    Router::new().merge(info::routes(wallet_address)).merge(
-        nodes_common::api::routes_with_services(started_services, version_str),
-    )
+    let build_info = buildinfo::build_info(version_str.clone());
+    tracing::info!(
+        version = build_info.version,
+        git_sha = build_info.git_sha,
+        build_timestamp = build_info.build_timestamp,
+        features = ?build_info.features,
+        "build info"
+    );
+    let mut router = Router::new()
+        .merge(info::routes(wallet_address))
+        .merge(buildinfo::routes(build_info))
+        .merge(nodes_common::api::routes_with_services(
+            started_services,
+            version_str,
+        ));
+    if config.metrics_endpoint {
+        let handle =
+            metrics::exporter::install().context("while installing Prometheus exporter")?;
+        router = router.merge(metrics::exporter::routes(handle));
+    }
+    if config.admin_endpoints {
+        router = router.merge(admin::routes(admin_handle));
+    }
+    Ok(match root_path {
+        Some(root_path) => Router::new().nest(root_path, router),
+        None => router,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+
+    use axum_test::TestServer;
+    use nodes_common::{Environment, web3::HttpRpcProviderConfig};
+    use oprf_types::{OprfKeyId, ShareEpoch};
+
+    use super::*;
+    use crate::config::OprfKeyGenServiceConfigMandatoryValues;
+
+    fn config(root_path: Option<&str>) -> OprfKeyGenServiceConfig {
+        let mut config =
+            OprfKeyGenServiceConfig::with_default_values(OprfKeyGenServiceConfigMandatoryValues {
+                environment: Environment::Dev,
+                oprf_key_registry_contract: Address::ZERO,
+                wallet_private_key: String::new().into(),
+                zkey_path: "key_gen.zkey".into(),
+                witness_graph_path: "key_gen.bin".into(),
+                expected_threshold: NonZeroU16::MIN,
+                expected_num_peers: NonZeroU16::MIN,
+                rpc_provider_config: HttpRpcProviderConfig::with_default_values([
+                    "http://localhost:8545",
+                ])
+                .expect("Can build provider config"),
+                ws_rpc_url: "ws://localhost:8545".into(),
+            });
+        config.root_path = root_path.map(ToOwned::to_owned);
+        config
+    }
+
+    #[tokio::test]
+    async fn root_path_prefixes_all_routes() {
+        let router = routes(
+            Address::ZERO,
+            StartedServices::new(),
+            AdminHandle::default(),
+            &config(Some("/oprf-key-gen")),
+        )
+        .expect("Can build routes");
+        let server = TestServer::new(router).expect("Can build test-server");
+
+        server.get("/oprf-key-gen/version").await.assert_status_ok();
+        server.get("/oprf-key-gen/wallet").await.assert_status_ok();
+        server
+            .get("/oprf-key-gen/buildinfo")
+            .await
+            .assert_status_ok();
+        server.get("/wallet").await.assert_status_not_found();
+    }
+
+    /// Registers a watcher of `registry` that tracks a single run of `oprf_key_id` until it is aborted.
+    fn stub_watcher(admin_handle: &mut AdminHandle, registry: Address, oprf_key_id: OprfKeyId) {
+        use crate::services::key_event_watcher::admin::{ActiveRun, AdminCommand};
+        let mut commands = admin_handle.register(registry);
+        tokio::spawn(async move {
+            let mut run = Some(ActiveRun {
+                registry,
+                oprf_key_id,
+                epoch: ShareEpoch::new(1),
+                expires_in_secs: 60,
+            });
+            while let Some(command) = commands.recv().await {
+                match command {
+                    AdminCommand::ListActive(reply) => {
+                        reply.send(run.clone().into_iter().collect()).ok();
+                    }
+                    AdminCommand::Abort { reply, .. } => {
+                        reply.send(Ok(run.take())).ok();
+                    }
+                }
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn admin_routes_list_and_abort_runs() {
+        let oprf_key_id = OprfKeyId::from(42usize);
+        let registry = Address::repeat_byte(0x42);
+        let mut admin_handle = AdminHandle::default();
+        stub_watcher(&mut admin_handle, registry, oprf_key_id);
+
+        let server = TestServer::new(
+            routes(
+                Address::ZERO,
+                StartedServices::new(),
+                admin_handle.clone(),
+                &config(None),
+            )
+            .expect("Can build routes"),
+        )
+        .expect("Can build test-server");
+        server.get("/keygen/active").await.assert_status_not_found();
+
+        let mut config = config(None);
+        config.admin_endpoints = true;
+        let router = routes(Address::ZERO, StartedServices::new(), admin_handle, &config)
+            .expect("Can build routes");
+        let server = TestServer::new(router).expect("Can build test-server");
+
+        let runs = server
+            .get("/keygen/active")
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(
+            runs.as_array().map(Vec::len),
+            Some(1),
+            "should list the run"
+        );
+        assert_eq!(runs[0]["epoch"], 1);
+
+        server
+            .delete(&format!("/keygen/{oprf_key_id}"))
+            .add_query_param("registry", Address::ZERO)
+            .await
+            .assert_status_not_found();
+        let aborted = server
+            .delete(&format!("/keygen/{oprf_key_id}"))
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(aborted, runs, "should abort the listed run");
+        server
+            .delete(&format!("/keygen/{oprf_key_id}"))
+            .await
+            .assert_status_not_found();
+        server
+            .get("/keygen/active")
+            .await
+            .assert_json(&serde_json::json!([]));
+    }
+
+    #[test]
+    fn invalid_root_path_is_rejected() {
+        for root_path in ["oprf-key-gen", "/oprf-key-gen/", "/"] {
+            assert!(
+                routes(
+                    Address::ZERO,
+                    StartedServices::new(),
+                    AdminHandle::default(),
+                    &config(Some(root_path))
+                )
+                .is_err(),
+                "should reject {root_path}"
+            );
+        }
+    }
 }
@@ -0,0 +1,79 @@
+//! Admin Endpoints
+//!
+//! Exposes the following API endpoints, if `admin_endpoints` is enabled in the config:
+//!
+//! - `/keygen/active` – returns the in-progress key-gen/reshare runs of all registries, i.e., the runs the key-gen currently holds intermediates for, the run that expires first first.
+//! - `DELETE /keygen/{oprf_key_id}` – aborts the in-progress run of the key: evicts its intermediates and calls the abort notifier, like for a run that did not finish within the `protocol_timeout`. Aborts the run in every registry, unless the registry is selected with the `registry` query parameter. Returns the aborted runs, or `404 Not Found` if there is no in-progress run of the key.
+//!
+//! The runs are listed as tracked by the key event watchers, see `services::key_event_watcher::admin`. Aborting a run only evicts it locally, it stays open on-chain until it is aborted there (e.g., by the abort notifier or with `abortKeyGen`).
+//!
+//! The endpoints include a `Cache-Control: no-cache` header to prevent caching of responses.
+use alloy::primitives::Address;
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+};
+use oprf_types::OprfKeyId;
+use serde::Deserialize;
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::services::key_event_watcher::admin::AdminHandle;
+
+/// The query parameters of `DELETE /keygen/{oprf_key_id}`.
+#[derive(Debug, Deserialize)]
+struct AbortQuery {
+    /// Only abort the run in this registry.
+    registry: Option<Address>,
+}
+
+/// Create a router containing the admin endpoints.
+///
+/// All endpoints have `Cache-Control: no-cache` set.
+pub(crate) fn routes(admin_handle: AdminHandle) -> Router {
+    Router::new()
+        .route("/keygen/active", get(active_runs))
+        .route("/keygen/{oprf_key_id}", delete(abort_run))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("no-cache"),
+        ))
+        .with_state(admin_handle)
+}
+
+/// Responds with the in-progress runs of all registries.
+///
+/// Returns `200 OK` with a JSON response, or `503 Service Unavailable` if a key event watcher stopped.
+async fn active_runs(State(admin_handle): State<AdminHandle>) -> Response {
+    match admin_handle.active_runs().await {
+        Ok(runs) => (StatusCode::OK, Json(runs)).into_response(),
+        Err(err) => {
+            tracing::warn!(%err, "cannot list in-progress runs");
+            (StatusCode::SERVICE_UNAVAILABLE, format!("{err:#}")).into_response()
+        }
+    }
+}
+
+/// Aborts the in-progress run of the key and responds with the aborted runs.
+///
+/// Returns `200 OK` with a JSON response, `404 Not Found` if there is no in-progress run of the key, or `500 Internal Server Error` if the run cannot be aborted.
+async fn abort_run(
+    State(admin_handle): State<AdminHandle>,
+    Path(oprf_key_id): Path<OprfKeyId>,
+    Query(query): Query<AbortQuery>,
+) -> Response {
+    match admin_handle.abort(oprf_key_id, query.registry).await {
+        Ok(aborted) if aborted.is_empty() => (
+            StatusCode::NOT_FOUND,
+            format!("no in-progress run of {oprf_key_id}"),
+        )
+            .into_response(),
+        Ok(aborted) => (StatusCode::OK, Json(aborted)).into_response(),
+        Err(err) => {
+            tracing::warn!(%err, "cannot abort run of {oprf_key_id}");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#}")).into_response()
+        }
+    }
+}
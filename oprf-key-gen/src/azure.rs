@@ -0,0 +1,251 @@
+//! Azure Key Vault backend for the OPRF key-gen service.
+//!
+//! This module provides [`AzureSecretManager`], an implementation of [`SecretManager`](crate::secret_manager::SecretManager)
+//! that stores the node information, the `DLog` shares and the in-progress key-gen state as secrets in
+//! [Azure Key Vault](https://learn.microsoft.com/azure/key-vault/). It can additionally load the
+//! wallet private key from a secret (see [`RemoteSecretManager::load_wallet_private_key`]).
+//!
+//! Authentication uses managed identities: access tokens are fetched from the Azure Instance
+//! Metadata Service (IMDS) for the system-assigned identity, or for the user-assigned identity
+//! selected by [`AzureConfig::client_id`].
+//!
+//! Every value is stored as the current version of one secret, named after
+//! [`AzureConfig::secret_prefix`]. See [`crate::remote`] for the layout of the secrets and the
+//! single-writer assumption. Key Vault usually has soft-delete enabled, which blocks re-creating a
+//! deleted secret until it is purged, so this backend never deletes secrets but overwrites them
+//! with `null` instead.
+//!
+//! Key Vault throttles requests per vault. Throttled requests are answered with `429` and a
+//! `Retry-After` header, which is honored by the retries.
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use oprf_types::retry::RetryPolicy;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::Deserialize;
+use tracing::instrument;
+use zeroize::Zeroizing;
+
+use crate::remote::{RemoteError, RemoteSecretManager, SecretStore, TokenCache, error_for_status};
+
+type Result<T> = std::result::Result<T, RemoteError>;
+
+/// The Key Vault REST API version used by this backend.
+const API_VERSION: &str = "7.4";
+
+/// The configuration for the Azure Key Vault backend.
+#[derive(Clone, Debug, Deserialize)]
+#[non_exhaustive]
+pub struct AzureConfig {
+    /// The URL of the vault, e.g., `https://my-vault.vault.azure.net`.
+    pub vault_url: String,
+    /// Prefix of all secrets created by this node. Must be unique per node within the vault.
+    #[serde(default = "AzureConfig::default_secret_prefix")]
+    pub secret_prefix: String,
+    /// The name of the secret holding the hex-encoded wallet private key, if it should be loaded from Key Vault.
+    #[serde(default)]
+    pub wallet_private_key_secret: Option<String>,
+    /// The client ID of a user-assigned managed identity. Uses the system-assigned identity if not set.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// The Instance Metadata Service used to obtain managed-identity access tokens.
+    #[serde(default = "AzureConfig::default_imds_endpoint")]
+    pub imds_endpoint: String,
+    /// Timeout of a single request to Key Vault.
+    #[serde(default = "AzureConfig::default_request_timeout")]
+    #[serde(with = "humantime_serde")]
+    pub request_timeout: Duration,
+    /// Maximum number of attempts for a request that failed with a transient error.
+    #[serde(default = "AzureConfig::default_max_retries")]
+    pub max_retries: NonZeroUsize,
+    /// Delay before the first retry, doubled (with jitter) for every further retry. Throttled requests wait at least as long as requested by Key Vault.
+    #[serde(default = "AzureConfig::default_retry_delay")]
+    #[serde(with = "humantime_serde")]
+    pub retry_delay: Duration,
+}
+
+impl AzureConfig {
+    /// Default secret prefix (`oprf`)
+    fn default_secret_prefix() -> String {
+        "oprf".to_owned()
+    }
+
+    /// Default Instance Metadata Service endpoint
+    fn default_imds_endpoint() -> String {
+        "http://169.254.169.254".to_owned()
+    }
+
+    /// Default request timeout (`10 s`)
+    fn default_request_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    /// Default max retries
+    fn default_max_retries() -> NonZeroUsize {
+        NonZeroUsize::new(3).expect("Is non-zero")
+    }
+
+    /// Default retry delay (`1 s`)
+    fn default_retry_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Creates a config for the provided vault with default values.
+    #[must_use]
+    pub fn with_default_values(vault_url: String) -> Self {
+        Self {
+            vault_url,
+            secret_prefix: Self::default_secret_prefix(),
+            wallet_private_key_secret: None,
+            client_id: None,
+            imds_endpoint: Self::default_imds_endpoint(),
+            request_timeout: Self::default_request_timeout(),
+            max_retries: Self::default_max_retries(),
+            retry_delay: Self::default_retry_delay(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: SecretString,
+    // IMDS returns the lifetime as string
+    expires_in: String,
+}
+
+#[derive(Deserialize)]
+struct SecretBundle {
+    value: SecretString,
+}
+
+/// Azure Key Vault backed implementation of [`SecretManager`](crate::secret_manager::SecretManager).
+pub type AzureSecretManager = RemoteSecretManager<AzureSecretStore>;
+
+/// The Key Vault REST API as [`SecretStore`] of an [`AzureSecretManager`].
+pub struct AzureSecretStore {
+    client: reqwest::Client,
+    config: AzureConfig,
+    access_token: TokenCache,
+}
+
+impl RemoteSecretManager<AzureSecretStore> {
+    /// Initializes the [`AzureSecretManager`] and checks that an access token can be obtained from the managed identity.
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client cannot be built or no access token can be obtained.
+    #[instrument(level = "info", skip_all)]
+    pub async fn init(config: AzureConfig) -> eyre::Result<Self> {
+        tracing::info!(
+            "init Azure Key Vault secret manager for vault {} with prefix {}",
+            config.vault_url,
+            config.secret_prefix
+        );
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()?;
+        let secret_manager = Self::new(
+            AzureSecretStore {
+                client,
+                config: config.clone(),
+                access_token: TokenCache::new(),
+            },
+            config.secret_prefix,
+            config.wallet_private_key_secret,
+            RetryPolicy::new(config.max_retries.get(), config.retry_delay),
+        );
+        secret_manager
+            .with_retry("fetch-access-token", || {
+                secret_manager.store().access_token()
+            })
+            .await?;
+        Ok(secret_manager)
+    }
+}
+
+impl AzureSecretStore {
+    fn secret_url(&self, secret_id: &str) -> String {
+        format!(
+            "{}/secrets/{secret_id}?api-version={API_VERSION}",
+            self.config.vault_url.trim_end_matches('/')
+        )
+    }
+
+    /// Returns a valid access token, refreshing it from IMDS if necessary.
+    async fn access_token(&self) -> Result<SecretString> {
+        self.access_token
+            .get(|| async {
+                let mut url = format!(
+                    "{}/metadata/identity/oauth2/token?api-version=2018-02-01&resource=https://vault.azure.net",
+                    self.config.imds_endpoint.trim_end_matches('/')
+                );
+                if let Some(client_id) = &self.config.client_id {
+                    url.push_str("&client_id=");
+                    url.push_str(client_id);
+                }
+                let response = self
+                    .client
+                    .get(url)
+                    .header("Metadata", "true")
+                    .send()
+                    .await?;
+                let response: TokenResponse = error_for_status(response).await?.json().await?;
+                let expires_in = response.expires_in.parse().map_err(|e| {
+                    eyre::eyre!("invalid expires_in {} from IMDS: {e}", response.expires_in)
+                })?;
+                Ok((response.access_token, Duration::from_secs(expires_in)))
+            })
+            .await
+    }
+
+    async fn set(&self, secret_id: &str, value: &str) -> Result<()> {
+        let response = self
+            .client
+            .put(self.secret_url(secret_id))
+            .bearer_auth(self.access_token().await?.expose_secret())
+            .json(&serde_json::json!({ "value": value }))
+            .send()
+            .await?;
+        error_for_status(response).await?;
+        Ok(())
+    }
+}
+
+impl SecretStore for AzureSecretStore {
+    /// Reads the current version of the secret, `None` if the secret does not exist.
+    async fn read(&self, secret_id: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        let response = self
+            .client
+            .get(self.secret_url(secret_id))
+            .bearer_auth(self.access_token().await?.expose_secret())
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response: SecretBundle = error_for_status(response).await?.json().await?;
+        Ok(Some(Zeroizing::new(
+            response.value.expose_secret().as_bytes().to_vec(),
+        )))
+    }
+
+    /// Sets a new version of the secret, creating the secret if it does not exist.
+    async fn write(&self, secret_id: &str, data: &[u8]) -> Result<()> {
+        let value = Zeroizing::new(
+            String::from_utf8(data.to_vec())
+                .map_err(|_| eyre::eyre!("secret {secret_id} is not UTF-8"))?,
+        );
+        self.set(secret_id, &value).await
+    }
+
+    /// Overwrites the secret with `null`, see the [module docs](self).
+    async fn remove(&self, secret_id: &str) -> Result<()> {
+        match self.read(secret_id).await? {
+            Some(value) if value.as_slice() != b"null" => self.set(secret_id, "null").await,
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroU16,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::azure::{AzureConfig, AzureSecretManager};
+use crate::secret_manager::{SecretManager as _, SecretManagerError};
+use alloy::primitives::U160;
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    crypto::{OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use parking_lot::Mutex;
+use secrecy::ExposeSecret as _;
+
+/// The current value of every secret in the mocked vault.
+type Secrets = Arc<Mutex<HashMap<String, String>>>;
+
+#[derive(Clone, Default)]
+struct MockState {
+    secrets: Secrets,
+    /// Whether the next secret request is throttled.
+    throttle: Arc<AtomicBool>,
+}
+
+/// Minimal mock of IMDS and the Key Vault REST API.
+fn mock_router(state: MockState) -> Router {
+    Router::new()
+        .route(
+            "/metadata/identity/oauth2/token",
+            get(|| async {
+                Json(serde_json::json!({ "access_token": "token", "expires_in": "3600" }))
+            }),
+        )
+        .route(
+            "/secrets/{secret}",
+            get(
+                |State(state): State<MockState>, Path(secret): Path<String>| async move {
+                    if state.throttle.swap(false, Ordering::SeqCst) {
+                        return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "1")])
+                            .into_response();
+                    }
+                    match state.secrets.lock().get(&secret).cloned() {
+                        Some(value) => Json(serde_json::json!({ "value": value })).into_response(),
+                        None => StatusCode::NOT_FOUND.into_response(),
+                    }
+                },
+            )
+            .put(
+                |State(state): State<MockState>,
+                 Path(secret): Path<String>,
+                 Json(body): Json<serde_json::Value>| async move {
+                    let value = body["value"].as_str().expect("value").to_owned();
+                    state.secrets.lock().insert(secret, value);
+                    StatusCode::OK
+                },
+            ),
+        )
+        .with_state(state)
+}
+
+async fn azure_secret_manager() -> eyre::Result<(AzureSecretManager, MockState)> {
+    let state = MockState::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(axum::serve(listener, mock_router(state.clone())).into_future());
+    let mut config = AzureConfig::with_default_values(format!("http://{addr}"));
+    config.imds_endpoint = format!("http://{addr}");
+    config.wallet_private_key_secret = Some("wallet".to_owned());
+    config.retry_delay = Duration::from_millis(10);
+    Ok((AzureSecretManager::init(config).await?, state))
+}
+
+fn read_secret(secrets: &Secrets, id: &str) -> Option<serde_json::Value> {
+    let value = secrets.lock().get(id).cloned()?;
+    Some(serde_json::from_str(&value).expect("json"))
+}
+
+/// Stages a pending share for `epoch` like a finished key-gen would.
+async fn stage_pending_share(
+    secret_manager: &AzureSecretManager,
+    secrets: &Secrets,
+    oprf_key_id: OprfKeyId,
+    epoch: ShareEpoch,
+    share: DLogShareShamir,
+) -> eyre::Result<()> {
+    // `confirm_dlog_share` only reads `pending_share`; these tests do not deserialize `intermediates`.
+    let keygens = serde_json::json!({
+        epoch.to_string(): { "intermediates": "AA==", "pending_share": null }
+    });
+    secrets.lock().insert(
+        secret_manager.keygen_secret(oprf_key_id),
+        serde_json::to_string(&keygens)?,
+    );
+    secret_manager
+        .store_pending_dlog_share(oprf_key_id, epoch, share)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn store_node_information_and_load_wallet() -> eyre::Result<()> {
+    let (secret_manager, state) = azure_secret_manager().await?;
+    assert!(
+        secret_manager.load_wallet_private_key().await.is_err(),
+        "missing wallet secret must be an error"
+    );
+    state
+        .secrets
+        .lock()
+        .insert("wallet".to_owned(), "0x42\n".to_owned());
+    let wallet = secret_manager
+        .load_wallet_private_key()
+        .await?
+        .expect("wallet secret is configured");
+    assert_eq!(wallet.expose_secret(), "0x42");
+
+    let node_information = NodeInformation::new(
+        PartyId(42),
+        "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc".to_owned(),
+        NonZeroU16::new(2).expect("is non-zero"),
+    );
+    secret_manager
+        .store_node_information(node_information)
+        .await?;
+    assert_eq!(
+        read_secret(&state.secrets, "oprf-node-information"),
+        Some(serde_json::json!({
+            "eth_address": "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+            "party_id": 42,
+            "threshold": 2
+        })),
+        "node information is stored as JSON"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirm_fetch_and_delete_share() -> eyre::Result<()> {
+    let (secret_manager, state) = azure_secret_manager().await?;
+    let secrets = &state.secrets;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+
+    stage_pending_share(&secret_manager, secrets, oprf_key_id, epoch, share.clone()).await?;
+    secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch, public_key)
+        .await?;
+    assert_eq!(
+        read_secret(secrets, &secret_manager.keygen_secret(oprf_key_id)),
+        Some(serde_json::Value::Null),
+        "intermediates are overwritten with null after confirmation"
+    );
+    let is_share = secret_manager
+        .get_share_by_epoch(oprf_key_id, epoch)
+        .await?
+        .expect("share is stored");
+    assert_eq!(
+        ark_babyjubjub::Fr::from(is_share),
+        ark_babyjubjub::Fr::from(share),
+        "Should load the confirmed share"
+    );
+
+    secret_manager.delete_oprf_key_material(oprf_key_id).await?;
+    assert!(
+        secret_manager
+            .get_share_by_epoch(oprf_key_id, epoch)
+            .await?
+            .is_none(),
+        "Should not return a deleted share"
+    );
+
+    // a removed secret can be written again
+    let next_share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    stage_pending_share(
+        &secret_manager,
+        secrets,
+        oprf_key_id,
+        epoch.next(),
+        next_share,
+    )
+    .await?;
+    let err = secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch.next(), public_key)
+        .await
+        .expect_err("confirm on deleted share should fail");
+    assert!(
+        matches!(err, SecretManagerError::StoreOnDeletedShare),
+        "Should be StoreOnDeletedShare but is {err}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn honors_retry_after_when_throttled() -> eyre::Result<()> {
+    let (secret_manager, state) = azure_secret_manager().await?;
+    state
+        .secrets
+        .lock()
+        .insert("wallet".to_owned(), "0x42".to_owned());
+    state.throttle.store(true, Ordering::SeqCst);
+    let started = tokio::time::Instant::now();
+    let wallet = secret_manager
+        .load_wallet_private_key()
+        .await?
+        .expect("wallet secret is configured");
+    assert_eq!(wallet.expose_secret(), "0x42");
+    assert!(
+        started.elapsed() >= Duration::from_secs(1),
+        "Should wait for Retry-After instead of the retry delay"
+    );
+    Ok(())
+}
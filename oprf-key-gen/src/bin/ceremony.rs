@@ -0,0 +1,335 @@
+//! OPRF Key Gen Ceremony Binary
+//!
+//! Runs the steps of an offline key-generation ceremony, see [`taceo_oprf_key_gen::ceremony`]. Records, transcripts and sign-offs are read from and written to JSON files.
+//!
+//! The steps that need secrets are configured via environment variables using the `TACEO_OPRF_KEY_GEN__` prefix, as for the key-gen service:
+//! - `round1` and `round2` store the intermediate values in the Postgres secret manager (`TACEO_OPRF_KEY_GEN__POSTGRES__*`).
+//! - `sign-off` signs with the wallet of the node (`TACEO_OPRF_KEY_GEN__SERVICE__WALLET_PRIVATE_KEY`).
+//! - `import` submits the transactions with the service config (`TACEO_OPRF_KEY_GEN__SERVICE__*`).
+
+use std::{
+    num::NonZeroU16,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr as _,
+    sync::Arc,
+};
+
+use alloy::{
+    primitives::{Address, U160},
+    signers::local::PrivateKeySigner,
+};
+use clap::{Args, Parser, Subcommand};
+use config::Config;
+use eyre::Context as _;
+use groth16_material::circom::{CircomGroth16Material, CircomGroth16MaterialBuilder};
+use nodes_common::postgres::PostgresConfig;
+use oprf_types::{OprfKeyId, crypto::PartyId};
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use taceo_oprf_key_gen::{
+    ceremony::{
+        self, Ceremony, CeremonyParams, CeremonyTranscript, ImportRound, Round1Record,
+        Round2Record, SignOff,
+    },
+    config::OprfKeyGenServiceConfig,
+    postgres::PostgresDb,
+};
+
+#[derive(Parser, Debug)]
+struct CeremonyCli {
+    #[command(subcommand)]
+    step: Step,
+}
+
+#[derive(Subcommand, Debug)]
+enum Step {
+    /// Runs round 1 and writes the round-1 record of this party
+    Round1 {
+        /// The key to generate (decimal or 0x-prefixed hex)
+        #[arg(long)]
+        oprf_key_id: String,
+        /// The threshold of the key
+        #[arg(long)]
+        threshold: NonZeroU16,
+        /// The number of parties of the ceremony
+        #[arg(long)]
+        num_parties: NonZeroU16,
+        #[command(flatten)]
+        party: PartyArgs,
+        /// The file the record is written to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Runs round 2 on the round-1 records of all parties and writes the round-2 record of this party
+    Round2 {
+        /// The round-1 records of all parties
+        #[arg(long, num_args = 1.., required = true)]
+        round1: Vec<PathBuf>,
+        #[command(flatten)]
+        party: PartyArgs,
+        /// The file the record is written to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Assembles and verifies the transcript from the records of all parties
+    Assemble {
+        /// The round-1 records of all parties
+        #[arg(long, num_args = 1.., required = true)]
+        round1: Vec<PathBuf>,
+        /// The round-2 records of all parties
+        #[arg(long, num_args = 1.., required = true)]
+        round2: Vec<PathBuf>,
+        #[command(flatten)]
+        material: MaterialArgs,
+        /// The file the transcript is written to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Verifies the transcript
+    Verify {
+        /// The transcript
+        #[arg(long)]
+        transcript: PathBuf,
+        #[command(flatten)]
+        material: MaterialArgs,
+    },
+    /// Verifies the transcript and signs it with the wallet of this party
+    SignOff {
+        /// The transcript
+        #[arg(long)]
+        transcript: PathBuf,
+        /// The party id of this node
+        #[arg(long)]
+        party_id: u16,
+        #[command(flatten)]
+        material: MaterialArgs,
+        /// The file the sign-off is written to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Submits the contribution of this party once all parties signed off the transcript
+    Import {
+        /// The transcript
+        #[arg(long)]
+        transcript: PathBuf,
+        /// The sign-offs of all parties
+        #[arg(long, num_args = 1.., required = true)]
+        sign_offs: Vec<PathBuf>,
+        /// The wallet addresses of all parties, sorted by party id
+        #[arg(long, value_delimiter = ',', required = true)]
+        participants: Vec<Address>,
+        /// The party id of this node
+        #[arg(long)]
+        party_id: u16,
+        /// The round to submit (1 or 2)
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=2))]
+        round: u8,
+    },
+}
+
+#[derive(Args, Debug)]
+struct PartyArgs {
+    /// The party id of this node
+    #[arg(long)]
+    party_id: u16,
+    #[command(flatten)]
+    material: MaterialArgs,
+}
+
+#[derive(Args, Debug)]
+struct MaterialArgs {
+    /// The path to the zkey of the key-gen circuit
+    #[arg(long, env = "TACEO_OPRF_KEY_GEN__SERVICE__ZKEY_PATH")]
+    zkey_path: PathBuf,
+    /// The path to the witness graph of the key-gen circuit
+    #[arg(long, env = "TACEO_OPRF_KEY_GEN__SERVICE__WITNESS_GRAPH_PATH")]
+    witness_graph_path: PathBuf,
+}
+
+impl MaterialArgs {
+    fn build(&self) -> eyre::Result<CircomGroth16Material> {
+        CircomGroth16MaterialBuilder::new()
+            .bbf_inv()
+            .bbf_num_2_bits_helper()
+            .build_from_paths(&self.zkey_path, &self.witness_graph_path)
+            .context("while building groth16 material")
+    }
+}
+
+#[derive(Deserialize)]
+struct PostgresOnly {
+    postgres: PostgresConfig,
+}
+
+#[derive(Deserialize)]
+struct WalletOnly {
+    service: WalletConfig,
+}
+
+#[derive(Deserialize)]
+struct WalletConfig {
+    wallet_private_key: SecretString,
+}
+
+#[derive(Deserialize)]
+struct ServiceOnly {
+    service: OprfKeyGenServiceConfig,
+}
+
+fn load_env_config<T: DeserializeOwned>() -> eyre::Result<T> {
+    Config::builder()
+        .add_source(
+            config::Environment::with_prefix("TACEO_OPRF_KEY_GEN")
+                .separator("__")
+                .list_separator(",")
+                .with_list_parse_key("service.rpc.http_urls")
+                .try_parsing(true),
+        )
+        .build()?
+        .try_deserialize()
+        .context("while loading config")
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> eyre::Result<T> {
+    let bytes = std::fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+    serde_json::from_slice(&bytes).with_context(|| format!("cannot parse {}", path.display()))
+}
+
+fn read_all_json<T: DeserializeOwned>(paths: &[PathBuf]) -> eyre::Result<Vec<T>> {
+    paths.iter().map(|path| read_json(path)).collect()
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> eyre::Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(value)?)
+        .with_context(|| format!("cannot write {}", path.display()))?;
+    tracing::info!("wrote {}", path.display());
+    Ok(())
+}
+
+async fn ceremony_party(party: &PartyArgs) -> eyre::Result<Ceremony> {
+    let config = load_env_config::<PostgresOnly>()?;
+    let postgres = PostgresDb::init(&config.postgres)
+        .await
+        .context("while starting postgres secret-manager")?;
+    Ok(Ceremony::new(
+        PartyId(party.party_id),
+        party.material.build()?,
+        Arc::new(postgres),
+    ))
+}
+
+fn verified_transcript(path: &Path, material: &MaterialArgs) -> eyre::Result<CeremonyTranscript> {
+    let transcript = read_json::<CeremonyTranscript>(path)?;
+    transcript.verify(&material.build()?)?;
+    tracing::info!("transcript digest: {}", transcript.digest()?);
+    Ok(transcript)
+}
+
+async fn run(step: Step) -> eyre::Result<()> {
+    match step {
+        Step::Round1 {
+            oprf_key_id,
+            threshold,
+            num_parties,
+            party,
+            out,
+        } => {
+            let oprf_key_id =
+                OprfKeyId::new(U160::from_str(&oprf_key_id).context("while parsing OPRF key id")?);
+            let params = CeremonyParams::new(oprf_key_id, threshold, num_parties)?;
+            let record = ceremony_party(&party).await?.round1(params).await?;
+            write_json(&out, &record)
+        }
+        Step::Round2 { round1, party, out } => {
+            let round1 = read_all_json::<Round1Record>(&round1)?;
+            let record = ceremony_party(&party).await?.round2(&round1).await?;
+            write_json(&out, &record)
+        }
+        Step::Assemble {
+            round1,
+            round2,
+            material,
+            out,
+        } => {
+            let round1 = read_all_json::<Round1Record>(&round1)?;
+            let round2 = read_all_json::<Round2Record>(&round2)?;
+            let transcript = CeremonyTranscript::assemble(&round1, &round2)?;
+            transcript.verify(&material.build()?)?;
+            tracing::info!("transcript digest: {}", transcript.digest()?);
+            write_json(&out, &transcript)
+        }
+        Step::Verify {
+            transcript,
+            material,
+        } => {
+            verified_transcript(&transcript, &material)?;
+            tracing::info!("transcript is valid");
+            Ok(())
+        }
+        Step::SignOff {
+            transcript,
+            party_id,
+            material,
+            out,
+        } => {
+            let transcript = verified_transcript(&transcript, &material)?;
+            let config = load_env_config::<WalletOnly>()?;
+            let signer =
+                PrivateKeySigner::from_str(config.service.wallet_private_key.expose_secret())
+                    .context("while loading wallet private key")?;
+            let sign_off = ceremony::sign_off(&transcript, PartyId(party_id), &signer).await?;
+            write_json(&out, &sign_off)
+        }
+        Step::Import {
+            transcript,
+            sign_offs,
+            participants,
+            party_id,
+            round,
+        } => {
+            let transcript = read_json::<CeremonyTranscript>(&transcript)?;
+            let sign_offs = read_all_json::<SignOff>(&sign_offs)?;
+            let config = load_env_config::<ServiceOnly>()?;
+            let transaction_submitter = ceremony::transaction_submitter(&config.service)?;
+            let round = if round == 1 {
+                ImportRound::Round1
+            } else {
+                ImportRound::Round2
+            };
+            ceremony::import(
+                &transaction_submitter,
+                &transcript,
+                &sign_offs,
+                &participants,
+                PartyId(party_id),
+                round,
+            )
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = CeremonyCli::parse();
+
+    rustls::crypto::aws_lc_rs::default_provider()
+        .install_default()
+        .expect("Can install");
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Can build Tokio runtime");
+    runtime.block_on(async {
+        let _guard = telemetry_batteries::init().expect("Can initialize tracing");
+        match run(cli.step).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                tracing::error!(?err, "ceremony step failed");
+                ExitCode::FAILURE
+            }
+        }
+    })
+}
@@ -0,0 +1,109 @@
+//! OPRF Key Gen Single-Node Binary
+//!
+//! Prepares the Postgres secret manager of a single-node (non-threshold) deployment, see [`taceo_oprf_key_gen::single_node`]. Stores the node information and generates the configured keys, then exits.
+//!
+//! Configured via environment variables using the `TACEO_OPRF_KEY_GEN__` prefix:
+//! - `TACEO_OPRF_KEY_GEN__POSTGRES__*` – the Postgres config, as for the key-gen service.
+//! - `TACEO_OPRF_KEY_GEN__SINGLE_NODE__ADDRESS` – the address reported by the node.
+//! - `TACEO_OPRF_KEY_GEN__SINGLE_NODE__OPRF_KEY_IDS` – comma-separated list of the keys to generate.
+
+use std::{process::ExitCode, str::FromStr as _, sync::Arc};
+
+use alloy::primitives::{Address, U160};
+use config::Config;
+use eyre::Context as _;
+use nodes_common::postgres::PostgresConfig;
+use oprf_types::OprfKeyId;
+use serde::Deserialize;
+use taceo_oprf_key_gen::{postgres::PostgresDb, secret_manager::SecretManagerService, single_node};
+
+/// The single-node settings.
+#[derive(Clone, Debug, Deserialize)]
+struct SingleNodeConfig {
+    /// The address reported by the node.
+    address: Address,
+    /// The keys to generate.
+    oprf_key_ids: Vec<String>,
+}
+
+/// The top-level configuration for the single-node binary.
+#[derive(Clone, Debug, Deserialize)]
+struct SingleNodeKeyGenConfig {
+    /// Postgres config of the secret manager.
+    #[serde(rename = "postgres")]
+    postgres_config: PostgresConfig,
+
+    /// The single-node settings.
+    single_node: SingleNodeConfig,
+}
+
+fn load_config() -> Result<SingleNodeKeyGenConfig, config::ConfigError> {
+    Config::builder()
+        .add_source(
+            config::Environment::with_prefix("TACEO_OPRF_KEY_GEN")
+                .separator("__")
+                .list_separator(",")
+                .with_list_parse_key("single_node.oprf_key_ids"),
+        )
+        .build()?
+        .try_deserialize()
+}
+
+async fn run(config: SingleNodeKeyGenConfig) -> eyre::Result<()> {
+    let oprf_key_ids = config
+        .single_node
+        .oprf_key_ids
+        .iter()
+        .map(|id| U160::from_str(id).map(OprfKeyId::new))
+        .collect::<Result<Vec<_>, _>>()
+        .context("while parsing OPRF key ids")?;
+
+    tracing::info!("connecting to postgres DB...");
+    let postgres = PostgresDb::init(&config.postgres_config)
+        .await
+        .context("while starting postgres secret-manager")?;
+    let secret_manager: SecretManagerService = Arc::new(postgres);
+
+    single_node::init_single_node(&secret_manager, config.single_node.address).await?;
+    let mut rng = rand::thread_rng();
+    for oprf_key_id in oprf_key_ids {
+        single_node::generate_single_node_key(&secret_manager, oprf_key_id, &mut rng)
+            .await
+            .with_context(|| format!("while generating key {oprf_key_id}"))?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let maybe_config = load_config();
+
+    rustls::crypto::aws_lc_rs::default_provider()
+        .install_default()
+        .expect("Can install");
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Can build Tokio runtime");
+    runtime.block_on(async {
+        let _guard = telemetry_batteries::init().expect("Can initialize tracing");
+
+        let config = match maybe_config {
+            Ok(config) => config,
+            Err(err) => {
+                tracing::error!(%err, "failed to load config");
+                return ExitCode::FAILURE;
+            }
+        };
+        match run(config).await {
+            Ok(()) => {
+                tracing::info!("single-node key material ready");
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                tracing::error!(?err, "failed to prepare single-node key material");
+                ExitCode::FAILURE
+            }
+        }
+    })
+}
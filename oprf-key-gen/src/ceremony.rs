@@ -0,0 +1,469 @@
+//! Offline key-generation ceremony.
+//!
+//! Some deployments require a documented, auditable genesis ceremony for their first keys. Instead of reacting to the events of the `OprfKeyRegistry`, the parties run round 1 and round 2 of the key generation offline and exchange their contributions as files:
+//!
+//! 1. Every party runs [`Ceremony::round1`] and publishes the resulting [`Round1Record`].
+//! 2. Every party runs [`Ceremony::round2`] on the round-1 records of all parties and publishes the resulting [`Round2Record`].
+//! 3. The records are assembled into a [`CeremonyTranscript`], which every party checks with [`CeremonyTranscript::verify`] (including the Groth16 proofs) and signs with [`sign_off`].
+//! 4. Once [`verify_sign_offs`] accepts the sign-offs of all parties, every party submits its own contributions with [`import`]: round 1 after the key-gen was initiated on-chain, round 2 once the registry advanced to round 2.
+//!
+//! The intermediate values of the ceremony are stored in the [`SecretManager`](crate::secret_manager::SecretManager) like during an online key generation, so the key-gen service finishes round 3 and confirms the share once it is started. The service must not run for the key before the round-2 contribution of the party was imported, otherwise it computes and submits fresh round-2 ciphertexts that differ from the signed transcript.
+//!
+//! The `taceo-oprf-key-gen-ceremony` binary wraps the steps for the operators of a ceremony.
+
+use std::{num::NonZeroU16, str::FromStr as _, sync::Arc};
+
+use alloy::{
+    network::EthereumWallet,
+    primitives::{Address, B256, Signature, TxHash, keccak256},
+    signers::{Signer as _, local::PrivateKeySigner},
+};
+use eyre::{Context as _, ContextCompat as _};
+use groth16_material::circom::CircomGroth16Material;
+use itertools::Itertools as _;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    chain::OprfKeyGen::{Round1Contribution, Round2Contribution},
+    crypto::{EphemeralEncryptionPublicKey, PartyId, SecretGenCiphertexts, SecretGenCommitment},
+};
+use secrecy::ExposeSecret as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::OprfKeyGenServiceConfig,
+    secret_manager::SecretManagerService,
+    services::secret_gen::DLogSecretGenService,
+    transaction_handler::{
+        TransactionHandler, TransactionHandlerArgs, TransactionSubmitterService,
+    },
+};
+
+/// The parameters of a key generation, shared by all records of a ceremony.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CeremonyParams {
+    /// The key that is generated.
+    pub oprf_key_id: OprfKeyId,
+    /// The threshold of the key.
+    pub threshold: NonZeroU16,
+    /// The number of parties of the ceremony.
+    pub num_parties: NonZeroU16,
+}
+
+impl CeremonyParams {
+    /// Creates new [`CeremonyParams`].
+    ///
+    /// # Errors
+    /// Returns an error if the threshold exceeds the number of parties.
+    pub fn new(
+        oprf_key_id: OprfKeyId,
+        threshold: NonZeroU16,
+        num_parties: NonZeroU16,
+    ) -> eyre::Result<Self> {
+        if threshold > num_parties {
+            eyre::bail!("threshold {threshold} exceeds number of parties {num_parties}");
+        }
+        Ok(Self {
+            oprf_key_id,
+            threshold,
+            num_parties,
+        })
+    }
+
+    fn num_parties(&self) -> usize {
+        usize::from(self.num_parties.get())
+    }
+}
+
+/// The round-1 contribution of a party, written to a file instead of the chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Round1Record {
+    /// The parameters of the ceremony.
+    pub params: CeremonyParams,
+    /// The party that created the record.
+    pub party_id: PartyId,
+    /// The commitments of the party.
+    pub commitment: SecretGenCommitment,
+}
+
+/// The round-2 contribution of a party, written to a file instead of the chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Round2Record {
+    /// The parameters of the ceremony.
+    pub params: CeremonyParams,
+    /// The party that created the record.
+    pub party_id: PartyId,
+    /// The ciphertexts for all parties and the proof of the party.
+    pub ciphertexts: SecretGenCiphertexts,
+}
+
+/// All contributions of a ceremony, sorted by party id. This is what the parties sign off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CeremonyTranscript {
+    /// The parameters of the ceremony.
+    pub params: CeremonyParams,
+    /// The round-1 commitments of all parties.
+    pub round1: Vec<SecretGenCommitment>,
+    /// The round-2 ciphertexts of all parties.
+    pub round2: Vec<SecretGenCiphertexts>,
+}
+
+/// The signature of a party over the digest of a [`CeremonyTranscript`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SignOff {
+    /// The party that signed.
+    pub party_id: PartyId,
+    /// The wallet address of the party.
+    pub signer: Address,
+    /// The signature over [`CeremonyTranscript::digest`].
+    pub signature: Signature,
+}
+
+/// Which contribution [`import`] submits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ImportRound {
+    /// The round-1 commitment.
+    Round1,
+    /// The round-2 ciphertexts.
+    Round2,
+}
+
+/// A party of an offline key-generation ceremony.
+pub struct Ceremony {
+    party_id: PartyId,
+    secret_gen: DLogSecretGenService,
+}
+
+impl Ceremony {
+    /// Creates the ceremony party with the given [`PartyId`]. The intermediate values are stored in the `secret_manager`.
+    #[must_use]
+    pub fn new(
+        party_id: PartyId,
+        key_gen_material: CircomGroth16Material,
+        secret_manager: SecretManagerService,
+    ) -> Self {
+        Self {
+            party_id,
+            secret_gen: DLogSecretGenService::init(key_gen_material, secret_manager),
+        }
+    }
+
+    /// Executes round 1 of the key generation.
+    ///
+    /// Idempotent: if the party ran round 1 for the key already, the existing commitment is returned.
+    ///
+    /// # Errors
+    /// Returns an error if the secret manager cannot store the intermediate values.
+    pub async fn round1(&self, params: CeremonyParams) -> eyre::Result<Round1Record> {
+        let commitment = self
+            .secret_gen
+            .key_gen_round1_commitment(params.oprf_key_id, ShareEpoch::default(), params.threshold)
+            .await
+            .context("while computing round 1")?;
+        Ok(Round1Record {
+            params,
+            party_id: self.party_id,
+            commitment,
+        })
+    }
+
+    /// Executes round 2 of the key generation on the round-1 records of all parties (including this party).
+    ///
+    /// # Errors
+    /// Returns an error if the records are inconsistent, if the own commitment does not match the stored intermediate values, or if the proof cannot be computed.
+    pub async fn round2(&self, round1: &[Round1Record]) -> eyre::Result<Round2Record> {
+        let params = round1.first().context("no round-1 records")?.params;
+        let commitments = sorted_by_party(
+            params,
+            round1
+                .iter()
+                .map(|r| (r.params, r.party_id, r.commitment.clone())),
+        )
+        .context("while checking round-1 records")?;
+        let own = self
+            .secret_gen
+            .key_gen_round1_commitment(params.oprf_key_id, ShareEpoch::default(), params.threshold)
+            .await
+            .context("while loading own round-1 commitment")?;
+        let recorded = commitments
+            .get(usize::from(self.party_id.0))
+            .with_context(|| format!("{} is not part of the ceremony", self.party_id))?;
+        if !same_commitment(&own, recorded) {
+            eyre::bail!(
+                "round-1 record of {} does not match own commitment",
+                self.party_id
+            );
+        }
+        let pks = commitments.iter().map(|c| c.eph_pub_key).collect();
+        let ciphertexts = self
+            .secret_gen
+            .producer_round2(params.oprf_key_id, ShareEpoch::default(), pks)
+            .await
+            .context("while computing round 2")?;
+        Ok(Round2Record {
+            params,
+            party_id: self.party_id,
+            ciphertexts,
+        })
+    }
+
+    /// The Groth16 material of the key-gen circuit, see [`CeremonyTranscript::verify`].
+    #[must_use]
+    pub fn key_gen_material(&self) -> &CircomGroth16Material {
+        self.secret_gen.key_gen_material()
+    }
+}
+
+impl CeremonyTranscript {
+    /// Assembles the round-1 and round-2 records of all parties.
+    ///
+    /// # Errors
+    /// Returns an error if the parameters of the records differ, or if not every party contributed exactly one record per round.
+    pub fn assemble(round1: &[Round1Record], round2: &[Round2Record]) -> eyre::Result<Self> {
+        let params = round1.first().context("no round-1 records")?.params;
+        let round1 = sorted_by_party(
+            params,
+            round1
+                .iter()
+                .map(|r| (r.params, r.party_id, r.commitment.clone())),
+        )
+        .context("while checking round-1 records")?;
+        let round2 = sorted_by_party(
+            params,
+            round2
+                .iter()
+                .map(|r| (r.params, r.party_id, r.ciphertexts.clone())),
+        )
+        .context("while checking round-2 records")?;
+        Ok(Self {
+            params,
+            round1,
+            round2,
+        })
+    }
+
+    /// The Keccak-256 digest of the JSON encoding of the transcript, signed by the parties.
+    ///
+    /// # Errors
+    /// Returns an error if the transcript cannot be encoded.
+    pub fn digest(&self) -> eyre::Result<B256> {
+        let encoded = serde_json::to_vec(self).context("while encoding transcript")?;
+        Ok(keccak256(encoded))
+    }
+
+    /// Verifies the transcript: every party contributed to both rounds, encrypted a share for every party, and the Groth16 proof of every party verifies against its round-1 commitment.
+    ///
+    /// # Errors
+    /// Returns an error describing the first contribution that is invalid.
+    pub fn verify(&self, key_gen_material: &CircomGroth16Material) -> eyre::Result<()> {
+        let num_parties = self.params.num_parties();
+        if self.round1.len() != num_parties || self.round2.len() != num_parties {
+            eyre::bail!("expected contributions of {num_parties} parties");
+        }
+        let pks = self.round1.iter().map(|c| c.eph_pub_key).collect_vec();
+        let degree = self.params.threshold.get() - 1;
+        for (party_id, (commitment, ciphertexts)) in
+            self.round1.iter().zip(&self.round2).enumerate()
+        {
+            if ciphertexts.ciphers.len() != num_parties {
+                eyre::bail!("party {party_id} did not encrypt a share for every party");
+            }
+            let public_inputs = keygen_public_inputs(degree, commitment, &pks, ciphertexts);
+            key_gen_material
+                .verify_proof(&ciphertexts.proof.clone().into(), &public_inputs)
+                .with_context(|| format!("invalid round-2 proof of party {party_id}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Signs the digest of the [`CeremonyTranscript`] as the given party.
+///
+/// # Errors
+/// Returns an error if the transcript cannot be encoded or signed.
+pub async fn sign_off(
+    transcript: &CeremonyTranscript,
+    party_id: PartyId,
+    signer: &PrivateKeySigner,
+) -> eyre::Result<SignOff> {
+    let signature = signer
+        .sign_hash(&transcript.digest()?)
+        .await
+        .context("while signing transcript")?;
+    Ok(SignOff {
+        party_id,
+        signer: signer.address(),
+        signature,
+    })
+}
+
+/// Verifies that every party signed off the [`CeremonyTranscript`] with its wallet.
+///
+/// `participants` are the wallet addresses of the parties, sorted by party id (as registered in the `OprfKeyRegistry`).
+///
+/// # Errors
+/// Returns an error if a sign-off is missing, signed by the wrong wallet, or signed a different transcript.
+pub fn verify_sign_offs(
+    transcript: &CeremonyTranscript,
+    sign_offs: &[SignOff],
+    participants: &[Address],
+) -> eyre::Result<()> {
+    if participants.len() != transcript.params.num_parties() {
+        eyre::bail!(
+            "expected {} participants, got {}",
+            transcript.params.num_parties,
+            participants.len()
+        );
+    }
+    let digest = transcript.digest()?;
+    for (party_id, participant) in participants.iter().enumerate() {
+        let sign_off = sign_offs
+            .iter()
+            .find(|s| usize::from(s.party_id.0) == party_id)
+            .with_context(|| format!("missing sign-off of party {party_id}"))?;
+        let recovered = sign_off
+            .signature
+            .recover_address_from_prehash(&digest)
+            .with_context(|| format!("invalid signature of party {party_id}"))?;
+        if recovered != *participant || recovered != sign_off.signer {
+            eyre::bail!("sign-off of party {party_id} is not signed by {participant}");
+        }
+    }
+    Ok(())
+}
+
+/// Submits the contribution of the party from the signed [`CeremonyTranscript`] to the `OprfKeyRegistry`, after checking the sign-offs with [`verify_sign_offs`].
+///
+/// # Errors
+/// Returns an error if the sign-offs do not verify or if the transaction fails.
+pub async fn import(
+    transaction_submitter: &TransactionSubmitterService,
+    transcript: &CeremonyTranscript,
+    sign_offs: &[SignOff],
+    participants: &[Address],
+    party_id: PartyId,
+    round: ImportRound,
+) -> eyre::Result<TxHash> {
+    verify_sign_offs(transcript, sign_offs, participants)?;
+    let index = usize::from(party_id.0);
+    let oprf_key_id = transcript.params.oprf_key_id;
+    let tx_hash = match round {
+        ImportRound::Round1 => {
+            let commitment = transcript
+                .round1
+                .get(index)
+                .with_context(|| format!("no round-1 contribution of {party_id}"))?;
+            transaction_submitter
+                .add_round1_keygen_contribution(
+                    oprf_key_id,
+                    Round1Contribution::from(commitment.clone()),
+                )
+                .await
+        }
+        ImportRound::Round2 => {
+            let ciphertexts = transcript
+                .round2
+                .get(index)
+                .with_context(|| format!("no round-2 contribution of {party_id}"))?;
+            transaction_submitter
+                .add_round2_contribution(oprf_key_id, Round2Contribution::from(ciphertexts.clone()))
+                .await
+        }
+    }
+    .context("while submitting contribution")?;
+    tracing::info!("imported {round:?} contribution of {party_id} for {oprf_key_id}: {tx_hash}");
+    Ok(tx_hash)
+}
+
+/// Creates the default transaction submitter of the key-gen service from its config, for [`import`].
+///
+/// # Errors
+/// Returns an error if the wallet private key cannot be parsed or the RPC provider cannot be initialized.
+pub fn transaction_submitter(
+    config: &OprfKeyGenServiceConfig,
+) -> eyre::Result<TransactionSubmitterService> {
+    let private_key = PrivateKeySigner::from_str(config.wallet_private_key.expose_secret())
+        .context("while loading wallet private key")?;
+    let wallet_address = private_key.address();
+    let rpc_provider =
+        nodes_common::web3::HttpRpcProviderBuilder::with_config(&config.rpc_provider_config)
+            .environment(config.environment)
+            .wallet(EthereumWallet::from(private_key))
+            .build()
+            .context("while init blockchain connection")?;
+    Ok(Arc::new(TransactionHandler::new(
+        TransactionHandlerArgs::from_config(config, rpc_provider, wallet_address),
+    )))
+}
+
+/// Checks that all records belong to the ceremony and every party contributed exactly once. Returns the contributions sorted by party id.
+fn sorted_by_party<T>(
+    params: CeremonyParams,
+    records: impl Iterator<Item = (CeremonyParams, PartyId, T)>,
+) -> eyre::Result<Vec<T>> {
+    let mut slots = (0..params.num_parties()).map(|_| None).collect_vec();
+    for (record_params, party_id, contribution) in records {
+        if record_params != params {
+            eyre::bail!("record of {party_id} belongs to a different ceremony");
+        }
+        let slot = slots
+            .get_mut(usize::from(party_id.0))
+            .with_context(|| format!("unexpected {party_id}"))?;
+        if slot.replace(contribution).is_some() {
+            eyre::bail!("duplicate record of {party_id}");
+        }
+    }
+    slots
+        .into_iter()
+        .enumerate()
+        .map(|(party_id, slot)| slot.with_context(|| format!("missing record of party {party_id}")))
+        .collect()
+}
+
+fn same_commitment(a: &SecretGenCommitment, b: &SecretGenCommitment) -> bool {
+    a.comm_share == b.comm_share && a.comm_coeffs == b.comm_coeffs && a.eph_pub_key == b.eph_pub_key
+}
+
+/// Builds the public inputs of the key-gen circuit:
+/// 1) the public key of the sender,
+/// 2) the commitment to the share,
+/// 3) the commitment to the coefficients,
+/// 4) one ciphertext per party,
+/// 5) one commitment to the plaintext per party,
+/// 6) the degree,
+/// 7) the public keys of all parties,
+/// 8) one nonce per party.
+fn keygen_public_inputs(
+    degree: u16,
+    commitment: &SecretGenCommitment,
+    pks: &[EphemeralEncryptionPublicKey],
+    ciphertexts: &SecretGenCiphertexts,
+) -> Vec<ark_babyjubjub::Fq> {
+    let sender = commitment.eph_pub_key.inner();
+    let mut public_inputs = vec![
+        sender.x,
+        sender.y,
+        commitment.comm_share.x,
+        commitment.comm_share.y,
+        commitment.comm_coeffs,
+    ];
+    public_inputs.extend(ciphertexts.ciphers.iter().map(|c| c.cipher));
+    public_inputs.extend(
+        ciphertexts
+            .ciphers
+            .iter()
+            .flat_map(|c| [c.commitment.x, c.commitment.y]),
+    );
+    public_inputs.push(ark_babyjubjub::Fq::from(degree));
+    public_inputs.extend(pks.iter().flat_map(|pk| [pk.inner().x, pk.inner().y]));
+    public_inputs.extend(ciphertexts.ciphers.iter().map(|c| c.nonce));
+    public_inputs
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use alloy::primitives::U160;
+use groth16_material::circom::{CircomGroth16MaterialBuilder, Validate};
+use rand::Rng as _;
+
+use super::*;
+use crate::postgres;
+
+fn key_gen_material() -> eyre::Result<CircomGroth16Material> {
+    let artifacts = PathBuf::from(std::env!("CARGO_MANIFEST_DIR")).join("../artifacts");
+    let graph = std::fs::read(artifacts.join("OPRFKeyGenGraph.13.bin"))?;
+    let zkey = std::fs::read(artifacts.join("OPRFKeyGen.13.arks.zkey"))?;
+    Ok(CircomGroth16MaterialBuilder::new()
+        .validate(Validate::No)
+        .bbf_inv()
+        .bbf_num_2_bits_helper()
+        .build_from_bytes(&zkey, &graph)?)
+}
+
+fn params(num_parties: u16) -> CeremonyParams {
+    CeremonyParams::new(
+        OprfKeyId::new(U160::from(42)),
+        NonZeroU16::new(2).expect("non-zero"),
+        NonZeroU16::new(num_parties).expect("non-zero"),
+    )
+    .expect("valid params")
+}
+
+fn empty_transcript() -> CeremonyTranscript {
+    CeremonyTranscript {
+        params: params(2),
+        round1: vec![],
+        round2: vec![],
+    }
+}
+
+#[tokio::test]
+async fn sign_offs_of_all_participants_required() -> eyre::Result<()> {
+    let transcript = empty_transcript();
+    let signers = [PrivateKeySigner::random(), PrivateKeySigner::random()];
+    let participants = signers.iter().map(PrivateKeySigner::address).collect_vec();
+    let sign_off0 = sign_off(&transcript, PartyId(0), &signers[0]).await?;
+    let sign_off1 = sign_off(&transcript, PartyId(1), &signers[1]).await?;
+
+    verify_sign_offs(
+        &transcript,
+        &[sign_off1.clone(), sign_off0.clone()],
+        &participants,
+    )?;
+    assert!(
+        verify_sign_offs(&transcript, std::slice::from_ref(&sign_off0), &participants).is_err(),
+        "missing sign-off must be rejected"
+    );
+
+    let wrong_party = sign_off(&transcript, PartyId(1), &signers[0]).await?;
+    assert!(
+        verify_sign_offs(
+            &transcript,
+            &[sign_off0.clone(), wrong_party],
+            &participants
+        )
+        .is_err(),
+        "sign-off by the wrong wallet must be rejected"
+    );
+
+    let mut other = empty_transcript();
+    other.params.threshold = NonZeroU16::MIN;
+    assert!(
+        verify_sign_offs(&other, &[sign_off0, sign_off1], &participants).is_err(),
+        "sign-offs of a different transcript must be rejected"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn offline_ceremony() -> eyre::Result<()> {
+    let key_gen_material = key_gen_material()?;
+    let connection_string = nodes_common::test_utils::shared_postgres_testcontainer().await?;
+    let mut parties = Vec::new();
+    for party_id in 0..3 {
+        let secret_manager = Arc::new(
+            postgres::tests::postgres_secret_manager_with_schema(
+                connection_string,
+                nodes_common::test_utils::next_test_schema(),
+            )
+            .await?,
+        );
+        parties.push(Ceremony::new(
+            PartyId(party_id),
+            key_gen_material.clone(),
+            secret_manager,
+        ));
+    }
+    let params = params(3);
+
+    let mut round1 = Vec::new();
+    for party in &parties {
+        round1.push(party.round1(params).await?);
+    }
+    // round 1 is idempotent
+    let again = parties[0].round1(params).await?;
+    assert!(
+        same_commitment(&again.commitment, &round1[0].commitment),
+        "round 1 must return the stored commitment"
+    );
+
+    let mut round2 = Vec::new();
+    for party in &parties {
+        round2.push(party.round2(&round1).await?);
+    }
+    let transcript = CeremonyTranscript::assemble(&round1, &round2)?;
+    transcript.verify(&key_gen_material)?;
+
+    let mut tampered = transcript.clone();
+    tampered.round2[1].ciphers[0].cipher =
+        ark_babyjubjub::Fq::from(rand::thread_rng().r#gen::<u64>());
+    assert!(
+        tampered.verify(&key_gen_material).is_err(),
+        "tampered ciphertext must be rejected"
+    );
+    assert!(
+        CeremonyTranscript::assemble(&round1[..2], &round2).is_err(),
+        "missing round-1 record must be rejected"
+    );
+    Ok(())
+}
@@ -21,9 +21,22 @@
 //! | `max_wait_time_transaction_confirmation` | 300 s       |
 //! | `max_gas_per_transaction`                | 8 000 000   |
 //! | `confirmations_for_transaction`          | 5           |
+//! | `max_fee_bumps_per_transaction`          | 3           |
+//! | `fee_bump_percent`                       | 20          |
+//! | `max_fee_per_gas_cap_gwei`               | 500         |
 //! | `max_tries_fetching_receipt`             | 5           |
 //! | `sleep_between_get_receipt`              | 5 s         |
 //! | `cursor_checkpoint_interval`             | 1 day       |
+//! | `protocol_timeout`                       | 1 day       |
+//! | `max_resubscribe_attempts`               | 5           |
+//! | `resubscribe_backoff`                    | 1 s         |
+//! | `max_resubscribe_backoff`                | 1 min       |
+//! | `metrics_endpoint`                       | `false`     |
+//! | `admin_endpoints`                        | `false`     |
+//! | `root_path`                              | none        |
+//! | `orphaned_keys`                          | `off`       |
+//! | `orphaned_keys_dry_run`                  | `false`     |
+//! | `additional_oprf_key_registry_contracts` | `[]`        |
 
 use std::num::NonZeroU16;
 use std::{path::PathBuf, time::Duration};
@@ -38,6 +51,22 @@ use nodes_common::{
 use secrecy::SecretString;
 use serde::Deserialize;
 
+/// What the key-gen does at startup with stored shares of keys the `OprfKeyRegistry` no longer knows, see [`OprfKeyGenServiceConfig::orphaned_keys`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum OrphanedKeyAction {
+    /// The stored keys are not compared with the registry.
+    #[default]
+    Off,
+    /// Orphaned keys are logged and counted, but kept.
+    Report,
+    /// The shares of orphaned keys are moved out of use, but kept in the secret manager so that an operator can restore them (see [`crate::secret_manager::SecretManager::quarantine_oprf_key_material`]).
+    Quarantine,
+    /// The shares of orphaned keys are deleted, like on a `KeyDeletion` event.
+    Delete,
+}
+
 /// The configuration for TACEO:OPRF key-gen functionality.
 #[derive(Debug, Clone, Deserialize)]
 #[non_exhaustive]
@@ -51,6 +80,14 @@ pub struct OprfKeyGenServiceConfig {
     /// The Address of the `OprfKeyRegistry` contract.
     pub oprf_key_registry_contract: Address,
 
+    /// Addresses of further `OprfKeyRegistry` contracts the key-gen takes part in, e.g. one per environment or customer.
+    ///
+    /// Every registry gets its own key event watcher. As the key ids of different registries may overlap, every registry must have its own secret manager and chain cursor store, see [`crate::OprfKeyGenBuilder::registry_storage`]. All registries must use the `expected_num_peers` and `expected_threshold` of the key-gen circuit, but may assign different party ids to the node. The node information of every registry, including its party id, is stored in the secret manager of the registry, from which the OPRF node serves the keys of the registry with this party id (see `OprfServiceBuilder::registry_module` of the node).
+    ///
+    /// Defaults to no further registries.
+    #[serde(default)]
+    pub additional_oprf_key_registry_contracts: Vec<Address>,
+
     /// The location of the zkey for the key-gen proof in round 2 of `KeyGen`
     pub zkey_path: PathBuf,
 
@@ -99,6 +136,24 @@ pub struct OprfKeyGenServiceConfig {
     #[serde(default = "OprfKeyGenServiceConfig::default_confirmations_for_transaction")]
     pub confirmations_for_transaction: u64,
 
+    /// Maximum number of times a transaction that is not mined within `max_wait_time_transaction_confirmation` is replaced by a transaction with the same nonce and escalated fees.
+    ///
+    /// Set to `0` to only wait for the first transaction. Defaults to `3`.
+    #[serde(default = "OprfKeyGenServiceConfig::default_max_fee_bumps_per_transaction")]
+    pub max_fee_bumps_per_transaction: usize,
+
+    /// Increase of `max_fee_per_gas` and `max_priority_fee_per_gas` per replacement, in percent.
+    ///
+    /// Most nodes reject replacements that increase the fees by less than 10 percent. Defaults to `20`.
+    #[serde(default = "OprfKeyGenServiceConfig::default_fee_bump_percent")]
+    pub fee_bump_percent: u64,
+
+    /// Upper bound of `max_fee_per_gas` of every transaction, in gwei.
+    ///
+    /// Estimated fees above the cap are lowered to the cap, and replacements stop once the cap is reached. Defaults to `500`.
+    #[serde(default = "OprfKeyGenServiceConfig::default_max_fee_per_gas_cap_gwei")]
+    pub max_fee_per_gas_cap_gwei: u64,
+
     /// Number of times we try to fetch the receipt after a confirmed transaction with `eth_getTransactionReceipt`.
     ///
     /// Defaults to `5`.
@@ -122,6 +177,73 @@ pub struct OprfKeyGenServiceConfig {
     #[serde(default = "OprfKeyGenServiceConfig::default_cursor_checkpoint_interval")]
     #[serde(with = "humantime_serde")]
     pub cursor_checkpoint_interval: Duration,
+
+    /// Max time a key-gen/reshare run may take from round 1 until finalize.
+    ///
+    /// If a run did not finish within this period, its intermediates are evicted and the run is abandoned. An abandoned run cannot be finished anymore and must be aborted on-chain (see [`crate::abort_notifier`]). Runs that were in progress during a restart are tracked again from the next event of the run. Must be longer than the time the committee needs for a run, including waiting for confirmations.
+    ///
+    /// Defaults to `1 day`.
+    #[serde(default = "OprfKeyGenServiceConfig::default_protocol_timeout")]
+    #[serde(with = "humantime_serde")]
+    pub protocol_timeout: Duration,
+
+    /// Max number of consecutive attempts to resubscribe to the events of a registry after the event stream was lost, e.g., because the WebSocket connection dropped.
+    ///
+    /// Every attempt connects a new WebSocket provider and backfills the missed events with `eth_getLogs` from the last handled event. A successful attempt resets the count. Once the attempts are exhausted, the key event watcher stops with [`ExitReason::EventStreamClosed`](crate::ExitReason::EventStreamClosed) and cancels the service. Set to `0` to stop on the first lost event stream.
+    ///
+    /// Defaults to `5`.
+    #[serde(default = "OprfKeyGenServiceConfig::default_max_resubscribe_attempts")]
+    pub max_resubscribe_attempts: usize,
+
+    /// Wait before the first attempt to resubscribe, doubled with every further consecutive attempt up to `max_resubscribe_backoff`.
+    ///
+    /// Defaults to `1 s`.
+    #[serde(default = "OprfKeyGenServiceConfig::default_resubscribe_backoff")]
+    #[serde(with = "humantime_serde")]
+    pub resubscribe_backoff: Duration,
+
+    /// Upper bound of the wait between two attempts to resubscribe.
+    ///
+    /// Defaults to `1 min`.
+    #[serde(default = "OprfKeyGenServiceConfig::default_max_resubscribe_backoff")]
+    #[serde(with = "humantime_serde")]
+    pub max_resubscribe_backoff: Duration,
+
+    /// Whether the key-gen installs a Prometheus recorder and serves the recorded metrics at `/metrics`, see [`crate::metrics::exporter`].
+    ///
+    /// The recorder replaces the global `metrics` recorder, so keep the metrics backend of `telemetry-batteries` disabled when enabling this.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub metrics_endpoint: bool,
+
+    /// Whether the key-gen serves the admin endpoints `/keygen/active` and `DELETE /keygen/{oprf_key_id}` to list and abort the in-progress key-gen/reshare runs, see `crate::api::admin`.
+    ///
+    /// The endpoints are not authenticated, so they must only be reachable from the internal network of the operator.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub admin_endpoints: bool,
+
+    /// Path prefix of all routes of the key-gen, e.g. `/oprf-key-gen` if a reverse proxy mounts the key-gen at `/oprf-key-gen/` without stripping the prefix.
+    ///
+    /// Must start with `/` and must not end with `/`. Defaults to `None` (routes are served at the root).
+    #[serde(default)]
+    pub root_path: Option<String>,
+
+    /// What the key-gen does at startup with stored shares of keys that the `OprfKeyRegistry` reports as deleted or unknown, e.g. because the `KeyDeletion` event was missed.
+    ///
+    /// The stored keys of every registry are compared with the registry once, after the node information was stored. Keys that cannot be compared are counted as failed and kept. As a misconfigured registry address reports every key as unknown, run with [`OrphanedKeyAction::Report`] or `orphaned_keys_dry_run` first.
+    ///
+    /// Defaults to [`OrphanedKeyAction::Off`].
+    #[serde(default)]
+    pub orphaned_keys: OrphanedKeyAction,
+
+    /// Whether the reconciliation of `orphaned_keys` only logs and counts what it would quarantine or delete, without changing the secret manager.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub orphaned_keys_dry_run: bool,
 }
 
 /// Subset of [`OprfKeyGenServiceConfig`] containing all values that must be
@@ -185,6 +307,21 @@ impl OprfKeyGenServiceConfig {
         5
     }
 
+    /// Default max fee bumps per transaction (`3`).
+    fn default_max_fee_bumps_per_transaction() -> usize {
+        3
+    }
+
+    /// Default fee bump (`20` percent).
+    fn default_fee_bump_percent() -> u64 {
+        20
+    }
+
+    /// Default cap of the max fee per gas (`500` gwei).
+    fn default_max_fee_per_gas_cap_gwei() -> u64 {
+        500
+    }
+
     /// Default max tries for fetching receipt after confirmed transaction (`5`).
     fn default_max_tries_fetching_receipt() -> usize {
         5
@@ -200,6 +337,26 @@ impl OprfKeyGenServiceConfig {
         Duration::from_hours(24)
     }
 
+    /// Default protocol timeout (`1 day`).
+    fn default_protocol_timeout() -> Duration {
+        Duration::from_hours(24)
+    }
+
+    /// Default max resubscribe attempts (`5`).
+    fn default_max_resubscribe_attempts() -> usize {
+        5
+    }
+
+    /// Default resubscribe backoff (`1 s`).
+    fn default_resubscribe_backoff() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Default max resubscribe backoff (`1 min`).
+    fn default_max_resubscribe_backoff() -> Duration {
+        Duration::from_mins(1)
+    }
+
     /// Construct with all default values except required fields.
     #[must_use]
     pub fn with_default_values(args: OprfKeyGenServiceConfigMandatoryValues) -> Self {
@@ -217,6 +374,7 @@ impl OprfKeyGenServiceConfig {
         Self {
             environment,
             oprf_key_registry_contract,
+            additional_oprf_key_registry_contracts: Vec::new(),
             wallet_private_key,
             ws_rpc_url,
             zkey_path,
@@ -228,10 +386,22 @@ impl OprfKeyGenServiceConfig {
                 Self::default_max_wait_time_transaction_confirmation(),
             max_gas_per_transaction: Self::default_max_gas_per_transaction(),
             confirmations_for_transaction: Self::default_confirmations_for_transaction(),
+            max_fee_bumps_per_transaction: Self::default_max_fee_bumps_per_transaction(),
+            fee_bump_percent: Self::default_fee_bump_percent(),
+            max_fee_per_gas_cap_gwei: Self::default_max_fee_per_gas_cap_gwei(),
             max_tries_fetching_receipt: Self::default_max_tries_fetching_receipt(),
             sleep_between_get_receipt: Self::default_sleep_between_get_receipt(),
             event_stream_config: EventStreamConfig::default(),
             cursor_checkpoint_interval: Self::default_cursor_checkpoint_interval(),
+            protocol_timeout: Self::default_protocol_timeout(),
+            max_resubscribe_attempts: Self::default_max_resubscribe_attempts(),
+            resubscribe_backoff: Self::default_resubscribe_backoff(),
+            max_resubscribe_backoff: Self::default_max_resubscribe_backoff(),
+            metrics_endpoint: false,
+            admin_endpoints: false,
+            root_path: None,
+            orphaned_keys: OrphanedKeyAction::Off,
+            orphaned_keys_dry_run: false,
         }
     }
 }
@@ -0,0 +1,231 @@
+use std::{collections::HashMap, num::NonZeroU16, sync::Arc};
+
+use crate::gcp::{GcpConfig, GcpSecretManager};
+use crate::secret_manager::{SecretManager as _, SecretManagerError};
+use alloy::primitives::U160;
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    crypto::{OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use parking_lot::Mutex;
+use secrecy::ExposeSecret as _;
+
+/// The latest (base64 encoded) version of every secret in the mocked project.
+type Secrets = Arc<Mutex<HashMap<String, Option<String>>>>;
+
+/// Minimal mock of the metadata server and the Secret Manager REST API.
+fn mock_router(secrets: Secrets) -> Router {
+    Router::new()
+        .route(
+            "/computeMetadata/v1/instance/service-accounts/default/token",
+            get(|| async {
+                Json(serde_json::json!({ "access_token": "token", "expires_in": 3600 }))
+            }),
+        )
+        .route(
+            "/v1/projects/{project}/secrets",
+            post(
+                |State(secrets): State<Secrets>,
+                 Query(query): Query<HashMap<String, String>>| async move {
+                    let mut secrets = secrets.lock();
+                    let id = query["secretId"].clone();
+                    if secrets.contains_key(&id) {
+                        return StatusCode::CONFLICT;
+                    }
+                    secrets.insert(id, None);
+                    StatusCode::OK
+                },
+            ),
+        )
+        .route(
+            "/v1/projects/{project}/secrets/{secret}",
+            post(
+                |State(secrets): State<Secrets>,
+                 Path((_, secret)): Path<(String, String)>,
+                 Json(body): Json<serde_json::Value>| async move {
+                    let id = secret.strip_suffix(":addVersion").expect("only addVersion");
+                    let mut secrets = secrets.lock();
+                    let Some(latest) = secrets.get_mut(id) else {
+                        return StatusCode::NOT_FOUND;
+                    };
+                    *latest = Some(body["payload"]["data"].as_str().expect("data").to_owned());
+                    StatusCode::OK
+                },
+            )
+            .delete(
+                |State(secrets): State<Secrets>,
+                 Path((_, secret)): Path<(String, String)>| async move {
+                    match secrets.lock().remove(&secret) {
+                        Some(_) => StatusCode::OK,
+                        None => StatusCode::NOT_FOUND,
+                    }
+                },
+            ),
+        )
+        .route(
+            "/v1/projects/{project}/secrets/{secret}/versions/latest:access",
+            get(
+                |State(secrets): State<Secrets>,
+                 Path((_, secret)): Path<(String, String)>| async move {
+                    match secrets.lock().get(&secret).cloned().flatten() {
+                        Some(data) => Ok(Json(serde_json::json!({ "payload": { "data": data } }))),
+                        None => Err(StatusCode::NOT_FOUND),
+                    }
+                },
+            ),
+        )
+        .with_state(secrets)
+}
+
+async fn gcp_secret_manager() -> eyre::Result<(GcpSecretManager, Secrets)> {
+    let secrets = Secrets::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(axum::serve(listener, mock_router(Arc::clone(&secrets))).into_future());
+    let mut config = GcpConfig::with_default_values("test-project".to_owned());
+    config.endpoint = format!("http://{addr}");
+    config.metadata_endpoint = format!("http://{addr}");
+    config.wallet_private_key_secret = Some("wallet".to_owned());
+    Ok((GcpSecretManager::init(config).await?, secrets))
+}
+
+fn read_secret(secrets: &Secrets, id: &str) -> Option<serde_json::Value> {
+    let data = secrets.lock().get(id).cloned().flatten()?;
+    Some(serde_json::from_slice(&BASE64.decode(data).expect("base64")).expect("json"))
+}
+
+/// Stages a pending share for `epoch` like a finished key-gen would.
+async fn stage_pending_share(
+    secret_manager: &GcpSecretManager,
+    secrets: &Secrets,
+    oprf_key_id: OprfKeyId,
+    epoch: ShareEpoch,
+    share: DLogShareShamir,
+) -> eyre::Result<()> {
+    // `confirm_dlog_share` only reads `pending_share`; these tests do not deserialize `intermediates`.
+    let keygens = serde_json::json!({
+        epoch.to_string(): { "intermediates": BASE64.encode([0_u8]), "pending_share": null }
+    });
+    secrets.lock().insert(
+        secret_manager.keygen_secret(oprf_key_id),
+        Some(BASE64.encode(serde_json::to_vec(&keygens)?)),
+    );
+    secret_manager
+        .store_pending_dlog_share(oprf_key_id, epoch, share)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn store_node_information_and_load_wallet() -> eyre::Result<()> {
+    let (secret_manager, secrets) = gcp_secret_manager().await?;
+    assert!(
+        secret_manager.load_wallet_private_key().await.is_err(),
+        "missing wallet secret must be an error"
+    );
+    secrets
+        .lock()
+        .insert("wallet".to_owned(), Some(BASE64.encode("0x42\n")));
+    let wallet = secret_manager
+        .load_wallet_private_key()
+        .await?
+        .expect("wallet secret is configured");
+    assert_eq!(wallet.expose_secret(), "0x42");
+
+    let node_information = NodeInformation::new(
+        PartyId(42),
+        "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc".to_owned(),
+        NonZeroU16::new(2).expect("is non-zero"),
+    );
+    secret_manager
+        .store_node_information(node_information)
+        .await?;
+    assert_eq!(
+        read_secret(&secrets, "oprf-node-information"),
+        Some(serde_json::json!({
+            "eth_address": "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+            "party_id": 42,
+            "threshold": 2
+        })),
+        "node information is stored as JSON"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirm_fetch_and_delete_share() -> eyre::Result<()> {
+    let (secret_manager, secrets) = gcp_secret_manager().await?;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+
+    let err = secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch, public_key)
+        .await
+        .expect_err("confirm without pending share should fail");
+    assert!(
+        matches!(err, SecretManagerError::MissingIntermediates(id, e) if id == oprf_key_id && e == epoch),
+        "Should be MissingIntermediates but is {err}"
+    );
+
+    stage_pending_share(&secret_manager, &secrets, oprf_key_id, epoch, share.clone()).await?;
+    secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch, public_key)
+        .await?;
+    // confirming twice is idempotent
+    secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch, public_key)
+        .await?;
+    assert!(
+        read_secret(&secrets, &secret_manager.keygen_secret(oprf_key_id)).is_none(),
+        "intermediates are removed after confirmation"
+    );
+    let is_share = secret_manager
+        .get_share_by_epoch(oprf_key_id, epoch)
+        .await?
+        .expect("share is stored");
+    assert_eq!(
+        ark_babyjubjub::Fr::from(is_share),
+        ark_babyjubjub::Fr::from(share),
+        "Should load the confirmed share"
+    );
+
+    secret_manager.delete_oprf_key_material(oprf_key_id).await?;
+    secret_manager.delete_oprf_key_material(oprf_key_id).await?;
+    assert!(
+        secret_manager
+            .get_share_by_epoch(oprf_key_id, epoch)
+            .await?
+            .is_none(),
+        "Should not return a deleted share"
+    );
+
+    let next_share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    stage_pending_share(
+        &secret_manager,
+        &secrets,
+        oprf_key_id,
+        epoch.next(),
+        next_share,
+    )
+    .await?;
+    let err = secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch.next(), public_key)
+        .await
+        .expect_err("confirm on deleted share should fail");
+    assert!(
+        matches!(err, SecretManagerError::StoreOnDeletedShare),
+        "Should be StoreOnDeletedShare but is {err}"
+    );
+    Ok(())
+}
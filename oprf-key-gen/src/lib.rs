@@ -24,16 +24,26 @@
 //! From there, they can be fetched by the OPRF nodes that handle OPRF requests.
 //!
 //! For details on the OPRF protocol, see the [design document](https://github.com/TaceoLabs/oprf-service/blob/main/docs/oprf.pdf).
+//!
+//! # Committees
+//!
+//! The committee is not chosen per key: every peer registered in the `OprfKeyRegistry` (see `registerOprfPeers`) takes part in the key generation and in every reshare of every key, and receives a share for the new epoch. A node therefore cannot leave the committee of a single key. A reshare triggered with `initReshare` hands a share to the same peers again, and wiping the local share only reduces the number of shares that are left to serve and reshare the key. To stop serving keys, the owner of the contract replaces the peer (emitting `OprfPeerChanged`) and the keys are reshared to the new peer.
+//!
+//! The size of the committee and the threshold cannot change with a reshare. The producers of a reshare encrypt to the peers returned by `loadPeerPublicKeysForProducers` with the threshold of the `ReshareRound1` event, but the round-2 proof is computed with the single key-gen circuit at `zkey_path`, which is built for a fixed number of peers and threshold. Resharing onto a committee with more or fewer peers needs a contract event carrying the new peer set and a circuit per committee size; the `OprfKeyRegistry` does not emit such an event.
 
-use std::{str::FromStr as _, time::Duration};
+use std::{path::PathBuf, str::FromStr as _, sync::Arc, time::Duration};
 
 use crate::{
     config::OprfKeyGenServiceConfig,
     services::{
+        abort_notifier::AbortNotifierService,
         event_cursor_store::ChainCursorService,
+        key_event_watcher::{ResubscribeBackoff, admin::AdminHandle},
         secret_gen::DLogSecretGenService,
         secret_manager::SecretManagerService,
-        transaction_handler::{TransactionHandler, TransactionHandlerArgs},
+        transaction_handler::{
+            TransactionHandler, TransactionHandlerArgs, TransactionSubmitterService,
+        },
     },
 };
 use alloy::{
@@ -45,27 +55,79 @@ use alloy::{
     transports::{TransportErrorKind, TransportResult},
 };
 use eyre::Context as _;
-use groth16_material::circom::CircomGroth16MaterialBuilder;
+use groth16_material::circom::{CircomGroth16Material, CircomGroth16MaterialBuilder};
 use nodes_common::web3::{self, event_stream::ChainCursor};
 use oprf_types::{chain::OprfKeyRegistry, crypto::PartyId, service::NodeInformation};
 use secrecy::ExposeSecret;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument as _;
 
 pub(crate) mod api;
+#[cfg(feature = "azure")]
+pub mod azure;
+pub mod ceremony;
 pub mod config;
+#[cfg(feature = "gcp")]
+pub mod gcp;
+pub mod listener;
 pub mod metrics;
 pub mod postgres;
+#[cfg(any(feature = "gcp", feature = "azure", feature = "vault"))]
+pub mod remote;
 pub(crate) mod services;
+pub mod single_node;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "vault")]
+pub mod vault;
 
 pub use nodes_common::Environment;
 pub use nodes_common::StartedServices;
+pub use services::abort_notifier;
 pub use services::event_cursor_store;
 pub use services::secret_manager;
+pub use services::transaction_handler;
+
+/// Why the key event watcher of the key-gen service stopped.
+///
+/// Allows hosts to decide between a clean shutdown, a restart, or an alert.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ExitReason {
+    /// The cancellation token was cancelled from the outside.
+    Cancelled,
+    /// The chain event stream ended, e.g., because the WebSocket connection dropped, and every attempt to resubscribe failed (see [`config::OprfKeyGenServiceConfig::max_resubscribe_attempts`]). Restarting backfills the missed events from the persisted [`ChainCursor`].
+    EventStreamClosed,
+    /// The watcher crashed with an error or panicked.
+    Failed(eyre::Report),
+}
+
+impl ExitReason {
+    /// Returns the error if the watcher crashed, `Ok(())` otherwise.
+    ///
+    /// # Errors
+    /// Returns the inner error of [`ExitReason::Failed`].
+    pub fn into_result(self) -> eyre::Result<()> {
+        match self {
+            ExitReason::Cancelled | ExitReason::EventStreamClosed => Ok(()),
+            ExitReason::Failed(err) => Err(err),
+        }
+    }
+
+    /// Orders the reasons of several watchers, the most severe is reported by [`KeyGenTasks::run`].
+    fn severity(&self) -> u8 {
+        match self {
+            ExitReason::Cancelled => 0,
+            ExitReason::EventStreamClosed => 1,
+            ExitReason::Failed(_) => 2,
+        }
+    }
+}
 
-/// The tasks spawned by the key-gen library. Should call [`KeyGenTasks::join`] when shutting down for graceful shutdown.
+/// The tasks spawned by the key-gen library. Should call [`KeyGenTasks::join`] or [`KeyGenTasks::run`] when shutting down for graceful shutdown.
 pub struct KeyGenTasks {
-    key_event_watcher: tokio::task::JoinHandle<eyre::Result<()>>,
-    cursor_checkpoint_task: tokio::task::JoinHandle<()>,
+    key_event_watchers: Vec<tokio::task::JoinHandle<ExitReason>>,
+    cursor_checkpoint_tasks: Vec<tokio::task::JoinHandle<()>>,
 
     // keep the providers alive as long as the tasks are
     _http_rpc_provider: web3::HttpRpcProvider,
@@ -78,9 +140,30 @@ impl KeyGenTasks {
     /// # Errors
     /// Returns the error from the inner tasks or an error if the task panicked.
     pub async fn join(self) -> eyre::Result<()> {
-        self.key_event_watcher.await??;
-        self.cursor_checkpoint_task.await?;
-        Ok(())
+        self.run().await.into_result()
+    }
+
+    /// Consumes the task by joining every registered `JoinHandle` and returns why the key event watchers stopped.
+    ///
+    /// Every watcher (one per registry) cancels the shared cancellation token when it stops, so this resolves once a watcher stopped for any reason. If the watchers stopped for different reasons, a failure is reported before a closed event stream, which is reported before a cancellation. A panic of any task is reported as [`ExitReason::Failed`].
+    pub async fn run(self) -> ExitReason {
+        let mut exit_reason = ExitReason::Cancelled;
+        for key_event_watcher in self.key_event_watchers {
+            let watcher_exit_reason = key_event_watcher
+                .await
+                .unwrap_or_else(|err| ExitReason::Failed(err.into()));
+            if watcher_exit_reason.severity() > exit_reason.severity() {
+                exit_reason = watcher_exit_reason;
+            }
+        }
+        for cursor_checkpoint_task in self.cursor_checkpoint_tasks {
+            if let Err(err) = cursor_checkpoint_task.await
+                && !matches!(exit_reason, ExitReason::Failed(_))
+            {
+                exit_reason = ExitReason::Failed(err.into());
+            }
+        }
+        exit_reason
     }
 }
 
@@ -89,10 +172,10 @@ impl KeyGenTasks {
 // When the WS connection drops, alloy's pubsub service calls `try_reconnect()` before
 // propagating the error. Returning a NonRetryable error here causes the service to shut
 // down immediately, which closes the subscription broadcast channel. The key-event-watcher
-// then sees `RecvError::Closed`, its event stream yields `None`, and the task returns
-// `Ok(())`. The cancellation-token drop-guard fires, the whole process exits cleanly, and
-// the supervisor (k8s) restarts it. On restart, `EventStreamBuilder` backfills missed events
-// from the persisted `ChainCursor`.
+// then sees `RecvError::Closed` and its event stream yields `None`. The watcher connects a new
+// provider and resubscribes, where `EventStreamBuilder` backfills the missed events from the
+// last handled `ChainCursor`. Only if resubscribing fails repeatedly does the task return, the
+// cancellation-token drop-guard fire, and the supervisor (k8s) restart the process.
 //
 // Why not `WsConnect::with_max_retries(0)`?
 // alloy-pubsub v2 always makes ONE reconnect attempt before checking the counter, so
@@ -123,6 +206,7 @@ impl PubSubConnect for NoReconnect {
 async fn contract_sanity_checks(
     rpc_provider: &web3::HttpRpcProvider,
     key_gen_wallet_address: Address,
+    contract_address: Address,
     config: &OprfKeyGenServiceConfig,
 ) -> eyre::Result<NodeInformation> {
     tracing::info!(
@@ -130,7 +214,7 @@ async fn contract_sanity_checks(
         config.expected_threshold,
         config.expected_num_peers
     );
-    let contract = OprfKeyRegistry::new(config.oprf_key_registry_contract, rpc_provider.inner());
+    let contract = OprfKeyRegistry::new(contract_address, rpc_provider.inner());
     // Fetch the party ID and log it.
     // This call verifies whether we are registered at the contract as participant and serves as early failing point if not
     let get_party_id_call = contract.getPartyIdForParticipant(key_gen_wallet_address);
@@ -169,23 +253,7 @@ async fn contract_sanity_checks(
 /// Starts the OPRF key generation service and spawns all required background tasks.
 /// Additionally, returns an `axum::Router` exposing basic service endpoints.
 ///
-/// # Exposed Routes
-/// The returned router provides the following endpoints:
-/// - `/health` – health and readiness endpoint.
-/// - `/version` – returns the running service version.
-/// - `/wallet` – returns the public Ethereum wallet address of this node.
-///
-/// # Initialization
-/// During startup the service performs several initialization steps:
-/// - Initializes the Ethereum wallet from the configured private key.
-/// - Stores the derived wallet address in the configured secret manager.
-/// - Initializes the RPC provider used to interact with the configured blockchain.
-/// - Fetches and logs the wallet balance.
-/// - Loads the party ID from the `OprfKeyRegistry` contract to verify that this
-///   node is registered as a participant.
-/// - Builds the Groth16 proving material required for the key generation protocol.
-/// - Initializes the `DLogSecretGenService`, which uses the secret manager to persist in-progress key-gen state between rounds.
-/// - Creates a `TransactionHandler` used for submitting and confirming on-chain transactions.
+/// This is a shorthand for [`OprfKeyGenBuilder`] without any injected components. See [`OprfKeyGenBuilder::build`] for the exposed routes, the initialization steps and the spawned tasks.
 ///
 /// # Parameters
 /// - `secret_manager` – Postgres-backed store for key shares and in-progress state.
@@ -193,23 +261,8 @@ async fn contract_sanity_checks(
 ///   the persisted `(block, log_index)` on startup so backfill resumes from where the
 ///   previous run left off rather than from the chain head.
 ///
-/// # Spawned Tasks
-/// The service spawns the following background tasks:
-/// - `key_event_watcher` – subscribes to the `OprfKeyRegistry` contract events and
-///   drives the key generation / resharing protocol. Backfills missed events from the
-///   last persisted chain cursor.
-///
-/// # Returns
-/// Returns:
-/// - An `axum::Router` exposing the service endpoints.
-/// - A `KeyGenTasks` handle containing the spawned tasks.
-///
 /// # Errors
-/// Returns an error if:
-/// - the configured wallet private key cannot be parsed,
-/// - the RPC provider cannot be initialized,
-/// - the node is not registered in the `OprfKeyRegistry` contract,
-/// - the Groth16 proving material cannot be built.
+/// See [`OprfKeyGenBuilder::build`].
 pub async fn start(
     config: OprfKeyGenServiceConfig,
     secret_manager: SecretManagerService,
@@ -217,32 +270,403 @@ pub async fn start(
     started_services: StartedServices,
     cancellation_token: CancellationToken,
 ) -> eyre::Result<(axum::Router, KeyGenTasks)> {
-    tracing::info!("init oprf key-gen service..");
+    OprfKeyGenBuilder::new(
+        config,
+        secret_manager,
+        chain_cursor_service,
+        cancellation_token,
+    )
+    .started_services(started_services)
+    .build()
+    .await
+}
 
-    tracing::info!("initializing wallet...");
-    let private_key = PrivateKeySigner::from_str(config.wallet_private_key.expose_secret())
-        .context("while loading wallet private key")?;
-    let address = private_key.address();
-    tracing::info!("my wallet address: {address}");
-    let wallet = EthereumWallet::from(private_key);
-
-    let http_rpc_provider =
-        nodes_common::web3::HttpRpcProviderBuilder::with_config(&config.rpc_provider_config)
-            .environment(config.environment)
-            .wallet(wallet)
-            .build()
-            .context("while init blockchain connection")?;
-
-    // NoReconnect refuses reconnects so that on WS connection errors the pubsub service shuts
-    // down, the event stream ends, and the supervisor can restart the process to backfill
-    // missed events from the persisted ChainCursor. See `NoReconnect` for the full rationale.
-    let ws_rpc_provider = ProviderBuilder::new()
-        .connect_pubsub_with(NoReconnect(WsConnect::new(
-            config.ws_rpc_url.expose_secret(),
-        )))
+/// Builder to embed the OPRF key generation service into a larger application.
+///
+/// In contrast to [`start`], the builder allows embedders to inject
+/// - an HTTP RPC provider ([`OprfKeyGenBuilder::http_rpc_provider`]),
+/// - a WebSocket RPC provider ([`OprfKeyGenBuilder::ws_rpc_provider`]),
+/// - a custom [`transaction_handler::TransactionSubmitter`] ([`OprfKeyGenBuilder::transaction_submitter`]),
+/// - the [`StartedServices`] shared with other co-hosted services ([`OprfKeyGenBuilder::started_services`]),
+/// - an [`abort_notifier::AbortNotifier`] called for abandoned runs ([`OprfKeyGenBuilder::abort_notifier`]),
+/// - the storage of every additional registry ([`OprfKeyGenBuilder::registry_storage`]).
+///
+/// Components that are not injected are created from the [`OprfKeyGenServiceConfig`].
+pub struct OprfKeyGenBuilder {
+    config: OprfKeyGenServiceConfig,
+    secret_manager: SecretManagerService,
+    chain_cursor_service: ChainCursorService,
+    registry_storages: Vec<RegistryStorage>,
+    started_services: StartedServices,
+    cancellation_token: CancellationToken,
+    http_rpc_provider: Option<web3::HttpRpcProvider>,
+    ws_rpc_provider: Option<DynProvider>,
+    transaction_submitter: Option<TransactionSubmitterService>,
+    abort_notifier: Option<AbortNotifierService>,
+}
+
+impl OprfKeyGenBuilder {
+    /// Creates a new builder with the required components.
+    #[must_use]
+    pub fn new(
+        config: OprfKeyGenServiceConfig,
+        secret_manager: SecretManagerService,
+        chain_cursor_service: ChainCursorService,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        Self {
+            config,
+            secret_manager,
+            chain_cursor_service,
+            registry_storages: Vec::new(),
+            started_services: StartedServices::default(),
+            cancellation_token,
+            http_rpc_provider: None,
+            ws_rpc_provider: None,
+            transaction_submitter: None,
+            abort_notifier: None,
+        }
+    }
+
+    /// Uses the provided [`StartedServices`] instead of a fresh one, e.g. to share the `/health` state with co-hosted services.
+    #[must_use]
+    pub fn started_services(mut self, started_services: StartedServices) -> Self {
+        self.started_services = started_services;
+        self
+    }
+
+    /// Uses the provided HTTP RPC provider for view calls, balance queries and (if no custom submitter is set) transaction submission.
+    ///
+    /// If the default transaction submitter is used, the provider must be configured with the wallet derived from `wallet_private_key`.
+    #[must_use]
+    pub fn http_rpc_provider(mut self, http_rpc_provider: web3::HttpRpcProvider) -> Self {
+        self.http_rpc_provider = Some(http_rpc_provider);
+        self
+    }
+
+    /// Uses the provided WebSocket RPC provider to subscribe to `OprfKeyRegistry` events.
+    ///
+    /// The default provider refuses to reconnect so that the service restarts and backfills missed events. Injected providers should behave the same.
+    #[must_use]
+    pub fn ws_rpc_provider(mut self, ws_rpc_provider: DynProvider) -> Self {
+        self.ws_rpc_provider = Some(ws_rpc_provider);
+        self
+    }
+
+    /// Uses the provided [`transaction_handler::TransactionSubmitter`] to submit the round contributions.
+    #[must_use]
+    pub fn transaction_submitter(
+        mut self,
+        transaction_submitter: TransactionSubmitterService,
+    ) -> Self {
+        self.transaction_submitter = Some(transaction_submitter);
+        self
+    }
+
+    /// Calls the provided [`abort_notifier::AbortNotifier`] after a run was abandoned because it did not finish within the configured `protocol_timeout`.
+    ///
+    /// Without a notifier, abandoned runs are only evicted locally.
+    #[must_use]
+    pub fn abort_notifier(mut self, abort_notifier: AbortNotifierService) -> Self {
+        self.abort_notifier = Some(abort_notifier);
+        self
+    }
+
+    /// Uses the provided secret manager and chain cursor store for the additional registry `contract` (see [`OprfKeyGenServiceConfig::additional_oprf_key_registry_contracts`]).
+    ///
+    /// The key ids of different registries may overlap, so the storage must not be shared with another registry, e.g. use a separate Postgres schema per registry. Every additional registry needs its own storage.
+    #[must_use]
+    pub fn registry_storage(
+        mut self,
+        contract: Address,
+        secret_manager: SecretManagerService,
+        chain_cursor_service: ChainCursorService,
+    ) -> Self {
+        self.registry_storages.push(RegistryStorage {
+            contract,
+            secret_manager,
+            chain_cursor_service,
+        });
+        self
+    }
+
+    /// Initializes the OPRF key generation service and spawns all required background tasks.
+    ///
+    /// # Exposed Routes
+    /// The returned router provides the following endpoints:
+    /// - `/health` – health and readiness endpoint.
+    /// - `/version` – returns the running service version.
+    /// - `/wallet` – returns the public Ethereum wallet address of this node.
+    /// - `/metrics` – the metrics in the Prometheus text format, only if `metrics_endpoint` is enabled in the config (see [`metrics::exporter`]).
+    /// - `/keygen/active` and `DELETE /keygen/{oprf_key_id}` – list and abort the in-progress key-gen/reshare runs, only if `admin_endpoints` is enabled in the config.
+    ///
+    /// # Initialization
+    /// During startup the service performs several initialization steps:
+    /// - Initializes the Ethereum wallet from the configured private key.
+    /// - Initializes the RPC providers used to interact with the configured blockchain, unless injected.
+    /// - Fetches and logs the wallet balance.
+    /// - Loads the party ID from every `OprfKeyRegistry` contract to verify that this
+    ///   node is registered as a participant.
+    /// - Stores the node information in the secret manager of every registry.
+    /// - Compares the stored keys of every registry with the registry and reports, quarantines or deletes the shares of keys that were deleted on-chain, if `orphaned_keys` is enabled in the config.
+    /// - Builds the Groth16 proving material required for the key generation protocol.
+    /// - Initializes a `DLogSecretGenService` per registry, which uses the secret manager of the registry to persist in-progress key-gen state between rounds.
+    /// - Creates the default transaction submitter used for submitting and confirming on-chain transactions, unless injected. The submitters of all registries send one transaction at a time, as they share the wallet.
+    ///
+    /// # Spawned Tasks
+    /// The service spawns the following background tasks per registry:
+    /// - `key_event_watcher` – subscribes to the `OprfKeyRegistry` contract events and
+    ///   drives the key generation / resharing protocol. Backfills missed events from the
+    ///   last persisted chain cursor and abandons runs that exceed the `protocol_timeout`.
+    /// - `cursor_checkpoint_task` – periodically persists the chain cursor.
+    ///
+    /// # Returns
+    /// Returns:
+    /// - An `axum::Router` exposing the service endpoints.
+    /// - A `KeyGenTasks` handle containing the spawned tasks.
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// - the configured wallet private key cannot be parsed,
+    /// - the additional registries do not match the storages provided via [`OprfKeyGenBuilder::registry_storage`], or a custom transaction submitter is used with additional registries,
+    /// - the RPC providers cannot be initialized,
+    /// - the node is not registered in one of the `OprfKeyRegistry` contracts,
+    /// - `orphaned_keys` is enabled but the secret manager of a registry cannot list its keys,
+    /// - the Groth16 proving material cannot be built,
+    /// - `metrics_endpoint` is enabled but the Prometheus recorder cannot be installed.
+    pub async fn build(self) -> eyre::Result<(axum::Router, KeyGenTasks)> {
+        let Self {
+            config,
+            secret_manager,
+            chain_cursor_service,
+            registry_storages,
+            started_services,
+            cancellation_token,
+            http_rpc_provider,
+            ws_rpc_provider,
+            transaction_submitter,
+            abort_notifier,
+        } = self;
+        tracing::info!("init oprf key-gen service..");
+
+        let registries = registries(
+            &config,
+            secret_manager,
+            chain_cursor_service,
+            registry_storages,
+        )?;
+        eyre::ensure!(
+            transaction_submitter.is_none() || registries.len() == 1,
+            "a custom transaction submitter cannot be used with additional registries"
+        );
+
+        tracing::info!("initializing wallet...");
+        let private_key = PrivateKeySigner::from_str(config.wallet_private_key.expose_secret())
+            .context("while loading wallet private key")?;
+        let address = private_key.address();
+        tracing::info!("my wallet address: {address}");
+
+        // resubscribing reuses the provider of the caller, but replaces ours with a new connection
+        let ws_rpc_url = ws_rpc_provider.is_none().then(|| config.ws_rpc_url.clone());
+        let (http_rpc_provider, ws_rpc_provider) =
+            init_rpc_providers(&config, private_key, http_rpc_provider, ws_rpc_provider).await?;
+
+        store_node_information(&http_rpc_provider, address, &registries, &config).await?;
+        reconcile_orphaned_keys(&http_rpc_provider, &registries, &config).await?;
+
+        let key_gen_material =
+            build_key_gen_material(config.zkey_path.clone(), config.witness_graph_path.clone())
+                .await?;
+        let dlog_secret_gen_service =
+            DLogSecretGenService::init(key_gen_material, Arc::clone(&registries[0].secret_manager));
+        let send_lock = Arc::default();
+        let mut admin_handle = AdminHandle::default();
+
+        let mut key_event_watchers = Vec::with_capacity(registries.len());
+        let mut cursor_checkpoint_tasks = Vec::with_capacity(registries.len());
+        for registry in registries {
+            let contract = registry.contract;
+            let transaction_submitter = transaction_submitter.clone().unwrap_or_else(|| {
+                default_transaction_submitter(
+                    &config,
+                    &http_rpc_provider,
+                    address,
+                    contract,
+                    &send_lock,
+                )
+            });
+
+            tracing::info!("spawning key event watcher for {contract}..");
+            let span = tracing::info_span!("registry", %contract);
+            key_event_watchers.push(tokio::spawn(
+                services::key_event_watcher::key_event_watcher_task(
+                    services::key_event_watcher::KeyEventWatcherTaskConfig {
+                        http_rpc_provider: http_rpc_provider.clone(),
+                        ws_rpc_provider: ws_rpc_provider.clone(),
+                        ws_rpc_url: ws_rpc_url.clone(),
+                        contract_address: contract,
+                        dlog_secret_gen_service: dlog_secret_gen_service
+                            .with_secret_manager(registry.secret_manager),
+                        chain_cursor_service: registry.chain_cursor_service.clone(),
+                        start_signal: started_services.new_service(),
+                        transaction_submitter,
+                        event_stream_config: config.event_stream_config.clone(),
+                        resubscribe_backoff: ResubscribeBackoff::from_config(&config),
+                        protocol_timeout: config.protocol_timeout,
+                        abort_notifier: abort_notifier.clone(),
+                        admin_commands: admin_handle.register(contract),
+                        cancellation_token: cancellation_token.clone(),
+                    },
+                )
+                .instrument(span.clone()),
+            ));
+            cursor_checkpoint_tasks.push(tokio::task::spawn(
+                start_cursor_checkpoint_task(
+                    config.cursor_checkpoint_interval,
+                    http_rpc_provider.clone(),
+                    registry.chain_cursor_service,
+                    cancellation_token.clone(),
+                )
+                .instrument(span),
+            ));
+        }
+
+        let key_gen_router = api::routes(address, started_services.clone(), admin_handle, &config)?;
+
+        Ok((
+            key_gen_router,
+            KeyGenTasks {
+                key_event_watchers,
+                cursor_checkpoint_tasks,
+                _http_rpc_provider: http_rpc_provider,
+                _ws_rpc_provider: ws_rpc_provider,
+            },
+        ))
+    }
+}
+
+/// The contract of a registry with the storage of its keys and chain cursor.
+struct RegistryStorage {
+    contract: Address,
+    secret_manager: SecretManagerService,
+    chain_cursor_service: ChainCursorService,
+}
+
+/// The [`TransactionHandler`] of the registry at `contract`.
+///
+/// The handlers of all registries share the wallet at `address`, so they share the `send_lock` to not reuse a nonce.
+fn default_transaction_submitter(
+    config: &OprfKeyGenServiceConfig,
+    http_rpc_provider: &web3::HttpRpcProvider,
+    address: Address,
+    contract: Address,
+    send_lock: &Arc<tokio::sync::Mutex<()>>,
+) -> TransactionSubmitterService {
+    let mut args = TransactionHandlerArgs::from_config(config, http_rpc_provider.clone(), address);
+    args.contract_address = contract;
+    Arc::new(TransactionHandler::new(args).with_send_lock(Arc::clone(send_lock)))
+}
+
+/// Does the sanity checks against every registry and stores the resulting node information in the secret manager of the registry.
+async fn store_node_information(
+    http_rpc_provider: &web3::HttpRpcProvider,
+    address: Address,
+    registries: &[RegistryStorage],
+    config: &OprfKeyGenServiceConfig,
+) -> eyre::Result<()> {
+    for registry in registries {
+        let node_information =
+            contract_sanity_checks(http_rpc_provider, address, registry.contract, config)
+                .await
+                .with_context(|| format!("while doing sanity checks for {}", registry.contract))?;
+
+        registry
+            .secret_manager
+            .store_node_information(node_information)
+            .await
+            .context("while storing node information in secret manager")?;
+    }
+    Ok(())
+}
+
+/// Runs the reconciliation of orphaned keys (see [`services::orphaned_keys`]) for every registry.
+async fn reconcile_orphaned_keys(
+    http_rpc_provider: &web3::HttpRpcProvider,
+    registries: &[RegistryStorage],
+    config: &OprfKeyGenServiceConfig,
+) -> eyre::Result<()> {
+    for registry in registries {
+        let contract = OprfKeyRegistry::new(registry.contract, http_rpc_provider.inner());
+        services::orphaned_keys::reconcile(
+            &contract,
+            &registry.secret_manager,
+            config.orphaned_keys,
+            config.orphaned_keys_dry_run,
+        )
+        .instrument(tracing::info_span!("registry", contract = %registry.contract))
         .await
-        .context("while connecting ws provider")?
-        .erased();
+        .with_context(|| format!("while reconciling the keys of {}", registry.contract))?;
+    }
+    Ok(())
+}
+
+/// Pairs the `OprfKeyRegistry` and the additional registries of the `config` with their storages, the registry of the `config` first.
+fn registries(
+    config: &OprfKeyGenServiceConfig,
+    secret_manager: SecretManagerService,
+    chain_cursor_service: ChainCursorService,
+    mut registry_storages: Vec<RegistryStorage>,
+) -> eyre::Result<Vec<RegistryStorage>> {
+    let mut registries = vec![RegistryStorage {
+        contract: config.oprf_key_registry_contract,
+        secret_manager,
+        chain_cursor_service,
+    }];
+    for contract in &config.additional_oprf_key_registry_contracts {
+        eyre::ensure!(
+            registries
+                .iter()
+                .all(|registry| registry.contract != *contract),
+            "registry {contract} is configured twice"
+        );
+        let position = registry_storages
+            .iter()
+            .position(|storage| storage.contract == *contract)
+            .ok_or_else(|| eyre::eyre!("no storage provided for registry {contract}"))?;
+        registries.push(registry_storages.swap_remove(position));
+    }
+    if let Some(storage) = registry_storages.first() {
+        eyre::bail!(
+            "storage provided for registry {} which is not configured",
+            storage.contract
+        );
+    }
+    Ok(registries)
+}
+
+/// Uses the given RPC providers or builds the default ones from the `config`, and reports the balance of the wallet.
+async fn init_rpc_providers(
+    config: &OprfKeyGenServiceConfig,
+    private_key: PrivateKeySigner,
+    http_rpc_provider: Option<web3::HttpRpcProvider>,
+    ws_rpc_provider: Option<DynProvider>,
+) -> eyre::Result<(web3::HttpRpcProvider, DynProvider)> {
+    let address = private_key.address();
+    let http_rpc_provider = match http_rpc_provider {
+        Some(http_rpc_provider) => http_rpc_provider,
+        None => {
+            nodes_common::web3::HttpRpcProviderBuilder::with_config(&config.rpc_provider_config)
+                .environment(config.environment)
+                .wallet(EthereumWallet::from(private_key))
+                .build()
+                .context("while init blockchain connection")?
+        }
+    };
+
+    let ws_rpc_provider = match ws_rpc_provider {
+        Some(ws_rpc_provider) => ws_rpc_provider,
+        None => connect_ws_provider(config.ws_rpc_url.expose_secret()).await?,
+    };
 
     let balance = http_rpc_provider
         .get_balance(address)
@@ -252,77 +676,35 @@ pub async fn start(
 
     tracing::info!("wallet balance: {balance} ETH");
     metrics::wallet::set_wallet_balance(&balance);
+    Ok((http_rpc_provider, ws_rpc_provider))
+}
 
-    let node_information = contract_sanity_checks(&http_rpc_provider, address, &config)
-        .await
-        .context("while doing sanity checks")?;
-
-    secret_manager
-        .store_node_information(node_information)
+/// Connects the default WebSocket provider.
+///
+/// `NoReconnect` refuses reconnects so that on WS connection errors the pubsub service shuts
+/// down, the event stream ends, and the supervisor can restart the process to backfill
+/// missed events from the persisted `ChainCursor`. See `NoReconnect` for the full rationale.
+pub(crate) async fn connect_ws_provider(ws_rpc_url: &str) -> eyre::Result<DynProvider> {
+    Ok(ProviderBuilder::new()
+        .connect_pubsub_with(NoReconnect(WsConnect::new(ws_rpc_url)))
         .await
-        .context("while storing node information in secret manager")?;
+        .context("while connecting ws provider")?
+        .erased())
+}
 
-    let key_gen_material = tokio::task::spawn_blocking(move || {
+async fn build_key_gen_material(
+    zkey_path: PathBuf,
+    witness_graph_path: PathBuf,
+) -> eyre::Result<CircomGroth16Material> {
+    tokio::task::spawn_blocking(move || {
         CircomGroth16MaterialBuilder::new()
             .bbf_inv()
             .bbf_num_2_bits_helper()
-            .build_from_paths(config.zkey_path, config.witness_graph_path)
+            .build_from_paths(zkey_path, witness_graph_path)
     })
     .await
     .context("while joining build groth16 task")?
-    .context("while building groth16 material")?;
-
-    let dlog_secret_gen_service =
-        DLogSecretGenService::init(key_gen_material, secret_manager.clone());
-    let transaction_handler = TransactionHandler::new(TransactionHandlerArgs {
-        max_wait_time_watch_transaction: config.max_wait_time_transaction_confirmation,
-        confirmations_for_transaction: config.confirmations_for_transaction,
-        sleep_between_get_receipt: config.sleep_between_get_receipt,
-        max_tries_fetching_receipt: config.max_tries_fetching_receipt,
-        max_gas_per_transaction: config.max_gas_per_transaction,
-        rpc_provider: http_rpc_provider.clone(),
-        wallet_address: address,
-        contract_address: config.oprf_key_registry_contract,
-    });
-
-    tracing::info!("spawning key event watcher..");
-    let key_event_watcher = tokio::spawn({
-        let contract_address = config.oprf_key_registry_contract;
-        let cancellation_token = cancellation_token.clone();
-        services::key_event_watcher::key_event_watcher_task(
-            services::key_event_watcher::KeyEventWatcherTaskConfig {
-                http_rpc_provider: http_rpc_provider.clone(),
-                ws_rpc_provider: ws_rpc_provider.clone(),
-                contract_address,
-                dlog_secret_gen_service,
-                chain_cursor_service: chain_cursor_service.clone(),
-                start_signal: started_services.new_service(),
-                transaction_handler,
-                event_stream_config: config.event_stream_config,
-                threshold: config.expected_threshold,
-                cancellation_token,
-            },
-        )
-    });
-
-    let key_gen_router = api::routes(address, started_services.clone());
-
-    let cursor_checkpoint_task = tokio::task::spawn(start_cursor_checkpoint_task(
-        config.cursor_checkpoint_interval,
-        http_rpc_provider.clone(),
-        chain_cursor_service,
-        cancellation_token,
-    ));
-
-    Ok((
-        key_gen_router,
-        KeyGenTasks {
-            key_event_watcher,
-            cursor_checkpoint_task,
-            _http_rpc_provider: http_rpc_provider,
-            _ws_rpc_provider: ws_rpc_provider,
-        },
-    ))
+    .context("while building groth16 material")
 }
 
 async fn start_cursor_checkpoint_task(
@@ -17,7 +17,10 @@ use config::Config;
 use eyre::Context;
 use nodes_common::{StartedServices, postgres::PostgresConfig};
 use serde::Deserialize;
-use taceo_oprf_key_gen::{config::OprfKeyGenServiceConfig, postgres::PostgresDb};
+use taceo_oprf_key_gen::{
+    OprfKeyGenBuilder, config::OprfKeyGenServiceConfig, listener::MultiListener,
+    postgres::PostgresDb, secret_manager::SecretManagerService,
+};
 
 /// The top-level configuration for the OPRF key-gen binary.
 ///
@@ -28,6 +31,10 @@ struct OprfKeyGenConfig {
     #[serde(default = "default_bind_addr")]
     pub bind_addr: SocketAddr,
 
+    /// Further addresses the AXUM server binds to, e.g., `[::]:4321` next to `0.0.0.0:4321` for IPv4 and IPv6.
+    #[serde(default)]
+    pub additional_bind_addrs: Vec<SocketAddr>,
+
     /// Max wait time the service waits for its workers during shutdown.
     #[serde(default = "default_max_wait_shutdown")]
     #[serde(with = "humantime_serde")]
@@ -40,6 +47,27 @@ struct OprfKeyGenConfig {
     /// Postgres config used by the shared [`PostgresDb`] backend (secret manager and chain cursor store).
     #[serde(rename = "postgres")]
     pub postgres_config: PostgresConfig,
+
+    /// If set, stores the shares (and optionally loads the wallet private key) in Google Cloud Secret Manager instead of Postgres.
+    ///
+    /// Postgres is still used for the chain cursor.
+    #[cfg(feature = "gcp")]
+    #[serde(default)]
+    pub gcp: Option<taceo_oprf_key_gen::gcp::GcpConfig>,
+
+    /// If set, stores the shares (and optionally loads the wallet private key) in Azure Key Vault instead of Postgres.
+    ///
+    /// Postgres is still used for the chain cursor.
+    #[cfg(feature = "azure")]
+    #[serde(default)]
+    pub azure: Option<taceo_oprf_key_gen::azure::AzureConfig>,
+
+    /// If set, stores the shares (and optionally loads the wallet private key) in `HashiCorp` Vault instead of Postgres.
+    ///
+    /// Postgres is still used for the chain cursor.
+    #[cfg(feature = "vault")]
+    #[serde(default)]
+    pub vault: Option<taceo_oprf_key_gen::vault::VaultConfig>,
 }
 
 fn default_bind_addr() -> SocketAddr {
@@ -53,11 +81,17 @@ fn default_max_wait_shutdown() -> Duration {
 // we are not allowed to build an eyre::Report yet because telemetry-batteries expects to install
 // the color-eyre hook
 fn load_key_gen_config() -> Result<OprfKeyGenConfig, config::ConfigError> {
-    let cfg = Config::builder().add_source(
+    let cfg = Config::builder();
+    // the wallet private key may be loaded from a remote secret manager instead
+    #[cfg(any(feature = "gcp", feature = "azure", feature = "vault"))]
+    let cfg = cfg.set_default("service.wallet_private_key", "")?;
+    let cfg = cfg.add_source(
         config::Environment::with_prefix("TACEO_OPRF_KEY_GEN")
             .separator("__")
             .list_separator(",")
             .with_list_parse_key("service.rpc.http_urls")
+            .with_list_parse_key("additional_bind_addrs")
+            .with_list_parse_key("service.additional_oprf_key_registry_contracts")
             .try_parsing(true),
     );
 
@@ -88,38 +122,145 @@ async fn run(config: OprfKeyGenConfig) -> eyre::Result<()> {
         .await
         .context("while starting postgres secret-manager")?;
 
-    // Init postgres secret manager (Postgres backed)
-    let secret_manager = Arc::new(postgres.clone());
+    // Init secret manager (Postgres backed unless configured otherwise)
+    let secret_manager: SecretManagerService = Arc::new(postgres.clone());
+    #[cfg(any(feature = "gcp", feature = "azure", feature = "vault"))]
+    let mut config = config;
+    #[cfg(any(feature = "gcp", feature = "azure", feature = "vault"))]
+    {
+        let configured = [
+            #[cfg(feature = "gcp")]
+            config.gcp.is_some(),
+            #[cfg(feature = "azure")]
+            config.azure.is_some(),
+            #[cfg(feature = "vault")]
+            config.vault.is_some(),
+        ];
+        eyre::ensure!(
+            configured
+                .into_iter()
+                .filter(|configured| *configured)
+                .count()
+                <= 1,
+            "only one of the GCP, Azure and Vault secret managers can be configured"
+        );
+        eyre::ensure!(
+            !configured.contains(&true)
+                || config
+                    .key_gen_config
+                    .additional_oprf_key_registry_contracts
+                    .is_empty(),
+            "additional registries require the postgres secret manager"
+        );
+    }
+    #[cfg(feature = "gcp")]
+    let secret_manager = if let Some(gcp_config) = config.gcp.clone() {
+        tracing::info!("using GCP secret manager..");
+        let gcp = taceo_oprf_key_gen::gcp::GcpSecretManager::init(gcp_config)
+            .await
+            .context("while starting GCP secret-manager")?;
+        if let Some(wallet_private_key) = gcp
+            .load_wallet_private_key()
+            .await
+            .context("while loading wallet private key from GCP")?
+        {
+            config.key_gen_config.wallet_private_key = wallet_private_key;
+        }
+        Arc::new(gcp)
+    } else {
+        secret_manager
+    };
+    #[cfg(feature = "azure")]
+    let secret_manager = if let Some(azure_config) = config.azure.clone() {
+        tracing::info!("using Azure Key Vault secret manager..");
+        let azure = taceo_oprf_key_gen::azure::AzureSecretManager::init(azure_config)
+            .await
+            .context("while starting Azure Key Vault secret-manager")?;
+        if let Some(wallet_private_key) = azure
+            .load_wallet_private_key()
+            .await
+            .context("while loading wallet private key from Azure Key Vault")?
+        {
+            config.key_gen_config.wallet_private_key = wallet_private_key;
+        }
+        Arc::new(azure)
+    } else {
+        secret_manager
+    };
+    #[cfg(feature = "vault")]
+    let secret_manager = if let Some(vault_config) = config.vault.clone() {
+        tracing::info!("using Vault secret manager..");
+        let vault = taceo_oprf_key_gen::vault::VaultSecretManager::init(vault_config)
+            .await
+            .context("while starting Vault secret-manager")?;
+        if let Some(wallet_private_key) = vault
+            .load_wallet_private_key()
+            .await
+            .context("while loading wallet private key from Vault")?
+        {
+            config.key_gen_config.wallet_private_key = wallet_private_key;
+        }
+        Arc::new(vault)
+    } else {
+        secret_manager
+    };
 
     // Init chain event store (Postgres backed)
     let chain_cursor_store = Arc::new(postgres.clone());
 
+    // Every additional registry gets its own schema, as the key ids of the registries may overlap
+    let mut registry_storages = Vec::new();
+    for contract in &config.key_gen_config.additional_oprf_key_registry_contracts {
+        let mut postgres_config = config.postgres_config.clone();
+        postgres_config.schema = format!(
+            "{}_{}",
+            config.postgres_config.schema,
+            alloy::hex::encode(contract)
+        )
+        .parse()
+        .context("while deriving the schema of an additional registry")?;
+        tracing::info!(
+            "connecting to postgres DB schema {} for registry {contract}...",
+            postgres_config.schema
+        );
+        let postgres = PostgresDb::init(&postgres_config)
+            .await
+            .with_context(|| format!("while starting postgres for registry {contract}"))?;
+        registry_storages.push((*contract, Arc::new(postgres)));
+    }
+
     let (cancellation_token, _) =
         nodes_common::spawn_shutdown_task(nodes_common::default_shutdown_signal());
 
     // Clone the values we need afterwards as well
-    let bind_addr = config.bind_addr;
+    let bind_addrs = std::iter::once(config.bind_addr)
+        .chain(config.additional_bind_addrs)
+        .collect::<Vec<_>>();
     let max_wait_time_shutdown = config.max_wait_time_shutdown;
 
-    let (key_gen_router, key_gen_task) = taceo_oprf_key_gen::start(
+    let mut builder = OprfKeyGenBuilder::new(
         config.key_gen_config,
         secret_manager,
         chain_cursor_store,
-        StartedServices::new(),
         cancellation_token.clone(),
     )
-    .await
-    .context("while initiating key-gen service")?;
+    .started_services(StartedServices::new());
+    for (contract, postgres) in registry_storages {
+        builder = builder.registry_storage(contract, postgres.clone(), postgres);
+    }
+    let (key_gen_router, key_gen_task) = builder
+        .build()
+        .await
+        .context("while initiating key-gen service")?;
 
     let server = tokio::spawn({
         let cancellation_token = cancellation_token.clone();
         async move {
             // we cancel the token if this task closes for some reason
             let _drop_guard = cancellation_token.drop_guard_ref();
-            tracing::info!("starting axum server on to {bind_addr}");
-            let tcp_listener = tokio::net::TcpListener::bind(bind_addr)
-                .await
-                .context("while binding tcp-listener")?;
+            tracing::info!("starting axum server on {bind_addrs:?}");
+            let tcp_listener =
+                MultiListener::bind(&bind_addrs).context("while binding tcp-listener")?;
             let axum_result = axum::serve(tcp_listener, key_gen_router)
                 .with_graceful_shutdown({
                     let cancellation_token = cancellation_token.clone();
@@ -138,9 +279,10 @@ async fn run(config: OprfKeyGenConfig) -> eyre::Result<()> {
     tracing::info!("waiting for shutdown of services (max wait time {max_wait_time_shutdown:?})..");
 
     match tokio::time::timeout(max_wait_time_shutdown, async move {
-        let (axum_result, key_gen_result) = tokio::join!(server, key_gen_task.join());
+        let (axum_result, exit_reason) = tokio::join!(server, key_gen_task.run());
         axum_result??;
-        key_gen_result?;
+        tracing::info!("key event watcher stopped: {exit_reason:?}");
+        exit_reason.into_result()?;
         eyre::Ok(())
     })
     .await
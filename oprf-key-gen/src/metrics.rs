@@ -10,12 +10,16 @@
 pub fn describe_metrics() {
     wallet::describe_metrics();
     chain_events::describe_metrics();
+    protocol::describe_metrics();
+    reconciliation::describe_metrics();
 }
 
 pub(crate) mod wallet {
 
     const METRICS_ID_KEY_GEN_WALLET_BALANCE: &str = "taceo.oprf.key_gen.wallet.balance";
     const METRICS_ID_GAS_PRICE: &str = "taceo.oprf.key_gen.wallet.transaction.gas_price";
+    const METRICS_ID_STUCK_TRANSACTIONS: &str = "taceo.oprf.key_gen.wallet.transaction.stuck";
+    const METRICS_ID_FEE_BUMPS: &str = "taceo.oprf.key_gen.wallet.transaction.fee_bumps";
 
     pub(super) fn describe_metrics() {
         metrics::describe_gauge!(
@@ -29,6 +33,18 @@ pub(crate) mod wallet {
             metrics::Unit::Count,
             "Gas price of the transactions in WEI"
         );
+
+        metrics::describe_counter!(
+            METRICS_ID_STUCK_TRANSACTIONS,
+            metrics::Unit::Count,
+            "Number of times a transaction was not mined within the max wait time"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_FEE_BUMPS,
+            metrics::Unit::Count,
+            "Number of stuck transactions replaced with escalated fees"
+        );
     }
 
     pub(crate) fn set_wallet_balance(balance_eth: &str) {
@@ -40,6 +56,14 @@ pub(crate) mod wallet {
         let gas_price_wei = gas_price_wei.to_string().parse::<f64>().unwrap_or(f64::NAN);
         metrics::gauge!(METRICS_ID_GAS_PRICE).set(gas_price_wei);
     }
+
+    pub(crate) fn inc_stuck_transactions() {
+        metrics::counter!(METRICS_ID_STUCK_TRANSACTIONS).increment(1);
+    }
+
+    pub(crate) fn inc_fee_bumps() {
+        metrics::counter!(METRICS_ID_FEE_BUMPS).increment(1);
+    }
 }
 
 pub(crate) mod chain_events {
@@ -57,6 +81,9 @@ pub(crate) mod chain_events {
     const ATTR_EVENT_ABORT: &str = "abort";
     const ATTR_EVENT_NOT_ENOUGH_PRODUCERS: &str = "not-enough-producers";
 
+    const METRIC_REORDERED_EVENT_COUNTER: &str = "taceo.oprf.key_gen.chain.events.reordered";
+    const METRIC_RESUBSCRIBE_COUNTER: &str = "taceo.oprf.key_gen.chain.resubscriptions";
+
     const METRIC_PRODUCER_ROLE: &str = "taceo.oprf.key_gen.role.producer";
     const METRIC_CONSUMER_ROLE: &str = "taceo.oprf.key_gen.role.consumer";
     const METRIC_CURRENT_BLOCK: &str = "taceo.oprf.key_gen.block.number";
@@ -68,6 +95,18 @@ pub(crate) mod chain_events {
             "Number of observed chain events successfully handled by this node"
         );
 
+        metrics::describe_counter!(
+            METRIC_REORDERED_EVENT_COUNTER,
+            metrics::Unit::Count,
+            "Number of skipped chain events for an epoch at or before the latest finalized epoch of the key"
+        );
+
+        metrics::describe_counter!(
+            METRIC_RESUBSCRIBE_COUNTER,
+            metrics::Unit::Count,
+            "Number of attempts to resubscribe to chain events after the event stream was lost"
+        );
+
         metrics::describe_counter!(
             METRIC_PRODUCER_ROLE,
             metrics::Unit::Count,
@@ -123,6 +162,14 @@ pub(crate) mod chain_events {
             .increment(1);
     }
 
+    pub(crate) fn inc_reordered(event: &'static str) {
+        metrics::counter!(METRIC_REORDERED_EVENT_COUNTER, ATTR_TYPE_EVENT => event).increment(1);
+    }
+
+    pub(crate) fn inc_resubscribe() {
+        metrics::counter!(METRIC_RESUBSCRIBE_COUNTER).increment(1);
+    }
+
     pub(crate) fn inc_producer() {
         metrics::counter!(METRIC_PRODUCER_ROLE).increment(1);
     }
@@ -139,3 +186,123 @@ pub(crate) mod chain_events {
         metrics::gauge!(METRIC_CURRENT_BLOCK).set(chain_cursor.block() as f64);
     }
 }
+
+pub(crate) mod protocol {
+
+    const METRIC_ABANDONED_RUNS: &str = "taceo.oprf.key_gen.protocol.abandoned";
+
+    pub(super) fn describe_metrics() {
+        metrics::describe_counter!(
+            METRIC_ABANDONED_RUNS,
+            metrics::Unit::Count,
+            "Number of key-gen/reshare runs abandoned after the protocol timeout"
+        );
+    }
+
+    pub(crate) fn inc_abandoned_run() {
+        metrics::counter!(METRIC_ABANDONED_RUNS).increment(1);
+    }
+}
+
+pub(crate) mod reconciliation {
+    use alloy::primitives::Address;
+
+    const METRIC_STORED_KEYS: &str = "taceo.oprf.key_gen.reconciliation.stored";
+    const METRIC_ORPHANED_KEYS: &str = "taceo.oprf.key_gen.reconciliation.orphaned";
+    const METRIC_KEYS: &str = "taceo.oprf.key_gen.reconciliation.keys";
+
+    const ATTR_CONTRACT: &str = "contract";
+    const ATTR_OUTCOME: &str = "outcome";
+
+    /// The orphaned key was logged but kept.
+    pub(crate) const OUTCOME_REPORTED: &str = "reported";
+    /// The share of the orphaned key was quarantined.
+    pub(crate) const OUTCOME_QUARANTINED: &str = "quarantined";
+    /// The share of the orphaned key was deleted.
+    pub(crate) const OUTCOME_DELETED: &str = "deleted";
+    /// The orphaned key would have been quarantined or deleted, but the reconciliation ran in dry-run mode.
+    pub(crate) const OUTCOME_DRY_RUN: &str = "dry_run";
+    /// The key could not be compared with the registry, or the action failed.
+    pub(crate) const OUTCOME_FAILED: &str = "failed";
+
+    pub(super) fn describe_metrics() {
+        metrics::describe_gauge!(
+            METRIC_STORED_KEYS,
+            metrics::Unit::Count,
+            "Number of keys in the secret manager of a registry at the last startup reconciliation"
+        );
+
+        metrics::describe_gauge!(
+            METRIC_ORPHANED_KEYS,
+            metrics::Unit::Count,
+            "Number of stored keys the registry reported as deleted or unknown at the last startup reconciliation"
+        );
+
+        metrics::describe_counter!(
+            METRIC_KEYS,
+            metrics::Unit::Count,
+            "Number of keys handled by the startup reconciliation, by outcome"
+        );
+    }
+
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "We accept precision loss as we don't expect more than 2^52 keys"
+    )]
+    pub(crate) fn set_stored_keys(contract: Address, stored: usize) {
+        metrics::gauge!(METRIC_STORED_KEYS, ATTR_CONTRACT => contract.to_string())
+            .set(stored as f64);
+    }
+
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "We accept precision loss as we don't expect more than 2^52 keys"
+    )]
+    pub(crate) fn set_orphaned_keys(contract: Address, orphaned: usize) {
+        metrics::gauge!(METRIC_ORPHANED_KEYS, ATTR_CONTRACT => contract.to_string())
+            .set(orphaned as f64);
+    }
+
+    pub(crate) fn inc_keys(contract: Address, outcome: &'static str) {
+        metrics::counter!(METRIC_KEYS, ATTR_CONTRACT => contract.to_string(), ATTR_OUTCOME => outcome)
+            .increment(1);
+    }
+}
+
+pub mod exporter {
+    //! Prometheus exporter for the metrics of this crate.
+    //!
+    //! [`install`] installs a Prometheus recorder as the global `metrics` recorder and [`routes`] renders the recorded metrics at `/metrics`. Only one global recorder can be installed per process, so the exporter cannot be combined with a metrics backend of `telemetry-batteries` (keep `TELEMETRY_METRICS_BACKEND` at `none`).
+
+    use std::sync::OnceLock;
+
+    use axum::{Router, routing::get};
+    use metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle};
+
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+    /// Installs the Prometheus recorder as the global `metrics` recorder and returns a handle to render the metrics.
+    ///
+    /// Returns the handle of the first call if the recorder was installed already.
+    ///
+    /// # Errors
+    /// Returns an error if another global recorder is installed.
+    pub fn install() -> Result<PrometheusHandle, BuildError> {
+        if let Some(handle) = HANDLE.get() {
+            return Ok(handle.clone());
+        }
+        let handle = PrometheusBuilder::new().install_recorder()?;
+        Ok(HANDLE.get_or_init(|| handle).clone())
+    }
+
+    /// Serves the metrics recorded by `handle` in the Prometheus text format at `/metrics`.
+    pub fn routes(handle: PrometheusHandle) -> Router {
+        Router::new().route(
+            "/metrics",
+            get(move || async move {
+                handle.run_upkeep();
+                handle.render()
+            }),
+        )
+    }
+}
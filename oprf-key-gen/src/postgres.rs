@@ -12,19 +12,21 @@
 //! The schema is managed by the embedded migrations in `./migrations`, applied automatically
 //! during [`PostgresDb::init`].
 
-use std::{num::NonZeroUsize, time::Duration};
+use std::num::NonZeroU16;
 
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use async_trait::async_trait;
-use backon::{BackoffBuilder, ConstantBackoff, ConstantBuilder, Retryable};
+use backon::Retryable;
 use eyre::Context;
 use nodes_common::{
     postgres::{CreateSchema, PostgresConfig},
     web3::event_stream::ChainCursor,
 };
 use oprf_core::ddlog_equality::shamir::DLogShareShamir;
-use oprf_types::{OprfKeyId, ShareEpoch, crypto::OprfPublicKey, service::NodeInformation};
-use sqlx::{Acquire, PgExecutor, PgPool, Row as _};
+use oprf_types::{
+    OprfKeyId, ShareEpoch, crypto::OprfPublicKey, retry::RetryPolicy, service::NodeInformation,
+};
+use sqlx::{Acquire, PgConnection, PgExecutor, PgPool, Row as _};
 use tracing::instrument;
 
 use crate::{
@@ -39,8 +41,7 @@ type Result<T> = std::result::Result<T, PostgresDbError>;
 #[derive(Clone, Debug)]
 pub struct PostgresDb {
     pool: PgPool,
-    max_retries: NonZeroUsize,
-    retry_delay: Duration,
+    retry_policy: RetryPolicy,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -75,25 +76,16 @@ impl PostgresDb {
 
         Ok(Self {
             pool,
-            max_retries: db_config.max_retries,
-            retry_delay: db_config.retry_delay,
+            retry_policy: RetryPolicy::new(db_config.max_retries.get(), db_config.retry_delay),
         })
     }
 
-    #[inline]
-    fn backoff_strategy(&self) -> ConstantBackoff {
-        ConstantBuilder::new()
-            .with_delay(self.retry_delay)
-            .with_max_times(self.max_retries.get())
-            .build()
-    }
-
     async fn with_retry<F, Fut, T>(&self, op_name: &str, f: F) -> Result<T>
     where
         F: Fn() -> Fut,
         Fut: Future<Output = Result<T>>,
     {
-        f.retry(self.backoff_strategy())
+        f.retry(self.retry_policy.backoff())
             .sleep(tokio::time::sleep)
             .when(is_retryable_error)
             .notify(|err, duration| {
@@ -259,19 +251,62 @@ impl SecretManager for PostgresDb {
             .await?)
     }
 
+    #[instrument(level = "info", skip(self))]
+    async fn list_oprf_key_ids(&self) -> secret_manager::Result<Vec<OprfKeyId>> {
+        tracing::trace!("listing key ids...");
+        let list_key_ids = || async {
+            let ids: Vec<Vec<u8>> =
+                sqlx::query_scalar("SELECT id FROM shares WHERE deleted = false")
+                    .fetch_all(&self.pool)
+                    .await?;
+            Ok(ids.iter().map(|id| OprfKeyId::from_le_slice(id)).collect())
+        };
+        Ok(self.with_retry("list-oprf-key-ids", list_key_ids).await?)
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn quarantine_oprf_key_material(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("trying to quarantine key-material..");
+
+        let quarantine_transaction = || async {
+            let mut tx = self.pool.begin().await?;
+            let conn = tx.acquire().await?;
+            // use standard isolation level: READ COMMITTED
+            sqlx::query("SET TRANSACTION ISOLATION LEVEL READ COMMITTED")
+                .execute(&mut *conn)
+                .await?;
+            let quarantined_shares = Self::quarantine_share_inner(oprf_key_id, &mut *conn).await?;
+            let deleted_intermediates =
+                Self::delete_intermediates_inner(oprf_key_id, &mut *tx).await?;
+            tx.commit().await?;
+            tracing::trace!(
+                "quarantined {quarantined_shares} shares + deleted {deleted_intermediates} intermediates in postgres"
+            );
+            Ok(())
+        };
+
+        Ok(self
+            .with_retry("quarantine-oprf-key-material", quarantine_transaction)
+            .await?)
+    }
+
     #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id))]
     async fn try_store_keygen_intermediates(
         &self,
         oprf_key_id: OprfKeyId,
         pending_epoch: ShareEpoch,
+        threshold: NonZeroU16,
         intermediate: KeyGenIntermediateValues,
     ) -> secret_manager::Result<KeyGenIntermediateValues> {
         tracing::trace!("trying to store intermediates...");
         let store_intermediates = || async {
             sqlx::query_scalar(
                 "
-                INSERT INTO in_progress_keygens (id, pending_epoch, intermediates)
-                VALUES ($1, $2, $3)
+                INSERT INTO in_progress_keygens (id, pending_epoch, intermediates, threshold)
+                VALUES ($1, $2, $3, $4)
                 ON CONFLICT (id, pending_epoch) DO UPDATE
                 SET intermediates = in_progress_keygens.intermediates
                 RETURNING intermediates;
@@ -281,6 +316,7 @@ impl SecretManager for PostgresDb {
             // Postgres lacks u32; cast to i64 to satisfy SQLx type mapping
             .bind(i64::from(pending_epoch))
             .bind(to_db_ark_serialize_uncompressed(&intermediate).as_slice())
+            .bind(i32::from(threshold.get()))
             .fetch_one(&self.pool)
             .await
             .map(from_db_ark_serialize_uncompressed)?
@@ -395,32 +431,7 @@ impl SecretManager for PostgresDb {
             sqlx::query("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE")
                 .execute(&mut *conn)
                 .await?;
-            // check if we already stored this share - maybe we had to redo this operation so that it is idempotent
-            if Self::get_share_by_epoch_inner(oprf_key_id, epoch, &mut *conn)
-                .await?
-                .is_some()
-            {
-                tracing::warn!("already have this share stored - delete intermediates");
-                Self::delete_intermediates_inner(oprf_key_id, &mut *conn).await?;
-                tx.commit().await?;
-                return Ok(());
-            }
-            let pending_share = Self::fetch_pending_share_inner(oprf_key_id, epoch, &mut *conn)
-                .await?
-                .ok_or_else(|| PostgresDbError::MissingIntermediates(oprf_key_id, epoch))?;
-
-            let rows_affected = Self::store_confirmed_dlog_share_inner(
-                oprf_key_id,
-                epoch,
-                &public_key,
-                &pending_share,
-                &mut *conn,
-            )
-            .await?;
-            if rows_affected != 1 {
-                return Err(PostgresDbError::RefusingToRollbackEpoch);
-            }
-            Self::delete_intermediates_inner(oprf_key_id, &mut *conn).await?;
+            Self::confirm_dlog_share_inner(oprf_key_id, epoch, &public_key, &mut *conn).await?;
             tx.commit().await?;
             Ok(())
         };
@@ -428,9 +439,166 @@ impl SecretManager for PostgresDb {
             .with_retry("confirm-dlog-share", confirm_dlog_share)
             .await?)
     }
+
+    #[instrument(level = "info", skip_all, fields(shares=shares.len()))]
+    async fn confirm_dlog_shares(
+        &self,
+        shares: Vec<(OprfKeyId, ShareEpoch, OprfPublicKey)>,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("storing shares...");
+
+        let shares = secret_manager::latest_epoch_per_key(shares);
+        let confirm_dlog_shares = || async {
+            let mut tx = self.pool.begin().await?;
+            let conn = tx.acquire().await?;
+            // Same isolation level as confirm_dlog_share - all shares are confirmed or none.
+            sqlx::query("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE")
+                .execute(&mut *conn)
+                .await?;
+            Self::confirm_dlog_shares_inner(&shares, &mut *conn).await?;
+            tx.commit().await?;
+            Ok(())
+        };
+        Ok(self
+            .with_retry("confirm-dlog-shares", confirm_dlog_shares)
+            .await?)
+    }
 }
 
 impl PostgresDb {
+    /// Confirms the pending share of `oprf_key_id` for `epoch` on `conn`, which must be a `SERIALIZABLE` transaction. The caller commits.
+    async fn confirm_dlog_share_inner(
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+        public_key: &OprfPublicKey,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        // check if we already stored this share - maybe we had to redo this operation so that it is idempotent
+        if Self::get_share_by_epoch_inner(oprf_key_id, epoch, &mut *conn)
+            .await?
+            .is_some()
+        {
+            tracing::warn!("already have this share stored - delete intermediates");
+            Self::delete_intermediates_inner(oprf_key_id, &mut *conn).await?;
+            return Ok(());
+        }
+        let (pending_share, threshold) =
+            Self::fetch_pending_share_inner(oprf_key_id, epoch, &mut *conn)
+                .await?
+                .ok_or_else(|| PostgresDbError::MissingIntermediates(oprf_key_id, epoch))?;
+
+        let rows_affected = Self::store_confirmed_dlog_share_inner(
+            oprf_key_id,
+            epoch,
+            public_key,
+            &pending_share,
+            threshold,
+            &mut *conn,
+        )
+        .await?;
+        if rows_affected != 1 {
+            return Err(PostgresDbError::RefusingToRollbackEpoch);
+        }
+        Self::delete_intermediates_inner(oprf_key_id, &mut *conn).await?;
+        Ok(())
+    }
+
+    /// Confirms the pending shares of a batch with one statement per step of [`Self::confirm_dlog_share_inner`], independent of the size of the batch. Every key must occur at most once. `conn` must be a `SERIALIZABLE` transaction, the caller commits.
+    async fn confirm_dlog_shares_inner(
+        shares: &[(OprfKeyId, ShareEpoch, OprfPublicKey)],
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let ids = shares
+            .iter()
+            .map(|(oprf_key_id, _, _)| oprf_key_id.to_le_bytes())
+            .collect::<Vec<_>>();
+        // Postgres lacks u32; cast to i64 to satisfy SQLx type mapping
+        let epochs = shares
+            .iter()
+            .map(|(_, epoch, _)| i64::from(*epoch))
+            .collect::<Vec<_>>();
+        // for every share: is it already stored (we may redo this operation) and is it pending?
+        let states = sqlx::query_as::<_, (bool, bool)>(
+            "
+                SELECT
+                    EXISTS (
+                        SELECT 1
+                        FROM shares
+                        WHERE id = batch.id AND epoch = batch.epoch AND deleted = false
+                    ),
+                    EXISTS (
+                        SELECT 1
+                        FROM in_progress_keygens
+                        WHERE id = batch.id
+                          AND pending_epoch = batch.epoch
+                          AND pending_share IS NOT NULL
+                    )
+                FROM UNNEST($1::bytea[], $2::bigint[]) WITH ORDINALITY AS batch(id, epoch, position)
+                ORDER BY batch.position;
+            ",
+        )
+        .bind(&ids)
+        .bind(&epochs)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut pending = Vec::with_capacity(shares.len());
+        for ((oprf_key_id, epoch, public_key), (stored, is_pending)) in shares.iter().zip(states) {
+            if stored {
+                tracing::warn!(%oprf_key_id, "already have this share stored - delete intermediates");
+            } else if is_pending {
+                pending.push((oprf_key_id.to_le_bytes(), i64::from(*epoch), public_key));
+            } else {
+                return Err(PostgresDbError::MissingIntermediates(*oprf_key_id, *epoch));
+            }
+        }
+
+        let (pending_ids, pending_epochs, public_keys) = pending.into_iter().fold(
+            (Vec::new(), Vec::new(), Vec::new()),
+            |(mut ids, mut epochs, mut public_keys), (id, epoch, public_key)| {
+                ids.push(id);
+                epochs.push(epoch);
+                public_keys.push(to_db_ark_serialize_uncompressed(public_key).to_vec());
+                (ids, epochs, public_keys)
+            },
+        );
+        let rows_affected = sqlx::query(
+            "
+                INSERT INTO shares (id, share, epoch, public_key, threshold)
+                SELECT keygen.id, keygen.pending_share, keygen.pending_epoch, batch.public_key, keygen.threshold
+                FROM UNNEST($1::bytea[], $2::bigint[], $3::bytea[]) AS batch(id, epoch, public_key),
+                    in_progress_keygens AS keygen
+                WHERE keygen.id = batch.id AND keygen.pending_epoch = batch.epoch
+                ON CONFLICT (id)
+                DO UPDATE SET
+                    share = EXCLUDED.share,
+                    epoch = EXCLUDED.epoch,
+                    public_key = EXCLUDED.public_key,
+                    threshold = EXCLUDED.threshold
+                WHERE
+                    shares.epoch < EXCLUDED.epoch;
+            ",
+        )
+        .bind(&pending_ids)
+        .bind(&pending_epochs)
+        .bind(&public_keys)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        if rows_affected != pending_ids.len() as u64 {
+            return Err(PostgresDbError::RefusingToRollbackEpoch);
+        }
+        sqlx::query(
+            "
+                DELETE FROM in_progress_keygens
+                WHERE id = ANY($1);
+            ",
+        )
+        .bind(&ids)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
     async fn get_share_by_epoch_inner(
         oprf_key_id: OprfKeyId,
         epoch: ShareEpoch,
@@ -471,14 +639,49 @@ impl PostgresDb {
         .rows_affected())
     }
 
+    /// Moves the confirmed share of `oprf_key_id` from `shares` to `quarantined_shares` on `conn`, which must be a transaction. The caller commits.
+    async fn quarantine_share_inner(
+        oprf_key_id: OprfKeyId,
+        conn: &mut PgConnection,
+    ) -> Result<u64> {
+        sqlx::query(
+            "
+                INSERT INTO quarantined_shares (id, share, epoch, public_key, threshold)
+                SELECT id, share, epoch, public_key, threshold
+                FROM shares
+                WHERE id = $1 AND deleted = false
+                ON CONFLICT (id)
+                DO UPDATE SET
+                    share = EXCLUDED.share,
+                    epoch = EXCLUDED.epoch,
+                    public_key = EXCLUDED.public_key,
+                    threshold = EXCLUDED.threshold,
+                    quarantined_at = now();
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .execute(&mut *conn)
+        .await?;
+        Ok(sqlx::query(
+            "
+                DELETE FROM shares
+                WHERE id = $1 AND deleted = false;
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .execute(&mut *conn)
+        .await?
+        .rows_affected())
+    }
+
     async fn fetch_pending_share_inner(
         oprf_key_id: OprfKeyId,
         pending_epoch: ShareEpoch,
         conn: impl PgExecutor<'_>,
-    ) -> Result<Option<DLogShareShamir>> {
-        sqlx::query_scalar(
+    ) -> Result<Option<(DLogShareShamir, Option<i32>)>> {
+        sqlx::query_as::<_, (Option<Vec<u8>>, Option<i32>)>(
             "
-                SELECT pending_share
+                SELECT pending_share, threshold
                 FROM in_progress_keygens
                 WHERE id = $1
                   AND pending_epoch = $2;
@@ -489,8 +692,8 @@ impl PostgresDb {
         .bind(i64::from(pending_epoch))
         .fetch_optional(conn)
         .await?
-        .flatten()
-        .map(from_db_ark_serialize_uncompressed)
+        .and_then(|(share, threshold)| share.map(|share| (share, threshold)))
+        .map(|(share, threshold)| Ok((from_db_ark_serialize_uncompressed(share)?, threshold)))
         .transpose()
     }
 
@@ -499,17 +702,19 @@ impl PostgresDb {
         pending_epoch: ShareEpoch,
         public_key: &OprfPublicKey,
         share: &DLogShareShamir,
+        threshold: Option<i32>,
         conn: impl PgExecutor<'_>,
     ) -> Result<u64> {
         Ok(sqlx::query(
             "
-                INSERT INTO shares (id, share, epoch, public_key)
-                VALUES ($1, $2, $3, $4)
+                INSERT INTO shares (id, share, epoch, public_key, threshold)
+                VALUES ($1, $2, $3, $4, $5)
                 ON CONFLICT (id)
                 DO UPDATE SET
                     share = EXCLUDED.share,
                     epoch = EXCLUDED.epoch,
-                    public_key = EXCLUDED.public_key
+                    public_key = EXCLUDED.public_key,
+                    threshold = EXCLUDED.threshold
                 WHERE
                     shares.epoch < EXCLUDED.epoch;
             ",
@@ -519,6 +724,7 @@ impl PostgresDb {
         // Postgres lacks u32; cast to i64 to satisfy SQLx type mapping
         .bind(i64::from(pending_epoch))
         .bind(to_db_ark_serialize_uncompressed(public_key).as_slice())
+        .bind(threshold)
         .execute(conn)
         .await?
         .rows_affected())
@@ -294,6 +294,118 @@ async fn confirm_without_pending_share_fails() -> eyre::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn confirm_dlog_shares_confirms_all_or_nothing() -> eyre::Result<()> {
+    let (secret_manager, connection_string, schema) = postgres_secret_manager().await?;
+    let mut conn = nodes_common::test_utils::open_pg_connection(connection_string, &schema).await?;
+
+    let epoch = ShareEpoch::default();
+    let public_key = OprfPublicKey::new(rand::random());
+    let staged = [
+        OprfKeyId::new(U160::from(42)),
+        OprfKeyId::new(U160::from(43)),
+    ];
+    let missing = OprfKeyId::new(U160::from(44));
+    for oprf_key_id in staged {
+        let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+        setup_pending_share(&mut conn, oprf_key_id, epoch, &share).await?;
+    }
+
+    // one share without intermediates rolls back the whole batch
+    let err = secret_manager
+        .confirm_dlog_shares(
+            [staged[0], missing, staged[1]]
+                .into_iter()
+                .map(|oprf_key_id| (oprf_key_id, epoch, public_key))
+                .collect(),
+        )
+        .await
+        .expect_err("confirm without pending share should fail");
+    assert!(
+        matches!(err, SecretManagerError::MissingIntermediates(id, _) if id == missing),
+        "Should be missing intermediates but is {err}"
+    );
+    assert!(
+        all_rows(&mut conn).await?.is_empty(),
+        "Should confirm no share"
+    );
+    assert_eq!(intermediate_count(staged[0], &mut conn).await?, 1);
+
+    secret_manager
+        .confirm_dlog_shares(
+            staged
+                .into_iter()
+                .map(|oprf_key_id| (oprf_key_id, epoch, public_key))
+                .collect(),
+        )
+        .await?;
+    for oprf_key_id in staged {
+        assert!(
+            secret_manager
+                .get_share_by_epoch(oprf_key_id, epoch)
+                .await?
+                .is_some(),
+            "Should confirm {oprf_key_id}"
+        );
+        assert_eq!(intermediate_count(oprf_key_id, &mut conn).await?, 0);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirm_dlog_shares_keeps_latest_epoch_and_is_idempotent() -> eyre::Result<()> {
+    let (secret_manager, connection_string, schema) = postgres_secret_manager().await?;
+    let mut conn = nodes_common::test_utils::open_pg_connection(connection_string, &schema).await?;
+
+    let epoch = ShareEpoch::default();
+    let public_key = OprfPublicKey::new(rand::random());
+    let reshared = OprfKeyId::new(U160::from(42));
+    let other = OprfKeyId::new(U160::from(43));
+    let latest_share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    setup_pending_share(
+        &mut conn,
+        reshared,
+        epoch,
+        &DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>()),
+    )
+    .await?;
+    setup_pending_share(&mut conn, reshared, epoch.next(), &latest_share).await?;
+    setup_pending_share(
+        &mut conn,
+        other,
+        epoch,
+        &DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>()),
+    )
+    .await?;
+
+    let batch = vec![
+        (reshared, epoch, public_key),
+        (other, epoch, public_key),
+        (reshared, epoch.next(), public_key),
+    ];
+    secret_manager.confirm_dlog_shares(batch.clone()).await?;
+    assert_eq!(
+        secret_manager
+            .get_share_by_epoch(reshared, epoch.next())
+            .await?
+            .map(ark_babyjubjub::Fr::from),
+        Some(ark_babyjubjub::Fr::from(latest_share)),
+        "Should confirm the latest epoch"
+    );
+    assert!(
+        secret_manager
+            .get_share_by_epoch(other, epoch)
+            .await?
+            .is_some(),
+        "Should confirm {other}"
+    );
+    assert_eq!(intermediate_count(reshared, &mut conn).await?, 0);
+
+    // replaying the batch (e.g. after a crash before the cursor was stored) is a no-op
+    secret_manager.confirm_dlog_shares(batch).await?;
+    Ok(())
+}
+
 #[tokio::test]
 async fn abort_keygen_is_idempotent_and_preserves_confirmed_share() -> eyre::Result<()> {
     let (secret_manager, connection_string, schema) = postgres_secret_manager().await?;
@@ -759,6 +871,64 @@ async fn confirm_deleted_share_returns_store_on_deleted_share() -> eyre::Result<
     Ok(())
 }
 
+#[tokio::test]
+async fn list_and_quarantine_keys() -> eyre::Result<()> {
+    let (secret_manager, connection_string, schema) = postgres_secret_manager().await?;
+    let mut conn = nodes_common::test_utils::open_pg_connection(connection_string, &schema).await?;
+
+    let keys = [
+        OprfKeyId::new(U160::from(42)),
+        OprfKeyId::new(U160::from(43)),
+        OprfKeyId::new(U160::from(44)),
+    ];
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    for oprf_key_id in keys {
+        let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+        setup_pending_share(&mut conn, oprf_key_id, epoch, &share).await?;
+        secret_manager
+            .confirm_dlog_share(oprf_key_id, epoch, public_key)
+            .await?;
+    }
+    secret_manager.delete_oprf_key_material(keys[2]).await?;
+
+    let mut listed = secret_manager.list_oprf_key_ids().await?;
+    listed.sort();
+    assert_eq!(
+        listed,
+        keys[..2],
+        "Should list the keys that are not deleted"
+    );
+
+    insert_intermediate_row(keys[0], epoch.next(), None, vec![0_u8], &mut conn).await?;
+    secret_manager.quarantine_oprf_key_material(keys[0]).await?;
+    // quarantining twice is idempotent
+    secret_manager.quarantine_oprf_key_material(keys[0]).await?;
+
+    assert_eq!(
+        secret_manager.list_oprf_key_ids().await?,
+        [keys[1]],
+        "Should not list the quarantined key"
+    );
+    assert_eq!(
+        intermediate_count(keys[0], &mut conn).await?,
+        0,
+        "Should remove the intermediates of the quarantined key"
+    );
+    let quarantined: Option<i64> =
+        sqlx::query_scalar("SELECT epoch FROM quarantined_shares WHERE id = $1")
+            .bind(keys[0].to_le_bytes())
+            .fetch_optional(&mut conn)
+            .await?;
+    assert_eq!(quarantined, Some(42), "Should keep the quarantined share");
+    assert_eq!(
+        all_rows(&mut conn).await?.len(),
+        2,
+        "Should move the quarantined share out of the shares"
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_delete() -> eyre::Result<()> {
     let (secret_manager, connection_string, schema) = postgres_secret_manager().await?;
@@ -0,0 +1,569 @@
+//! [`SecretManager`] on top of a remote key-value secret store.
+//!
+//! Secret stores like Google Cloud Secret Manager ([`crate::gcp`]), Azure Key Vault
+//! ([`crate::azure`]) and `HashiCorp` Vault ([`crate::vault`]) only provide read, write, and delete
+//! for single secrets. [`RemoteSecretManager`] implements the key-gen state machine on top of such
+//! a store; the backend modules only implement the transport and authentication.
+//!
+//! Every value is stored as the latest version of one secret, named after the configured prefix:
+//!
+//! | Secret                                   | Content                                     |
+//! |------------------------------------------|---------------------------------------------|
+//! | `{prefix}-node-information`              | the [`NodeInformation`] of this node        |
+//! | `{prefix}-share-{oprf_key_id}`           | the confirmed share with epoch and key      |
+//! | `{prefix}-keygen-{oprf_key_id}`          | intermediates and pending shares by epoch   |
+//!
+//! Values are JSON documents, binary values are base64 encoded. A secret with the value `null` is
+//! treated like a missing secret, which allows stores with soft-delete to "delete" by overwriting.
+//!
+//! Secret stores have no transactions, so [`RemoteSecretManager`] assumes it is the only writer for
+//! its prefix, i.e., exactly one key-gen instance per party. The chain cursor is not a secret and
+//! therefore still needs a [`ChainCursorStorage`](crate::event_cursor_store::ChainCursorStorage)
+//! like Postgres.
+
+use std::{collections::BTreeMap, num::NonZeroU16, time::Duration};
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use async_trait::async_trait;
+use backon::Retryable;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch, crypto::OprfPublicKey, retry::RetryPolicy, service::NodeInformation,
+};
+use reqwest::StatusCode;
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Mutex, time::Instant};
+use tracing::instrument;
+use zeroize::Zeroizing;
+
+use crate::secret_manager::{self, KeyGenIntermediateValues, SecretManager, SecretManagerError};
+
+pub(crate) use store::{RemoteError, SecretStore};
+
+type Result<T> = std::result::Result<T, RemoteError>;
+
+mod store {
+    use std::time::Duration;
+
+    use oprf_types::{OprfKeyId, ShareEpoch};
+    use reqwest::StatusCode;
+    use zeroize::Zeroizing;
+
+    /// Errors of a [`SecretStore`] and the [`super::RemoteSecretManager`] built on top of it.
+    #[derive(Debug, thiserror::Error)]
+    pub enum RemoteError {
+        #[error("Intermediates NOT stored for {0}/{1} - stuck")]
+        MissingIntermediates(OprfKeyId, ShareEpoch),
+        #[error("Refusing to overwrite newer share")]
+        RefusingToRollbackEpoch,
+        #[error("Refusing to store share for deleted key")]
+        StoreOnDeletedShare,
+        #[error(transparent)]
+        Http(#[from] reqwest::Error),
+        #[error("secret store returned {status}: {body}")]
+        Status {
+            status: StatusCode,
+            body: String,
+            retry_after: Option<Duration>,
+        },
+        #[error("internal error: {0:?}")]
+        Internal(#[from] eyre::Report),
+    }
+
+    /// A remote key-value store for secrets. Sealed, implemented by the backend modules.
+    pub trait SecretStore: Send + Sync {
+        /// Reads the latest value of the secret, `None` if the secret does not exist.
+        fn read(
+            &self,
+            secret_id: &str,
+        ) -> impl Future<Output = Result<Option<Zeroizing<Vec<u8>>>, RemoteError>> + Send;
+
+        /// Stores a new value for the secret, creating the secret if necessary.
+        fn write(
+            &self,
+            secret_id: &str,
+            data: &[u8],
+        ) -> impl Future<Output = Result<(), RemoteError>> + Send;
+
+        /// Removes the secret. Removing a missing secret is not an error.
+        fn remove(&self, secret_id: &str) -> impl Future<Output = Result<(), RemoteError>> + Send;
+    }
+}
+
+impl From<RemoteError> for SecretManagerError {
+    fn from(value: RemoteError) -> Self {
+        match value {
+            RemoteError::MissingIntermediates(oprf_key_id, share_epoch) => {
+                Self::MissingIntermediates(oprf_key_id, share_epoch)
+            }
+            RemoteError::RefusingToRollbackEpoch => Self::RefusingToRollbackEpoch,
+            RemoteError::StoreOnDeletedShare => Self::StoreOnDeletedShare,
+            RemoteError::Internal(report) => Self::Internal(report),
+            err @ (RemoteError::Http(_) | RemoteError::Status { .. }) => {
+                Self::Internal(eyre::Report::from(err))
+            }
+        }
+    }
+}
+
+/// A cached OAuth access token, e.g., from a workload or managed identity.
+pub(crate) struct TokenCache(Mutex<Option<(SecretString, Instant)>>);
+
+impl TokenCache {
+    pub(crate) fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    /// Returns the cached token, or fetches a new one with `fetch`, which returns the token and its lifetime.
+    pub(crate) async fn get<F, Fut>(&self, fetch: F) -> Result<SecretString>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(SecretString, Duration)>>,
+    {
+        let mut cached = self.0.lock().await;
+        if let Some((token, refresh_at)) = cached.as_ref()
+            && *refresh_at > Instant::now()
+        {
+            return Ok(token.clone());
+        }
+        tracing::debug!("fetching new access token");
+        let (token, expires_in) = fetch().await?;
+        // refresh a minute before the token expires
+        let refresh_at = Instant::now() + expires_in.saturating_sub(Duration::from_mins(1));
+        *cached = Some((token.clone(), refresh_at));
+        Ok(token)
+    }
+}
+
+/// Returns the response if it is successful, a [`RemoteError::Status`] otherwise.
+pub(crate) async fn error_for_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs);
+    let body = response.text().await.unwrap_or_default();
+    Err(RemoteError::Status {
+        status,
+        body,
+        retry_after,
+    })
+}
+
+/// The stored content of the `{prefix}-node-information` secret.
+#[derive(Serialize, Deserialize)]
+struct StoredNodeInformation {
+    eth_address: String,
+    party_id: u16,
+    threshold: u16,
+}
+
+/// The stored content of a `{prefix}-share-{oprf_key_id}` secret.
+#[derive(Serialize, Deserialize)]
+struct StoredShare {
+    epoch: u32,
+    #[serde(with = "base64_bytes")]
+    share: Option<Zeroizing<Vec<u8>>>,
+    #[serde(with = "base64_bytes")]
+    public_key: Option<Zeroizing<Vec<u8>>>,
+    deleted: bool,
+    /// Missing for keys generated before the threshold was stored per key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<u16>,
+}
+
+/// The stored state of one in-progress key-gen.
+#[derive(Serialize, Deserialize)]
+struct StoredKeyGen {
+    #[serde(with = "base64_bytes")]
+    intermediates: Option<Zeroizing<Vec<u8>>>,
+    #[serde(with = "base64_bytes")]
+    pending_share: Option<Zeroizing<Vec<u8>>>,
+    #[serde(default)]
+    threshold: Option<u16>,
+}
+
+/// Implementation of [`SecretManager`] on top of a remote secret store.
+///
+/// See the [module docs](self) for the layout of the secrets.
+pub struct RemoteSecretManager<S> {
+    store: S,
+    secret_prefix: String,
+    wallet_private_key_secret: Option<String>,
+    retry_policy: RetryPolicy,
+}
+
+impl<S> std::fmt::Debug for RemoteSecretManager<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteSecretManager")
+            .field("secret_prefix", &self.secret_prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: SecretStore> RemoteSecretManager<S> {
+    pub(crate) fn new(
+        store: S,
+        secret_prefix: String,
+        wallet_private_key_secret: Option<String>,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            store,
+            secret_prefix,
+            wallet_private_key_secret,
+            retry_policy,
+        }
+    }
+
+    pub(crate) fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Loads the wallet private key from the configured wallet private key secret.
+    ///
+    /// Returns `None` if no secret is configured.
+    ///
+    /// # Errors
+    /// Returns an error if the secret cannot be accessed or does not exist.
+    #[instrument(level = "info", skip_all)]
+    pub async fn load_wallet_private_key(&self) -> eyre::Result<Option<SecretString>> {
+        let Some(secret_id) = &self.wallet_private_key_secret else {
+            return Ok(None);
+        };
+        let payload = self
+            .with_retry("load-wallet-private-key", || self.store.read(secret_id))
+            .await?
+            .ok_or_else(|| eyre::eyre!("wallet private key secret {secret_id} does not exist"))?;
+        let private_key = String::from_utf8(payload.to_vec())
+            .map_err(|_| eyre::eyre!("wallet private key secret {secret_id} is not UTF-8"))?;
+        Ok(Some(SecretString::from(private_key.trim().to_owned())))
+    }
+
+    pub(crate) async fn with_retry<F, Fut, T>(&self, op_name: &str, f: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        f.retry(self.retry_policy.backoff())
+            .sleep(tokio::time::sleep)
+            .when(is_retryable_error)
+            // honor the store's throttling hints
+            .adjust(|err, delay| match err {
+                RemoteError::Status {
+                    retry_after: Some(retry_after),
+                    ..
+                } => delay.map(|delay| delay.max(*retry_after)),
+                _ => delay,
+            })
+            .notify(|err, duration| {
+                tracing::warn!(%err, "Retrying {op_name} in secret store after {duration:?}");
+            })
+            .await
+    }
+
+    fn node_information_secret(&self) -> String {
+        format!("{}-node-information", self.secret_prefix)
+    }
+
+    fn share_secret(&self, oprf_key_id: OprfKeyId) -> String {
+        format!("{}-share-{oprf_key_id}", self.secret_prefix)
+    }
+
+    pub(crate) fn keygen_secret(&self, oprf_key_id: OprfKeyId) -> String {
+        format!("{}-keygen-{oprf_key_id}", self.secret_prefix)
+    }
+
+    async fn load_json<T: for<'de> Deserialize<'de>>(&self, secret_id: &str) -> Result<Option<T>> {
+        let Some(payload) = self
+            .with_retry("read-secret", || self.store.read(secret_id))
+            .await?
+        else {
+            return Ok(None);
+        };
+        serde_json::from_slice::<Option<T>>(&payload)
+            .map_err(|e| RemoteError::from(eyre::eyre!("secret {secret_id} not sane: {e}")))
+    }
+
+    async fn store_json<T: Serialize>(&self, secret_id: &str, value: &T) -> Result<()> {
+        let payload = Zeroizing::new(
+            serde_json::to_vec(value).map_err(|e| RemoteError::from(eyre::Report::from(e)))?,
+        );
+        self.with_retry("write-secret", || self.store.write(secret_id, &payload))
+            .await
+    }
+
+    async fn load_keygens(&self, oprf_key_id: OprfKeyId) -> Result<BTreeMap<u32, StoredKeyGen>> {
+        Ok(self
+            .load_json(&self.keygen_secret(oprf_key_id))
+            .await?
+            .unwrap_or_default())
+    }
+
+    async fn load_share(&self, oprf_key_id: OprfKeyId) -> Result<Option<StoredShare>> {
+        self.load_json(&self.share_secret(oprf_key_id)).await
+    }
+
+    async fn remove_keygens(&self, oprf_key_id: OprfKeyId) -> Result<()> {
+        let secret_id = self.keygen_secret(oprf_key_id);
+        self.with_retry("remove-secret", || self.store.remove(&secret_id))
+            .await
+    }
+}
+
+#[async_trait]
+impl<S: SecretStore> SecretManager for RemoteSecretManager<S> {
+    #[instrument(level = "info", skip(self))]
+    async fn store_node_information(
+        &self,
+        node_information: NodeInformation,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("storing node information...");
+        let stored = StoredNodeInformation {
+            eth_address: node_information.address().to_owned(),
+            party_id: node_information.party_id().into_inner(),
+            threshold: node_information.threshold().get(),
+        };
+        self.store_json(&self.node_information_secret(), &stored)
+            .await?;
+        tracing::debug!("successfully stored node-information");
+        Ok(())
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn get_share_by_epoch(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> secret_manager::Result<Option<DLogShareShamir>> {
+        tracing::trace!("loading share...");
+        match self.load_share(oprf_key_id).await? {
+            Some(StoredShare {
+                epoch: stored_epoch,
+                share: Some(share),
+                deleted: false,
+                ..
+            }) if ShareEpoch::new(stored_epoch) == epoch => Ok(Some(deserialize(&share)?)),
+            _ => Ok(None),
+        }
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn delete_oprf_key_material(&self, oprf_key_id: OprfKeyId) -> secret_manager::Result<()> {
+        tracing::trace!("trying to delete key-material..");
+        if let Some(stored) = self.load_share(oprf_key_id).await?
+            && !stored.deleted
+        {
+            // keep a tombstone so that we never store a share for this key again
+            let tombstone = StoredShare {
+                share: None,
+                deleted: true,
+                ..stored
+            };
+            self.store_json(&self.share_secret(oprf_key_id), &tombstone)
+                .await?;
+        }
+        self.remove_keygens(oprf_key_id).await?;
+        tracing::trace!("deleted key-material from secret store");
+        Ok(())
+    }
+
+    #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id))]
+    async fn try_store_keygen_intermediates(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+        threshold: NonZeroU16,
+        intermediate: KeyGenIntermediateValues,
+    ) -> secret_manager::Result<KeyGenIntermediateValues> {
+        tracing::trace!("trying to store intermediates...");
+        let mut keygens = self.load_keygens(oprf_key_id).await?;
+        if let Some(StoredKeyGen {
+            intermediates: Some(stored),
+            ..
+        }) = keygens.get(&pending_epoch.into_inner())
+        {
+            tracing::debug!("intermediates already stored - reusing them");
+            return Ok(deserialize(stored)?);
+        }
+        keygens.insert(
+            pending_epoch.into_inner(),
+            StoredKeyGen {
+                intermediates: Some(serialize(&intermediate)),
+                pending_share: None,
+                threshold: Some(threshold.get()),
+            },
+        );
+        self.store_json(&self.keygen_secret(oprf_key_id), &keygens)
+            .await?;
+        Ok(intermediate)
+    }
+
+    #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id))]
+    async fn fetch_keygen_intermediates(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+    ) -> secret_manager::Result<KeyGenIntermediateValues> {
+        tracing::trace!("trying to fetch intermediates...");
+        match self
+            .load_keygens(oprf_key_id)
+            .await?
+            .get(&pending_epoch.into_inner())
+        {
+            Some(StoredKeyGen {
+                intermediates: Some(intermediates),
+                ..
+            }) => Ok(deserialize(intermediates)?),
+            _ => Err(SecretManagerError::MissingIntermediates(
+                oprf_key_id,
+                pending_epoch,
+            )),
+        }
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn abort_keygen(&self, oprf_key_id: OprfKeyId) -> secret_manager::Result<()> {
+        tracing::trace!("trying to abort key-gen...");
+        self.remove_keygens(oprf_key_id).await?;
+        tracing::debug!("aborted key-gen in secret store");
+        Ok(())
+    }
+
+    #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id))]
+    async fn store_pending_dlog_share(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+        share: DLogShareShamir,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("store pending dlog-share..");
+        let mut keygens = self.load_keygens(oprf_key_id).await?;
+        let Some(keygen) = keygens.get_mut(&pending_epoch.into_inner()) else {
+            tracing::warn!("cannot store pending share because no matching intermediates exist");
+            return Err(SecretManagerError::MissingIntermediates(
+                oprf_key_id,
+                pending_epoch,
+            ));
+        };
+        keygen.pending_share = Some(serialize(&share));
+        self.store_json(&self.keygen_secret(oprf_key_id), &keygens)
+            .await?;
+        tracing::debug!("successfully stored pending dlog share");
+        Ok(())
+    }
+
+    #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id, epoch=%epoch))]
+    async fn confirm_dlog_share(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+        public_key: OprfPublicKey,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("storing share...");
+        let stored = self.load_share(oprf_key_id).await?;
+        if let Some(stored) = &stored
+            && !stored.deleted
+            && ShareEpoch::new(stored.epoch) == epoch
+        {
+            // we may have to redo this operation, so it must be idempotent
+            tracing::warn!("already have this share stored - delete intermediates");
+            self.remove_keygens(oprf_key_id).await?;
+            return Ok(());
+        }
+        let (pending_share, threshold) = self
+            .load_keygens(oprf_key_id)
+            .await?
+            .remove(&epoch.into_inner())
+            .and_then(|keygen| Some((keygen.pending_share?, keygen.threshold)))
+            .ok_or(RemoteError::MissingIntermediates(oprf_key_id, epoch))?;
+        match stored {
+            Some(stored) if stored.deleted => return Err(RemoteError::StoreOnDeletedShare.into()),
+            Some(stored) if ShareEpoch::new(stored.epoch) >= epoch => {
+                return Err(RemoteError::RefusingToRollbackEpoch.into());
+            }
+            _ => {}
+        }
+        let confirmed = StoredShare {
+            epoch: epoch.into_inner(),
+            share: Some(pending_share),
+            public_key: Some(serialize(&public_key)),
+            deleted: false,
+            threshold,
+        };
+        self.store_json(&self.share_secret(oprf_key_id), &confirmed)
+            .await?;
+        self.remove_keygens(oprf_key_id).await?;
+        Ok(())
+    }
+}
+
+#[inline]
+fn serialize<T: CanonicalSerialize>(t: &T) -> Zeroizing<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(t.uncompressed_size());
+    t.serialize_uncompressed(&mut bytes).expect("Can serialize");
+    Zeroizing::new(bytes)
+}
+
+#[inline]
+fn deserialize<T: CanonicalDeserialize>(b: &[u8]) -> Result<T> {
+    T::deserialize_uncompressed(b).map_err(|e| {
+        RemoteError::from(eyre::eyre!(
+            "Cannot deserialize bytes: secret not sane: {e}"
+        ))
+    })
+}
+
+#[inline]
+fn is_retryable_error(e: &RemoteError) -> bool {
+    match e {
+        RemoteError::Http(err) => err.is_timeout() || err.is_connect() || err.is_request(),
+        RemoteError::Status { status, .. } => {
+            *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+        }
+        _ => false,
+    }
+}
+
+/// Serializes optional bytes as base64 strings.
+mod base64_bytes {
+    use base64::Engine as _;
+    use serde::{Deserialize as _, Deserializer, Serializer};
+    use zeroize::Zeroizing;
+
+    use super::BASE64;
+
+    #[expect(
+        clippy::ref_option,
+        reason = "signature required by serde's `with` attribute"
+    )]
+    pub(super) fn serialize<S: Serializer>(
+        bytes: &Option<Zeroizing<Vec<u8>>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => serializer.serialize_some(&BASE64.encode(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Zeroizing<Vec<u8>>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|encoded| {
+                BASE64
+                    .decode(encoded)
+                    .map(Zeroizing::new)
+                    .map_err(serde::de::Error::custom)
+            })
+            .transpose()
+    }
+}
@@ -8,13 +8,17 @@
 //!
 //! # Services overview
 //!
+//! - [`abort_notifier`] – hook called after a stalled key-gen/reshare run was abandoned.
 //! - [`key_event_watcher`] – watches the blockchain for key-generation events.
+//! - [`orphaned_keys`] – compares the stored keys with the registry at startup and handles the shares of deleted keys.
 //! - [`secret_gen`] – handles multi-round secret generation protocols.
 //! - [`secret_manager`] – stores and retrieves secrets.
 //! - [`transaction_handler`] – handles transaction submitting including error handling and retry when the RPC breaks down.
 //! - [`event_cursor_store`] – persists the chain event cursor so that `key_event_watcher` can resume backfill from the last processed `(block, log_index)` after a restart.
+pub mod abort_notifier;
 pub mod event_cursor_store;
 pub(crate) mod key_event_watcher;
+pub(crate) mod orphaned_keys;
 pub(crate) mod secret_gen;
 pub mod secret_manager;
-pub(crate) mod transaction_handler;
+pub mod transaction_handler;
@@ -0,0 +1,71 @@
+//! Notification hook for abandoned key-gen/reshare runs.
+//!
+//! The key-event watcher evicts the intermediates of a run that did not finish within the configured `protocol_timeout` (see [`crate::config::OprfKeyGenServiceConfig::protocol_timeout`]). Afterwards, it calls the [`AbortNotifier`] provided via [`crate::OprfKeyGenBuilder::abort_notifier`], so that the run can also be aborted on-chain. The notifier is also called for runs aborted with the admin endpoint `DELETE /keygen/{oprf_key_id}` (see `admin_endpoints` in [`crate::config::OprfKeyGenServiceConfig`]). Without a notifier, the run stays open on-chain until an admin aborts it.
+//!
+//! [`ContractAbortNotifier`] calls `abortKeyGen` on the `OprfKeyRegistry`. The contract only accepts this call from its key-gen admins (see `addKeyGenAdmin`), so use it only with a key-gen admin wallet.
+
+use std::sync::Arc;
+
+use alloy::{primitives::Address, providers::DynProvider};
+use async_trait::async_trait;
+use eyre::Context as _;
+use oprf_types::{OprfKeyId, ShareEpoch, chain::OprfKeyRegistry::OprfKeyRegistryInstance};
+
+/// Dynamic trait object for the abort notifier.
+pub type AbortNotifierService = Arc<dyn AbortNotifier + Send + Sync>;
+
+/// Hook called after the key-gen service abandoned a stalled run.
+#[async_trait]
+pub trait AbortNotifier {
+    /// Called after the intermediates of the run for `oprf_key_id` and `pending_epoch` were evicted.
+    ///
+    /// Errors are logged by the caller and do not stop the key-event watcher.
+    async fn notify_abandoned(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+    ) -> eyre::Result<()>;
+}
+
+/// [`AbortNotifier`] that aborts the run on-chain with `abortKeyGen`.
+pub struct ContractAbortNotifier {
+    contract: OprfKeyRegistryInstance<DynProvider>,
+}
+
+impl ContractAbortNotifier {
+    /// Creates a notifier calling the `OprfKeyRegistry` at `contract_address`.
+    ///
+    /// The `provider` must be configured with the wallet of a key-gen admin of the contract.
+    #[must_use]
+    pub fn new(contract_address: Address, provider: DynProvider) -> Self {
+        Self {
+            contract: OprfKeyRegistryInstance::new(contract_address, provider),
+        }
+    }
+}
+
+#[async_trait]
+impl AbortNotifier for ContractAbortNotifier {
+    async fn notify_abandoned(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+    ) -> eyre::Result<()> {
+        let receipt = self
+            .contract
+            .abortKeyGen(oprf_key_id.into_inner())
+            .send()
+            .await
+            .context("while sending abortKeyGen")?
+            .get_receipt()
+            .await
+            .context("while waiting for abortKeyGen receipt")?;
+        eyre::ensure!(
+            receipt.status(),
+            "abortKeyGen for {oprf_key_id} reverted: {}",
+            receipt.transaction_hash
+        );
+        tracing::info!("aborted {oprf_key_id} with epoch {pending_epoch} on-chain");
+        Ok(())
+    }
+}
@@ -11,7 +11,10 @@
 //!   [`events::KeyRegistryEvent`] enum.
 //! * **[`handler`]** —  that calls [`DLogSecretGenService`],
 //!   reads peer/consumer public keys from the contract, and submits contributions back
-//!   via [`TransactionHandler`].
+//!   via the [`TransactionSubmitterService`].
+//! * **[`deadlines`]** — tracks the deadline of every in-progress run.
+//! * **[`epochs`]** — tracks the latest finalized epoch of every key.
+//! * **[`admin`]** — lists and aborts the in-progress runs on request of the admin API.
 //!
 //! The watcher loads the persisted [`ChainCursor`] from [`ChainCursorService`] on startup and
 //! passes it to the event stream so backfill resumes from the last processed `(block, log_index)`.
@@ -19,27 +22,61 @@
 //! soft-error downgrade in [`handle_soft_errors`].  Hard errors propagate immediately and the
 //! cursor is **not** advanced, causing the watcher task to abort and restart from the last
 //! successfully stored position.
+//!
+//! Consecutive finalize events that the event stream returns at once (e.g., while replaying the
+//! backfill after downtime) are confirmed with a single secret-manager write (see
+//! [`crate::secret_manager::SecretManager::confirm_dlog_shares`]) and the cursor is advanced once
+//! after all of them.
+//!
+//! Runs that did not finish within the configured `protocol_timeout` are abandoned: the watcher
+//! evicts their intermediates between two events and calls the optional [`AbortNotifierService`].
+//! Admins can abort a run before its deadline in the same way (see [`admin`]).
+//!
+//! Events of a run for an epoch at or before the latest finalized epoch of their key (e.g., a
+//! finalize of epoch `N` that arrives after the finalize of `N + 1`) are skipped, so the epoch of a
+//! key never regresses. Skipped events are counted in the `taceo.oprf.key_gen.chain.events.reordered`
+//! metric.
+//!
+//! Logs at or before the last processed cursor (e.g., returned twice where the backfill and the
+//! live subscription overlap) are skipped, so every event is handled exactly once and in chain
+//! order. If the RPC provider rejects the block range of the backfill, lower
+//! `event_stream_config.chunk_size` to the maximum range of the provider.
+//!
+//! If the event stream ends or fails (e.g., because the WebSocket connection dropped), the
+//! watcher resubscribes with exponential backoff (see [`resubscribe`]). The new event stream
+//! backfills the missed logs with `eth_getLogs` from the last handled event. Only after
+//! `max_resubscribe_attempts` consecutive failed attempts does the watcher stop and cancel the
+//! service.
 
-use std::{
-    num::NonZeroU16,
-    sync::{
-        Arc,
-        atomic::{AtomicBool, Ordering},
-    },
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
 };
 
+use std::time::Duration;
+
 use crate::{
+    ExitReason,
+    abort_notifier::AbortNotifierService,
     event_cursor_store::ChainCursorService,
+    metrics,
     secret_manager::SecretManagerError,
     services::{
-        key_event_watcher::{events::KeyRegistryEvent, handler::KeyRegistryEventHandler},
+        key_event_watcher::{
+            admin::AdminCommand,
+            deadlines::ProtocolDeadlines,
+            epochs::FinalizedEpochs,
+            events::KeyRegistryEvent,
+            handler::KeyRegistryEventHandler,
+            resubscribe::{Resubscription, Subscriber},
+        },
         secret_gen::{DLogSecretGenService, SecretGenError},
-        transaction_handler::TransactionHandler,
+        transaction_handler::{TransactionSubmitterError, TransactionSubmitterService},
     },
 };
 use alloy::{
     network::primitives::TransactionFailedError,
-    primitives::{Address, LogData},
+    primitives::{Address, B256, LogData},
     providers::{DynProvider, PendingTransactionError},
     rpc::types::Log,
     sol_types::SolEvent as _,
@@ -49,21 +86,32 @@ use eyre::Context;
 use futures::StreamExt;
 use nodes_common::web3::{
     self,
-    event_stream::{ChainCursor, EventStreamBuilder, EventStreamConfig},
+    event_stream::{ChainCursor, EventStreamConfig},
 };
 use oprf_types::chain::{
     OprfKeyRegistry::{self, AlreadySubmitted, DeletedId, OprfKeyRegistryErrors, WrongRound},
     RevertError,
     Verifier::VerifierErrors,
+    logs::is_range_limit_error,
 };
+use secrecy::SecretString;
 use tokio_util::sync::CancellationToken;
 use tracing::instrument;
 
+pub(crate) use resubscribe::ResubscribeBackoff;
+
+/// Max number of logs the watcher takes from the event stream at once, see [`handle_logs`].
+const MAX_LOGS_PER_CHUNK: usize = 64;
+
 #[cfg(test)]
 mod tests;
 
+pub(crate) mod admin;
+mod deadlines;
+mod epochs;
 mod events;
 mod handler;
+mod resubscribe;
 
 type Result<T> = std::result::Result<T, KeyRegistryEventError>;
 
@@ -101,6 +149,20 @@ impl From<SecretGenError> for KeyRegistryEventError {
     }
 }
 
+impl From<TransactionSubmitterError> for KeyRegistryEventError {
+    fn from(value: TransactionSubmitterError) -> Self {
+        match value {
+            TransactionSubmitterError::Contract(err) => Self::from(err),
+            TransactionSubmitterError::Rpc(err) => Self::Rpc(err),
+            TransactionSubmitterError::PendingTransaction(err) => {
+                Self::PendingTransactionError(err)
+            }
+            TransactionSubmitterError::TransactionFailed(err) => Self::TransactionFailedError(err),
+            TransactionSubmitterError::Internal(report) => Self::Internal(report),
+        }
+    }
+}
+
 impl From<alloy::contract::Error> for KeyRegistryEventError {
     fn from(value: alloy::contract::Error) -> Self {
         if let Some(err) = value.as_decoded_interface_error::<OprfKeyRegistryErrors>() {
@@ -119,6 +181,9 @@ pub(crate) struct KeyEventWatcherTaskConfig {
     pub(crate) http_rpc_provider: web3::HttpRpcProvider,
     /// WebSocket provider used to subscribe to contract events.
     pub(crate) ws_rpc_provider: DynProvider,
+    /// URL to connect a new WebSocket provider when resubscribing. `None` resubscribes with
+    /// `ws_rpc_provider`.
+    pub(crate) ws_rpc_url: Option<SecretString>,
     /// Address of the `OprfKeyRegistry` contract to watch.
     pub(crate) contract_address: Address,
     /// Secret-generation service that mutates local key-gen state in response to events.
@@ -129,12 +194,18 @@ pub(crate) struct KeyEventWatcherTaskConfig {
     /// Set to `true` once the watcher has subscribed and is ready to process events.  Used
     /// by startup-ordering / health-check logic in the outer service.
     pub(crate) start_signal: Arc<AtomicBool>,
-    /// Transaction handler used to submit round contributions back to the contract.
-    pub(crate) transaction_handler: TransactionHandler,
+    /// Transaction submitter used to submit round contributions back to the contract.
+    pub(crate) transaction_submitter: TransactionSubmitterService,
     /// Filtering and backfill settings forwarded to the event-stream builder.
     pub(crate) event_stream_config: EventStreamConfig,
-    /// MPC threshold; passed to [`DLogSecretGenService`] for each round-1 call.
-    pub(crate) threshold: NonZeroU16,
+    /// Backoff between the attempts to resubscribe after the event stream was lost.
+    pub(crate) resubscribe_backoff: ResubscribeBackoff,
+    /// Max time of a run before its intermediates are evicted.
+    pub(crate) protocol_timeout: Duration,
+    /// Optional hook called after a stalled run was abandoned.
+    pub(crate) abort_notifier: Option<AbortNotifierService>,
+    /// Commands of the admin API, handled between two events.
+    pub(crate) admin_commands: tokio::sync::mpsc::Receiver<AdminCommand>,
     /// Signals the task to shut down cleanly.
     pub(crate) cancellation_token: CancellationToken,
 }
@@ -142,21 +213,29 @@ pub(crate) struct KeyEventWatcherTaskConfig {
 /// Background task that subscribes to key generation events and handles them.
 ///
 /// Connects to the blockchain via WebSocket and verifies that the
-/// `OprfKeyRegistry` contract is ready.
-pub(crate) async fn key_event_watcher_task(args: KeyEventWatcherTaskConfig) -> eyre::Result<()> {
+/// `OprfKeyRegistry` contract is ready. Returns why the task stopped.
+pub(crate) async fn key_event_watcher_task(args: KeyEventWatcherTaskConfig) -> ExitReason {
     // shutdown service if event watcher encounters an error and drops this guard
     let _drop_guard = args.cancellation_token.clone().drop_guard();
+    handle_events(args).await.unwrap_or_else(ExitReason::Failed)
+}
+
+async fn handle_events(args: KeyEventWatcherTaskConfig) -> eyre::Result<ExitReason> {
     tracing::info!("start handling events");
     let KeyEventWatcherTaskConfig {
         http_rpc_provider,
         ws_rpc_provider,
+        ws_rpc_url,
         contract_address,
         dlog_secret_gen_service,
         chain_cursor_service,
         start_signal,
-        transaction_handler,
+        transaction_submitter,
         event_stream_config,
-        threshold,
+        resubscribe_backoff,
+        protocol_timeout,
+        abort_notifier,
+        mut admin_commands,
         cancellation_token,
     } = args;
 
@@ -168,47 +247,71 @@ pub(crate) async fn key_event_watcher_task(args: KeyEventWatcherTaskConfig) -> e
 
     let contract = OprfKeyRegistry::new(contract_address, http_rpc_provider.inner());
 
-    let event_signatures = vec![
-        OprfKeyRegistry::SecretGenRound1::SIGNATURE_HASH,
-        OprfKeyRegistry::SecretGenRound2::SIGNATURE_HASH,
-        OprfKeyRegistry::SecretGenRound3::SIGNATURE_HASH,
-        OprfKeyRegistry::SecretGenFinalize::SIGNATURE_HASH,
-        OprfKeyRegistry::ReshareRound1::SIGNATURE_HASH,
-        OprfKeyRegistry::ReshareRound3::SIGNATURE_HASH,
-        OprfKeyRegistry::KeyDeletion::SIGNATURE_HASH,
-        OprfKeyRegistry::KeyGenAbort::SIGNATURE_HASH,
-        OprfKeyRegistry::NotEnoughProducers::SIGNATURE_HASH,
-    ];
-
-    let mut event_stream = EventStreamBuilder::with_config(
-        chain_cursor,
+    let mut subscriber = Subscriber::new(
         contract_address,
         http_rpc_provider,
-        ws_rpc_provider.clone(),
-        event_signatures,
+        ws_rpc_provider,
+        ws_rpc_url,
         event_stream_config,
-    )
-    .build()
-    .await
-    .context("while building event-stream")?;
+        resubscribe_backoff,
+    );
+    let mut event_stream = subscriber.subscribe(chain_cursor).await?;
 
     let event_handler = KeyRegistryEventHandler::new(
         contract,
         dlog_secret_gen_service,
-        threshold,
-        transaction_handler,
+        transaction_submitter,
+        abort_notifier,
     );
+    let mut deadlines = ProtocolDeadlines::new(protocol_timeout);
+    let mut finalized_epochs = FinalizedEpochs::new();
 
     start_signal.store(true, Ordering::Relaxed);
+    let mut last_cursor = chain_cursor;
     loop {
+        let next_deadline = deadlines.next();
         tokio::select! {
-            log = event_stream.next() => {
-                let Some(log) = log else {
-                    tracing::info!("event-stream closed - initiate shutdown");
-                    return Ok(());
+            logs = event_stream.next() => {
+                let lost = if let Some(logs) = logs {
+                    match handle_logs(
+                        logs,
+                        &event_handler,
+                        &chain_cursor_service,
+                        &mut deadlines,
+                        &mut finalized_epochs,
+                        &mut last_cursor,
+                    )
+                    .await
+                    {
+                        Ok(()) => continue,
+                        Err(HandleLogsError::Stream(err)) => {
+                            tracing::warn!(?err, "event-stream failed");
+                            Err(err)
+                        }
+                        Err(HandleLogsError::Fatal(err)) => return Err(err),
+                    }
+                } else {
+                    tracing::warn!("event-stream closed");
+                    Ok(ExitReason::EventStreamClosed)
                 };
-                let log = log.context("while fetching event from event_stream")?;
-                key_gen_event(log, &event_handler, &chain_cursor_service).await?;
+                match subscriber.resubscribe(last_cursor, &cancellation_token).await {
+                    Resubscription::Subscribed(resubscribed) => event_stream = resubscribed,
+                    Resubscription::Exhausted => {
+                        tracing::info!("cannot resubscribe to events - initiate shutdown");
+                        return lost;
+                    }
+                    Resubscription::Cancelled => break,
+                }
+            }
+            run = deadlines::expired(next_deadline) => {
+                event_handler
+                    .abandon(run.key_id, run.epoch)
+                    .await
+                    .context("while abandoning stalled run")?;
+                deadlines.remove(run.key_id);
+            }
+            Some(command) = admin_commands.recv() => {
+                admin::handle(command, contract_address, &event_handler, &mut deadlines).await;
             }
             () = cancellation_token.cancelled() => {
                 break;
@@ -217,12 +320,120 @@ pub(crate) async fn key_event_watcher_task(args: KeyEventWatcherTaskConfig) -> e
     }
 
     tracing::info!("successfully closed key_event_watcher without error");
-    Ok(())
+    Ok(ExitReason::Cancelled)
+}
+
+/// Why [`handle_logs`] stopped before handling every log.
+enum HandleLogsError {
+    /// The event stream returned an error, the logs after it must be fetched with a new subscription.
+    Stream(eyre::Report),
+    /// Handling a log failed, the watcher must stop.
+    Fatal(eyre::Report),
+}
+
+/// Handles the logs the event stream had ready at once, in chain order.
+///
+/// Logs at or before `last_cursor` are skipped. Consecutive finalize events (e.g., replayed during
+/// the backfill after downtime) are handled together by [`finalize_events`], all other logs one by
+/// one by [`key_gen_event`]. If the event stream returned an error, the logs before it are handled
+/// and the error is returned as [`HandleLogsError::Stream`], except for errors of a backfill range
+/// the RPC provider rejects, which a resubscription cannot resolve.
+async fn handle_logs<E>(
+    logs: Vec<std::result::Result<Log<LogData>, E>>,
+    event_handler: &KeyRegistryEventHandler,
+    chain_cursor_service: &ChainCursorService,
+    deadlines: &mut ProtocolDeadlines,
+    finalized_epochs: &mut FinalizedEpochs,
+    last_cursor: &mut ChainCursor,
+) -> std::result::Result<(), HandleLogsError>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let mut finalizes = Vec::new();
+    for log in logs {
+        let log = match log {
+            Ok(log) => log,
+            Err(err) if is_range_limit_error(&err.to_string()) => {
+                return Err(HandleLogsError::Fatal(eyre::Report::new(err).wrap_err(
+                    "RPC provider rejected the backfill range - lower event_stream_config.chunk_size",
+                )));
+            }
+            Err(err) => {
+                finalize_events(
+                    finalizes,
+                    event_handler,
+                    chain_cursor_service,
+                    deadlines,
+                    finalized_epochs,
+                )
+                .await
+                .map_err(HandleLogsError::Fatal)?;
+                return Err(HandleLogsError::Stream(
+                    eyre::Report::new(err).wrap_err("while fetching event from event_stream"),
+                ));
+            }
+        };
+        let cursor = log_cursor(&log).map_err(HandleLogsError::Fatal)?;
+        if !last_cursor.is_before(cursor) {
+            tracing::debug!("skipping event at {cursor} - already processed up to {last_cursor}");
+            continue;
+        }
+        *last_cursor = cursor;
+        if log.topic0() == Some(&OprfKeyRegistry::SecretGenFinalize::SIGNATURE_HASH) {
+            finalizes.push((log, cursor));
+            continue;
+        }
+        finalize_events(
+            std::mem::take(&mut finalizes),
+            event_handler,
+            chain_cursor_service,
+            deadlines,
+            finalized_epochs,
+        )
+        .await
+        .map_err(HandleLogsError::Fatal)?;
+        key_gen_event(
+            log,
+            cursor,
+            event_handler,
+            chain_cursor_service,
+            deadlines,
+            finalized_epochs,
+        )
+        .await
+        .map_err(HandleLogsError::Fatal)?;
+    }
+    finalize_events(
+        finalizes,
+        event_handler,
+        chain_cursor_service,
+        deadlines,
+        finalized_epochs,
+    )
+    .await
+    .map_err(HandleLogsError::Fatal)
+}
+
+/// The signatures of all `OprfKeyRegistry` events the watcher handles.
+fn event_signatures() -> Vec<B256> {
+    vec![
+        OprfKeyRegistry::SecretGenRound1::SIGNATURE_HASH,
+        OprfKeyRegistry::SecretGenRound2::SIGNATURE_HASH,
+        OprfKeyRegistry::SecretGenRound3::SIGNATURE_HASH,
+        OprfKeyRegistry::SecretGenFinalize::SIGNATURE_HASH,
+        OprfKeyRegistry::ReshareRound1::SIGNATURE_HASH,
+        OprfKeyRegistry::ReshareRound3::SIGNATURE_HASH,
+        OprfKeyRegistry::KeyDeletion::SIGNATURE_HASH,
+        OprfKeyRegistry::KeyGenAbort::SIGNATURE_HASH,
+        OprfKeyRegistry::NotEnoughProducers::SIGNATURE_HASH,
+    ]
 }
 
 /// Decode a single chain log, dispatch it to the event handler, apply the soft-error policy,
 /// and - on success - advance the chain cursor.
 ///
+/// Stale events (see [`FinalizedEpochs`]) are not dispatched, but still advance the chain cursor.
+///
 /// Span fields populated by [`events::KeyRegistryEvent::record_span_fields`]: `oprf_key_id`, `share_epoch`, `event`.
 ///
 /// `tx_hash` is recorded inside [`handler::KeyRegistryEventHandler::handle`] after a contribution is submitted.
@@ -237,32 +448,112 @@ pub(crate) async fn key_event_watcher_task(args: KeyEventWatcherTaskConfig) -> e
     ))]
 async fn key_gen_event(
     log: Log<LogData>,
+    chain_cursor: ChainCursor,
     event_handler: &KeyRegistryEventHandler,
     chain_cursor_service: &ChainCursorService,
+    deadlines: &mut ProtocolDeadlines,
+    finalized_epochs: &mut FinalizedEpochs,
 ) -> eyre::Result<()> {
     tracing::trace!("parsing event...");
     let event = KeyRegistryEvent::try_decode_log(&log).context("while decoding chain event")?;
     event.record_span_fields(&tracing::Span::current());
 
-    tracing::trace!("process event...");
-    let result = event_handler.handle(event, &tracing::Span::current()).await;
+    if let Some(finalized) = finalized_epochs.observe(&event) {
+        tracing::warn!(
+            "skipping {} event - key is already finalized with epoch {finalized}",
+            event.event_type()
+        );
+        metrics::chain_events::inc_reordered(event.event_type());
+    } else {
+        deadlines.observe(&event);
 
-    tracing::trace!("process result...");
-    handle_soft_errors(result).context("while handling key-gen event")?;
+        tracing::trace!("process event...");
+        let result = event_handler.handle(event, &tracing::Span::current()).await;
+
+        tracing::trace!("process result...");
+        handle_soft_errors(result).context("while handling key-gen event")?;
+    }
 
     tracing::trace!("store chain cursor...");
+    chain_cursor_service
+        .store_chain_cursor(chain_cursor)
+        .await
+        .context("while storing chain cursor")?;
+    Ok(())
+}
+
+/// Handles consecutive finalize logs with one write to the secret manager and advances the chain
+/// cursor to the last of them.
+///
+/// Stale events (see [`FinalizedEpochs`]) are skipped like in [`key_gen_event`]. If the batch
+/// fails, the events are handled again one by one, so the soft-error policy applies to every
+/// event. A single log is handled by [`key_gen_event`].
+#[instrument(level = "info", skip_all, fields(events = logs.len()))]
+async fn finalize_events(
+    mut logs: Vec<(Log<LogData>, ChainCursor)>,
+    event_handler: &KeyRegistryEventHandler,
+    chain_cursor_service: &ChainCursorService,
+    deadlines: &mut ProtocolDeadlines,
+    finalized_epochs: &mut FinalizedEpochs,
+) -> eyre::Result<()> {
+    let Some(&(_, chain_cursor)) = logs.last() else {
+        return Ok(());
+    };
+    if logs.len() == 1 {
+        let (log, chain_cursor) = logs.remove(0);
+        return key_gen_event(
+            log,
+            chain_cursor,
+            event_handler,
+            chain_cursor_service,
+            deadlines,
+            finalized_epochs,
+        )
+        .await;
+    }
+
+    let mut finalizes = Vec::with_capacity(logs.len());
+    for (log, _) in &logs {
+        let event = KeyRegistryEvent::try_decode_log(log).context("while decoding chain event")?;
+        if let Some(finalized) = finalized_epochs.observe(&event) {
+            tracing::warn!(
+                "skipping {} event - key is already finalized with epoch {finalized}",
+                event.event_type()
+            );
+            metrics::chain_events::inc_reordered(event.event_type());
+        } else if let KeyRegistryEvent::Finalize { key_id, epoch } = event {
+            deadlines.observe(&event);
+            finalizes.push((key_id, epoch));
+        }
+    }
+
+    tracing::trace!("process {} finalize events...", finalizes.len());
+    if let Err(err) = event_handler.finalize_batch(&finalizes).await {
+        tracing::warn!(%err, "cannot finalize events at once - handling them one by one");
+        for (key_id, epoch) in finalizes {
+            let event = KeyRegistryEvent::Finalize { key_id, epoch };
+            let result = event_handler.handle(event, &tracing::Span::current()).await;
+            handle_soft_errors(result).context("while handling key-gen event")?;
+        }
+    }
+
+    tracing::trace!("store chain cursor...");
+    chain_cursor_service
+        .store_chain_cursor(chain_cursor)
+        .await
+        .context("while storing chain cursor")?;
+    Ok(())
+}
+
+/// The [`ChainCursor`] of a mined log.
+fn log_cursor(log: &Log<LogData>) -> eyre::Result<ChainCursor> {
     let block_number = log
         .block_number
         .ok_or_else(|| eyre::eyre!("block number missing on log"))?;
     let index = log
         .log_index
         .ok_or_else(|| eyre::eyre!("log index missing on log"))?;
-    let chain_cursor = ChainCursor::new(block_number, index);
-    chain_cursor_service
-        .store_chain_cursor(chain_cursor)
-        .await
-        .context("while storing chain cursor")?;
-    Ok(())
+    Ok(ChainCursor::new(block_number, index))
 }
 
 /// Downgrades known recoverable errors to `Ok(())` so the cursor still advances.
@@ -324,6 +615,7 @@ fn handle_soft_errors(result: Result<()>) -> eyre::Result<()> {
             tracing::warn!(
                 "SecretManager refusing to rollback to older share - maybe we got an event out of order?"
             );
+            metrics::chain_events::inc_reordered("finalize");
             Ok(())
         }
         Err(
@@ -0,0 +1,161 @@
+//! Admin commands to inspect and abort the in-progress runs of the key event watchers.
+//!
+//! Every key event watcher owns the [`ProtocolDeadlines`] and the [`DLogSecretGenService`](crate::services::secret_gen::DLogSecretGenService) of its registry, so the admin API cannot access them directly. Instead, it sends an [`AdminCommand`] to the watcher via the [`AdminHandle`], which the watcher handles between two events, like the deadline of a stalled run.
+//!
+//! A run is listed from its round-1 event (or from the first later event of the run after a restart) until its finalize, abort or delete event, i.e., as long as the node holds intermediates for it.
+
+use alloy::primitives::Address;
+use eyre::Context as _;
+use oprf_types::{OprfKeyId, ShareEpoch};
+use serde::Serialize;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+
+use super::{deadlines::ProtocolDeadlines, handler::KeyRegistryEventHandler};
+
+/// Max number of admin commands queued per key event watcher.
+const COMMAND_BUFFER: usize = 16;
+
+/// An in-progress key-gen/reshare run of a registry.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ActiveRun {
+    /// The `OprfKeyRegistry` of the run.
+    pub(crate) registry: Address,
+    /// The key of the run.
+    pub(crate) oprf_key_id: OprfKeyId,
+    /// The epoch the run generates.
+    pub(crate) epoch: ShareEpoch,
+    /// Seconds until the run is abandoned because it did not finish within the `protocol_timeout`.
+    pub(crate) expires_in_secs: u64,
+}
+
+/// A command to a key event watcher.
+pub(crate) enum AdminCommand {
+    /// Lists the in-progress runs of the registry.
+    ListActive(oneshot::Sender<Vec<ActiveRun>>),
+    /// Aborts the in-progress run of the key, replies `None` if there is none.
+    Abort {
+        oprf_key_id: OprfKeyId,
+        reply: oneshot::Sender<eyre::Result<Option<ActiveRun>>>,
+    },
+}
+
+/// Sends [`AdminCommand`]s to the key event watchers of all registries.
+#[derive(Clone, Default)]
+pub(crate) struct AdminHandle {
+    watchers: Vec<(Address, mpsc::Sender<AdminCommand>)>,
+}
+
+impl AdminHandle {
+    /// Registers the watcher of `registry` and returns the receiver of its commands.
+    pub(crate) fn register(&mut self, registry: Address) -> mpsc::Receiver<AdminCommand> {
+        let (tx, rx) = mpsc::channel(COMMAND_BUFFER);
+        self.watchers.push((registry, tx));
+        rx
+    }
+
+    /// The in-progress runs of all registries, the run that expires first first.
+    pub(crate) async fn active_runs(&self) -> eyre::Result<Vec<ActiveRun>> {
+        let mut runs = Vec::new();
+        for (registry, watcher) in &self.watchers {
+            let (reply, rx) = oneshot::channel();
+            send(*registry, watcher, AdminCommand::ListActive(reply)).await?;
+            runs.extend(rx.await.context("key event watcher stopped")?);
+        }
+        runs.sort_by_key(|run| run.expires_in_secs);
+        Ok(runs)
+    }
+
+    /// Aborts the in-progress run of `oprf_key_id` in `registry`, or in every registry if `None`. Returns the aborted runs.
+    pub(crate) async fn abort(
+        &self,
+        oprf_key_id: OprfKeyId,
+        registry: Option<Address>,
+    ) -> eyre::Result<Vec<ActiveRun>> {
+        let mut aborted = Vec::new();
+        for (watcher_registry, watcher) in &self.watchers {
+            if registry.is_some_and(|registry| registry != *watcher_registry) {
+                continue;
+            }
+            let (reply, rx) = oneshot::channel();
+            send(
+                *watcher_registry,
+                watcher,
+                AdminCommand::Abort { oprf_key_id, reply },
+            )
+            .await?;
+            aborted.extend(rx.await.context("key event watcher stopped")??);
+        }
+        Ok(aborted)
+    }
+}
+
+async fn send(
+    registry: Address,
+    watcher: &mpsc::Sender<AdminCommand>,
+    command: AdminCommand,
+) -> eyre::Result<()> {
+    watcher
+        .send(command)
+        .await
+        .map_err(|_| eyre::eyre!("key event watcher of {registry} stopped"))
+}
+
+/// Handles an [`AdminCommand`] in the key event watcher of `registry`.
+///
+/// Aborting a run evicts its intermediates and calls the abort notifier, like for a run that did not finish in time.
+pub(super) async fn handle(
+    command: AdminCommand,
+    registry: Address,
+    event_handler: &KeyRegistryEventHandler,
+    deadlines: &mut ProtocolDeadlines,
+) {
+    match command {
+        AdminCommand::ListActive(reply) => {
+            let runs = deadlines
+                .runs()
+                .map(|run| active_run(registry, run.key_id, run.epoch, run.deadline))
+                .collect();
+            if reply.send(runs).is_err() {
+                tracing::debug!("admin request was cancelled");
+            }
+        }
+        AdminCommand::Abort { oprf_key_id, reply } => {
+            let result = match deadlines.get(oprf_key_id) {
+                Some(run) => match event_handler.abort_on_request(oprf_key_id, run.epoch).await {
+                    Ok(()) => {
+                        deadlines.remove(oprf_key_id);
+                        Ok(Some(active_run(
+                            registry,
+                            oprf_key_id,
+                            run.epoch,
+                            run.deadline,
+                        )))
+                    }
+                    Err(err) => Err(eyre::Report::from(err)
+                        .wrap_err(format!("while aborting run for {oprf_key_id}"))),
+                },
+                None => Ok(None),
+            };
+            if reply.send(result).is_err() {
+                tracing::debug!("admin request was cancelled");
+            }
+        }
+    }
+}
+
+fn active_run(
+    registry: Address,
+    oprf_key_id: OprfKeyId,
+    epoch: ShareEpoch,
+    deadline: Instant,
+) -> ActiveRun {
+    ActiveRun {
+        registry,
+        oprf_key_id,
+        epoch,
+        expires_in_secs: deadline.saturating_duration_since(Instant::now()).as_secs(),
+    }
+}
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, time::Duration};
+
+use oprf_types::{OprfKeyId, ShareEpoch};
+use tokio::time::Instant;
+
+use crate::services::key_event_watcher::KeyRegistryEvent;
+
+/// A key-gen/reshare run that did not finish within the protocol timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct StalledRun {
+    pub(super) key_id: OprfKeyId,
+    pub(super) epoch: ShareEpoch,
+    pub(super) deadline: Instant,
+}
+
+/// Tracks the deadline of every in-progress run, at most one per [`OprfKeyId`].
+///
+/// A run is tracked from its round-1 event (or from the first later event of the run after a restart) until its finalize, abort or delete event.
+pub(super) struct ProtocolDeadlines {
+    protocol_timeout: Duration,
+    runs: HashMap<OprfKeyId, (ShareEpoch, Instant)>,
+}
+
+impl ProtocolDeadlines {
+    pub(super) fn new(protocol_timeout: Duration) -> Self {
+        Self {
+            protocol_timeout,
+            runs: HashMap::new(),
+        }
+    }
+
+    /// Updates the tracked runs with an event of the `OprfKeyRegistry`.
+    pub(super) fn observe(&mut self, event: &KeyRegistryEvent) {
+        match event {
+            KeyRegistryEvent::KeyGenRound1 { key_id, .. } => {
+                self.track(*key_id, ShareEpoch::default());
+            }
+            KeyRegistryEvent::ReshareRound1 { key_id, epoch, .. }
+            | KeyRegistryEvent::Round2 { key_id, epoch }
+            | KeyRegistryEvent::Round3 { key_id, epoch, .. } => self.track(*key_id, *epoch),
+            KeyRegistryEvent::Finalize { key_id, .. }
+            | KeyRegistryEvent::Delete { key_id }
+            | KeyRegistryEvent::Abort { key_id } => self.remove(*key_id),
+            KeyRegistryEvent::NotEnoughProducers { .. } | KeyRegistryEvent::Unknown => {}
+        }
+    }
+
+    /// Tracks the run for `key_id` and `epoch`. Keeps the deadline if the run is tracked already, so duplicate or later events of the run do not extend it.
+    fn track(&mut self, key_id: OprfKeyId, epoch: ShareEpoch) {
+        let deadline = Instant::now() + self.protocol_timeout;
+        match self.runs.get(&key_id) {
+            Some((tracked_epoch, _)) if *tracked_epoch == epoch => {}
+            _ => {
+                self.runs.insert(key_id, (epoch, deadline));
+            }
+        }
+    }
+
+    /// Stops tracking the run for `key_id`.
+    pub(super) fn remove(&mut self, key_id: OprfKeyId) {
+        self.runs.remove(&key_id);
+    }
+
+    /// The tracked run of `key_id`, if any.
+    pub(super) fn get(&self, key_id: OprfKeyId) -> Option<StalledRun> {
+        self.runs.get(&key_id).map(|(epoch, deadline)| StalledRun {
+            key_id,
+            epoch: *epoch,
+            deadline: *deadline,
+        })
+    }
+
+    /// All tracked runs, in no particular order.
+    pub(super) fn runs(&self) -> impl Iterator<Item = StalledRun> {
+        self.runs
+            .iter()
+            .map(|(key_id, (epoch, deadline))| StalledRun {
+                key_id: *key_id,
+                epoch: *epoch,
+                deadline: *deadline,
+            })
+    }
+
+    /// The tracked run with the earliest deadline.
+    pub(super) fn next(&self) -> Option<StalledRun> {
+        self.runs().min_by_key(|run| run.deadline)
+    }
+}
+
+/// Resolves with `run` once its deadline passed, never if there is no tracked run.
+pub(super) async fn expired(run: Option<StalledRun>) -> StalledRun {
+    match run {
+        Some(run) => {
+            tokio::time::sleep_until(run.deadline).await;
+            run
+        }
+        None => std::future::pending().await,
+    }
+}
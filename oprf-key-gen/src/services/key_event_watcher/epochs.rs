@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use oprf_types::{OprfKeyId, ShareEpoch};
+
+use crate::services::key_event_watcher::KeyRegistryEvent;
+
+/// Enforces that the epoch of every [`OprfKeyId`] only moves forward.
+///
+/// Tracks the latest finalized epoch per key. Events of a run for an epoch at or before it (e.g., the finalize of epoch `N` after the finalize of `N + 1`) are stale, as handling them could regress the stored share. The tracked epochs are empty after a restart, there the secret manager still refuses to roll back a stored share (see [`crate::secret_manager::SecretManagerError::RefusingToRollbackEpoch`]).
+pub(super) struct FinalizedEpochs {
+    finalized: HashMap<OprfKeyId, ShareEpoch>,
+}
+
+impl FinalizedEpochs {
+    pub(super) fn new() -> Self {
+        Self {
+            finalized: HashMap::new(),
+        }
+    }
+
+    /// Checks an event of the `OprfKeyRegistry` against the latest finalized epoch of its key and updates the tracked epochs.
+    ///
+    /// Returns the latest finalized epoch of the key if the event is stale. Stale events do not change the tracked epochs.
+    pub(super) fn observe(&mut self, event: &KeyRegistryEvent) -> Option<ShareEpoch> {
+        match event {
+            KeyRegistryEvent::ReshareRound1 { key_id, epoch, .. }
+            | KeyRegistryEvent::Round2 { key_id, epoch }
+            | KeyRegistryEvent::Round3 { key_id, epoch, .. } => self.stale_since(*key_id, *epoch),
+            KeyRegistryEvent::Finalize { key_id, epoch } => {
+                let stale_since = self.stale_since(*key_id, *epoch);
+                if stale_since.is_none() {
+                    self.finalized.insert(*key_id, *epoch);
+                }
+                stale_since
+            }
+            KeyRegistryEvent::Delete { key_id } => {
+                self.finalized.remove(key_id);
+                None
+            }
+            KeyRegistryEvent::KeyGenRound1 { .. }
+            | KeyRegistryEvent::Abort { .. }
+            | KeyRegistryEvent::NotEnoughProducers { .. }
+            | KeyRegistryEvent::Unknown => None,
+        }
+    }
+
+    /// The latest finalized epoch of `key_id`, if `epoch` is not after it.
+    fn stale_since(&self, key_id: OprfKeyId, epoch: ShareEpoch) -> Option<ShareEpoch> {
+        self.finalized
+            .get(&key_id)
+            .copied()
+            .filter(|finalized| epoch <= *finalized)
+    }
+}
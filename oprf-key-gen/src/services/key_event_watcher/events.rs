@@ -1,4 +1,10 @@
-use alloy::{primitives::LogData, rpc::types::Log, sol_types::SolEvent as _};
+use std::num::NonZeroU16;
+
+use alloy::{
+    primitives::{LogData, U256},
+    rpc::types::Log,
+    sol_types::SolEvent as _,
+};
 use eyre::Context as _;
 use oprf_types::chain::OprfKeyRegistry;
 use oprf_types::{OprfKeyId, ShareEpoch};
@@ -11,6 +17,7 @@ use crate::services::secret_gen::Contributions;
 pub(super) enum KeyRegistryEvent {
     KeyGenRound1 {
         key_id: OprfKeyId,
+        threshold: NonZeroU16,
     },
     Round2 {
         key_id: OprfKeyId,
@@ -28,6 +35,7 @@ pub(super) enum KeyRegistryEvent {
     ReshareRound1 {
         key_id: OprfKeyId,
         epoch: ShareEpoch,
+        threshold: NonZeroU16,
     },
     Delete {
         key_id: OprfKeyId,
@@ -64,10 +72,14 @@ impl KeyRegistryEvent {
         tracing::trace!("trying to decode log...");
         let event = match log.topic0() {
             Some(&OprfKeyRegistry::SecretGenRound1::SIGNATURE_HASH) => {
-                let OprfKeyRegistry::SecretGenRound1 { oprfKeyId, .. } = decode!();
+                let OprfKeyRegistry::SecretGenRound1 {
+                    oprfKeyId,
+                    threshold,
+                } = decode!();
 
                 Self::KeyGenRound1 {
                     key_id: OprfKeyId::from(oprfKeyId),
+                    threshold: parse_threshold(threshold)?,
                 }
             }
             Some(&OprfKeyRegistry::SecretGenRound2::SIGNATURE_HASH) => {
@@ -94,11 +106,14 @@ impl KeyRegistryEvent {
             }
             Some(&OprfKeyRegistry::ReshareRound1::SIGNATURE_HASH) => {
                 let OprfKeyRegistry::ReshareRound1 {
-                    oprfKeyId, epoch, ..
+                    oprfKeyId,
+                    threshold,
+                    epoch,
                 } = decode!();
                 Self::ReshareRound1 {
                     key_id: OprfKeyId::from(oprfKeyId),
                     epoch: ShareEpoch::from(epoch),
+                    threshold: parse_threshold(threshold)?,
                 }
             }
             Some(&OprfKeyRegistry::ReshareRound3::SIGNATURE_HASH) => {
@@ -107,23 +122,10 @@ impl KeyRegistryEvent {
                     lagrange,
                     epoch,
                 } = decode!();
-                tracing::trace!("parsing lagrange contributions..");
-                let lagrange = lagrange
-                    .into_iter()
-                    .filter_map(|x| {
-                        if x.is_zero() {
-                            // filter the empty coefficients - the smart contract produces lagrange coeffs 0 for the not relevant parties
-                            None
-                        } else {
-                            Some(oprf_types::chain::try_u256_into_bjj_fr(x))
-                        }
-                    })
-                    .collect::<eyre::Result<Vec<_>>>()
-                    .context("while parsing lagrange coeffs from chain")?;
                 Self::Round3 {
                     key_id: OprfKeyId::from(oprfKeyId),
                     epoch: ShareEpoch::from(epoch),
-                    contributions: Contributions::Shamir(lagrange),
+                    contributions: Contributions::Shamir(parse_lagrange(lagrange)?),
                 }
             }
             Some(&OprfKeyRegistry::KeyGenAbort::SIGNATURE_HASH) => {
@@ -164,7 +166,7 @@ impl KeyRegistryEvent {
     ///   (e.g. `"keygen-round1"`, `"round2"`, …).
     pub(super) fn record_span_fields(&self, span: &tracing::Span) {
         match self {
-            KeyRegistryEvent::KeyGenRound1 { key_id }
+            KeyRegistryEvent::KeyGenRound1 { key_id, .. }
             | KeyRegistryEvent::Delete { key_id }
             | KeyRegistryEvent::Abort { key_id }
             | KeyRegistryEvent::NotEnoughProducers { key_id } => {
@@ -172,7 +174,7 @@ impl KeyRegistryEvent {
             }
             KeyRegistryEvent::Round2 { key_id, epoch }
             | KeyRegistryEvent::Finalize { key_id, epoch }
-            | KeyRegistryEvent::ReshareRound1 { key_id, epoch }
+            | KeyRegistryEvent::ReshareRound1 { key_id, epoch, .. }
             | KeyRegistryEvent::Round3 { key_id, epoch, .. } => {
                 record_oprf_key_id(*key_id, span);
                 record_share_epoch(*epoch, span);
@@ -182,7 +184,7 @@ impl KeyRegistryEvent {
         span.record("event", self.event_type());
     }
 
-    fn event_type(&self) -> &'static str {
+    pub(super) fn event_type(&self) -> &'static str {
         match self {
             Self::KeyGenRound1 { .. } => "keygen-round1",
             Self::Round2 { .. } => "round2",
@@ -206,3 +208,28 @@ fn record_oprf_key_id(oprf_key_id: OprfKeyId, span: &tracing::Span) {
 fn record_share_epoch(epoch: ShareEpoch, span: &tracing::Span) {
     span.record("share_epoch", epoch.to_string());
 }
+
+/// Parses the threshold of a round-1 event, which the contract emits as `uint256`.
+fn parse_threshold(threshold: U256) -> eyre::Result<NonZeroU16> {
+    u16::try_from(threshold)
+        .ok()
+        .and_then(NonZeroU16::new)
+        .ok_or_else(|| eyre::eyre!("invalid threshold {threshold} in round-1 event"))
+}
+
+/// Parses the lagrange coefficients of a `ReshareRound3` event.
+fn parse_lagrange(lagrange: Vec<U256>) -> eyre::Result<Vec<ark_babyjubjub::Fr>> {
+    tracing::trace!("parsing lagrange contributions..");
+    lagrange
+        .into_iter()
+        .filter_map(|x| {
+            if x.is_zero() {
+                // filter the empty coefficients - the smart contract produces lagrange coeffs 0 for the not relevant parties
+                None
+            } else {
+                Some(oprf_types::chain::try_u256_into_bjj_fr(x))
+            }
+        })
+        .collect::<eyre::Result<Vec<_>>>()
+        .context("while parsing lagrange coeffs from chain")
+}
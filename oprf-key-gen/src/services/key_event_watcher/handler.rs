@@ -13,9 +13,10 @@ use oprf_types::{
 
 use crate::metrics;
 use crate::services::{
+    abort_notifier::AbortNotifierService,
     key_event_watcher::{KeyRegistryEvent, KeyRegistryEventError},
     secret_gen::{Contributions, DLogSecretGenService},
-    transaction_handler::TransactionHandler,
+    transaction_handler::TransactionSubmitterService,
 };
 
 use super::Result;
@@ -24,8 +25,8 @@ use super::Result;
 pub(super) struct KeyRegistryEventHandler {
     contract: OprfKeyRegistryInstance<DynProvider>,
     secret_gen: DLogSecretGenService,
-    threshold: NonZeroU16,
-    tx: TransactionHandler,
+    tx: TransactionSubmitterService,
+    abort_notifier: Option<AbortNotifierService>,
 }
 
 impl KeyRegistryEventHandler {
@@ -36,19 +37,19 @@ impl KeyRegistryEventHandler {
     /// * `contract` - A connected `OprfKeyRegistry` instance used for view calls (public-key
     ///   fetches) and round submissions.
     /// * `secret_gen` - Manages local key-gen intermediates and computes contributions.
-    /// * `threshold` - MPC threshold forwarded to round-1 calls.
     /// * `tx` - Submits contribution transactions and waits for confirmations.
+    /// * `abort_notifier` - Optional hook called after a stalled run was abandoned.
     pub(super) fn new(
         contract: OprfKeyRegistryInstance<DynProvider>,
         secret_gen: DLogSecretGenService,
-        threshold: NonZeroU16,
-        tx: TransactionHandler,
+        tx: TransactionSubmitterService,
+        abort_notifier: Option<AbortNotifierService>,
     ) -> Self {
         Self {
             contract,
             secret_gen,
-            threshold,
             tx,
+            abort_notifier,
         }
     }
 
@@ -59,8 +60,8 @@ impl KeyRegistryEventHandler {
         event_span: &tracing::Span,
     ) -> Result<()> {
         match event {
-            KeyRegistryEvent::KeyGenRound1 { key_id } => {
-                self.keygen_round1(key_id, event_span).await
+            KeyRegistryEvent::KeyGenRound1 { key_id, threshold } => {
+                self.keygen_round1(key_id, threshold, event_span).await
             }
             KeyRegistryEvent::Round2 { key_id, epoch } => {
                 self.round2(key_id, epoch, event_span).await
@@ -71,8 +72,13 @@ impl KeyRegistryEventHandler {
                 contributions,
             } => self.round3(key_id, epoch, contributions, event_span).await,
             KeyRegistryEvent::Finalize { key_id, epoch } => self.finalize(key_id, epoch).await,
-            KeyRegistryEvent::ReshareRound1 { key_id, epoch } => {
-                self.reshare_round1(key_id, epoch, event_span).await
+            KeyRegistryEvent::ReshareRound1 {
+                key_id,
+                epoch,
+                threshold,
+            } => {
+                self.reshare_round1(key_id, epoch, threshold, event_span)
+                    .await
             }
             KeyRegistryEvent::Delete { key_id } => self.delete(key_id).await,
             KeyRegistryEvent::Abort { key_id } => self.abort(key_id).await,
@@ -92,12 +98,13 @@ impl KeyRegistryEventHandler {
     async fn keygen_round1(
         &self,
         oprf_key_id: OprfKeyId,
+        threshold: NonZeroU16,
         event_span: &tracing::Span,
     ) -> Result<()> {
         tracing::trace!("Received KeyGenRound1 event");
         let contribution = self
             .secret_gen
-            .key_gen_round1(oprf_key_id, ShareEpoch::default(), self.threshold)
+            .key_gen_round1(oprf_key_id, ShareEpoch::default(), threshold)
             .await?;
         tracing::trace!("finished round1 - now reporting to chain..");
         let tx_hash = self
@@ -180,16 +187,48 @@ impl KeyRegistryEventHandler {
         Ok(())
     }
 
+    /// Handles several finalize events with one write to the secret manager.
+    ///
+    /// Finalize events of deleted keys are skipped like in [`Self::handle`]. Soft errors are not downgraded, the caller retries the events one by one with [`Self::handle`] instead.
+    pub(super) async fn finalize_batch(&self, finalizes: &[(OprfKeyId, ShareEpoch)]) -> Result<()> {
+        tracing::trace!("Finalize events for {} keys", finalizes.len());
+        let oprf_public_keys = futures::future::try_join_all(
+            finalizes
+                .iter()
+                .map(|(oprf_key_id, _)| self.fetch_oprf_public_key(*oprf_key_id)),
+        )
+        .await?;
+        let shares = finalizes
+            .iter()
+            .zip(oprf_public_keys)
+            .filter_map(|(&(oprf_key_id, epoch), oprf_public_key)| {
+                if oprf_public_key.is_none() {
+                    tracing::info!(
+                        "Received finalize on deleted key {oprf_key_id} - continue and mark as done"
+                    );
+                }
+                Some((oprf_key_id, epoch, oprf_public_key?))
+            })
+            .collect();
+        self.secret_gen.finalize_batch(shares).await?;
+        tracing::info!("Finished finalize for {} keys", finalizes.len());
+        for _ in finalizes {
+            metrics::chain_events::inc_finalize();
+        }
+        Ok(())
+    }
+
     async fn reshare_round1(
         &self,
         oprf_key_id: OprfKeyId,
         epoch: ShareEpoch,
+        threshold: NonZeroU16,
         event_span: &tracing::Span,
     ) -> Result<()> {
         tracing::trace!("Received ReshareRound1 event");
         let contribution = self
             .secret_gen
-            .reshare_round1(oprf_key_id, epoch, self.threshold)
+            .reshare_round1(oprf_key_id, epoch, threshold)
             .await?;
         let tx_hash = self
             .tx
@@ -220,6 +259,41 @@ impl KeyRegistryEventHandler {
         Ok(())
     }
 
+    /// Abandons a run that did not finish within the protocol timeout.
+    ///
+    /// Evicts the intermediates of the run and calls the abort notifier. Errors of the notifier are only logged.
+    pub(super) async fn abandon(&self, oprf_key_id: OprfKeyId, epoch: ShareEpoch) -> Result<()> {
+        tracing::warn!(
+            "run for {oprf_key_id} with epoch {epoch} did not finish in time - abandoning it"
+        );
+        self.evict(oprf_key_id, epoch).await?;
+        metrics::protocol::inc_abandoned_run();
+        Ok(())
+    }
+
+    /// Aborts an in-progress run on request of an admin, see [`super::admin`].
+    ///
+    /// Evicts the intermediates of the run and calls the abort notifier like [`Self::abandon`].
+    pub(super) async fn abort_on_request(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> Result<()> {
+        tracing::info!("aborting run for {oprf_key_id} with epoch {epoch} on admin request");
+        self.evict(oprf_key_id, epoch).await
+    }
+
+    /// Evicts the intermediates of a run and calls the abort notifier. Errors of the notifier are only logged.
+    async fn evict(&self, oprf_key_id: OprfKeyId, epoch: ShareEpoch) -> Result<()> {
+        self.secret_gen.abort_keygen(oprf_key_id).await?;
+        if let Some(abort_notifier) = &self.abort_notifier
+            && let Err(err) = abort_notifier.notify_abandoned(oprf_key_id, epoch).await
+        {
+            tracing::warn!(?err, "cannot notify abandoned run for {oprf_key_id}");
+        }
+        Ok(())
+    }
+
     // ================== HELPER FUNCTIONS ==================
 
     /// Calls `OprfKeyRegistry::loadPeerPublicKeysForProducers` and parses the result.
@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use alloy::{primitives::Address, providers::DynProvider, rpc::types::Log};
+use eyre::Context as _;
+use futures::{StreamExt as _, stream::BoxStream};
+use nodes_common::web3::{
+    self,
+    event_stream::{ChainCursor, EventStreamBuilder, EventStreamConfig, EventStreamError},
+};
+use secrecy::{ExposeSecret as _, SecretString};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::OprfKeyGenServiceConfig,
+    metrics,
+    services::key_event_watcher::{MAX_LOGS_PER_CHUNK, event_signatures},
+};
+
+/// The logs of the `OprfKeyRegistry`, in chunks of the logs the event stream had ready at once.
+pub(super) type LogChunks = BoxStream<'static, Vec<Result<Log, EventStreamError>>>;
+
+/// Exponential backoff between consecutive attempts to resubscribe to the `OprfKeyRegistry`.
+///
+/// The first attempt waits `initial`, every further attempt doubles the wait up to `max`. After `max_attempts` consecutive attempts, no further attempt is made until the backoff is reset.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ResubscribeBackoff {
+    max_attempts: usize,
+    initial: Duration,
+    max: Duration,
+    attempts: usize,
+}
+
+impl ResubscribeBackoff {
+    pub(crate) fn new(max_attempts: usize, initial: Duration, max: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial,
+            max,
+            attempts: 0,
+        }
+    }
+
+    /// The backoff configured with `max_resubscribe_attempts`, `resubscribe_backoff` and `max_resubscribe_backoff`.
+    pub(crate) fn from_config(config: &OprfKeyGenServiceConfig) -> Self {
+        Self::new(
+            config.max_resubscribe_attempts,
+            config.resubscribe_backoff,
+            config.max_resubscribe_backoff,
+        )
+    }
+
+    /// The wait before the next attempt, or `None` if the attempts are exhausted.
+    pub(super) fn next_backoff(&mut self) -> Option<Duration> {
+        if self.attempts >= self.max_attempts {
+            return None;
+        }
+        let factor = u32::try_from(self.attempts)
+            .ok()
+            .and_then(|attempts| 2_u32.checked_pow(attempts))
+            .unwrap_or(u32::MAX);
+        self.attempts += 1;
+        Some(self.initial.saturating_mul(factor).min(self.max))
+    }
+
+    /// Starts over with the first attempt.
+    pub(super) fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// The outcome of [`Subscriber::resubscribe`].
+pub(super) enum Resubscription {
+    /// Subscribed again, the stream starts with the backfill of the missed logs.
+    Subscribed(LogChunks),
+    /// Every attempt failed.
+    Exhausted,
+    /// The cancellation token was cancelled while waiting for the next attempt.
+    Cancelled,
+}
+
+/// Subscribes to the events of an `OprfKeyRegistry` and subscribes again after the event stream was lost.
+pub(super) struct Subscriber {
+    contract_address: Address,
+    http_rpc_provider: web3::HttpRpcProvider,
+    ws_rpc_provider: DynProvider,
+    ws_rpc_url: Option<SecretString>,
+    event_stream_config: EventStreamConfig,
+    backoff: ResubscribeBackoff,
+}
+
+impl Subscriber {
+    /// Creates a subscriber for the registry at `contract_address`.
+    ///
+    /// With a `ws_rpc_url`, every resubscription connects a new WebSocket provider, otherwise `ws_rpc_provider` is reused.
+    pub(super) fn new(
+        contract_address: Address,
+        http_rpc_provider: web3::HttpRpcProvider,
+        ws_rpc_provider: DynProvider,
+        ws_rpc_url: Option<SecretString>,
+        event_stream_config: EventStreamConfig,
+        backoff: ResubscribeBackoff,
+    ) -> Self {
+        Self {
+            contract_address,
+            http_rpc_provider,
+            ws_rpc_provider,
+            ws_rpc_url,
+            event_stream_config,
+            backoff,
+        }
+    }
+
+    /// Subscribes to the events after `chain_cursor`, backfilling the logs since `chain_cursor` with `eth_getLogs`.
+    pub(super) async fn subscribe(&self, chain_cursor: ChainCursor) -> eyre::Result<LogChunks> {
+        let event_stream = EventStreamBuilder::with_config(
+            chain_cursor,
+            self.contract_address,
+            self.http_rpc_provider.clone(),
+            self.ws_rpc_provider.clone(),
+            event_signatures(),
+            self.event_stream_config.clone(),
+        )
+        .build()
+        .await
+        .context("while building event-stream")?;
+        Ok(event_stream.ready_chunks(MAX_LOGS_PER_CHUNK).boxed())
+    }
+
+    /// Subscribes again to the events after `chain_cursor` with exponential backoff, see [`ResubscribeBackoff`].
+    ///
+    /// A successful resubscription resets the backoff.
+    pub(super) async fn resubscribe(
+        &mut self,
+        chain_cursor: ChainCursor,
+        cancellation_token: &CancellationToken,
+    ) -> Resubscription {
+        while let Some(backoff) = self.backoff.next_backoff() {
+            tracing::warn!(
+                "resubscribing to events in {backoff:?} - attempt {}/{}",
+                self.backoff.attempts,
+                self.backoff.max_attempts
+            );
+            tokio::select! {
+                () = tokio::time::sleep(backoff) => {}
+                () = cancellation_token.cancelled() => return Resubscription::Cancelled,
+            }
+            metrics::chain_events::inc_resubscribe();
+            match self.reconnect(chain_cursor).await {
+                Ok(event_stream) => {
+                    tracing::info!("resubscribed to events after {chain_cursor}");
+                    self.backoff.reset();
+                    return Resubscription::Subscribed(event_stream);
+                }
+                Err(err) => tracing::warn!(?err, "cannot resubscribe to events"),
+            }
+        }
+        tracing::error!(
+            "cannot resubscribe to events after {} attempts - giving up",
+            self.backoff.max_attempts
+        );
+        Resubscription::Exhausted
+    }
+
+    async fn reconnect(&mut self, chain_cursor: ChainCursor) -> eyre::Result<LogChunks> {
+        if let Some(ws_rpc_url) = &self.ws_rpc_url {
+            self.ws_rpc_provider = crate::connect_ws_provider(ws_rpc_url.expose_secret()).await?;
+        }
+        self.subscribe(chain_cursor).await
+    }
+}
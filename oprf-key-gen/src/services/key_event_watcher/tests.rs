@@ -18,19 +18,28 @@ use rand::{CryptoRng, Rng};
 use sqlx::PgPool;
 
 use crate::{
+    abort_notifier::AbortNotifier,
     postgres::{PostgresDb, to_db_ark_serialize_uncompressed},
     secret_manager::{SecretManager, SecretManagerError},
     services::{
         key_event_watcher::{KeyRegistryEventError, handler::KeyRegistryEventHandler},
         secret_gen::DLogSecretGenService,
-        transaction_handler::{TransactionHandler, TransactionHandlerArgs},
+        transaction_handler::{FeeEscalation, TransactionHandler, TransactionHandlerArgs},
     },
 };
 
-use super::events::KeyRegistryEvent;
+use super::{
+    ResubscribeBackoff, deadlines::ProtocolDeadlines, epochs::FinalizedEpochs,
+    events::KeyRegistryEvent,
+};
 
 const CONTRACT_ADDRESS: Address = Address::repeat_byte(0x42);
 const WALLET_ADDRESS: Address = Address::repeat_byte(0x24);
+const FEE_ESCALATION: FeeEscalation = FeeEscalation {
+    max_bumps: 0,
+    bump_percent: 20,
+    max_fee_per_gas_cap: u128::MAX,
+};
 
 struct HandlerFixture {
     handler: KeyRegistryEventHandler,
@@ -79,6 +88,7 @@ async fn fixture() -> eyre::Result<HandlerFixture> {
         sleep_between_get_receipt: Duration::from_millis(500),
         max_tries_fetching_receipt: 5,
         max_gas_per_transaction: 10_000_000,
+        fee_escalation: FEE_ESCALATION,
         rpc_provider: rpc_provider.clone(),
         wallet_address: WALLET_ADDRESS,
         contract_address: CONTRACT_ADDRESS,
@@ -86,9 +96,12 @@ async fn fixture() -> eyre::Result<HandlerFixture> {
 
     // Handler view-call contract shares the same asserter-backed provider.
     let contract = OprfKeyRegistry::new(CONTRACT_ADDRESS, rpc_provider.inner());
-    let threshold = NonZeroU16::new(2).expect("2 is non-zero");
-    let handler =
-        KeyRegistryEventHandler::new(contract, secret_gen.clone(), threshold, transaction_handler);
+    let handler = KeyRegistryEventHandler::new(
+        contract,
+        secret_gen.clone(),
+        Arc::new(transaction_handler),
+        None,
+    );
 
     Ok(HandlerFixture {
         handler,
@@ -319,3 +332,201 @@ async fn test_abort() -> eyre::Result<()> {
     );
     Ok(())
 }
+
+#[derive(Default)]
+struct RecordingAbortNotifier {
+    abandoned: parking_lot::Mutex<Vec<(OprfKeyId, ShareEpoch)>>,
+}
+
+#[async_trait::async_trait]
+impl AbortNotifier for RecordingAbortNotifier {
+    async fn notify_abandoned(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+    ) -> eyre::Result<()> {
+        self.abandoned.lock().push((oprf_key_id, pending_epoch));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_abandon() -> eyre::Result<()> {
+    let fx = fixture().await?;
+    let key_id = OprfKeyId::new(U160::from(242u32));
+    let confirmed_epoch = ShareEpoch::default();
+    let pending_epoch = confirmed_epoch.next();
+
+    fx.add_random_key_material_with_id_epoch(key_id, confirmed_epoch, &mut rand::thread_rng())
+        .await?;
+    fx.secret_gen
+        .reshare_round1(key_id, pending_epoch, NonZeroU16::new(2).expect("non-zero"))
+        .await?;
+
+    let abort_notifier = Arc::new(RecordingAbortNotifier::default());
+    let handler = KeyRegistryEventHandler::new(
+        OprfKeyRegistry::new(
+            CONTRACT_ADDRESS,
+            HttpRpcProvider::with_mock_asserter(Asserter::new()).inner(),
+        ),
+        fx.secret_gen.clone(),
+        Arc::new(TransactionHandler::new(TransactionHandlerArgs {
+            max_wait_time_watch_transaction: Duration::from_secs(10),
+            confirmations_for_transaction: 1,
+            sleep_between_get_receipt: Duration::from_millis(500),
+            max_tries_fetching_receipt: 5,
+            max_gas_per_transaction: 10_000_000,
+            fee_escalation: FEE_ESCALATION,
+            rpc_provider: HttpRpcProvider::with_mock_asserter(Asserter::new()),
+            wallet_address: WALLET_ADDRESS,
+            contract_address: CONTRACT_ADDRESS,
+        })),
+        Some(abort_notifier.clone()),
+    );
+    handler.abandon(key_id, pending_epoch).await?;
+
+    // In-progress state cleared.
+    let err = fx
+        .secret_manager
+        .fetch_keygen_intermediates(key_id, pending_epoch)
+        .await
+        .expect_err("intermediates must be gone");
+    assert!(
+        matches!(err, SecretManagerError::MissingIntermediates(id, ep) if id == key_id && ep == pending_epoch),
+        "unexpected error: {err}"
+    );
+
+    // Confirmed share preserved.
+    assert!(
+        fx.secret_manager
+            .get_share_by_epoch(key_id, confirmed_epoch)
+            .await?
+            .is_some()
+    );
+    assert_eq!(
+        *abort_notifier.abandoned.lock(),
+        vec![(key_id, pending_epoch)]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_protocol_deadlines() {
+    let timeout = Duration::from_millis(100);
+    let mut deadlines = ProtocolDeadlines::new(timeout);
+    let key_id = OprfKeyId::new(U160::from(1u32));
+    let other_key_id = OprfKeyId::new(U160::from(2u32));
+    let threshold = NonZeroU16::new(2).expect("non-zero");
+    assert!(deadlines.next().is_none(), "nothing tracked yet");
+
+    deadlines.observe(&KeyRegistryEvent::KeyGenRound1 { key_id, threshold });
+    let run = deadlines.next().expect("key-gen is tracked");
+    assert_eq!(run.key_id, key_id);
+    assert_eq!(run.epoch, ShareEpoch::default());
+
+    // later events of the same run do not extend the deadline
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    deadlines.observe(&KeyRegistryEvent::Round2 {
+        key_id,
+        epoch: ShareEpoch::default(),
+    });
+    deadlines.observe(&KeyRegistryEvent::ReshareRound1 {
+        key_id: other_key_id,
+        epoch: ShareEpoch::default().next(),
+        threshold,
+    });
+    assert_eq!(deadlines.next(), Some(run));
+    assert_eq!(deadlines.get(key_id), Some(run));
+    assert_eq!(deadlines.runs().count(), 2, "both runs are tracked");
+
+    // the run expires after the timeout
+    let expired = super::deadlines::expired(deadlines.next()).await;
+    assert_eq!(expired, run);
+    assert!(tokio::time::Instant::now() >= run.deadline);
+
+    deadlines.observe(&KeyRegistryEvent::Finalize {
+        key_id,
+        epoch: ShareEpoch::default(),
+    });
+    let run = deadlines.next().expect("reshare is still tracked");
+    assert_eq!(run.key_id, other_key_id);
+    deadlines.observe(&KeyRegistryEvent::Abort {
+        key_id: other_key_id,
+    });
+    assert!(deadlines.next().is_none(), "all runs finished");
+}
+
+#[test]
+fn test_finalized_epochs() {
+    let mut finalized = FinalizedEpochs::new();
+    let key_id = OprfKeyId::new(U160::from(1u32));
+    let epoch1 = ShareEpoch::default().next();
+    let epoch2 = epoch1.next();
+    let finalize = |epoch| KeyRegistryEvent::Finalize { key_id, epoch };
+
+    // finalize of epoch 2 arrives before the finalize of epoch 1
+    assert_eq!(finalized.observe(&finalize(epoch2)), None);
+    assert_eq!(
+        finalized.observe(&finalize(epoch1)),
+        Some(epoch2),
+        "older finalize is stale"
+    );
+    assert_eq!(
+        finalized.observe(&finalize(epoch2)),
+        Some(epoch2),
+        "same epoch is stale"
+    );
+    assert_eq!(
+        finalized.observe(&KeyRegistryEvent::Round2 {
+            key_id,
+            epoch: epoch1
+        }),
+        Some(epoch2),
+        "round of an older run is stale"
+    );
+    assert_eq!(
+        finalized.observe(&KeyRegistryEvent::Round2 {
+            key_id,
+            epoch: epoch2.next()
+        }),
+        None,
+        "round of the next reshare is in order"
+    );
+    assert_eq!(
+        finalized.observe(&KeyRegistryEvent::Finalize {
+            key_id: OprfKeyId::new(U160::from(2u32)),
+            epoch: epoch1,
+        }),
+        None,
+        "epochs are tracked per key"
+    );
+
+    finalized.observe(&KeyRegistryEvent::Delete { key_id });
+    assert_eq!(
+        finalized.observe(&finalize(epoch1)),
+        None,
+        "deleted keys are not tracked"
+    );
+}
+
+#[test]
+fn test_resubscribe_backoff() {
+    let mut backoff = ResubscribeBackoff::new(4, Duration::from_secs(1), Duration::from_secs(5));
+    let backoffs = std::iter::from_fn(|| backoff.next_backoff()).collect::<Vec<_>>();
+    assert_eq!(
+        backoffs,
+        [1, 2, 4, 5].map(Duration::from_secs),
+        "doubles up to the max"
+    );
+    assert_eq!(backoff.next_backoff(), None, "attempts are exhausted");
+
+    backoff.reset();
+    assert_eq!(
+        backoff.next_backoff(),
+        Some(Duration::from_secs(1)),
+        "starts over after a reset"
+    );
+
+    let mut backoff = ResubscribeBackoff::new(0, Duration::from_secs(1), Duration::from_secs(5));
+    assert_eq!(backoff.next_backoff(), None, "no attempt is made");
+}
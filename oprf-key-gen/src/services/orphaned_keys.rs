@@ -0,0 +1,152 @@
+//! Startup reconciliation of the stored keys with the `OprfKeyRegistry`.
+//!
+//! A missed `KeyDeletion` event (e.g. an event before the persisted chain cursor) leaves the share of a deleted key in the secret manager forever. If enabled with `orphaned_keys` (see [`crate::config::OprfKeyGenServiceConfig`]), the key-gen lists the stored keys of every registry once at startup and asks the registry for each of them. Keys the registry reports as deleted or unknown are orphans and are reported, quarantined or deleted according to [`OrphanedKeyAction`]. With `orphaned_keys_dry_run`, the orphans are only logged.
+//!
+//! Keys that cannot be compared (e.g. because of an RPC error) and orphans that cannot be quarantined or deleted are counted as failed and kept. They are compared again at the next startup.
+
+use alloy::{primitives::Address, providers::DynProvider};
+use eyre::Context as _;
+use oprf_types::{
+    OprfKeyId,
+    chain::OprfKeyRegistry::{OprfKeyRegistryErrors, OprfKeyRegistryInstance},
+};
+
+use crate::{
+    config::OrphanedKeyAction,
+    metrics::{
+        self,
+        reconciliation::{
+            OUTCOME_DELETED, OUTCOME_DRY_RUN, OUTCOME_FAILED, OUTCOME_QUARANTINED, OUTCOME_REPORTED,
+        },
+    },
+    secret_manager::SecretManagerService,
+};
+
+/// The outcome of a reconciliation run of one registry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ReconciliationSummary {
+    /// Keys with a confirmed share in the secret manager.
+    pub(crate) stored: usize,
+    /// Stored keys the registry reported as deleted or unknown.
+    pub(crate) orphaned: usize,
+    /// Orphans that were logged but kept.
+    pub(crate) reported: usize,
+    /// Orphans that were quarantined.
+    pub(crate) quarantined: usize,
+    /// Orphans that were deleted.
+    pub(crate) deleted: usize,
+    /// Orphans that were kept because of the dry-run mode.
+    pub(crate) dry_run: usize,
+    /// Keys that could not be compared, or orphans that could not be quarantined or deleted.
+    pub(crate) failed: usize,
+}
+
+/// Compares the keys stored in `secret_manager` with the registry `contract` and applies `action` to the orphans.
+///
+/// Does nothing for [`OrphanedKeyAction::Off`].
+///
+/// # Errors
+///
+/// Returns an error if the stored keys cannot be listed. Errors for single keys are logged and counted as failed.
+pub(crate) async fn reconcile(
+    contract: &OprfKeyRegistryInstance<DynProvider>,
+    secret_manager: &SecretManagerService,
+    action: OrphanedKeyAction,
+    dry_run: bool,
+) -> eyre::Result<ReconciliationSummary> {
+    let mut summary = ReconciliationSummary::default();
+    if action == OrphanedKeyAction::Off {
+        return Ok(summary);
+    }
+    let address = *contract.address();
+    tracing::info!("reconciling stored keys with the registry ({action:?}, dry run: {dry_run})..");
+    let oprf_key_ids = secret_manager
+        .list_oprf_key_ids()
+        .await
+        .context("while listing the stored keys")?;
+    summary.stored = oprf_key_ids.len();
+
+    for oprf_key_id in oprf_key_ids {
+        match is_orphaned(contract, oprf_key_id).await {
+            Ok(false) => continue,
+            Ok(true) => summary.orphaned += 1,
+            Err(err) => {
+                tracing::warn!("cannot compare {oprf_key_id} with the registry: {err:?}");
+                record(address, &mut summary.failed, OUTCOME_FAILED);
+                continue;
+            }
+        }
+        let result = match action {
+            OrphanedKeyAction::Off | OrphanedKeyAction::Report => {
+                tracing::warn!("{oprf_key_id} is not registered anymore, keeping its share");
+                record(address, &mut summary.reported, OUTCOME_REPORTED);
+                continue;
+            }
+            _ if dry_run => {
+                tracing::warn!("{oprf_key_id} is not registered anymore, would {action:?} it");
+                record(address, &mut summary.dry_run, OUTCOME_DRY_RUN);
+                continue;
+            }
+            OrphanedKeyAction::Quarantine => secret_manager
+                .quarantine_oprf_key_material(oprf_key_id)
+                .await
+                .map(|()| (&mut summary.quarantined, OUTCOME_QUARANTINED)),
+            OrphanedKeyAction::Delete => secret_manager
+                .delete_oprf_key_material(oprf_key_id)
+                .await
+                .map(|()| (&mut summary.deleted, OUTCOME_DELETED)),
+        };
+        match result {
+            Ok((count, outcome)) => {
+                tracing::info!("{oprf_key_id} is not registered anymore, {outcome} its share");
+                record(address, count, outcome);
+            }
+            Err(err) => {
+                tracing::warn!("cannot {action:?} {oprf_key_id}: {err:?}");
+                record(address, &mut summary.failed, OUTCOME_FAILED);
+            }
+        }
+    }
+
+    metrics::reconciliation::set_stored_keys(address, summary.stored);
+    metrics::reconciliation::set_orphaned_keys(address, summary.orphaned);
+    tracing::info!(
+        "reconciled {} stored keys: {} orphaned ({} reported, {} quarantined, {} deleted, {} dry run), {} failed",
+        summary.stored,
+        summary.orphaned,
+        summary.reported,
+        summary.quarantined,
+        summary.deleted,
+        summary.dry_run,
+        summary.failed,
+    );
+    Ok(summary)
+}
+
+/// Whether the registry reports `oprf_key_id` as deleted or unknown.
+async fn is_orphaned(
+    contract: &OprfKeyRegistryInstance<DynProvider>,
+    oprf_key_id: OprfKeyId,
+) -> eyre::Result<bool> {
+    match contract
+        .getOprfPublicKeyAndEpoch(oprf_key_id.into_inner())
+        .call()
+        .await
+    {
+        Ok(_) => Ok(false),
+        Err(err) => match err.as_decoded_interface_error::<OprfKeyRegistryErrors>() {
+            Some(OprfKeyRegistryErrors::DeletedId(_) | OprfKeyRegistryErrors::UnknownId(_)) => {
+                Ok(true)
+            }
+            _ => Err(err.into()),
+        },
+    }
+}
+
+fn record(address: Address, count: &mut usize, outcome: &'static str) {
+    *count += 1;
+    metrics::reconciliation::inc_keys(address, outcome);
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,305 @@
+use std::{
+    num::NonZeroU16,
+    sync::{Arc, Mutex},
+};
+
+use alloy::{
+    primitives::{Address, Bytes, U256},
+    providers::mock::Asserter,
+    sol_types::{SolCall as _, SolInterface as _},
+};
+use async_trait::async_trait;
+use nodes_common::web3::HttpRpcProvider;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    chain::{
+        BabyJubJub,
+        OprfKeyGen::RegisteredOprfPublicKey,
+        OprfKeyRegistry::{
+            self, DeletedId, OprfKeyRegistryErrors, UnknownId, getOprfPublicKeyAndEpochCall,
+        },
+    },
+    crypto::OprfPublicKey,
+    service::NodeInformation,
+};
+
+use crate::{
+    config::OrphanedKeyAction,
+    secret_manager::{
+        KeyGenIntermediateValues, Result, SecretManager, SecretManagerError, SecretManagerService,
+    },
+};
+
+use super::{ReconciliationSummary, reconcile};
+
+const CONTRACT_ADDRESS: Address = Address::repeat_byte(0x42);
+
+/// Holds the ids of the stored keys and records which keys were quarantined or deleted.
+#[derive(Default)]
+struct InMemorySecretManager {
+    stored: Mutex<Vec<OprfKeyId>>,
+    quarantined: Mutex<Vec<OprfKeyId>>,
+    deleted: Mutex<Vec<OprfKeyId>>,
+}
+
+impl InMemorySecretManager {
+    fn with_keys(keys: &[OprfKeyId]) -> Self {
+        Self {
+            stored: Mutex::new(keys.to_vec()),
+            ..Self::default()
+        }
+    }
+
+    fn remove(&self, oprf_key_id: OprfKeyId, into: &Mutex<Vec<OprfKeyId>>) {
+        self.stored
+            .lock()
+            .expect("not poisoned")
+            .retain(|id| *id != oprf_key_id);
+        into.lock().expect("not poisoned").push(oprf_key_id);
+    }
+
+    fn stored(&self) -> Vec<OprfKeyId> {
+        self.stored.lock().expect("not poisoned").clone()
+    }
+
+    fn quarantined(&self) -> Vec<OprfKeyId> {
+        self.quarantined.lock().expect("not poisoned").clone()
+    }
+
+    fn deleted(&self) -> Vec<OprfKeyId> {
+        self.deleted.lock().expect("not poisoned").clone()
+    }
+}
+
+fn not_needed() -> SecretManagerError {
+    SecretManagerError::Internal(eyre::eyre!("not needed in tests"))
+}
+
+#[async_trait]
+impl SecretManager for InMemorySecretManager {
+    async fn store_node_information(&self, _: NodeInformation) -> Result<()> {
+        Err(not_needed())
+    }
+
+    async fn get_share_by_epoch(
+        &self,
+        _: OprfKeyId,
+        _: ShareEpoch,
+    ) -> Result<Option<DLogShareShamir>> {
+        Err(not_needed())
+    }
+
+    async fn delete_oprf_key_material(&self, oprf_key_id: OprfKeyId) -> Result<()> {
+        self.remove(oprf_key_id, &self.deleted);
+        Ok(())
+    }
+
+    async fn list_oprf_key_ids(&self) -> Result<Vec<OprfKeyId>> {
+        Ok(self.stored())
+    }
+
+    async fn quarantine_oprf_key_material(&self, oprf_key_id: OprfKeyId) -> Result<()> {
+        self.remove(oprf_key_id, &self.quarantined);
+        Ok(())
+    }
+
+    async fn abort_keygen(&self, _: OprfKeyId) -> Result<()> {
+        Err(not_needed())
+    }
+
+    async fn try_store_keygen_intermediates(
+        &self,
+        _: OprfKeyId,
+        _: ShareEpoch,
+        _: NonZeroU16,
+        _: KeyGenIntermediateValues,
+    ) -> Result<KeyGenIntermediateValues> {
+        Err(not_needed())
+    }
+
+    async fn fetch_keygen_intermediates(
+        &self,
+        _: OprfKeyId,
+        _: ShareEpoch,
+    ) -> Result<KeyGenIntermediateValues> {
+        Err(not_needed())
+    }
+
+    async fn store_pending_dlog_share(
+        &self,
+        _: OprfKeyId,
+        _: ShareEpoch,
+        _: DLogShareShamir,
+    ) -> Result<()> {
+        Err(not_needed())
+    }
+
+    async fn confirm_dlog_share(
+        &self,
+        _: OprfKeyId,
+        _: ShareEpoch,
+        _: OprfPublicKey,
+    ) -> Result<()> {
+        Err(not_needed())
+    }
+}
+
+/// The keys of the fixture: registered, deleted, unknown, and one the registry cannot be asked for.
+fn keys() -> [OprfKeyId; 4] {
+    [
+        OprfKeyId::from(1usize),
+        OprfKeyId::from(2usize),
+        OprfKeyId::from(3usize),
+        OprfKeyId::from(4usize),
+    ]
+}
+
+/// Queues the registry responses for [`keys`], in order.
+fn registry(
+    keys: [OprfKeyId; 4],
+) -> OprfKeyRegistry::OprfKeyRegistryInstance<alloy::providers::DynProvider> {
+    let asserter = Asserter::new();
+    let registered = RegisteredOprfPublicKey {
+        key: BabyJubJub::Affine {
+            x: U256::from(1),
+            y: U256::from(2),
+        },
+        epoch: 1,
+    };
+    asserter.push_success(&Bytes::from(
+        getOprfPublicKeyAndEpochCall::abi_encode_returns(&registered),
+    ));
+    push_revert(
+        &asserter,
+        &OprfKeyRegistryErrors::DeletedId(DeletedId {
+            id: keys[1].into_inner(),
+        }),
+    );
+    push_revert(
+        &asserter,
+        &OprfKeyRegistryErrors::UnknownId(UnknownId {
+            id: keys[2].into_inner(),
+        }),
+    );
+    asserter.push_failure_msg("rpc is down");
+    let provider = HttpRpcProvider::with_mock_asserter(asserter);
+    OprfKeyRegistry::new(CONTRACT_ADDRESS, provider.inner())
+}
+
+fn push_revert(asserter: &Asserter, error: &OprfKeyRegistryErrors) {
+    let data = alloy::hex::encode_prefixed(error.abi_encode());
+    asserter.push_failure(alloy::rpc::json_rpc::ErrorPayload {
+        code: 3,
+        message: "execution reverted".into(),
+        data: Some(serde_json::value::to_raw_value(&data).expect("valid json")),
+    });
+}
+
+async fn run(
+    action: OrphanedKeyAction,
+    dry_run: bool,
+) -> eyre::Result<(ReconciliationSummary, Arc<InMemorySecretManager>)> {
+    let keys = keys();
+    let secret_manager = Arc::new(InMemorySecretManager::with_keys(&keys));
+    let service: SecretManagerService = secret_manager.clone();
+    let summary = reconcile(&registry(keys), &service, action, dry_run).await?;
+    Ok((summary, secret_manager))
+}
+
+#[tokio::test]
+async fn off_does_not_compare_keys() -> eyre::Result<()> {
+    let (summary, secret_manager) = run(OrphanedKeyAction::Off, false).await?;
+    assert_eq!(
+        summary,
+        ReconciliationSummary::default(),
+        "Should do nothing"
+    );
+    assert_eq!(secret_manager.stored(), keys(), "Should keep all keys");
+    Ok(())
+}
+
+#[tokio::test]
+async fn report_keeps_orphans() -> eyre::Result<()> {
+    let (summary, secret_manager) = run(OrphanedKeyAction::Report, false).await?;
+    assert_eq!(
+        summary,
+        ReconciliationSummary {
+            stored: 4,
+            orphaned: 2,
+            reported: 2,
+            failed: 1,
+            ..Default::default()
+        },
+        "Should report the deleted and the unknown key"
+    );
+    assert_eq!(secret_manager.stored(), keys(), "Should keep all keys");
+    Ok(())
+}
+
+#[tokio::test]
+async fn quarantine_moves_orphans() -> eyre::Result<()> {
+    let keys = keys();
+    let (summary, secret_manager) = run(OrphanedKeyAction::Quarantine, false).await?;
+    assert_eq!(
+        summary,
+        ReconciliationSummary {
+            stored: 4,
+            orphaned: 2,
+            quarantined: 2,
+            failed: 1,
+            ..Default::default()
+        },
+        "Should quarantine the deleted and the unknown key"
+    );
+    assert_eq!(
+        secret_manager.quarantined(),
+        [keys[1], keys[2]],
+        "Should quarantine the orphans"
+    );
+    assert_eq!(
+        secret_manager.stored(),
+        [keys[0], keys[3]],
+        "Should keep the registered key and the key that could not be compared"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_removes_orphans() -> eyre::Result<()> {
+    let keys = keys();
+    let (summary, secret_manager) = run(OrphanedKeyAction::Delete, false).await?;
+    assert_eq!(summary.deleted, 2, "Should delete the orphans");
+    assert_eq!(
+        secret_manager.deleted(),
+        [keys[1], keys[2]],
+        "Should delete the deleted and the unknown key"
+    );
+    assert!(
+        secret_manager.quarantined().is_empty(),
+        "Should not quarantine keys"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn dry_run_keeps_orphans() -> eyre::Result<()> {
+    let (summary, secret_manager) = run(OrphanedKeyAction::Delete, true).await?;
+    assert_eq!(
+        summary,
+        ReconciliationSummary {
+            stored: 4,
+            orphaned: 2,
+            dry_run: 2,
+            failed: 1,
+            ..Default::default()
+        },
+        "Should only count the orphans"
+    );
+    assert_eq!(secret_manager.stored(), keys(), "Should keep all keys");
+    assert!(
+        secret_manager.deleted().is_empty(),
+        "Should not delete keys"
+    );
+    Ok(())
+}
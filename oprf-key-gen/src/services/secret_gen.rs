@@ -5,8 +5,8 @@
 //! This intermediate state is persisted via the [`SecretManager`](crate::secret_manager::SecretManager)
 //! between protocol rounds and removed again when a run is finalized, aborted, or deleted.
 //!
-//! Currently, there is no timeout for a protocol run. Therefore, stale in-progress state is not
-//! cleaned up automatically if a run never finishes.
+//! The key-event watcher evicts the in-progress state of a run that did not finish within the
+//! configured `protocol_timeout` via [`DLogSecretGenService::abort_keygen`].
 //!
 //! **Important:** This service is **not thread-safe**. It is intended to be used
 //! only in contexts where a single dedicated task owns the struct. No internal
@@ -139,7 +139,7 @@ impl KeyGenIntermediateValues {
     ///
     /// * `degree` - The degree of the polynomial to be generated (relates to threshold settings).
     /// * `rng` - A mutable reference to a cryptographically secure random number generator.
-    fn new<R: Rng + CryptoRng>(degree: usize, rng: &mut R) -> Self {
+    pub(crate) fn new<R: Rng + CryptoRng>(degree: usize, rng: &mut R) -> Self {
         let poly = KeyGenPoly::new(rng, degree);
         let sk = EphemeralEncryptionPrivateKey::generate(rng);
         Self {
@@ -174,16 +174,20 @@ impl KeyGenIntermediateValues {
         }
     }
 
-    fn build_round1_contribution(&self) -> Round1Contribution {
+    /// The [`SecretGenCommitment`] of a producer, `None` for a consumer.
+    fn commitment(&self) -> Option<SecretGenCommitment> {
         let Self { sk, poly } = self;
-        if let Some(poly) = poly {
-            Round1Contribution::from(SecretGenCommitment {
-                comm_share: poly.get_pk_share(),
-                comm_coeffs: poly.get_coeff_commitment(),
-                eph_pub_key: sk.get_public_key(),
-            })
-        } else {
-            Round1Contribution::from(sk.get_public_key())
+        poly.as_ref().map(|poly| SecretGenCommitment {
+            comm_share: poly.get_pk_share(),
+            comm_coeffs: poly.get_coeff_commitment(),
+            eph_pub_key: sk.get_public_key(),
+        })
+    }
+
+    fn build_round1_contribution(&self) -> Round1Contribution {
+        match self.commitment() {
+            Some(commitment) => Round1Contribution::from(commitment),
+            None => Round1Contribution::from(self.sk.get_public_key()),
         }
     }
 }
@@ -200,6 +204,19 @@ impl DLogSecretGenService {
         }
     }
 
+    /// Returns a service that shares the Groth16 material of this service but persists its state in `secret_manager`, e.g. the secret manager of another registry.
+    pub(crate) fn with_secret_manager(&self, secret_manager: SecretManagerService) -> Self {
+        Self {
+            secret_manager,
+            key_gen_material: Arc::clone(&self.key_gen_material),
+        }
+    }
+
+    /// The Groth16 material of the key-gen circuit.
+    pub(crate) fn key_gen_material(&self) -> &CircomGroth16Material {
+        &self.key_gen_material
+    }
+
     /// Deletes all material associated with the [`OprfKeyId`].
     pub(crate) async fn delete_oprf_key_material(
         &self,
@@ -232,14 +249,45 @@ impl DLogSecretGenService {
         pending_epoch: ShareEpoch,
         threshold: NonZeroU16,
     ) -> SecretGenResult<Round1Contribution> {
+        let intermediates = self
+            .keygen_intermediates(oprf_key_id, pending_epoch, threshold)
+            .await?;
+        Ok(intermediates.build_round1_contribution())
+    }
+
+    /// Executes round 1 of the key-gen protocol like [`Self::key_gen_round1`], but returns the [`SecretGenCommitment`] instead of the on-chain contribution.
+    ///
+    /// Used by the offline [ceremony](crate::ceremony), which writes the commitment to a file.
+    pub(crate) async fn key_gen_round1_commitment(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+        threshold: NonZeroU16,
+    ) -> SecretGenResult<SecretGenCommitment> {
+        let intermediates = self
+            .keygen_intermediates(oprf_key_id, pending_epoch, threshold)
+            .await?;
+        let commitment = intermediates
+            .commitment()
+            .context("stored intermediates belong to a consumer")?;
+        Ok(commitment)
+    }
+
+    /// Creates and stores new key-gen intermediates. If intermediates exist already, they are returned instead.
+    async fn keygen_intermediates(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+        threshold: NonZeroU16,
+    ) -> SecretGenResult<KeyGenIntermediateValues> {
         tracing::trace!("secret gen round1 - creating new intermediates");
         let degree = usize::from(threshold.get() - 1);
         let intermediates = KeyGenIntermediateValues::new(degree, &mut rand::thread_rng());
         let intermediates = self
             .secret_manager
-            .try_store_keygen_intermediates(oprf_key_id, pending_epoch, intermediates)
+            .try_store_keygen_intermediates(oprf_key_id, pending_epoch, threshold, intermediates)
             .await?;
-        Ok(intermediates.build_round1_contribution())
+        Ok(intermediates)
     }
 
     /// Executes the producer round 2 of the key-gen/reshare protocol.
@@ -318,6 +366,20 @@ impl DLogSecretGenService {
         Ok(())
     }
 
+    /// Finalizes several key generations (or reshares) with one write to the secret manager.
+    ///
+    /// Confirms the pending shares, see [`Self::finalize`] and [`SecretManager::confirm_dlog_shares`](crate::secret_manager::SecretManager::confirm_dlog_shares).
+    ///
+    /// # Arguments
+    /// * `shares` - The identifiers, generated epochs and generated [`OprfPublicKey`]s of the finalized keys.
+    pub(crate) async fn finalize_batch(
+        &self,
+        shares: Vec<(OprfKeyId, ShareEpoch, OprfPublicKey)>,
+    ) -> SecretGenResult<()> {
+        self.secret_manager.confirm_dlog_shares(shares).await?;
+        Ok(())
+    }
+
     /// Executes round 1 of the reshare protocol.
     ///
     /// Generates a secret-sharing polynomial where the secret value is the previously confirmed share and persists the resulting intermediate values.
@@ -352,7 +414,7 @@ impl DLogSecretGenService {
 
         let intermediates = self
             .secret_manager
-            .try_store_keygen_intermediates(oprf_key_id, pending_epoch, intermediates)
+            .try_store_keygen_intermediates(oprf_key_id, pending_epoch, threshold, intermediates)
             .await?;
         Ok(intermediates.build_round1_contribution())
     }
@@ -6,7 +6,11 @@
 //! Current `SecretManager` implementations:
 //! - Postgres
 
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    num::NonZeroU16,
+    sync::Arc,
+};
 
 use async_trait::async_trait;
 use oprf_core::ddlog_equality::shamir::DLogShareShamir;
@@ -65,6 +69,24 @@ pub trait SecretManager {
     /// Removes finalized share material and any in-progress state for the specified [`OprfKeyId`].
     async fn delete_oprf_key_material(&self, oprf_key_id: OprfKeyId) -> Result<()>;
 
+    /// Returns the ids of all keys with a confirmed share that was not deleted.
+    ///
+    /// Used by the startup reconciliation to find shares of keys the `OprfKeyRegistry` no longer knows. The default implementation returns [`SecretManagerError::Internal`], as not every store can list its keys.
+    async fn list_oprf_key_ids(&self) -> Result<Vec<OprfKeyId>> {
+        Err(SecretManagerError::Internal(eyre::eyre!(
+            "listing the stored keys is not supported by this secret manager"
+        )))
+    }
+
+    /// Moves the confirmed share of the specified [`OprfKeyId`] out of use and removes any in-progress state for it.
+    ///
+    /// In contrast to [`Self::delete_oprf_key_material`] the share is kept, so that an operator can restore it. Must return `Ok(())` if there is no share for the key. The default implementation returns [`SecretManagerError::Internal`].
+    async fn quarantine_oprf_key_material(&self, oprf_key_id: OprfKeyId) -> Result<()> {
+        Err(SecretManagerError::Internal(eyre::eyre!(
+            "cannot quarantine {oprf_key_id}, quarantining is not supported by this secret manager"
+        )))
+    }
+
     /// Aborts ALL in progress key generations (or reshare).
     ///
     /// In contrast to [`Self::delete_oprf_key_material`] this method only removes in-progress state associated with the given key. It MUST not delete finalized shares.
@@ -83,10 +105,13 @@ pub trait SecretManager {
     /// Tries to persist the intermediate values needed for key generation (or reshare).
     ///
     /// If intermediate values already exist for this `OprfKeyId` and `ShareEpoch` pair, this method must return the already stored `KeyGenIntermediateValues` and discard the new ones.
+    ///
+    /// The `threshold` of the key-gen is kept with the intermediates and stored next to the share by [`Self::confirm_dlog_share`].
     async fn try_store_keygen_intermediates(
         &self,
         oprf_key_id: OprfKeyId,
         pending_epoch: ShareEpoch,
+        threshold: NonZeroU16,
         intermediate: KeyGenIntermediateValues,
     ) -> Result<KeyGenIntermediateValues>;
 
@@ -114,7 +139,7 @@ pub trait SecretManager {
 
     /// Confirms a pending share and finalizes key-generation for the given epoch.
     ///
-    /// This method MUST store the confirmed share and delete all in-progress intermediates
+    /// This method MUST store the confirmed share together with the threshold of the key-gen and delete all in-progress intermediates
     /// associated with this [`OprfKeyId`]. After calling this method, the share for the provided
     /// epoch MUST be ready to use.
     ///
@@ -131,4 +156,46 @@ pub trait SecretManager {
         epoch: ShareEpoch,
         public_key: OprfPublicKey,
     ) -> Result<()>;
+
+    /// Confirms several pending shares, see [`Self::confirm_dlog_share`].
+    ///
+    /// Used when replaying many finalize events at once (e.g. while catching up after downtime). If the batch contains several epochs of a key, only the latest one is confirmed (see [`latest_epoch_per_key`]), as confirming a share deletes all intermediates of its key. Implementations SHOULD confirm all shares in one transaction with a constant number of statements, so that either all or none of them are confirmed, independent of the size of the batch.
+    ///
+    /// The default implementation, meant for stores without transactions, calls [`Self::confirm_dlog_share`] for every share and stops at the first error, so the shares before it stay confirmed.
+    async fn confirm_dlog_shares(
+        &self,
+        shares: Vec<(OprfKeyId, ShareEpoch, OprfPublicKey)>,
+    ) -> Result<()> {
+        for (oprf_key_id, epoch, public_key) in latest_epoch_per_key(shares) {
+            self.confirm_dlog_share(oprf_key_id, epoch, public_key)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps only the latest epoch of every key of a batch for [`SecretManager::confirm_dlog_shares`].
+///
+/// The keys keep the position of their first share in the batch.
+#[must_use]
+pub fn latest_epoch_per_key(
+    shares: Vec<(OprfKeyId, ShareEpoch, OprfPublicKey)>,
+) -> Vec<(OprfKeyId, ShareEpoch, OprfPublicKey)> {
+    let mut latest: Vec<(OprfKeyId, ShareEpoch, OprfPublicKey)> = Vec::with_capacity(shares.len());
+    let mut positions = HashMap::<OprfKeyId, usize>::with_capacity(shares.len());
+    for share in shares {
+        match positions.entry(share.0) {
+            Entry::Occupied(position) => {
+                let kept = &mut latest[*position.get()];
+                if kept.1 < share.1 {
+                    *kept = share;
+                }
+            }
+            Entry::Vacant(position) => {
+                position.insert(latest.len());
+                latest.push(share);
+            }
+        }
+    }
+    latest
 }
@@ -1,14 +1,22 @@
-use std::{f64, time::Duration};
+//! Transaction submission for the key-gen protocol.
+//!
+//! The key-event watcher submits its round contributions through a [`TransactionSubmitterService`]. The default implementation simulates every call, broadcasts it with the configured wallet and waits for confirmations. Embedders can provide their own [`TransactionSubmitter`] (e.g. a relayer or a custodial signer) via [`crate::OprfKeyGenBuilder::transaction_submitter`].
+//!
+//! The default implementation sends EIP-1559 transactions with an explicit nonce. If a transaction is not mined within `max_wait_time_transaction_confirmation`, it is replaced by a transaction with the same nonce and escalated fees (see [`FeeEscalation`]), until one of the sent transactions is mined or the fees reach the configured cap.
+
+use std::{f64, sync::Arc, time::Duration};
 
 use alloy::{
     contract::{CallBuilder, CallDecoder},
-    network::ReceiptResponse,
+    eips::eip1559::Eip1559Estimation,
+    network::{ReceiptResponse, primitives::TransactionFailedError},
     primitives::{Address, TxHash},
     providers::{DynProvider, PendingTransactionError, Provider, WatchTxError},
     rpc::types::TransactionReceipt,
-    transports::{RpcError, TransportError},
+    transports::{RpcError, TransportError, TransportErrorKind},
 };
-use backon::{BackoffBuilder as _, ConstantBackoff, ConstantBuilder, Retryable as _};
+use async_trait::async_trait;
+use backon::Retryable as _;
 use nodes_common::web3;
 use oprf_types::{
     OprfKeyId,
@@ -16,28 +24,128 @@ use oprf_types::{
         OprfKeyGen::Round1Contribution, OprfKeyGen::Round2Contribution,
         OprfKeyRegistry::OprfKeyRegistryInstance,
     },
+    retry::RetryPolicy,
 };
 use tracing::instrument;
 
-use crate::{metrics, services::key_event_watcher::KeyRegistryEventError};
+use crate::{config::OprfKeyGenServiceConfig, metrics};
+
+/// Dynamic trait object for the transaction submitter.
+pub type TransactionSubmitterService = Arc<dyn TransactionSubmitter + Send + Sync>;
+
+/// All errors that might occur when submitting a transaction.
+///
+/// Reverts should be reported as [`TransactionSubmitterError::Contract`], so the key-event watcher can decode the revert reason.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TransactionSubmitterError {
+    /// Error when interacting with the contract, including reverts.
+    #[error(transparent)]
+    Contract(#[from] alloy::contract::Error),
+    /// RPC error.
+    #[error(transparent)]
+    Rpc(#[from] RpcError<TransportErrorKind>),
+    /// Error while waiting for the transaction.
+    #[error(transparent)]
+    PendingTransaction(#[from] PendingTransactionError),
+    /// The transaction was included but failed.
+    #[error(transparent)]
+    TransactionFailed(#[from] TransactionFailedError),
+    /// Implementation dependent error.
+    #[error(transparent)]
+    Internal(#[from] eyre::Report),
+}
+
+/// Submits the round contributions of the key-gen protocol to the `OprfKeyRegistry`.
+///
+/// Every method returns once the transaction is confirmed.
+#[async_trait]
+pub trait TransactionSubmitter {
+    /// Submits a round-1 key-gen contribution (`addRound1KeyGenContribution`).
+    async fn add_round1_keygen_contribution(
+        &self,
+        oprf_key_id: OprfKeyId,
+        contribution: Round1Contribution,
+    ) -> Result<TxHash, TransactionSubmitterError>;
+
+    /// Submits a round-1 reshare contribution (`addRound1ReshareContribution`).
+    async fn add_round1_reshare_contribution(
+        &self,
+        oprf_key_id: OprfKeyId,
+        contribution: Round1Contribution,
+    ) -> Result<TxHash, TransactionSubmitterError>;
+
+    /// Submits a round-2 contribution (`addRound2Contribution`).
+    async fn add_round2_contribution(
+        &self,
+        oprf_key_id: OprfKeyId,
+        contribution: Round2Contribution,
+    ) -> Result<TxHash, TransactionSubmitterError>;
+
+    /// Submits a round-3 contribution (`addRound3Contribution`).
+    async fn add_round3_contribution(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> Result<TxHash, TransactionSubmitterError>;
+}
+
+/// Replacement of transactions that are not mined in time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FeeEscalation {
+    /// Maximum number of replacements of a single transaction. `0` disables the replacement.
+    pub(crate) max_bumps: usize,
+    /// Increase of `max_fee_per_gas` and `max_priority_fee_per_gas` per replacement, in percent.
+    pub(crate) bump_percent: u64,
+    /// Upper bound of `max_fee_per_gas` of every transaction, in wei.
+    pub(crate) max_fee_per_gas_cap: u128,
+}
+
+impl FeeEscalation {
+    /// Caps the estimated `fees` of the first transaction.
+    fn initial_fees(self, fees: Eip1559Estimation) -> Eip1559Estimation {
+        let max_fee_per_gas = fees.max_fee_per_gas.min(self.max_fee_per_gas_cap);
+        Eip1559Estimation {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: fees.max_priority_fee_per_gas.min(max_fee_per_gas),
+        }
+    }
+
+    /// The fees of the replacement of a transaction with `fees`. Returns `None` if `fees` reached the cap already.
+    fn bump(self, fees: Eip1559Estimation) -> Option<Eip1559Estimation> {
+        if fees.max_fee_per_gas >= self.max_fee_per_gas_cap {
+            return None;
+        }
+        let escalate = |fee: u128| {
+            fee.saturating_mul(100 + u128::from(self.bump_percent))
+                .div_ceil(100)
+        };
+        let max_fee_per_gas = escalate(fees.max_fee_per_gas).min(self.max_fee_per_gas_cap);
+        Some(Eip1559Estimation {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: escalate(fees.max_priority_fee_per_gas).min(max_fee_per_gas),
+        })
+    }
+}
 
 /// Service that handles transaction submission and receipt confirmation.
 ///
 /// Simulates each call first (`eth_call` pre-flight via [`submit`](TransactionHandler::submit)),
 /// then broadcasts the transaction and polls for receipts, retrying on `NullResp` responses up
-/// to `max_tries_fetching_receipt` times.  On receipt, [`ReceiptResponse::ensure_success`] is
-/// called to surface reverts.  Gas price and wallet balance are recorded as metrics after every
-/// confirmed transaction.
+/// to `max_tries_fetching_receipt` times.  Transactions that are not mined in time are replaced
+/// with escalated fees according to the [`FeeEscalation`].  On receipt,
+/// [`ReceiptResponse::ensure_success`] is called to surface reverts.  Gas price and wallet
+/// balance are recorded as metrics after every confirmed transaction.
 #[derive(Clone)]
 pub(crate) struct TransactionHandler {
     max_wait_time_watch_transaction: Duration,
     confirmations_for_transaction: u64,
-    sleep_between_get_receipt: Duration,
-    max_tries_fetching_receipt: usize,
+    receipt_retry_policy: RetryPolicy,
     max_gas_per_transaction: u64,
+    fee_escalation: FeeEscalation,
     rpc_provider: web3::HttpRpcProvider,
     wallet_address: Address,
     contract: OprfKeyRegistryInstance<DynProvider>,
+    send_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 /// Construction arguments for [`TransactionHandler`].
@@ -54,6 +162,8 @@ pub(crate) struct TransactionHandlerArgs {
     pub(crate) max_tries_fetching_receipt: usize,
     /// Gas limit applied to every call and send, in gas units.
     pub(crate) max_gas_per_transaction: u64,
+    /// Replacement of transactions that are not mined within `max_wait_time_watch_transaction`.
+    pub(crate) fee_escalation: FeeEscalation,
     /// HTTP provider used for transaction submission, balance queries, and receipt polling.
     pub(crate) rpc_provider: web3::HttpRpcProvider,
     /// Wallet address used to query the on-chain balance after each confirmed transaction.
@@ -62,6 +172,31 @@ pub(crate) struct TransactionHandlerArgs {
     pub(crate) contract_address: Address,
 }
 
+impl TransactionHandlerArgs {
+    /// The arguments configured by `config`, sending with `wallet_address` over `rpc_provider`.
+    pub(crate) fn from_config(
+        config: &OprfKeyGenServiceConfig,
+        rpc_provider: web3::HttpRpcProvider,
+        wallet_address: Address,
+    ) -> Self {
+        Self {
+            max_wait_time_watch_transaction: config.max_wait_time_transaction_confirmation,
+            confirmations_for_transaction: config.confirmations_for_transaction,
+            sleep_between_get_receipt: config.sleep_between_get_receipt,
+            max_tries_fetching_receipt: config.max_tries_fetching_receipt,
+            max_gas_per_transaction: config.max_gas_per_transaction,
+            fee_escalation: FeeEscalation {
+                max_bumps: config.max_fee_bumps_per_transaction,
+                bump_percent: config.fee_bump_percent,
+                max_fee_per_gas_cap: u128::from(config.max_fee_per_gas_cap_gwei) * 1_000_000_000,
+            },
+            rpc_provider,
+            wallet_address,
+            contract_address: config.oprf_key_registry_contract,
+        }
+    }
+}
+
 impl From<TransactionHandlerArgs> for TransactionHandler {
     fn from(value: TransactionHandlerArgs) -> Self {
         let TransactionHandlerArgs {
@@ -70,6 +205,7 @@ impl From<TransactionHandlerArgs> for TransactionHandler {
             sleep_between_get_receipt,
             max_tries_fetching_receipt,
             max_gas_per_transaction,
+            fee_escalation,
             rpc_provider,
             wallet_address,
             contract_address,
@@ -77,12 +213,16 @@ impl From<TransactionHandlerArgs> for TransactionHandler {
         Self {
             max_wait_time_watch_transaction,
             confirmations_for_transaction,
-            sleep_between_get_receipt,
-            max_tries_fetching_receipt,
+            receipt_retry_policy: RetryPolicy::constant(
+                max_tries_fetching_receipt,
+                sleep_between_get_receipt,
+            ),
             max_gas_per_transaction,
+            fee_escalation,
             wallet_address,
             contract: OprfKeyRegistryInstance::new(contract_address, rpc_provider.inner()),
             rpc_provider,
+            send_lock: Arc::default(),
         }
     }
 }
@@ -93,17 +233,16 @@ impl TransactionHandler {
         Self::from(args)
     }
 
-    fn backoff_strategy(&self) -> ConstantBackoff {
-        ConstantBuilder::new()
-            .with_delay(self.sleep_between_get_receipt)
-            .with_max_times(self.max_tries_fetching_receipt)
-            .build()
+    /// Shares `send_lock` with other handlers of the same wallet, e.g. the handlers of other registries, so that only one of them sends a transaction at a time and they do not pick the same nonce.
+    pub(crate) fn with_send_lock(mut self, send_lock: Arc<tokio::sync::Mutex<()>>) -> Self {
+        self.send_lock = send_lock;
+        self
     }
 
     async fn simulate_transaction<D>(
         &self,
         transaction: CallBuilder<&DynProvider, D>,
-    ) -> Result<(), KeyRegistryEventError>
+    ) -> Result<(), TransactionSubmitterError>
     where
         D: CallDecoder + Unpin,
     {
@@ -112,49 +251,110 @@ impl TransactionHandler {
         Ok(())
     }
 
+    /// Sends the transaction and waits for its receipt.
+    ///
+    /// If the transaction is not mined within `max_wait_time_watch_transaction`, it is replaced by a transaction with the same nonce and escalated fees. Returns the receipt of whichever of the sent transactions is mined.
     async fn send_transaction<D>(
         &self,
         transaction: CallBuilder<&DynProvider, D>,
-    ) -> Result<TransactionReceipt, KeyRegistryEventError>
+    ) -> Result<TransactionReceipt, TransactionSubmitterError>
     where
-        D: CallDecoder + Unpin,
+        D: CallDecoder + Unpin + Clone,
     {
-        tracing::trace!("sending transaction");
-        let pending_transaction = transaction
-            .gas(self.max_gas_per_transaction)
-            .send()
-            .await?
-            .with_required_confirmations(self.confirmations_for_transaction)
-            .with_timeout(Some(self.max_wait_time_watch_transaction));
-        let tx_hash = pending_transaction.tx_hash().to_owned();
-        let get_receipt_result = pending_transaction.get_receipt().await;
-        match get_receipt_result {
-            Ok(receipt) => Ok(receipt),
-            Err(
-                err @ (PendingTransactionError::TransportError(RpcError::NullResp)
-                | PendingTransactionError::TxWatcher(WatchTxError::Timeout)),
-            ) => {
-                tracing::warn!(%err, "initial get_receipt failed - starting backoff");
-                let receipt = (|| async {
-                    self.rpc_provider
-                        .get_transaction_receipt(tx_hash)
-                        .await?
-                        .ok_or(TransportError::NullResp)
-                })
-                .retry(self.backoff_strategy())
-                .sleep(tokio::time::sleep)
-                .when(|e| matches!(e, TransportError::NullResp))
-                .notify(|_e, duration| {
-                    tracing::warn!(
-                        "Retrying eth_getTransactionReceipt in {duration:?} due to NullResp"
-                    );
-                })
-                .await?;
-                tracing::info!("successfully fetched receipt after initial fail");
-                Ok(receipt)
+        // the nonce is only taken once the previous transaction of the wallet is mined
+        let _send_guard = self.send_lock.lock().await;
+        let nonce = self
+            .rpc_provider
+            .get_transaction_count(self.wallet_address)
+            .pending()
+            .await?;
+        let mut fees = self
+            .fee_escalation
+            .initial_fees(self.rpc_provider.estimate_eip1559_fees().await?);
+        let mut sent = Vec::new();
+        loop {
+            tracing::trace!(
+                "sending transaction with nonce {nonce} and max fee per gas {} wei",
+                fees.max_fee_per_gas
+            );
+            let pending_transaction = match transaction
+                .clone()
+                .gas(self.max_gas_per_transaction)
+                .nonce(nonce)
+                .max_fee_per_gas(fees.max_fee_per_gas)
+                .max_priority_fee_per_gas(fees.max_priority_fee_per_gas)
+                .send()
+                .await
+            {
+                Ok(pending_transaction) => pending_transaction,
+                Err(err) if !sent.is_empty() => {
+                    // e.g. the replaced transaction was mined in the meantime
+                    tracing::warn!(%err, "cannot replace stuck transaction");
+                    break;
+                }
+                Err(err) => return Err(err.into()),
+            };
+            sent.push(*pending_transaction.tx_hash());
+            match pending_transaction
+                .with_required_confirmations(self.confirmations_for_transaction)
+                .with_timeout(Some(self.max_wait_time_watch_transaction))
+                .get_receipt()
+                .await
+            {
+                Ok(receipt) => return Ok(receipt),
+                Err(err @ PendingTransactionError::TransportError(RpcError::NullResp)) => {
+                    tracing::warn!(%err, "initial get_receipt failed - starting backoff");
+                    break;
+                }
+                Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout)) => {}
+                Err(err) => return Err(err.into()),
+            }
+            // the transaction might be mined without enough confirmations, or an earlier one was mined
+            if let Some(receipt) = self.find_receipt(&sent).await? {
+                return Ok(receipt);
+            }
+            metrics::wallet::inc_stuck_transactions();
+            let Some(bumped) = (sent.len() <= self.fee_escalation.max_bumps)
+                .then(|| self.fee_escalation.bump(fees))
+                .flatten()
+            else {
+                tracing::warn!("transaction with nonce {nonce} is stuck - starting backoff");
+                break;
+            };
+            tracing::warn!(
+                "transaction with nonce {nonce} is stuck - replacing it with max fee per gas {} wei",
+                bumped.max_fee_per_gas
+            );
+            metrics::wallet::inc_fee_bumps();
+            fees = bumped;
+        }
+        let receipt = (|| async {
+            self.find_receipt(&sent)
+                .await?
+                .ok_or(TransportError::NullResp)
+        })
+        .retry(self.receipt_retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(|e| matches!(e, TransportError::NullResp))
+        .notify(|_e, duration| {
+            tracing::warn!("Retrying eth_getTransactionReceipt in {duration:?} due to NullResp");
+        })
+        .await?;
+        tracing::info!("successfully fetched receipt after initial fail");
+        Ok(receipt)
+    }
+
+    /// Returns the receipt of the first of the `sent` transactions that was mined.
+    async fn find_receipt(
+        &self,
+        sent: &[TxHash],
+    ) -> Result<Option<TransactionReceipt>, TransportError> {
+        for tx_hash in sent {
+            if let Some(receipt) = self.rpc_provider.get_transaction_receipt(*tx_hash).await? {
+                return Ok(Some(receipt));
             }
-            Err(err) => Err(KeyRegistryEventError::from(err)),
         }
+        Ok(None)
     }
 
     async fn record_metrics(&self, receipt: &TransactionReceipt) {
@@ -197,7 +397,7 @@ impl TransactionHandler {
     async fn submit<D>(
         &self,
         transaction: CallBuilder<&DynProvider, D>,
-    ) -> Result<TxHash, KeyRegistryEventError>
+    ) -> Result<TxHash, TransactionSubmitterError>
     where
         D: CallDecoder + Unpin + Clone,
     {
@@ -208,19 +408,22 @@ impl TransactionHandler {
         receipt.ensure_success()?;
         Ok(receipt.transaction_hash)
     }
+}
 
+#[async_trait]
+impl TransactionSubmitter for TransactionHandler {
     /// Submits a round-1 key-gen contribution to `OprfKeyRegistry::addRound1KeyGenContribution`.
     ///
     /// Returns the `TxHash` of the confirmed transaction.
     ///
     /// # Errors
     ///
-    /// Returns [`KeyRegistryEventError`] on revert, RPC failure, or receipt timeout.
-    pub(crate) async fn add_round1_keygen_contribution(
+    /// Returns [`TransactionSubmitterError`] on revert, RPC failure, or receipt timeout.
+    async fn add_round1_keygen_contribution(
         &self,
         oprf_key_id: OprfKeyId,
         contribution: Round1Contribution,
-    ) -> Result<TxHash, KeyRegistryEventError> {
+    ) -> Result<TxHash, TransactionSubmitterError> {
         let transaction = self
             .contract
             .addRound1KeyGenContribution(oprf_key_id.into_inner(), contribution);
@@ -233,12 +436,12 @@ impl TransactionHandler {
     ///
     /// # Errors
     ///
-    /// Returns [`KeyRegistryEventError`] on revert, RPC failure, or receipt timeout.
-    pub(crate) async fn add_round1_reshare_contribution(
+    /// Returns [`TransactionSubmitterError`] on revert, RPC failure, or receipt timeout.
+    async fn add_round1_reshare_contribution(
         &self,
         oprf_key_id: OprfKeyId,
         contribution: Round1Contribution,
-    ) -> Result<TxHash, KeyRegistryEventError> {
+    ) -> Result<TxHash, TransactionSubmitterError> {
         let transaction = self
             .contract
             .addRound1ReshareContribution(oprf_key_id.into_inner(), contribution);
@@ -251,12 +454,12 @@ impl TransactionHandler {
     ///
     /// # Errors
     ///
-    /// Returns [`KeyRegistryEventError`] on revert, RPC failure, or receipt timeout.
-    pub(crate) async fn add_round2_contribution(
+    /// Returns [`TransactionSubmitterError`] on revert, RPC failure, or receipt timeout.
+    async fn add_round2_contribution(
         &self,
         oprf_key_id: OprfKeyId,
         contribution: Round2Contribution,
-    ) -> Result<TxHash, KeyRegistryEventError> {
+    ) -> Result<TxHash, TransactionSubmitterError> {
         let transaction = self
             .contract
             .addRound2Contribution(oprf_key_id.into_inner(), contribution);
@@ -269,14 +472,17 @@ impl TransactionHandler {
     ///
     /// # Errors
     ///
-    /// Returns [`KeyRegistryEventError`] on revert, RPC failure, or receipt timeout.
-    pub(crate) async fn add_round3_contribution(
+    /// Returns [`TransactionSubmitterError`] on revert, RPC failure, or receipt timeout.
+    async fn add_round3_contribution(
         &self,
         oprf_key_id: OprfKeyId,
-    ) -> Result<TxHash, KeyRegistryEventError> {
+    ) -> Result<TxHash, TransactionSubmitterError> {
         let transaction = self
             .contract
             .addRound3Contribution(oprf_key_id.into_inner());
         self.submit(transaction).await
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,33 @@
+use alloy::eips::eip1559::Eip1559Estimation;
+
+use super::FeeEscalation;
+
+const ESCALATION: FeeEscalation = FeeEscalation {
+    max_bumps: 3,
+    bump_percent: 20,
+    max_fee_per_gas_cap: 1_000,
+};
+
+fn fees(max_fee_per_gas: u128, max_priority_fee_per_gas: u128) -> Eip1559Estimation {
+    Eip1559Estimation {
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+    }
+}
+
+#[test]
+fn initial_fees_are_capped() {
+    assert_eq!(ESCALATION.initial_fees(fees(500, 100)), fees(500, 100));
+    assert_eq!(
+        ESCALATION.initial_fees(fees(2_000, 1_500)),
+        fees(1_000, 1_000)
+    );
+}
+
+#[test]
+fn bump_escalates_fees_up_to_cap() {
+    assert_eq!(ESCALATION.bump(fees(500, 100)), Some(fees(600, 120)));
+    assert_eq!(ESCALATION.bump(fees(101, 11)), Some(fees(122, 14)));
+    assert_eq!(ESCALATION.bump(fees(900, 900)), Some(fees(1_000, 1_000)));
+    assert_eq!(ESCALATION.bump(fees(1_000, 100)), None);
+}
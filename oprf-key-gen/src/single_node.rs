@@ -0,0 +1,91 @@
+//! Single-node (non-threshold) mode.
+//!
+//! Some deployments start with a single trusted evaluator before they move to a threshold committee. In single-node mode there is no `OprfKeyRegistry` and no distributed key generation: the key is sampled locally and stored in the [`SecretManager`](crate::secret_manager::SecretManager) as if it was the result of a key generation with threshold 1 and a single party.
+//!
+//! A node reading from that secret manager serves the key with the regular API, and clients use `single_node_oprf` of the client crate (or `distributed_oprf` with one service and threshold 1). With a single party the share is the secret itself and its Lagrange coefficient is 1, so upgrading to a threshold committee later only changes the list of nodes and the threshold on the client, not the API.
+//!
+//! The `taceo-oprf-key-gen-single-node` binary wraps [`init_single_node`] and [`generate_single_node_key`] for deployments without custom tooling.
+
+use std::num::NonZeroU16;
+
+use alloy::primitives::Address;
+use ark_ec::{AffineRepr as _, CurveGroup as _};
+use ark_ff::UniformRand as _;
+use eyre::Context as _;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    crypto::{OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use rand::{CryptoRng, Rng};
+
+use crate::{secret_manager::SecretManagerService, services::secret_gen::KeyGenIntermediateValues};
+
+/// Stores the [`NodeInformation`] of a single-node deployment (party 0, threshold 1) in the secret manager.
+///
+/// # Errors
+/// Returns an error if the secret manager cannot store the node information.
+pub async fn init_single_node(
+    secret_manager: &SecretManagerService,
+    address: Address,
+) -> eyre::Result<()> {
+    secret_manager
+        .store_node_information(NodeInformation::new(
+            PartyId(0),
+            address.to_string(),
+            NonZeroU16::MIN,
+        ))
+        .await
+        .context("while storing node information in secret manager")
+}
+
+/// Samples a key for the [`OprfKeyId`] and stores it as confirmed share of a single-node deployment. Returns the [`OprfPublicKey`] of the key.
+///
+/// The key is stored for the initial [`ShareEpoch`]. Idempotent: if a key was generated for the [`OprfKeyId`] already, it is kept and its public key is returned.
+///
+/// # Errors
+/// Returns an error if the secret manager cannot load or store the share (e.g., because the key was deleted).
+pub async fn generate_single_node_key(
+    secret_manager: &SecretManagerService,
+    oprf_key_id: OprfKeyId,
+    rng: &mut (impl CryptoRng + Rng),
+) -> eyre::Result<OprfPublicKey> {
+    let epoch = ShareEpoch::default();
+    if let Some(share) = secret_manager
+        .get_share_by_epoch(oprf_key_id, epoch)
+        .await
+        .context("while loading existing share")?
+    {
+        tracing::info!("key {oprf_key_id} exists already");
+        return Ok(public_key(share));
+    }
+    let share = DLogShareShamir::from(ark_babyjubjub::Fr::rand(rng));
+    let oprf_public_key = public_key(share.clone());
+    // the secret managers keep pending shares next to the key-gen intermediates, a single node has no other use for them
+    secret_manager
+        .try_store_keygen_intermediates(
+            oprf_key_id,
+            epoch,
+            NonZeroU16::MIN,
+            KeyGenIntermediateValues::new(0, rng),
+        )
+        .await
+        .context("while storing intermediates")?;
+    secret_manager
+        .store_pending_dlog_share(oprf_key_id, epoch, share)
+        .await
+        .context("while storing share")?;
+    secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch, oprf_public_key)
+        .await
+        .context("while confirming share")?;
+    tracing::info!("generated key {oprf_key_id}: {oprf_public_key}");
+    Ok(oprf_public_key)
+}
+
+/// With a single party, the share is the secret, so the public key is `G·share`.
+fn public_key(share: DLogShareShamir) -> OprfPublicKey {
+    let secret = ark_babyjubjub::Fr::from(share);
+    OprfPublicKey::new((ark_babyjubjub::EdwardsAffine::generator() * secret).into_affine())
+}
@@ -0,0 +1,861 @@
+//! SQLite backend for the OPRF key-gen service.
+//!
+//! This module provides [`SqliteDb`], the SQLite counterpart of [`crate::postgres::PostgresDb`] for
+//! small self-hosted deployments that do not run a Postgres server. Like the Postgres backend, it
+//! implements both [`SecretManager`] and [`ChainCursorStorage`] on one shared pool.
+//!
+//! The schema mirrors the Postgres schema and is managed by the embedded migrations in
+//! `./sqlite_migrations` (same migration versions as `./migrations`), applied automatically during
+//! [`SqliteDb::init`]. The database is opened in WAL mode, so an OPRF node can read the shares
+//! from the same file while the key-gen service writes to it.
+//!
+//! If [`SqliteConfig::encryption_key`] is set, the key is passed to SQLCipher with
+//! `PRAGMA key`. This requires linking against SQLCipher instead of plain SQLite (e.g. by enabling
+//! the `bundled-sqlcipher` feature of `libsqlite3-sys`); [`SqliteDb::init`] refuses to start with
+//! a key if the linked library is not SQLCipher.
+
+use std::{
+    num::{NonZeroU16, NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
+
+use ark_serialize::CanonicalDeserialize;
+use async_trait::async_trait;
+use backon::Retryable;
+use eyre::Context;
+use nodes_common::web3::event_stream::ChainCursor;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch, crypto::OprfPublicKey, retry::RetryPolicy, service::NodeInformation,
+};
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::Deserialize;
+use sqlx::{
+    Row as _, SqliteConnection, SqliteExecutor, SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+};
+use tracing::instrument;
+
+use crate::{
+    metrics,
+    postgres::to_db_ark_serialize_uncompressed,
+    secret_manager::{self, KeyGenIntermediateValues, SecretManager, SecretManagerError},
+    services::event_cursor_store::ChainCursorStorage,
+};
+
+type Result<T> = std::result::Result<T, SqliteDbError>;
+
+/// The configuration for a SQLite database file.
+#[derive(Clone, Debug, Deserialize)]
+#[non_exhaustive]
+pub struct SqliteConfig {
+    /// Path to the database file. The key-gen service creates it if it does not exist.
+    pub path: PathBuf,
+    /// Optional SQLCipher key. Treat this as a secret.
+    ///
+    /// Requires linking against SQLCipher, see the [module docs](self).
+    #[serde(default)]
+    pub encryption_key: Option<SecretString>,
+    /// Maximum number of connections in the connection pool.
+    #[serde(default = "SqliteConfig::default_max_connections")]
+    pub max_connections: NonZeroU32,
+    /// How long a statement waits for a lock held by another connection (or process) before failing.
+    #[serde(default = "SqliteConfig::default_busy_timeout")]
+    #[serde(with = "humantime_serde")]
+    pub busy_timeout: Duration,
+    /// Maximum number of attempts for an operation that failed with a transient error.
+    #[serde(default = "SqliteConfig::default_max_retries")]
+    pub max_retries: NonZeroUsize,
+    /// Delay before the first retry, doubled (with jitter) for every further retry.
+    #[serde(default = "SqliteConfig::default_retry_delay")]
+    #[serde(with = "humantime_serde")]
+    pub retry_delay: Duration,
+}
+
+impl SqliteConfig {
+    /// Default max connections
+    fn default_max_connections() -> NonZeroU32 {
+        NonZeroU32::MIN
+    }
+
+    /// Default busy timeout (`5 s`)
+    fn default_busy_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Default max retries
+    fn default_max_retries() -> NonZeroUsize {
+        NonZeroUsize::new(3).expect("Is non-zero")
+    }
+
+    /// Default retry delay (`1 s`)
+    fn default_retry_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Creates a config for the provided database file with default values and without encryption.
+    #[must_use]
+    pub fn with_default_values(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            encryption_key: None,
+            max_connections: Self::default_max_connections(),
+            busy_timeout: Self::default_busy_timeout(),
+            max_retries: Self::default_max_retries(),
+            retry_delay: Self::default_retry_delay(),
+        }
+    }
+}
+
+/// SQLite-backed store implementing both [`SecretManager`] and [`ChainCursorStorage`] on a shared `SqlitePool`.
+#[derive(Clone, Debug)]
+pub struct SqliteDb {
+    pool: SqlitePool,
+    retry_policy: RetryPolicy,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum SqliteDbError {
+    #[error("Intermediates NOT stored for {0}/{1} - stuck")]
+    MissingIntermediates(OprfKeyId, ShareEpoch),
+    #[error("Refusing to overwrite newer share")]
+    RefusingToRollbackEpoch,
+    #[error(transparent)]
+    DbError(#[from] sqlx::Error),
+    #[error("internal error: {0:?}")]
+    Internal(#[from] eyre::Report),
+}
+
+impl SqliteDb {
+    /// Initializes a [`SqliteDb`] by opening (or creating) the database file and running all pending migrations.
+    ///
+    /// # Errors
+    /// Returns an error if opening the database fails, if an encryption key is configured but the linked SQLite library is not SQLCipher, or if running the migrations fails.
+    #[instrument(level = "info", skip_all)]
+    pub async fn init(db_config: &SqliteConfig) -> eyre::Result<Self> {
+        tracing::info!("init SqlitePool at: {}", db_config.path.display());
+        let pool = sqlite_pool(db_config, true)
+            .await
+            .context("while creating pool")?;
+        tracing::trace!("potentially running migrations..");
+        sqlx::migrate!("./sqlite_migrations")
+            .run(&pool)
+            .await
+            .context("while running migrations")?;
+
+        Ok(Self {
+            pool,
+            retry_policy: RetryPolicy::new(db_config.max_retries.get(), db_config.retry_delay),
+        })
+    }
+
+    async fn with_retry<F, Fut, T>(&self, op_name: &str, f: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        f.retry(self.retry_policy.backoff())
+            .sleep(tokio::time::sleep)
+            .when(is_retryable_error)
+            .notify(|err, duration| {
+                tracing::warn!(%err, "Retrying {op_name} in db after {duration:?}");
+            })
+            .await
+    }
+}
+
+/// Opens a pool for the configured database file in WAL mode.
+///
+/// If an encryption key is configured, checks that the linked library is SQLCipher, as plain SQLite silently ignores `PRAGMA key`.
+pub(crate) async fn sqlite_pool(config: &SqliteConfig, create: bool) -> eyre::Result<SqlitePool> {
+    let mut options = SqliteConnectOptions::new()
+        .filename(&config.path)
+        .create_if_missing(create)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(config.busy_timeout);
+    if let Some(key) = &config.encryption_key {
+        // sqlx sends the `key` pragma before any other statement, as required by SQLCipher
+        options = options.pragma(
+            "key",
+            format!("'{}'", key.expose_secret().replace('\'', "''")),
+        );
+    }
+    let pool = SqlitePoolOptions::new()
+        .max_connections(config.max_connections.get())
+        .connect_with(options)
+        .await?;
+    if config.encryption_key.is_some() {
+        let cipher_version: Option<String> = sqlx::query_scalar("PRAGMA cipher_version")
+            .fetch_optional(&pool)
+            .await?;
+        let cipher_version = cipher_version.ok_or_else(|| {
+            eyre::eyre!("encryption_key is set, but the linked SQLite library is not SQLCipher")
+        })?;
+        tracing::info!("using SQLCipher {cipher_version}");
+    }
+    Ok(pool)
+}
+
+#[async_trait]
+impl ChainCursorStorage for SqliteDb {
+    /// Loads the `ChainEventCursor` for backfill.
+    #[instrument(level = "info", skip_all)]
+    #[allow(
+        clippy::cast_sign_loss,
+        reason = "SQLite integers are signed. We deserialize it then to u64 which is ok"
+    )]
+    async fn load_chain_cursor(&self) -> eyre::Result<ChainCursor> {
+        tracing::trace!("loading chain event cursor...");
+        let get_chain_cursor = || async {
+            let row = sqlx::query(
+                "
+                    SELECT block, idx
+                    FROM chain_cursor
+                ",
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            let block = row.get::<i64, _>(0) as u64;
+            let index = row.get::<i64, _>(1) as u64;
+            Ok(ChainCursor::new(block, index))
+        };
+        Ok(self
+            .with_retry("load-chain-cursor", get_chain_cursor)
+            .await?)
+    }
+
+    #[instrument(level = "info", skip_all, fields(chain_cursor=%chain_cursor))]
+    #[allow(
+        clippy::cast_possible_wrap,
+        reason = "SQLite integers are signed, so we store the u64 as i64."
+    )]
+    async fn store_chain_cursor(&self, chain_cursor: ChainCursor) -> eyre::Result<()> {
+        tracing::trace!("trying to store chain cursor...");
+        let store_chain_cursor = || async {
+            Ok(sqlx::query(
+                "
+                    UPDATE chain_cursor
+                    SET block = $1, idx = $2
+                    WHERE id = TRUE
+                      AND ($1 > block OR ($1 = block AND $2 > idx));
+                ",
+            )
+            .bind(chain_cursor.block() as i64)
+            .bind(chain_cursor.index() as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected())
+        };
+        let rows_affected = self
+            .with_retry("store-chain-cursor", store_chain_cursor)
+            .await?;
+        if rows_affected == 0 {
+            tracing::info!("did not update chain-event cursor - refusing to rollback");
+        } else {
+            metrics::chain_events::record_current_block(chain_cursor);
+        }
+        Ok(())
+    }
+}
+
+impl From<SqliteDbError> for SecretManagerError {
+    fn from(value: SqliteDbError) -> Self {
+        match value {
+            SqliteDbError::MissingIntermediates(oprf_key_id, share_epoch) => {
+                Self::MissingIntermediates(oprf_key_id, share_epoch)
+            }
+            SqliteDbError::RefusingToRollbackEpoch => Self::RefusingToRollbackEpoch,
+            SqliteDbError::DbError(error) => {
+                if let Some(error) = error.as_database_error()
+                    && error.is_check_violation()
+                {
+                    // we tried to store on deleted share
+                    Self::StoreOnDeletedShare
+                } else {
+                    Self::Internal(eyre::Report::from(error))
+                }
+            }
+            SqliteDbError::Internal(report) => Self::Internal(report),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretManager for SqliteDb {
+    #[instrument(level = "info", skip(self))]
+    async fn store_node_information(
+        &self,
+        node_information: NodeInformation,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("storing node information...");
+        let store_address = || async {
+            sqlx::query(
+                "
+                INSERT INTO node_information (id, eth_address, party_id, threshold)
+                VALUES (TRUE, $1, $2, $3)
+                ON CONFLICT (id)
+                DO UPDATE SET
+                    eth_address = excluded.eth_address,
+                    party_id = excluded.party_id,
+                    threshold = excluded.threshold
+            ",
+            )
+            .bind(node_information.address())
+            .bind(i32::from(node_information.party_id().into_inner()))
+            .bind(i32::from(node_information.threshold().get()))
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        };
+        self.with_retry("store-node-information", store_address)
+            .await?;
+        tracing::debug!("successfully stored node-information");
+        Ok(())
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn get_share_by_epoch(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> secret_manager::Result<Option<DLogShareShamir>> {
+        tracing::trace!("loading share...");
+        let get_share = || Self::get_share_by_epoch_inner(oprf_key_id, epoch, &self.pool);
+        Ok(self.with_retry("get-share-by-epoch", get_share).await?)
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn delete_oprf_key_material(&self, oprf_key_id: OprfKeyId) -> secret_manager::Result<()> {
+        tracing::trace!("trying to delete key-material..");
+
+        let delete_transaction = || async {
+            let mut tx = self.pool.begin().await?;
+            // Soft-delete finalized shares for this key.
+            let deleted_shares = Self::soft_delete_shares_inner(oprf_key_id, &mut *tx).await?;
+            // Remove any remaining in-progress state for this key.
+            let deleted_intermediates =
+                Self::delete_intermediates_inner(oprf_key_id, &mut *tx).await?;
+            tx.commit().await?;
+            tracing::trace!(
+                "deleted {deleted_shares} shares +  {deleted_intermediates} intermediates from sqlite"
+            );
+            Ok(())
+        };
+
+        Ok(self
+            .with_retry("delete-oprf-key-material", delete_transaction)
+            .await?)
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn list_oprf_key_ids(&self) -> secret_manager::Result<Vec<OprfKeyId>> {
+        tracing::trace!("listing key ids...");
+        let list_key_ids = || async {
+            let ids: Vec<Vec<u8>> =
+                sqlx::query_scalar("SELECT id FROM shares WHERE deleted = FALSE")
+                    .fetch_all(&self.pool)
+                    .await?;
+            Ok(ids.iter().map(|id| OprfKeyId::from_le_slice(id)).collect())
+        };
+        Ok(self.with_retry("list-oprf-key-ids", list_key_ids).await?)
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn quarantine_oprf_key_material(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("trying to quarantine key-material..");
+
+        let quarantine_transaction = || async {
+            let mut tx = self.pool.begin().await?;
+            let quarantined_shares = Self::quarantine_share_inner(oprf_key_id, &mut tx).await?;
+            let deleted_intermediates =
+                Self::delete_intermediates_inner(oprf_key_id, &mut *tx).await?;
+            tx.commit().await?;
+            tracing::trace!(
+                "quarantined {quarantined_shares} shares + deleted {deleted_intermediates} intermediates in sqlite"
+            );
+            Ok(())
+        };
+
+        Ok(self
+            .with_retry("quarantine-oprf-key-material", quarantine_transaction)
+            .await?)
+    }
+
+    #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id))]
+    async fn try_store_keygen_intermediates(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+        threshold: NonZeroU16,
+        intermediate: KeyGenIntermediateValues,
+    ) -> secret_manager::Result<KeyGenIntermediateValues> {
+        tracing::trace!("trying to store intermediates...");
+        let store_intermediates = || async {
+            sqlx::query_scalar(
+                "
+                INSERT INTO in_progress_keygens (id, pending_epoch, intermediates, threshold)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (id, pending_epoch) DO UPDATE
+                SET intermediates = in_progress_keygens.intermediates
+                RETURNING intermediates;
+            ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(i64::from(pending_epoch))
+            .bind(to_db_ark_serialize_uncompressed(&intermediate).as_slice())
+            .bind(i32::from(threshold.get()))
+            .fetch_one(&self.pool)
+            .await
+            .map(from_db_ark_serialize_uncompressed)?
+        };
+
+        Ok(self
+            .with_retry("store-keygen-intermediates", store_intermediates)
+            .await?)
+    }
+
+    #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id))]
+    async fn fetch_keygen_intermediates(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+    ) -> secret_manager::Result<KeyGenIntermediateValues> {
+        tracing::trace!("trying to fetch intermediates...");
+
+        let fetch_keygen = || async {
+            sqlx::query_scalar(
+                "
+                SELECT intermediates
+                FROM in_progress_keygens
+                WHERE id = $1
+                  AND pending_epoch = $2;
+            ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(i64::from(pending_epoch))
+            .fetch_optional(&self.pool)
+            .await?
+            .map(from_db_ark_serialize_uncompressed)
+            .transpose()
+        };
+
+        Ok(self
+            .with_retry("fetch-keygen-intermediates", fetch_keygen)
+            .await?
+            .ok_or_else(|| SqliteDbError::MissingIntermediates(oprf_key_id, pending_epoch))?)
+    }
+
+    #[instrument(level = "info", skip(self))]
+    async fn abort_keygen(&self, oprf_key_id: OprfKeyId) -> secret_manager::Result<()> {
+        tracing::trace!("trying to abort key-gen...");
+
+        let abort_keygen = || Self::delete_intermediates_inner(oprf_key_id, &self.pool);
+        let rows_deleted = self.with_retry("abort-keygen", abort_keygen).await?;
+
+        tracing::debug!("aborted {rows_deleted} key-gens from sqlite");
+        Ok(())
+    }
+
+    #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id))]
+    async fn store_pending_dlog_share(
+        &self,
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+        share: DLogShareShamir,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("store pending dlog-share..");
+        let store_pending = || async {
+            Ok(sqlx::query(
+                "
+                    UPDATE in_progress_keygens
+                    SET pending_share = $3
+                    WHERE id = $1
+                      AND pending_epoch = $2;
+                ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(i64::from(pending_epoch))
+            .bind(to_db_ark_serialize_uncompressed(&share).as_slice())
+            .execute(&self.pool)
+            .await?
+            .rows_affected())
+        };
+        let rows_affected = self
+            .with_retry("store-pending-dlog-share", store_pending)
+            .await?;
+
+        if rows_affected == 1 {
+            tracing::debug!("successfully stored pending dlog share");
+            Ok(())
+        } else {
+            tracing::warn!("cannot store pending share because no matching intermediates exist");
+            Err(SecretManagerError::MissingIntermediates(
+                oprf_key_id,
+                pending_epoch,
+            ))
+        }
+    }
+
+    #[instrument(level = "info", skip_all, fields(oprf_key_id=%oprf_key_id, epoch=%epoch))]
+    async fn confirm_dlog_share(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+        public_key: OprfPublicKey,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("storing share...");
+
+        let confirm_dlog_share = || async {
+            // SQLite transactions are always serializable. On retry, we check whether
+            // another transaction already completed this work via get_share_by_epoch_inner
+            // and short-circuit if so.
+            let mut tx = self.pool.begin().await?;
+            Self::confirm_dlog_share_inner(oprf_key_id, epoch, &public_key, &mut tx).await?;
+            tx.commit().await?;
+            Ok(())
+        };
+        Ok(self
+            .with_retry("confirm-dlog-share", confirm_dlog_share)
+            .await?)
+    }
+
+    #[instrument(level = "info", skip_all, fields(shares=shares.len()))]
+    async fn confirm_dlog_shares(
+        &self,
+        shares: Vec<(OprfKeyId, ShareEpoch, OprfPublicKey)>,
+    ) -> secret_manager::Result<()> {
+        tracing::trace!("storing shares...");
+
+        let shares = secret_manager::latest_epoch_per_key(shares);
+        let confirm_dlog_shares = || async {
+            // All shares are confirmed or none.
+            let mut tx = self.pool.begin().await?;
+            Self::confirm_dlog_shares_inner(&shares, &mut tx).await?;
+            tx.commit().await?;
+            Ok(())
+        };
+        Ok(self
+            .with_retry("confirm-dlog-shares", confirm_dlog_shares)
+            .await?)
+    }
+}
+
+impl SqliteDb {
+    /// Confirms the pending share of `oprf_key_id` for `epoch` on `conn`, which must be a transaction. The caller commits.
+    async fn confirm_dlog_share_inner(
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+        public_key: &OprfPublicKey,
+        conn: &mut SqliteConnection,
+    ) -> Result<()> {
+        // check if we already stored this share - maybe we had to redo this operation so that it is idempotent
+        if Self::get_share_by_epoch_inner(oprf_key_id, epoch, &mut *conn)
+            .await?
+            .is_some()
+        {
+            tracing::warn!("already have this share stored - delete intermediates");
+            Self::delete_intermediates_inner(oprf_key_id, &mut *conn).await?;
+            return Ok(());
+        }
+        let (pending_share, threshold) =
+            Self::fetch_pending_share_inner(oprf_key_id, epoch, &mut *conn)
+                .await?
+                .ok_or_else(|| SqliteDbError::MissingIntermediates(oprf_key_id, epoch))?;
+
+        let rows_affected = Self::store_confirmed_dlog_share_inner(
+            oprf_key_id,
+            epoch,
+            public_key,
+            &pending_share,
+            threshold,
+            &mut *conn,
+        )
+        .await?;
+        if rows_affected != 1 {
+            return Err(SqliteDbError::RefusingToRollbackEpoch);
+        }
+        Self::delete_intermediates_inner(oprf_key_id, &mut *conn).await?;
+        Ok(())
+    }
+
+    /// Confirms the pending shares of a batch with one statement per step of [`Self::confirm_dlog_share_inner`], independent of the size of the batch. Every key must occur at most once. `conn` must be a transaction, the caller commits.
+    ///
+    /// SQLite has no arrays, so the batch is bound as JSON array of `{id, epoch, public_key}` objects with hex-encoded bytes.
+    async fn confirm_dlog_shares_inner(
+        shares: &[(OprfKeyId, ShareEpoch, OprfPublicKey)],
+        conn: &mut SqliteConnection,
+    ) -> Result<()> {
+        let batch = |shares: &[&(OprfKeyId, ShareEpoch, OprfPublicKey)]| {
+            serde_json::Value::Array(
+                shares
+                    .iter()
+                    .map(|(oprf_key_id, epoch, public_key)| {
+                        serde_json::json!({
+                            "id": alloy::hex::encode(oprf_key_id.to_le_bytes()),
+                            "epoch": epoch.into_inner(),
+                            "public_key": alloy::hex::encode(
+                                to_db_ark_serialize_uncompressed(public_key).as_slice()
+                            ),
+                        })
+                    })
+                    .collect(),
+            )
+            .to_string()
+        };
+        let all = batch(&shares.iter().collect::<Vec<_>>());
+        // for every share: is it already stored (we may redo this operation) and is it pending?
+        let states = sqlx::query_as::<_, (bool, bool)>(
+            "
+                SELECT
+                    EXISTS (
+                        SELECT 1
+                        FROM shares
+                        WHERE id = unhex(batch.value ->> '$.id')
+                          AND epoch = batch.value ->> '$.epoch'
+                          AND deleted = FALSE
+                    ),
+                    EXISTS (
+                        SELECT 1
+                        FROM in_progress_keygens
+                        WHERE id = unhex(batch.value ->> '$.id')
+                          AND pending_epoch = batch.value ->> '$.epoch'
+                          AND pending_share IS NOT NULL
+                    )
+                FROM json_each($1) AS batch
+                ORDER BY batch.key;
+            ",
+        )
+        .bind(&all)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut pending = Vec::with_capacity(shares.len());
+        for (share, (stored, is_pending)) in shares.iter().zip(states) {
+            let (oprf_key_id, epoch, _) = share;
+            if stored {
+                tracing::warn!(%oprf_key_id, "already have this share stored - delete intermediates");
+            } else if is_pending {
+                pending.push(share);
+            } else {
+                return Err(SqliteDbError::MissingIntermediates(*oprf_key_id, *epoch));
+            }
+        }
+
+        // the SELECT needs a WHERE clause, otherwise ON CONFLICT is parsed as part of a join
+        let rows_affected = sqlx::query(
+            "
+                INSERT INTO shares (id, share, epoch, public_key, threshold)
+                SELECT keygen.id, keygen.pending_share, keygen.pending_epoch, unhex(batch.value ->> '$.public_key'), keygen.threshold
+                FROM json_each($1) AS batch, in_progress_keygens AS keygen
+                WHERE keygen.id = unhex(batch.value ->> '$.id')
+                  AND keygen.pending_epoch = batch.value ->> '$.epoch'
+                ON CONFLICT (id)
+                DO UPDATE SET
+                    share = excluded.share,
+                    epoch = excluded.epoch,
+                    public_key = excluded.public_key,
+                    threshold = excluded.threshold
+                WHERE
+                    shares.epoch < excluded.epoch;
+            ",
+        )
+        .bind(batch(&pending))
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        if rows_affected != pending.len() as u64 {
+            return Err(SqliteDbError::RefusingToRollbackEpoch);
+        }
+        sqlx::query(
+            "
+                DELETE FROM in_progress_keygens
+                WHERE id IN (SELECT unhex(value ->> '$.id') FROM json_each($1));
+            ",
+        )
+        .bind(&all)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_share_by_epoch_inner(
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+        conn: impl SqliteExecutor<'_>,
+    ) -> Result<Option<DLogShareShamir>> {
+        sqlx::query_scalar(
+            "
+                SELECT share
+                FROM shares
+                WHERE id = $1 AND epoch = $2 AND deleted = FALSE
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .bind(i64::from(epoch))
+        .fetch_optional(conn)
+        .await?
+        .map(from_db_ark_serialize_uncompressed)
+        .transpose()
+    }
+
+    async fn soft_delete_shares_inner(
+        oprf_key_id: OprfKeyId,
+        conn: impl SqliteExecutor<'_>,
+    ) -> Result<u64> {
+        Ok(sqlx::query(
+            "
+                UPDATE shares
+                SET
+                    share = NULL,
+                    deleted = TRUE
+                WHERE id = $1;
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .execute(conn)
+        .await?
+        .rows_affected())
+    }
+
+    /// Moves the confirmed share of `oprf_key_id` from `shares` to `quarantined_shares` on `conn`, which must be a transaction. The caller commits.
+    async fn quarantine_share_inner(
+        oprf_key_id: OprfKeyId,
+        conn: &mut SqliteConnection,
+    ) -> Result<u64> {
+        sqlx::query(
+            "
+                INSERT INTO quarantined_shares (id, share, epoch, public_key, threshold)
+                SELECT id, share, epoch, public_key, threshold
+                FROM shares
+                WHERE id = $1 AND deleted = FALSE
+                ON CONFLICT (id)
+                DO UPDATE SET
+                    share = excluded.share,
+                    epoch = excluded.epoch,
+                    public_key = excluded.public_key,
+                    threshold = excluded.threshold,
+                    quarantined_at = CURRENT_TIMESTAMP;
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .execute(&mut *conn)
+        .await?;
+        Ok(sqlx::query(
+            "
+                DELETE FROM shares
+                WHERE id = $1 AND deleted = FALSE;
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .execute(&mut *conn)
+        .await?
+        .rows_affected())
+    }
+
+    async fn fetch_pending_share_inner(
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+        conn: impl SqliteExecutor<'_>,
+    ) -> Result<Option<(DLogShareShamir, Option<i32>)>> {
+        sqlx::query_as::<_, (Option<Vec<u8>>, Option<i32>)>(
+            "
+                SELECT pending_share, threshold
+                FROM in_progress_keygens
+                WHERE id = $1
+                  AND pending_epoch = $2;
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .bind(i64::from(pending_epoch))
+        .fetch_optional(conn)
+        .await?
+        .and_then(|(share, threshold)| share.map(|share| (share, threshold)))
+        .map(|(share, threshold)| Ok((from_db_ark_serialize_uncompressed(share)?, threshold)))
+        .transpose()
+    }
+
+    async fn store_confirmed_dlog_share_inner(
+        oprf_key_id: OprfKeyId,
+        pending_epoch: ShareEpoch,
+        public_key: &OprfPublicKey,
+        share: &DLogShareShamir,
+        threshold: Option<i32>,
+        conn: impl SqliteExecutor<'_>,
+    ) -> Result<u64> {
+        Ok(sqlx::query(
+            "
+                INSERT INTO shares (id, share, epoch, public_key, threshold)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (id)
+                DO UPDATE SET
+                    share = excluded.share,
+                    epoch = excluded.epoch,
+                    public_key = excluded.public_key,
+                    threshold = excluded.threshold
+                WHERE
+                    shares.epoch < excluded.epoch;
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .bind(to_db_ark_serialize_uncompressed(share).as_slice())
+        .bind(i64::from(pending_epoch))
+        .bind(to_db_ark_serialize_uncompressed(public_key).as_slice())
+        .bind(threshold)
+        .execute(conn)
+        .await?
+        .rows_affected())
+    }
+
+    async fn delete_intermediates_inner(
+        oprf_key_id: OprfKeyId,
+        conn: impl SqliteExecutor<'_>,
+    ) -> Result<u64> {
+        Ok(sqlx::query(
+            "
+                DELETE FROM in_progress_keygens
+                WHERE id = $1;
+            ",
+        )
+        .bind(oprf_key_id.to_le_bytes())
+        .execute(conn)
+        .await?
+        .rows_affected())
+    }
+}
+
+#[inline]
+fn from_db_ark_serialize_uncompressed<T: CanonicalDeserialize>(b: Vec<u8>) -> Result<T> {
+    T::deserialize_uncompressed(zeroize::Zeroizing::from(b).as_slice())
+        .map_err(|e| SqliteDbError::from(eyre::eyre!("Cannot deserialize bytes: DB not sane: {e}")))
+}
+
+#[inline]
+fn is_retryable_error(e: &SqliteDbError) -> bool {
+    match e {
+        SqliteDbError::DbError(err) => match err {
+            // structural / driver-level errors
+            sqlx::Error::PoolTimedOut
+            | sqlx::Error::Io(_)
+            | sqlx::Error::WorkerCrashed
+            | sqlx::Error::BeginFailed => true,
+
+            // SQLITE_BUSY, SQLITE_LOCKED and their extended codes, e.g. if the busy timeout elapsed
+            sqlx::Error::Database(db_err) => {
+                matches!(
+                    db_err.code().as_deref(),
+                    Some("5" | "6" | "261" | "262" | "517")
+                )
+            }
+
+            _ => false,
+        },
+
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,415 @@
+use std::path::PathBuf;
+
+use crate::event_cursor_store::ChainCursorStorage as _;
+use crate::postgres::to_db_ark_serialize_uncompressed;
+use crate::secret_manager::{SecretManager as _, SecretManagerError};
+use crate::sqlite::{SqliteConfig, SqliteDb};
+use alloy::primitives::U160;
+use nodes_common::web3::event_stream::ChainCursor;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{OprfKeyId, ShareEpoch, crypto::OprfPublicKey};
+use secrecy::SecretString;
+
+/// A database file in the temp dir that is removed (including the WAL files) on drop.
+struct TempDbFile(PathBuf);
+
+impl TempDbFile {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("oprf-key-gen-{}.sqlite", rand::random::<u64>())))
+    }
+}
+
+impl Drop for TempDbFile {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.0.clone().into_os_string();
+            path.push(suffix);
+            // the WAL files may not exist
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+async fn sqlite_db() -> eyre::Result<(SqliteDb, TempDbFile)> {
+    let file = TempDbFile::new();
+    let db = SqliteDb::init(&SqliteConfig::with_default_values(&file.0)).await?;
+    Ok((db, file))
+}
+
+/// The threshold of the keys staged by [`stage_pending_share`].
+const STAGED_THRESHOLD: i32 = 3;
+
+/// Stages a pending share for `epoch` like a finished key-gen would.
+async fn stage_pending_share(
+    secret_manager: &SqliteDb,
+    oprf_key_id: OprfKeyId,
+    epoch: ShareEpoch,
+    share: DLogShareShamir,
+) -> eyre::Result<()> {
+    sqlx::query(
+        "
+            INSERT INTO in_progress_keygens (id, pending_epoch, intermediates, threshold)
+            VALUES ($1, $2, $3, $4)
+        ",
+    )
+    .bind(oprf_key_id.to_le_bytes())
+    .bind(i64::from(epoch))
+    // `confirm_dlog_share` only reads `pending_share`; these tests do not deserialize `intermediates`.
+    .bind(vec![0_u8])
+    .bind(STAGED_THRESHOLD)
+    .execute(&secret_manager.pool)
+    .await?;
+    secret_manager
+        .store_pending_dlog_share(oprf_key_id, epoch, share)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn init_is_idempotent() -> eyre::Result<()> {
+    let (_, file) = sqlite_db().await?;
+    // re-opening an existing database must not re-run migrations
+    SqliteDb::init(&SqliteConfig::with_default_values(&file.0)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirm_and_fetch_share() -> eyre::Result<()> {
+    let (secret_manager, _file) = sqlite_db().await?;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+
+    stage_pending_share(&secret_manager, oprf_key_id, epoch, share.clone()).await?;
+    secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch, public_key)
+        .await?;
+    // confirming twice is idempotent
+    secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch, public_key)
+        .await?;
+
+    let is_share = secret_manager
+        .get_share_by_epoch(oprf_key_id, epoch)
+        .await?
+        .expect("share is stored");
+    assert_eq!(
+        to_db_ark_serialize_uncompressed(&is_share),
+        to_db_ark_serialize_uncompressed(&share),
+        "Should load the confirmed share"
+    );
+    let threshold: Option<i32> = sqlx::query_scalar("SELECT threshold FROM shares WHERE id = $1")
+        .bind(oprf_key_id.to_le_bytes())
+        .fetch_one(&secret_manager.pool)
+        .await?;
+    assert_eq!(
+        threshold,
+        Some(STAGED_THRESHOLD),
+        "Should store the threshold of the key with the share"
+    );
+    assert!(
+        secret_manager
+            .get_share_by_epoch(oprf_key_id, epoch.next())
+            .await?
+            .is_none(),
+        "Should not have a share for the next epoch"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_keygen_intermediates_missing_returns_error() -> eyre::Result<()> {
+    let (secret_manager, _file) = sqlite_db().await?;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let epoch = ShareEpoch::new(12);
+
+    let should_err = secret_manager
+        .fetch_keygen_intermediates(oprf_key_id, epoch)
+        .await
+        .expect_err("Should be an error");
+    assert!(
+        matches!(
+            should_err,
+            SecretManagerError::MissingIntermediates(is_oprf_key, is_epoch) if is_oprf_key == oprf_key_id && is_epoch == epoch
+        ),
+        "Should be MissingIntermediates but is {should_err}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirm_deleted_share_returns_store_on_deleted_share() -> eyre::Result<()> {
+    let (secret_manager, _file) = sqlite_db().await?;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    stage_pending_share(&secret_manager, oprf_key_id, epoch, share).await?;
+    secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch, public_key)
+        .await?;
+    secret_manager.delete_oprf_key_material(oprf_key_id).await?;
+    assert!(
+        secret_manager
+            .get_share_by_epoch(oprf_key_id, epoch)
+            .await?
+            .is_none(),
+        "Should not return a deleted share"
+    );
+
+    let next_share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    stage_pending_share(&secret_manager, oprf_key_id, epoch.next(), next_share).await?;
+    let err = secret_manager
+        .confirm_dlog_share(oprf_key_id, epoch.next(), public_key)
+        .await
+        .expect_err("confirm on deleted share should fail");
+    assert!(
+        matches!(err, SecretManagerError::StoreOnDeletedShare),
+        "Should be StoreOnDeletedShare but is {err}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_insert_chain_cursor_refusing_rollback() -> eyre::Result<()> {
+    let (secret_manager, _file) = sqlite_db().await?;
+    assert!(
+        secret_manager.load_chain_cursor().await?.is_genesis(),
+        "Should be genesis cursor on empty DB"
+    );
+
+    let should_chain_cursor = ChainCursor::new(42, 0x42);
+    secret_manager
+        .store_chain_cursor(should_chain_cursor)
+        .await?;
+    secret_manager
+        .store_chain_cursor(ChainCursor::new(41, 0x42))
+        .await?;
+    secret_manager
+        .store_chain_cursor(ChainCursor::new(42, 0x41))
+        .await?;
+    assert_eq!(
+        should_chain_cursor,
+        secret_manager.load_chain_cursor().await?,
+        "Should have refused insertions of older cursor"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn encryption_key_requires_sqlcipher() -> eyre::Result<()> {
+    let file = TempDbFile::new();
+    let mut config = SqliteConfig::with_default_values(&file.0);
+    config.encryption_key = Some(SecretString::from("secret".to_owned()));
+    // the bundled SQLite is not SQLCipher, so we must refuse to store shares unencrypted
+    let err = SqliteDb::init(&config)
+        .await
+        .expect_err("plain SQLite cannot encrypt");
+    assert!(
+        format!("{err:?}").contains("not SQLCipher"),
+        "unexpected error: {err:?}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn single_node_key_is_stored_and_reused() -> eyre::Result<()> {
+    let (db, _file) = sqlite_db().await?;
+    let secret_manager: crate::secret_manager::SecretManagerService = std::sync::Arc::new(db);
+    let oprf_key_id = OprfKeyId::new(U160::from(7));
+    let mut rng = rand::thread_rng();
+
+    let public_key =
+        crate::single_node::generate_single_node_key(&secret_manager, oprf_key_id, &mut rng)
+            .await?;
+    assert!(
+        secret_manager
+            .get_share_by_epoch(oprf_key_id, ShareEpoch::default())
+            .await?
+            .is_some(),
+        "share should be confirmed"
+    );
+    let again =
+        crate::single_node::generate_single_node_key(&secret_manager, oprf_key_id, &mut rng)
+            .await?;
+    assert_eq!(public_key, again, "existing key should be kept");
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirm_dlog_shares_confirms_all_or_nothing() -> eyre::Result<()> {
+    let (secret_manager, _file) = sqlite_db().await?;
+    let epoch = ShareEpoch::default();
+    let public_key = OprfPublicKey::new(rand::random());
+    let staged = [
+        OprfKeyId::new(U160::from(42)),
+        OprfKeyId::new(U160::from(43)),
+    ];
+    let missing = OprfKeyId::new(U160::from(44));
+    for oprf_key_id in staged {
+        let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+        stage_pending_share(&secret_manager, oprf_key_id, epoch, share).await?;
+    }
+
+    // one share without intermediates rolls back the whole batch
+    let err = secret_manager
+        .confirm_dlog_shares(
+            [staged[0], missing, staged[1]]
+                .into_iter()
+                .map(|oprf_key_id| (oprf_key_id, epoch, public_key))
+                .collect(),
+        )
+        .await
+        .expect_err("confirm without pending share should fail");
+    assert!(
+        matches!(err, SecretManagerError::MissingIntermediates(id, _) if id == missing),
+        "Should be missing intermediates but is {err}"
+    );
+    assert!(
+        secret_manager
+            .get_share_by_epoch(staged[0], epoch)
+            .await?
+            .is_none(),
+        "Should confirm no share"
+    );
+
+    secret_manager
+        .confirm_dlog_shares(
+            staged
+                .into_iter()
+                .map(|oprf_key_id| (oprf_key_id, epoch, public_key))
+                .collect(),
+        )
+        .await?;
+    for oprf_key_id in staged {
+        assert!(
+            secret_manager
+                .get_share_by_epoch(oprf_key_id, epoch)
+                .await?
+                .is_some(),
+            "Should confirm {oprf_key_id}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirm_dlog_shares_keeps_latest_epoch_and_is_idempotent() -> eyre::Result<()> {
+    let (secret_manager, _file) = sqlite_db().await?;
+    let epoch = ShareEpoch::default();
+    let public_key = OprfPublicKey::new(rand::random());
+    let reshared = OprfKeyId::new(U160::from(42));
+    let other = OprfKeyId::new(U160::from(43));
+    let latest_share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    stage_pending_share(
+        &secret_manager,
+        reshared,
+        epoch,
+        DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>()),
+    )
+    .await?;
+    stage_pending_share(
+        &secret_manager,
+        reshared,
+        epoch.next(),
+        latest_share.clone(),
+    )
+    .await?;
+    stage_pending_share(
+        &secret_manager,
+        other,
+        epoch,
+        DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>()),
+    )
+    .await?;
+
+    let batch = vec![
+        (reshared, epoch, public_key),
+        (other, epoch, public_key),
+        (reshared, epoch.next(), public_key),
+    ];
+    secret_manager.confirm_dlog_shares(batch.clone()).await?;
+    assert_eq!(
+        secret_manager
+            .get_share_by_epoch(reshared, epoch.next())
+            .await?
+            .map(ark_babyjubjub::Fr::from),
+        Some(ark_babyjubjub::Fr::from(latest_share)),
+        "Should confirm the latest epoch"
+    );
+    assert!(
+        secret_manager
+            .get_share_by_epoch(other, epoch)
+            .await?
+            .is_some(),
+        "Should confirm {other}"
+    );
+
+    // replaying the batch (e.g. after a crash before the cursor was stored) is a no-op
+    secret_manager.confirm_dlog_shares(batch).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_and_quarantine_keys() -> eyre::Result<()> {
+    let (secret_manager, _file) = sqlite_db().await?;
+    let keys = [
+        OprfKeyId::new(U160::from(42)),
+        OprfKeyId::new(U160::from(43)),
+        OprfKeyId::new(U160::from(44)),
+    ];
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(1);
+    for oprf_key_id in keys {
+        let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+        stage_pending_share(&secret_manager, oprf_key_id, epoch, share).await?;
+        secret_manager
+            .confirm_dlog_share(oprf_key_id, epoch, public_key)
+            .await?;
+    }
+    secret_manager.delete_oprf_key_material(keys[2]).await?;
+
+    let mut listed = secret_manager.list_oprf_key_ids().await?;
+    listed.sort();
+    assert_eq!(
+        listed,
+        keys[..2],
+        "Should list the keys that are not deleted"
+    );
+
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    stage_pending_share(&secret_manager, keys[0], epoch.next(), share).await?;
+    secret_manager.quarantine_oprf_key_material(keys[0]).await?;
+    // quarantining twice is idempotent
+    secret_manager.quarantine_oprf_key_material(keys[0]).await?;
+
+    assert_eq!(
+        secret_manager.list_oprf_key_ids().await?,
+        [keys[1]],
+        "Should not list the quarantined key"
+    );
+    assert!(
+        secret_manager
+            .get_share_by_epoch(keys[0], epoch)
+            .await?
+            .is_none(),
+        "Should not return a quarantined share"
+    );
+    let quarantined: Option<i64> =
+        sqlx::query_scalar("SELECT epoch FROM quarantined_shares WHERE id = $1")
+            .bind(keys[0].to_le_bytes())
+            .fetch_optional(&secret_manager.pool)
+            .await?;
+    assert_eq!(quarantined, Some(1), "Should keep the quarantined share");
+    let err = secret_manager
+        .fetch_keygen_intermediates(keys[0], epoch.next())
+        .await
+        .expect_err("intermediates should be removed");
+    assert!(
+        matches!(err, SecretManagerError::MissingIntermediates(..)),
+        "Should be missing intermediates but is {err}"
+    );
+    Ok(())
+}
@@ -0,0 +1,328 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroU16,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::secret_manager::SecretManager as _;
+use crate::vault::{VaultConfig, VaultSecretManager};
+use alloy::primitives::U160;
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+};
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    crypto::{OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use parking_lot::Mutex;
+use secrecy::{ExposeSecret as _, SecretString};
+
+/// All versions of every secret in the mocked KV engine.
+type Secrets = Arc<Mutex<HashMap<String, Vec<String>>>>;
+
+#[derive(Clone, Default)]
+struct MockState {
+    secrets: Secrets,
+    logins: Arc<AtomicUsize>,
+    renewals: Arc<AtomicUsize>,
+    /// Lifetime of the issued tokens in seconds.
+    lease_duration: u64,
+}
+
+fn authorized(headers: &HeaderMap) -> bool {
+    headers
+        .get("X-Vault-Token")
+        .is_some_and(|token| token == "static-token" || token == "approle-token")
+}
+
+/// Minimal mock of the token and `AppRole` auth methods and a KV v2 engine mounted at `secret`.
+fn mock_router(state: MockState) -> Router {
+    Router::new()
+        .route(
+            "/v1/auth/token/lookup-self",
+            get(|State(state): State<MockState>, headers: HeaderMap| async move {
+                if !authorized(&headers) {
+                    return StatusCode::FORBIDDEN.into_response();
+                }
+                Json(serde_json::json!({
+                    "data": { "ttl": state.lease_duration, "renewable": true }
+                }))
+                .into_response()
+            }),
+        )
+        .route(
+            "/v1/auth/token/renew-self",
+            post(|State(state): State<MockState>| async move {
+                state.renewals.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({
+                    "auth": { "client_token": "static-token", "lease_duration": state.lease_duration }
+                }))
+            }),
+        )
+        .route(
+            "/v1/auth/approle/login",
+            post(
+                |State(state): State<MockState>, Json(body): Json<serde_json::Value>| async move {
+                    if body["role_id"] != "role" || body["secret_id"] != "secret" {
+                        return StatusCode::BAD_REQUEST.into_response();
+                    }
+                    state.logins.fetch_add(1, Ordering::SeqCst);
+                    Json(serde_json::json!({
+                        "auth": { "client_token": "approle-token", "lease_duration": state.lease_duration }
+                    }))
+                    .into_response()
+                },
+            ),
+        )
+        .route(
+            "/v1/secret/data/{secret}",
+            get(
+                |State(state): State<MockState>, headers: HeaderMap, Path(secret): Path<String>| async move {
+                    if !authorized(&headers) {
+                        return StatusCode::FORBIDDEN.into_response();
+                    }
+                    match state.secrets.lock().get(&secret).and_then(|v| v.last().cloned()) {
+                        Some(value) => Json(serde_json::json!({
+                            "data": { "data": { "value": value }, "metadata": {} }
+                        }))
+                        .into_response(),
+                        None => StatusCode::NOT_FOUND.into_response(),
+                    }
+                },
+            )
+            .post(
+                |State(state): State<MockState>,
+                 headers: HeaderMap,
+                 Path(secret): Path<String>,
+                 Json(body): Json<serde_json::Value>| async move {
+                    if !authorized(&headers) {
+                        return StatusCode::FORBIDDEN;
+                    }
+                    let value = body["data"]["value"].as_str().expect("value").to_owned();
+                    state.secrets.lock().entry(secret).or_default().push(value);
+                    StatusCode::OK
+                },
+            ),
+        )
+        .route(
+            "/v1/secret/metadata/{secret}",
+            axum::routing::delete(
+                |State(state): State<MockState>, headers: HeaderMap, Path(secret): Path<String>| async move {
+                    if !authorized(&headers) {
+                        return StatusCode::FORBIDDEN;
+                    }
+                    state.secrets.lock().remove(&secret);
+                    StatusCode::NO_CONTENT
+                },
+            ),
+        )
+        .with_state(state)
+}
+
+async fn serve(state: MockState) -> eyre::Result<String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(axum::serve(listener, mock_router(state)).into_future());
+    Ok(format!("http://{addr}"))
+}
+
+async fn vault_secret_manager(state: MockState) -> eyre::Result<VaultSecretManager> {
+    let mut config =
+        VaultConfig::with_token(serve(state).await?, SecretString::from("static-token"));
+    config.wallet_private_key_secret = Some("wallet".to_owned());
+    config.retry_delay = Duration::from_millis(10);
+    VaultSecretManager::init(config).await
+}
+
+fn read_secret(secrets: &Secrets, id: &str) -> Option<serde_json::Value> {
+    let value = secrets.lock().get(id)?.last().cloned()?;
+    Some(serde_json::from_str(&value).expect("json"))
+}
+
+/// Stages a pending share for `epoch` like a finished key-gen would.
+async fn stage_pending_share(
+    secret_manager: &VaultSecretManager,
+    secrets: &Secrets,
+    oprf_key_id: OprfKeyId,
+    epoch: ShareEpoch,
+    share: DLogShareShamir,
+) -> eyre::Result<()> {
+    // `confirm_dlog_share` only reads `pending_share`; these tests do not deserialize `intermediates`.
+    let keygens = serde_json::json!({
+        epoch.to_string(): { "intermediates": "AA==", "pending_share": null }
+    });
+    secrets.lock().insert(
+        secret_manager.keygen_secret(oprf_key_id),
+        vec![serde_json::to_string(&keygens)?],
+    );
+    secret_manager
+        .store_pending_dlog_share(oprf_key_id, epoch, share)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn store_node_information_and_load_wallet() -> eyre::Result<()> {
+    let state = MockState {
+        lease_duration: 3600,
+        ..Default::default()
+    };
+    let secret_manager = vault_secret_manager(state.clone()).await?;
+    assert_eq!(
+        state.renewals.load(Ordering::SeqCst),
+        1,
+        "renewable token is renewed on init"
+    );
+    assert!(
+        secret_manager.load_wallet_private_key().await.is_err(),
+        "missing wallet secret must be an error"
+    );
+    state
+        .secrets
+        .lock()
+        .insert("wallet".to_owned(), vec!["0x42\n".to_owned()]);
+    let wallet = secret_manager
+        .load_wallet_private_key()
+        .await?
+        .expect("wallet secret is configured");
+    assert_eq!(wallet.expose_secret(), "0x42");
+
+    let node_information = NodeInformation::new(
+        PartyId(42),
+        "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc".to_owned(),
+        NonZeroU16::new(2).expect("is non-zero"),
+    );
+    secret_manager
+        .store_node_information(node_information)
+        .await?;
+    assert_eq!(
+        read_secret(&state.secrets, "oprf-node-information"),
+        Some(serde_json::json!({
+            "eth_address": "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+            "party_id": 42,
+            "threshold": 2
+        })),
+        "node information is stored as JSON"
+    );
+    assert_eq!(
+        state.renewals.load(Ordering::SeqCst),
+        1,
+        "token is cached until it expires"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn rotated_share_keeps_previous_version() -> eyre::Result<()> {
+    let state = MockState {
+        lease_duration: 3600,
+        ..Default::default()
+    };
+    let secret_manager = vault_secret_manager(state.clone()).await?;
+    let secrets = &state.secrets;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    let share_secret = format!("oprf-share-{oprf_key_id}");
+
+    for (epoch, share) in [
+        (epoch, rand::random::<ark_babyjubjub::Fr>()),
+        (epoch.next(), rand::random::<ark_babyjubjub::Fr>()),
+    ] {
+        stage_pending_share(
+            &secret_manager,
+            secrets,
+            oprf_key_id,
+            epoch,
+            DLogShareShamir::from(share),
+        )
+        .await?;
+        secret_manager
+            .confirm_dlog_share(oprf_key_id, epoch, public_key)
+            .await?;
+        let is_share = secret_manager
+            .get_share_by_epoch(oprf_key_id, epoch)
+            .await?
+            .expect("share is stored");
+        assert_eq!(
+            ark_babyjubjub::Fr::from(is_share),
+            share,
+            "Should load the confirmed share"
+        );
+    }
+    assert_eq!(
+        secrets.lock().get(&share_secret).map(Vec::len),
+        Some(2),
+        "rotation adds a version"
+    );
+
+    secret_manager.delete_oprf_key_material(oprf_key_id).await?;
+    assert!(
+        secret_manager
+            .get_share_by_epoch(oprf_key_id, epoch.next())
+            .await?
+            .is_none(),
+        "Should not return a deleted share"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn approle_token_is_replaced_before_expiry() -> eyre::Result<()> {
+    // tokens are refreshed a minute before they expire, so short-lived tokens are replaced on every request
+    let state = MockState {
+        lease_duration: 30,
+        ..Default::default()
+    };
+    let address = serve(state.clone()).await?;
+    let config = VaultConfig::with_approle(
+        address.clone(),
+        "role".to_owned(),
+        SecretString::from("secret"),
+    );
+    let secret_manager = VaultSecretManager::init(config).await?;
+    assert_eq!(state.logins.load(Ordering::SeqCst), 1, "logged in on init");
+    secret_manager
+        .store_node_information(NodeInformation::new(
+            PartyId(1),
+            "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc".to_owned(),
+            NonZeroU16::new(2).expect("is non-zero"),
+        ))
+        .await?;
+    assert!(
+        state.logins.load(Ordering::SeqCst) > 1,
+        "expiring token is replaced"
+    );
+
+    let wrong = VaultConfig::with_approle(address, "role".to_owned(), SecretString::from("wrong"));
+    assert!(
+        VaultSecretManager::init(wrong).await.is_err(),
+        "wrong secret id must be rejected"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn requires_exactly_one_auth_method() {
+    let mut config =
+        VaultConfig::with_token("http://127.0.0.1:1".to_owned(), SecretString::from("token"));
+    config.approle_role_id = Some("role".to_owned());
+    config.approle_secret_id = Some(SecretString::from("secret"));
+    let err = VaultSecretManager::init(config)
+        .await
+        .expect_err("ambiguous credentials");
+    assert!(
+        err.to_string().contains("either a token or an AppRole"),
+        "unexpected error: {err}"
+    );
+}
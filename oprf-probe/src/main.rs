@@ -0,0 +1,141 @@
+//! Minimal health/status probe for OPRF nodes, intended for Kubernetes exec probes and sidecars.
+//!
+//! Checks `GET /health` and `GET /version`, and for every `--module` that the module serves sane
+//! limits at `GET /api{module}/params`. If `--oprf-key-id` is set, checks that the key is served at
+//! `GET /oprf_params/{id}` and `GET /oprf_pub/{id}` (optionally with at least `--min-epoch`).
+//!
+//! The probe lives in its own crate with few dependencies and uses rustls, so it can be built as a
+//! static binary.
+//!
+//! Exit codes:
+//! - `0` – all checks passed
+//! - `1` – a check failed (unreachable, unexpected status, unexpected body)
+//! - `2` – invalid arguments (reported by clap)
+//! - `3` – the probe did not finish within `--timeout`
+use std::{process::ExitCode, time::Duration};
+
+use clap::Parser;
+use eyre::Context as _;
+use oprf_types::{
+    OprfKeyId,
+    api::{ModuleParams, OprfKeyParams, OprfPublicKeyWithEpoch},
+};
+use ruint::aliases::U160;
+
+const EXIT_CHECK_FAILED: u8 = 1;
+const EXIT_TIMEOUT: u8 = 3;
+
+#[derive(Clone, Parser, Debug)]
+struct ProbeConfig {
+    /// The base URL of the OPRF node, including its root path, if any
+    #[clap(long, env = "OPRF_PROBE_NODE", default_value = "http://127.0.0.1:4321")]
+    node: String,
+
+    /// The OPRF modules to check, e.g. `/my-module`. Can be passed multiple times
+    #[clap(long = "module", env = "OPRF_PROBE_MODULES", value_delimiter = ',')]
+    modules: Vec<String>,
+
+    /// If set, checks that the node serves this OPRF key
+    #[clap(long, env = "OPRF_PROBE_OPRF_KEY_ID")]
+    oprf_key_id: Option<U160>,
+
+    /// The minimum epoch the node must serve for `oprf_key_id`
+    #[clap(long, env = "OPRF_PROBE_MIN_EPOCH", requires = "oprf_key_id")]
+    min_epoch: Option<u32>,
+
+    /// Max time for all checks combined
+    #[clap(long, env = "OPRF_PROBE_TIMEOUT", default_value = "5s", value_parser = humantime::parse_duration)]
+    timeout: Duration,
+}
+
+async fn get(client: &reqwest::Client, url: String) -> eyre::Result<reqwest::Response> {
+    client
+        .get(&url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("GET {url} failed"))
+}
+
+async fn check_module(client: &reqwest::Client, node: &str, module: &str) -> eyre::Result<()> {
+    let module = module.trim_end_matches('/');
+    let params = get(client, format!("{node}/api{module}/params"))
+        .await?
+        .json::<ModuleParams>()
+        .await
+        .with_context(|| format!("while parsing params of module {module}"))?;
+    eyre::ensure!(
+        params.max_message_size > 0 && params.max_batch_size > 0 && params.session_lifetime_ms > 0,
+        "module {module} serves unusable limits: {params:?}"
+    );
+    Ok(())
+}
+
+async fn check_key(
+    client: &reqwest::Client,
+    node: &str,
+    oprf_key_id: OprfKeyId,
+    min_epoch: Option<u32>,
+) -> eyre::Result<()> {
+    let params = get(client, format!("{node}/oprf_params/{oprf_key_id}"))
+        .await?
+        .json::<OprfKeyParams>()
+        .await
+        .context("while parsing OPRF key params")?;
+    let material = get(client, format!("{node}/oprf_pub/{oprf_key_id}"))
+        .await?
+        .json::<OprfPublicKeyWithEpoch>()
+        .await
+        .context("while parsing OPRF public key")?;
+    // the epoch may change between the two requests, the public key is kept across reshares
+    eyre::ensure!(
+        params.key == material.key,
+        "node serves different public keys for {oprf_key_id} at /oprf_params and /oprf_pub"
+    );
+    if let Some(min_epoch) = min_epoch {
+        eyre::ensure!(
+            params.epoch.into_inner() >= min_epoch,
+            "node serves epoch {} for {oprf_key_id}, expected at least {min_epoch}",
+            params.epoch
+        );
+    }
+    Ok(())
+}
+
+async fn probe(config: &ProbeConfig) -> eyre::Result<()> {
+    let client = reqwest::Client::new();
+    let node = config.node.trim_end_matches('/');
+
+    let health = get(&client, format!("{node}/health")).await?.text().await?;
+    eyre::ensure!(health == "healthy", "node reported {health:?} on /health");
+
+    let version = get(&client, format!("{node}/version"))
+        .await?
+        .text()
+        .await?;
+
+    for module in &config.modules {
+        check_module(&client, node, module).await?;
+    }
+    if let Some(oprf_key_id) = config.oprf_key_id {
+        check_key(&client, node, OprfKeyId::new(oprf_key_id), config.min_epoch).await?;
+    }
+    println!("ok: {node} ({})", version.trim());
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let config = ProbeConfig::parse();
+    match tokio::time::timeout(config.timeout, probe(&config)).await {
+        Ok(Ok(())) => ExitCode::SUCCESS,
+        Ok(Err(err)) => {
+            eprintln!("probe failed: {err:?}");
+            ExitCode::from(EXIT_CHECK_FAILED)
+        }
+        Err(_) => {
+            eprintln!("probe did not finish within {:?}", config.timeout);
+            ExitCode::from(EXIT_TIMEOUT)
+        }
+    }
+}
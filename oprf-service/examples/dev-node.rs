@@ -0,0 +1,107 @@
+//! A single development node without authentication, serving random keys from memory.
+//!
+//! Every request is accepted, the `auth` of the request is the [`OprfKeyId`] to evaluate. The keys are generated at startup and lost on shutdown, so this node is only meant for local development against the client, e.g. with the `full-client` example of `taceo-oprf-client`:
+//!
+//! ```sh
+//! TACEO_OPRF_DEV_NODE__OPRF_KEY_IDS=1,2 cargo run -p taceo-oprf-service --features test-kit --example dev-node
+//! ```
+//!
+//! To try a deployment behind a reverse proxy that mounts the node at `/oprf-node/`, set `TACEO_OPRF_DEV_NODE__ROOT_PATH=/oprf-node` and use `http://127.0.0.1:4321/oprf-node` as base URL of the node in the client.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use config::{Config, Environment};
+use eyre::Context;
+use oprf_types::OprfKeyId;
+use serde::Deserialize;
+use taceo_oprf_service::{
+    OprfServiceBuilder, StartedServices,
+    config::OprfNodeServiceConfig,
+    test_kit::{MockAuthenticator, StaticSecretManager},
+};
+
+/// The configuration of the dev node.
+///
+/// Configured via environment variables using the `TACEO_OPRF_DEV_NODE__` prefix and `__` as separator.
+#[derive(Clone, Debug, Deserialize)]
+struct DevNodeConfig {
+    /// The bind addr of the AXUM server
+    #[serde(default = "default_bind_addr")]
+    bind_addr: SocketAddr,
+
+    /// The ids of the random keys the node serves
+    #[serde(default = "default_oprf_key_ids")]
+    oprf_key_ids: Vec<usize>,
+
+    /// The path prefix of all routes, see `OprfNodeServiceConfig::root_path`
+    #[serde(default)]
+    root_path: Option<String>,
+}
+
+fn default_bind_addr() -> SocketAddr {
+    "127.0.0.1:4321".parse().expect("valid SocketAddr")
+}
+
+fn default_oprf_key_ids() -> Vec<usize> {
+    vec![1]
+}
+
+fn load_dev_node_config() -> eyre::Result<DevNodeConfig> {
+    Config::builder()
+        .add_source(
+            Environment::with_prefix("TACEO_OPRF_DEV_NODE")
+                .separator("__")
+                .list_separator(",")
+                .with_list_parse_key("oprf_key_ids")
+                .try_parsing(true),
+        )
+        .build()
+        .context("while building from config")?
+        .try_deserialize()
+        .context("while parsing config")
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let _guard = telemetry_batteries::init()?;
+    let config = load_dev_node_config()?;
+    tracing::info!("starting dev node with config: {config:#?}");
+
+    // a single node with threshold 1, the share of every key is its secret
+    let secret_manager = config.oprf_key_ids.iter().fold(
+        StaticSecretManager::single_node(),
+        |secret_manager, id| {
+            secret_manager.with_random_key(OprfKeyId::from(*id), &mut rand::thread_rng())
+        },
+    );
+
+    let mut node_config = OprfNodeServiceConfig::with_default_values(
+        taceo_oprf_service::Environment::Dev,
+        taceo_oprf_service::VersionReq::STAR,
+    );
+    node_config.root_path.clone_from(&config.root_path);
+
+    let router = OprfServiceBuilder::load(
+        node_config,
+        Arc::new(secret_manager),
+        StartedServices::default(),
+        nodes_common::version_info!(),
+    )
+    .await
+    .context("while loading oprf service")?
+    .module("/dev", MockAuthenticator::allow_all().into_service())
+    .build()
+    .context("while building oprf service")?;
+
+    let listener = tokio::net::TcpListener::bind(config.bind_addr).await?;
+    tracing::info!(
+        "serving keys {:?} at ws://{}{}/api/dev/oprf",
+        config.oprf_key_ids,
+        config.bind_addr,
+        config.root_path.as_deref().unwrap_or_default()
+    );
+    axum::serve(listener, router)
+        .with_graceful_shutdown(nodes_common::default_shutdown_signal())
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,192 @@
+//! A node of a committee that authenticates requests with JWTs and loads its shares from Postgres.
+//!
+//! - Requests carry an ES256-signed JWT as `auth`, granting access to the key in its `oprf_key_id` claim (see [`jwt_authenticator`]).
+//! - Shares and node information are loaded from Postgres, see [`PostgresSecretManager`].
+//! - Metrics are described at startup and exported by `telemetry-batteries` (configured via its environment variables, e.g. to StatsD).
+//! - Requests are delegated to the other nodes of the committee via TLS, verifying their certificates against the webpki roots.
+//!
+//! Configured via environment variables using the `TACEO_OPRF_NODE__` prefix and `__` as separator, e.g.:
+//!
+//! ```sh
+//! TACEO_OPRF_NODE__SERVICE__ENVIRONMENT=prod \
+//! TACEO_OPRF_NODE__SERVICE__VERSION_REQ=">=1.0.0" \
+//! TACEO_OPRF_NODE__POSTGRES__CONNECTION_STRING=postgres://... \
+//! TACEO_OPRF_NODE__POSTGRES__SCHEMA=oprf \
+//! TACEO_OPRF_NODE__JWT__PUBLIC_KEY_PEM="$(cat issuer.pem)" \
+//! TACEO_OPRF_NODE__JWT__ISSUER=https://issuer.example.com \
+//! TACEO_OPRF_NODE__JWT__AUDIENCE=oprf \
+//! TACEO_OPRF_NODE__NODE_URLS=https://node0.example.com,https://node1.example.com,https://node2.example.com \
+//! cargo run -p taceo-oprf-service --example jwt-postgres-node
+//! ```
+
+use std::{net::SocketAddr, process::ExitCode, sync::Arc, time::Duration};
+
+use config::{Config, Environment};
+use eyre::Context;
+use nodes_common::postgres::PostgresConfig;
+use oprf_client::Connector;
+use rustls::{ClientConfig, RootCertStore};
+use serde::Deserialize;
+use taceo_oprf_service::{
+    OprfServiceBuilder, StartedServices, config::OprfNodeServiceConfig,
+    secret_manager::postgres::PostgresSecretManager,
+};
+use url::Url;
+
+use crate::jwt_authenticator::JwtOprfRequestAuthenticator;
+
+mod jwt_authenticator;
+
+/// The name of the OPRF module, served at `/api/jwt/oprf`.
+const MODULE: &str = "jwt";
+
+/// The configuration of the JWT issuer.
+#[derive(Clone, Debug, Deserialize)]
+struct JwtConfig {
+    /// The PEM-encoded ES256 public key of the issuer
+    public_key_pem: String,
+    /// The expected `iss` claim
+    issuer: String,
+    /// The expected `aud` claim
+    audience: String,
+}
+
+/// The top-level configuration of the node.
+#[derive(Clone, Debug, Deserialize)]
+struct JwtNodeConfig {
+    /// The bind addr of the AXUM server
+    #[serde(default = "default_bind_addr")]
+    bind_addr: SocketAddr,
+
+    /// Max wait time the service waits for its workers during shutdown.
+    #[serde(default = "default_max_wait_shutdown")]
+    #[serde(with = "humantime_serde")]
+    max_wait_time_shutdown: Duration,
+
+    /// The OPRF service config
+    #[serde(rename = "service")]
+    node_config: OprfNodeServiceConfig,
+
+    /// The postgres config for the secret-manager
+    #[serde(rename = "postgres")]
+    postgres_config: PostgresConfig,
+
+    /// The JWT issuer
+    jwt: JwtConfig,
+
+    /// The https base urls of the other OPRF nodes to delegate requests to.
+    node_urls: Vec<Url>,
+}
+
+fn default_bind_addr() -> SocketAddr {
+    "0.0.0.0:4321".parse().expect("valid SocketAddr")
+}
+
+fn default_max_wait_shutdown() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn load_jwt_node_config() -> eyre::Result<JwtNodeConfig> {
+    Config::builder()
+        .add_source(
+            Environment::with_prefix("TACEO_OPRF_NODE")
+                .separator("__")
+                .list_separator(",")
+                .with_list_parse_key("node_urls")
+                .try_parsing(true),
+        )
+        .build()
+        .context("while building from config")?
+        .try_deserialize()
+        .context("while parsing config")
+}
+
+/// Verifies the certificates of the other nodes against the webpki roots.
+fn tls_connector() -> Connector {
+    let mut root_store = RootCertStore::empty();
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    Connector::Rustls(Arc::new(
+        ClientConfig::builder()
+            .with_root_certificates(root_store)
+            .with_no_client_auth(),
+    ))
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<ExitCode> {
+    rustls::crypto::aws_lc_rs::default_provider()
+        .install_default()
+        .expect("can install");
+    let _guard = telemetry_batteries::init()?;
+    taceo_oprf_service::metrics::describe_metrics();
+
+    tracing::info!("{}", nodes_common::version_info!());
+    let config = load_jwt_node_config()?;
+
+    match run(config).await {
+        Ok(()) => {
+            tracing::info!("good night!");
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(err) => {
+            tracing::error!(?err, "oprf-service exited with error");
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+async fn run(config: JwtNodeConfig) -> eyre::Result<()> {
+    let (cancellation_token, _) =
+        nodes_common::spawn_shutdown_task(nodes_common::default_shutdown_signal());
+
+    let authenticator = JwtOprfRequestAuthenticator::new(
+        &config.jwt.public_key_pem,
+        &config.jwt.issuer,
+        &config.jwt.audience,
+    )
+    .context("while loading JWT public key")?;
+    let secret_manager = PostgresSecretManager::init(&config.postgres_config)
+        .await
+        .context("while starting postgres secret-manager")?;
+
+    let router = OprfServiceBuilder::load(
+        config.node_config,
+        Arc::new(secret_manager),
+        StartedServices::default(),
+        nodes_common::version_info!(),
+    )
+    .await
+    .context("while loading oprf service")?
+    .module_with_delegate(
+        &format!("/{MODULE}"),
+        Arc::new(authenticator),
+        oprf_client::to_oprf_uri_many(config.node_urls, MODULE)?,
+        tls_connector(),
+    )
+    .build()
+    .context("while building oprf service")?;
+
+    let listener = tokio::net::TcpListener::bind(config.bind_addr).await?;
+    tracing::info!("starting axum server on {}", config.bind_addr);
+    let axum_cancel_token = cancellation_token.clone();
+    let server = tokio::spawn(async move {
+        let axum_result = axum::serve(listener, router)
+            .with_graceful_shutdown(axum_cancel_token.clone().cancelled_owned())
+            .await;
+        if let Err(err) = axum_result {
+            tracing::error!(%err, "got error from axum");
+        }
+        // shutdown the service if axum encountered an error
+        axum_cancel_token.cancel();
+    });
+
+    cancellation_token.cancelled().await;
+    tracing::info!(
+        "waiting for shutdown of services (max wait time {:?})..",
+        config.max_wait_time_shutdown
+    );
+    tokio::time::timeout(config.max_wait_time_shutdown, server)
+        .await
+        .context("could not finish shutdown in time")??;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use oprf_types::{
+    OprfKeyId,
+    api::{OprfRequest, OprfRequestAuthenticator, OprfRequestAuthenticatorError},
+    close_frame_message,
+};
+use serde::{Deserialize, Serialize};
+
+/// The close code of requests with an invalid token.
+pub(crate) const INVALID_TOKEN: u16 = 4500;
+
+/// A JWT signed by the issuer, sent as `auth` of the [`OprfRequest`].
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct JwtOprfRequestAuth(String);
+
+/// The claims of the JWT. The issuer grants access to exactly one key.
+#[derive(Deserialize)]
+struct Claims {
+    oprf_key_id: OprfKeyId,
+}
+
+/// Accepts requests with a JWT signed by the issuer with ES256.
+pub(crate) struct JwtOprfRequestAuthenticator {
+    decoding_key: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtOprfRequestAuthenticator {
+    /// Accepts tokens of `issuer` for `audience`, signed with the private key of `public_key_pem`.
+    pub(crate) fn new(public_key_pem: &str, issuer: &str, audience: &str) -> eyre::Result<Self> {
+        let mut validation = Validation::new(Algorithm::ES256);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        Ok(Self {
+            decoding_key: DecodingKey::from_ec_pem(public_key_pem.as_bytes())?,
+            validation,
+        })
+    }
+}
+
+#[async_trait]
+impl OprfRequestAuthenticator for JwtOprfRequestAuthenticator {
+    type RequestAuth = JwtOprfRequestAuth;
+
+    async fn authenticate(
+        &self,
+        request: &OprfRequest<Self::RequestAuth>,
+    ) -> Result<OprfKeyId, OprfRequestAuthenticatorError> {
+        let JwtOprfRequestAuth(token) = &request.auth;
+        let token = jsonwebtoken::decode::<Claims>(token, &self.decoding_key, &self.validation)
+            .map_err(|err| {
+                tracing::debug!(%err, "rejecting token");
+                OprfRequestAuthenticatorError::with_message(
+                    INVALID_TOKEN,
+                    close_frame_message!("invalid token"),
+                )
+            })?;
+        Ok(token.claims.oprf_key_id)
+    }
+}
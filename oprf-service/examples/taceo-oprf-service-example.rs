@@ -4,12 +4,16 @@ use config::{Config, Environment};
 use eyre::Context;
 use nodes_common::postgres::PostgresConfig;
 use oprf_client::Connector;
+use secrecy::SecretString;
 use serde::Deserialize;
 use taceo_oprf_service::{
     OprfServiceBuilder, StartedServices,
-    config::OprfNodeServiceConfig,
+    config::{OprfNodeServiceConfig, SessionPersistence},
+    listener::MultiListener,
     secret_manager::{SecretManagerService, postgres::PostgresSecretManager},
+    session_store::postgres::PostgresSessionStore,
 };
+use tokio_util::sync::CancellationToken;
 use url::Url;
 
 use crate::simple_authenticator::ExampleOprfRequestAuthenticator;
@@ -25,6 +29,10 @@ pub struct ExampleOprfNodeConfig {
     #[serde(default = "default_bind_addr")]
     pub bind_addr: SocketAddr,
 
+    /// Further addresses the AXUM server binds to, e.g., `[::]:4321` next to `0.0.0.0:4321` for IPv4 and IPv6.
+    #[serde(default)]
+    pub additional_bind_addrs: Vec<SocketAddr>,
+
     /// Max wait time the service waits for its workers during shutdown.
     #[serde(default = "default_max_wait_shutdown")]
     #[serde(with = "humantime_serde")]
@@ -36,7 +44,26 @@ pub struct ExampleOprfNodeConfig {
 
     /// The postgres config for the secret-manager
     #[serde(rename = "postgres")]
-    pub postgres_config: PostgresConfig,
+    pub postgres_config: Option<PostgresConfig>,
+
+    /// The secret the pending sessions are encrypted with, required with durable `service.session_persistence` (which stores the sessions in Postgres)
+    #[serde(default)]
+    pub session_secret: Option<SecretString>,
+
+    /// If set, loads the shares from Google Cloud Secret Manager instead of Postgres
+    #[cfg(feature = "gcp")]
+    #[serde(default)]
+    pub gcp: Option<taceo_oprf_service::secret_manager::gcp::GcpConfig>,
+
+    /// If set, loads the shares from Azure Key Vault instead of Postgres
+    #[cfg(feature = "azure")]
+    #[serde(default)]
+    pub azure: Option<taceo_oprf_service::secret_manager::azure::AzureConfig>,
+
+    /// If set, loads the shares from `HashiCorp` Vault instead of Postgres
+    #[cfg(feature = "vault")]
+    #[serde(default)]
+    pub vault: Option<taceo_oprf_service::secret_manager::vault::VaultConfig>,
 
     /// The http base urls of the other OPRF nodes to delegate requests to.
     pub node_urls: Vec<Url>,
@@ -57,6 +84,7 @@ pub fn load_example_config() -> eyre::Result<ExampleOprfNodeConfig> {
             .list_separator(",")
             .with_list_parse_key("rpc.http_urls")
             .with_list_parse_key("node_urls")
+            .with_list_parse_key("additional_bind_addrs")
             .try_parsing(true),
     );
 
@@ -78,12 +106,7 @@ async fn main() -> eyre::Result<ExitCode> {
     let config = load_example_config()?;
     tracing::info!("starting oprf-service with config: {config:#?}");
 
-    // Load the postgres secret manager.
-    let secret_manager = Arc::new(
-        PostgresSecretManager::init(&config.postgres_config)
-            .await
-            .context("while starting postgres secret-manager")?,
-    );
+    let secret_manager = init_secret_manager(&config).await?;
 
     let result = start_service(
         config,
@@ -104,6 +127,43 @@ async fn main() -> eyre::Result<ExitCode> {
     }
 }
 
+async fn init_secret_manager(config: &ExampleOprfNodeConfig) -> eyre::Result<SecretManagerService> {
+    #[cfg(feature = "gcp")]
+    if let Some(gcp_config) = config.gcp.clone() {
+        return Ok(Arc::new(
+            taceo_oprf_service::secret_manager::gcp::GcpSecretManager::init(gcp_config)
+                .await
+                .context("while starting GCP secret-manager")?,
+        ));
+    }
+    #[cfg(feature = "azure")]
+    if let Some(azure_config) = config.azure.clone() {
+        return Ok(Arc::new(
+            taceo_oprf_service::secret_manager::azure::AzureSecretManager::init(azure_config)
+                .await
+                .context("while starting Azure Key Vault secret-manager")?,
+        ));
+    }
+    #[cfg(feature = "vault")]
+    if let Some(vault_config) = config.vault.clone() {
+        return Ok(Arc::new(
+            taceo_oprf_service::secret_manager::vault::VaultSecretManager::init(vault_config)
+                .await
+                .context("while starting Vault secret-manager")?,
+        ));
+    }
+    // Load the postgres secret manager.
+    let postgres_config = config
+        .postgres_config
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("no secret-manager configured"))?;
+    Ok(Arc::new(
+        PostgresSecretManager::init(postgres_config)
+            .await
+            .context("while starting postgres secret-manager")?,
+    ))
+}
+
 pub async fn start_service(
     config: ExampleOprfNodeConfig,
     secret_manager: SecretManagerService,
@@ -111,39 +171,49 @@ pub async fn start_service(
 ) -> eyre::Result<()> {
     let (cancellation_token, _) = nodes_common::spawn_shutdown_task(shutdown_signal);
 
-    tracing::info!("loading node-information from secret-manager..");
-    let node_information = secret_manager
-        .load_node_information()
-        .await
-        .context("while loading node information")?;
-    tracing::info!("node information: {node_information:#?}");
-
     tracing::info!("init oprf service..");
-    let oprf_service_router = OprfServiceBuilder::init(
+    let session_persistence = config.node_config.session_persistence;
+    let session_lifetime = config.node_config.session_lifetime;
+    let mut builder = OprfServiceBuilder::load(
         config.node_config,
         secret_manager,
         StartedServices::default(),
-        &node_information,
         nodes_common::version_info!(),
     )
-    .module_with_delegate(
-        "/example",
-        Arc::new(ExampleOprfRequestAuthenticator),
-        oprf_client::to_oprf_uri_many(config.node_urls, "example")?,
-        Connector::Plain,
-    )
-    .build();
-
-    let listener = tokio::net::TcpListener::bind(config.bind_addr).await?;
-    let axum_cancel_token = cancellation_token.clone();
+    .await
+    .context("while loading oprf service")?;
+    if session_persistence == SessionPersistence::Durable {
+        let (Some(postgres_config), Some(session_secret)) =
+            (&config.postgres_config, &config.session_secret)
+        else {
+            eyre::bail!("durable session persistence requires postgres and session_secret");
+        };
+        builder = builder.session_store(Arc::new(
+            PostgresSessionStore::init(postgres_config, session_secret, session_lifetime)
+                .await
+                .context("while starting postgres session store")?,
+        ));
+    }
+    let (oprf_service_router, oprf_service_shutdown) = builder
+        .module_with_delegate(
+            "/example",
+            Arc::new(ExampleOprfRequestAuthenticator),
+            oprf_client::to_oprf_uri_many(config.node_urls, "example")?,
+            Connector::Plain,
+        )
+        .build_with_shutdown(cancellation_token.clone())
+        .context("while building oprf service")?;
+
+    let bind_addrs = std::iter::once(config.bind_addr)
+        .chain(config.additional_bind_addrs)
+        .collect::<Vec<_>>();
+    let listener = MultiListener::bind(&bind_addrs).context("while binding tcp-listener")?;
+    // the server keeps running until the open sessions drained
+    let server_cancel_token = CancellationToken::new();
+    let axum_cancel_token = server_cancel_token.clone();
+    let server_error_token = cancellation_token.clone();
     let server = tokio::spawn(async move {
-        tracing::info!(
-            "starting axum server on {}",
-            listener
-                .local_addr()
-                .map(|x| x.to_string())
-                .unwrap_or(String::from("invalid addr"))
-        );
+        tracing::info!("starting axum server on {bind_addrs:?}");
         let axum_shutdown_signal = axum_cancel_token.clone();
         let axum_result = axum::serve(listener, oprf_service_router)
             .with_graceful_shutdown(async move { axum_shutdown_signal.cancelled().await })
@@ -153,12 +223,20 @@ pub async fn start_service(
             tracing::error!(%err, "got error from axum");
         }
         // we cancel the token in case axum encountered an error to shutdown the service
-        axum_cancel_token.cancel();
+        server_error_token.cancel();
     });
 
     tracing::info!("everything started successfully - now waiting for shutdown...");
     cancellation_token.cancelled().await;
 
+    let report = oprf_service_shutdown
+        .run(config.max_wait_time_shutdown)
+        .await;
+    if !report.is_clean() {
+        tracing::warn!(?report, "oprf service did not shut down cleanly");
+    }
+    server_cancel_token.cancel();
+
     tracing::info!(
         "waiting for shutdown of services (max wait time {:?})..",
         config.max_wait_time_shutdown
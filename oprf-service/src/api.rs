@@ -2,14 +2,36 @@
 //!
 //! This module defines all HTTP endpoints an OPRF node must serve to participate in TACEO:OPRF and organizes them into submodules:
 //!
+//! - [`admin`] – Runtime overrides of the limits of the OPRF modules (`/admin/limits/{path}`), if enabled.
+//! - [`buildinfo`] – Build provenance of the node (`/buildinfo`).
+//! - [`chaos`] – Artificial latency and errors for the OPRF modules, if enabled.
+//! - [`committee`] – Aggregated committee health (`/committee/health`) and the registered committee (`/committee`), if enabled.
+//! - [`epoch_notifications`] – The web-socket endpoint `/epoch_notifications` pushing epoch changes to subscribed clients.
 //! - [`errors`] – Defines API error types and conversions from internal service errors.
-//! - [`info`] – Info about the service (`/version`, `/wallet` and `/oprf_pub/{id}`).
-//! - [`oprf`] – The implementation of the OPRF WebSocket endpoint `/oprf`.
+//! - `grpc` – The gRPC transport of the OPRF modules (`taceo.oprf.v1.OprfNode/Oprf`), if enabled (requires the `grpc` feature).
+//! - [`health`] – Readiness of the started services and subsystems of the node (`/health/details`).
+//! - [`info`] – Info about the service (`/version`, `/info`, `/wallet` and `/oprf_pub/{id}`) and the public key history (`/oprf_pub/{id}/history`), if enabled.
+//! - [`memory`] – Memory statistics of the process and the subsystems of the node (`/debug/memory`), if enabled (requires the `jemalloc` feature).
+//! - [`multiplex`] – The multiplexed OPRF WebSocket endpoint `/oprf/multiplex`, carrying many interleaved sessions per connection.
+//! - [`oprf`] – The implementation of the OPRF WebSocket endpoint `/oprf` and the limits of the module at `/params`.
 //! - [`oprf_delegate`] – The implementation of the OPRF delegate endpoint `/delegate`.
+//! - [`replica`] – Snapshots of the key material for replicas of this node (`/replica/snapshot`), if enabled.
 //! - [`version_header`] – Serialization for the custom [`version_header::ProtocolVersion`] header the clients needs to send.
 
+pub(crate) mod admin;
+pub(crate) mod buildinfo;
+pub(crate) mod chaos;
+pub(crate) mod committee;
+pub(crate) mod epoch_notifications;
 pub(crate) mod errors;
+#[cfg(feature = "grpc")]
+pub(crate) mod grpc;
+pub(crate) mod health;
 pub(crate) mod info;
+#[cfg(feature = "jemalloc")]
+pub(crate) mod memory;
+pub(crate) mod multiplex;
 pub(crate) mod oprf;
 pub(crate) mod oprf_delegate;
+pub(crate) mod replica;
 pub(crate) mod version_header;
@@ -0,0 +1,77 @@
+//! Admin Endpoint
+//!
+//! Exposes the following API endpoint if enabled with [`crate::OprfServiceBuilder::admin_api`]:
+//!
+//! - `PUT /admin/limits/{path}` – overrides the runtime limits of the module at `/api/{path}` (see [`crate::services::runtime_limits`]) and returns its new [`oprf_types::api::ModuleParams`].
+//!
+//! Requests must carry the admin token as `Authorization: Bearer` header.
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::put,
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+
+use crate::services::runtime_limits::{LimitsUpdate, RuntimeLimits};
+
+#[derive(Clone)]
+struct AdminState {
+    /// The hash of the admin token, compared in constant time.
+    admin_token: blake3::Hash,
+    runtime_limits: RuntimeLimits,
+    max_batch_size: usize,
+}
+
+/// Create a router containing the admin endpoints for the given modules.
+pub(crate) fn routes(
+    admin_token: blake3::Hash,
+    modules: Vec<(String, RuntimeLimits)>,
+    max_batch_size: usize,
+) -> Router {
+    modules
+        .into_iter()
+        .fold(Router::new(), |router, (path, runtime_limits)| {
+            router.route(
+                &format!("/admin/limits{path}"),
+                put(update_limits).with_state(AdminState {
+                    admin_token,
+                    runtime_limits,
+                    max_batch_size,
+                }),
+            )
+        })
+}
+
+/// Overrides the runtime limits of the module.
+///
+/// Returns `200 OK` with the new [`oprf_types::api::ModuleParams`].
+/// Returns `400 Bad Request` if a value is out of bounds, in which case no value is changed.
+/// Returns `401 Unauthorized` if the request does not carry the admin token.
+async fn update_limits(
+    State(state): State<AdminState>,
+    bearer: Option<TypedHeader<Authorization<Bearer>>>,
+    Json(update): Json<LimitsUpdate>,
+) -> Response {
+    let authorized = bearer.is_some_and(|TypedHeader(Authorization(bearer))| {
+        blake3::hash(bearer.token().as_bytes()) == state.admin_token
+    });
+    if !authorized {
+        tracing::warn!("rejected unauthorized admin request");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match state.runtime_limits.update(update) {
+        Ok(limits) => {
+            tracing::warn!(?limits, "overrode runtime limits");
+            (StatusCode::OK, Json(limits.params(state.max_batch_size))).into_response()
+        }
+        Err(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests;
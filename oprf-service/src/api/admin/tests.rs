@@ -0,0 +1,81 @@
+use axum_test::TestServerBuilder;
+use oprf_types::api::{ModuleParams, OPRF_MAX_MESSAGE_SIZE_HEADER};
+use secrecy::SecretString;
+
+use crate::{
+    test_kit::MockAuthenticator,
+    test_utils::{builder, default_config},
+};
+
+#[tokio::test]
+async fn admin_overrides_module_limits() {
+    let router = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .module("/other", MockAuthenticator::allow_all().into_service())
+        .admin_api(&SecretString::from("admin-token"))
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let update = serde_json::json!({ "max_message_size": 512, "session_lifetime": "10s" });
+
+    server
+        .put("/admin/limits/test")
+        .json(&update)
+        .await
+        .assert_status_unauthorized();
+    server
+        .put("/admin/limits/test")
+        .authorization_bearer("wrong-token")
+        .json(&update)
+        .await
+        .assert_status_unauthorized();
+    server
+        .put("/admin/limits/test")
+        .authorization_bearer("admin-token")
+        .json(&serde_json::json!({ "max_message_size": 1 << 30 }))
+        .await
+        .assert_status_bad_request();
+
+    let params = server
+        .put("/admin/limits/test")
+        .authorization_bearer("admin-token")
+        .json(&update)
+        .await
+        .json::<ModuleParams>();
+    assert_eq!(params.max_message_size, 512);
+    assert_eq!(params.session_lifetime_ms, 10_000);
+    assert_eq!(
+        server.get("/api/test/params").await.json::<ModuleParams>(),
+        params,
+        "should reflect the override"
+    );
+    assert_eq!(
+        server
+            .get("/api/other/params")
+            .await
+            .json::<ModuleParams>()
+            .max_message_size,
+        default_config().ws_max_message_size,
+        "should not change other modules"
+    );
+    let upgrade = server.get_websocket("/api/test/oprf?version=1.0.0").await;
+    assert_eq!(
+        upgrade.header(&OPRF_MAX_MESSAGE_SIZE_HEADER),
+        "512",
+        "new connections should use the override"
+    );
+
+    server
+        .put("/admin/limits/test")
+        .authorization_bearer("admin-token")
+        .json(&serde_json::json!({ "version_req": ">=2.0.0" }))
+        .await
+        .assert_status_ok();
+    server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .assert_status_bad_request();
+}
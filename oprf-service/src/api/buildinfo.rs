@@ -0,0 +1,75 @@
+//! Build Info Endpoint
+//!
+//! Exposes the following API endpoint:
+//!
+//! - `/buildinfo` – returns the [`BuildInfo`] of the node: the version string of the host (see `nodes_common::version_info!`), the versions of the TACEO:OPRF crates, the git hash, the build timestamp, the enabled cargo features of this crate and the client protocol (see [`NodeInfo`]).
+//!
+//! The git hash is taken from `GIT_HASH` and the build timestamp from `BUILD_TIMESTAMP` at compile time, like in our Docker builds. Without `GIT_HASH`, the hash of the checkout is used.
+//!
+//! The endpoint includes a `Cache-Control: no-cache` header to prevent caching of responses.
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderValue, StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
+use oprf_types::api::{BuildInfo, NodeInfo};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+/// The cargo features of this crate, enabled or not.
+const FEATURES: [(&str, bool); 10] = [
+    ("postgres", cfg!(feature = "postgres")),
+    ("sqlite", cfg!(feature = "sqlite")),
+    ("gcp", cfg!(feature = "gcp")),
+    ("azure", cfg!(feature = "azure")),
+    ("vault", cfg!(feature = "vault")),
+    ("test-kit", cfg!(feature = "test-kit")),
+    ("registry", cfg!(feature = "registry")),
+    ("jemalloc", cfg!(feature = "jemalloc")),
+    ("grpc", cfg!(feature = "grpc")),
+    ("exemplars", cfg!(feature = "exemplars")),
+];
+
+/// Returns the [`BuildInfo`] of a node with the host `version_str` accepting the client versions `version_req`.
+pub(crate) fn build_info(version_str: String, version_req: String) -> BuildInfo {
+    let features = FEATURES
+        .iter()
+        .filter_map(|(name, enabled)| enabled.then_some(*name))
+        .collect::<Vec<_>>();
+    BuildInfo::new(
+        version_str,
+        option_env!("GIT_HASH")
+            .unwrap_or(nodes_common::git_version::git_version!(
+                fallback = "UNKNOWN"
+            ))
+            .to_owned(),
+        option_env!("BUILD_TIMESTAMP").map(ToOwned::to_owned),
+        &features,
+    )
+    .with_crate(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+    .with_protocol(NodeInfo::new(version_req))
+}
+
+/// Create a router containing the build info endpoint.
+pub(crate) fn routes(build_info: BuildInfo) -> Router {
+    Router::new()
+        .route("/buildinfo", get(buildinfo))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("no-cache"),
+        ))
+        .with_state(Arc::new(build_info))
+}
+
+/// Responds with the [`BuildInfo`] of the node.
+///
+/// Returns `200 OK` with a JSON response.
+async fn buildinfo(State(build_info): State<Arc<BuildInfo>>) -> impl IntoResponse {
+    (StatusCode::OK, Json(build_info))
+}
+
+#[cfg(test)]
+mod tests;
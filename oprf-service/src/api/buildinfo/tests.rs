@@ -0,0 +1,36 @@
+use axum_test::TestServerBuilder;
+use oprf_types::api::{BuildInfo, SchemaFingerprint};
+
+use crate::{test_kit::MockAuthenticator, test_utils::builder};
+
+#[tokio::test]
+async fn buildinfo_exposes_crates_and_protocol() {
+    let router = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+
+    let response = server.get("/buildinfo").await;
+    response.assert_status_ok();
+    response.assert_header("cache-control", "no-cache");
+    let build_info = response.json::<BuildInfo>();
+    for crate_name in ["taceo-oprf-service", "taceo-oprf-types"] {
+        assert!(
+            build_info.crates.contains_key(crate_name),
+            "should contain the version of {crate_name}, got {:?}",
+            build_info.crates
+        );
+    }
+    assert!(!build_info.git_sha.is_empty(), "should expose the git hash");
+    let protocol = build_info.protocol.expect("node serves clients");
+    assert_eq!(
+        protocol.schema_fingerprint,
+        SchemaFingerprint::CURRENT,
+        "should expose our schema fingerprint"
+    );
+    assert_eq!(protocol.version_req, "*", "should expose the version req");
+}
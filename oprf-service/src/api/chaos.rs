@@ -0,0 +1,42 @@
+//! Middleware injecting artificial latency and errors into the requests of the OPRF modules, see [`ChaosConfig`].
+//!
+//! Only installed if [`crate::config::OprfNodeServiceConfig::chaos`] is set, never in [`crate::Environment::Prod`].
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse as _, Response},
+};
+use http::StatusCode;
+use rand::Rng as _;
+
+use crate::{config::ChaosConfig, metrics};
+
+/// Delays and/or rejects the request at random, as configured in the [`ChaosConfig`].
+pub(crate) async fn inject(
+    State(config): State<ChaosConfig>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (delay, fail) = {
+        let mut rng = rand::thread_rng();
+        (
+            rng.gen_bool(config.latency_rate),
+            rng.gen_bool(config.error_rate),
+        )
+    };
+    if delay {
+        tracing::debug!("chaos: delaying request by {:?}", config.latency);
+        metrics::request::inc_chaos_injected("latency");
+        tokio::time::sleep(config.latency).await;
+    }
+    if fail {
+        tracing::debug!("chaos: rejecting request");
+        metrics::request::inc_chaos_injected("error");
+        return (StatusCode::SERVICE_UNAVAILABLE, "injected by chaos config").into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use axum_test::TestServerBuilder;
+
+use crate::{
+    BuilderError, Environment,
+    config::{ChaosConfig, OprfNodeServiceConfig},
+    test_kit::MockAuthenticator,
+    test_utils::{builder_with_config, default_config},
+};
+
+#[test]
+fn chaos_is_rejected_in_prod() {
+    let mut config =
+        OprfNodeServiceConfig::with_default_values(Environment::Prod, semver::VersionReq::STAR);
+    config.chaos = Some(ChaosConfig::default());
+    let err = builder_with_config(config)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect_err("should fail");
+    assert!(
+        matches!(
+            err,
+            BuilderError::InvalidConfig("chaos must not be enabled in prod")
+        ),
+        "expected invalid config, got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn chaos_injects_errors_and_latency() {
+    let mut config = default_config();
+    config.chaos = Some(ChaosConfig {
+        latency_rate: 1.0,
+        latency: Duration::from_millis(200),
+        error_rate: 1.0,
+    });
+    let router = builder_with_config(config)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+
+    let start = tokio::time::Instant::now();
+    let response = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .expect_failure()
+        .await;
+    assert!(
+        start.elapsed() >= Duration::from_millis(200),
+        "should delay the session"
+    );
+    response.assert_status(http::StatusCode::SERVICE_UNAVAILABLE);
+    server.get("/version").await.assert_status_ok();
+}
@@ -0,0 +1,56 @@
+//! Committee Endpoints
+//!
+//! Exposes the following API endpoints if enabled:
+//!
+//! - `/committee/health` – returns the aggregated health of the committee as seen by this node, including peers lagging on epochs (see [`crate::OprfServiceBuilder::committee_health`]).
+//! - `/committee` – returns the party ids and wallet addresses of the committee as registered in the `OprfKeyRegistry` (requires the `registry` feature, see `OprfServiceBuilder::committee_registry`).
+use axum::{Json, Router, extract::State, response::IntoResponse, routing::get};
+
+use crate::services::committee_health::CommitteeHealthService;
+#[cfg(feature = "registry")]
+use crate::services::committee_registry::CommitteeRegistryService;
+
+/// Create a router containing the committee endpoints.
+pub(crate) fn routes(committee_health: CommitteeHealthService) -> Router {
+    Router::new()
+        .route("/committee/health", get(committee_health_report))
+        .with_state(committee_health)
+}
+
+/// Responds with the latest committee health report.
+///
+/// Returns `200 OK` with the report as JSON.
+async fn committee_health_report(
+    State(committee_health): State<CommitteeHealthService>,
+) -> impl IntoResponse {
+    Json(committee_health.report())
+}
+
+/// Create a router containing the committee registry endpoint.
+#[cfg(feature = "registry")]
+pub(crate) fn registry_routes(committee_registry: CommitteeRegistryService) -> Router {
+    Router::new()
+        .route("/committee", get(committee))
+        .with_state(committee_registry)
+}
+
+/// Responds with the committee as registered in the `OprfKeyRegistry`.
+///
+/// Returns `200 OK` with the [`oprf_types::api::Committee`] as JSON.
+/// Returns `503 Service Unavailable` if the committee was not loaded from the registry yet.
+#[cfg(feature = "registry")]
+async fn committee(
+    State(committee_registry): State<CommitteeRegistryService>,
+) -> axum::response::Response {
+    match committee_registry.committee() {
+        Some(committee) => Json(committee).into_response(),
+        None => (
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            "committee not loaded yet",
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests;
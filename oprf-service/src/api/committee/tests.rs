@@ -0,0 +1,72 @@
+use std::{sync::Arc, time::Duration};
+
+use axum_test::TestServerBuilder;
+use oprf_types::{OprfKeyId, ShareEpoch};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    test_kit::MockAuthenticator,
+    test_utils::{MockSecretManager, builder_with_secret_manager, default_config},
+};
+
+#[tokio::test]
+async fn committee_health_reports_lagging_peer() {
+    let peer = TestServerBuilder::new()
+        .http_transport()
+        .build(
+            builder_with_secret_manager(
+                default_config(),
+                Arc::new(MockSecretManager::fixed_key(ShareEpoch::new(2))),
+            )
+            .module("/test", MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect("Can build peer"),
+        )
+        .expect("Can build peer test-server");
+    let peer_url = peer.server_address().expect("Has address");
+
+    let mut config = default_config();
+    config.committee_poll_interval = Duration::from_millis(50);
+    let cancellation_token = CancellationToken::new();
+    let node = TestServerBuilder::new()
+        .http_transport()
+        .build(
+            builder_with_secret_manager(
+                config,
+                Arc::new(MockSecretManager::fixed_key(ShareEpoch::new(3))),
+            )
+            .committee_health(
+                vec![peer_url],
+                reqwest::Client::new(),
+                cancellation_token.clone(),
+            )
+            .module("/test", MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect("Can build node"),
+        )
+        .expect("Can build node test-server");
+
+    // load the key at the node so it is compared with the peer
+    let oprf_key_id = OprfKeyId::from(42usize);
+    node.get(&format!("/oprf_pub/{oprf_key_id}"))
+        .await
+        .assert_status_ok();
+
+    let mut report = serde_json::Value::Null;
+    for _ in 0..100 {
+        report = node.get("/committee/health").await.json();
+        if report["peers"][0]["lagging_keys"]
+            .as_array()
+            .is_some_and(|lagging| !lagging.is_empty())
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    cancellation_token.cancel();
+    assert_eq!(report["healthy_peers"], 1, "peer should be healthy");
+    assert_eq!(
+        report["peers"][0]["lagging_keys"][0]["peer_epoch"], 2,
+        "peer should lag behind on epoch: {report}"
+    );
+}
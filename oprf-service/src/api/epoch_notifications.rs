@@ -0,0 +1,136 @@
+//! Epoch Notifications Endpoint
+//!
+//! Exposes the web-socket endpoint `/epoch_notifications`. Clients that keep connections or cached epochs around opt in by connecting to this endpoint. The node then pushes an [`EpochChanged`] notification as `Binary` (`cbor`) frame whenever it loads another epoch of an [`oprf_types::OprfKeyId`] than it saw last, e.g., when it reloads a reshared key.
+//!
+//! The endpoint is push-only. Any frame sent by the client other than `Ping`/`Pong` terminates the connection. After `max_connection_lifetime` the node closes the connection with a `Normal` close frame and clients are expected to reconnect. A subscriber that falls so far behind that it misses notifications is closed with [`oprf_error_codes::NOTIFICATIONS_LAGGED`], as it cannot tell which epochs changed in the meantime.
+use std::time::Duration;
+
+use axum::{
+    Router,
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{self, CloseFrame, WebSocket, close_code},
+    },
+    response::IntoResponse,
+    routing::any,
+};
+use oprf_types::api::{EpochChanged, oprf_error_codes, oprf_error_messages};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::services::oprf_key_material_store::OprfKeyMaterialStore;
+
+#[derive(Clone)]
+struct EpochNotificationsState {
+    oprf_material_store: OprfKeyMaterialStore,
+    max_connection_lifetime: Duration,
+    websocket_shutdown_timeout: Duration,
+}
+
+/// Create a router containing the `/epoch_notifications` endpoint.
+pub(crate) fn routes(
+    oprf_material_store: OprfKeyMaterialStore,
+    max_connection_lifetime: Duration,
+    websocket_shutdown_timeout: Duration,
+) -> Router {
+    Router::new()
+        .route("/epoch_notifications", any(epoch_notifications_ws_handler))
+        .with_state(EpochNotificationsState {
+            oprf_material_store,
+            max_connection_lifetime,
+            websocket_shutdown_timeout,
+        })
+}
+
+/// Upgrades the connection and subscribes it to [`EpochChanged`] notifications.
+///
+/// The subscription is created before the upgrade finishes, so no notification sent after the `101` response is lost.
+async fn epoch_notifications_ws_handler(
+    State(state): State<EpochNotificationsState>,
+    websocket_upgrade: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let epoch_changes = state.oprf_material_store.subscribe_epoch_changes();
+    websocket_upgrade
+        .on_failed_upgrade(|err| {
+            tracing::warn!(user_error=true, %err, "could not establish websocket connection");
+        })
+        .on_upgrade(move |ws| push_epoch_changes(ws, epoch_changes, state))
+}
+
+async fn push_epoch_changes(
+    mut socket: WebSocket,
+    epoch_changes: broadcast::Receiver<EpochChanged>,
+    state: EpochNotificationsState,
+) {
+    let close_frame = match tokio::time::timeout(
+        state.max_connection_lifetime,
+        push_epoch_changes_inner(&mut socket, epoch_changes),
+    )
+    .await
+    {
+        Ok(close_frame) => close_frame,
+        Err(_) => Some(CloseFrame {
+            code: close_code::NORMAL,
+            reason: "reconnect".into(),
+        }),
+    };
+    if let Some(close_frame) = close_frame {
+        let send_close = socket.send(ws::Message::Close(Some(close_frame)));
+        if tokio::time::timeout(state.websocket_shutdown_timeout, send_close)
+            .await
+            .is_err()
+        {
+            tracing::trace!("timeout during web-socket teardown");
+        }
+    }
+}
+
+/// Forwards notifications until either side goes away. Returns the close frame to send, if any.
+async fn push_epoch_changes_inner(
+    socket: &mut WebSocket,
+    mut epoch_changes: broadcast::Receiver<EpochChanged>,
+) -> Option<CloseFrame> {
+    loop {
+        tokio::select! {
+            notification = epoch_changes.recv() => match notification {
+                Ok(notification) => {
+                    let mut buf = Vec::new();
+                    ciborium::into_writer(&notification, &mut buf).expect("Can serialize notification");
+                    if socket.send(ws::Message::binary(buf)).await.is_err() {
+                        tracing::trace!("client went away - web-socket connection closed");
+                        return None;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    // the client cannot tell which keys it missed, so it must start over
+                    tracing::debug!("epoch notification subscriber lagged behind - skipped {skipped} notifications");
+                    return Some(CloseFrame {
+                        code: oprf_error_codes::NOTIFICATIONS_LAGGED,
+                        reason: oprf_error_messages::NOTIFICATIONS_LAGGED.into(),
+                    });
+                }
+                Err(RecvError::Closed) => {
+                    return Some(CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "shutdown".into(),
+                    });
+                }
+            },
+            msg = socket.recv() => match msg {
+                Some(Ok(ws::Message::Ping(_) | ws::Message::Pong(_))) => {}
+                Some(Ok(ws::Message::Close(_)) | Err(_)) | None => {
+                    tracing::trace!("client closed epoch notification subscription");
+                    return None;
+                }
+                Some(Ok(_)) => {
+                    return Some(CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "push-only endpoint".into(),
+                    });
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
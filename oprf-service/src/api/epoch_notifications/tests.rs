@@ -0,0 +1,94 @@
+use std::{sync::Arc, time::Duration};
+
+use axum_test::TestServerBuilder;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{EpochChanged, oprf_error_codes},
+};
+
+use crate::{
+    services::oprf_key_material_store::OprfKeyMaterialStore,
+    test_kit::MockAuthenticator,
+    test_utils::{MockSecretManager, builder_with_secret_manager, default_config},
+};
+
+#[tokio::test]
+async fn epoch_notifications_push_loaded_key() {
+    let router = builder_with_secret_manager(
+        default_config(),
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::new(3))),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let mut subscription = server
+        .get_websocket("/epoch_notifications")
+        .await
+        .into_websocket()
+        .await;
+
+    let oprf_key_id = OprfKeyId::from(42usize);
+    server
+        .get(&format!("/oprf_pub/{oprf_key_id}"))
+        .await
+        .assert_status_ok();
+
+    let bytes = subscription.receive_bytes().await;
+    let notification: EpochChanged =
+        ciborium::from_reader(bytes.as_ref()).expect("Can parse notification");
+    assert_eq!(
+        notification,
+        EpochChanged {
+            oprf_key_id,
+            previous: None,
+            epoch: ShareEpoch::new(3),
+        },
+        "should push the loaded epoch"
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn epoch_notifications_close_lagging_subscriber() {
+    let config = default_config();
+    let store = OprfKeyMaterialStore::new(
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::new(3))),
+        config.store_max_capacity,
+        config.store_ttl,
+        config.store_tti,
+        config.store_eviction_policy,
+    );
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(super::routes(
+            store.clone(),
+            Duration::from_mins(1),
+            Duration::from_secs(1),
+        ))
+        .expect("Can build test-server");
+    let mut subscription = server
+        .get_websocket("/epoch_notifications")
+        .await
+        .into_websocket()
+        .await;
+
+    // the preload tasks run before the subscription is polled again, so it misses more notifications than the channel holds
+    let oprf_key_ids = (0..1024usize).map(OprfKeyId::from).collect::<Vec<_>>();
+    store.preload(&oprf_key_ids).await;
+
+    let frame = loop {
+        match subscription.receive_message().await {
+            tungstenite::Message::Close(frame) => break frame.expect("has close frame"),
+            tungstenite::Message::Binary(_) => {}
+            msg => panic!("unexpected message {msg:?}"),
+        }
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::NOTIFICATIONS_LAGGED,
+        "should close the subscription of a lagging subscriber"
+    );
+}
@@ -1,13 +1,18 @@
 //! This module defines the [`Error`] the websocket connection may encounter during a OPRF request. It further provides a method to transform the encountered errors into a close frame if necessary.
 
-use std::{io::ErrorKind, sync::Arc};
+use std::{error::Error as _, io::ErrorKind, sync::Arc, time::Duration};
 
 use axum::extract::ws::{CloseFrame, Utf8Bytes, close_code};
-use oprf_types::api::{OprfRequestAuthenticatorError, oprf_error_codes};
-use tungstenite::error::ProtocolError;
+use oprf_types::api::{
+    CLOSE_FRAME_MAX_LENGTH, OprfRequestAuthenticatorError, oprf_error_codes, oprf_error_messages,
+};
+use tungstenite::error::{CapacityError, ProtocolError};
 use uuid::Uuid;
 
-use crate::secret_manager::SecretManagerError;
+use crate::{
+    config::CloseFrameVerbosity, secret_manager::SecretManagerError,
+    session_store::OprfSessionStoreError,
+};
 
 macro_rules! to_close_frame_bytes {
     ($s: expr) => {
@@ -24,31 +29,154 @@ pub(crate) enum Error {
     ConnectionClosed,
     #[error(transparent)]
     Axum(#[from] axum::Error),
+    #[cfg(feature = "grpc")]
+    #[error("gRPC stream failed: {0}")]
+    Grpc(#[from] tonic::Status),
     #[error("unexpected message - received PING/PONG or user switched encoding between messages")]
     UnexpectedMessage,
     #[error("cannot authenticate: {0}")]
     Auth(#[from] OprfRequestAuthenticatorError),
+    #[error("authentication did not finish within {0:?}")]
+    AuthTimeout(Duration),
     #[error(transparent)]
     Json(#[from] serde_json::Error),
     #[error(transparent)]
     Cbor(#[from] ciborium::de::Error<std::io::Error>),
-    #[error("blinded query must not be identity")]
+    #[error("{}", oprf_error_messages::NON_CANONICAL_CBOR)]
+    NonCanonicalCbor,
+    #[error("{}", oprf_error_messages::BLINDED_QUERY_IS_IDENTITY)]
     BlindedQueryIsIdentity,
+    #[error("issued-at timestamp is missing or outside the accepted window: {0:?}")]
+    StaleQuery(Option<u64>),
     #[error("expected {threshold} contributing parties but got {num_coeffs}")]
     ThresholdContributingPartiesMissmatch { threshold: u16, num_coeffs: usize },
-    #[error("contributing parties does not contain my coefficient")]
+    #[error("{}", oprf_error_messages::MISSING_MY_COEFFICIENT)]
     MissingMyCoefficient,
-    #[error("contributing parties are not sorted")]
+    #[error("{}", oprf_error_messages::UNSORTED_CONTRIBUTING_PARTIES)]
     ContributionsNotSorted,
-    #[error("contributing parties contains duplicate coefficients")]
+    #[error("{}", oprf_error_messages::DUPLICATE_COEFFICIENT)]
     DuplicateCoefficients,
+    #[error("request denied by risk scorer")]
+    RiskDenied,
+    #[error("request throttled by risk scorer, retry after {0:?}")]
+    RiskThrottled(Duration),
+    #[error("key quota exceeded, retry after {0:?}")]
+    QuotaExceeded(Duration),
+    #[error("OPRF key busy, it already runs {0} concurrent sessions")]
+    KeyBusy(usize),
+    #[error("resumed session {0} sent a different challenge")]
+    ChallengeMismatch(Uuid),
+    #[error("session {0} was pending during the restart of the node")]
+    SessionLost(Uuid),
+    #[error(transparent)]
+    Batch(#[from] BatchError),
     #[error(transparent)]
     SecretManager(#[from] Arc<SecretManagerError>),
+    #[error("session store: {0:?}")]
+    SessionStore(#[from] eyre::Report),
+}
+
+/// Errors specific to batch sessions, see [`crate::api::oprf`].
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum BatchError {
+    #[error("request carries {num_queries} queries but at most {max_batch_size} are accepted")]
+    TooLarge {
+        num_queries: usize,
+        max_batch_size: usize,
+    },
+    #[error("expected {expected} challenges for the batch but got {got}")]
+    SizeMismatch { expected: usize, got: usize },
+}
+
+impl From<OprfSessionStoreError> for Error {
+    fn from(value: OprfSessionStoreError) -> Self {
+        match value {
+            OprfSessionStoreError::SessionReuse(session_id) => Self::SessionReuse(session_id),
+            OprfSessionStoreError::Internal(report) => Self::SessionStore(report),
+        }
+    }
 }
 
 impl Error {
     /// Transforms the error into a [`CloseFrame`](https://docs.rs/axum/latest/axum/extract/ws/struct.CloseFrame.html) if necessary.
-    pub(crate) fn into_close_frame(self) -> Option<CloseFrame> {
+    ///
+    /// With [`CloseFrameVerbosity::Detailed`] the reason is the full error (truncated to [`CLOSE_FRAME_MAX_LENGTH`] bytes), with [`CloseFrameVerbosity::Redacted`] the full error without the content of messages that cannot be deserialized (see [`Error::redacted`]), otherwise the fixed message of the error. The code is the same for all.
+    pub(crate) fn into_close_frame(self, verbosity: CloseFrameVerbosity) -> Option<CloseFrame> {
+        // the retry-after of a throttled request or exceeded quota and the size limits are meant for the client
+        let details = (verbosity != CloseFrameVerbosity::Generic
+            && !matches!(
+                self,
+                Error::RiskThrottled(_)
+                    | Error::QuotaExceeded(_)
+                    | Error::Batch(BatchError::TooLarge { .. })
+            )
+            && !self.is_message_too_large())
+        .then(|| {
+            if verbosity == CloseFrameVerbosity::Redacted {
+                self.redacted()
+            } else {
+                self.to_string()
+            }
+        });
+        let mut close_frame = self.into_generic_close_frame()?;
+        if let Some(details) = details {
+            close_frame.reason = truncated_reason(details);
+        }
+        Some(close_frame)
+    }
+
+    /// The full error, except for messages that cannot be deserialized.
+    ///
+    /// Errors of `serde_json` and `ciborium` echo fragments of the message (e.g., `invalid type: string "..."`), which may contain secrets of the authentication. For those only the kind and position of the error are kept.
+    fn redacted(&self) -> String {
+        match self {
+            Error::Json(err) => {
+                let kind = match err.classify() {
+                    serde_json::error::Category::Io => "cannot read message",
+                    serde_json::error::Category::Syntax => "syntax error",
+                    serde_json::error::Category::Data => "unexpected data",
+                    serde_json::error::Category::Eof => "unexpected end of message",
+                };
+                format!(
+                    "invalid json: {kind} at line {} column {}",
+                    err.line(),
+                    err.column()
+                )
+            }
+            Error::Cbor(err) => match err {
+                ciborium::de::Error::Io(_) => "invalid cbor: cannot read message".to_owned(),
+                ciborium::de::Error::Syntax(offset) => {
+                    format!("invalid cbor: syntax error at offset {offset}")
+                }
+                ciborium::de::Error::Semantic(Some(offset), _) => {
+                    format!("invalid cbor: unexpected data at offset {offset}")
+                }
+                ciborium::de::Error::Semantic(None, _) => {
+                    "invalid cbor: unexpected data".to_owned()
+                }
+                ciborium::de::Error::RecursionLimitExceeded => {
+                    "invalid cbor: recursion limit exceeded".to_owned()
+                }
+            },
+            err => err.to_string(),
+        }
+    }
+
+    fn is_message_too_large(&self) -> bool {
+        match self {
+            Error::Axum(err) => matches!(
+                err.source()
+                    .and_then(|err| err.downcast_ref::<tungstenite::Error>()),
+                Some(tungstenite::Error::Capacity(_))
+            ),
+            #[cfg(feature = "grpc")]
+            Error::Grpc(status) => status.code() == tonic::Code::ResourceExhausted,
+            _ => false,
+        }
+    }
+
+    /// Transforms the error into a [`CloseFrame`](https://docs.rs/axum/latest/axum/extract/ws/struct.CloseFrame.html) with the fixed message of the error if necessary.
+    fn into_generic_close_frame(self) -> Option<CloseFrame> {
         // Prepare the error log line as we need to consume self.
         let maybe_log_line = format!("{self}");
         let close_frame = match self {
@@ -57,6 +185,8 @@ impl Error {
             // * handle secret-manager error log in the dedicated method
             // * handle auth error log in downstream crate
             Error::Axum(axum_error) => return handle_axum_error(axum_error),
+            #[cfg(feature = "grpc")]
+            Error::Grpc(status) => return handle_grpc_error(&status),
             Error::Auth(err) => {
                 return Some(CloseFrame {
                     code: err.code(),
@@ -66,6 +196,13 @@ impl Error {
             Error::SecretManager(ref secret_manager_error) => {
                 return Some(handle_secret_manager_error(secret_manager_error));
             }
+            Error::SessionStore(report) => {
+                tracing::error!(err=?report, "session store error");
+                return Some(CloseFrame {
+                    code: close_code::ERROR,
+                    reason: to_close_frame_bytes!(oprf_error_messages::UNEXPECTED_ERROR),
+                });
+            }
             // For all other errors, we print it before returning the CloseFrame.
             Error::ConnectionClosed => {
                 // nothing to do here
@@ -74,46 +211,69 @@ impl Error {
             }
             Error::BlindedQueryIsIdentity => Some(CloseFrame {
                 code: oprf_error_codes::BLINDED_QUERY_IS_IDENTITY,
-                reason: to_close_frame_bytes!("blinded query must not be identity"),
+                reason: to_close_frame_bytes!(oprf_error_messages::BLINDED_QUERY_IS_IDENTITY),
+            }),
+            Error::AuthTimeout(_) => Some(CloseFrame {
+                code: oprf_error_codes::AUTH_TIMEOUT,
+                reason: to_close_frame_bytes!(oprf_error_messages::AUTH_TIMEOUT),
+            }),
+            Error::RiskDenied => Some(CloseFrame {
+                code: oprf_error_codes::RISK_DENIED,
+                reason: to_close_frame_bytes!(oprf_error_messages::RISK_DENIED),
+            }),
+            ref err @ (Error::RiskThrottled(_) | Error::QuotaExceeded(_) | Error::KeyBusy(_)) => {
+                Some(handle_load_shedding_error(err))
+            }
+            Error::StaleQuery(_) => Some(CloseFrame {
+                code: oprf_error_codes::STALE_QUERY,
+                reason: to_close_frame_bytes!(oprf_error_messages::STALE_QUERY),
             }),
             Error::SessionReuse(_) => Some(CloseFrame {
                 code: oprf_error_codes::SESSION_REUSE,
-                reason: to_close_frame_bytes!("session already in use"),
+                reason: to_close_frame_bytes!(oprf_error_messages::SESSION_REUSE),
             }),
             Error::UnexpectedMessage => Some(CloseFrame {
                 code: close_code::UNSUPPORTED,
-                reason: to_close_frame_bytes!("unexpected ws message"),
+                reason: to_close_frame_bytes!(oprf_error_messages::UNEXPECTED_MESSAGE),
             }),
             Error::Json(_) => Some(CloseFrame {
                 code: oprf_error_codes::CORRUPTED_MESSAGE,
-                reason: to_close_frame_bytes!("invalid json"),
+                reason: to_close_frame_bytes!(oprf_error_messages::INVALID_JSON),
             }),
             Error::Cbor(_) => Some(CloseFrame {
                 code: oprf_error_codes::CORRUPTED_MESSAGE,
-                reason: to_close_frame_bytes!("invalid cbor"),
+                reason: to_close_frame_bytes!(oprf_error_messages::INVALID_CBOR),
             }),
-            Error::ThresholdContributingPartiesMissmatch {
-                threshold: _,
-                num_coeffs: _,
-            } => Some(CloseFrame {
+            Error::NonCanonicalCbor => Some(CloseFrame {
+                code: oprf_error_codes::CORRUPTED_MESSAGE,
+                reason: to_close_frame_bytes!(oprf_error_messages::NON_CANONICAL_CBOR),
+            }),
+            Error::ThresholdContributingPartiesMissmatch { .. } => Some(CloseFrame {
                 code: oprf_error_codes::COEFFICIENTS_DOES_NOT_EQUAL_THRESHOLD,
-                reason: to_close_frame_bytes!("not exactly threshold many contributions"),
+                reason: to_close_frame_bytes!(
+                    oprf_error_messages::COEFFICIENTS_DOES_NOT_EQUAL_THRESHOLD
+                ),
             }),
             Error::ContributionsNotSorted => Some(CloseFrame {
                 code: oprf_error_codes::UNSORTED_CONTRIBUTING_PARTIES,
-                reason: to_close_frame_bytes!("contributing parties are not sorted"),
+                reason: to_close_frame_bytes!(oprf_error_messages::UNSORTED_CONTRIBUTING_PARTIES),
             }),
             Error::DuplicateCoefficients => Some(CloseFrame {
                 code: oprf_error_codes::DUPLICATE_COEFFICIENT,
-                reason: to_close_frame_bytes!(
-                    "contributing parties contains duplicate coefficients"
-                ),
+                reason: to_close_frame_bytes!(oprf_error_messages::DUPLICATE_COEFFICIENT),
+            }),
+            Error::ChallengeMismatch(_) => Some(CloseFrame {
+                code: oprf_error_codes::CHALLENGE_MISMATCH,
+                reason: to_close_frame_bytes!(oprf_error_messages::CHALLENGE_MISMATCH),
+            }),
+            Error::SessionLost(_) => Some(CloseFrame {
+                code: oprf_error_codes::SESSION_LOST,
+                reason: to_close_frame_bytes!(oprf_error_messages::SESSION_LOST),
             }),
+            Error::Batch(ref batch_error) => Some(handle_batch_error(batch_error)),
             Error::MissingMyCoefficient => Some(CloseFrame {
                 code: oprf_error_codes::MISSING_MY_COEFFICIENT,
-                reason: to_close_frame_bytes!(
-                    "contributing parties does not contain my coefficient"
-                ),
+                reason: to_close_frame_bytes!(oprf_error_messages::MISSING_MY_COEFFICIENT),
             }),
         };
         tracing::warn!(user_error = true, "{maybe_log_line}");
@@ -121,32 +281,92 @@ impl Error {
     }
 }
 
+/// Truncates the reason to [`CLOSE_FRAME_MAX_LENGTH`] bytes at a char boundary.
+fn truncated_reason(mut reason: String) -> Utf8Bytes {
+    reason.truncate(reason.floor_char_boundary(CLOSE_FRAME_MAX_LENGTH));
+    Utf8Bytes::from(reason)
+}
+
+/// The close frame with `code` that tells the client to retry after `retry_after`.
+fn retry_after_close_frame(code: u16, retry_after: Duration) -> CloseFrame {
+    CloseFrame {
+        code,
+        reason: Utf8Bytes::from(format!("retry after {}s", retry_after.as_secs_f64().ceil())),
+    }
+}
+
+/// The close frames of requests the node sheds to protect itself, the client may retry them later.
+fn handle_load_shedding_error(err: &Error) -> CloseFrame {
+    match err {
+        Error::RiskThrottled(retry_after) => {
+            retry_after_close_frame(oprf_error_codes::RISK_THROTTLED, *retry_after)
+        }
+        Error::QuotaExceeded(retry_after) => {
+            retry_after_close_frame(oprf_error_codes::QUOTA_EXCEEDED, *retry_after)
+        }
+        Error::KeyBusy(_) => CloseFrame {
+            code: oprf_error_codes::KEY_BUSY,
+            reason: to_close_frame_bytes!(oprf_error_messages::KEY_BUSY),
+        },
+        _ => unreachable!("{err} is not a load shedding error"),
+    }
+}
+
+fn handle_batch_error(err: &BatchError) -> CloseFrame {
+    match err {
+        BatchError::TooLarge { max_batch_size, .. } => CloseFrame {
+            code: oprf_error_codes::BATCH_TOO_LARGE,
+            reason: Utf8Bytes::from(format!(
+                "batch exceeds max batch size of {max_batch_size} queries"
+            )),
+        },
+        BatchError::SizeMismatch { .. } => CloseFrame {
+            code: oprf_error_codes::CORRUPTED_MESSAGE,
+            reason: to_close_frame_bytes!(oprf_error_messages::BATCH_SIZE_MISMATCH),
+        },
+    }
+}
+
 fn handle_secret_manager_error(err: &SecretManagerError) -> CloseFrame {
     match err {
         SecretManagerError::UnknownOprfKeyId(oprf_key_id) => {
             tracing::warn!(user_error=true, %err, "unknown OPRF key {oprf_key_id}");
             CloseFrame {
                 code: oprf_error_codes::UNKNOWN_OPRF_KEY_ID,
-                reason: to_close_frame_bytes!("unknown OPRF key id"),
+                reason: to_close_frame_bytes!(oprf_error_messages::UNKNOWN_OPRF_KEY_ID),
             }
         }
         SecretManagerError::DeletedOprfKeyId(oprf_key_id) => {
             tracing::warn!(user_error=true, %err, "requested deleted OPRF key {oprf_key_id}");
             CloseFrame {
                 code: oprf_error_codes::DELETED_OPRF_KEY_ID,
-                reason: to_close_frame_bytes!("OPRF key already deleted"),
+                reason: to_close_frame_bytes!(oprf_error_messages::DELETED_OPRF_KEY_ID),
             }
         }
         SecretManagerError::Internal(report) => {
             tracing::error!(err=?report,"internal error");
             CloseFrame {
                 code: close_code::ERROR,
-                reason: to_close_frame_bytes!("unexpected error"),
+                reason: to_close_frame_bytes!(oprf_error_messages::UNEXPECTED_ERROR),
             }
         }
     }
 }
 
+/// The gRPC counterpart of [`handle_axum_error`]: messages that exceed the limit are reported, everything else means the client went away.
+#[cfg(feature = "grpc")]
+fn handle_grpc_error(status: &tonic::Status) -> Option<CloseFrame> {
+    if status.code() == tonic::Code::ResourceExhausted {
+        tracing::warn!(user_error=true, %status, "grpc message too large");
+        return Some(CloseFrame {
+            code: close_code::SIZE,
+            reason: truncated_reason(status.message().to_owned()),
+        });
+    }
+    tracing::trace!(%status, "nothing to do client closed stream");
+    None
+}
+
 fn handle_axum_error(err: axum::Error) -> Option<CloseFrame> {
     let inner = err.into_inner();
     if let Some(err) = inner.downcast_ref::<tungstenite::Error>() {
@@ -159,11 +379,20 @@ fn handle_axum_error(err: axum::Error) -> Option<CloseFrame> {
                 tracing::trace!("nothing to do client closed session");
                 return None;
             }
+            tungstenite::Error::Capacity(CapacityError::MessageTooLong { max_size, .. }) => {
+                tracing::warn!(user_error=true, %err, "websocket message too large");
+                return Some(CloseFrame {
+                    code: close_code::SIZE,
+                    reason: Utf8Bytes::from(format!(
+                        "message exceeds max message size of {max_size} bytes"
+                    )),
+                });
+            }
             tungstenite::Error::Capacity(_) => {
                 tracing::warn!(user_error=true, %err, "websocket message too large");
                 return Some(CloseFrame {
                     code: close_code::SIZE,
-                    reason: to_close_frame_bytes!("size exceeds max frame length"),
+                    reason: to_close_frame_bytes!(oprf_error_messages::FRAME_TOO_LARGE),
                 });
             }
             _ => {}
@@ -173,6 +402,6 @@ fn handle_axum_error(err: axum::Error) -> Option<CloseFrame> {
     tracing::error!(err = %inner, "unknown axum error");
     Some(CloseFrame {
         code: close_code::ERROR,
-        reason: to_close_frame_bytes!("unexpected error"),
+        reason: to_close_frame_bytes!(oprf_error_messages::UNEXPECTED_ERROR),
     })
 }
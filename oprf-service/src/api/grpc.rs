@@ -0,0 +1,223 @@
+//! gRPC transport of the OPRF modules (requires the `grpc` feature).
+//!
+//! If `grpc` is enabled in the [`crate::config::OprfNodeServiceConfig`], every OPRF module additionally serves the bidirectional-streaming RPC `taceo.oprf.v1.OprfNode/Oprf` below its path, e.g., `/api/{module}/taceo.oprf.v1.OprfNode/Oprf`. The RPC runs the same session flow as the web-socket endpoint (see [`crate::api::oprf`]) and shares the key-material store, session store and caches of the module, so a session can be resumed over either transport.
+//!
+//! ```protobuf
+//! syntax = "proto3";
+//! package taceo.oprf.v1;
+//!
+//! service OprfNode {
+//!   rpc Oprf(stream OprfFrame) returns (stream OprfFrame);
+//! }
+//!
+//! message OprfFrame {
+//!   bytes cbor = 1;
+//! }
+//! ```
+//!
+//! Every frame carries one CBOR encoded message, in the same order as the `Binary` frames of the web-socket: the client sends the [`oprf_types::api::OprfRequest`], the node answers with the [`oprf_types::api::OprfResponse`], the client sends the challenge and the node answers with the proof share. Frames larger than `ws_max_message_size` are rejected.
+//!
+//! Clients announce their protocol version in the [`oprf_types::api::OPRF_PROTOCOL_VERSION_HEADER`] metadata. A successful session ends with status `OK`. A failed session ends with an error status whose message is the reason of the close frame the web-socket would send, and whose [`OPRF_ERROR_CODE_METADATA`] carries the close code (see [`oprf_types::api::oprf_error_codes`]).
+//!
+//! gRPC requires HTTP/2, so the host must serve the router with HTTP/2 enabled (e.g., `axum::serve` with the `http2` feature of `axum`).
+
+use axum::{
+    Router,
+    extract::{Request, State, ws::close_code},
+    response::{IntoResponse as _, Response},
+    routing::post,
+};
+use futures::{SinkExt as _, channel::mpsc};
+use oprf_types::api::{OPRF_PROTOCOL_VERSION_HEADER, oprf_error_codes};
+use serde::{Deserialize, Serialize};
+use tonic::{
+    Code, Status, Streaming,
+    metadata::{MetadataMap, MetadataValue},
+    server::{Grpc, StreamingService},
+};
+use tonic_prost::ProstCodec;
+use tracing::Instrument as _;
+
+use crate::{
+    api::{
+        errors::Error,
+        oprf::{HumanReadable, OprfModuleState, SessionTransport, decode_cbor, run_session},
+    },
+    config::CborEncoding,
+    metrics,
+};
+
+/// The path of the `Oprf` RPC, relative to the path of the module.
+pub(crate) const OPRF_PATH: &str = "/taceo.oprf.v1.OprfNode/Oprf";
+
+/// The metadata key of a failed session's status that carries the close code.
+pub(crate) const OPRF_ERROR_CODE_METADATA: &str = "x-taceo-oprf-error-code";
+
+/// A message of the `Oprf` RPC, see the [module docs](self).
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct OprfFrame {
+    /// The CBOR encoded message
+    #[prost(bytes = "vec", tag = "1")]
+    pub(crate) cbor: Vec<u8>,
+}
+
+/// Creates a `Router` with the `Oprf` RPC of a module.
+pub(crate) fn routes<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    state: OprfModuleState<ReqAuth>,
+) -> Router {
+    Router::new()
+        .route(OPRF_PATH, post(oprf_grpc_handler::<ReqAuth>))
+        .with_state(state)
+}
+
+/// Decodes the gRPC request and starts the session, see [`OprfGrpcService`].
+async fn oprf_grpc_handler<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    State(state): State<OprfModuleState<ReqAuth>>,
+    request: Request,
+) -> Response {
+    let state = state.with_current_limits();
+    let max_message_size = Some(state.max_message_size);
+    let mut grpc = Grpc::new(ProstCodec::<OprfFrame, OprfFrame>::default())
+        .apply_max_message_size_config(max_message_size, max_message_size);
+    grpc.streaming(OprfGrpcService(state), request)
+        .await
+        .into_response()
+}
+
+/// Handles a single `Oprf` call.
+struct OprfGrpcService<ReqAuth>(OprfModuleState<ReqAuth>);
+
+impl<ReqAuth: for<'de> Deserialize<'de> + Send + 'static> StreamingService<OprfFrame>
+    for OprfGrpcService<ReqAuth>
+{
+    type Response = OprfFrame;
+    type ResponseStream = mpsc::Receiver<Result<OprfFrame, Status>>;
+    type Future = std::future::Ready<Result<tonic::Response<Self::ResponseStream>, Status>>;
+
+    /// Checks the client version and spawns the session, which sends its messages to the returned stream.
+    fn call(&mut self, request: tonic::Request<Streaming<OprfFrame>>) -> Self::Future {
+        let state = &self.0;
+        if state.shutdown.is_draining() {
+            return std::future::ready(Err(Status::unavailable("node is shutting down")));
+        }
+        let client_version = request
+            .metadata()
+            .get(OPRF_PROTOCOL_VERSION_HEADER.as_str())
+            .and_then(|version| version.to_str().ok())
+            .and_then(|version| semver::Version::parse(version).ok());
+        let Some(client_version) = client_version else {
+            tracing::warn!(user_error = true, "missing client version");
+            return std::future::ready(Err(Status::invalid_argument("missing client version")));
+        };
+        let parent_span = tracing::Span::current();
+        parent_span.record("client_version", client_version.to_string());
+        if !state.version_req.matches(&client_version) {
+            let msg = format!(
+                "invalid version, expected: {} got: {client_version}",
+                state.version_req
+            );
+            tracing::warn!(user_error = true, "{msg}");
+            metrics::request::inc_client_version_mismatch();
+            return std::future::ready(Err(Status::invalid_argument(msg)));
+        }
+
+        let (outbound, response_stream) = mpsc::channel(1);
+        let transport = GrpcTransport {
+            inbound: request.into_inner(),
+            outbound,
+            cbor_encoding: state.cbor_encoding,
+        };
+        tokio::spawn(
+            state
+                .shutdown
+                .track(partial_oprf(transport, state.clone()).instrument(parent_span)),
+        );
+        std::future::ready(Ok(tonic::Response::new(response_stream)))
+    }
+}
+
+/// Runs the session and ends the response stream with the status of the session.
+async fn partial_oprf<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    mut transport: GrpcTransport,
+    state: OprfModuleState<ReqAuth>,
+) {
+    let mut transcript = None;
+    let auth = state.connection_auth();
+    let close_frame = run_session(&mut transport, &state, &auth, &mut transcript).await;
+    if let Some(status) = close_frame
+        .filter(|close_frame| close_frame.code != close_code::NORMAL)
+        .map(|close_frame| into_status(close_frame.code, close_frame.reason.as_str()))
+        && tokio::time::timeout(
+            state.websocket_shutdown_timeout,
+            transport.outbound.send(Err(status)),
+        )
+        .await
+        .map_or(true, |sent| sent.is_err())
+    {
+        tracing::trace!("could not send status - client went away");
+    }
+    // dropping the sender ends the response stream
+
+    if let (Some(transcript_writer), Some(transcript)) = (&state.transcript_writer, transcript) {
+        transcript_writer.write(&transcript).await;
+    }
+}
+
+/// The status of a session that failed with the close frame `code` and `reason`.
+fn into_status(code: u16, reason: &str) -> Status {
+    let grpc_code = match code {
+        close_code::SIZE | oprf_error_codes::QUOTA_EXCEEDED => Code::ResourceExhausted,
+        oprf_error_codes::TIMEOUT | oprf_error_codes::AUTH_TIMEOUT => Code::DeadlineExceeded,
+        oprf_error_codes::RISK_THROTTLED | oprf_error_codes::KEY_BUSY => Code::Unavailable,
+        close_code::ERROR => Code::Internal,
+        _ => Code::Aborted,
+    };
+    let mut metadata = MetadataMap::new();
+    metadata.insert(OPRF_ERROR_CODE_METADATA, MetadataValue::from(code));
+    Status::with_metadata(grpc_code, reason, metadata)
+}
+
+/// The [`SessionTransport`] of a single `Oprf` call.
+struct GrpcTransport {
+    inbound: Streaming<OprfFrame>,
+    outbound: mpsc::Sender<Result<OprfFrame, Status>>,
+    cbor_encoding: CborEncoding,
+}
+
+impl SessionTransport for GrpcTransport {
+    /// Reads the next frame of the client and deserializes its `cbor`.
+    #[tracing::instrument(level = "info", skip_all)]
+    async fn read_request<Msg: for<'de> Deserialize<'de>>(
+        &mut self,
+    ) -> Result<(Msg, HumanReadable), Error> {
+        tracing::trace!("read request..");
+        let frame = self
+            .inbound
+            .message()
+            .await?
+            .ok_or(Error::ConnectionClosed)?;
+        Ok((
+            decode_cbor(&frame.cbor, self.cbor_encoding)?,
+            HumanReadable::No,
+        ))
+    }
+
+    /// Sends the CBOR encoded `Msg` to the client. gRPC frames are never human-readable, so `human_readable` is ignored.
+    #[tracing::instrument(level = "info", skip_all)]
+    async fn write_response<Msg: Serialize + Send>(
+        &mut self,
+        response: Msg,
+        _human_readable: HumanReadable,
+    ) -> Result<(), Error> {
+        tracing::trace!("write response..");
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&response, &mut cbor).expect("Can serialize response");
+        self.outbound
+            .send(Ok(OprfFrame { cbor }))
+            .await
+            .map_err(|_| Error::ConnectionClosed)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use ark_ec::AffineRepr as _;
+use axum_test::TestServerBuilder;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{OprfRequest, OprfResponse, oprf_error_codes},
+};
+use uuid::Uuid;
+
+use crate::{
+    test_kit::MockAuthenticator,
+    test_utils::{MockSecretManager, builder_with_secret_manager, challenge, default_config},
+};
+
+/// Encodes `msg` as frame of the gRPC transport.
+#[cfg(feature = "grpc")]
+fn grpc_frame(msg: &impl serde::Serialize) -> crate::api::grpc::OprfFrame {
+    let mut cbor = Vec::new();
+    ciborium::into_writer(msg, &mut cbor).expect("Can serialize");
+    crate::api::grpc::OprfFrame { cbor }
+}
+
+/// Receives and decodes the next frame of the gRPC transport.
+#[cfg(feature = "grpc")]
+async fn grpc_receive<Msg: for<'de> serde::Deserialize<'de>>(
+    responses: &mut tonic::Streaming<crate::api::grpc::OprfFrame>,
+) -> Msg {
+    let frame = responses
+        .message()
+        .await
+        .expect("no error")
+        .expect("should send a frame");
+    ciborium::from_reader(frame.cbor.as_slice()).expect("Can deserialize")
+}
+
+/// Starts an `Oprf` call on the gRPC transport of the module `/test`, announcing `version`.
+#[cfg(feature = "grpc")]
+async fn grpc_call(
+    router: &axum::Router,
+    version: Option<&'static str>,
+) -> Result<
+    (
+        futures::channel::mpsc::Sender<crate::api::grpc::OprfFrame>,
+        tonic::Streaming<crate::api::grpc::OprfFrame>,
+    ),
+    tonic::Status,
+> {
+    use crate::api::grpc::{OPRF_PATH, OprfFrame};
+
+    let (frames, stream) = futures::channel::mpsc::channel::<OprfFrame>(2);
+    let mut request = tonic::Request::new(stream);
+    if let Some(version) = version {
+        request.metadata_mut().insert(
+            oprf_types::api::OPRF_PROTOCOL_VERSION_HEADER.as_str(),
+            version.parse().expect("valid metadata"),
+        );
+    }
+    let path = format!("/api/test{OPRF_PATH}").parse().expect("valid path");
+    let mut client = tonic::client::Grpc::new(router.clone());
+    client.ready().await.expect("client is ready");
+    let responses = client
+        .streaming(
+            request,
+            path,
+            tonic_prost::ProstCodec::<OprfFrame, OprfFrame>::default(),
+        )
+        .await?;
+    Ok((frames, responses.into_inner()))
+}
+
+#[cfg(feature = "grpc")]
+#[tokio::test]
+async fn grpc_session_shares_state_with_websocket() {
+    use futures::SinkExt as _;
+    use oprf_core::ddlog_equality::shamir::DLogProofShareShamir;
+
+    use crate::api::grpc::OPRF_ERROR_CODE_METADATA;
+
+    let mut config = default_config();
+    config.grpc = true;
+    let router = builder_with_secret_manager(
+        config,
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let status = grpc_call(&router, None)
+        .await
+        .expect_err("should reject calls without version");
+    assert_eq!(
+        status.code(),
+        tonic::Code::InvalidArgument,
+        "should reject the missing version"
+    );
+
+    let request = OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+    let (mut frames, mut responses) = grpc_call(&router, Some("1.0.0"))
+        .await
+        .expect("Can start session");
+    frames.send(grpc_frame(&request)).await.expect("Can send");
+    let response: OprfResponse = grpc_receive(&mut responses).await;
+    frames
+        .send(grpc_frame(&challenge(1)))
+        .await
+        .expect("Can send");
+    let proof_share: DLogProofShareShamir = grpc_receive(&mut responses).await;
+    assert!(
+        responses
+            .message()
+            .await
+            .expect("should end with OK")
+            .is_none(),
+        "should end the stream after the proof share"
+    );
+
+    // the web-socket resumes the session, as both transports share the caches of the module
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router.clone())
+        .expect("Can build test-server");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&request).await;
+    assert_eq!(
+        ws.receive_json::<serde_json::Value>().await,
+        serde_json::to_value(&response).expect("Can serialize"),
+        "should send the same commitments"
+    );
+    ws.send_json(&challenge(1)).await;
+    assert_eq!(
+        ws.receive_json::<serde_json::Value>().await,
+        serde_json::to_value(&proof_share).expect("Can serialize"),
+        "should send the same proof share"
+    );
+
+    let (mut frames, mut responses) = grpc_call(&router, Some("1.0.0"))
+        .await
+        .expect("Can start session");
+    frames.send(grpc_frame(&request)).await.expect("Can send");
+    let _: OprfResponse = grpc_receive(&mut responses).await;
+    frames
+        .send(grpc_frame(&challenge(6)))
+        .await
+        .expect("Can send");
+    let status = responses
+        .message()
+        .await
+        .expect_err("should reject a different challenge");
+    assert_eq!(
+        status.metadata().get(OPRF_ERROR_CODE_METADATA),
+        Some(&oprf_error_codes::CHALLENGE_MISMATCH.into()),
+        "should carry the close code"
+    );
+}
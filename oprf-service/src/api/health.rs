@@ -0,0 +1,67 @@
+//! Health Details Endpoint
+//!
+//! Exposes the following API endpoint:
+//!
+//! - `/health/details` – returns whether all [`StartedServices`] started (like `/health`) together with the state, last error, last success and last processed block of every registered subsystem (see [`crate::subsystem_health`]).
+//!
+//! The endpoint includes a `Cache-Control: no-cache` header to prevent caching of responses.
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderValue, StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
+use nodes_common::StartedServices;
+use serde::Serialize;
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::services::subsystem_health::{SubsystemRegistry, SubsystemStatus};
+
+#[derive(Clone)]
+struct HealthState {
+    started_services: StartedServices,
+    subsystems: SubsystemRegistry,
+}
+
+/// Body of `/health/details`.
+#[derive(Debug, Serialize)]
+struct HealthDetails {
+    /// `"healthy"` if all started services started, `"starting"` otherwise, the same as the body of `/health`.
+    status: &'static str,
+    /// Status of every registered subsystem, in registration order.
+    subsystems: Vec<SubsystemStatus>,
+}
+
+/// Create a router containing the health details endpoint.
+pub(crate) fn routes(started_services: StartedServices, subsystems: SubsystemRegistry) -> Router {
+    Router::new()
+        .route("/health/details", get(health_details))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("no-cache"),
+        ))
+        .with_state(HealthState {
+            started_services,
+            subsystems,
+        })
+}
+
+/// Responds with the [`HealthDetails`] of the node.
+///
+/// Returns `200 OK` with a JSON response if all services already started.
+/// Returns `503 Service Unavailable` with a JSON response if one of the services did not start yet.
+async fn health_details(State(health_state): State<HealthState>) -> impl IntoResponse {
+    let (status_code, status) = if health_state.started_services.all_started() {
+        (StatusCode::OK, "healthy")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "starting")
+    };
+    (
+        status_code,
+        Json(HealthDetails {
+            status,
+            subsystems: health_state.subsystems.statuses(),
+        }),
+    )
+}
@@ -0,0 +1,53 @@
+#[tokio::test]
+async fn health_details_reports_subsystems() {
+    let builder = builder_with_secret_manager(
+        default_config(),
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    );
+    let watcher = builder.subsystem("event_watcher");
+    let server = TestServerBuilder::new()
+        .build(
+            builder
+                .module("/test", MockAuthenticator::allow_all().into_service())
+                .build()
+                .expect("Can build"),
+        )
+        .expect("Can build test-server");
+
+    let details = server
+        .get("/health/details")
+        .await
+        .json::<serde_json::Value>();
+    assert_eq!(details["status"], "healthy", "no service is starting");
+    assert_eq!(details["subsystems"][0]["name"], "secret_manager");
+    assert_eq!(
+        details["subsystems"][0]["state"], "starting",
+        "no key loaded"
+    );
+    assert_eq!(details["subsystems"][1]["name"], "event_watcher");
+
+    server
+        .get(&format!("/oprf_pub/{}", OprfKeyId::from(42usize)))
+        .await
+        .assert_status_ok();
+    watcher.succeeded_at_block(7);
+    watcher.failed("rpc unavailable");
+    let details = server
+        .get("/health/details")
+        .await
+        .json::<serde_json::Value>();
+    assert_eq!(
+        details["subsystems"][0]["state"], "healthy",
+        "key was loaded: {details}"
+    );
+    assert!(
+        details["subsystems"][0]["last_success"].is_u64(),
+        "should record last success: {details}"
+    );
+    assert_eq!(details["subsystems"][1]["state"], "degraded");
+    assert_eq!(details["subsystems"][1]["last_error"], "rpc unavailable");
+    assert_eq!(
+        details["subsystems"][1]["last_block"], 7,
+        "should keep last block after failure"
+    );
+}
@@ -2,38 +2,73 @@
 //!
 //! Exposes the following API endpoints:
 //!
+//! - `/info` – returns the [`NodeInfo`], including the message [`oprf_types::api::SchemaFingerprint`] of this build
 //! - `/wallet` – returns the wallet address
 //! - `/oprf_pub/{id}` – returns the [`oprf_types::crypto::OprfPublicKey`] associated with the [`OprfKeyId`] if the OPRF node has the information stored.
+//! - `/oprf_pub/{id}/history` – returns the [`oprf_types::api::OprfPublicKeyWithEpoch`] of every finalized epoch of the [`OprfKeyId`] recorded by this node, oldest first (only if enabled with `OprfServiceBuilder::public_key_history`, requires the `registry` feature).
+//! - `/oprf_keys` – returns the [`oprf_types::api::LoadedOprfKey`] of every key currently loaded by the OPRF node, sorted by [`OprfKeyId`]. The optional `prefix` query parameter keeps only keys whose hex-encoded id starts with the prefix (e.g. `/oprf_keys?prefix=0x2a`).
+//! - `/oprf_params/{id}` – returns the [`oprf_types::api::OprfKeyParams`] (public key, epoch, threshold and max web-socket message size) associated with the [`OprfKeyId`] if the OPRF node has the information stored.
 //!
 //! The endpoints include a `Cache-Control: no-cache` header to prevent caching of responses.
 use crate::secret_manager::SecretManagerError;
 use crate::services::oprf_key_material_store::OprfKeyMaterialStore;
 use axum::{
     Json, Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     routing::get,
 };
-use oprf_types::OprfKeyId;
+use oprf_types::{
+    OprfKeyId,
+    api::{NodeInfo, OprfKeyParams},
+};
+use semver::VersionReq;
+use serde::Deserialize;
+use std::{num::NonZeroU16, sync::Arc};
 
 #[derive(Clone)]
 struct InfoState {
     wallet_address: String,
+    threshold: NonZeroU16,
+    max_message_size: usize,
+    max_batch_size: usize,
+    node_info: NodeInfo,
     oprf_material_store: OprfKeyMaterialStore,
 }
 
 /// Create a router containing the info endpoints.
-pub(crate) fn routes(oprf_material_store: OprfKeyMaterialStore, wallet_address: String) -> Router {
+pub(crate) fn routes(
+    oprf_material_store: OprfKeyMaterialStore,
+    wallet_address: String,
+    threshold: NonZeroU16,
+    max_message_size: usize,
+    max_batch_size: usize,
+    version_req: &VersionReq,
+) -> Router {
     Router::new()
+        .route("/info", get(info))
         .route("/wallet", get(wallet))
         .route("/oprf_pub/{id}", get(oprf_key_available))
+        .route("/oprf_keys", get(oprf_keys))
+        .route("/oprf_params/{id}", get(oprf_key_params))
         .with_state(InfoState {
             wallet_address,
+            threshold,
+            max_message_size,
+            max_batch_size,
+            node_info: NodeInfo::new(version_req.to_string()),
             oprf_material_store,
         })
 }
 
+/// Responds with the [`NodeInfo`] of the oprf node
+///
+/// Returns `200 OK` with a JSON response.
+async fn info(State(info_state): State<InfoState>) -> impl IntoResponse {
+    (StatusCode::OK, Json(info_state.node_info))
+}
+
 /// Responds with the wallet address of the oprf node
 ///
 /// Returns `200 OK` with a string response.
@@ -55,14 +90,100 @@ async fn oprf_key_available(
         .await
     {
         Ok(public_material) => (StatusCode::OK, Json(public_material)).into_response(),
-        Err(err) => match err.as_ref() {
-            SecretManagerError::UnknownOprfKeyId(_) | SecretManagerError::DeletedOprfKeyId(_) => {
-                StatusCode::NOT_FOUND.into_response()
-            }
-            SecretManagerError::Internal(report) => {
-                tracing::error!(err=?report, "internal error");
-                StatusCode::INTERNAL_SERVER_ERROR.into_response()
-            }
-        },
+        Err(err) => secret_manager_error_response(&err),
+    }
+}
+
+/// The query parameters of `/oprf_keys`.
+#[derive(Deserialize)]
+struct OprfKeysQuery {
+    prefix: Option<String>,
+}
+
+/// Responds with the [`oprf_types::api::LoadedOprfKey`] of every key in the [`OprfKeyMaterialStore`], i.e., the keys currently loaded by the node. Keys that were not requested yet (or were evicted) are not listed.
+///
+/// With a `prefix`, responds only with the keys whose lower-case hex-encoded id starts with the prefix. The prefix is case-insensitive and the `0x` is optional.
+///
+/// Returns `200 OK` with a list of [`oprf_types::api::LoadedOprfKey`].
+async fn oprf_keys(
+    State(info_state): State<InfoState>,
+    Query(query): Query<OprfKeysQuery>,
+) -> impl IntoResponse {
+    let mut keys = info_state.oprf_material_store.loaded_keys();
+    if let Some(prefix) = query.prefix {
+        let prefix = prefix.to_lowercase();
+        let prefix = prefix.strip_prefix("0x").unwrap_or(&prefix);
+        keys.retain(|key| format!("{:x}", key.oprf_key_id.into_inner()).starts_with(prefix));
     }
+    (StatusCode::OK, Json(keys))
 }
+
+/// Responds with the [`oprf_types::api::OprfKeyParams`] of the [`OprfKeyId`], i.e., the public key, the latest epoch and the threshold of the key. Keys that were stored without their own threshold report the threshold of the node. The max message size and the max batch size are the `ws_max_message_size` and the `max_batch_size` of the node.
+///
+/// Returns `200 OK` with [`oprf_types::api::OprfKeyParams`].
+/// Returns `404 Not Found` if not registered.
+async fn oprf_key_params(
+    State(info_state): State<InfoState>,
+    Path(id): Path<OprfKeyId>,
+) -> impl IntoResponse {
+    match info_state
+        .oprf_material_store
+        .oprf_key_params(id, info_state.threshold)
+        .await
+    {
+        Ok(params) => (
+            StatusCode::OK,
+            Json(OprfKeyParams {
+                max_message_size: Some(info_state.max_message_size),
+                max_batch_size: Some(info_state.max_batch_size),
+                ..params
+            }),
+        )
+            .into_response(),
+        Err(err) => secret_manager_error_response(&err),
+    }
+}
+
+/// Create a router containing the public key history endpoint.
+#[cfg(feature = "registry")]
+pub(crate) fn history_routes(
+    secret_manager: crate::secret_manager::SecretManagerService,
+) -> Router {
+    Router::new()
+        .route("/oprf_pub/{id}/history", get(oprf_key_history))
+        .with_state(secret_manager)
+}
+
+/// Responds with the public keys of all finalized epochs of the [`OprfKeyId`] within the retention of the node, oldest epoch first.
+///
+/// Returns `200 OK` with a list of [`oprf_types::api::OprfPublicKeyWithEpoch`].
+/// Returns `404 Not Found` if no epoch was recorded.
+#[cfg(feature = "registry")]
+async fn oprf_key_history(
+    State(secret_manager): State<crate::secret_manager::SecretManagerService>,
+    Path(id): Path<OprfKeyId>,
+) -> axum::response::Response {
+    match secret_manager.load_public_key_history(id).await {
+        Ok(history) if history.is_empty() => StatusCode::NOT_FOUND.into_response(),
+        Ok(history) => (StatusCode::OK, Json(history)).into_response(),
+        Err(err) => {
+            tracing::error!(?err, "cannot load public key history");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+fn secret_manager_error_response(err: &Arc<SecretManagerError>) -> axum::response::Response {
+    match err.as_ref() {
+        SecretManagerError::UnknownOprfKeyId(_) | SecretManagerError::DeletedOprfKeyId(_) => {
+            StatusCode::NOT_FOUND.into_response()
+        }
+        SecretManagerError::Internal(report) => {
+            tracing::error!(err=?report, "internal error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
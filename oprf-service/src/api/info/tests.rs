@@ -0,0 +1,93 @@
+use axum_test::TestServerBuilder;
+use oprf_types::{
+    OprfKeyId,
+    api::{LoadedOprfKey, NodeInfo, OprfPublicKeyWithEpoch, SchemaFingerprint},
+};
+
+use crate::{
+    test_kit::{MockAuthenticator, StaticSecretManager, test_router},
+    test_utils::builder,
+};
+
+#[tokio::test]
+async fn info_exposes_schema_and_rejects_mismatch() {
+    let router = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+
+    let info = server.get("/info").await.json::<NodeInfo>();
+    assert_eq!(
+        info.schema_fingerprint,
+        SchemaFingerprint::CURRENT,
+        "should expose our schema fingerprint"
+    );
+
+    let response = server
+        .get_websocket("/api/test/oprf?version=1.0.0&schema=0000000000000000")
+        .await;
+    response.assert_status_bad_request();
+    assert!(
+        response.text().contains("incompatible message schema"),
+        "should reject explicitly, got {}",
+        response.text()
+    );
+}
+
+#[tokio::test]
+async fn oprf_keys_lists_loaded_keys() {
+    let mut rng = rand::thread_rng();
+    let secret_manager = StaticSecretManager::single_node()
+        .with_random_key(OprfKeyId::from(0x2a_usize), &mut rng)
+        .with_random_key(OprfKeyId::from(0x2b_usize), &mut rng)
+        .with_random_key(OprfKeyId::from(0x2c_usize), &mut rng);
+    let server = TestServerBuilder::new()
+        .build(test_router(
+            secret_manager,
+            "/test",
+            MockAuthenticator::allow_all().into_service(),
+        ))
+        .expect("Can build test-server");
+    assert!(
+        server
+            .get("/oprf_keys")
+            .await
+            .json::<Vec<LoadedOprfKey>>()
+            .is_empty(),
+        "no key is loaded yet"
+    );
+
+    // loads the keys
+    let mut public_keys = Vec::new();
+    for oprf_key_id in [0x2b_usize, 0x2a] {
+        public_keys.push(
+            server
+                .get(&format!("/oprf_pub/{}", OprfKeyId::from(oprf_key_id)))
+                .await
+                .json::<OprfPublicKeyWithEpoch>(),
+        );
+    }
+    let keys = server.get("/oprf_keys").await.json::<Vec<LoadedOprfKey>>();
+    assert_eq!(
+        keys.iter().map(|key| key.oprf_key_id).collect::<Vec<_>>(),
+        [OprfKeyId::from(0x2a_usize), OprfKeyId::from(0x2b_usize)],
+        "should list the loaded keys sorted by id"
+    );
+    assert_eq!(keys[0].key, public_keys[1].key);
+    assert_eq!(keys[1].epoch, public_keys[0].epoch);
+
+    let keys = server
+        .get("/oprf_keys")
+        .add_query_param("prefix", "0x2B")
+        .await
+        .json::<Vec<LoadedOprfKey>>();
+    assert_eq!(
+        keys.iter().map(|key| key.oprf_key_id).collect::<Vec<_>>(),
+        [OprfKeyId::from(0x2b_usize)],
+        "should filter by prefix"
+    );
+}
@@ -0,0 +1,116 @@
+//! Memory Endpoint (requires the `jemalloc` feature)
+//!
+//! Exposes the following API endpoint if enabled (see `OprfServiceBuilder::memory_stats`):
+//!
+//! - `/debug/memory` – returns the [`MemoryStats`] of the process: the resident set size, the statistics of the jemalloc allocator and the number of entries of the subsystems of the node.
+//!
+//! The allocator statistics are only meaningful if the host uses jemalloc as global allocator (e.g. `tikv_jemallocator::Jemalloc`), otherwise they are zero.
+use std::ffi::{CStr, c_void};
+
+use axum::{Json, Router, extract::State, response::IntoResponse, routing::get};
+use oprf_types::api::{AllocatorStats, MemoryStats, SubsystemStats};
+
+use crate::{
+    metrics,
+    services::{
+        challenge_replay::ChallengeReplayCache, oprf_key_material_store::OprfKeyMaterialStore,
+    },
+};
+
+#[derive(Clone)]
+struct MemoryState {
+    oprf_material_store: OprfKeyMaterialStore,
+    challenge_replay_cache: ChallengeReplayCache,
+}
+
+/// Create a router containing the memory endpoint.
+pub(crate) fn routes(
+    oprf_material_store: OprfKeyMaterialStore,
+    challenge_replay_cache: ChallengeReplayCache,
+) -> Router {
+    Router::new()
+        .route("/debug/memory", get(memory))
+        .with_state(MemoryState {
+            oprf_material_store,
+            challenge_replay_cache,
+        })
+}
+
+/// Responds with the current [`MemoryStats`].
+///
+/// Returns `200 OK` with the stats as JSON.
+async fn memory(State(state): State<MemoryState>) -> impl IntoResponse {
+    Json(MemoryStats {
+        rss_bytes: rss_bytes(),
+        allocator: allocator_stats(),
+        subsystems: SubsystemStats {
+            open_sessions: metrics::sessions::open(),
+            key_material_entries: state.oprf_material_store.entry_count(),
+            negative_cache_entries: state.oprf_material_store.negative_entry_count(),
+            replay_cache_entries: state.challenge_replay_cache.entry_count(),
+        },
+    })
+}
+
+/// Reads the `VmRSS` of this process from `/proc/self/status`, `None` if not available (e.g. not on Linux).
+fn rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kib = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+/// Refreshes and reads the jemalloc statistics. Values that cannot be read are reported as `0`.
+fn allocator_stats() -> AllocatorStats {
+    // the statistics are cached by jemalloc and only refreshed when the epoch is advanced
+    let mut epoch = 1u64;
+    let mut len = size_of::<u64>();
+    let epoch_ptr = (&raw mut epoch).cast::<c_void>();
+    // SAFETY: `epoch` is a `u64` as required by the `epoch` mallctl, and `len` is its size, both outlive the call.
+    let refreshed = unsafe {
+        tikv_jemalloc_sys::mallctl(c"epoch".as_ptr(), epoch_ptr, &raw mut len, epoch_ptr, len)
+    };
+    if refreshed != 0 {
+        tracing::debug!("cannot refresh jemalloc stats: {refreshed}");
+    }
+    AllocatorStats {
+        allocated_bytes: read_size(c"stats.allocated"),
+        active_bytes: read_size(c"stats.active"),
+        resident_bytes: read_size(c"stats.resident"),
+        mapped_bytes: read_size(c"stats.mapped"),
+        retained_bytes: read_size(c"stats.retained"),
+        metadata_bytes: read_size(c"stats.metadata"),
+        arenas: read::<u32>(c"arenas.narenas").unwrap_or_default(),
+    }
+}
+
+/// Reads a `size_t` statistic, `0` if it cannot be read.
+fn read_size(name: &CStr) -> u64 {
+    read::<usize>(name).map_or(0, |value| value as u64)
+}
+
+/// Reads the mallctl entry `name` of type `T`.
+fn read<T: Default>(name: &CStr) -> Option<T> {
+    let mut value = T::default();
+    let mut len = size_of::<T>();
+    // SAFETY: the caller picks `T` matching the type of the mallctl entry, `len` is its size, and nothing is written (null `newp`). `value` and `len` outlive the call.
+    let result = unsafe {
+        tikv_jemalloc_sys::mallctl(
+            name.as_ptr(),
+            (&raw mut value).cast::<c_void>(),
+            &raw mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (result == 0 && len == size_of::<T>()).then_some(value)
+}
+
+#[cfg(test)]
+mod tests;
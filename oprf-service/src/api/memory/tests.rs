@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use axum_test::TestServerBuilder;
+use oprf_types::OprfKeyId;
+
+use crate::{
+    test_kit::{MockAuthenticator, StaticSecretManager},
+    test_utils::{builder_with_secret_manager, default_config},
+};
+
+#[cfg(feature = "jemalloc")]
+#[tokio::test]
+async fn memory_stats_report_subsystems() {
+    use oprf_types::api::MemoryStats;
+
+    let oprf_key_id = OprfKeyId::from(42usize);
+    let secret_manager =
+        StaticSecretManager::single_node().with_random_key(oprf_key_id, &mut rand::thread_rng());
+    let router = builder_with_secret_manager(default_config(), Arc::new(secret_manager))
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .build(router)
+        .expect("Can build test-server");
+    server.get("/debug/memory").await.assert_status_not_found();
+
+    let router = builder_with_secret_manager(
+        default_config(),
+        Arc::new(
+            StaticSecretManager::single_node()
+                .with_random_key(oprf_key_id, &mut rand::thread_rng()),
+        ),
+    )
+    .memory_stats()
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .build(router)
+        .expect("Can build test-server");
+    server
+        .get(&format!("/oprf_pub/{oprf_key_id}"))
+        .await
+        .assert_status_ok();
+    let stats = server.get("/debug/memory").await.json::<MemoryStats>();
+    assert!(
+        stats.subsystems.key_material_entries <= 1,
+        "should count the loaded key at most once"
+    );
+    if cfg!(target_os = "linux") {
+        assert!(stats.rss_bytes.is_some(), "should report the RSS on linux");
+    }
+}
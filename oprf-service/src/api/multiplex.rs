@@ -0,0 +1,307 @@
+//! Multiplexed OPRF web-socket endpoint.
+//!
+//! Every OPRF module additionally serves `/oprf/multiplex` (unless `max_multiplexed_sessions` is `0`, see [`crate::config::OprfNodeServiceConfig`]). A connection to this endpoint carries many interleaved sessions, so high-throughput clients don't need a web-socket per session.
+//!
+//! Every frame is a [`MultiplexedFrame`] that names the session by its `request_id`. The first frame of an unknown `request_id` opens a session, which runs the same session flow as a single-session connection (see [`crate::api::oprf`]) and shares the key-material store, session store and caches of the module. The sessions of a connection also share its cached authentications (see [`oprf_types::api::OprfRequestAuthenticator::cacheable`]), so a client reusing its credentials is authenticated once per connection. Later frames of the same `request_id` are routed to its session. The node answers with [`MultiplexedNodeMessage`]s, encoded as the frames of the session (`json` in `Text`, `cbor` in `Binary` frames).
+//!
+//! Sessions are independent of each other:
+//!
+//! - Every session runs into its own `session_lifetime`.
+//! - A failed session is closed with a [`MultiplexedNodeMessage::Close`] that carries the close code and reason a single-session connection would be closed with. The connection and the other sessions stay open.
+//! - At most `max_multiplexed_sessions` sessions are open at the same time. Further sessions are closed with close code `1013` (try again later).
+//! - While the node shuts down (see [`crate::shutdown`]), new sessions are closed with close code `1012` (service restart). The connection is closed with the same code once its open sessions finished.
+//!
+//! The connection itself is closed if the client sends a frame without `request_id`, or if no session was open and no frame was received for `session_lifetime`. Closing the connection aborts all its open sessions.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{
+        Query, State, WebSocketUpgrade,
+        ws::{self, CloseFrame, WebSocket, close_code},
+    },
+    response::Response,
+};
+use axum_extra::TypedHeader;
+use oprf_types::api::{MultiplexedFrame, MultiplexedNodeMessage, oprf_error_messages};
+use serde::{Deserialize, Serialize, de::IgnoredAny};
+use tokio::{sync::mpsc, task::JoinSet};
+use tracing::Instrument as _;
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        errors::Error,
+        oprf::{
+            ConnectionAuth, HumanReadable, OprfModuleState, SessionTransport, decode_message,
+            encode_message, run_session, teardown_websocket, upgrade,
+        },
+        version_header::{ProtocolVersion, ProtocolVersionQuery},
+    },
+    config::CborEncoding,
+};
+
+/// Checks the client like the single-session endpoint and upgrades the connection, see the [module docs](self).
+pub(crate) async fn oprf_multiplex_handler<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    State(state): State<OprfModuleState<ReqAuth>>,
+    websocket_upgrade: WebSocketUpgrade,
+    header_version: Option<TypedHeader<ProtocolVersion>>,
+    query_version: Query<ProtocolVersionQuery>,
+) -> Response {
+    upgrade(
+        state,
+        websocket_upgrade,
+        header_version,
+        query_version,
+        multiplexed_oprf,
+    )
+}
+
+/// The sessions of a multiplexed connection that did not finish yet.
+struct OpenSessions {
+    /// The inbound frames of every open session.
+    inbound: HashMap<Uuid, mpsc::Sender<ws::Message>>,
+    tasks: JoinSet<()>,
+    max_sessions: usize,
+}
+
+impl OpenSessions {
+    fn new(max_sessions: usize) -> Self {
+        Self {
+            inbound: HashMap::new(),
+            tasks: JoinSet::new(),
+            max_sessions,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inbound.is_empty()
+    }
+
+    /// Forgets the sessions whose task finished.
+    fn remove_finished(&mut self) {
+        self.inbound.retain(|_, inbound| !inbound.is_closed());
+    }
+}
+
+/// Reads the frames of the connection, routes them to the sessions and writes the frames of the sessions until the client goes away.
+async fn multiplexed_oprf<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    mut socket: WebSocket,
+    state: OprfModuleState<ReqAuth>,
+) {
+    let mut sessions = OpenSessions::new(state.max_multiplexed_sessions);
+    let auth = state.connection_auth();
+    let (outbound, mut outbound_frames) = mpsc::channel(state.max_multiplexed_sessions);
+    let idle_timeout = tokio::time::sleep(state.max_connection_lifetime);
+    tokio::pin!(idle_timeout);
+
+    let close_frame = loop {
+        tokio::select! {
+            message = socket.recv() => {
+                idle_timeout.as_mut().reset(tokio::time::Instant::now() + state.max_connection_lifetime);
+                match message {
+                    Some(Ok(message @ (ws::Message::Text(_) | ws::Message::Binary(_)))) => {
+                        if let Err(err) = route(message, &mut sessions, &state, &auth, &outbound) {
+                            break err.into_close_frame(state.close_frame_verbosity);
+                        }
+                    }
+                    Some(Ok(ws::Message::Close(_))) | None => break None,
+                    // pings are answered by axum
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => break Error::from(err).into_close_frame(state.close_frame_verbosity),
+                }
+            }
+            Some(frame) = outbound_frames.recv() => {
+                if let Err(err) = socket.send(frame).await {
+                    tracing::trace!(?err, "could not send frame - client went away");
+                    break None;
+                }
+            }
+            Some(_) = sessions.tasks.join_next() => sessions.remove_finished(),
+            () = &mut idle_timeout, if sessions.is_empty() => {
+                tracing::trace!("multiplexed connection ran into idle timeout");
+                break Some(CloseFrame {
+                    code: close_code::NORMAL,
+                    reason: "idle".into(),
+                });
+            }
+            () = state.shutdown.draining(), if sessions.is_empty() => {
+                tracing::trace!("closing drained multiplexed connection");
+                // the last frames of finished sessions may still be queued
+                while let Ok(frame) = outbound_frames.try_recv() {
+                    if socket.send(frame).await.is_err() {
+                        break;
+                    }
+                }
+                break Some(CloseFrame {
+                    code: close_code::RESTART,
+                    reason: oprf_error_messages::SHUTTING_DOWN.into(),
+                });
+            }
+        }
+    };
+    // aborts the open sessions
+    drop(sessions);
+
+    if tokio::time::timeout(
+        state.websocket_shutdown_timeout,
+        teardown_websocket(socket, close_frame),
+    )
+    .await
+    .is_err()
+    {
+        tracing::trace!("timeout during web-socket teardown");
+    }
+}
+
+/// Routes the `message` to its session, opening a new session for an unknown `request_id`. New sessions share the `auth` of the connection.
+///
+/// # Errors
+/// Returns the corresponding error if the `message` is no [`MultiplexedFrame`], which closes the connection.
+fn route<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    message: ws::Message,
+    sessions: &mut OpenSessions,
+    state: &OprfModuleState<ReqAuth>,
+    auth: &ConnectionAuth<ReqAuth>,
+    outbound: &mpsc::Sender<ws::Message>,
+) -> Result<(), Error> {
+    let (MultiplexedFrame { request_id, .. }, human_readable) =
+        decode_message::<MultiplexedFrame<IgnoredAny>>(message.clone(), CborEncoding::Any)?;
+    sessions.remove_finished();
+    if let Some(inbound) = sessions.inbound.get(&request_id) {
+        // a session only reads its next message after answering the last one
+        if inbound.try_send(message).is_err() {
+            tracing::debug!("session {request_id} did not expect a message - dropping it");
+        }
+        return Ok(());
+    }
+
+    if state.shutdown.is_draining() {
+        tracing::debug!("node is shutting down - rejecting session {request_id}");
+        let frame = close_session(
+            request_id,
+            human_readable,
+            close_code::RESTART,
+            "node is shutting down",
+        );
+        if outbound.try_send(frame).is_err() {
+            tracing::trace!("could not reject session {request_id} - outbound frames are full");
+        }
+        return Ok(());
+    }
+    if sessions.inbound.len() >= sessions.max_sessions {
+        tracing::debug!("too many open sessions - rejecting session {request_id}");
+        let frame = close_session(
+            request_id,
+            human_readable,
+            close_code::AGAIN,
+            "too many open sessions",
+        );
+        if outbound.try_send(frame).is_err() {
+            tracing::trace!("could not reject session {request_id} - outbound frames are full");
+        }
+        return Ok(());
+    }
+    let (inbound, inbound_frames) = mpsc::channel(1);
+    inbound.try_send(message).expect("new channel has capacity");
+    sessions.inbound.insert(request_id, inbound);
+    let transport = MultiplexedTransport {
+        request_id,
+        human_readable,
+        cbor_encoding: state.cbor_encoding,
+        inbound: inbound_frames,
+        outbound: outbound.clone(),
+    };
+    let session_span = tracing::info_span!("multiplexed_session", %request_id);
+    sessions.tasks.spawn(
+        multiplexed_session(transport, state.clone(), auth.clone()).instrument(session_span),
+    );
+    Ok(())
+}
+
+/// Runs a single session of a multiplexed connection and closes it if it failed.
+async fn multiplexed_session<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    mut transport: MultiplexedTransport,
+    state: OprfModuleState<ReqAuth>,
+    auth: ConnectionAuth<ReqAuth>,
+) {
+    let mut transcript = None;
+    let close_frame = run_session(&mut transport, &state, &auth, &mut transcript).await;
+    if let Some(close_frame) =
+        close_frame.filter(|close_frame| close_frame.code != close_code::NORMAL)
+    {
+        let frame = close_session(
+            transport.request_id,
+            transport.human_readable,
+            close_frame.code,
+            close_frame.reason.as_str(),
+        );
+        if transport.outbound.send(frame).await.is_err() {
+            tracing::trace!("could not close session - client went away");
+        }
+    }
+
+    if let (Some(transcript_writer), Some(transcript)) = (&state.transcript_writer, transcript) {
+        transcript_writer.write(&transcript).await;
+    }
+}
+
+/// The frame that closes the session `request_id` with `code` and `reason`.
+fn close_session(
+    request_id: Uuid,
+    human_readable: HumanReadable,
+    code: u16,
+    reason: &str,
+) -> ws::Message {
+    let frame = MultiplexedFrame {
+        request_id,
+        payload: MultiplexedNodeMessage::<()>::Close {
+            code,
+            reason: reason.to_owned(),
+        },
+    };
+    encode_message(&frame, human_readable)
+}
+
+/// The [`SessionTransport`] of a single session of a multiplexed connection.
+struct MultiplexedTransport {
+    request_id: Uuid,
+    /// The encoding of the last frame of the client, used for closing the session.
+    human_readable: HumanReadable,
+    /// Checked when the session reads the frame, the connection routes frames regardless of their encoding.
+    cbor_encoding: CborEncoding,
+    inbound: mpsc::Receiver<ws::Message>,
+    outbound: mpsc::Sender<ws::Message>,
+}
+
+impl SessionTransport for MultiplexedTransport {
+    /// Reads the next frame the connection routed to this session and deserializes its `payload`.
+    async fn read_request<Msg: for<'de> Deserialize<'de>>(
+        &mut self,
+    ) -> Result<(Msg, HumanReadable), Error> {
+        let message = self.inbound.recv().await.ok_or(Error::ConnectionClosed)?;
+        let (frame, human_readable) =
+            decode_message::<MultiplexedFrame<Msg>>(message, self.cbor_encoding)?;
+        self.human_readable = human_readable;
+        Ok((frame.payload, human_readable))
+    }
+
+    /// Wraps the `Msg` in a [`MultiplexedFrame`] of this session and hands it to the connection.
+    async fn write_response<Msg: Serialize + Send>(
+        &mut self,
+        response: Msg,
+        human_readable: HumanReadable,
+    ) -> Result<(), Error> {
+        let frame = MultiplexedFrame {
+            request_id: self.request_id,
+            payload: MultiplexedNodeMessage::Message(response),
+        };
+        self.outbound
+            .send(encode_message(&frame, human_readable))
+            .await
+            .map_err(|_| Error::ConnectionClosed)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,192 @@
+use std::{sync::Arc, time::Duration};
+
+use ark_ec::AffineRepr as _;
+use axum_test::TestServerBuilder;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{MultiplexedFrame, MultiplexedNodeMessage, OprfRequest, oprf_error_codes},
+};
+use uuid::Uuid;
+
+use crate::{
+    test_kit::MockAuthenticator,
+    test_utils::{MockSecretManager, builder_with_secret_manager, challenge, default_config},
+};
+
+/// Receives the next frame of a multiplexed connection.
+async fn multiplexed_receive(
+    ws: &mut axum_test::TestWebSocket,
+) -> MultiplexedFrame<MultiplexedNodeMessage<serde_json::Value>> {
+    ws.receive_json().await
+}
+
+#[tokio::test]
+async fn multiplexed_connection_interleaves_sessions() {
+    let mut config = default_config();
+    config.max_multiplexed_sessions = 2;
+    let router = builder_with_secret_manager(
+        config,
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let mut ws = server
+        .get_websocket("/api/test/oprf/multiplex?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    let request = |blinded_query: ark_babyjubjub::EdwardsAffine| {
+        let request_id = Uuid::new_v4();
+        MultiplexedFrame {
+            request_id,
+            payload: OprfRequest {
+                request_id,
+                blinded_query,
+                auth: OprfKeyId::from(42usize),
+                issued_at: None,
+                batch: Vec::new(),
+            },
+        }
+    };
+    let first = request(ark_babyjubjub::EdwardsAffine::generator());
+    let second = request(ark_babyjubjub::EdwardsAffine::generator());
+    ws.send_json(&first).await;
+    ws.send_json(&second).await;
+    let mut responded = vec![
+        multiplexed_receive(&mut ws).await.request_id,
+        multiplexed_receive(&mut ws).await.request_id,
+    ];
+    responded.sort();
+    let mut expected = vec![first.request_id, second.request_id];
+    expected.sort();
+    assert_eq!(responded, expected, "should answer both sessions");
+
+    // both sessions are open, so a third one is rejected without closing the connection
+    let third = request(ark_babyjubjub::EdwardsAffine::generator());
+    ws.send_json(&third).await;
+    let rejected = multiplexed_receive(&mut ws).await;
+    assert_eq!(
+        rejected.request_id, third.request_id,
+        "should name the session"
+    );
+    assert!(
+        matches!(
+            rejected.payload,
+            MultiplexedNodeMessage::Close { code: 1013, .. }
+        ),
+        "should reject sessions above the limit"
+    );
+
+    // the second session finishes before the first one
+    for session in [&second, &first] {
+        ws.send_json(&MultiplexedFrame {
+            request_id: session.request_id,
+            payload: challenge(1),
+        })
+        .await;
+        let proof_share = multiplexed_receive(&mut ws).await;
+        assert_eq!(
+            proof_share.request_id, session.request_id,
+            "should answer the challenged session"
+        );
+        assert!(
+            matches!(proof_share.payload, MultiplexedNodeMessage::Message(_)),
+            "should send the proof share"
+        );
+    }
+
+    // a failed session is closed on its own
+    let invalid = request(ark_babyjubjub::EdwardsAffine::zero());
+    ws.send_json(&invalid).await;
+    let closed = multiplexed_receive(&mut ws).await;
+    assert_eq!(
+        closed.request_id, invalid.request_id,
+        "should name the session"
+    );
+    assert!(
+        matches!(
+            closed.payload,
+            MultiplexedNodeMessage::Close {
+                code: oprf_error_codes::BLINDED_QUERY_IS_IDENTITY,
+                ..
+            }
+        ),
+        "should close the failed session"
+    );
+    let next = request(ark_babyjubjub::EdwardsAffine::generator());
+    ws.send_json(&next).await;
+    assert!(
+        matches!(
+            multiplexed_receive(&mut ws).await.payload,
+            MultiplexedNodeMessage::Message(_)
+        ),
+        "should keep the connection open"
+    );
+}
+
+/// Opens a session for key 42 and waits for the commitments of the node.
+async fn open_session(ws: &mut axum_test::TestWebSocket) {
+    let request_id = Uuid::new_v4();
+    ws.send_json(&MultiplexedFrame {
+        request_id,
+        payload: OprfRequest {
+            request_id,
+            blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+            auth: OprfKeyId::from(42usize),
+            issued_at: None,
+            batch: Vec::new(),
+        },
+    })
+    .await;
+    assert!(
+        matches!(
+            multiplexed_receive(ws).await.payload,
+            MultiplexedNodeMessage::Message(_)
+        ),
+        "should answer the session"
+    );
+}
+
+#[tokio::test]
+async fn multiplexed_sessions_share_cached_authentication() {
+    let authenticator = Arc::new(MockAuthenticator::allow_all().with_cache(Duration::from_mins(1)));
+    let router = builder_with_secret_manager(
+        default_config(),
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .module("/test", Arc::clone(&authenticator) as _)
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let connect = || async {
+        server
+            .get_websocket("/api/test/oprf/multiplex?version=1.0.0")
+            .await
+            .into_websocket()
+            .await
+    };
+    let mut ws = connect().await;
+    open_session(&mut ws).await;
+    open_session(&mut ws).await;
+    assert_eq!(
+        authenticator.calls(),
+        1,
+        "should authenticate once per connection"
+    );
+
+    let mut other = connect().await;
+    open_session(&mut other).await;
+    assert_eq!(
+        authenticator.calls(),
+        2,
+        "should not share the authentication with other connections"
+    );
+}
@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use std::num::NonZeroU16;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use axum::{
@@ -8,15 +10,24 @@ use axum::{
         ws::{self, CloseFrame, WebSocket, close_code},
     },
     response::IntoResponse,
-    routing::any,
+    routing::{any, get},
 };
 use axum_extra::TypedHeader;
-use http::StatusCode;
+use futures::future::Either;
+use http::{HeaderValue, StatusCode};
 use oprf_core::ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir};
 use oprf_types::{
-    api::{OprfRequest, OprfRequestAuthService, OprfResponse, oprf_error_codes},
+    OprfKeyId,
+    api::{
+        AuthCacheInvalidator, OPRF_MAX_MESSAGE_SIZE_HEADER, OprfBatchChallenge,
+        OprfBatchProofShares, OprfRequest, OprfRequestAuthService, OprfResponse, ShareProof,
+        oprf_error_codes, oprf_error_messages,
+    },
+    canonical_cbor,
     crypto::PartyId,
+    transcript::{FrameDirection, Transcript, TranscriptMessage, TranscriptParticipant},
 };
+use parking_lot::Mutex;
 use semver::VersionReq;
 use serde::{Deserialize, Serialize};
 use tracing::{Instrument, instrument};
@@ -24,26 +35,408 @@ use uuid::Uuid;
 
 use crate::{
     api::{
-        errors::Error,
+        errors::{BatchError, Error},
         version_header::{ProtocolVersion, ProtocolVersionQuery},
     },
+    config::{CborEncoding, CloseFrameVerbosity, OprfNodeServiceConfig},
     metrics,
     services::{
-        open_sessions::OpenSessions,
+        challenge_replay::{ChallengeReplayCache, ReplayEntry},
+        clock::ClockService,
+        key_concurrency::{KeyConcurrency, KeyPermit},
+        key_labels::KeyLabels,
+        key_quota::KeyQuota,
         oprf_key_material_store::{OprfKeyMaterialStore, OprfSession},
+        risk_scorer::{RiskDecision, RiskRequest, RiskScorerService},
+        runtime_limits::RuntimeLimits,
+        secret_manager::SecretManagerError,
+        session_handoff::SessionHandoff,
+        session_store::{OprfSessionStoreService, SessionGuard},
+        transcript_writer::TranscriptWriter,
     },
+    shutdown::ShutdownSignal,
 };
 
 pub(crate) struct OprfModuleState<ReqAuth> {
     pub(crate) party_id: PartyId,
     pub(crate) threshold: NonZeroU16,
     pub(crate) oprf_material_store: OprfKeyMaterialStore,
-    pub(crate) open_sessions: OpenSessions,
-    pub(crate) req_auth_service: OprfRequestAuthService<ReqAuth>,
+    pub(crate) session_store: OprfSessionStoreService,
+    pub(crate) challenge_replay_cache: ChallengeReplayCache,
+    pub(crate) session_handoff: Option<SessionHandoff>,
+    pub(crate) transcript_writer: Option<TranscriptWriter>,
+    pub(crate) req_auth_service: TimeBoxedAuthService<ReqAuth>,
+    pub(crate) risk_scorer: Option<TimeBoxedRiskScorer>,
+    pub(crate) key_quota: Option<KeyQuota>,
+    pub(crate) key_concurrency: Option<KeyConcurrency>,
+    pub(crate) key_labels: Option<KeyLabels>,
+    pub(crate) clock: ClockService,
+    pub(crate) runtime_limits: RuntimeLimits,
+    pub(crate) shutdown: ShutdownSignal,
     pub(crate) version_req: VersionReq,
     pub(crate) max_message_size: usize,
     pub(crate) max_connection_lifetime: Duration,
+    pub(crate) max_multiplexed_sessions: usize,
+    pub(crate) max_batch_size: usize,
+    pub(crate) cbor_encoding: CborEncoding,
+    pub(crate) share_proofs: bool,
+    pub(crate) signed_responses: bool,
     pub(crate) websocket_shutdown_timeout: Duration,
+    pub(crate) query_age_policy: QueryAgePolicy,
+    pub(crate) close_frame_verbosity: CloseFrameVerbosity,
+    /// The context the proof shares of the module are bound to (see [`crate::OprfServiceBuilder::module_context`]), `0` if the module has none.
+    pub(crate) challenge_context: ark_babyjubjub::Fq,
+}
+
+/// Wraps an [`OprfRequestAuthService`] and bounds every `authenticate` call by `timeout`.
+///
+/// This isolates a slow authenticator (e.g., a remote policy or key fetch) from the session lifetime, so it surfaces as [`oprf_error_codes::AUTH_TIMEOUT`] instead of a generic session timeout.
+pub(crate) struct TimeBoxedAuthService<ReqAuth> {
+    service: OprfRequestAuthService<ReqAuth>,
+    timeout: Duration,
+}
+
+impl<ReqAuth> Clone for TimeBoxedAuthService<ReqAuth> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl<ReqAuth> TimeBoxedAuthService<ReqAuth> {
+    pub(crate) fn new(service: OprfRequestAuthService<ReqAuth>, timeout: Duration) -> Self {
+        Self { service, timeout }
+    }
+
+    /// Authenticates the request with the wrapped service, failing with [`Error::AuthTimeout`] if it takes longer than `timeout`.
+    ///
+    /// Not an `async fn`, as the returned future must not capture `request` (which is not `Sync`).
+    pub(crate) fn authenticate<'a>(
+        &'a self,
+        request: &'a OprfRequest<ReqAuth>,
+    ) -> impl Future<Output = Result<OprfKeyId, Error>> + Send + 'a {
+        let authenticate = self.service.authenticate(request);
+        let timeout = self.timeout;
+        async move {
+            let start_verify = Instant::now();
+            let oprf_key_id =
+                tokio::time::timeout(timeout, authenticate)
+                    .await
+                    .map_err(|_| {
+                        metrics::request::inc_auth_timeout();
+                        Error::AuthTimeout(timeout)
+                    })??;
+            metrics::request::record_verify_duration(start_verify.elapsed());
+            Ok(oprf_key_id)
+        }
+    }
+}
+
+/// The [`TimeBoxedAuthService`] of a module together with the authentications cached for a single connection.
+///
+/// Created once per connection (see [`OprfModuleState::connection_auth`]) and shared by all sessions of the connection, e.g., the sessions of a multiplexed connection. Authentications are only cached if the authenticator opts in via [`oprf_types::api::OprfRequestAuthenticator::cacheable`]. Cached entries expire after that time (measured with the [`crate::clock::Clock`] of the node, a time beyond the range of the clock never expires) or when the authenticator bumps its [`oprf_types::api::AuthCacheInvalidator`]. Expired and invalidated entries are dropped whenever an authentication is cached, and at most [`MAX_CACHED_AUTHS`] credentials are cached at a time. The cache is dropped with the connection.
+pub(crate) struct ConnectionAuth<ReqAuth> {
+    service: TimeBoxedAuthService<ReqAuth>,
+    clock: ClockService,
+    cache: Arc<Mutex<HashMap<Vec<u8>, CachedAuth>>>,
+}
+
+impl<ReqAuth> Clone for ConnectionAuth<ReqAuth> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            clock: Arc::clone(&self.clock),
+            cache: Arc::clone(&self.cache),
+        }
+    }
+}
+
+/// Max number of credentials a [`ConnectionAuth`] caches. Further credentials are authenticated on every request until cached entries expire or are invalidated.
+const MAX_CACHED_AUTHS: usize = 1024;
+
+/// A successful authentication in the [`ConnectionAuth`] cache.
+struct CachedAuth {
+    oprf_key_id: OprfKeyId,
+    /// `None` if the entry does not expire, i.e., the time to live of the authenticator exceeds the range of the clock.
+    expires_at: Option<tokio::time::Instant>,
+    generation: u64,
+}
+
+impl CachedAuth {
+    /// Whether the entry is neither expired at `now` nor invalidated by a newer `generation`.
+    fn is_valid(&self, generation: u64, now: tokio::time::Instant) -> bool {
+        self.generation == generation && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+impl<ReqAuth> ConnectionAuth<ReqAuth> {
+    pub(crate) fn new(service: &TimeBoxedAuthService<ReqAuth>, clock: &ClockService) -> Self {
+        Self {
+            service: service.clone(),
+            clock: Arc::clone(clock),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Authenticates the request, reusing a cached authentication of the same credentials if the authenticator allows it.
+    ///
+    /// Not an `async fn`, as the returned future must not capture `request` (which is not `Sync`).
+    pub(crate) fn authenticate<'b>(
+        &'b self,
+        request: &'b OprfRequest<ReqAuth>,
+    ) -> impl Future<Output = Result<OprfKeyId, Error>> + Send + 'b
+    where
+        ReqAuth: 'b,
+    {
+        let authenticator = &self.service.service;
+        let generation = authenticator
+            .cache_invalidator()
+            .map_or(0, AuthCacheInvalidator::generation);
+        let cacheable = authenticator
+            .cacheable()
+            .zip(authenticator.cache_key(request));
+        if let Some((_, key)) = &cacheable
+            && let Some(oprf_key_id) = self.cached(key, generation)
+        {
+            metrics::request::inc_auth_cache_hit();
+            return Either::Left(std::future::ready(Ok(oprf_key_id)));
+        }
+        let authenticate = self.service.authenticate(request);
+        Either::Right(async move {
+            let oprf_key_id = authenticate.await?;
+            if let Some((ttl, key)) = cacheable {
+                self.insert(key, oprf_key_id, ttl, generation);
+            }
+            Ok(oprf_key_id)
+        })
+    }
+
+    /// Returns the cached authentication of the credentials `key`, if it is neither expired nor invalidated.
+    fn cached(&self, key: &[u8], generation: u64) -> Option<OprfKeyId> {
+        let cache = self.cache.lock();
+        let cached = cache.get(key)?;
+        cached
+            .is_valid(generation, self.clock.now())
+            .then_some(cached.oprf_key_id)
+    }
+
+    /// Caches the authentication of the credentials `key` for `ttl`.
+    ///
+    /// Drops the expired and invalidated entries first. Does not cache new credentials if the cache still holds [`MAX_CACHED_AUTHS`] entries.
+    fn insert(&self, key: Vec<u8>, oprf_key_id: OprfKeyId, ttl: Duration, generation: u64) {
+        let now = self.clock.now();
+        let mut cache = self.cache.lock();
+        cache.retain(|_, cached| cached.is_valid(generation, now));
+        if cache.len() >= MAX_CACHED_AUTHS && !cache.contains_key(&key) {
+            return;
+        }
+        cache.insert(
+            key,
+            CachedAuth {
+                oprf_key_id,
+                expires_at: now.checked_add(ttl),
+                generation,
+            },
+        );
+    }
+}
+
+/// Wraps the [`RiskScorerService`] of a module, bounds every `score` call by `timeout` and applies the fail-open/fail-closed policy, see [`crate::services::risk_scorer`].
+#[derive(Clone)]
+pub(crate) struct TimeBoxedRiskScorer {
+    scorer: RiskScorerService,
+    timeout: Duration,
+    fail_open: bool,
+    module: Arc<str>,
+}
+
+impl TimeBoxedRiskScorer {
+    pub(crate) fn new(
+        scorer: RiskScorerService,
+        timeout: Duration,
+        fail_open: bool,
+        module: &str,
+    ) -> Self {
+        Self {
+            scorer,
+            timeout,
+            fail_open,
+            module: Arc::from(module),
+        }
+    }
+
+    /// Scores the authenticated request, failing with [`Error::RiskDenied`] or [`Error::RiskThrottled`] if the request must not be evaluated.
+    pub(crate) async fn check(
+        &self,
+        request_id: Uuid,
+        oprf_key_id: OprfKeyId,
+        issued_at: Option<u64>,
+    ) -> Result<(), Error> {
+        let request = RiskRequest {
+            request_id,
+            oprf_key_id,
+            module: Arc::clone(&self.module),
+            issued_at,
+        };
+        let fallback = if self.fail_open {
+            RiskDecision::Allow
+        } else {
+            RiskDecision::Deny
+        };
+        let start = Instant::now();
+        let decision = match tokio::time::timeout(self.timeout, self.scorer.score(&request)).await {
+            Ok(Ok(decision)) => {
+                metrics::request::inc_risk_decision(decision.label());
+                decision
+            }
+            Ok(Err(err)) => {
+                tracing::warn!(?err, "risk scorer failed, falling back to {fallback:?}");
+                metrics::request::inc_risk_decision("error");
+                fallback
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "risk scorer did not finish within {:?}, falling back to {fallback:?}",
+                    self.timeout
+                );
+                metrics::request::inc_risk_decision("timeout");
+                fallback
+            }
+        };
+        metrics::request::record_risk_duration(start.elapsed());
+        match decision {
+            RiskDecision::Allow => Ok(()),
+            RiskDecision::Deny => Err(Error::RiskDenied),
+            RiskDecision::Throttle { retry_after } => Err(Error::RiskThrottled(retry_after)),
+        }
+    }
+}
+
+/// Checks the `issued_at` timestamp of incoming [`OprfRequest`]s.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct QueryAgePolicy {
+    /// Max age of a query. `None` disables the check.
+    pub(crate) max_age: Option<Duration>,
+    /// Tolerated clock skew in both directions.
+    pub(crate) max_clock_skew: Duration,
+}
+
+impl From<&OprfNodeServiceConfig> for QueryAgePolicy {
+    fn from(config: &OprfNodeServiceConfig) -> Self {
+        Self {
+            max_age: config.max_query_age,
+            max_clock_skew: config.max_clock_skew,
+        }
+    }
+}
+
+impl QueryAgePolicy {
+    /// Returns `Ok(())` if the policy is disabled or `issued_at` lies within `[now - max_age - skew, now + skew]`.
+    pub(crate) fn check(&self, issued_at: Option<u64>, now: u64) -> Result<(), Error> {
+        let Some(max_age) = self.max_age else {
+            return Ok(());
+        };
+        let timestamp = issued_at.ok_or(Error::StaleQuery(None))?;
+        let skew = self.max_clock_skew.as_secs();
+        let oldest = now.saturating_sub(max_age.as_secs().saturating_add(skew));
+        let newest = now.saturating_add(skew);
+        if (oldest..=newest).contains(&timestamp) {
+            Ok(())
+        } else {
+            Err(Error::StaleQuery(issued_at))
+        }
+    }
+}
+
+impl<ReqAuth> OprfModuleState<ReqAuth> {
+    /// Creates the [`ConnectionAuth`] shared by the sessions of a new connection.
+    pub(crate) fn connection_auth(&self) -> ConnectionAuth<ReqAuth> {
+        ConnectionAuth::new(&self.req_auth_service, &self.clock)
+    }
+
+    /// Fails with [`Error::SessionLost`] if the session was pending during the last restart, see [`crate::services::session_handoff`].
+    ///
+    /// Pending sessions of a durable session store are resumed instead (see [`crate::session_store::OprfSessionStore::is_durable`]).
+    fn check_not_lost(&self, request_id: Uuid) -> Result<(), Error> {
+        match &self.session_handoff {
+            Some(handoff) if !self.session_store.is_durable() && handoff.is_lost(request_id) => {
+                Err(Error::SessionLost(request_id))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<ReqAuth> OprfModuleState<ReqAuth> {
+    /// Charges `evaluations` to the quota of `oprf_key_id`, failing with [`Error::QuotaExceeded`] if it is exhausted, see [`crate::services::key_quota`].
+    fn charge_quota(&self, oprf_key_id: OprfKeyId, evaluations: usize) -> Result<(), Error> {
+        let Some(key_quota) = &self.key_quota else {
+            return Ok(());
+        };
+        match key_quota.try_acquire(oprf_key_id, evaluations) {
+            Ok(()) => {
+                metrics::request::inc_key_quota("allowed", evaluations);
+                Ok(())
+            }
+            Err(retry_after) => {
+                metrics::request::inc_key_quota("exceeded", evaluations);
+                Err(Error::QuotaExceeded(retry_after))
+            }
+        }
+    }
+
+    /// Takes a permit for a session of `oprf_key_id`, failing with [`Error::KeyBusy`] if the key runs its limit of concurrent sessions, see [`crate::services::key_concurrency`].
+    ///
+    /// Returns `None` if the number of sessions is not limited.
+    fn acquire_key_permit(&self, oprf_key_id: OprfKeyId) -> Result<Option<KeyPermit>, Error> {
+        let Some(key_concurrency) = &self.key_concurrency else {
+            return Ok(None);
+        };
+        match key_concurrency.try_acquire(oprf_key_id) {
+            Ok(permit) => Ok(Some(permit)),
+            Err(limit) => {
+                metrics::keys::inc_busy(key_concurrency.label(oprf_key_id));
+                Err(Error::KeyBusy(limit))
+            }
+        }
+    }
+
+    /// The `oprf_key_id` label of the request metrics of `oprf_key_id`, `None` if per-key labels are disabled, see [`crate::services::key_labels`].
+    fn key_label(&self, oprf_key_id: OprfKeyId) -> Option<String> {
+        self.key_labels
+            .as_ref()
+            .map(|key_labels| key_labels.label(oprf_key_id))
+    }
+
+    /// Attaches the [`ResponseSignature`](oprf_types::api::ResponseSignature) of the wallet key of the node to the `response` to the session `request_id`, if enabled with `OprfServiceBuilder::signed_responses`.
+    async fn sign_response(
+        &self,
+        response: &mut OprfResponse,
+        request_id: Uuid,
+    ) -> Result<(), Error> {
+        if self.signed_responses {
+            tracing::trace!("signing response with secret manager...");
+            let signature = self
+                .oprf_material_store
+                .secret_manager()
+                .sign_response(&response.signing_message(request_id))
+                .await
+                .map_err(|err| Error::SecretManager(Arc::new(SecretManagerError::Internal(err))))?;
+            response.signature = Some(signature);
+        }
+        Ok(())
+    }
+
+    /// Takes the current [`RuntimeLimits`] of the module for a new connection.
+    pub(crate) fn with_current_limits(mut self) -> Self {
+        let limits = self.runtime_limits.current();
+        self.version_req = limits.version_req;
+        self.max_message_size = limits.max_message_size;
+        self.max_connection_lifetime = limits.session_lifetime;
+        self
+    }
 }
 
 impl<ReqAuth> Clone for OprfModuleState<ReqAuth> {
@@ -52,18 +445,36 @@ impl<ReqAuth> Clone for OprfModuleState<ReqAuth> {
             party_id: self.party_id,
             threshold: self.threshold,
             oprf_material_store: self.oprf_material_store.clone(),
-            open_sessions: self.open_sessions.clone(),
+            session_store: Arc::clone(&self.session_store),
+            challenge_replay_cache: self.challenge_replay_cache.clone(),
+            session_handoff: self.session_handoff.clone(),
+            transcript_writer: self.transcript_writer.clone(),
             req_auth_service: self.req_auth_service.clone(),
+            risk_scorer: self.risk_scorer.clone(),
+            key_quota: self.key_quota.clone(),
+            key_concurrency: self.key_concurrency.clone(),
+            key_labels: self.key_labels.clone(),
+            clock: Arc::clone(&self.clock),
+            runtime_limits: self.runtime_limits.clone(),
+            shutdown: self.shutdown.clone(),
             version_req: self.version_req.clone(),
             max_message_size: self.max_message_size,
             max_connection_lifetime: self.max_connection_lifetime,
+            max_multiplexed_sessions: self.max_multiplexed_sessions,
+            max_batch_size: self.max_batch_size,
+            cbor_encoding: self.cbor_encoding,
+            share_proofs: self.share_proofs,
+            signed_responses: self.signed_responses,
             websocket_shutdown_timeout: self.websocket_shutdown_timeout,
+            query_age_policy: self.query_age_policy,
+            close_frame_verbosity: self.close_frame_verbosity,
+            challenge_context: self.challenge_context,
         }
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum HumanReadable {
+pub(crate) enum HumanReadable {
     Yes,
     No,
 }
@@ -76,9 +487,13 @@ enum HumanReadable {
 ///
 /// Sets the `max_message_size` for the web-socket to the provided value. Implementations are encouraged to use a very conservative value here. We only expect exactly two kinds of messages, and those are very small (depending on your authentication request), therefore we can reject larger requests efficiently.
 ///
+/// The limit is advertised in the [`oprf_types::api::OPRF_MAX_MESSAGE_SIZE_HEADER`] of the upgrade response and in the [`oprf_types::api::OprfKeyParams`] at `/oprf_params/{id}`, so clients can validate their payloads before sending them. Larger messages are rejected with close code `1009` (message too big) and a reason naming the limit.
+///
+/// The limit can be overridden at runtime, see [`crate::services::runtime_limits`].
+///
 /// ## Session Locking
 ///
-/// The created web-socket connection holds all the information bound to the session. At the very start of the session, it tries to lock the requested session-id with the [`OpenSessions`] service, as no two sessions with the same id must be handled at the same time.
+/// At the very start of the session, the web-socket connection tries to reserve the requested session-id with the [`crate::session_store::OprfSessionStore`] of the module, as no two sessions with the same id must be handled at the same time. The reservation is released when the connection ends.
 ///
 /// ## Client Protocol Version Requirement
 ///
@@ -92,9 +507,25 @@ enum HumanReadable {
 ///
 /// Connections that do not provide a valid protocol version are rejected before the web-socket session is established.
 ///
+/// Clients may additionally send their [`oprf_types::api::SchemaFingerprint`] as `schema` query parameter. Connections with a fingerprint different from ours are rejected as well.
+///
 /// ## Randomness & Session Data
 ///
-/// The generated randomness (which is not allowed to be used twice and shall not leak) only lives in the created task and is consumed when the session finishes (also releasing the session-id lock).
+/// The generated randomness (which is not allowed to be used twice and shall not leak) is held by the session store between the two rounds and is consumed when the challenge arrives. With the default [`crate::session_store::LocalSessionStore`] it never leaves the memory of the node.
+///
+/// ## Resuming Sessions
+///
+/// If the connection breaks after the client sent the challenge, the client can resume the session within `session_lifetime` by sending the same [`OprfRequest`] again. The node answers with the commitments and proof share of the finished session (see [`crate::services::challenge_replay`]), as long as the challenge is the same.
+///
+/// With a session handoff (see [`crate::services::session_handoff`]), finished sessions can also be resumed after a planned restart of the node. Sessions that were still pending during the restart are closed with [`oprf_error_codes::SESSION_LOST`], unless the node uses a durable session store (see [`crate::session_store::OprfSessionStore::is_durable`]). Durable stores keep pending sessions across restarts, the node answers the resumed request with the commitments of the stored session.
+///
+/// ## Multiplexing
+///
+/// Clients that run many sessions can carry them over a single connection to `/oprf/multiplex` instead, see [`crate::api::multiplex`].
+///
+/// ## Transcripts
+///
+/// If `transcript_dir` is configured, the messages of every session (without the authentication part of the request) are written to a transcript file when the session ends, see [`TranscriptWriter`].
 ///
 /// ## Connection Lifetime
 ///
@@ -113,6 +544,37 @@ async fn oprf_ws_handler<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
     header_version: Option<TypedHeader<ProtocolVersion>>,
     query_version: Query<ProtocolVersionQuery>,
 ) -> axum::response::Response {
+    upgrade(
+        state,
+        websocket_upgrade,
+        header_version,
+        query_version,
+        partial_oprf,
+    )
+}
+
+/// Checks the schema and version of the client and upgrades the connection to a web-socket that runs `connection`, see [`oprf_ws_handler`].
+///
+/// Shared by the single-session and the multiplexed web-socket endpoint (see [`crate::api::multiplex`]).
+pub(crate) fn upgrade<ReqAuth, Connection, Fut>(
+    state: OprfModuleState<ReqAuth>,
+    websocket_upgrade: WebSocketUpgrade,
+    header_version: Option<TypedHeader<ProtocolVersion>>,
+    query_version: Query<ProtocolVersionQuery>,
+    connection: Connection,
+) -> axum::response::Response
+where
+    ReqAuth: Send + 'static,
+    Connection: FnOnce(WebSocket, OprfModuleState<ReqAuth>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    if state.shutdown.is_draining() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "node is shutting down").into_response();
+    }
+    let state = state.with_current_limits();
+    if let Err(msg) = query_version.check_schema() {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
     let Some(client_version) = parse_client_header(header_version, query_version) else {
         tracing::warn!(user_error = true, "missing client version");
         return (StatusCode::BAD_REQUEST, "missing client version").into_response();
@@ -120,14 +582,21 @@ async fn oprf_ws_handler<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
     let parent_span = tracing::Span::current();
     parent_span.record("client_version", client_version.to_string());
     if state.version_req.matches(&client_version) {
-        websocket_upgrade
-            .max_message_size(state.max_message_size)
+        let max_message_size = state.max_message_size;
+        let mut response = websocket_upgrade
+            .max_message_size(max_message_size)
             .on_failed_upgrade(|err| {
                 tracing::warn!(user_error=true, %err, "could not establish websocket connection");
             })
             .on_upgrade(move |ws| {
-                async move { partial_oprf(ws, state).await }.instrument(parent_span)
-            })
+                let shutdown = state.shutdown.clone();
+                shutdown.track(connection(ws, state).instrument(parent_span))
+            });
+        response.headers_mut().insert(
+            OPRF_MAX_MESSAGE_SIZE_HEADER.clone(),
+            HeaderValue::from(max_message_size),
+        );
+        response
     } else {
         let msg = format!(
             "invalid version, expected: {} got: {client_version}",
@@ -140,22 +609,61 @@ async fn oprf_ws_handler<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
 }
 
 async fn partial_oprf<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
-    mut socket: WebSocket,
+    socket: WebSocket,
     state: OprfModuleState<ReqAuth>,
 ) {
-    let close_frame = match tokio::time::timeout(
-        state.max_connection_lifetime,
-        partial_oprf_inner::<ReqAuth>(
-            &mut socket,
-            state.party_id,
-            state.threshold,
-            state.open_sessions,
-            state.oprf_material_store,
-            state.req_auth_service,
-        ),
+    let mut transcript = None;
+    let mut transport = WebSocketTransport {
+        socket,
+        cbor_encoding: state.cbor_encoding,
+    };
+    let auth = state.connection_auth();
+    let close_frame = run_session(&mut transport, &state, &auth, &mut transcript).await;
+
+    if tokio::time::timeout(
+        state.websocket_shutdown_timeout,
+        teardown_websocket(transport.socket, close_frame),
     )
     .await
+    .is_err()
     {
+        tracing::trace!("timeout during web-socket teardown");
+    }
+
+    if let (Some(transcript_writer), Some(transcript)) = (&state.transcript_writer, transcript) {
+        transcript_writer.write(&transcript).await;
+    }
+}
+
+/// Runs [`partial_oprf_inner`] on the `transport` for at most `max_connection_lifetime` and returns the [`CloseFrame`] that ends the session, if any.
+///
+/// The session authenticates with the `auth` of its connection, so sessions of the same connection share the cached authentications. Failed sessions record the close frame in the `transcript`. If per-key labels are enabled, the outcome of every authenticated session is counted per key (see [`crate::services::key_labels`]). Shared by the web-socket and the gRPC transport.
+pub(crate) async fn run_session<ReqAuth, T>(
+    transport: &mut T,
+    state: &OprfModuleState<ReqAuth>,
+    auth: &ConnectionAuth<ReqAuth>,
+    transcript: &mut Option<Transcript>,
+) -> Option<CloseFrame>
+where
+    ReqAuth: for<'de> Deserialize<'de> + Send + 'static,
+    T: SessionTransport,
+{
+    metrics::request::inc_oprf_request();
+    let mut oprf_key_id = None;
+    let result = tokio::time::timeout(
+        state.max_connection_lifetime,
+        partial_oprf_inner(transport, state, auth, transcript, &mut oprf_key_id),
+    )
+    .await;
+    if let Some(key_label) = oprf_key_id.and_then(|oprf_key_id| state.key_label(oprf_key_id)) {
+        let outcome = if matches!(result, Ok(Ok(_))) {
+            "success"
+        } else {
+            "error"
+        };
+        metrics::keys::inc_outcome(key_label, outcome);
+    }
+    let close_frame = match result {
         Ok(Ok(session_id)) => {
             tracing::trace!("successfully created nullifier for {session_id}");
             metrics::request::inc_success();
@@ -164,30 +672,79 @@ async fn partial_oprf<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
                 reason: "success".into(),
             })
         }
-        Ok(Err(err)) => err.into_close_frame(),
+        Ok(Err(err)) => err.into_close_frame(state.close_frame_verbosity),
         Err(_) => {
             tracing::trace!("session ran into timeout");
             metrics::request::inc_client_timeout();
             Some(CloseFrame {
                 code: oprf_error_codes::TIMEOUT,
-                reason: "timeout".into(),
+                reason: oprf_error_messages::TIMEOUT.into(),
             })
         }
     };
-
-    if tokio::time::timeout(
-        state.websocket_shutdown_timeout,
-        teardown_websocket(socket, close_frame),
-    )
-    .await
-    .is_err()
+    if let Some(close_frame) = close_frame
+        .as_ref()
+        .filter(|close_frame| close_frame.code != close_code::NORMAL)
     {
-        tracing::trace!("timeout during web-socket teardown");
+        record(transcript, FrameDirection::Sent, || {
+            TranscriptMessage::Close {
+                code: close_frame.code,
+                reason: close_frame.reason.to_string(),
+            }
+        });
+    }
+    close_frame
+}
+
+/// Starts the transcript of the session with the received request, if capturing transcripts is enabled.
+fn start_transcript<ReqAuth>(
+    state: &OprfModuleState<ReqAuth>,
+    request: &OprfRequest<ReqAuth>,
+) -> Option<Transcript> {
+    state.transcript_writer.as_ref()?;
+    let mut transcript = Transcript::new(
+        request.request_id,
+        TranscriptParticipant::Node(state.party_id),
+    );
+    transcript.record(
+        FrameDirection::Received,
+        TranscriptParticipant::Client,
+        TranscriptMessage::Request {
+            blinded_query: request.blinded_query,
+            issued_at: request.issued_at,
+            batch: request.batch.clone(),
+        },
+    );
+    Some(transcript)
+}
+
+/// Records the authenticated key of the session in the span, the transcript and `session_key`.
+fn record_oprf_key_id(
+    span: &tracing::Span,
+    transcript: &mut Option<Transcript>,
+    session_key: &mut Option<OprfKeyId>,
+    oprf_key_id: OprfKeyId,
+) {
+    *session_key = Some(oprf_key_id);
+    span.record("oprf_key_id", oprf_key_id.to_string());
+    if let Some(transcript) = transcript {
+        transcript.oprf_key_id = Some(oprf_key_id);
+    }
+}
+
+/// Records a frame exchanged with the client, if capturing transcripts is enabled.
+fn record(
+    transcript: &mut Option<Transcript>,
+    direction: FrameDirection,
+    message: impl FnOnce() -> TranscriptMessage,
+) {
+    if let Some(transcript) = transcript {
+        transcript.record(direction, TranscriptParticipant::Client, message());
     }
 }
 
 #[instrument(level = "info", skip_all)]
-async fn teardown_websocket(mut ws: WebSocket, close_frame: Option<CloseFrame>) {
+pub(crate) async fn teardown_websocket(mut ws: WebSocket, close_frame: Option<CloseFrame>) {
     tracing::trace!("initiating teardown websocket");
 
     if let Some(close_frame) = close_frame {
@@ -219,104 +776,456 @@ async fn teardown_websocket(mut ws: WebSocket, close_frame: Option<CloseFrame>)
 /// The whole life-cycle of a single user session.
 ///
 /// 1) Read the [`OprfRequest`] of the user. Accepts `Text` and `Binary` frames and deserializes the request with `json` or `cbor` respectively.
-/// 2) Verifies the implementation dependent authentication part with the provided [`OprfRequestAuthService`].
+/// 2) Checks the issued-at timestamp against the configured [`QueryAgePolicy`] and verifies the implementation dependent authentication part with the provided [`OprfRequestAuthService`]. If the module has a [`TimeBoxedRiskScorer`], the authenticated request is scored afterwards.
 /// 3) Computes the nodes partial contribution for the session. The created randomness does not leave the task.
 /// 4) Sends the commitment back to the user (using same serialization as the user).
 /// 5) Read the [`DLogCommitmentsShamir`] of the user. Accepts `Text` and `Binary` frames and deserializes the request with `json` or `cbor` respectively.
-/// 6) Finalizes the proof share for the session and sends it back to the user (same serialization as the initial request of the user).
+/// 6) Finalizes the proof share for the session, caches it for resumed sessions, and sends it back to the user (same serialization as the initial request of the user).
+///
+/// If the [`ChallengeReplayCache`] holds a finished session for the request, steps 3) and 6) are replaced by the cached commitments and proof share, see [`replay_session`]. Requests with a non-empty [`OprfRequest::batch`] continue with [`batch_session`] after 1).
 ///
 /// Clients may and will close the connection at any point because they only need `threshold` amount of sessions, therefore it is very much expected that sane clients send a `Close` frame at any point (or simply drop the connection). This method handles this gracefully at any point.
 #[instrument(level = "info", skip_all, name = "partial_oprf")]
 async fn partial_oprf_inner<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
-    socket: &mut WebSocket,
-    party_id: PartyId,
-    threshold: NonZeroU16,
-    open_sessions: OpenSessions,
-    oprf_material_store: OprfKeyMaterialStore,
-    req_auth_service: OprfRequestAuthService<ReqAuth>,
+    transport: &mut impl SessionTransport,
+    state: &OprfModuleState<ReqAuth>,
+    auth: &ConnectionAuth<ReqAuth>,
+    transcript: &mut Option<Transcript>,
+    session_key: &mut Option<OprfKeyId>,
 ) -> Result<Uuid, Error> {
-    metrics::request::inc_oprf_request();
     tracing::trace!("new oprf session - reading request...");
-    let (init_request, human_readable) = read_request::<OprfRequest<ReqAuth>>(socket).await?;
+    let (init_request, human_readable) = transport.read_request::<OprfRequest<ReqAuth>>().await?;
 
     // Some setup before we start processing - setup span and reserve the session ID
     let request_id = init_request.request_id;
     tracing::trace!("starting with request id: {request_id}");
     let oprf_span = tracing::Span::current();
     oprf_span.record("request_id", request_id.to_string());
+    *transcript = start_transcript(state, &init_request);
 
     // this session guard need to live throughout the whole run. Do not touch except you really know what you are doing (you really don't want to move this, this must be at the very top of the method).
-    let _session_guard = open_sessions.insert_new_session(request_id)?;
+    let _session_guard = SessionGuard::reserve(&state.session_store, request_id).await?;
+    state.check_not_lost(request_id)?;
 
-    let (session, response) = init_session(
-        init_request,
-        party_id,
-        &req_auth_service,
-        &oprf_material_store,
-    )
-    .await?;
+    if !init_request.batch.is_empty() {
+        batch_session(
+            transport,
+            state,
+            auth,
+            init_request,
+            human_readable,
+            transcript,
+            session_key,
+        )
+        .await?;
+        return Ok(request_id);
+    }
+
+    let blinded_query = init_request.blinded_query;
+    let (session, response, _key_permit) = match init_session(init_request, state, auth).await? {
+        InitSession::New(new_session) => *new_session,
+        InitSession::Replay(entry) => {
+            record_oprf_key_id(&oprf_span, transcript, session_key, entry.oprf_key_id);
+            replay_session(
+                transport,
+                request_id,
+                state,
+                &entry,
+                human_readable,
+                transcript,
+            )
+            .await?;
+            return Ok(request_id);
+        }
+    };
     // record the key-id for the span
-    oprf_span.record("oprf_key_id", session.key_id().to_string());
+    let oprf_key_id = session.key_id();
+    record_oprf_key_id(&oprf_span, transcript, session_key, oprf_key_id);
+    let _pending_guard = state
+        .session_handoff
+        .as_ref()
+        .map(|handoff| handoff.track(request_id, oprf_key_id));
+    state.session_store.store(request_id, session).await?;
 
-    write_response(response, human_readable, socket).await?;
+    record(transcript, FrameDirection::Sent, || {
+        TranscriptMessage::Response(OprfResponse {
+            commitments: response.commitments.clone(),
+            party_id: state.party_id,
+            oprf_pub_key_with_epoch: response.oprf_pub_key_with_epoch.clone(),
+            batch_commitments: Vec::new(),
+            share_proof: response.share_proof.clone(),
+            signature: response.signature.clone(),
+        })
+    });
+    transport.write_response(&response, human_readable).await?;
 
-    let (challenge_request, still_human_readable) =
-        read_request::<DLogCommitmentsShamir>(socket).await?;
-    if still_human_readable != human_readable {
-        tracing::trace!("user switched encoding between round 1 and round 2. Will reject");
-        return Err(Error::UnexpectedMessage);
-    }
+    let challenge_request = read_challenge(transport, human_readable, transcript).await?;
 
-    let proof_share =
-        challenge(challenge_request, request_id, party_id, threshold, session).await?;
+    let session = state
+        .session_store
+        .take(request_id)
+        .await?
+        .ok_or_else(|| eyre::eyre!("session {request_id} is missing in the session store"))?;
+    let challenge_hash = ChallengeReplayCache::challenge_hash(&challenge_request);
+    let proof_share = challenge(
+        challenge_request,
+        request_id,
+        state,
+        session,
+        state.key_label(oprf_key_id),
+    )
+    .await?;
+    state
+        .challenge_replay_cache
+        .insert(
+            request_id,
+            ReplayEntry {
+                oprf_key_id,
+                blinded_query,
+                commitments: response.commitments,
+                oprf_pub_key_with_epoch: response.oprf_pub_key_with_epoch,
+                share_proof: response.share_proof,
+                challenge_hash,
+                proof_share: proof_share.clone(),
+            },
+        )
+        .await;
 
     tracing::trace!("sending challenge response to client...");
-    write_response(proof_share, human_readable, socket).await?;
+    record(transcript, FrameDirection::Sent, || {
+        TranscriptMessage::ProofShare(proof_share.clone())
+    });
+    transport
+        .write_response(proof_share, human_readable)
+        .await?;
     Ok(request_id)
 }
 
+/// The outcome of [`init_session`].
+enum InitSession {
+    /// A new session with fresh randomness and its permit of the key, if limited.
+    New(Box<(OprfSession, OprfResponse, Option<KeyPermit>)>),
+    /// A finished session that is resumed.
+    Replay(Arc<ReplayEntry>),
+}
+
 #[instrument(level = "info", skip_all)]
 async fn init_session<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
     init_request: OprfRequest<ReqAuth>,
-    party_id: PartyId,
-    req_auth_service: &OprfRequestAuthService<ReqAuth>,
-    oprf_material_store: &OprfKeyMaterialStore,
-) -> Result<(OprfSession, OprfResponse), Error> {
+    state: &OprfModuleState<ReqAuth>,
+    auth: &ConnectionAuth<ReqAuth>,
+) -> Result<InitSession, Error> {
     let start_part_one = Instant::now();
-    tracing::trace!("checking that blinded query is not zero...");
-    // check that blinded query (B) is not the identity element
-    if init_request.blinded_query.is_zero() {
-        return Err(Error::BlindedQueryIsIdentity);
-    }
+    let request_id = init_request.request_id;
+    let oprf_key_id = authorize(
+        &init_request,
+        auth,
+        state.risk_scorer.as_ref(),
+        state.query_age_policy,
+        &state.clock,
+    )
+    .await?;
 
-    tracing::trace!("verifying request with auth service...");
-    let start_verify = Instant::now();
-    let oprf_key_id = req_auth_service.authenticate(&init_request).await?;
-    metrics::request::record_verify_duration(start_verify.elapsed());
+    if let Some(entry) = state.challenge_replay_cache.get(request_id).await {
+        // a resumed session must be the same request, everything else is a reused session-id
+        if entry.oprf_key_id != oprf_key_id || entry.blinded_query != init_request.blinded_query {
+            return Err(Error::SessionReuse(request_id));
+        }
+        tracing::trace!("resuming finished session...");
+        return Ok(InitSession::Replay(entry));
+    }
 
-    tracing::trace!("initiating session with key id {oprf_key_id:?}...");
-    let (session, commitments) = oprf_material_store
-        .partial_commit(init_request.blinded_query, oprf_key_id)
-        .await?;
+    let key_permit = state.acquire_key_permit(oprf_key_id)?;
+    // only durable stores hold a session at this point, stored before a restart of the node
+    let (session, commitments) = if let Some(session) = state.session_store.take(request_id).await?
+    {
+        // same as above, the stored session is dropped on mismatch
+        if session.key_id() != oprf_key_id || session.blinded_query() != init_request.blinded_query
+        {
+            return Err(Error::SessionReuse(request_id));
+        }
+        tracing::debug!("resuming pending session from session store...");
+        metrics::request::inc_session_resumed();
+        let commitments = session.commitments();
+        (session, commitments)
+    } else {
+        state.charge_quota(oprf_key_id, 1)?;
+        tracing::trace!("initiating session with key id {oprf_key_id:?}...");
+        state
+            .oprf_material_store
+            .partial_commit(init_request.blinded_query, oprf_key_id)
+            .await?
+    };
 
-    let response = OprfResponse {
+    let mut response = OprfResponse {
         commitments,
-        party_id,
+        party_id: state.party_id,
         oprf_pub_key_with_epoch: session.public_key_with_epoch(),
+        batch_commitments: Vec::new(),
+        share_proof: state.share_proofs.then(|| share_proof([&session])),
+        signature: None,
     };
-    metrics::request::record_part1_duration(start_part_one.elapsed());
-    Ok((session, response))
+    state.sign_response(&mut response, request_id).await?;
+    metrics::request::record_part1_duration(start_part_one.elapsed(), state.key_label(oprf_key_id));
+    Ok(InitSession::New(Box::new((session, response, key_permit))))
 }
 
+/// Checks the blinded queries and the issued-at timestamp of the request, authenticates it and scores it with the risk scorer, if any.
+///
+/// Returns the [`OprfKeyId`] the request is authenticated for. Not an `async fn`, as the returned future must not capture `init_request` (which is not `Sync`).
+fn authorize<'a, ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    init_request: &'a OprfRequest<ReqAuth>,
+    auth: &'a ConnectionAuth<ReqAuth>,
+    risk_scorer: Option<&'a TimeBoxedRiskScorer>,
+    query_age_policy: QueryAgePolicy,
+    clock: &ClockService,
+) -> impl Future<Output = Result<OprfKeyId, Error>> + Send + 'a {
+    tracing::trace!("checking that blinded queries are not zero...");
+    // check that no blinded query (B) is the identity element
+    let checks = if init_request.blinded_queries().any(|query| query.is_zero()) {
+        Err(Error::BlindedQueryIsIdentity)
+    } else {
+        tracing::trace!("checking issued-at timestamp...");
+        query_age_policy.check(init_request.issued_at, clock.unix_now())
+    };
+    let request_id = init_request.request_id;
+    let issued_at = init_request.issued_at;
+    let authenticate = checks.map(|()| auth.authenticate(init_request));
+    async move {
+        tracing::trace!("verifying request with auth service...");
+        let oprf_key_id = authenticate?.await?;
+
+        if let Some(risk_scorer) = risk_scorer {
+            tracing::trace!("scoring request with risk scorer...");
+            risk_scorer
+                .check(request_id, oprf_key_id, issued_at)
+                .await?;
+        }
+        Ok(oprf_key_id)
+    }
+}
+
+/// The life-cycle of a batch session, i.e., a request with a non-empty [`OprfRequest::batch`].
+///
+/// Same flow as [`partial_oprf_inner`], but the node sends one commitment per query in its [`OprfResponse`], reads an [`OprfBatchChallenge`] and answers with [`OprfBatchProofShares`]. All queries share the authentication and risk scoring of the request and use the same epoch. Requests with more than `max_batch_size` queries are rejected before authentication.
+///
+/// The randomness of a batch session never leaves the task: batch sessions cannot be resumed and are not handed off on restart. A request id that was already used for a single query is rejected.
+#[instrument(level = "info", skip_all, fields(num_queries = init_request.num_queries()))]
+async fn batch_session<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
+    transport: &mut impl SessionTransport,
+    state: &OprfModuleState<ReqAuth>,
+    auth: &ConnectionAuth<ReqAuth>,
+    init_request: OprfRequest<ReqAuth>,
+    human_readable: HumanReadable,
+    transcript: &mut Option<Transcript>,
+    session_key: &mut Option<OprfKeyId>,
+) -> Result<(), Error> {
+    let request_id = init_request.request_id;
+    let num_queries = init_request.num_queries();
+    if num_queries > state.max_batch_size {
+        return Err(BatchError::TooLarge {
+            num_queries,
+            max_batch_size: state.max_batch_size,
+        }
+        .into());
+    }
+    if state.challenge_replay_cache.get(request_id).await.is_some() {
+        return Err(Error::SessionReuse(request_id));
+    }
+    metrics::request::record_batch_size(num_queries);
+
+    let start_part_one = Instant::now();
+    let oprf_key_id = authorize(
+        &init_request,
+        auth,
+        state.risk_scorer.as_ref(),
+        state.query_age_policy,
+        &state.clock,
+    )
+    .await?;
+    record_oprf_key_id(
+        &tracing::Span::current(),
+        transcript,
+        session_key,
+        oprf_key_id,
+    );
+    let _key_permit = state.acquire_key_permit(oprf_key_id)?;
+    state.charge_quota(oprf_key_id, num_queries)?;
+
+    tracing::trace!("initiating batch session with key id {oprf_key_id:?}...");
+    let (sessions, commitments): (Vec<_>, Vec<_>) = state
+        .oprf_material_store
+        .partial_commit_batch(init_request.blinded_queries(), oprf_key_id)
+        .await?
+        .into_iter()
+        .unzip();
+    let mut commitments = commitments.into_iter();
+    let mut response = OprfResponse {
+        commitments: commitments.next().expect("batch has at least one query"),
+        party_id: state.party_id,
+        oprf_pub_key_with_epoch: sessions[0].public_key_with_epoch(),
+        batch_commitments: commitments.collect(),
+        share_proof: state.share_proofs.then(|| share_proof(&sessions)),
+        signature: None,
+    };
+    state.sign_response(&mut response, request_id).await?;
+    let key_label = state.key_label(oprf_key_id);
+    metrics::request::record_part1_duration(start_part_one.elapsed(), key_label.clone());
+    record(transcript, FrameDirection::Sent, || {
+        TranscriptMessage::Response(OprfResponse {
+            commitments: response.commitments.clone(),
+            party_id: response.party_id,
+            oprf_pub_key_with_epoch: response.oprf_pub_key_with_epoch.clone(),
+            batch_commitments: response.batch_commitments.clone(),
+            share_proof: response.share_proof.clone(),
+            signature: response.signature.clone(),
+        })
+    });
+    transport.write_response(response, human_readable).await?;
+
+    let OprfBatchChallenge { challenges } = read_message(
+        transport,
+        human_readable,
+        transcript,
+        TranscriptMessage::BatchChallenge,
+    )
+    .await?;
+    if challenges.len() != num_queries {
+        return Err(BatchError::SizeMismatch {
+            expected: num_queries,
+            got: challenges.len(),
+        }
+        .into());
+    }
+    let mut proof_shares = Vec::with_capacity(num_queries);
+    for (session, challenge_request) in sessions.into_iter().zip(challenges) {
+        proof_shares.push(
+            challenge(
+                challenge_request,
+                request_id,
+                state,
+                session,
+                key_label.clone(),
+            )
+            .await?,
+        );
+    }
+
+    tracing::trace!("sending batch challenge response to client...");
+    let proof_shares = OprfBatchProofShares { proof_shares };
+    record(transcript, FrameDirection::Sent, || {
+        TranscriptMessage::BatchProofShares(proof_shares.clone())
+    });
+    transport.write_response(proof_shares, human_readable).await
+}
+
+/// Creates the [`ShareProof`] for the commitments of the `sessions`, which use the same key material, e.g., the sessions of a batch session.
+fn share_proof<'a>(sessions: impl IntoIterator<Item = &'a OprfSession>) -> ShareProof {
+    let mut sessions = sessions.into_iter().peekable();
+    let public_share = sessions
+        .peek()
+        .expect("at least one session")
+        .public_share();
+    ShareProof {
+        public_share,
+        proofs: sessions.map(OprfSession::prove_share).collect(),
+    }
+}
+
+/// Answers a resumed session from the [`ChallengeReplayCache`].
+///
+/// Sends the cached commitments, reads the challenge and sends the cached proof share if the challenge is the same as the answered one. No new randomness is created. The response carries the cached [`ShareProof`], if the node sent one for the session, and is signed again if the node signs its responses.
 #[instrument(level = "info", skip_all)]
-async fn challenge(
+async fn replay_session<ReqAuth>(
+    transport: &mut impl SessionTransport,
+    request_id: Uuid,
+    state: &OprfModuleState<ReqAuth>,
+    entry: &ReplayEntry,
+    human_readable: HumanReadable,
+    transcript: &mut Option<Transcript>,
+) -> Result<(), Error> {
+    let mut response = OprfResponse {
+        commitments: entry.commitments.clone(),
+        party_id: state.party_id,
+        oprf_pub_key_with_epoch: entry.oprf_pub_key_with_epoch.clone(),
+        batch_commitments: Vec::new(),
+        share_proof: entry.share_proof.clone(),
+        signature: None,
+    };
+    state.sign_response(&mut response, request_id).await?;
+    record(transcript, FrameDirection::Sent, || {
+        TranscriptMessage::Response(OprfResponse {
+            commitments: response.commitments.clone(),
+            party_id: response.party_id,
+            oprf_pub_key_with_epoch: response.oprf_pub_key_with_epoch.clone(),
+            batch_commitments: Vec::new(),
+            share_proof: response.share_proof.clone(),
+            signature: response.signature.clone(),
+        })
+    });
+    transport.write_response(response, human_readable).await?;
+
+    let challenge_request = read_challenge(transport, human_readable, transcript).await?;
+    if ChallengeReplayCache::challenge_hash(&challenge_request) != entry.challenge_hash {
+        return Err(Error::ChallengeMismatch(request_id));
+    }
+
+    tracing::trace!("sending cached challenge response to client...");
+    metrics::request::inc_challenge_replay();
+    record(transcript, FrameDirection::Sent, || {
+        TranscriptMessage::ProofShare(entry.proof_share.clone())
+    });
+    transport
+        .write_response(entry.proof_share.clone(), human_readable)
+        .await
+}
+
+/// Reads the [`DLogCommitmentsShamir`] of the user, which must use the same encoding as the initial request.
+async fn read_challenge(
+    transport: &mut impl SessionTransport,
+    human_readable: HumanReadable,
+    transcript: &mut Option<Transcript>,
+) -> Result<DLogCommitmentsShamir, Error> {
+    read_message(
+        transport,
+        human_readable,
+        transcript,
+        TranscriptMessage::Challenge,
+    )
+    .await
+}
+
+/// Reads a message of the user after the initial request (e.g., the challenge), which must use the same encoding as the initial request. The message is recorded in the transcript as `record_as`.
+async fn read_message<Msg: for<'de> Deserialize<'de> + Clone>(
+    transport: &mut impl SessionTransport,
+    human_readable: HumanReadable,
+    transcript: &mut Option<Transcript>,
+    record_as: impl FnOnce(Msg) -> TranscriptMessage,
+) -> Result<Msg, Error> {
+    let (message, still_human_readable) = transport.read_request::<Msg>().await?;
+    record(transcript, FrameDirection::Received, || {
+        record_as(message.clone())
+    });
+    if still_human_readable != human_readable {
+        tracing::trace!("user switched encoding between round 1 and round 2. Will reject");
+        return Err(Error::UnexpectedMessage);
+    }
+    Ok(message)
+}
+
+/// Finalizes the proof share of the `session` for the `challenge`, bound to the challenge context of the module.
+#[instrument(level = "info", skip_all)]
+async fn challenge<ReqAuth>(
     challenge: DLogCommitmentsShamir,
     request_id: Uuid,
-    party_id: PartyId,
-    threshold: NonZeroU16,
+    state: &OprfModuleState<ReqAuth>,
     session: OprfSession,
+    key_label: Option<String>,
 ) -> Result<DLogProofShareShamir, Error> {
     let start_part_two = Instant::now();
+    let party_id = state.party_id;
+    // keys generated with their own threshold take precedence over the node's threshold
+    let threshold = session.threshold().unwrap_or(state.threshold);
     let coeffs = challenge.get_contributing_parties();
     let num_coeffs = coeffs.len();
     if num_coeffs != usize::from(threshold.get()) {
@@ -339,53 +1248,123 @@ async fn challenge(
     }
 
     tracing::trace!("finalizing session...");
-    let proof_share = OprfKeyMaterialStore::challenge(request_id, party_id, session, challenge);
-    metrics::request::record_part2_duration(start_part_two.elapsed());
+    let proof_share = OprfKeyMaterialStore::challenge(
+        request_id,
+        party_id,
+        session,
+        challenge,
+        state.challenge_context,
+    );
+    metrics::request::record_part2_duration(start_part_two.elapsed(), key_label);
     Ok(proof_share)
 }
 
-/// Attempts to read a `Msg` from the web-socket. Accepts `Text` and `Binary` frames and tries to deserialize the message with either `json` or `cbor`.
+/// The framing of the messages of a session, see [`partial_oprf_inner`].
+///
+/// Implemented by the web-socket transport and the gRPC transport (see `crate::api::grpc`), so both run the same session flow.
+pub(crate) trait SessionTransport: Send {
+    /// Attempts to read a `Msg` from the client and reports whether it was encoded human-readable.
+    ///
+    /// # Errors
+    /// Returns the corresponding error if either the peer closes the connection or if the `Msg` cannot be deserialized.
+    fn read_request<Msg: for<'de> Deserialize<'de>>(
+        &mut self,
+    ) -> impl Future<Output = Result<(Msg, HumanReadable), Error>> + Send;
+
+    /// Attempts to write a `Msg` to the client, encoded human-readable depending on `human_readable`.
+    fn write_response<Msg: Serialize + Send>(
+        &mut self,
+        response: Msg,
+        human_readable: HumanReadable,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+/// The [`SessionTransport`] of a web-socket connection with a single session.
+struct WebSocketTransport {
+    socket: WebSocket,
+    cbor_encoding: CborEncoding,
+}
+
+impl SessionTransport for WebSocketTransport {
+    /// Attempts to read a `Msg` from the web-socket. Accepts `Text` and `Binary` frames and tries to deserialize the message with either `json` or `cbor`.
+    ///
+    /// # Errors
+    /// Returns the corresponding error if either the peer closes the connection (gracefully with a `Close` frame or not) or if the `Msg` cannot be serialized with the corresponding format.
+    #[instrument(level = "info", skip_all)]
+    async fn read_request<Msg: for<'de> Deserialize<'de>>(
+        &mut self,
+    ) -> Result<(Msg, HumanReadable), Error> {
+        tracing::trace!("read request..");
+        let message = self.socket.recv().await.ok_or(Error::ConnectionClosed)??;
+        decode_message(message, self.cbor_encoding)
+    }
+
+    /// Attempts to write a `Msg` to the web-socket. Depending on `human_readable` either sends a `Text` (`json`) frame or `Binary` (`cbor`) frame.
+    #[instrument(level = "info", skip_all)]
+    async fn write_response<Msg: Serialize + Send>(
+        &mut self,
+        response: Msg,
+        human_readable: HumanReadable,
+    ) -> Result<(), Error> {
+        tracing::trace!("write response..");
+        self.socket
+            .send(encode_message(&response, human_readable))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Deserializes a `Text` frame with `json` and a `Binary` frame with `cbor`.
 ///
 /// # Errors
-/// Returns the corresponding error if either the peer closes the connection (gracefully with a `Close` frame or not) or if the `Msg` cannot be serialized with the corresponding format.
-#[instrument(level = "info", skip_all)]
-async fn read_request<Msg: for<'de> Deserialize<'de>>(
-    socket: &mut WebSocket,
+/// Returns [`Error::ConnectionClosed`] for `Close` frames, [`Error::UnexpectedMessage`] for all other frames and the corresponding error if the `Msg` cannot be deserialized. Returns [`Error::NonCanonicalCbor`] if a `Binary` frame is not in the `cbor_encoding`.
+pub(crate) fn decode_message<Msg: for<'de> Deserialize<'de>>(
+    message: ws::Message,
+    cbor_encoding: CborEncoding,
 ) -> Result<(Msg, HumanReadable), Error> {
-    tracing::trace!("read request..");
-    let res = match socket.recv().await.ok_or(Error::ConnectionClosed)?? {
+    let res = match message {
         ws::Message::Text(json) => (
             serde_json::from_slice::<Msg>(json.as_bytes())?,
             HumanReadable::Yes,
         ),
-        ws::Message::Binary(cbor) => (ciborium::from_reader(cbor.as_ref())?, HumanReadable::No),
+        ws::Message::Binary(cbor) => (
+            decode_cbor(cbor.as_ref(), cbor_encoding)?,
+            HumanReadable::No,
+        ),
         ws::Message::Close(_) => return Err(Error::ConnectionClosed),
         _ => return Err(Error::UnexpectedMessage),
     };
     Ok(res)
 }
 
-/// Attempts to write a `Msg` to the web-socket. Depending on `human_readable` either sends a `Text` (`json`) frame or `Binary` (`cbor`) frame.
-#[instrument(level = "info", skip_all)]
-async fn write_response<Msg: Serialize>(
-    response: Msg,
-    human_readable: HumanReadable,
-    socket: &mut WebSocket,
-) -> Result<(), Error> {
-    tracing::trace!("write response..");
-    let msg = match human_readable {
+/// Deserializes `cbor`, which must be in the `cbor_encoding`.
+///
+/// # Errors
+/// Returns the corresponding error if the `Msg` cannot be deserialized or [`Error::NonCanonicalCbor`].
+pub(crate) fn decode_cbor<Msg: for<'de> Deserialize<'de>>(
+    cbor: &[u8],
+    cbor_encoding: CborEncoding,
+) -> Result<Msg, Error> {
+    let msg = ciborium::from_reader(cbor)?;
+    if cbor_encoding == CborEncoding::Canonical && !canonical_cbor::is_canonical(cbor) {
+        tracing::trace!("user sent non-canonical cbor. Will reject");
+        return Err(Error::NonCanonicalCbor);
+    }
+    Ok(msg)
+}
+
+/// Serializes the `msg` as `Text` (`json`) or `Binary` (`cbor`) frame, depending on `human_readable`.
+pub(crate) fn encode_message(msg: &impl Serialize, human_readable: HumanReadable) -> ws::Message {
+    match human_readable {
         HumanReadable::Yes => {
-            let msg = serde_json::to_string(&response).expect("Can serialize response");
-            ws::Message::text(msg)
+            ws::Message::text(serde_json::to_string(msg).expect("Can serialize response"))
         }
         HumanReadable::No => {
             let mut buf = Vec::new();
-            ciborium::into_writer(&response, &mut buf).expect("Can serialize response");
+            ciborium::into_writer(msg, &mut buf).expect("Can serialize response");
             ws::Message::binary(buf)
         }
-    };
-    socket.send(msg).await?;
-    Ok(())
+    }
 }
 
 /// Tries to determine the client version of the request by checking the http header and query parameters. At least one of those must be present. The query takes precedence.
@@ -407,7 +1386,17 @@ fn parse_client_header(
     }
 }
 
-/// Creates a `Router` with a single `/oprf` route.
+/// Responds with the current [`oprf_types::api::ModuleParams`] of the module, including runtime overrides (see [`crate::services::runtime_limits`]).
+async fn module_params<ReqAuth>(
+    State(state): State<OprfModuleState<ReqAuth>>,
+) -> impl IntoResponse {
+    (
+        [(http::header::CACHE_CONTROL, "no-cache")],
+        axum::Json(state.runtime_limits.current().params(state.max_batch_size)),
+    )
+}
+
+/// Creates a `Router` with the `/oprf` and `/params` routes and, unless `max_multiplexed_sessions` is `0`, the `/oprf/multiplex` route (see [`crate::api::multiplex`]).
 ///
 /// The clients will upgrade their connection via the web-socket upgrade protocol. Axum basically supports HTTP/1.1 and HTTP/2.0 web-socket connections, therefore we accept connections with `any`.
 ///
@@ -415,7 +1404,19 @@ fn parse_client_header(
 pub fn routes<ReqAuth: for<'de> Deserialize<'de> + Send + 'static>(
     args: OprfModuleState<ReqAuth>,
 ) -> Router {
-    Router::new()
+    let router = Router::new()
         .route("/oprf", any(oprf_ws_handler))
-        .with_state(args)
+        .route("/params", get(module_params));
+    let router = if args.max_multiplexed_sessions > 0 {
+        router.route(
+            "/oprf/multiplex",
+            any(crate::api::multiplex::oprf_multiplex_handler),
+        )
+    } else {
+        router
+    };
+    router.with_state(args)
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1360 @@
+use std::{num::NonZeroU16, sync::Arc, time::Duration};
+
+use ark_ec::{AffineRepr as _, CurveGroup as _};
+use async_trait::async_trait;
+use axum_test::TestServerBuilder;
+use oprf_core::{ddlog_equality::shamir::DLogShareShamir, oprf::BlindingFactor};
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{
+        Committee, CommitteeMember, OPRF_MAX_MESSAGE_SIZE_HEADER, OprfKeyParams, OprfRequest,
+        OprfResponse, oprf_error_codes, oprf_error_messages,
+    },
+    crypto::PartyId,
+    service::NodeInformation,
+    signed_response::Address,
+    transcript::{Transcript, TranscriptMessage},
+};
+use uuid::Uuid;
+
+use crate::{
+    BuilderError, Environment, OprfServiceBuilder, StartedServices,
+    api::{
+        errors::Error,
+        oprf::{ConnectionAuth, MAX_CACHED_AUTHS, QueryAgePolicy, TimeBoxedAuthService},
+    },
+    clock::MockClock,
+    config::{CborEncoding, CloseFrameVerbosity, OprfNodeServiceConfig},
+    risk_scorer::{RiskDecision, RiskRequest, RiskScorer},
+    services::clock::ClockService,
+    test_kit::MockAuthenticator,
+    test_utils::{
+        MockSecretManager, builder, builder_with_config, builder_with_secret_manager, challenge,
+        default_config,
+    },
+};
+
+/// Returns the wrapped decision, or never finishes if there is none.
+struct FixedRiskScorer(Option<RiskDecision>);
+
+#[async_trait]
+impl RiskScorer for FixedRiskScorer {
+    async fn score(&self, request: &RiskRequest) -> eyre::Result<RiskDecision> {
+        assert_eq!(&*request.module, "/test", "should pass the module path");
+        match self.0 {
+            Some(decision) => Ok(decision),
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[test]
+fn query_age_policy_window() {
+    let now = 1_000_000;
+    let disabled = QueryAgePolicy {
+        max_age: None,
+        max_clock_skew: Duration::from_secs(5),
+    };
+    assert!(
+        disabled.check(None, now).is_ok(),
+        "disabled accepts anything"
+    );
+
+    let policy = QueryAgePolicy {
+        max_age: Some(Duration::from_mins(1)),
+        max_clock_skew: Duration::from_secs(5),
+    };
+    assert!(policy.check(Some(now), now).is_ok(), "fresh query accepted");
+    assert!(
+        policy.check(Some(now - 65), now).is_ok(),
+        "age within skew accepted"
+    );
+    assert!(
+        policy.check(Some(now + 5), now).is_ok(),
+        "future within skew accepted"
+    );
+    assert!(
+        matches!(policy.check(None, now), Err(Error::StaleQuery(None))),
+        "missing timestamp rejected"
+    );
+    assert!(
+        matches!(policy.check(Some(now - 66), now), Err(Error::StaleQuery(_))),
+        "too old rejected"
+    );
+    assert!(
+        matches!(policy.check(Some(now + 6), now), Err(Error::StaleQuery(_))),
+        "too far in future rejected"
+    );
+}
+
+#[tokio::test]
+async fn slow_auth_closes_with_auth_timeout() {
+    let mut config = default_config();
+    config.auth_timeout = Some(Duration::from_millis(50));
+    let router = builder_with_secret_manager(
+        config,
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .module(
+        "/test",
+        MockAuthenticator::allow_all()
+            .with_delay(Duration::from_secs(10))
+            .into_service(),
+    )
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::AUTH_TIMEOUT,
+        "should close with auth timeout"
+    );
+}
+
+#[tokio::test]
+async fn connection_auth_reuses_cacheable_authentications() {
+    let request = |auth: usize| OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(auth),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+    let mock_clock = MockClock::new();
+    let clock: ClockService = Arc::new(mock_clock.clone());
+    let authenticator = Arc::new(MockAuthenticator::allow_all().with_cache(Duration::from_mins(1)));
+    let service = TimeBoxedAuthService::new(authenticator.clone(), Duration::from_secs(5));
+    let auth = ConnectionAuth::new(&service, &clock);
+    for _ in 0..3 {
+        auth.authenticate(&request(42))
+            .await
+            .expect("can authenticate");
+    }
+    assert_eq!(authenticator.calls(), 1, "should reuse the authentication");
+    auth.authenticate(&request(43))
+        .await
+        .expect("can authenticate");
+    assert_eq!(
+        authenticator.calls(),
+        2,
+        "should authenticate other credentials"
+    );
+
+    authenticator.invalidator().invalidate();
+    auth.authenticate(&request(42))
+        .await
+        .expect("can authenticate");
+    assert_eq!(
+        authenticator.calls(),
+        3,
+        "should authenticate after invalidation"
+    );
+
+    mock_clock.advance(Duration::from_mins(1));
+    auth.authenticate(&request(42))
+        .await
+        .expect("can authenticate");
+    assert_eq!(
+        authenticator.calls(),
+        4,
+        "should authenticate after the cached entry expired"
+    );
+
+    let other_connection = ConnectionAuth::new(&service, &clock);
+    other_connection
+        .authenticate(&request(42))
+        .await
+        .expect("can authenticate");
+    assert_eq!(
+        authenticator.calls(),
+        5,
+        "should not share the cache between connections"
+    );
+
+    let uncached = Arc::new(MockAuthenticator::allow_all());
+    let service = TimeBoxedAuthService::new(uncached.clone(), Duration::from_secs(5));
+    let auth = ConnectionAuth::new(&service, &clock);
+    for _ in 0..3 {
+        auth.authenticate(&request(42))
+            .await
+            .expect("can authenticate");
+    }
+    assert_eq!(uncached.calls(), 3, "should not cache by default");
+}
+
+#[tokio::test]
+async fn connection_auth_caches_without_expiry_for_unbounded_ttl() {
+    let clock: ClockService = Arc::new(MockClock::new());
+    let authenticator = Arc::new(MockAuthenticator::allow_all().with_cache(Duration::MAX));
+    let service = TimeBoxedAuthService::new(authenticator.clone(), Duration::from_secs(5));
+    let auth = ConnectionAuth::new(&service, &clock);
+    for _ in 0..3 {
+        auth.authenticate(&auth_request(42))
+            .await
+            .expect("can authenticate");
+    }
+    assert_eq!(authenticator.calls(), 1, "should reuse the authentication");
+}
+
+#[tokio::test]
+async fn connection_auth_drops_expired_and_excess_entries() {
+    let mock_clock = MockClock::new();
+    let clock: ClockService = Arc::new(mock_clock.clone());
+    let authenticator = Arc::new(MockAuthenticator::allow_all().with_cache(Duration::from_mins(1)));
+    let service = TimeBoxedAuthService::new(authenticator.clone(), Duration::from_secs(5));
+    let auth = ConnectionAuth::new(&service, &clock);
+    for key in 0..2 {
+        auth.authenticate(&auth_request(key))
+            .await
+            .expect("can authenticate");
+    }
+    mock_clock.advance(Duration::from_mins(1));
+    auth.authenticate(&auth_request(2))
+        .await
+        .expect("can authenticate");
+    assert_eq!(auth.cache.lock().len(), 1, "should drop expired entries");
+
+    authenticator.invalidator().invalidate();
+    auth.authenticate(&auth_request(3))
+        .await
+        .expect("can authenticate");
+    assert_eq!(
+        auth.cache.lock().len(),
+        1,
+        "should drop invalidated entries"
+    );
+
+    for key in 4..MAX_CACHED_AUTHS + 8 {
+        auth.authenticate(&auth_request(key))
+            .await
+            .expect("can authenticate");
+    }
+    assert_eq!(
+        auth.cache.lock().len(),
+        MAX_CACHED_AUTHS,
+        "should cache at most MAX_CACHED_AUTHS credentials"
+    );
+}
+
+/// A request authenticated with the key id `auth` by the [`MockAuthenticator`].
+fn auth_request(auth: usize) -> OprfRequest<OprfKeyId> {
+    OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(auth),
+        issued_at: None,
+        batch: Vec::new(),
+    }
+}
+
+/// Sends a request to a node with the provided risk scorer and returns the first message of the node.
+async fn first_message_with_risk_scorer(
+    risk_scorer: FixedRiskScorer,
+    fail_open: bool,
+) -> tungstenite::Message {
+    let mut config = default_config();
+    config.risk_scorer_timeout = Duration::from_millis(50);
+    config.risk_scorer_fail_open = Some(fail_open);
+    let router = builder_with_secret_manager(
+        config,
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .risk_scorer(Arc::new(risk_scorer))
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    ws.receive_message().await
+}
+
+#[tokio::test]
+async fn risk_scorer_decides_evaluation() {
+    let message =
+        first_message_with_risk_scorer(FixedRiskScorer(Some(RiskDecision::Allow)), false).await;
+    assert!(
+        matches!(message, tungstenite::Message::Text(_)),
+        "allowed request should be answered, got {message:?}"
+    );
+
+    let message =
+        first_message_with_risk_scorer(FixedRiskScorer(Some(RiskDecision::Deny)), false).await;
+    let tungstenite::Message::Close(Some(frame)) = message else {
+        panic!("expected close frame, got {message:?}");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::RISK_DENIED,
+        "should close with risk denied"
+    );
+
+    let message = first_message_with_risk_scorer(
+        FixedRiskScorer(Some(RiskDecision::Throttle {
+            retry_after: Duration::from_secs(30),
+        })),
+        false,
+    )
+    .await;
+    let tungstenite::Message::Close(Some(frame)) = message else {
+        panic!("expected close frame, got {message:?}");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::RISK_THROTTLED,
+        "should close with risk throttled"
+    );
+    assert_eq!(frame.reason, "retry after 30s", "should send retry-after");
+}
+
+#[tokio::test]
+async fn risk_scorer_timeout_respects_fail_mode() {
+    let message = first_message_with_risk_scorer(FixedRiskScorer(None), false).await;
+    let tungstenite::Message::Close(Some(frame)) = message else {
+        panic!("expected close frame, got {message:?}");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::RISK_DENIED,
+        "fail-closed should deny"
+    );
+
+    let message = first_message_with_risk_scorer(FixedRiskScorer(None), true).await;
+    assert!(
+        matches!(message, tungstenite::Message::Text(_)),
+        "fail-open should answer, got {message:?}"
+    );
+}
+
+#[test]
+fn risk_scorer_after_module_is_rejected() {
+    let err = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .risk_scorer(Arc::new(FixedRiskScorer(None)))
+        .build()
+        .expect_err("should fail");
+    assert!(
+        matches!(err, BuilderError::InvalidConfig(_)),
+        "expected invalid config, got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn resumed_session_replays_proof_share() {
+    let router = builder_with_secret_manager(
+        default_config(),
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let request = OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+    // the first session finishes, but the client pretends it lost the proof share
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&request).await;
+    let response = ws.receive_json::<serde_json::Value>().await;
+    ws.send_json(&challenge(1)).await;
+    let proof_share = ws.receive_json::<serde_json::Value>().await;
+    drop(ws);
+
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&request).await;
+    assert_eq!(
+        ws.receive_json::<serde_json::Value>().await,
+        response,
+        "should send the same commitments"
+    );
+    ws.send_json(&challenge(1)).await;
+    assert_eq!(
+        ws.receive_json::<serde_json::Value>().await,
+        proof_share,
+        "should send the same proof share"
+    );
+    drop(ws);
+
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&request).await;
+    assert_eq!(
+        ws.receive_json::<serde_json::Value>().await,
+        response,
+        "should not create new randomness"
+    );
+    ws.send_json(&challenge(6)).await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::CHALLENGE_MISMATCH,
+        "should reject a different challenge"
+    );
+}
+
+async fn unknown_key_close_frame(environment: Environment) -> tungstenite::protocol::CloseFrame {
+    let router = builder_with_config(OprfNodeServiceConfig::with_default_values(
+        environment,
+        "*".parse().expect("valid version req"),
+    ))
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    frame
+}
+
+#[tokio::test]
+async fn close_frame_details_only_in_dev() {
+    let oprf_key_id = OprfKeyId::from(42usize).to_string();
+
+    let frame = unknown_key_close_frame(Environment::Prod).await;
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::UNKNOWN_OPRF_KEY_ID,
+        "should send the specific code"
+    );
+    assert_eq!(
+        frame.reason,
+        oprf_error_messages::UNKNOWN_OPRF_KEY_ID,
+        "should send the generic reason"
+    );
+
+    let frame = unknown_key_close_frame(Environment::Dev).await;
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::UNKNOWN_OPRF_KEY_ID,
+        "should send the specific code"
+    );
+    assert!(
+        frame.reason.contains(&oprf_key_id),
+        "should send the full error, got {}",
+        frame.reason
+    );
+}
+
+#[tokio::test]
+async fn redacted_close_frame_omits_message_content() {
+    let secret = "secret-auth-token";
+    let mut reasons = Vec::new();
+    for verbosity in [CloseFrameVerbosity::Detailed, CloseFrameVerbosity::Redacted] {
+        let mut config = default_config();
+        config.close_frame_verbosity = Some(verbosity);
+        let router = builder_with_config(config)
+            .module("/test", MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect("Can build");
+        let server = TestServerBuilder::new()
+            .http_transport()
+            .build(router)
+            .expect("Can build test-server");
+        let mut ws = server
+            .get_websocket("/api/test/oprf?version=1.0.0")
+            .await
+            .into_websocket()
+            .await;
+        ws.send_message(tungstenite::Message::text(format!("\"{secret}\"")))
+            .await;
+        let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+            panic!("expected close frame");
+        };
+        assert_eq!(
+            u16::from(frame.code),
+            oprf_error_codes::CORRUPTED_MESSAGE,
+            "should send the specific code"
+        );
+        reasons.push(frame.reason.to_string());
+    }
+    assert!(
+        reasons[0].contains(secret),
+        "detailed reason should echo the message, got {}",
+        reasons[0]
+    );
+    assert_eq!(
+        reasons[1], "invalid json: unexpected data at line 1 column 19",
+        "redacted reason should only keep kind and position"
+    );
+}
+
+#[tokio::test]
+async fn canonical_cbor_encoding_rejects_other_encodings() {
+    let mut config = default_config();
+    config.cbor_encoding = CborEncoding::Canonical;
+    let router = builder_with_config(config)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let request = OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+    let mut cbor = Vec::new();
+    ciborium::into_writer(&request, &mut cbor).expect("Can serialize");
+    let canonical = oprf_types::canonical_cbor::to_vec(&request).expect("Can serialize");
+
+    let mut close_frames = Vec::new();
+    for bytes in [cbor, canonical] {
+        let mut ws = server
+            .get_websocket("/api/test/oprf?version=1.0.0")
+            .await
+            .into_websocket()
+            .await;
+        ws.send_message(tungstenite::Message::binary(bytes)).await;
+        let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+            panic!("expected close frame");
+        };
+        close_frames.push((u16::from(frame.code), frame.reason.to_string()));
+    }
+    assert_eq!(
+        close_frames[0],
+        (
+            oprf_error_codes::CORRUPTED_MESSAGE,
+            oprf_error_messages::NON_CANONICAL_CBOR.to_owned()
+        ),
+        "should reject the field order of ciborium"
+    );
+    assert_eq!(
+        close_frames[1].0,
+        oprf_error_codes::UNKNOWN_OPRF_KEY_ID,
+        "should accept the canonical request"
+    );
+}
+
+#[tokio::test]
+async fn oversized_message_closes_with_limit() {
+    let config = default_config();
+    let max_message_size = config.ws_max_message_size;
+    let router = builder_with_config(config)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let upgrade = server.get_websocket("/api/test/oprf?version=1.0.0").await;
+    assert_eq!(
+        upgrade.header(&OPRF_MAX_MESSAGE_SIZE_HEADER),
+        max_message_size.to_string(),
+        "should advertise the limit"
+    );
+    let mut ws = upgrade.into_websocket().await;
+    ws.send_text("x".repeat(max_message_size + 1)).await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        frame.code,
+        tungstenite::protocol::frame::coding::CloseCode::Size,
+        "should close with message too big"
+    );
+    assert_eq!(
+        frame.reason,
+        format!("message exceeds max message size of {max_message_size} bytes"),
+        "should name the limit"
+    );
+}
+
+#[tokio::test]
+async fn per_key_threshold_overrides_node_threshold() {
+    let router = builder_with_secret_manager(
+        default_config(),
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default()).with_threshold(3)),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let oprf_key_id = OprfKeyId::from(42usize);
+
+    let params = server
+        .get(&format!("/oprf_params/{oprf_key_id}"))
+        .await
+        .json::<OprfKeyParams>();
+    assert_eq!(
+        params.threshold.get(),
+        3,
+        "should report the threshold of the key"
+    );
+    assert_eq!(
+        params.epoch,
+        ShareEpoch::default(),
+        "should report the epoch"
+    );
+    assert_eq!(
+        params.max_message_size,
+        Some(default_config().ws_max_message_size),
+        "should report the max message size"
+    );
+
+    // the node threshold is 2, but the key requires 3 contributing parties
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: oprf_key_id,
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let _commitments = ws.receive_json::<serde_json::Value>().await;
+    ws.send_json(&challenge(1)).await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::COEFFICIENTS_DOES_NOT_EQUAL_THRESHOLD,
+        "should check the contributions against the threshold of the key"
+    );
+}
+
+#[tokio::test]
+async fn signed_responses_bind_the_wallet() {
+    let wallet = k256::ecdsa::SigningKey::from_slice(&[7; 32]).expect("valid key");
+    let address = Address::from_private_key(&wallet);
+    let router = OprfServiceBuilder::init(
+        default_config(),
+        Arc::new(
+            MockSecretManager::single_node(ark_babyjubjub::Fr::from(1337)).with_wallet(wallet),
+        ),
+        StartedServices::default(),
+        &NodeInformation::new(PartyId(0), address.to_checksum(None), NonZeroU16::MIN),
+        "test".to_owned(),
+    )
+    .signed_responses()
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+
+    let request_id = Uuid::new_v4();
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id,
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let response = ws.receive_json::<OprfResponse>().await;
+    assert_eq!(
+        response.signer(request_id),
+        Some(address),
+        "should sign with the wallet of the node"
+    );
+
+    let committee = |address: Address| Committee {
+        members: vec![CommitteeMember {
+            party_id: PartyId(0),
+            address: address.to_checksum(None),
+        }],
+    };
+    oprf_client::verify_response_signature(&response, request_id, &committee(address))
+        .expect("should be signed by the registered wallet");
+    assert_eq!(
+        oprf_client::verify_response_signature(
+            &response,
+            request_id,
+            &committee(Address::repeat_byte(0x42))
+        )
+        .err(),
+        Some(oprf_client::NodeError::InvalidResponseSignature(PartyId(0))),
+        "should reject wallets that are not registered for the party"
+    );
+
+    // the client keeps the signatures of a run until the sessions are finished
+    let service = oprf_client::to_oprf_uri(
+        server.server_address().expect("Has address").as_str(),
+        "test",
+    )
+    .expect("valid uri");
+    let request_id = Uuid::new_v4();
+    let sessions = oprf_client::init_sessions(
+        request_id,
+        std::slice::from_ref(&service),
+        1,
+        OprfRequest {
+            request_id,
+            blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+            auth: OprfKeyId::from(42usize),
+            issued_at: None,
+            batch: Vec::new(),
+        },
+        oprf_client::Connector::Plain,
+    )
+    .await
+    .expect("Can init sessions");
+    sessions
+        .verify_signatures(&committee(address))
+        .expect("should be signed by the registered wallet");
+    assert_eq!(
+        sessions
+            .verify_signatures(&committee(Address::repeat_byte(0x42)))
+            .err(),
+        Some(oprf_client::NodeError::InvalidResponseSignature(PartyId(0))),
+        "should reject wallets that are not registered for the party"
+    );
+}
+
+/// Serves a single node with the given secret at the `test` module.
+fn single_node_server(secret: ark_babyjubjub::Fr) -> (axum_test::TestServer, http::Uri) {
+    let router = OprfServiceBuilder::init(
+        default_config(),
+        Arc::new(MockSecretManager::single_node(secret)),
+        StartedServices::default(),
+        &NodeInformation::new(
+            PartyId(0),
+            "0x0000000000000000000000000000000000000000".to_owned(),
+            NonZeroU16::MIN,
+        ),
+        "test".to_owned(),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let service = oprf_client::to_oprf_uri(
+        server.server_address().expect("Has address").as_str(),
+        "test",
+    )
+    .expect("valid uri");
+    (server, service)
+}
+
+#[tokio::test]
+async fn single_node_mode_end_to_end() {
+    let secret = ark_babyjubjub::Fr::from(1337);
+    let (_server, service) = single_node_server(secret);
+
+    let output = oprf_client::single_node_oprf(
+        &service,
+        ark_babyjubjub::Fq::from(42),
+        BlindingFactor::rand(&mut rand::thread_rng()),
+        ark_babyjubjub::Fq::from(1),
+        OprfKeyId::from(42usize),
+        oprf_client::Connector::Plain,
+    )
+    .await
+    .expect("single-node evaluation should succeed");
+    assert_eq!(
+        output.oprf_public_key.inner(),
+        (ark_babyjubjub::EdwardsAffine::generator() * secret).into_affine(),
+        "should evaluate with the single-node key"
+    );
+    let unblinded_query = oprf_core::oprf::client::blind_query(
+        ark_babyjubjub::Fq::from(42),
+        BlindingFactor::from_scalar(ark_babyjubjub::Fr::from(1)).expect("non-zero"),
+    );
+    assert_eq!(
+        output.unblinded_response,
+        (unblinded_query.blinded_query() * secret).into_affine(),
+        "should compute the plain OPRF of the secret"
+    );
+}
+
+#[tokio::test]
+async fn aggregator_end_to_end() {
+    let secret = ark_babyjubjub::Fr::from(1337);
+    let (_server, service) = single_node_server(secret);
+    let aggregator = oprf_client::aggregator::Aggregator::new(
+        vec![service.clone()],
+        1,
+        oprf_client::Connector::Plain,
+    )
+    .expect("valid services");
+    let aggregator_server = TestServerBuilder::new()
+        .http_transport()
+        .build(aggregator.routes::<OprfKeyId>())
+        .expect("Can build test-server");
+
+    let output = oprf_client::aggregator::aggregated_oprf(
+        &aggregator_server
+            .server_url("/aggregate")
+            .expect("valid url"),
+        ark_babyjubjub::Fq::from(42),
+        BlindingFactor::rand(&mut rand::thread_rng()),
+        ark_babyjubjub::Fq::from(1),
+        OprfKeyId::from(42usize),
+        &reqwest::Client::new(),
+    )
+    .await
+    .expect("aggregated evaluation should succeed");
+    let expected = oprf_client::single_node_oprf(
+        &service,
+        ark_babyjubjub::Fq::from(42),
+        BlindingFactor::rand(&mut rand::thread_rng()),
+        ark_babyjubjub::Fq::from(1),
+        OprfKeyId::from(42usize),
+        oprf_client::Connector::Plain,
+    )
+    .await
+    .expect("single-node evaluation should succeed");
+    assert_eq!(
+        output.output, expected.output,
+        "should compute the same output"
+    );
+    assert_eq!(output.oprf_public_key, expected.oprf_public_key);
+}
+
+/// Serves the modules `/a` and `/b` with the contexts `a` and `b` and the module `/test` without context.
+fn context_modules_server() -> axum_test::TestServer {
+    let router = OprfServiceBuilder::init(
+        default_config(),
+        Arc::new(MockSecretManager::single_node(ark_babyjubjub::Fr::from(
+            1337,
+        ))),
+        StartedServices::default(),
+        &NodeInformation::new(
+            PartyId(0),
+            "0x0000000000000000000000000000000000000000".to_owned(),
+            NonZeroU16::MIN,
+        ),
+        "test".to_owned(),
+    )
+    .module_context("/a/", "a")
+    .module_context("/b", "b")
+    .module("/a", MockAuthenticator::allow_all().into_service())
+    .module("/b", MockAuthenticator::allow_all().into_service())
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server")
+}
+
+/// Evaluates the query `42` at `module` of `server` with `domain`.
+async fn evaluate_at_module(
+    server: &axum_test::TestServer,
+    module: &str,
+    domain: oprf_client::OutputDomain,
+) -> Result<oprf_client::VerifiableOprfOutput, oprf_client::Error> {
+    let address = server.server_address().expect("Has address");
+    let service = oprf_client::to_oprf_uri(address.as_str(), module).expect("valid uri");
+    oprf_client::single_node_oprf(
+        &service,
+        ark_babyjubjub::Fq::from(42),
+        BlindingFactor::rand(&mut rand::thread_rng()),
+        domain,
+        OprfKeyId::from(42usize),
+        oprf_client::Connector::Plain,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn module_context_binds_the_proofs() {
+    let server = context_modules_server();
+    let domain = oprf_client::OutputDomain::new(ark_babyjubjub::Fq::from(1));
+
+    let output = evaluate_at_module(&server, "a", domain.clone().with_context("a"))
+        .await
+        .expect("should verify with the context of the module");
+    assert_eq!(
+        output.context,
+        oprf_core::oprf::client::query_context("a").inner()
+    );
+    let plain = evaluate_at_module(&server, "test", domain.clone())
+        .await
+        .expect("module without context should verify without context");
+    assert_eq!(plain.context, ark_babyjubjub::Fq::default());
+    assert_ne!(
+        output.output, plain.output,
+        "the context should change the output"
+    );
+
+    for (module, domain) in [("a", domain.clone()), ("test", domain.with_context("a"))] {
+        let err = evaluate_at_module(&server, module, domain)
+            .await
+            .expect_err("should not verify with another context");
+        assert!(
+            matches!(err, oprf_client::Error::InvalidDLogProof),
+            "expected InvalidDLogProof, got {err:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn query_bound_to_context_fails_at_other_module() {
+    let server = context_modules_server();
+    let domain = oprf_client::OutputDomain::new(ark_babyjubjub::Fq::from(1)).with_context("a");
+    evaluate_at_module(&server, "a", domain.clone())
+        .await
+        .expect("should verify at the module of the context");
+    // module b binds its proof share to context b, so the replayed query does not verify in context a
+    let err = evaluate_at_module(&server, "b", domain)
+        .await
+        .expect_err("should not verify at a module with another context");
+    assert!(
+        matches!(err, oprf_client::Error::InvalidDLogProof),
+        "expected InvalidDLogProof, got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn batch_session_end_to_end() {
+    let secret = ark_babyjubjub::Fr::from(1337);
+    let mut config = default_config();
+    config.max_batch_size = 3;
+    let router = OprfServiceBuilder::init(
+        config,
+        Arc::new(MockSecretManager::single_node(secret)),
+        StartedServices::default(),
+        &NodeInformation::new(
+            PartyId(0),
+            "0x0000000000000000000000000000000000000000".to_owned(),
+            NonZeroU16::MIN,
+        ),
+        "test".to_owned(),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let params = server.get("/oprf_params/42").await.json::<OprfKeyParams>();
+    assert_eq!(params.batch_size(), 3, "should report the max batch size");
+    let service = oprf_client::to_oprf_uri(
+        server.server_address().expect("Has address").as_str(),
+        "test",
+    )
+    .expect("valid uri");
+
+    let blinding_factor =
+        || BlindingFactor::from_scalar(ark_babyjubjub::Fr::from(1)).expect("non-zero");
+    let queries = (0..5u64)
+        .map(|query| oprf_client::BatchQuery {
+            query: ark_babyjubjub::Fq::from(query),
+            blinding_factor: blinding_factor(),
+        })
+        .collect::<Vec<_>>();
+    let outputs = oprf_client::distributed_oprf_batch(
+        std::slice::from_ref(&service),
+        1,
+        queries,
+        ark_babyjubjub::Fq::from(1),
+        OprfKeyId::from(42usize),
+        oprf_client::Connector::Plain,
+        oprf_client::BatchConfig {
+            concurrency: 2,
+            max_batch_size: params.batch_size(),
+            ..Default::default()
+        },
+    )
+    .await;
+    assert_eq!(outputs.len(), 5, "should return one output per query");
+    for (query, output) in outputs.into_iter().enumerate() {
+        let output = output.expect("batch evaluation should succeed");
+        let blinded_query = oprf_core::oprf::client::blind_query(
+            ark_babyjubjub::Fq::from(query as u64),
+            blinding_factor(),
+        );
+        assert_eq!(
+            output.unblinded_response,
+            (blinded_query.blinded_query() * secret).into_affine(),
+            "should evaluate query {query} in order"
+        );
+    }
+}
+
+#[tokio::test]
+async fn share_proofs_end_to_end() {
+    let secret = ark_babyjubjub::Fr::from(1337);
+    let mut config = default_config();
+    config.max_batch_size = 2;
+    let router = OprfServiceBuilder::init(
+        config,
+        Arc::new(MockSecretManager::single_node(secret)),
+        StartedServices::default(),
+        &NodeInformation::new(
+            PartyId(0),
+            "0x0000000000000000000000000000000000000000".to_owned(),
+            NonZeroU16::MIN,
+        ),
+        "test".to_owned(),
+    )
+    .share_proofs()
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+
+    let blinded_query = ark_babyjubjub::EdwardsAffine::generator();
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query,
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let response = ws.receive_json::<OprfResponse>().await;
+    let share_proof = response.share_proof.expect("should send a share proof");
+    assert_eq!(
+        share_proof.public_share,
+        DLogShareShamir::from(secret).public_share(),
+        "should send the public share of the node"
+    );
+    assert_eq!(share_proof.proofs.len(), 1, "should prove every query");
+    response
+        .commitments
+        .verify_share_proof(
+            &share_proof.proofs[0],
+            blinded_query,
+            share_proof.public_share,
+        )
+        .expect("should prove the commitments");
+
+    // the client verifies the proofs of single and batch sessions before finishing them
+    let service = oprf_client::to_oprf_uri(
+        server.server_address().expect("Has address").as_str(),
+        "test",
+    )
+    .expect("valid uri");
+    oprf_client::distributed_oprf(
+        std::slice::from_ref(&service),
+        1,
+        ark_babyjubjub::Fq::from(42),
+        BlindingFactor::rand(&mut rand::thread_rng()),
+        ark_babyjubjub::Fq::from(1),
+        OprfKeyId::from(42usize),
+        oprf_client::Connector::Plain,
+    )
+    .await
+    .expect("evaluation with share proofs should succeed");
+    let queries = (0..2u64)
+        .map(|query| oprf_client::BatchQuery {
+            query: ark_babyjubjub::Fq::from(query),
+            blinding_factor: BlindingFactor::rand(&mut rand::thread_rng()),
+        })
+        .collect::<Vec<_>>();
+    let outputs = oprf_client::distributed_oprf_batch(
+        std::slice::from_ref(&service),
+        1,
+        queries,
+        ark_babyjubjub::Fq::from(1),
+        OprfKeyId::from(42usize),
+        oprf_client::Connector::Plain,
+        oprf_client::BatchConfig {
+            concurrency: 1,
+            max_batch_size: 2,
+            require_share_proofs: true,
+        },
+    )
+    .await;
+    for output in outputs {
+        output.expect("batch evaluation with share proofs should succeed");
+    }
+}
+
+#[tokio::test]
+async fn resumed_session_replays_share_proof() {
+    let router = OprfServiceBuilder::init(
+        default_config(),
+        Arc::new(MockSecretManager::single_node(ark_babyjubjub::Fr::from(
+            1337,
+        ))),
+        StartedServices::default(),
+        &NodeInformation::new(
+            PartyId(0),
+            "0x0000000000000000000000000000000000000000".to_owned(),
+            NonZeroU16::MIN,
+        ),
+        "test".to_owned(),
+    )
+    .share_proofs()
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let service = oprf_client::to_oprf_uri(
+        server.server_address().expect("Has address").as_str(),
+        "test",
+    )
+    .expect("valid uri");
+    let aggregator =
+        oprf_client::aggregator::Aggregator::new(vec![service], 1, oprf_client::Connector::Plain)
+            .expect("valid services")
+            .require_share_proofs();
+    let request = OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+
+    let first = aggregator
+        .aggregate(request.clone())
+        .await
+        .expect("evaluation with share proofs should succeed");
+    // the same request resumes the finished session, which must carry the share proof again
+    let resumed = aggregator
+        .aggregate(request)
+        .await
+        .expect("resumed evaluation with share proofs should succeed");
+    assert_eq!(
+        resumed.blinded_response, first.blinded_response,
+        "should replay the same session"
+    );
+}
+
+#[tokio::test]
+async fn required_share_proofs_reject_nodes_without_proofs() {
+    let (_server, service) = single_node_server(ark_babyjubjub::Fr::from(1337));
+    let outputs = oprf_client::distributed_oprf_batch(
+        std::slice::from_ref(&service),
+        1,
+        vec![oprf_client::BatchQuery {
+            query: ark_babyjubjub::Fq::from(42),
+            blinding_factor: BlindingFactor::rand(&mut rand::thread_rng()),
+        }],
+        ark_babyjubjub::Fq::from(1),
+        OprfKeyId::from(42usize),
+        oprf_client::Connector::Plain,
+        oprf_client::BatchConfig {
+            require_share_proofs: true,
+            ..Default::default()
+        },
+    )
+    .await;
+    let Some(Err(oprf_client::Error::NodeErrorDisagreement(errors))) = outputs.into_iter().next()
+    else {
+        panic!("evaluation without share proofs should fail");
+    };
+    assert_eq!(
+        errors,
+        [oprf_client::NodeError::InvalidShareProof],
+        "should reject the response without share proof"
+    );
+}
+
+#[tokio::test]
+async fn batch_too_large() {
+    let router = builder_with_secret_manager(
+        default_config(),
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: vec![ark_babyjubjub::EdwardsAffine::generator()],
+    })
+    .await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::BATCH_TOO_LARGE,
+        "should reject batches larger than the max batch size"
+    );
+    assert_eq!(
+        frame.reason.as_str(),
+        "batch exceeds max batch size of 1 queries",
+        "should report the max batch size"
+    );
+}
+
+#[tokio::test]
+async fn session_writes_transcript() {
+    let transcript_dir = std::env::temp_dir().join(format!("oprf-transcripts-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&transcript_dir).expect("Can create transcript dir");
+    let mut config = default_config();
+    config.transcript_dir = Some(transcript_dir.clone());
+    config.websocket_shutdown_timeout = Duration::from_millis(100);
+    let router = builder_with_secret_manager(
+        config,
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let request_id = Uuid::new_v4();
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id,
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let response = ws.receive_json::<OprfResponse>().await;
+    assert_eq!(response.party_id, PartyId(0), "should answer as party 0");
+    ws.send_json(&challenge(1)).await;
+    let proof_share = ws.receive_json::<serde_json::Value>().await;
+    assert!(!proof_share.is_null(), "should receive the proof share");
+    drop(ws);
+
+    let path = transcript_dir.join(format!("{request_id}-node-0.json"));
+    let mut transcript = None;
+    for _ in 0..100 {
+        if let Ok(json) = std::fs::read(&path) {
+            transcript =
+                Some(serde_json::from_slice::<Transcript>(&json).expect("valid transcript"));
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    std::fs::remove_dir_all(&transcript_dir).expect("Can remove transcript dir");
+    let transcript = transcript.expect("transcript should be written");
+    assert_eq!(
+        transcript.oprf_key_id,
+        Some(OprfKeyId::from(42usize)),
+        "should record the authenticated key"
+    );
+    assert!(
+        matches!(
+            transcript
+                .frames
+                .iter()
+                .map(|frame| &frame.message)
+                .collect::<Vec<_>>()
+                .as_slice(),
+            [
+                TranscriptMessage::Request { .. },
+                TranscriptMessage::Response(_),
+                TranscriptMessage::Challenge(_),
+                TranscriptMessage::ProofShare(_),
+            ]
+        ),
+        "should record all frames, got {:?}",
+        transcript.frames
+    );
+}
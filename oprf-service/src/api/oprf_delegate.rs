@@ -38,13 +38,16 @@ pub(crate) struct DelegateOprfState {
 /// Versioning (semver)] as a query parameter of the request URL. Requests without a version,
 /// or with a version that does not satisfy `state.version_req`, are rejected with `400 Bad
 /// Request` before any work is done.
+/// The same applies to requests announcing a `schema` fingerprint different from our
+/// [`oprf_types::api::SchemaFingerprint::CURRENT`].
 ///
 /// ## Delegation
 ///
 /// The request body is forwarded as-is to [`oprf_client::distributed_oprf_core`], which talks
 /// to `state.services` and collects `state.threshold` responses. On success, the OPRF public
 /// key/epoch, challenge, and per-service responses are returned to the client as `200 OK` so it
-/// can finish the protocol itself.
+/// can finish the protocol itself. Requests with a non-empty [`oprf_types::api::OprfRequest::batch`]
+/// are rejected with `400 Bad Request`, the delegate only evaluates single queries.
 ///
 /// ## Error Handling
 ///
@@ -68,6 +71,9 @@ async fn delegate_oprf_handler<ReqAuth>(
 where
     ReqAuth: Serialize + for<'de> Deserialize<'de> + Clone + Send + 'static,
 {
+    if let Err(msg) = query_version.check_schema() {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
     if let Some(ProtocolVersion(client_version)) = query_version.version {
         tracing::trace!(%client_version, "received delegate OPRF request with version");
         if !state.version_req.matches(&client_version) {
@@ -84,6 +90,15 @@ where
         return (StatusCode::BAD_REQUEST, "missing client version").into_response();
     }
 
+    if !req.batch.is_empty() {
+        tracing::warn!(user_error = true, "batch delegate request");
+        return (
+            StatusCode::BAD_REQUEST,
+            "batch requests are not supported by the delegate",
+        )
+            .into_response();
+    }
+
     tracing::trace!("received delegate OPRF request");
     metrics::request::inc_delegate_request();
     match oprf_client::distributed_oprf_core(
@@ -0,0 +1,67 @@
+//! Replica Endpoint
+//!
+//! Exposes the following API endpoint if enabled with [`crate::OprfServiceBuilder::replica_snapshot`]:
+//!
+//! - `POST /replica/snapshot` – returns the encrypted key material cached at this node to an authenticated replica of the same node, see [`crate::services::replica_snapshot`].
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+};
+
+use crate::services::{
+    oprf_key_material_store::OprfKeyMaterialStore,
+    replica_snapshot::{ReplicaKeys, SnapshotRequest},
+};
+
+#[derive(Clone)]
+struct ReplicaState {
+    keys: ReplicaKeys,
+    wallet_address: String,
+    oprf_material_store: OprfKeyMaterialStore,
+}
+
+/// Create a router containing the replica endpoints.
+pub(crate) fn routes(
+    keys: ReplicaKeys,
+    wallet_address: String,
+    oprf_material_store: OprfKeyMaterialStore,
+) -> Router {
+    Router::new()
+        .route("/replica/snapshot", post(replica_snapshot))
+        .with_state(ReplicaState {
+            keys,
+            wallet_address,
+            oprf_material_store,
+        })
+}
+
+/// Responds with the encrypted snapshot of the key-material store.
+///
+/// Returns `200 OK` with the encrypted snapshot.
+/// Returns `401 Unauthorized` if the request is not authenticated by a replica of this node.
+async fn replica_snapshot(
+    State(state): State<ReplicaState>,
+    Json(request): Json<SnapshotRequest>,
+) -> Response {
+    if let Err(err) = state.keys.verify(&request, &state.wallet_address) {
+        tracing::warn!(%err, "rejected replica snapshot request");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let snapshot = state.oprf_material_store.snapshot();
+    match state.keys.seal(&request, &snapshot) {
+        Ok(sealed) => {
+            tracing::info!("sending snapshot with {} keys to replica", snapshot.len());
+            (StatusCode::OK, sealed).into_response()
+        }
+        Err(err) => {
+            tracing::error!(?err, "cannot seal replica snapshot");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use axum_test::TestServerBuilder;
+use oprf_types::OprfKeyId;
+use secrecy::SecretString;
+
+use crate::{
+    test_kit::{MockAuthenticator, StaticSecretManager},
+    test_utils::{builder_with_secret_manager, default_config},
+};
+
+async fn bootstrapped_node(sibling: &url::Url, replica_secret: &str) -> axum_test::TestServer {
+    let router = builder_with_secret_manager(
+        default_config(),
+        Arc::new(StaticSecretManager::single_node()),
+    )
+    .bootstrap_from_replica(
+        sibling,
+        &SecretString::from(replica_secret),
+        &reqwest::Client::new(),
+    )
+    .await
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build node");
+    TestServerBuilder::new()
+        .build(router)
+        .expect("Can build node test-server")
+}
+
+#[tokio::test]
+async fn replica_bootstraps_from_sibling_snapshot() {
+    let oprf_key_id = OprfKeyId::from(42usize);
+    let sibling = TestServerBuilder::new()
+        .http_transport()
+        .build(
+            builder_with_secret_manager(
+                default_config(),
+                Arc::new(
+                    StaticSecretManager::single_node()
+                        .with_random_key(oprf_key_id, &mut rand::thread_rng()),
+                ),
+            )
+            .replica_snapshot(&SecretString::from("replica-secret"))
+            .module("/test", MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect("Can build sibling"),
+        )
+        .expect("Can build sibling test-server");
+    // cache the key at the sibling
+    sibling
+        .get(&format!("/oprf_pub/{oprf_key_id}"))
+        .await
+        .assert_status_ok();
+    let sibling_url = sibling.server_address().expect("Has address");
+
+    // the new node has no keys in its secret manager, so it can only serve the key from the snapshot
+    let node = bootstrapped_node(&sibling_url, "replica-secret").await;
+    node.get(&format!("/oprf_pub/{oprf_key_id}"))
+        .await
+        .assert_status_ok();
+
+    let node = bootstrapped_node(&sibling_url, "wrong-secret").await;
+    node.get(&format!("/oprf_pub/{oprf_key_id}"))
+        .await
+        .assert_status_not_found();
+}
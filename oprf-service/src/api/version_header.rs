@@ -1,7 +1,10 @@
 use axum_extra::headers::{self, Header};
 use http::HeaderValue;
+use oprf_types::api::SchemaFingerprint;
 use serde::{Deserialize, de};
 
+use crate::metrics;
+
 /// A custom header that clients need to send to OPRF servers to indicate their version.
 #[derive(Debug, Clone)]
 pub(crate) struct ProtocolVersion(pub(crate) semver::Version);
@@ -9,6 +12,28 @@ pub(crate) struct ProtocolVersion(pub(crate) semver::Version);
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct ProtocolVersionQuery {
     pub(crate) version: Option<ProtocolVersion>,
+    /// The [`SchemaFingerprint`] of the client. Optional, as older clients don't send it.
+    pub(crate) schema: Option<SchemaFingerprint>,
+}
+
+impl ProtocolVersionQuery {
+    /// Checks that the client's [`SchemaFingerprint`] (if provided) matches ours.
+    ///
+    /// Returns the message for the `400 Bad Request` response on mismatch.
+    pub(crate) fn check_schema(&self) -> Result<(), String> {
+        match self.schema {
+            Some(client_schema) if client_schema != SchemaFingerprint::CURRENT => {
+                let msg = format!(
+                    "incompatible message schema, expected: {} got: {client_schema}",
+                    SchemaFingerprint::CURRENT
+                );
+                tracing::warn!(user_error = true, "{msg}");
+                metrics::request::inc_client_schema_mismatch();
+                Err(msg)
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl<'a> de::Deserialize<'a> for ProtocolVersion {
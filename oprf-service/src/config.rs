@@ -6,6 +6,7 @@
 //! The struct supports:
 //! - Required fields: `environment` and `version_req`.
 //! - Optional fields with sensible defaults (see below).
+//! - Defaults that depend on the `environment`, bundled as [`EnvironmentPreset`] and overridable per field.
 //! - Serde deserialization (with [`humantime_serde`] for durations).
 //!
 //! # Defaults
@@ -14,19 +15,255 @@
 //! |----------------------------------|------------|
 //! | `ws_max_message_size`            | 1024 bytes |
 //! | `session_lifetime`               | 30 s       |
+//! | `max_multiplexed_sessions`       | 32         |
+//! | `max_batch_size`                 | 1          |
+//! | `epoch_notifications_lifetime`   | 10 min     |
+//! | `max_query_age`                  | disabled   |
+//! | `max_clock_skew`                 | 5 s        |
+//! | `auth_timeout`                   | by `environment`, see [`EnvironmentPreset`] |
+//! | `risk_scorer_timeout`            | 1 s        |
+//! | `risk_scorer_fail_open`          | by `environment`, see [`EnvironmentPreset`] |
+//! | `challenge_replay_max_capacity`  | 10_000     |
+//! | `session_handoff_grace_period`   | 30 s       |
+//! | `session_persistence`            | `memory`   |
+//! | `committee_poll_interval`        | 30 s       |
+//! | `key_reconciliation_interval`    | 5 min      |
+//! | `key_reconciliation_concurrency` | 8          |
+//! | `key_reconciliation_max_keys`    | 1_000      |
 //! | `store_max_capacity`             | 10_000     |
 //! | `store_ttl`                      | 1 day      |
 //! | `store_tti`                      | 1 h        |
+//! | `store_eviction_policy`          | `tiny_lfu` |
+//! | `store_negative_max_capacity`    | by `environment`, see [`EnvironmentPreset`] |
+//! | `store_negative_ttl`             | 5 s        |
+//! | `preload_oprf_key_ids`           | empty      |
+//! | `transcript_dir`                 | disabled   |
+//! | `close_frame_verbosity`          | by `environment`, see [`EnvironmentPreset`] |
+//! | `grpc`                           | `false`    |
+//! | `cbor_encoding`                  | `any`      |
+//! | `metrics_endpoint`               | `false`    |
+//! | `root_path`                      | none       |
+//! | `accept_changed_party_id`        | `false`    |
+//! | `public_key_history_retention`   | 100 epochs |
+//! | `chaos`                          | disabled   |
+//! | `key_quota`                      | disabled   |
+//! | `key_concurrency`                | disabled   |
+//! | `key_metrics`                    | disabled   |
 
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
 
 use nodes_common::Environment;
+use oprf_types::OprfKeyId;
 use semver::VersionReq;
 use serde::{
     Deserialize,
     de::{self},
 };
 
+/// How much an OPRF node tells clients about a failed session in the close frame.
+///
+/// Close frames reach untrusted clients. Independent of the verbosity, the close code is always the specific [`oprf_types::api::oprf_error_codes`] code, so clients can react to the error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum CloseFrameVerbosity {
+    /// The reason contains the full error, including key ids, session ids and internal errors. Errors of messages that cannot be deserialized echo fragments of the message, which may include secrets of the authentication. Only meant for local development.
+    Detailed,
+    /// Like [`CloseFrameVerbosity::Detailed`], but errors of messages that cannot be deserialized only name the kind and position of the error, never the content of the message. The full error is still logged by the node.
+    Redacted,
+    /// The reason is a fixed message per error that never contains request data or internal details.
+    Generic,
+}
+
+/// Where an OPRF node keeps its pending sessions, see [`OprfNodeServiceConfig::session_persistence`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SessionPersistence {
+    /// Pending sessions only live in the memory of the node and are lost on restart.
+    #[default]
+    Memory,
+    /// Pending sessions are kept across restarts in a durable session store (see [`crate::session_store::OprfSessionStore::is_durable`]), e.g. the [`crate::session_store::postgres::PostgresSessionStore`]. Every session waits for the store between the two rounds, which trades latency for durability.
+    Durable,
+}
+
+/// Which `cbor` encoding an OPRF node accepts from clients, see [`OprfNodeServiceConfig::cbor_encoding`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum CborEncoding {
+    /// Any valid `cbor` encoding of the messages.
+    #[default]
+    Any,
+    /// Only the canonical encoding (see [`oprf_types::canonical_cbor`]), e.g., if requests are hashed or signed by their encoding. Other messages are rejected with [`oprf_types::api::oprf_error_codes::CORRUPTED_MESSAGE`]. Clients encode canonically with the `canonical-cbor` feature.
+    Canonical,
+}
+
+/// Which key the key-material store evicts when it is full, see [`OprfNodeServiceConfig::store_eviction_policy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum StoreEvictionPolicy {
+    /// Keys are admitted and evicted by their estimated access frequency. A full store may reject a cold key instead of evicting a hot one, so the cold key is loaded from the secret manager on every request.
+    #[default]
+    TinyLfu,
+    /// The least recently used key is evicted. Every loaded key is cached, which suits a hot set that shifts over time, e.g., nodes serving more keys than `store_max_capacity`.
+    Lru,
+}
+
+/// Artificial latency and errors injected into the sessions of the OPRF modules, see [`OprfNodeServiceConfig::chaos`].
+///
+/// Every request to an OPRF module (web-socket upgrade, gRPC call or delegate request) is delayed by `latency` with probability `latency_rate` and afterwards rejected with `503 Service Unavailable` with probability `error_rate`. Both are decided independently per request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[non_exhaustive]
+pub struct ChaosConfig {
+    /// Probability (in `[0, 1]`) that a request is delayed by `latency`.
+    #[serde(default)]
+    pub latency_rate: f64,
+    /// Latency added to delayed requests.
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub latency: Duration,
+    /// Probability (in `[0, 1]`) that a request is rejected with `503 Service Unavailable`.
+    #[serde(default)]
+    pub error_rate: f64,
+}
+
+/// Quota of evaluations per [`OprfKeyId`], see [`OprfNodeServiceConfig::key_quota`].
+///
+/// Every key may consume `max_evaluations` per `window` and at most `burst` at once. Every blinded query counts as one evaluation, so `burst` must be at least `max_batch_size`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[non_exhaustive]
+pub struct KeyQuotaConfig {
+    /// Number of evaluations a key may consume per `window`.
+    pub max_evaluations: NonZeroU32,
+    /// The window in which `max_evaluations` are refilled.
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    /// Max number of evaluations a key may consume at once, e.g. after being idle.
+    ///
+    /// Defaults to `max_evaluations`.
+    #[serde(default)]
+    pub burst: Option<NonZeroU32>,
+}
+
+/// Limit of concurrent sessions per [`OprfKeyId`], see [`OprfNodeServiceConfig::key_concurrency`].
+///
+/// A batch session counts as one session, independent of its number of queries.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[non_exhaustive]
+pub struct KeyConcurrencyConfig {
+    /// Number of sessions a key may run concurrently.
+    pub max_sessions: NonZeroUsize,
+    /// Limits of single keys that replace `max_sessions`, e.g., for keys of relying parties with more traffic.
+    #[serde(default)]
+    pub overrides: HashMap<OprfKeyId, NonZeroUsize>,
+}
+
+impl KeyConcurrencyConfig {
+    /// Limits every key to `max_sessions` concurrent sessions, without overrides.
+    #[must_use]
+    pub fn new(max_sessions: NonZeroUsize) -> Self {
+        Self {
+            max_sessions,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Returns the limit of `oprf_key_id`.
+    #[must_use]
+    pub fn max_sessions_of(&self, oprf_key_id: OprfKeyId) -> NonZeroUsize {
+        self.overrides
+            .get(&oprf_key_id)
+            .copied()
+            .unwrap_or(self.max_sessions)
+    }
+}
+
+/// Per-key labels of the request metrics, see [`OprfNodeServiceConfig::key_metrics`].
+///
+/// Every labeled key adds time series to every metric with the label, so only the keys of the `allowlist` and the first `max_keys` other keys served by the node get their own `oprf_key_id` label. All further keys share the label `other`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[non_exhaustive]
+pub struct KeyMetricsConfig {
+    /// Number of keys outside the `allowlist` that get their own label, in the order of their first session.
+    pub max_keys: usize,
+    /// Keys that always get their own label, e.g., the keys of known relying parties.
+    #[serde(default)]
+    pub allowlist: Vec<OprfKeyId>,
+}
+
+impl KeyMetricsConfig {
+    /// Labels the first `max_keys` keys, without allowlist.
+    #[must_use]
+    pub fn new(max_keys: usize) -> Self {
+        Self {
+            max_keys,
+            allowlist: Vec::new(),
+        }
+    }
+}
+
+/// The defaults of a node that depend on its [`Environment`].
+///
+/// Production deployments get safe defaults without configuring them, while local development gets defaults that ease debugging. Every value can be overridden by the corresponding field of the [`OprfNodeServiceConfig`], see [`OprfNodeServiceConfig::preset`].
+///
+/// | Value                         | `dev`      | `test`    | `stage`   | `prod`    |
+/// |-------------------------------|------------|-----------|-----------|-----------|
+/// | `log_filter`                  | `debug`    | `debug`   | `info`    | `info`    |
+/// | `close_frame_verbosity`       | `Redacted` | `Generic` | `Generic` | `Generic` |
+/// | `auth_timeout`                | 10 s       | 5 s       | 5 s       | 5 s       |
+/// | `risk_scorer_fail_open`       | `true`     | `true`    | `false`   | `false`   |
+/// | `store_negative_max_capacity` | 0          | 10_000    | 10_000    | 10_000    |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EnvironmentPreset {
+    /// Log filter (in `RUST_LOG` syntax) for hosts that do not configure one. Not applied by the node itself, as logging is set up by the host.
+    pub log_filter: &'static str,
+    /// How much failed sessions tell the client in the close frame.
+    pub close_frame_verbosity: CloseFrameVerbosity,
+    /// Max time a single `authenticate` call may take.
+    pub auth_timeout: Duration,
+    /// Whether requests are evaluated if the risk scorer fails or times out.
+    pub risk_scorer_fail_open: bool,
+    /// Max number of unknown or deleted keys remembered by the key-material store. Disabled in `dev`, so keys created during development are served right away.
+    pub store_negative_max_capacity: u64,
+}
+
+impl EnvironmentPreset {
+    /// Returns the preset of the `environment`.
+    #[must_use]
+    pub fn for_environment(environment: Environment) -> Self {
+        let prod = Self {
+            log_filter: "info",
+            close_frame_verbosity: CloseFrameVerbosity::Generic,
+            auth_timeout: Duration::from_secs(5),
+            risk_scorer_fail_open: false,
+            store_negative_max_capacity: 10_000,
+        };
+        match environment {
+            Environment::Prod | Environment::Stage => prod,
+            Environment::Test => Self {
+                log_filter: "debug",
+                risk_scorer_fail_open: true,
+                ..prod
+            },
+            Environment::Dev => Self {
+                log_filter: "debug",
+                close_frame_verbosity: CloseFrameVerbosity::Redacted,
+                auth_timeout: Duration::from_secs(10),
+                risk_scorer_fail_open: true,
+                store_negative_max_capacity: 0,
+            },
+        }
+    }
+}
+
 /// The configuration for TACEO:OPRF core functionality.
 #[derive(Debug, Clone, Deserialize)]
 #[non_exhaustive]
@@ -40,6 +277,8 @@ pub struct OprfNodeServiceConfig {
 
     /// Max message size the websocket connection accepts.
     ///
+    /// Modules authenticating with binary blobs (see [`oprf_types::api::OprfAuthBlob`]) must raise this limit accordingly. The limit is advertised to clients in the upgrade response and at `/oprf_params/{id}`.
+    ///
     /// Defaults to `1024`.
     #[serde(default = "OprfNodeServiceConfig::default_ws_max_message_size")]
     pub ws_max_message_size: usize,
@@ -52,6 +291,136 @@ pub struct OprfNodeServiceConfig {
     #[serde(with = "humantime_serde")]
     pub session_lifetime: Duration,
 
+    /// Max number of sessions a multiplexed connection (`/oprf/multiplex`) carries at the same time.
+    ///
+    /// Every session of a multiplexed connection runs into its own `session_lifetime`. Further sessions are rejected with close code `1013` (try again later) until one finishes. `0` disables multiplexed connections.
+    ///
+    /// Defaults to `32`.
+    #[serde(default = "OprfNodeServiceConfig::default_max_multiplexed_sessions")]
+    pub max_multiplexed_sessions: usize,
+
+    /// Max number of blinded queries a single session carries (see [`oprf_types::api::OprfRequest::batch`]). Larger requests are closed with close code [`oprf_types::api::oprf_error_codes::BATCH_TOO_LARGE`].
+    ///
+    /// The limit is advertised in the [`oprf_types::api::OprfKeyParams`] at `/oprf_params/{id}`. A batch session sends all queries in one message, so `ws_max_message_size` must be raised accordingly. Must be greater than `0`, `1` disables batch sessions.
+    ///
+    /// Defaults to `1`.
+    #[serde(default = "OprfNodeServiceConfig::default_max_batch_size")]
+    pub max_batch_size: usize,
+
+    /// Max time an `/epoch_notifications` subscription is kept open.
+    ///
+    /// After this time the node closes the connection and clients are expected to reconnect.
+    ///
+    /// Defaults to `10 min`.
+    #[serde(default = "OprfNodeServiceConfig::default_epoch_notifications_lifetime")]
+    #[serde(with = "humantime_serde")]
+    pub epoch_notifications_lifetime: Duration,
+
+    /// Max age of a blinded query, measured from the `issued_at` timestamp of the [`oprf_types::api::OprfRequest`].
+    ///
+    /// If set, requests without a timestamp or with a timestamp older than this age are rejected with [`oprf_types::api::oprf_error_codes::STALE_QUERY`]. This limits how long a precomputed query stays usable.
+    ///
+    /// Defaults to `None` (disabled).
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub max_query_age: Option<Duration>,
+
+    /// Tolerated clock skew between clients and the node when checking `max_query_age`.
+    ///
+    /// Timestamps up to this far in the future are accepted, and the accepted age is extended by it.
+    ///
+    /// Defaults to `5 s`.
+    #[serde(default = "OprfNodeServiceConfig::default_max_clock_skew")]
+    #[serde(with = "humantime_serde")]
+    pub max_clock_skew: Duration,
+
+    /// Interval in which the committee health service polls the other nodes, and the committee registry and public key history services poll the `OprfKeyRegistry` for changed peers and finalized epochs.
+    ///
+    /// Only used if enabled with [`crate::OprfServiceBuilder::committee_health`], `OprfServiceBuilder::committee_registry` or `OprfServiceBuilder::public_key_history` (both require the `registry` feature).
+    ///
+    /// Defaults to `30 s`.
+    #[serde(default = "OprfNodeServiceConfig::default_committee_poll_interval")]
+    #[serde(with = "humantime_serde")]
+    pub committee_poll_interval: Duration,
+
+    /// Interval in which the cached keys are compared with the `OprfKeyRegistry`, see `crate::services::key_reconciliation`.
+    ///
+    /// Only used if enabled with `OprfServiceBuilder::key_reconciliation` (requires the `registry` feature).
+    ///
+    /// Defaults to `5 min`.
+    #[serde(default = "OprfNodeServiceConfig::default_key_reconciliation_interval")]
+    #[serde(with = "humantime_serde")]
+    pub key_reconciliation_interval: Duration,
+
+    /// Max number of concurrent `OprfKeyRegistry` calls of a key reconciliation run.
+    ///
+    /// Only used if enabled with `OprfServiceBuilder::key_reconciliation` (requires the `registry` feature).
+    ///
+    /// Defaults to `8`.
+    #[serde(default = "OprfNodeServiceConfig::default_key_reconciliation_concurrency")]
+    pub key_reconciliation_concurrency: NonZeroUsize,
+
+    /// Max number of cached keys compared with the `OprfKeyRegistry` per key reconciliation run.
+    ///
+    /// If more keys are cached, every run continues after the last key compared by the previous run, so all keys are compared within a few runs.
+    ///
+    /// Only used if enabled with `OprfServiceBuilder::key_reconciliation` (requires the `registry` feature).
+    ///
+    /// Defaults to `1_000`.
+    #[serde(default = "OprfNodeServiceConfig::default_key_reconciliation_max_keys")]
+    pub key_reconciliation_max_keys: NonZeroUsize,
+
+    /// Max time a single `authenticate` call of an [`oprf_types::api::OprfRequestAuthenticator`] may take.
+    ///
+    /// Sessions whose authentication exceeds this time are closed with [`oprf_types::api::oprf_error_codes::AUTH_TIMEOUT`], so a slow authenticator does not hold sessions open until `session_lifetime`. Should be smaller than `session_lifetime`.
+    ///
+    /// Defaults to `None`, which uses the [`EnvironmentPreset`] of `environment`.
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    pub auth_timeout: Option<Duration>,
+
+    /// Max time the [`crate::risk_scorer::RiskScorer`] may take to score a request.
+    ///
+    /// Only used if a risk scorer is set with [`crate::OprfServiceBuilder::risk_scorer`]. A scorer that exceeds this time is handled like a failing scorer, see `risk_scorer_fail_open`.
+    ///
+    /// Defaults to `1 s`.
+    #[serde(default = "OprfNodeServiceConfig::default_risk_scorer_timeout")]
+    #[serde(with = "humantime_serde")]
+    pub risk_scorer_timeout: Duration,
+
+    /// Whether requests are evaluated if the [`crate::risk_scorer::RiskScorer`] fails or times out.
+    ///
+    /// If `false` (fail-closed), such requests are rejected with [`oprf_types::api::oprf_error_codes::RISK_DENIED`].
+    ///
+    /// Defaults to `None`, which uses the [`EnvironmentPreset`] of `environment`.
+    #[serde(default)]
+    pub risk_scorer_fail_open: Option<bool>,
+
+    /// Max number of finished sessions whose proof share is kept for resumed sessions.
+    ///
+    /// Clients whose connection broke after sending the challenge can resume the session by sending the same [`oprf_types::api::OprfRequest`] again within `session_lifetime`. The node then answers with the same commitments and proof share instead of creating new randomness. `0` disables resuming sessions.
+    ///
+    /// Defaults to `10_000`.
+    #[serde(default = "OprfNodeServiceConfig::default_challenge_replay_max_capacity")]
+    pub challenge_replay_max_capacity: u64,
+
+    /// Max age of a session handoff file that is still restored at startup, see [`crate::OprfServiceBuilder::session_handoff`].
+    ///
+    /// Should cover the time between the shutdown of the old and the startup of the new process. Older files are ignored, as the clients have given up on their sessions by then.
+    ///
+    /// Defaults to `30 s`.
+    #[serde(default = "OprfNodeServiceConfig::default_session_handoff_grace_period")]
+    #[serde(with = "humantime_serde")]
+    pub session_handoff_grace_period: Duration,
+
+    /// Where the node keeps pending sessions, see [`SessionPersistence`].
+    ///
+    /// [`crate::OprfServiceBuilder::build`] rejects a durable session store with [`SessionPersistence::Memory`] and [`SessionPersistence::Durable`] without a durable session store.
+    ///
+    /// Defaults to [`SessionPersistence::Memory`].
+    #[serde(default)]
+    pub session_persistence: SessionPersistence,
+
     /// Max time to wait for a graceful shutdown of the web-socket connection.
     ///
     /// This duration defines how long the web-socket connection stays alive until after one of the parties initiated a shutdown.
@@ -87,6 +456,131 @@ pub struct OprfNodeServiceConfig {
     #[serde(default = "OprfNodeServiceConfig::default_store_tti")]
     #[serde(with = "humantime_serde")]
     pub store_tti: Duration,
+
+    /// Which key the key-material store evicts once it holds `store_max_capacity` keys.
+    ///
+    /// Evicted keys are loaded from the secret manager again on the next request.
+    ///
+    /// Defaults to [`StoreEvictionPolicy::TinyLfu`].
+    #[serde(default)]
+    pub store_eviction_policy: StoreEvictionPolicy,
+
+    /// Max number of unknown or deleted keys remembered by the key-material store.
+    ///
+    /// Requests for these keys are rejected without asking the secret manager again, which protects the secret manager from clients enumerating key ids. The least recently used key is evicted first. `0` disables the negative cache.
+    ///
+    /// Defaults to `None`, which uses the [`EnvironmentPreset`] of `environment`.
+    #[serde(default)]
+    pub store_negative_max_capacity: Option<u64>,
+
+    /// Time-to-live for unknown or deleted keys in the negative cache.
+    ///
+    /// Keys created while they are cached as unknown are only served after this time, so keep this short.
+    ///
+    /// Defaults to `5 s`.
+    #[serde(default = "OprfNodeServiceConfig::default_store_negative_ttl")]
+    #[serde(with = "humantime_serde")]
+    pub store_negative_ttl: Duration,
+
+    /// OPRF keys that are loaded into the key-material store during startup.
+    ///
+    /// Only used by [`crate::OprfServiceBuilder::load`], which loads them concurrently with the node information. Other keys are still loaded on first use.
+    ///
+    /// Defaults to none.
+    #[serde(default)]
+    pub preload_oprf_key_ids: Vec<OprfKeyId>,
+
+    /// Directory to write a transcript of every session to, for debugging client-reported failures.
+    ///
+    /// Transcripts (see [`oprf_types::transcript`]) contain all messages of a session except the authentication part of the request and are named `{request_id}-node-{party_id}.json`. The directory must exist. Do not enable this permanently in production, as every session creates a file.
+    ///
+    /// Defaults to `None` (disabled).
+    #[serde(default)]
+    pub transcript_dir: Option<PathBuf>,
+
+    /// How much failed sessions tell the client in the close frame, see [`CloseFrameVerbosity`].
+    ///
+    /// Defaults to `None`, which uses the [`EnvironmentPreset`] of `environment`: [`CloseFrameVerbosity::Redacted`] in [`Environment::Dev`] and [`CloseFrameVerbosity::Generic`] in all other environments.
+    #[serde(default)]
+    pub close_frame_verbosity: Option<CloseFrameVerbosity>,
+
+    /// Whether the OPRF modules additionally serve the session flow over gRPC (requires the `grpc` feature).
+    ///
+    /// The gRPC service runs next to the web-socket endpoint of every module and shares its key-material store, session store and caches. Enabling it without the `grpc` feature is reported by [`crate::OprfServiceBuilder::build`].
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub grpc: bool,
+
+    /// Which `cbor` encoding the OPRF modules accept from clients.
+    ///
+    /// Defaults to [`CborEncoding::Any`].
+    #[serde(default)]
+    pub cbor_encoding: CborEncoding,
+
+    /// Whether the node installs a Prometheus recorder and serves the recorded metrics at `/metrics`, see [`crate::metrics::exporter`].
+    ///
+    /// The recorder replaces the global `metrics` recorder, so keep the metrics backend of `telemetry-batteries` disabled when enabling this.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub metrics_endpoint: bool,
+
+    /// Path prefix of all routes of the node, e.g. `/oprf-node` if a reverse proxy mounts the node at `/oprf-node/` without stripping the prefix.
+    ///
+    /// The web-socket endpoints, health and info routes are then served at `/oprf-node/api/{path}/oprf`, `/oprf-node/health`, and so on. Clients use the prefixed URL as base URL of the node, e.g. `https://example.com/oprf-node`. Must start with `/` and must not end with `/`, which is reported by [`crate::OprfServiceBuilder::build`].
+    ///
+    /// Defaults to `None` (routes are served at the root).
+    #[serde(default)]
+    pub root_path: Option<String>,
+
+    /// Operator override that accepts a party id that differs from the one persisted on an earlier start, see [`crate::OprfServiceBuilder::bind_party_id`].
+    ///
+    /// Only set this for a single start after verifying that the shares in the secret manager belong to the new party id, e.g. after re-running the key generation. The new binding is persisted, so later starts don't need the override.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub accept_changed_party_id: bool,
+
+    /// Number of finalized epochs per key kept in the public key history, older epochs are removed when a new one is recorded.
+    ///
+    /// Only used if enabled with `OprfServiceBuilder::public_key_history` (requires the `registry` feature).
+    ///
+    /// Defaults to `100`.
+    #[serde(default = "OprfNodeServiceConfig::default_public_key_history_retention")]
+    pub public_key_history_retention: NonZeroUsize,
+
+    /// Artificial latency and errors for the sessions of the OPRF modules, see [`ChaosConfig`].
+    ///
+    /// Meant for staging environments to validate the retry logic of clients against realistic failures. Enabling this in [`Environment::Prod`] is rejected by [`crate::OprfServiceBuilder::build`].
+    ///
+    /// Defaults to `None` (disabled).
+    #[serde(default)]
+    pub chaos: Option<ChaosConfig>,
+
+    /// Quota of evaluations per [`OprfKeyId`], see [`KeyQuotaConfig`].
+    ///
+    /// Requests of a key that exhausted its quota are closed with close code [`oprf_types::api::oprf_error_codes::QUOTA_EXCEEDED`].
+    ///
+    /// Defaults to `None` (unlimited).
+    #[serde(default)]
+    pub key_quota: Option<KeyQuotaConfig>,
+
+    /// Limit of concurrent sessions per [`OprfKeyId`], see [`KeyConcurrencyConfig`].
+    ///
+    /// Bounds the share of the node a single popular key can take. Requests of a key that already runs its limit of sessions are closed with close code [`oprf_types::api::oprf_error_codes::KEY_BUSY`].
+    ///
+    /// Defaults to `None` (unlimited).
+    #[serde(default)]
+    pub key_concurrency: Option<KeyConcurrencyConfig>,
+
+    /// Per-key labels of the request metrics, see [`KeyMetricsConfig`].
+    ///
+    /// If set, the duration histograms of part one and part two of the OPRF computation carry an `oprf_key_id` label and the outcome of every session is counted per key as `taceo.oprf.node.request.key.outcome`.
+    ///
+    /// Defaults to `None` (no `oprf_key_id` label).
+    #[serde(default)]
+    pub key_metrics: Option<KeyMetricsConfig>,
 }
 
 fn deserialize_version_req<'de, D>(deserializer: D) -> Result<VersionReq, D::Error>
@@ -108,7 +602,61 @@ impl OprfNodeServiceConfig {
         Duration::from_secs(30)
     }
 
+    /// Default lifetime of epoch notification subscriptions (`10 min`).
+    fn default_epoch_notifications_lifetime() -> Duration {
+        Duration::from_mins(10)
+    }
+
+    /// Default tolerated clock skew (`5 s`).
+    fn default_max_clock_skew() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Default committee poll interval (`30 s`).
+    fn default_committee_poll_interval() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    /// Default key reconciliation interval (`5 min`).
+    fn default_key_reconciliation_interval() -> Duration {
+        Duration::from_mins(5)
+    }
+
+    /// Default number of concurrent registry calls of a key reconciliation run (`8`).
+    fn default_key_reconciliation_concurrency() -> NonZeroUsize {
+        NonZeroUsize::new(8).expect("Is non-zero")
+    }
+
+    /// Default number of keys compared per key reconciliation run (`1_000`).
+    fn default_key_reconciliation_max_keys() -> NonZeroUsize {
+        NonZeroUsize::new(1_000).expect("Is non-zero")
+    }
+
+    /// Default risk scorer timeout (`1 s`).
+    fn default_risk_scorer_timeout() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Default max capacity for replayable sessions (`10_000`).
+    fn default_challenge_replay_max_capacity() -> u64 {
+        10_000
+    }
+
+    /// Default grace period for session handoff files (`30 s`).
+    fn default_session_handoff_grace_period() -> Duration {
+        Duration::from_secs(30)
+    }
+
     /// Default websocket shutdown timeout (`10 s`).
+    fn default_max_multiplexed_sessions() -> usize {
+        32
+    }
+
+    /// Default max batch size (`1`, no batch sessions).
+    fn default_max_batch_size() -> usize {
+        1
+    }
+
     fn default_websocket_shutdown_timeout() -> Duration {
         Duration::from_secs(10)
     }
@@ -133,6 +681,16 @@ impl OprfNodeServiceConfig {
         Duration::from_hours(1)
     }
 
+    /// Default TTL for the negative cache (`5 s`).
+    fn default_store_negative_ttl() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Default retention of the public key history (`100` epochs per key).
+    fn default_public_key_history_retention() -> NonZeroUsize {
+        NonZeroUsize::new(100).expect("Is non-zero")
+    }
+
     /// Construct with all default values except required fields.
     #[must_use]
     pub fn with_default_values(environment: Environment, version_req: VersionReq) -> Self {
@@ -142,10 +700,69 @@ impl OprfNodeServiceConfig {
             ws_max_message_size: Self::default_ws_max_message_size(),
             websocket_shutdown_timeout: Self::default_websocket_shutdown_timeout(),
             session_lifetime: Self::default_session_lifetime(),
+            max_multiplexed_sessions: Self::default_max_multiplexed_sessions(),
+            max_batch_size: Self::default_max_batch_size(),
+            epoch_notifications_lifetime: Self::default_epoch_notifications_lifetime(),
+            max_query_age: None,
+            max_clock_skew: Self::default_max_clock_skew(),
+            auth_timeout: None,
+            risk_scorer_timeout: Self::default_risk_scorer_timeout(),
+            risk_scorer_fail_open: None,
+            challenge_replay_max_capacity: Self::default_challenge_replay_max_capacity(),
+            session_handoff_grace_period: Self::default_session_handoff_grace_period(),
+            session_persistence: SessionPersistence::Memory,
+            committee_poll_interval: Self::default_committee_poll_interval(),
+            key_reconciliation_interval: Self::default_key_reconciliation_interval(),
+            key_reconciliation_concurrency: Self::default_key_reconciliation_concurrency(),
+            key_reconciliation_max_keys: Self::default_key_reconciliation_max_keys(),
             http_request_timeout: Self::default_http_request_timeout(),
             store_max_capacity: Self::default_store_max_capacity(),
             store_ttl: Self::default_store_ttl(),
             store_tti: Self::default_store_tti(),
+            store_eviction_policy: StoreEvictionPolicy::TinyLfu,
+            store_negative_max_capacity: None,
+            store_negative_ttl: Self::default_store_negative_ttl(),
+            preload_oprf_key_ids: Vec::new(),
+            transcript_dir: None,
+            close_frame_verbosity: None,
+            grpc: false,
+            cbor_encoding: CborEncoding::Any,
+            metrics_endpoint: false,
+            root_path: None,
+            accept_changed_party_id: false,
+            public_key_history_retention: Self::default_public_key_history_retention(),
+            chaos: None,
+            key_quota: None,
+            key_concurrency: None,
+            key_metrics: None,
+        }
+    }
+
+    /// The [`EnvironmentPreset`] of `environment`, with the values explicitly set in this config applied.
+    #[must_use]
+    pub fn preset(&self) -> EnvironmentPreset {
+        let preset = EnvironmentPreset::for_environment(self.environment);
+        EnvironmentPreset {
+            log_filter: preset.log_filter,
+            close_frame_verbosity: self
+                .close_frame_verbosity
+                .unwrap_or(preset.close_frame_verbosity),
+            auth_timeout: self.auth_timeout.unwrap_or(preset.auth_timeout),
+            risk_scorer_fail_open: self
+                .risk_scorer_fail_open
+                .unwrap_or(preset.risk_scorer_fail_open),
+            store_negative_max_capacity: self
+                .store_negative_max_capacity
+                .unwrap_or(preset.store_negative_max_capacity),
         }
     }
+
+    /// The [`CloseFrameVerbosity`] of this node: the configured one, otherwise the one of the [`EnvironmentPreset`].
+    #[must_use]
+    pub fn close_frame_verbosity_or_default(&self) -> CloseFrameVerbosity {
+        self.preset().close_frame_verbosity
+    }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use crate::{
+    Environment,
+    config::{CloseFrameVerbosity, EnvironmentPreset, OprfNodeServiceConfig},
+    test_utils::default_config,
+};
+
+#[test]
+fn preset_depends_on_environment_and_overrides() {
+    let prod = OprfNodeServiceConfig::with_default_values(
+        Environment::Prod,
+        "*".parse().expect("valid version req"),
+    );
+    assert_eq!(
+        prod.preset(),
+        EnvironmentPreset::for_environment(Environment::Prod),
+        "should use the preset without overrides"
+    );
+    assert_eq!(
+        prod.close_frame_verbosity_or_default(),
+        CloseFrameVerbosity::Generic,
+        "prod should not leak error details"
+    );
+    assert!(
+        !prod.preset().risk_scorer_fail_open,
+        "prod should fail closed"
+    );
+
+    let mut dev = default_config();
+    assert_eq!(
+        dev.close_frame_verbosity_or_default(),
+        CloseFrameVerbosity::Redacted,
+        "dev should show error details without the content of messages"
+    );
+    dev.close_frame_verbosity = Some(CloseFrameVerbosity::Generic);
+    dev.auth_timeout = Some(Duration::from_secs(1));
+    let preset = dev.preset();
+    assert_eq!(
+        preset.close_frame_verbosity,
+        CloseFrameVerbosity::Generic,
+        "should apply overridden verbosity"
+    );
+    assert_eq!(
+        preset.auth_timeout,
+        Duration::from_secs(1),
+        "should apply overridden auth timeout"
+    );
+    assert_eq!(
+        preset.store_negative_max_capacity, 0,
+        "should keep the remaining dev values"
+    );
+}
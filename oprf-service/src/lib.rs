@@ -25,10 +25,11 @@
 //! When implementing a concrete instantiation of TACEO:OPRF, projects use this composable library to build their flavor of the distributed OPRF protocol. The main entry point for implementations is the [`OprfServiceBuilder`].
 //! It loads node information (party ID, address) from the secret manager and initializes a cache-backed key material store.
 //! With the [`OprfServiceBuilder::module`] method, implementations can add multiple OPRF modules, each with its own authentication mechanism.
-//! Finally, the [`OprfServiceBuilder::build`] method returns an `axum::Router` that should be incorporated into a larger `axum` server that provides project-based functionality for authentication.
+//! Finally, the [`OprfServiceBuilder::build`] method returns an `axum::Router` (or a [`BuilderError`] on misconfiguration) that should be incorporated into a larger `axum` server that provides project-based functionality for authentication.
 //!
 //! If internal services of the OPRF service encounter an error, the provided `CancellationToken` will be cancelled, allowing the hosting application to handle the shutdown process gracefully.
 //! Additionally, the `CancellationToken` can be cancelled externally to signal the OPRF service to stop its operations.
+//! Cancelling the token stops the background tasks at once. To let open sessions finish first, build the service with [`OprfServiceBuilder::build_with_shutdown`] and stop it with [`Shutdown::run`], see [`shutdown`].
 //!
 //! For OPRF modules, implementations must provide their project-specific authentication. For that, this library exposes the [`oprf_types::api::OprfRequestAuthenticator`] trait. A call to `[OprfServiceBuilder::module]` expects an [`OprfRequestAuthService`], which is a dyn object of `OprfRequestAuthenticator`.
 //!
@@ -48,41 +49,96 @@
 
 use std::fmt;
 use std::num::NonZeroU16;
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::api::oprf::OprfModuleState;
+use crate::api::oprf::{
+    OprfModuleState, QueryAgePolicy, TimeBoxedAuthService, TimeBoxedRiskScorer,
+};
 use crate::api::oprf_delegate::DelegateOprfState;
-use crate::services::open_sessions::OpenSessions;
+use crate::services::challenge_replay::ChallengeReplayCache;
+use crate::services::clock::{ClockService, TokioClock};
+use crate::services::committee_health::CommitteeHealthService;
+use crate::services::key_concurrency::KeyConcurrency;
+use crate::services::key_labels::KeyLabels;
+use crate::services::key_quota::KeyQuota;
 use crate::services::oprf_key_material_store::OprfKeyMaterialStore;
-use crate::{config::OprfNodeServiceConfig, services::secret_manager::SecretManagerService};
+use crate::services::replica_snapshot::{self, ReplicaKeys};
+use crate::services::risk_scorer::RiskScorerService;
+use crate::services::runtime_limits::{Limits, RuntimeLimits};
+use crate::services::session_handoff::SessionHandoff;
+use crate::services::session_store::{LocalSessionStore, OprfSessionStoreService};
+use crate::services::subsystem_health::{Subsystem, SubsystemRegistry};
+use crate::services::transcript_writer::TranscriptWriter;
+use crate::shutdown::ShutdownSignal;
+use crate::{
+    config::{OprfNodeServiceConfig, SessionPersistence},
+    services::secret_manager::SecretManagerService,
+};
 use axum::Router;
 use axum::extract::{DefaultBodyLimit, MatchedPath};
+use eyre::Context as _;
 use http::{HeaderMap, HeaderName, Method, StatusCode, Uri};
 use oprf_client::Connector;
 use oprf_types::api::OprfRequestAuthService;
 use oprf_types::crypto::PartyId;
+use oprf_types::retry::RetryPolicy;
 use oprf_types::service::NodeInformation;
+use secrecy::{ExposeSecret as _, SecretString};
 use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::{MakeSpan, TraceLayer};
 
 pub(crate) mod api;
 pub mod config;
+pub mod listener;
 pub mod metrics;
 pub(crate) mod services;
+pub mod shutdown;
+#[cfg(any(test, feature = "test-kit"))]
+pub mod test_kit;
+#[cfg(test)]
+pub(crate) mod test_utils;
 
 pub use nodes_common::{Environment, StartedServices};
 pub use semver::VersionReq;
+pub use services::clock;
+pub use services::composite_authenticator;
+pub use services::epoch_transitions;
+pub use services::oprf_key_material_store;
+pub use services::risk_scorer;
 pub use services::secret_manager;
+pub use services::session_store;
+pub use services::subsystem_health;
+pub use shutdown::{Shutdown, ShutdownReport};
 
 /// [`OprfServiceBuilder`] to initialize a `OprfService` with multiple [`OprfRequestAuthService`]s.
 ///
 /// Beyond the OPRF modules added via [`OprfServiceBuilder::module`], the builder always exposes a
 /// set of read-only info routes at the root (not under `/api`):
 /// - `GET /health`
+/// - `GET /health/details` (state of the started services and of every registered subsystem, see [`subsystem_health`])
 /// - `GET /version`
+/// - `GET /buildinfo` (returns [`oprf_types::api::BuildInfo`])
+/// - `GET /info` (returns [`oprf_types::api::NodeInfo`])
 /// - `GET /wallet`
 /// - `GET /oprf_pub/{id}`
+/// - `GET /oprf_params/{id}` (returns [`oprf_types::api::OprfKeyParams`])
+/// - `GET /epoch_notifications` (web-socket, pushes [`oprf_types::api::EpochChanged`])
+/// - `GET /committee/health` (only if enabled with [`OprfServiceBuilder::committee_health`])
+/// - `GET /committee` (returns [`oprf_types::api::Committee`], only if enabled with `OprfServiceBuilder::committee_registry`, requires the `registry` feature)
+/// - `GET /oprf_pub/{id}/history` (only if enabled with `OprfServiceBuilder::public_key_history`, requires the `registry` feature)
+/// - `POST /replica/snapshot` (only if enabled with [`OprfServiceBuilder::replica_snapshot`])
+/// - `PUT /admin/limits/{path}` (only if enabled with [`OprfServiceBuilder::admin_api`])
+/// - `GET /debug/memory` (returns [`oprf_types::api::MemoryStats`], only if enabled with `OprfServiceBuilder::memory_stats`, requires the `jemalloc` feature)
+/// - `GET /metrics` (Prometheus text format, only if `metrics_endpoint` is enabled in the [`OprfNodeServiceConfig`], see [`metrics::exporter`])
+///
+/// Every module serves its web-socket endpoint at `/api/{path}/oprf` and its current limits at `/api/{path}/params` (returns [`oprf_types::api::ModuleParams`]). If `grpc` is enabled in the [`OprfNodeServiceConfig`] (requires the `grpc` feature), it additionally serves the gRPC service `taceo.oprf.v1.OprfNode` at `/api/{path}/taceo.oprf.v1.OprfNode/Oprf`.
+///
+/// If `root_path` is set in the [`OprfNodeServiceConfig`] (e.g. `/oprf-node` behind a reverse proxy), all of these routes are served below it, e.g. `/oprf-node/health` and `/oprf-node/api/{path}/oprf`.
 ///
 /// CORS for those info routes is **opt-in**: call [`OprfServiceBuilder::cors_for_info`] before
 /// [`OprfServiceBuilder::build`] to allow cross-origin `GET` requests from any origin.
@@ -90,17 +146,35 @@ pub struct OprfServiceBuilder {
     config: OprfNodeServiceConfig,
     info_routes: Router,
     api: Router,
-    open_sessions: OpenSessions,
+    module_paths: Vec<String>,
+    module_limits: Vec<(String, RuntimeLimits)>,
+    module_contexts: Vec<(String, ark_babyjubjub::Fq)>,
+    admin_token: Option<blake3::Hash>,
+    shutdown: ShutdownSignal,
+    error: Option<BuilderError>,
+    session_store: OprfSessionStoreService,
+    challenge_replay_cache: ChallengeReplayCache,
+    session_handoff: Option<SessionHandoff>,
+    risk_scorer: Option<RiskScorerService>,
+    key_quota: Option<KeyQuota>,
+    key_concurrency: Option<KeyConcurrency>,
+    key_labels: Option<KeyLabels>,
+    clock: ClockService,
+    share_proofs: bool,
+    signed_responses: bool,
     oprf_key_material_store: OprfKeyMaterialStore,
     party_id: PartyId,
     threshold: NonZeroU16,
+    wallet_address: String,
+    subsystems: SubsystemRegistry,
+    tasks: Vec<JoinHandle<ExitReason>>,
 }
 
 impl OprfServiceBuilder {
     /// Initializes the OPRF node service.
     ///
     /// During initialization the service:
-    /// - Loads node information (party ID, address) from the secret manager.
+    /// - Uses the provided node information (party ID, address), see [`OprfServiceBuilder::load`] to load it from the secret manager.
     /// - Initializes the cache-backed OPRF key material store.
     /// - Initializes the Axum router exposing the node API.
     pub fn init(
@@ -116,31 +190,169 @@ impl OprfServiceBuilder {
             config.store_max_capacity,
             config.store_ttl,
             config.store_tti,
+            config.store_eviction_policy,
+        )
+        .with_negative_cache(
+            config.preset().store_negative_max_capacity,
+            config.store_negative_ttl,
+        );
+        Self::init_with_store(
+            config,
+            oprf_key_material_store,
+            started_services,
+            node_information,
+            version_str,
+        )
+    }
+
+    /// Loads the node information from the secret manager and initializes the OPRF node service.
+    ///
+    /// Like [`OprfServiceBuilder::init`], but loads the node information and the keys in [`OprfNodeServiceConfig::preload_oprf_key_ids`] concurrently, so a node that serves many keys does not pay for them on the first requests. Logs the duration of every step and records the total in the `taceo.oprf.node.startup.duration` metric.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the node information cannot be loaded. Keys that cannot be preloaded are only logged.
+    pub async fn load(
+        config: OprfNodeServiceConfig,
+        secret_manager: SecretManagerService,
+        started_services: StartedServices,
+        version_str: String,
+    ) -> eyre::Result<Self> {
+        let start = Instant::now();
+        let oprf_key_material_store = OprfKeyMaterialStore::new(
+            Arc::clone(&secret_manager),
+            config.store_max_capacity,
+            config.store_ttl,
+            config.store_tti,
+            config.store_eviction_policy,
+        )
+        .with_negative_cache(
+            config.preset().store_negative_max_capacity,
+            config.store_negative_ttl,
         );
+        let (node_information, loaded) = tokio::join!(
+            async {
+                let start = Instant::now();
+                let node_information = secret_manager.load_node_information().await;
+                tracing::info!("loaded node information in {:?}", start.elapsed());
+                node_information
+            },
+            async {
+                let start = Instant::now();
+                let loaded = oprf_key_material_store
+                    .preload(&config.preload_oprf_key_ids)
+                    .await;
+                tracing::info!(
+                    "preloaded {loaded}/{} OPRF keys in {:?}",
+                    config.preload_oprf_key_ids.len(),
+                    start.elapsed()
+                );
+                loaded
+            }
+        );
+        let node_information = node_information.context("while loading node information")?;
+        tracing::debug!("node information: {node_information:?}, preloaded keys: {loaded}");
+        let builder = Self::init_with_store(
+            config,
+            oprf_key_material_store,
+            started_services,
+            &node_information,
+            version_str,
+        );
+        let elapsed = start.elapsed();
+        tracing::info!("loaded oprf-service in {elapsed:?}");
+        metrics::startup::duration(elapsed);
+        Ok(builder)
+    }
 
+    fn init_with_store(
+        config: OprfNodeServiceConfig,
+        oprf_key_material_store: OprfKeyMaterialStore,
+        started_services: StartedServices,
+        node_information: &NodeInformation,
+        version_str: String,
+    ) -> Self {
         tracing::info!("init oprf-service...");
 
+        let subsystems = SubsystemRegistry::default();
+        let oprf_key_material_store =
+            oprf_key_material_store.with_subsystem(subsystems.register("secret_manager"));
+
+        let build_info =
+            api::buildinfo::build_info(version_str.clone(), config.version_req.to_string());
+        tracing::info!(
+            version = build_info.version,
+            git_sha = build_info.git_sha,
+            build_timestamp = build_info.build_timestamp,
+            features = ?build_info.features,
+            "build info"
+        );
+
         let info_route = Router::new()
             .merge(nodes_common::api::routes_with_services(
-                started_services,
+                started_services.clone(),
                 version_str,
             ))
+            .merge(api::buildinfo::routes(build_info))
+            .merge(api::health::routes(started_services, subsystems.clone()))
             .merge(api::info::routes(
                 oprf_key_material_store.clone(),
                 node_information.address().to_owned(),
+                node_information.threshold(),
+                config.ws_max_message_size,
+                config.max_batch_size,
+                &config.version_req,
+            ))
+            .merge(api::epoch_notifications::routes(
+                oprf_key_material_store.clone(),
+                config.epoch_notifications_lifetime,
+                config.websocket_shutdown_timeout,
             ));
 
+        metrics::sessions::reset();
+        let clock: ClockService = Arc::new(TokioClock);
         Self {
-            open_sessions: OpenSessions::new(),
+            session_store: Arc::new(LocalSessionStore::new()),
+            challenge_replay_cache: ChallengeReplayCache::new(
+                config.challenge_replay_max_capacity,
+                config.session_lifetime,
+            ),
+            session_handoff: None,
+            risk_scorer: None,
+            key_quota: config
+                .key_quota
+                .map(|key_quota| KeyQuota::new(key_quota, Arc::clone(&clock))),
+            key_concurrency: config.key_concurrency.clone().map(KeyConcurrency::new),
+            key_labels: config.key_metrics.as_ref().map(KeyLabels::new),
+            clock,
+            share_proofs: false,
+            signed_responses: false,
             info_routes: info_route,
             api: Router::new(),
+            module_paths: Vec::new(),
+            module_limits: Vec::new(),
+            module_contexts: Vec::new(),
+            admin_token: None,
+            shutdown: ShutdownSignal::default(),
+            error: None,
             oprf_key_material_store,
             party_id: node_information.party_id(),
             threshold: node_information.threshold(),
+            wallet_address: node_information.address().to_owned(),
+            subsystems,
+            tasks: Vec::new(),
             config,
         }
     }
 
+    /// Registers a subsystem of the host, e.g., an event watcher, whose state is served at `/health/details`.
+    ///
+    /// The subsystem reports the outcome of its operations to the returned handle, see [`subsystem_health`]. Subsystems are listed in registration order after the `secret_manager`.
+    #[must_use]
+    pub fn subsystem(&self, name: impl Into<String>) -> Subsystem {
+        self.subsystems.register(name)
+    }
+
     /// Adds a CORS layer for the `info` routes.
     ///
     /// This CORS layer uses the default values from [`CorsLayer`](https://docs.rs/tower-http/latest/tower_http/cors/struct.CorsLayer.html) and
@@ -156,36 +368,644 @@ impl OprfServiceBuilder {
         self
     }
 
+    /// Enables the committee health service.
+    ///
+    /// Spawns a task that polls the `/health` and `/oprf_pub/{id}` endpoints of the provided `peers` every `committee_poll_interval` (see [`OprfNodeServiceConfig`]) and serves the aggregated view at `GET /committee/health`, including which peers are lagging on epochs. The task stops when `cancellation_token` is cancelled.
+    ///
+    /// Must be called from within a Tokio runtime. A zero `committee_poll_interval` is reported by [`OprfServiceBuilder::build`].
+    #[must_use]
+    pub fn committee_health(
+        mut self,
+        peers: Vec<url::Url>,
+        client: reqwest::Client,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        if self.config.committee_poll_interval.is_zero() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "committee_poll_interval must be greater than 0",
+            ));
+            return self;
+        }
+        let (committee_health, committee_health_task) = CommitteeHealthService::spawn(
+            peers,
+            client,
+            self.oprf_key_material_store.clone(),
+            self.config.committee_poll_interval,
+            cancellation_token,
+        );
+        self.tasks.push(committee_health_task);
+        self.info_routes = self
+            .info_routes
+            .merge(api::committee::routes(committee_health));
+        self
+    }
+
+    /// Enables the committee registry service (requires the `registry` feature).
+    ///
+    /// Spawns a task that loads the party ids and wallet addresses of all nodes from the `OprfKeyRegistry` at `registry_address` and serves them at `GET /committee`. Afterwards, the task polls the registry every `committee_poll_interval` (see [`OprfNodeServiceConfig`]) and reloads the committee on `OprfPeerChanged` events. The task stops when `cancellation_token` is cancelled.
+    ///
+    /// Must be called from within a Tokio runtime. A zero `committee_poll_interval` is reported by [`OprfServiceBuilder::build`].
+    #[cfg(feature = "registry")]
+    #[must_use]
+    pub fn committee_registry(
+        mut self,
+        rpc_provider: nodes_common::web3::HttpRpcProvider,
+        registry_address: alloy::primitives::Address,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        if self.config.committee_poll_interval.is_zero() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "committee_poll_interval must be greater than 0",
+            ));
+            return self;
+        }
+        let (committee_registry, committee_registry_task) =
+            services::committee_registry::CommitteeRegistryService::spawn(
+                rpc_provider,
+                registry_address,
+                self.config.committee_poll_interval,
+                cancellation_token,
+            );
+        self.tasks.push(committee_registry_task);
+        self.info_routes = self
+            .info_routes
+            .merge(api::committee::registry_routes(committee_registry));
+        self
+    }
+
+    /// Enables the public key history service (requires the `registry` feature).
+    ///
+    /// Spawns a task that polls the `OprfKeyRegistry` at `registry_address` every `committee_poll_interval` (see [`OprfNodeServiceConfig`]) for `SecretGenFinalize` events, starting at `from_block` (e.g., the deployment block of the registry). After a restart, the task resumes after the last handled block stored in the secret manager (see [`secret_manager::SecretManager::store_event_watcher_block`]) if that is later. For every finalized epoch, the public key is recorded in the secret manager, keeping the latest `public_key_history_retention` epochs per key, and served at `GET /oprf_pub/{id}/history`. The secret manager must be able to persist the history (see [`secret_manager::SecretManager::record_public_key`]). The task stops when `cancellation_token` is cancelled.
+    ///
+    /// Must be called from within a Tokio runtime. A zero `committee_poll_interval` is reported by [`OprfServiceBuilder::build`].
+    #[cfg(feature = "registry")]
+    #[must_use]
+    pub fn public_key_history(
+        mut self,
+        rpc_provider: nodes_common::web3::HttpRpcProvider,
+        registry_address: alloy::primitives::Address,
+        from_block: u64,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        if self.config.committee_poll_interval.is_zero() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "committee_poll_interval must be greater than 0",
+            ));
+            return self;
+        }
+        let secret_manager = Arc::clone(self.oprf_key_material_store.secret_manager());
+        self.tasks.push(services::public_key_history::spawn(
+            rpc_provider,
+            registry_address,
+            Arc::clone(&secret_manager),
+            from_block,
+            self.config.committee_poll_interval,
+            self.config.public_key_history_retention,
+            cancellation_token,
+        ));
+        self.info_routes = self
+            .info_routes
+            .merge(api::info::history_routes(secret_manager));
+        self
+    }
+
+    /// Enables the reconciliation of the cached keys with the `OprfKeyRegistry` at `registry_address` (requires the `registry` feature).
+    ///
+    /// Spawns a task that compares the cached keys with the registry every `key_reconciliation_interval`, at most `key_reconciliation_max_keys` keys per run with `key_reconciliation_concurrency` concurrent registry calls (see [`OprfNodeServiceConfig`]). Keys the registry reports as deleted or unknown are evicted, keys with a newer epoch are reloaded from the secret manager (see `services::key_reconciliation`). Every run is reported as the `key_reconciliation` subsystem at `/health/details`, including the block number of the RPC connection. The task stops when `cancellation_token` is cancelled.
+    ///
+    /// Must be called from within a Tokio runtime. A zero `key_reconciliation_interval` is reported by [`OprfServiceBuilder::build`].
+    #[cfg(feature = "registry")]
+    #[must_use]
+    pub fn key_reconciliation(
+        self,
+        rpc_provider: nodes_common::web3::HttpRpcProvider,
+        registry_address: alloy::primitives::Address,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        let oprf_key_material_store = self.oprf_key_material_store.clone();
+        self.spawn_key_reconciliation(
+            rpc_provider,
+            registry_address,
+            oprf_key_material_store,
+            "key_reconciliation".to_owned(),
+            cancellation_token,
+        )
+    }
+
+    /// Like [`OprfServiceBuilder::key_reconciliation`], but reconciles the keys of a module store (see [`OprfServiceBuilder::registry_module`]) with the `OprfKeyRegistry` at `registry_address` (requires the `registry` feature).
+    ///
+    /// Use this to serve the keys of several registries from one node: give every registry its own store and module, and reconcile every store with its registry. Every run is reported as the `key_reconciliation_{registry_address}` subsystem at `/health/details`.
+    ///
+    /// Must be called from within a Tokio runtime. A zero `key_reconciliation_interval` is reported by [`OprfServiceBuilder::build`].
+    #[cfg(feature = "registry")]
+    #[must_use]
+    pub fn module_key_reconciliation(
+        self,
+        rpc_provider: nodes_common::web3::HttpRpcProvider,
+        registry_address: alloy::primitives::Address,
+        oprf_key_material_store: OprfKeyMaterialStore,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        self.spawn_key_reconciliation(
+            rpc_provider,
+            registry_address,
+            oprf_key_material_store,
+            format!("key_reconciliation_{registry_address}"),
+            cancellation_token,
+        )
+    }
+
+    #[cfg(feature = "registry")]
+    fn spawn_key_reconciliation(
+        mut self,
+        rpc_provider: nodes_common::web3::HttpRpcProvider,
+        registry_address: alloy::primitives::Address,
+        oprf_key_material_store: OprfKeyMaterialStore,
+        subsystem: String,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        if self.config.key_reconciliation_interval.is_zero() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "key_reconciliation_interval must be greater than 0",
+            ));
+            return self;
+        }
+        self.tasks.push(services::key_reconciliation::spawn(
+            rpc_provider,
+            registry_address,
+            oprf_key_material_store,
+            &self.config,
+            self.subsystems.register(subsystem),
+            cancellation_token,
+        ));
+        self
+    }
+
+    /// Serves memory statistics at `GET /debug/memory` (requires the `jemalloc` feature).
+    ///
+    /// Returns the resident set size of the process, the jemalloc statistics and the number of entries of the session store, key-material store and caches as [`oprf_types::api::MemoryStats`], so long-running soak tests can trend them. The allocator statistics require the host to use jemalloc as global allocator. The statistics reveal the load of the node, so only enable this if the route is not reachable from the internet.
+    #[cfg(feature = "jemalloc")]
+    #[must_use]
+    pub fn memory_stats(mut self) -> Self {
+        self.info_routes = self.info_routes.merge(api::memory::routes(
+            self.oprf_key_material_store.clone(),
+            self.challenge_replay_cache.clone(),
+        ));
+        self
+    }
+
+    /// Serves snapshots of the cached key material to replicas of this node at `POST /replica/snapshot`.
+    ///
+    /// A new replica (same party and wallet address) bootstraps from this node with [`OprfServiceBuilder::bootstrap_from_replica`] using the same `replica_secret`. Requests are authenticated with a MAC derived from the secret and the snapshot is encrypted with a key derived from it, so only replicas knowing the secret can read it. Use a long random secret and keep it in your secret store.
+    #[must_use]
+    pub fn replica_snapshot(mut self, replica_secret: &SecretString) -> Self {
+        self.info_routes = self.info_routes.merge(api::replica::routes(
+            ReplicaKeys::derive(replica_secret),
+            self.wallet_address.clone(),
+            self.oprf_key_material_store.clone(),
+        ));
+        self
+    }
+
+    /// Serves the admin API at `PUT /admin/limits/{path}`, authenticated with the bearer `admin_token`.
+    ///
+    /// Lets operators override the `ws_max_message_size`, `session_lifetime` and `version_req` of a single module at runtime, e.g. to tighten the limits during an incident without a redeploy. Overrides apply to new connections and are reflected at `/api/{path}/params`, see [`services::runtime_limits`] for the bounds. Use a long random token and keep it in your secret store.
+    #[must_use]
+    pub fn admin_api(mut self, admin_token: &SecretString) -> Self {
+        self.admin_token = Some(blake3::hash(admin_token.expose_secret().as_bytes()));
+        self
+    }
+
+    /// Caches the key material of the sibling replica at `peer` (see [`OprfServiceBuilder::replica_snapshot`]) instead of loading every key from the secret manager.
+    ///
+    /// Call this before serving requests on a new replica of a node. Keys not in the snapshot of the sibling are loaded from the secret manager on first use, as usual. If the snapshot cannot be fetched, the error is logged and the node starts with an empty store.
+    pub async fn bootstrap_from_replica(
+        self,
+        peer: &url::Url,
+        replica_secret: &SecretString,
+        client: &reqwest::Client,
+    ) -> Self {
+        let start = Instant::now();
+        let keys = ReplicaKeys::derive(replica_secret);
+        match replica_snapshot::fetch(peer, &keys, &self.wallet_address, client).await {
+            Ok(snapshot) => {
+                let cached = self.oprf_key_material_store.insert_snapshot(snapshot).await;
+                tracing::info!(
+                    "bootstrapped {cached} OPRF keys from replica {peer} in {:?}",
+                    start.elapsed()
+                );
+            }
+            Err(err) => {
+                tracing::warn!(
+                    ?err,
+                    "cannot bootstrap from replica {peer}, starting with empty store"
+                );
+            }
+        }
+        self
+    }
+
+    /// Binds the party id of this node to its wallet address and the `OprfKeyRegistry` at `oprf_key_registry` on `chain_id` (see [`secret_manager::PartyIdBinding`]).
+    ///
+    /// The first start persists the binding via the secret manager. Every later start compares the party id loaded from the secret manager with the persisted binding, as serving with a changed party id (e.g. after the node re-registered) would use the shares with the wrong Shamir index. A changed binding is reported by [`OprfServiceBuilder::build`], unless the operator sets `accept_changed_party_id` (see [`OprfNodeServiceConfig`]), which persists the new binding instead.
+    ///
+    /// Requires write access to the store of the secret manager, otherwise [`OprfServiceBuilder::build`] reports an error.
+    pub async fn bind_party_id(mut self, oprf_key_registry: &str, chain_id: u64) -> Self {
+        let current = secret_manager::PartyIdBinding::new(
+            &self.wallet_address,
+            self.party_id,
+            oprf_key_registry,
+            chain_id,
+        );
+        if let Err(err) = services::party_id_binding::bind(
+            self.oprf_key_material_store.secret_manager(),
+            current,
+            self.config.accept_changed_party_id,
+        )
+        .await
+        {
+            self.error.get_or_insert(err);
+        }
+        self
+    }
+
+    /// Replaces the default [`LocalSessionStore`] of all modules that share their session ids (see [`SessionNamespace`]).
+    ///
+    /// Must be called before adding modules, otherwise [`OprfServiceBuilder::build`] reports an error. Modules with [`SessionNamespace::Isolated`] always use their own [`LocalSessionStore`].
+    #[must_use]
+    pub fn session_store(mut self, session_store: OprfSessionStoreService) -> Self {
+        if !self.module_paths.is_empty() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "session_store must be set before adding modules",
+            ));
+            return self;
+        }
+        self.session_store = session_store;
+        self
+    }
+
+    /// Hands off sessions across planned restarts via the file at `path`.
+    ///
+    /// Restores the sessions handed off by the previous process, if its file is not older than `session_handoff_grace_period` (see [`OprfNodeServiceConfig`]). When `cancellation_token` is cancelled, the finished sessions and the metadata of the pending sessions (no randomness) are written to `path` for the next process. Clients can resume finished sessions after the restart, resuming pending sessions is rejected with [`oprf_types::api::oprf_error_codes::SESSION_LOST`] (see [`crate::services::session_handoff`]).
+    ///
+    /// Only covers modules with [`SessionNamespace::Shared`]. Must be called before adding modules, otherwise [`OprfServiceBuilder::build`] reports an error.
+    pub async fn session_handoff(
+        mut self,
+        path: impl Into<std::path::PathBuf>,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        if !self.module_paths.is_empty() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "session_handoff must be set before adding modules",
+            ));
+            return self;
+        }
+        let (session_handoff, session_handoff_task) = SessionHandoff::spawn(
+            path.into(),
+            self.config.session_handoff_grace_period,
+            self.config.session_lifetime,
+            self.challenge_replay_cache.clone(),
+            cancellation_token,
+        )
+        .await;
+        self.tasks.push(session_handoff_task);
+        self.session_handoff = Some(session_handoff);
+        self
+    }
+
+    /// Sets the [`risk_scorer::RiskScorer`] that scores every authenticated request of all modules before it is evaluated.
+    ///
+    /// Calls are bounded by `risk_scorer_timeout`, and `risk_scorer_fail_open` decides what happens if the scorer fails or times out (see [`OprfNodeServiceConfig`]).
+    ///
+    /// Must be called before adding modules, otherwise [`OprfServiceBuilder::build`] reports an error.
+    #[must_use]
+    pub fn risk_scorer(mut self, risk_scorer: RiskScorerService) -> Self {
+        if !self.module_paths.is_empty() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "risk_scorer must be set before adding modules",
+            ));
+            return self;
+        }
+        self.risk_scorer = Some(risk_scorer);
+        self
+    }
+
+    /// Sets the [`epoch_transitions::EpochTransitionHook`] that is called for every epoch transition of a key, after the key-material store swapped to the share of the new epoch.
+    ///
+    /// Spawns a task that delivers the transitions at least once, retrying a failing hook with exponential backoff, and persists the delivered epochs with the secret manager, so transitions while the node was down are delivered after a restart (see [`epoch_transitions`] for the delivery guarantees). The task stops when `cancellation_token` is cancelled.
+    ///
+    /// Must be called from within a Tokio runtime. Setting a second hook is reported by [`OprfServiceBuilder::build`].
+    #[must_use]
+    pub fn epoch_transition_hook(
+        mut self,
+        hook: epoch_transitions::EpochTransitionHookService,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        let Some(epoch_changes) = self.oprf_key_material_store.forward_epoch_changes() else {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "epoch_transition_hook can only be set once",
+            ));
+            return self;
+        };
+        self.tasks.push(services::epoch_transitions::spawn(
+            hook,
+            epoch_changes,
+            self.oprf_key_material_store.clone(),
+            RetryPolicy::default(),
+            cancellation_token,
+        ));
+        self
+    }
+
+    /// Replaces the [`clock::TokioClock`] of all modules, e.g. with a `clock::MockClock` in tests (see [`clock`]).
+    ///
+    /// Must be called before adding modules, otherwise [`OprfServiceBuilder::build`] reports an error.
+    #[must_use]
+    pub fn clock(mut self, clock: ClockService) -> Self {
+        if !self.module_paths.is_empty() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "clock must be set before adding modules",
+            ));
+            return self;
+        }
+        self.key_quota = self
+            .config
+            .key_quota
+            .map(|key_quota| KeyQuota::new(key_quota, Arc::clone(&clock)));
+        self.clock = clock;
+        self
+    }
+
+    /// Sends a [`ShareProof`](oprf_types::api::ShareProof) with every [`OprfResponse`](oprf_types::api::OprfResponse) of all modules, proving that the commitments were computed with the share of this node.
+    ///
+    /// Clients verify the proofs before counting the session toward the threshold. Creating a proof costs two scalar multiplications per query. Resumed sessions answered from the challenge replay cache carry no proof.
+    ///
+    /// Must be called before adding modules, otherwise [`OprfServiceBuilder::build`] reports an error.
+    #[must_use]
+    pub fn share_proofs(mut self) -> Self {
+        if !self.module_paths.is_empty() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "share_proofs must be set before adding modules",
+            ));
+            return self;
+        }
+        self.share_proofs = true;
+        self
+    }
+
+    /// Attaches a [`ResponseSignature`](oprf_types::api::ResponseSignature) of the wallet key of this node to every [`OprfResponse`](oprf_types::api::OprfResponse) of all modules, binding the session id, the commitments, the party id and the epoch to the wallet (see `oprf_types::signed_response`).
+    ///
+    /// Clients check the signer against the wallet address registered for the party id, e.g., in the `OprfKeyRegistry`. The responses are signed with [`secret_manager::SecretManager::sign_response`], which fails for the bundled secret managers, as they do not hold the wallet key. Without a secret manager that can sign, every session fails with an internal error. Resumed sessions answered from the challenge replay cache are signed again.
+    ///
+    /// Must be called before adding modules, otherwise [`OprfServiceBuilder::build`] reports an error.
+    #[must_use]
+    pub fn signed_responses(mut self) -> Self {
+        if !self.module_paths.is_empty() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "signed_responses must be set before adding modules",
+            ));
+            return self;
+        }
+        self.signed_responses = true;
+        self
+    }
+
+    /// Binds the proof shares of the module at `path` to `context`, e.g., the name of the relying party the module serves.
+    ///
+    /// The node mixes the context into the challenge of the `DLog` equality proof (see [`oprf_core::oprf::QueryContext`]), so the proof shares of the module only combine to a proof that verifies in the same context. Clients of the module additionally hash the query together with the context before mapping it to the curve (see `oprf_client::OutputDomain`), so modules with distinct contexts derive unrelated outputs. A blinded query bound to the context of one module and replayed to a module with another context is answered with a proof that does not verify in the context of the query. Modules without context keep the challenge and the outputs of clients without context.
+    ///
+    /// Must be called before the module at `path` is added. A context for an already added module or an empty context is reported by [`OprfServiceBuilder::build`].
+    #[must_use]
+    pub fn module_context(mut self, path: &str, context: &str) -> Self {
+        let normalized = path.trim_end_matches('/');
+        if self.module_paths.iter().any(|p| p == normalized) {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "module_context must be set before adding the module",
+            ));
+            return self;
+        }
+        if context.is_empty() {
+            self.error.get_or_insert(BuilderError::InvalidConfig(
+                "module_context must not be empty",
+            ));
+            return self;
+        }
+        self.module_contexts.retain(|(p, _)| p != normalized);
+        self.module_contexts.push((
+            normalized.to_owned(),
+            oprf_core::oprf::client::query_context(context).inner(),
+        ));
+        self
+    }
+
     /// Add a new `OprfRequestAuthService` module with the given `path`.
     ///
     /// Each module represents a distinct OPRF service that can handle requests
     /// authenticated using the provided `OprfRequestAuthService`.
     ///
+    /// The module shares its session ids with all other modules of this service (see [`SessionNamespace::Shared`]). Use [`OprfServiceBuilder::module_with_session_namespace`] to isolate it. Use [`OprfServiceBuilder::module_context`] to bind its responses to a context.
+    ///
     /// # Parameters
     ///
     /// - `path`: The URL path where the OPRF module will be accessible (`/api/{path}`).
     /// - `service`: An instance of `OprfRequestAuthService` that will handle authentication for this module.
+    ///
+    /// An invalid or duplicate `path` is not added and is reported by [`OprfServiceBuilder::build`].
     #[must_use]
     pub fn module<RequestAuth: for<'de> Deserialize<'de> + Send + 'static>(
-        mut self,
+        self,
         path: &str,
         service: OprfRequestAuthService<RequestAuth>,
     ) -> Self {
-        let args = Router::new().merge(self.api).nest(
+        self.module_with_session_namespace(path, service, SessionNamespace::Shared)
+    }
+
+    /// Like [`OprfServiceBuilder::module`], but lets the caller choose the [`SessionNamespace`] of the module.
+    ///
+    /// With [`SessionNamespace::Isolated`], a session id in use on this module does not block the same id on other modules and vice versa.
+    #[must_use]
+    pub fn module_with_session_namespace<
+        RequestAuth: for<'de> Deserialize<'de> + Send + 'static,
+    >(
+        self,
+        path: &str,
+        service: OprfRequestAuthService<RequestAuth>,
+        session_namespace: SessionNamespace,
+    ) -> Self {
+        let oprf_key_material_store = self.oprf_key_material_store.clone();
+        let (party_id, threshold) = (self.party_id, self.threshold);
+        self.add_module(
             path,
-            api::oprf::routes(OprfModuleState {
-                party_id: self.party_id,
-                threshold: self.threshold,
-                oprf_material_store: self.oprf_key_material_store.clone(),
-                req_auth_service: service,
-                version_req: self.config.version_req.clone(),
-                max_message_size: self.config.ws_max_message_size,
-                max_connection_lifetime: self.config.session_lifetime,
-                websocket_shutdown_timeout: self.config.websocket_shutdown_timeout,
-                open_sessions: self.open_sessions.clone(),
-            }),
+            service,
+            session_namespace,
+            oprf_key_material_store,
+            party_id,
+            threshold,
+        )
+    }
+
+    /// Like [`OprfServiceBuilder::module`], but the module serves the keys of its own [`OprfKeyMaterialStore`] instead of the store of the node.
+    ///
+    /// This lets one node serve logically separate key universes, e.g. a store backed by a secret manager with a different secret prefix or a subset of the keys. The module only loads key material from `oprf_key_material_store`, and the same key id of another module resolves to the key of that module. As key ids of separate universes may overlap, the module always uses [`SessionNamespace::Isolated`], so neither in-flight nor finished sessions are shared with other modules.
+    ///
+    /// The module uses the party id and threshold of the node. To serve the keys of another `OprfKeyRegistry`, which may assign another party id to the node, use [`OprfServiceBuilder::registry_module`] instead.
+    ///
+    /// The info routes (e.g. `/oprf_pub/{id}` and `/epoch_notifications`), replica snapshots and memory stats only cover the store of the node.
+    #[must_use]
+    pub fn module_with_key_material_store<
+        RequestAuth: for<'de> Deserialize<'de> + Send + 'static,
+    >(
+        self,
+        path: &str,
+        service: OprfRequestAuthService<RequestAuth>,
+        oprf_key_material_store: OprfKeyMaterialStore,
+    ) -> Self {
+        let (party_id, threshold) = (self.party_id, self.threshold);
+        self.add_module(
+            path,
+            service,
+            SessionNamespace::Isolated,
+            oprf_key_material_store,
+            party_id,
+            threshold,
+        )
+    }
+
+    /// Like [`OprfServiceBuilder::module_with_key_material_store`], but the module serves the keys of the `OprfKeyRegistry` at `oprf_key_registry` on `chain_id` with the party id and threshold this registry assigned to the node.
+    ///
+    /// To serve the keys of several registries from one node, back a store per registry with the secret manager the key-gen instance of that registry writes to (see `additional_oprf_key_registry_contracts` of the key-gen), add a module per registry and reconcile every store with [`OprfServiceBuilder::module_key_reconciliation`]. Key ids of the registries are namespaced by the module path.
+    ///
+    /// Loads the node information of the registry from the secret manager of `oprf_key_material_store` and binds its party id like [`OprfServiceBuilder::bind_party_id`] does, so a changed party id of the node at this registry is detected on the next start. Node information that cannot be loaded or belongs to another wallet than the node is reported by [`OprfServiceBuilder::build`], as are the errors of [`OprfServiceBuilder::bind_party_id`].
+    pub async fn registry_module<RequestAuth: for<'de> Deserialize<'de> + Send + 'static>(
+        mut self,
+        path: &str,
+        service: OprfRequestAuthService<RequestAuth>,
+        oprf_key_material_store: OprfKeyMaterialStore,
+        oprf_key_registry: &str,
+        chain_id: u64,
+    ) -> Self {
+        let node_information = match oprf_key_material_store
+            .secret_manager()
+            .load_node_information()
+            .await
+        {
+            Ok(node_information)
+                if node_information
+                    .address()
+                    .eq_ignore_ascii_case(&self.wallet_address) =>
+            {
+                node_information
+            }
+            Ok(node_information) => {
+                self.error
+                    .get_or_insert(BuilderError::ModuleNodeInformation {
+                        path: path.to_owned(),
+                        report: eyre::eyre!(
+                            "wallet {} differs from the wallet {} of the node",
+                            node_information.address(),
+                            self.wallet_address
+                        ),
+                    });
+                return self;
+            }
+            Err(report) => {
+                self.error
+                    .get_or_insert(BuilderError::ModuleNodeInformation {
+                        path: path.to_owned(),
+                        report,
+                    });
+                return self;
+            }
+        };
+        let current = secret_manager::PartyIdBinding::new(
+            &self.wallet_address,
+            node_information.party_id(),
+            oprf_key_registry,
+            chain_id,
         );
-        self.api = args;
+        if let Err(err) = services::party_id_binding::bind(
+            oprf_key_material_store.secret_manager(),
+            current,
+            self.config.accept_changed_party_id,
+        )
+        .await
+        {
+            self.error.get_or_insert(err);
+            return self;
+        }
+        self.add_module(
+            path,
+            service,
+            SessionNamespace::Isolated,
+            oprf_key_material_store,
+            node_information.party_id(),
+            node_information.threshold(),
+        )
+    }
+
+    fn add_module<RequestAuth: for<'de> Deserialize<'de> + Send + 'static>(
+        mut self,
+        path: &str,
+        service: OprfRequestAuthService<RequestAuth>,
+        session_namespace: SessionNamespace,
+        oprf_key_material_store: OprfKeyMaterialStore,
+        party_id: PartyId,
+        threshold: NonZeroU16,
+    ) -> Self {
+        if !self.register_module_path(path) {
+            return self;
+        }
+        let (session_store, challenge_replay_cache, session_handoff): (
+            OprfSessionStoreService,
+            _,
+            _,
+        ) = match session_namespace {
+            SessionNamespace::Shared => (
+                Arc::clone(&self.session_store),
+                self.challenge_replay_cache.clone(),
+                self.session_handoff.clone(),
+            ),
+            SessionNamespace::Isolated => (
+                Arc::new(LocalSessionStore::new()),
+                ChallengeReplayCache::new(
+                    self.config.challenge_replay_max_capacity,
+                    self.config.session_lifetime,
+                ),
+                None,
+            ),
+        };
+        let risk_scorer = self.module_risk_scorer(path);
+        let runtime_limits = self.module_runtime_limits(path);
+        let routes = self.module_routes(OprfModuleState {
+            party_id,
+            threshold,
+            oprf_material_store: oprf_key_material_store,
+            req_auth_service: TimeBoxedAuthService::new(service, self.config.preset().auth_timeout),
+            risk_scorer,
+            key_quota: self.key_quota.clone(),
+            key_concurrency: self.key_concurrency.clone(),
+            key_labels: self.key_labels.clone(),
+            clock: Arc::clone(&self.clock),
+            runtime_limits,
+            shutdown: self.shutdown.clone(),
+            version_req: self.config.version_req.clone(),
+            max_message_size: self.config.ws_max_message_size,
+            max_connection_lifetime: self.config.session_lifetime,
+            max_multiplexed_sessions: self.config.max_multiplexed_sessions,
+            max_batch_size: self.config.max_batch_size,
+            cbor_encoding: self.config.cbor_encoding,
+            share_proofs: self.share_proofs,
+            signed_responses: self.signed_responses,
+            websocket_shutdown_timeout: self.config.websocket_shutdown_timeout,
+            query_age_policy: QueryAgePolicy::from(&self.config),
+            close_frame_verbosity: self.config.close_frame_verbosity_or_default(),
+            session_store,
+            challenge_replay_cache,
+            session_handoff,
+            transcript_writer: TranscriptWriter::new(self.config.transcript_dir.clone()),
+            challenge_context: self.module_challenge_context(path),
+        });
+        self.api = Router::new().merge(self.api).nest(path, routes);
         self
     }
 
@@ -197,6 +1017,8 @@ impl OprfServiceBuilder {
     /// - `service`: An instance of `OprfRequestAuthService` that will handle authentication for this module.
     /// - `services`: A list of URIs of other OPRF services to which requests can be delegated.
     /// - `connector`: A connector used to establish connections to the delegate OPRF services
+    ///
+    /// An invalid or duplicate `path` is not added and is reported by [`OprfServiceBuilder::build`].
     #[must_use]
     pub fn module_with_delegate<
         RequestAuth: Serialize + for<'de> Deserialize<'de> + Clone + Send + 'static,
@@ -207,47 +1029,182 @@ impl OprfServiceBuilder {
         services: Vec<Uri>,
         connector: Connector,
     ) -> Self {
-        let args = Router::new().merge(self.api).nest(
-            path,
-            Router::new()
-                .merge(api::oprf::routes(OprfModuleState {
-                    party_id: self.party_id,
-                    threshold: self.threshold,
-                    oprf_material_store: self.oprf_key_material_store.clone(),
-                    req_auth_service: service,
-                    version_req: self.config.version_req.clone(),
-                    max_message_size: self.config.ws_max_message_size,
-                    max_connection_lifetime: self.config.session_lifetime,
-                    websocket_shutdown_timeout: self.config.websocket_shutdown_timeout,
-                    open_sessions: self.open_sessions.clone(),
-                }))
-                .merge(api::oprf_delegate::routes::<RequestAuth>(
-                    DelegateOprfState {
-                        threshold: self.threshold,
-                        services,
-                        version_req: self.config.version_req.clone(),
-                        connector,
-                    },
-                )),
-        );
-        self.api = args;
+        if !self.register_module_path(path) {
+            return self;
+        }
+        let risk_scorer = self.module_risk_scorer(path);
+        let runtime_limits = self.module_runtime_limits(path);
+        let routes = self.module_routes(OprfModuleState {
+            party_id: self.party_id,
+            threshold: self.threshold,
+            oprf_material_store: self.oprf_key_material_store.clone(),
+            req_auth_service: TimeBoxedAuthService::new(service, self.config.preset().auth_timeout),
+            risk_scorer,
+            key_quota: self.key_quota.clone(),
+            key_concurrency: self.key_concurrency.clone(),
+            key_labels: self.key_labels.clone(),
+            clock: Arc::clone(&self.clock),
+            runtime_limits,
+            shutdown: self.shutdown.clone(),
+            version_req: self.config.version_req.clone(),
+            max_message_size: self.config.ws_max_message_size,
+            max_connection_lifetime: self.config.session_lifetime,
+            max_multiplexed_sessions: self.config.max_multiplexed_sessions,
+            max_batch_size: self.config.max_batch_size,
+            cbor_encoding: self.config.cbor_encoding,
+            share_proofs: self.share_proofs,
+            signed_responses: self.signed_responses,
+            websocket_shutdown_timeout: self.config.websocket_shutdown_timeout,
+            query_age_policy: QueryAgePolicy::from(&self.config),
+            close_frame_verbosity: self.config.close_frame_verbosity_or_default(),
+            session_store: Arc::clone(&self.session_store),
+            challenge_replay_cache: self.challenge_replay_cache.clone(),
+            session_handoff: self.session_handoff.clone(),
+            transcript_writer: TranscriptWriter::new(self.config.transcript_dir.clone()),
+            challenge_context: self.module_challenge_context(path),
+        });
+        let routes = routes.merge(api::oprf_delegate::routes::<RequestAuth>(
+            DelegateOprfState {
+                threshold: self.threshold,
+                services,
+                version_req: self.config.version_req.clone(),
+                connector,
+            },
+        ));
+        self.api = Router::new().merge(self.api).nest(path, routes);
         self
     }
 
+    /// The routes of an OPRF module: the web-socket endpoint `/oprf` and, if `grpc` is enabled in the config, the gRPC transport.
+    fn module_routes<RequestAuth: for<'de> Deserialize<'de> + Send + 'static>(
+        &self,
+        state: OprfModuleState<RequestAuth>,
+    ) -> Router {
+        #[cfg(feature = "grpc")]
+        if self.config.grpc {
+            return api::oprf::routes(state.clone()).merge(api::grpc::routes(state));
+        }
+        #[cfg(not(feature = "grpc"))]
+        debug_assert!(
+            !self.config.grpc,
+            "enabling gRPC without the feature is rejected by build"
+        );
+        api::oprf::routes(state)
+    }
+
+    /// The [`TimeBoxedRiskScorer`] for the module at `path`, if a risk scorer is set.
+    fn module_risk_scorer(&self, path: &str) -> Option<TimeBoxedRiskScorer> {
+        self.risk_scorer.as_ref().map(|risk_scorer| {
+            TimeBoxedRiskScorer::new(
+                Arc::clone(risk_scorer),
+                self.config.risk_scorer_timeout,
+                self.config.preset().risk_scorer_fail_open,
+                path.trim_end_matches('/'),
+            )
+        })
+    }
+
+    /// The challenge context of the module at `path` (see [`OprfServiceBuilder::module_context`]), `0` if it has none.
+    fn module_challenge_context(&self, path: &str) -> ark_babyjubjub::Fq {
+        let normalized = path.trim_end_matches('/');
+        self.module_contexts
+            .iter()
+            .find_map(|(p, context)| (p == normalized).then_some(*context))
+            .unwrap_or_default()
+    }
+
+    /// The [`RuntimeLimits`] of the module at `path`, initialized from the config and recorded for the admin API.
+    fn module_runtime_limits(&mut self, path: &str) -> RuntimeLimits {
+        let runtime_limits = RuntimeLimits::new(Limits {
+            max_message_size: self.config.ws_max_message_size,
+            session_lifetime: self.config.session_lifetime,
+            version_req: self.config.version_req.clone(),
+        });
+        self.module_limits.push((
+            path.trim_end_matches('/').to_owned(),
+            runtime_limits.clone(),
+        ));
+        runtime_limits
+    }
+
+    /// Checks that `path` is a valid, not yet used module path and records it.
+    ///
+    /// Stores the first encountered error, which is then returned by [`OprfServiceBuilder::build`].
+    fn register_module_path(&mut self, path: &str) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        if !path.starts_with('/') || path.len() == 1 || path.contains('*') {
+            self.error = Some(BuilderError::InvalidModulePath(path.to_owned()));
+            return false;
+        }
+        let normalized = path.trim_end_matches('/');
+        if self.module_paths.iter().any(|p| p == normalized) {
+            self.error = Some(BuilderError::DuplicateModulePath(path.to_owned()));
+            return false;
+        }
+        self.module_paths.push(normalized.to_owned());
+        true
+    }
+
     /// Build the `axum` [`Router`] with all added oprf modules.
     ///
-    /// # Panics
+    /// The background tasks keep running detached, use [`OprfServiceBuilder::build_with_tasks`] to observe them.
+    ///
+    /// # Errors
     ///
-    /// - If no oprf modules were added
-    pub fn build(self) -> axum::Router {
-        assert!(self.api.has_routes(), "Needs at least 1 oprf-module");
+    /// - [`BuilderError::NoModules`] if no oprf modules were added.
+    /// - [`BuilderError::InvalidModulePath`] or [`BuilderError::DuplicateModulePath`] if a module was added with a bad path.
+    /// - [`BuilderError::InvalidConfig`] if the provided config contains unusable values.
+    /// - [`BuilderError::MetricsExporter`] if `metrics_endpoint` is enabled but the Prometheus recorder cannot be installed.
+    pub fn build(self) -> Result<axum::Router, BuilderError> {
+        let (router, _tasks) = self.build_with_tasks()?;
+        Ok(router)
+    }
+
+    /// Build the `axum` [`Router`] with all added oprf modules, together with the [`OprfServiceTasks`] spawned by the builder.
+    ///
+    /// # Errors
+    ///
+    /// See [`OprfServiceBuilder::build`].
+    pub fn build_with_tasks(self) -> Result<(axum::Router, OprfServiceTasks), BuilderError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        if !self.api.has_routes() {
+            return Err(BuilderError::NoModules);
+        }
+        self.validate_config()?;
+        let mut info_routes = self.info_routes;
+        if let Some(admin_token) = self.admin_token {
+            info_routes = info_routes.merge(api::admin::routes(
+                admin_token,
+                self.module_limits,
+                self.config.max_batch_size,
+            ));
+        }
+        if self.config.metrics_endpoint {
+            let handle = metrics::exporter::install()
+                .map_err(|err| BuilderError::MetricsExporter(err.into()))?;
+            info_routes = info_routes.merge(metrics::exporter::routes(handle));
+        }
+        let mut auth_modules = self.api;
+        if let Some(chaos) = self.config.chaos {
+            tracing::warn!(
+                ?chaos,
+                "injecting artificial latency and errors into sessions"
+            );
+            auth_modules = auth_modules.layer(axum::middleware::from_fn_with_state(
+                chaos,
+                api::chaos::inject,
+            ));
+        }
         // setup the dedicated HTTP trace layer for the auth modules
-        let auth_modules = self
-            .api
-            .layer(TraceLayer::new_for_http().make_span_with(OprfAuthModulesMakeSpan));
+        let auth_modules =
+            auth_modules.layer(TraceLayer::new_for_http().make_span_with(OprfAuthModulesMakeSpan));
 
-        Router::new()
-            .merge(self.info_routes.layer(TimeoutLayer::with_status_code(
+        let router = Router::new()
+            .merge(info_routes.layer(TimeoutLayer::with_status_code(
                 StatusCode::REQUEST_TIMEOUT,
                 self.config.http_request_timeout,
             )))
@@ -258,8 +1215,204 @@ impl OprfServiceBuilder {
                     self.config.session_lifetime, // use session lifetime align with ws timeout
                 )),
             )
-            .layer(DefaultBodyLimit::max(self.config.ws_max_message_size))
+            .layer(DefaultBodyLimit::max(self.config.ws_max_message_size));
+        let router = match &self.config.root_path {
+            Some(root_path) => Router::new().nest(root_path, router),
+            None => router,
+        };
+        let tasks = OprfServiceTasks { tasks: self.tasks };
+        Ok((router, tasks))
     }
+
+    /// Build the `axum` [`Router`] with all added oprf modules, together with a [`Shutdown`] that stops the node gracefully.
+    ///
+    /// `cancellation_token` must be the token the background tasks were spawned with (e.g. the one passed to [`OprfServiceBuilder::committee_health`]). [`Shutdown::run`] cancels it only after the open sessions drained, see [`shutdown`].
+    ///
+    /// # Errors
+    ///
+    /// See [`OprfServiceBuilder::build`].
+    pub fn build_with_shutdown(
+        self,
+        cancellation_token: CancellationToken,
+    ) -> Result<(axum::Router, Shutdown), BuilderError> {
+        let signal = self.shutdown.clone();
+        let (router, tasks) = self.build_with_tasks()?;
+        Ok((router, Shutdown::new(signal, tasks, cancellation_token)))
+    }
+
+    /// Checks the config for unusable values, see [`BuilderError::InvalidConfig`].
+    fn validate_config(&self) -> Result<(), BuilderError> {
+        if self.config.ws_max_message_size == 0 {
+            return Err(BuilderError::InvalidConfig(
+                "ws_max_message_size must be greater than 0",
+            ));
+        }
+        if self.config.max_batch_size == 0 {
+            return Err(BuilderError::InvalidConfig(
+                "max_batch_size must be greater than 0",
+            ));
+        }
+        if self.config.epoch_notifications_lifetime.is_zero() {
+            return Err(BuilderError::InvalidConfig(
+                "epoch_notifications_lifetime must be greater than 0",
+            ));
+        }
+        if self.config.preset().auth_timeout.is_zero() {
+            return Err(BuilderError::InvalidConfig(
+                "auth_timeout must be greater than 0",
+            ));
+        }
+        if self.risk_scorer.is_some() && self.config.risk_scorer_timeout.is_zero() {
+            return Err(BuilderError::InvalidConfig(
+                "risk_scorer_timeout must be greater than 0",
+            ));
+        }
+        if self.config.session_lifetime.is_zero() {
+            return Err(BuilderError::InvalidConfig(
+                "session_lifetime must be greater than 0",
+            ));
+        }
+        if self.config.root_path.as_deref().is_some_and(|root_path| {
+            !root_path.starts_with('/') || root_path.ends_with('/') || root_path.contains("//")
+        }) {
+            return Err(BuilderError::InvalidConfig(
+                "root_path must start with `/` and must not end with `/`",
+            ));
+        }
+        if self.config.grpc && !cfg!(feature = "grpc") {
+            return Err(BuilderError::InvalidConfig(
+                "grpc requires the `grpc` feature",
+            ));
+        }
+        if (self.config.session_persistence == SessionPersistence::Durable)
+            != self.session_store.is_durable()
+        {
+            return Err(BuilderError::InvalidConfig(
+                "durable session_persistence requires a durable session_store and vice versa",
+            ));
+        }
+        if self.config.http_request_timeout.is_zero() {
+            return Err(BuilderError::InvalidConfig(
+                "http_request_timeout must be greater than 0",
+            ));
+        }
+        if let Some(key_quota) = self.config.key_quota {
+            if key_quota.window.is_zero() {
+                return Err(BuilderError::InvalidConfig(
+                    "key_quota window must be greater than 0",
+                ));
+            }
+            let burst = key_quota.burst.unwrap_or(key_quota.max_evaluations);
+            if usize::try_from(burst.get()).is_ok_and(|burst| burst < self.config.max_batch_size) {
+                return Err(BuilderError::InvalidConfig(
+                    "key_quota burst must be at least max_batch_size",
+                ));
+            }
+        }
+        if let Some(chaos) = self.config.chaos {
+            if self.config.environment == Environment::Prod {
+                return Err(BuilderError::InvalidConfig(
+                    "chaos must not be enabled in prod",
+                ));
+            }
+            if !(0.0..=1.0).contains(&chaos.latency_rate)
+                || !(0.0..=1.0).contains(&chaos.error_rate)
+            {
+                return Err(BuilderError::InvalidConfig("chaos rates must be in [0, 1]"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a background task of the OPRF node stopped.
+///
+/// Allows hosts to decide between a clean shutdown, a restart, or an alert.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ExitReason {
+    /// The cancellation token of the task was cancelled from the outside.
+    Cancelled,
+    /// The task crashed with an error or panicked.
+    Failed(eyre::Report),
+}
+
+/// The background tasks spawned by the [`OprfServiceBuilder`], see [`OprfServiceBuilder::build_with_tasks`].
+///
+/// Dropping this detaches the tasks, they keep running until their cancellation token is cancelled.
+#[derive(Debug)]
+pub struct OprfServiceTasks {
+    tasks: Vec<JoinHandle<ExitReason>>,
+}
+
+impl OprfServiceTasks {
+    /// Waits until the first background task stopped and returns why.
+    ///
+    /// Returns `None` if the builder spawned no background task. A panic of a task is reported as [`ExitReason::Failed`]. The other tasks keep running detached.
+    pub async fn run(self) -> Option<ExitReason> {
+        if self.tasks.is_empty() {
+            return None;
+        }
+        let (result, _, _) = futures::future::select_all(self.tasks).await;
+        Some(result.unwrap_or_else(|err| ExitReason::Failed(err.into())))
+    }
+}
+
+/// Controls which modules share the namespace of session ids (the `request_id` of an [`oprf_types::api::OprfRequest`]).
+///
+/// A session id can only be used by one in-flight session per namespace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionNamespace {
+    /// The module shares session ids with all other shared modules. This is the default.
+    #[default]
+    Shared,
+    /// The module has its own session ids, so other tenants cannot block them.
+    ///
+    /// Only isolate modules that authenticate requests for disjoint OPRF keys, as the same session id may otherwise be used concurrently for one key.
+    Isolated,
+}
+
+/// Errors returned by [`OprfServiceBuilder::build`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum BuilderError {
+    /// No OPRF module was added to the builder.
+    #[error("needs at least one OPRF module")]
+    NoModules,
+    /// A module path must start with `/`, must not be the root and must not contain wildcards.
+    #[error("invalid module path: {0:?}")]
+    InvalidModulePath(String),
+    /// A module was added twice with the same path.
+    #[error("module path {0:?} was added more than once")]
+    DuplicateModulePath(String),
+    /// The provided [`OprfNodeServiceConfig`] contains an unusable value.
+    #[error("invalid config: {0}")]
+    InvalidConfig(&'static str),
+    /// The party id binding differs from the one persisted on an earlier start, see [`OprfServiceBuilder::bind_party_id`].
+    #[error(
+        "persisted {persisted} differs from {current}, set accept_changed_party_id to accept the change"
+    )]
+    PartyIdChanged {
+        /// The binding persisted on an earlier start
+        persisted: Box<secret_manager::PartyIdBinding>,
+        /// The binding of this start
+        current: Box<secret_manager::PartyIdBinding>,
+    },
+    /// The secret manager cannot load or store the party id binding.
+    #[error("cannot load or store the party id binding: {0:?}")]
+    PartyIdBindingUnavailable(eyre::Report),
+    /// The node information of a module added with [`OprfServiceBuilder::registry_module`] cannot be loaded or belongs to another wallet than the node.
+    #[error("invalid node information for module {path:?}: {report:?}")]
+    ModuleNodeInformation {
+        /// The path of the module
+        path: String,
+        /// Why the node information is invalid
+        report: eyre::Report,
+    },
+    /// The Prometheus recorder for `metrics_endpoint` cannot be installed, e.g. because another global metrics recorder is installed.
+    #[error("cannot install the Prometheus exporter: {0:?}")]
+    MetricsExporter(eyre::Report),
 }
 
 #[derive(Clone, Copy)]
@@ -285,6 +1438,9 @@ impl fmt::Debug for FilteredHeaders<'_> {
     }
 }
 
+#[cfg(test)]
+mod tests;
+
 impl<B> MakeSpan<B> for OprfAuthModulesMakeSpan {
     fn make_span(&mut self, request: &http::Request<B>) -> tracing::Span {
         let matched_path = request
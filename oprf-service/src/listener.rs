@@ -0,0 +1,154 @@
+//! Binds the HTTP server of a node to several addresses, e.g., `0.0.0.0:4321` and `[::]:4321` for IPv4 and IPv6.
+//!
+//! [`MultiListener`] accepts the connections of all bound addresses and is passed to [`axum::serve`] like a [`TcpListener`]. [`local_base_url`] returns the URL under which a bound address is reachable from the same host, e.g., for health checks.
+
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use axum::serve::Listener;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Backlog of pending connections per bound address, the same as [`TcpListener::bind`].
+const BACKLOG: i32 = 1024;
+
+/// A listener accepting the connections of one or more bound addresses.
+#[derive(Debug)]
+pub struct MultiListener {
+    listeners: Vec<TcpListener>,
+}
+
+impl MultiListener {
+    /// Binds all `addrs`.
+    ///
+    /// If more than one address is bound, IPv6 addresses only accept IPv6 connections, so an IPv4 and an IPv6 wildcard address can share a port. A single IPv6 address keeps the dual-stack default of the OS.
+    ///
+    /// # Errors
+    /// Returns an error if `addrs` is empty or one of the addresses cannot be bound.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    pub fn bind(addrs: &[SocketAddr]) -> io::Result<Self> {
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "at least one bind address is required",
+            ));
+        }
+        let only_v6 = addrs.len() > 1;
+        let listeners = addrs
+            .iter()
+            .map(|addr| bind(*addr, only_v6))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { listeners })
+    }
+
+    /// Returns the bound addresses, in the order of [`MultiListener::bind`].
+    ///
+    /// # Errors
+    /// Returns an error if the address of a socket cannot be read.
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.listeners.iter().map(TcpListener::local_addr).collect()
+    }
+}
+
+impl Listener for MultiListener {
+    type Io = TcpStream;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        // accepting is cancel safe, the connections of the other listeners stay in their backlog
+        let accepts = self
+            .listeners
+            .iter_mut()
+            .map(|listener| Box::pin(Listener::accept(listener)));
+        futures::future::select_all(accepts).await.0
+    }
+
+    /// Returns the first bound address, see [`MultiListener::local_addrs`] for all of them.
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.listeners[0].local_addr()
+    }
+}
+
+fn bind(addr: SocketAddr, only_v6: bool) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() && only_v6 {
+        socket.set_only_v6(true)?;
+    }
+    // same as tokio, so restarted nodes can bind while old connections are in TIME_WAIT
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Returns the base URL (`http://{ip}:{port}`) under which a server bound to `addr` is reachable from the same host.
+///
+/// Wildcard addresses (`0.0.0.0` and `[::]`) are replaced by the loopback address of their family, IPv6 addresses are enclosed in brackets.
+#[must_use]
+pub fn local_base_url(addr: SocketAddr) -> String {
+    let mut addr = addr;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+    format!("http://{addr}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_base_url_uses_loopback_for_wildcards() {
+        let url = |addr: &str| local_base_url(addr.parse().expect("valid addr"));
+        assert_eq!(url("0.0.0.0:4321"), "http://127.0.0.1:4321");
+        assert_eq!(url("[::]:4321"), "http://[::1]:4321");
+        assert_eq!(url("10.0.0.1:4321"), "http://10.0.0.1:4321");
+        assert_eq!(url("[fe80::1]:4321"), "http://[fe80::1]:4321");
+    }
+
+    #[tokio::test]
+    async fn serves_ipv4_and_ipv6_on_the_same_port() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("can bind")
+            .local_addr()
+            .expect("has addr")
+            .port();
+        let addrs = [
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)),
+            SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)),
+        ];
+        let listener = match MultiListener::bind(&addrs) {
+            Ok(listener) => listener,
+            // hosts without IPv6
+            Err(err) if err.kind() == io::ErrorKind::AddrNotAvailable => return,
+            Err(err) => panic!("cannot bind both addresses: {err}"),
+        };
+        assert_eq!(
+            listener.local_addrs().expect("has addrs"),
+            addrs,
+            "should bind all addresses"
+        );
+        let router = axum::Router::new().route("/health", axum::routing::get(|| async { "ok" }));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        for addr in addrs {
+            let url = format!("{}/health", local_base_url(addr));
+            let response = reqwest::get(&url).await.expect("can reach server");
+            assert_eq!(
+                response.text().await.expect("has body"),
+                "ok",
+                "should serve {url}"
+            );
+        }
+    }
+}
@@ -11,6 +11,10 @@ pub fn describe_metrics() {
     request::describe_metrics();
     sessions::describe_metrics();
     secrets::describe_metrics();
+    committee::describe_metrics();
+    keys::describe_metrics();
+    startup::describe_metrics();
+    shutdown::describe_metrics();
 }
 
 pub(crate) mod request {
@@ -29,9 +33,47 @@ pub(crate) mod request {
     /// Metrics key for the duration of part two of the OPRF computation
     const METRICS_ID_NODE_PART_2_DURATION: &str = "taceo.oprf.node.request.part2.duration";
 
+    /// The request duration histograms that carry exemplars, see [`super::exemplars`].
+    #[cfg(feature = "exemplars")]
+    pub(super) const DURATION_METRICS: [&str; 3] = [
+        METRICS_ID_NODE_REQUEST_VERIFY_DURATION,
+        METRICS_ID_NODE_PART_1_DURATION,
+        METRICS_ID_NODE_PART_2_DURATION,
+    ];
+
+    /// Metrics key for counting authentications that exceeded the auth timeout
+    const METRICS_ID_NODE_AUTH_TIMEOUT: &str = "taceo.oprf.node.request.verify.timeout";
+
+    /// Metrics key for counting requests authenticated from the per-connection auth cache
+    const METRICS_ID_NODE_AUTH_CACHE_HIT: &str = "taceo.oprf.node.request.verify.cached";
+
+    /// Metrics key for counting resumed sessions answered from the challenge replay cache
+    const METRICS_ID_NODE_CHALLENGE_REPLAY: &str = "taceo.oprf.node.request.replay";
+
+    /// Metrics key for counting pending sessions resumed from a durable session store
+    const METRICS_ID_NODE_SESSION_RESUMED: &str = "taceo.oprf.node.request.resumed";
+
+    /// Metrics key for the number of queries of batch sessions
+    const METRICS_ID_NODE_BATCH_SIZE: &str = "taceo.oprf.node.request.batch.size";
+
+    /// Metrics key for counting the evaluations checked against the key quota
+    const METRICS_ID_NODE_KEY_QUOTA: &str = "taceo.oprf.node.request.quota";
+
+    /// Metrics key for counting the faults injected by the chaos config
+    const METRICS_ID_NODE_CHAOS_INJECTED: &str = "taceo.oprf.node.request.chaos";
+
+    /// Metrics key for counting the decisions of the risk scorer
+    const METRICS_ID_NODE_RISK_DECISION: &str = "taceo.oprf.node.risk.decision";
+
+    /// Metrics key for the duration of risk scoring
+    const METRICS_ID_NODE_RISK_DURATION: &str = "taceo.oprf.node.risk.duration";
+
     /// Metrics key for how often we reject clients due to version mismatch.
     const METRICS_CLIENT_VERSION_MISMATCH: &str = "taceo.oprf.node.client.invalid_version";
 
+    /// Metrics key for how often we reject clients due to a schema fingerprint mismatch.
+    const METRICS_CLIENT_SCHEMA_MISMATCH: &str = "taceo.oprf.node.client.invalid_schema";
+
     /// Metrics key for how often we terminated user connection due to timeout.
     const METRICS_CLIENT_TIMEOUT: &str = "taceo.oprf.node.request.timeout";
 
@@ -61,16 +103,70 @@ pub(crate) mod request {
             "Duration of successful OprfRequestAuth verification"
         );
 
+        metrics::describe_counter!(
+            METRICS_ID_NODE_AUTH_TIMEOUT,
+            metrics::Unit::Count,
+            "How often OprfRequestAuth verification exceeded the auth timeout"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_AUTH_CACHE_HIT,
+            metrics::Unit::Count,
+            "Number of requests authenticated from the per-connection auth cache"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_CHALLENGE_REPLAY,
+            metrics::Unit::Count,
+            "Number of resumed sessions answered with a cached proof share"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_SESSION_RESUMED,
+            metrics::Unit::Count,
+            "Number of pending sessions resumed from a durable session store"
+        );
+
+        metrics::describe_histogram!(
+            METRICS_ID_NODE_BATCH_SIZE,
+            metrics::Unit::Count,
+            "Number of queries of batch sessions"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_KEY_QUOTA,
+            metrics::Unit::Count,
+            "Evaluations checked against the quota of their OPRF key, labeled by `outcome` (allowed, exceeded)"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_CHAOS_INJECTED,
+            metrics::Unit::Count,
+            "Faults injected by the chaos config, labeled by `fault` (latency, error)"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_RISK_DECISION,
+            metrics::Unit::Count,
+            "Decisions of the risk scorer, labeled by `decision` (allow, deny, throttle, timeout, error)"
+        );
+
+        metrics::describe_histogram!(
+            METRICS_ID_NODE_RISK_DURATION,
+            metrics::Unit::Milliseconds,
+            "Duration of risk scoring, including timeouts and errors"
+        );
+
         metrics::describe_histogram!(
             METRICS_ID_NODE_PART_1_DURATION,
             metrics::Unit::Milliseconds,
-            "Duration of the OPRF computation part one"
+            "Duration of the OPRF computation part one, labeled by `oprf_key_id` if enabled"
         );
 
         metrics::describe_histogram!(
             METRICS_ID_NODE_PART_2_DURATION,
             metrics::Unit::Milliseconds,
-            "Duration of the OPRF computation part two"
+            "Duration of the OPRF computation part two, labeled by `oprf_key_id` if enabled"
         );
 
         metrics::describe_counter!(
@@ -79,6 +175,12 @@ pub(crate) mod request {
             "How often we rejected clients due to version mismatch"
         );
 
+        metrics::describe_counter!(
+            METRICS_CLIENT_SCHEMA_MISMATCH,
+            metrics::Unit::Count,
+            "How often we rejected clients due to a message schema fingerprint mismatch"
+        );
+
         metrics::describe_counter!(
             METRICS_CLIENT_TIMEOUT,
             metrics::Unit::Count,
@@ -98,6 +200,47 @@ pub(crate) mod request {
         );
     }
 
+    pub(crate) fn inc_auth_timeout() {
+        metrics::counter!(METRICS_ID_NODE_AUTH_TIMEOUT).increment(1);
+    }
+
+    pub(crate) fn inc_auth_cache_hit() {
+        metrics::counter!(METRICS_ID_NODE_AUTH_CACHE_HIT).increment(1);
+    }
+
+    pub(crate) fn inc_challenge_replay() {
+        metrics::counter!(METRICS_ID_NODE_CHALLENGE_REPLAY).increment(1);
+    }
+
+    pub(crate) fn inc_session_resumed() {
+        metrics::counter!(METRICS_ID_NODE_SESSION_RESUMED).increment(1);
+    }
+
+    pub(crate) fn record_batch_size(num_queries: usize) {
+        metrics::histogram!(METRICS_ID_NODE_BATCH_SIZE).record(num_queries as f64);
+    }
+
+    pub(crate) fn inc_key_quota(outcome: &'static str, evaluations: usize) {
+        metrics::counter!(METRICS_ID_NODE_KEY_QUOTA, "outcome" => outcome)
+            .increment(evaluations as u64);
+    }
+
+    pub(crate) fn inc_chaos_injected(fault: &'static str) {
+        metrics::counter!(METRICS_ID_NODE_CHAOS_INJECTED, "fault" => fault).increment(1);
+    }
+
+    pub(crate) fn inc_risk_decision(decision: &'static str) {
+        metrics::counter!(METRICS_ID_NODE_RISK_DECISION, "decision" => decision).increment(1);
+    }
+
+    pub(crate) fn record_risk_duration(duration: Duration) {
+        metrics::histogram!(METRICS_ID_NODE_RISK_DURATION).record(duration.as_millis() as f64);
+    }
+
+    pub(crate) fn inc_client_schema_mismatch() {
+        metrics::counter!(METRICS_CLIENT_SCHEMA_MISMATCH).increment(1);
+    }
+
     pub(crate) fn inc_client_version_mismatch() {
         metrics::counter!(METRICS_CLIENT_VERSION_MISMATCH).increment(1);
     }
@@ -115,16 +258,28 @@ pub(crate) mod request {
     }
 
     pub(crate) fn record_verify_duration(duration: Duration) {
-        metrics::histogram!(METRICS_ID_NODE_REQUEST_VERIFY_DURATION)
-            .record(duration.as_millis() as f64);
+        record_duration(METRICS_ID_NODE_REQUEST_VERIFY_DURATION, duration, None);
+    }
+
+    /// Records the duration of part one, labeled by `key_label` if per-key labels are enabled (see `crate::services::key_labels`).
+    pub(crate) fn record_part1_duration(duration: Duration, key_label: Option<String>) {
+        record_duration(METRICS_ID_NODE_PART_1_DURATION, duration, key_label);
     }
 
-    pub(crate) fn record_part1_duration(duration: Duration) {
-        metrics::histogram!(METRICS_ID_NODE_PART_1_DURATION).record(duration.as_millis() as f64);
+    /// Records the duration of part two, labeled by `key_label` if per-key labels are enabled (see `crate::services::key_labels`).
+    pub(crate) fn record_part2_duration(duration: Duration, key_label: Option<String>) {
+        record_duration(METRICS_ID_NODE_PART_2_DURATION, duration, key_label);
     }
 
-    pub(crate) fn record_part2_duration(duration: Duration) {
-        metrics::histogram!(METRICS_ID_NODE_PART_2_DURATION).record(duration.as_millis() as f64);
+    fn record_duration(metric: &'static str, duration: Duration, key_label: Option<String>) {
+        let millis = duration.as_millis() as f64;
+        if let Some(key_label) = key_label {
+            metrics::histogram!(metric, "oprf_key_id" => key_label).record(millis);
+        } else {
+            metrics::histogram!(metric).record(millis);
+        }
+        #[cfg(feature = "exemplars")]
+        super::exemplars::record(metric, millis);
     }
 
     pub(crate) fn inc_delegate_request() {
@@ -166,9 +321,14 @@ pub(crate) mod request {
 }
 
 pub(crate) mod sessions {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
     /// Metrics key for counting currently running sessions.
     const METRICS_ID_NODE_SESSIONS_OPEN: &str = "taceo.oprf.node.sessions.open";
 
+    /// The number of live session guards, readable by the node itself (see [`open`]). Unlike the gauge, not reset on startup.
+    static OPEN: AtomicU64 = AtomicU64::new(0);
+
     pub(super) fn describe_metrics() {
         metrics::describe_gauge!(
             METRICS_ID_NODE_SESSIONS_OPEN,
@@ -182,12 +342,23 @@ pub(crate) mod sessions {
     }
 
     pub(crate) fn inc() {
+        OPEN.fetch_add(1, Ordering::Relaxed);
         ::metrics::gauge!(METRICS_ID_NODE_SESSIONS_OPEN).increment(1);
     }
 
     pub(crate) fn dec() {
+        OPEN.fetch_sub(1, Ordering::Relaxed);
         ::metrics::gauge!(METRICS_ID_NODE_SESSIONS_OPEN).decrement(1);
     }
+
+    /// The number of currently open sessions of all modules of this process.
+    #[cfg_attr(
+        not(feature = "jemalloc"),
+        allow(dead_code, reason = "only read by the memory stats")
+    )]
+    pub(crate) fn open() -> u64 {
+        OPEN.load(Ordering::Relaxed)
+    }
 }
 
 pub(crate) mod secrets {
@@ -198,6 +369,15 @@ pub(crate) mod secrets {
     const METRICS_ID_NODE_OPRF_SECRETS_MISSES: &str = "taceo.oprf.node.secrets.misses";
     /// Number of hits in the `DLogSecrets` cache.
     const METRICS_ID_NODE_OPRF_SECRETS_HITS: &str = "taceo.oprf.node.secrets.hits";
+    /// Number of keys evicted from the `DLogSecrets` cache, labeled by `cause`.
+    const METRICS_ID_NODE_OPRF_SECRETS_EVICTIONS: &str = "taceo.oprf.node.secrets.evictions";
+    /// Number of unknown or deleted keys in the negative cache.
+    const METRICS_ID_NODE_OPRF_SECRETS_NEGATIVE: &str = "taceo.oprf.node.secrets.negative";
+    /// Number of requests for unknown or deleted keys answered from the negative cache.
+    const METRICS_ID_NODE_OPRF_SECRETS_NEGATIVE_HITS: &str =
+        "taceo.oprf.node.secrets.negative.hits";
+    /// Number of cached keys the reconciliation with the `OprfKeyRegistry` reloaded or evicted, labeled by `outcome`.
+    const METRICS_ID_NODE_OPRF_SECRETS_RECONCILED: &str = "taceo.oprf.node.secrets.reconciled";
 
     pub(super) fn describe_metrics() {
         metrics::describe_gauge!(
@@ -217,6 +397,30 @@ pub(crate) mod secrets {
             metrics::Unit::Count,
             "Number of hits in the oprf-secrets cache."
         );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_OPRF_SECRETS_EVICTIONS,
+            metrics::Unit::Count,
+            "Number of keys evicted from the oprf-secrets cache because it was full (size) or the key expired (expired)."
+        );
+
+        metrics::describe_gauge!(
+            METRICS_ID_NODE_OPRF_SECRETS_NEGATIVE,
+            metrics::Unit::Count,
+            "Number of unknown or deleted keys in the negative cache"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_OPRF_SECRETS_NEGATIVE_HITS,
+            metrics::Unit::Count,
+            "Number of lookups of unknown or deleted keys answered without the secret manager"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_OPRF_SECRETS_RECONCILED,
+            metrics::Unit::Count,
+            "Number of cached keys reloaded or evicted after comparing them with the OprfKeyRegistry"
+        );
     }
 
     pub(crate) fn set(x: u64) {
@@ -230,4 +434,403 @@ pub(crate) mod secrets {
     pub(crate) fn miss() {
         metrics::counter!(METRICS_ID_NODE_OPRF_SECRETS_MISSES).increment(1);
     }
+
+    pub(crate) fn inc_evicted(cause: &'static str) {
+        metrics::counter!(METRICS_ID_NODE_OPRF_SECRETS_EVICTIONS, "cause" => cause).increment(1);
+    }
+
+    pub(crate) fn set_negative(x: u64) {
+        ::metrics::gauge!(METRICS_ID_NODE_OPRF_SECRETS_NEGATIVE).set(x as f64);
+    }
+
+    pub(crate) fn negative_hit() {
+        metrics::counter!(METRICS_ID_NODE_OPRF_SECRETS_NEGATIVE_HITS).increment(1);
+    }
+
+    #[cfg(feature = "registry")]
+    pub(crate) fn inc_reconciled(outcome: &'static str) {
+        metrics::counter!(METRICS_ID_NODE_OPRF_SECRETS_RECONCILED, "outcome" => outcome)
+            .increment(1);
+    }
+}
+
+pub(crate) mod committee {
+    /// Metrics key for the number of healthy peers in the committee.
+    const METRICS_ID_NODE_COMMITTEE_HEALTHY_PEERS: &str = "taceo.oprf.node.committee.healthy_peers";
+
+    pub(super) fn describe_metrics() {
+        metrics::describe_gauge!(
+            METRICS_ID_NODE_COMMITTEE_HEALTHY_PEERS,
+            metrics::Unit::Count,
+            "Number of peers that reported healthy in the last committee health poll"
+        );
+    }
+
+    pub(crate) fn set_healthy_peers(healthy_peers: usize) {
+        ::metrics::gauge!(METRICS_ID_NODE_COMMITTEE_HEALTHY_PEERS).set(healthy_peers as f64);
+    }
+}
+
+pub(crate) mod keys {
+    use oprf_types::OprfKeyId;
+
+    /// Metrics key for the share of the concurrent sessions of a key that are in use
+    const METRICS_ID_NODE_KEY_UTILIZATION: &str = "taceo.oprf.node.request.key.utilization";
+
+    /// Metrics key for the number of sessions holding a permit of their key
+    const METRICS_ID_NODE_KEY_SESSIONS: &str = "taceo.oprf.node.request.key.sessions";
+
+    /// Metrics key for counting the sessions rejected because their key was busy
+    const METRICS_ID_NODE_KEY_BUSY: &str = "taceo.oprf.node.request.key.busy";
+
+    /// Metrics key for counting the outcome of the sessions of a key
+    const METRICS_ID_NODE_KEY_OUTCOME: &str = "taceo.oprf.node.request.key.outcome";
+
+    pub(super) fn describe_metrics() {
+        metrics::describe_gauge!(
+            METRICS_ID_NODE_KEY_UTILIZATION,
+            metrics::Unit::Percent,
+            "Share (0 to 1) of the concurrent sessions of an OPRF key that are in use, labeled by `oprf_key_id` of the keys with their own limit"
+        );
+
+        metrics::describe_gauge!(
+            METRICS_ID_NODE_KEY_SESSIONS,
+            metrics::Unit::Count,
+            "Number of sessions holding a permit of their OPRF key, across all keys"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_KEY_BUSY,
+            metrics::Unit::Count,
+            "Sessions rejected because their OPRF key ran its limit of concurrent sessions, labeled by `oprf_key_id` of the keys with their own limit or `other`"
+        );
+
+        metrics::describe_counter!(
+            METRICS_ID_NODE_KEY_OUTCOME,
+            metrics::Unit::Count,
+            "Sessions of an OPRF key, labeled by `oprf_key_id` and `outcome` (success, error)"
+        );
+    }
+
+    pub(crate) fn set_utilization(oprf_key_id: OprfKeyId, in_use: usize, limit: usize) {
+        metrics::gauge!(METRICS_ID_NODE_KEY_UTILIZATION, "oprf_key_id" => oprf_key_id.to_string())
+            .set(in_use as f64 / limit as f64);
+    }
+
+    pub(crate) fn set_sessions(in_use: usize) {
+        metrics::gauge!(METRICS_ID_NODE_KEY_SESSIONS).set(in_use as f64);
+    }
+
+    /// Counts a session rejected because its key labeled `key_label` was busy (see `crate::services::key_concurrency`).
+    pub(crate) fn inc_busy(key_label: String) {
+        metrics::counter!(METRICS_ID_NODE_KEY_BUSY, "oprf_key_id" => key_label).increment(1);
+    }
+
+    /// Counts a session with the `outcome` of the key labeled `key_label` (see `crate::services::key_labels`).
+    pub(crate) fn inc_outcome(key_label: String, outcome: &'static str) {
+        metrics::counter!(METRICS_ID_NODE_KEY_OUTCOME, "oprf_key_id" => key_label, "outcome" => outcome)
+            .increment(1);
+    }
+}
+
+pub(crate) mod startup {
+    use std::time::Duration;
+
+    /// Metrics key for the duration of [`crate::OprfServiceBuilder::load`].
+    const METRICS_ID_NODE_STARTUP_DURATION: &str = "taceo.oprf.node.startup.duration";
+
+    pub(super) fn describe_metrics() {
+        metrics::describe_gauge!(
+            METRICS_ID_NODE_STARTUP_DURATION,
+            metrics::Unit::Milliseconds,
+            "Duration of loading node information and preloading OPRF keys during startup"
+        );
+    }
+
+    pub(crate) fn duration(duration: Duration) {
+        ::metrics::gauge!(METRICS_ID_NODE_STARTUP_DURATION).set(duration.as_millis() as f64);
+    }
+}
+
+pub(crate) mod shutdown {
+    /// Metrics key for the phase of [`crate::Shutdown::run`].
+    const METRICS_ID_NODE_SHUTDOWN_PHASE: &str = "taceo.oprf.node.shutdown.phase";
+    /// Metrics key for the connections that are still open while draining.
+    const METRICS_ID_NODE_SHUTDOWN_OPEN_CONNECTIONS: &str =
+        "taceo.oprf.node.shutdown.open_connections";
+
+    /// The phases of a shutdown, reported as gauge value.
+    #[derive(Clone, Copy)]
+    pub(crate) enum Phase {
+        Draining = 1,
+        StoppingTasks = 2,
+        Stopped = 3,
+    }
+
+    pub(super) fn describe_metrics() {
+        metrics::describe_gauge!(
+            METRICS_ID_NODE_SHUTDOWN_PHASE,
+            metrics::Unit::Count,
+            "Phase of the shutdown: 0 running, 1 draining sessions, 2 stopping background tasks, 3 stopped"
+        );
+        metrics::describe_gauge!(
+            METRICS_ID_NODE_SHUTDOWN_OPEN_CONNECTIONS,
+            metrics::Unit::Count,
+            "Number of connections that are still open while the node shuts down"
+        );
+    }
+
+    pub(crate) fn phase(phase: Phase) {
+        ::metrics::gauge!(METRICS_ID_NODE_SHUTDOWN_PHASE).set(f64::from(phase as u8));
+    }
+
+    pub(crate) fn open_connections(open_connections: usize) {
+        ::metrics::gauge!(METRICS_ID_NODE_SHUTDOWN_OPEN_CONNECTIONS).set(open_connections as f64);
+    }
+}
+
+#[cfg(feature = "exemplars")]
+pub(crate) mod exemplars {
+    //! OpenMetrics exemplars for the request duration histograms.
+    //!
+    //! The `metrics` crate cannot attach exemplars to observations, so this module keeps the trace id of the latest observation per bucket of [`super::request::DURATION_METRICS`] and attaches it to the `_bucket` samples when [`super::exporter::routes`] renders OpenMetrics. Observations only get an exemplar if the current span belongs to an OpenTelemetry trace, i.e. if OTLP tracing is active.
+
+    use std::{
+        collections::{HashMap, HashSet},
+        fmt::Write as _,
+        sync::LazyLock,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use opentelemetry::trace::TraceContextExt as _;
+    use parking_lot::Mutex;
+    use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+    /// The bucket bounds in milliseconds of [`super::request::DURATION_METRICS`].
+    pub(crate) const DURATION_BUCKETS_MS: [f64; 12] = [
+        1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+    ];
+
+    /// The content type of the OpenMetrics text format.
+    pub(crate) const OPENMETRICS_CONTENT_TYPE: &str =
+        "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+    #[derive(Debug, Clone)]
+    struct Exemplar {
+        trace_id: String,
+        value: f64,
+        /// Seconds since the unix epoch.
+        timestamp: f64,
+    }
+
+    /// The latest exemplar per sanitized metric name and bucket index (`DURATION_BUCKETS_MS.len()` is `+Inf`).
+    static EXEMPLARS: LazyLock<Mutex<HashMap<(String, usize), Exemplar>>> =
+        LazyLock::new(Mutex::default);
+
+    /// Keeps the observation `value` of `metric` as exemplar of its bucket if the current span belongs to a trace.
+    pub(crate) fn record(metric: &'static str, value: f64) {
+        let context = tracing::Span::current().context();
+        let span = context.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+        store(metric, value, span_context.trace_id().to_string());
+    }
+
+    fn store(metric: &str, value: f64, trace_id: String) {
+        let bucket = DURATION_BUCKETS_MS
+            .iter()
+            .position(|le| value <= *le)
+            .unwrap_or(DURATION_BUCKETS_MS.len());
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        EXEMPLARS.lock().insert(
+            (metric.replace('.', "_"), bucket),
+            Exemplar {
+                trace_id,
+                value,
+                timestamp,
+            },
+        );
+    }
+
+    /// Converts the Prometheus text `rendered` by the exporter to the OpenMetrics text format and attaches the exemplars to the `_bucket` samples.
+    ///
+    /// OpenMetrics requires the samples of counters to end with `_total`, so scrapers see counters with that suffix.
+    pub(crate) fn to_openmetrics(rendered: &str) -> String {
+        let counters = rendered
+            .lines()
+            .filter_map(|line| line.strip_prefix("# TYPE ")?.strip_suffix(" counter"))
+            .collect::<HashSet<_>>();
+        let exemplars = EXEMPLARS.lock().clone();
+        let mut openmetrics = String::with_capacity(rendered.len());
+        for line in rendered.lines().filter(|line| !line.is_empty()) {
+            if line.starts_with('#') {
+                openmetrics.push_str(line);
+                openmetrics.push('\n');
+                continue;
+            }
+            let name_end = line.find(['{', ' ']).unwrap_or(line.len());
+            let (name, rest) = line.split_at(name_end);
+            openmetrics.push_str(name);
+            if counters.contains(name) && !name.ends_with("_total") {
+                openmetrics.push_str("_total");
+            }
+            openmetrics.push_str(rest);
+            if let Some(exemplar) = name
+                .strip_suffix("_bucket")
+                .zip(bucket_index(rest))
+                .and_then(|(name, bucket)| exemplars.get(&(name.to_owned(), bucket)))
+            {
+                write!(
+                    openmetrics,
+                    " # {{trace_id=\"{}\"}} {} {}",
+                    exemplar.trace_id, exemplar.value, exemplar.timestamp
+                )
+                .expect("writing to a String cannot fail");
+            }
+            openmetrics.push('\n');
+        }
+        openmetrics.push_str("# EOF\n");
+        openmetrics
+    }
+
+    /// The index of the bucket named by the `le` label of a `_bucket` sample.
+    fn bucket_index(labels: &str) -> Option<usize> {
+        let (_, le) = labels.split_once("le=\"")?;
+        let (le, _) = le.split_once('"')?;
+        if le == "+Inf" {
+            return Some(DURATION_BUCKETS_MS.len());
+        }
+        let le = le.parse::<f64>().ok()?;
+        DURATION_BUCKETS_MS
+            .iter()
+            .position(|bound| (bound - le).abs() < f64::EPSILON)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn openmetrics_attaches_exemplar_to_bucket() {
+            store(
+                "test.exemplar.duration",
+                7.0,
+                "0af7651916cd43dd8448eb211c80319c".to_owned(),
+            );
+            let rendered = "# TYPE test_requests counter\n\
+                            test_requests 3\n\
+                            \n\
+                            # TYPE test_exemplar_duration histogram\n\
+                            test_exemplar_duration_bucket{le=\"5\"} 0\n\
+                            test_exemplar_duration_bucket{le=\"10\"} 1\n\
+                            test_exemplar_duration_bucket{le=\"+Inf\"} 1\n\
+                            test_exemplar_duration_sum 7\n\
+                            test_exemplar_duration_count 1\n";
+            let openmetrics = to_openmetrics(rendered);
+            let lines = openmetrics.lines().collect::<Vec<_>>();
+            assert_eq!(lines[1], "test_requests_total 3", "counters need _total");
+            assert_eq!(lines[3], "test_exemplar_duration_bucket{le=\"5\"} 0");
+            assert!(
+                lines[4].starts_with(
+                    "test_exemplar_duration_bucket{le=\"10\"} 1 # {trace_id=\"0af7651916cd43dd8448eb211c80319c\"} 7 "
+                ),
+                "exemplar belongs to the bucket of the observation: {}",
+                lines[4]
+            );
+            assert_eq!(lines.last(), Some(&"# EOF"), "must end with EOF");
+            assert!(
+                !openmetrics.contains("\n\n"),
+                "must not contain empty lines"
+            );
+        }
+    }
+}
+
+pub mod exporter {
+    //! Prometheus exporter for the metrics of this crate.
+    //!
+    //! [`install`] installs a Prometheus recorder as the global `metrics` recorder and [`routes`] renders the recorded metrics at `/metrics`. Only one global recorder can be installed per process, so the exporter cannot be combined with a metrics backend of `telemetry-batteries` (keep `TELEMETRY_METRICS_BACKEND` at `none`).
+    //!
+    //! With the `exemplars` feature, the request duration histograms are rendered with fixed buckets instead of quantiles, and scrapers that accept `application/openmetrics-text` get the metrics in the OpenMetrics format with the trace ids of recent observations as exemplars. Enable exemplar storage in Prometheus (`--enable-feature=exemplar-storage`) to jump from a latency spike to the trace.
+
+    use std::sync::OnceLock;
+
+    use axum::{Router, routing::get};
+    use metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle};
+
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+    /// Installs the Prometheus recorder as the global `metrics` recorder and returns a handle to render the metrics.
+    ///
+    /// Returns the handle of the first call if the recorder was installed already.
+    ///
+    /// # Errors
+    /// Returns an error if another global recorder is installed.
+    pub fn install() -> Result<PrometheusHandle, BuildError> {
+        if let Some(handle) = HANDLE.get() {
+            return Ok(handle.clone());
+        }
+        let builder = PrometheusBuilder::new();
+        #[cfg(feature = "exemplars")]
+        let builder =
+            super::request::DURATION_METRICS
+                .into_iter()
+                .try_fold(builder, |builder, metric| {
+                    builder.set_buckets_for_metric(
+                        metrics_exporter_prometheus::Matcher::Full(metric.to_owned()),
+                        &super::exemplars::DURATION_BUCKETS_MS,
+                    )
+                })?;
+        let handle = builder.install_recorder()?;
+        Ok(HANDLE.get_or_init(|| handle).clone())
+    }
+
+    /// Serves the metrics recorded by `handle` in the Prometheus text format at `/metrics`.
+    #[cfg(not(feature = "exemplars"))]
+    pub fn routes(handle: PrometheusHandle) -> Router {
+        Router::new().route(
+            "/metrics",
+            get(move || async move {
+                handle.run_upkeep();
+                handle.render()
+            }),
+        )
+    }
+
+    /// Serves the metrics recorded by `handle` at `/metrics`, in the OpenMetrics text format with exemplars if the scraper accepts it and in the Prometheus text format otherwise.
+    #[cfg(feature = "exemplars")]
+    pub fn routes(handle: PrometheusHandle) -> Router {
+        use axum::response::IntoResponse as _;
+        use http::{HeaderMap, header};
+
+        Router::new().route(
+            "/metrics",
+            get(move |headers: HeaderMap| async move {
+                handle.run_upkeep();
+                let rendered = handle.render();
+                let openmetrics = headers
+                    .get_all(header::ACCEPT)
+                    .iter()
+                    .filter_map(|accept| accept.to_str().ok())
+                    .any(|accept| accept.contains("application/openmetrics-text"));
+                if openmetrics {
+                    (
+                        [(
+                            header::CONTENT_TYPE,
+                            super::exemplars::OPENMETRICS_CONTENT_TYPE,
+                        )],
+                        super::exemplars::to_openmetrics(&rendered),
+                    )
+                        .into_response()
+                } else {
+                    rendered.into_response()
+                }
+            }),
+        )
+    }
 }
@@ -8,10 +8,49 @@
 //!
 //! # Services overview
 //!
-//! - [`open_sessions`] – bookkeeping of all open session-ids to prevent session-id re-usage.
+//! - [`challenge_replay`] – replays proof shares of finished sessions to clients that resume them.
+//! - [`clock`] – source of the current time, replaceable in tests.
+//! - [`composite_authenticator`] – chains several authenticators of an OPRF module with AND/OR semantics.
+//! - [`committee_health`] – optional polling of the other nodes for a committee-level health view.
+//! - `committee_registry` – optional cache of the committee registered in the `OprfKeyRegistry` (requires the `registry` feature).
+//! - [`epoch_transitions`] – optional hook for host applications on epoch transitions of OPRF keys.
+//! - [`key_concurrency`] – optional limit of concurrent sessions per OPRF key.
+//! - [`key_labels`] – optional `oprf_key_id` labels of the request metrics, capped in number.
+//! - [`key_quota`] – optional quota of evaluations per OPRF key.
+//! - `key_reconciliation` – optional reconciliation of the cached keys with the `OprfKeyRegistry` (requires the `registry` feature).
 //! - [`oprf_key_material_store`] – provides a store that securely holds all OPRF key-material.
+//! - [`party_id_binding`] – detects party ids that changed since the last start.
+//! - `public_key_history` – optional history of the public keys of all finalized epochs (requires the `registry` feature).
+//! - [`replica_snapshot`] – authenticated snapshots of the key-material store to bootstrap replicas of the same node.
+//! - [`risk_scorer`] – optional hook for external fraud/risk scoring of authenticated requests.
+//! - [`runtime_limits`] – limits of the OPRF modules that can be overridden at runtime via the admin API.
 //! - [`secret_manager`] – stores and retrieves secrets.
+//! - [`session_handoff`] – optional handoff of finished and pending sessions across planned restarts.
+//! - [`session_store`] – reserves session-ids and holds the session state between the two rounds.
+//! - [`subsystem_health`] – state, last error and last processed block of the subsystems of the node, served at `/health/details`.
+//! - [`transcript_writer`] – writes opt-in transcripts of sessions for debugging.
 
-pub(crate) mod open_sessions;
+pub(crate) mod challenge_replay;
+pub mod clock;
+pub(crate) mod committee_health;
+#[cfg(feature = "registry")]
+pub(crate) mod committee_registry;
+pub mod composite_authenticator;
+pub mod epoch_transitions;
+pub(crate) mod key_concurrency;
+pub(crate) mod key_labels;
+pub(crate) mod key_quota;
+#[cfg(feature = "registry")]
+pub(crate) mod key_reconciliation;
 pub mod oprf_key_material_store;
+pub(crate) mod party_id_binding;
+#[cfg(feature = "registry")]
+pub(crate) mod public_key_history;
+pub(crate) mod replica_snapshot;
+pub mod risk_scorer;
+pub(crate) mod runtime_limits;
 pub mod secret_manager;
+pub(crate) mod session_handoff;
+pub mod session_store;
+pub mod subsystem_health;
+pub(crate) mod transcript_writer;
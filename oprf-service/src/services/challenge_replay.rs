@@ -0,0 +1,104 @@
+//! Replay of proof shares for resumed sessions.
+//!
+//! If the connection of a client breaks right after it sent the challenge, the proof share of the node is lost. The randomness of the session is consumed by the challenge, so the node cannot compute a new proof share for it. Instead, the node caches the proof share together with its commitments and the hash of the challenge for `session_lifetime`.
+//!
+//! A client resumes the session by sending the same [`oprf_types::api::OprfRequest`] (same request id and blinded query) again. The node answers with the cached commitments and, if the hash of the challenge matches, with the cached proof share. No new randomness is created. A different challenge for the same commitments is rejected, as answering it would leak the share.
+//!
+//! Replays are node-local. If the client reaches a node without a cached entry (e.g., a different replica), the node starts a fresh session with new commitments and the client has to restart the whole OPRF evaluation.
+
+use std::{sync::Arc, time::Duration};
+
+use moka::future::Cache;
+use oprf_core::ddlog_equality::shamir::{
+    DLogCommitmentsShamir, DLogProofShareShamir, PartialDLogCommitmentsShamir,
+};
+use oprf_types::{
+    OprfKeyId,
+    api::{OprfPublicKeyWithEpoch, ShareProof},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A finished session that can be replayed.
+///
+/// Contains no randomness, only what was already sent to the client, so it can be written to the handoff file (see [`crate::services::session_handoff`]).
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ReplayEntry {
+    /// The key the session was authenticated for.
+    pub(crate) oprf_key_id: OprfKeyId,
+    /// The blinded query of the session.
+    #[serde(with = "ark_serde_compat::babyjubjub::affine")]
+    pub(crate) blinded_query: ark_babyjubjub::EdwardsAffine,
+    /// The commitments sent in the first round.
+    pub(crate) commitments: PartialDLogCommitmentsShamir,
+    /// The public key and epoch sent in the first round.
+    pub(crate) oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch,
+    /// The share proof sent in the first round, if the node sends share proofs.
+    #[serde(default)]
+    pub(crate) share_proof: Option<ShareProof>,
+    /// The hash of the answered challenge, see [`ChallengeReplayCache::challenge_hash`].
+    pub(crate) challenge_hash: blake3::Hash,
+    /// The proof share for the answered challenge.
+    pub(crate) proof_share: DLogProofShareShamir,
+}
+
+/// Caches the proof shares of finished sessions by session-id, see the [module docs](self).
+#[derive(Clone)]
+pub(crate) struct ChallengeReplayCache(Option<Cache<Uuid, Arc<ReplayEntry>>>);
+
+impl ChallengeReplayCache {
+    /// Creates a cache holding at most `max_capacity` sessions for `time_to_live`. A `max_capacity` of `0` disables replays.
+    pub(crate) fn new(max_capacity: u64, time_to_live: Duration) -> Self {
+        if max_capacity == 0 {
+            return Self(None);
+        }
+        Self(Some(
+            Cache::builder()
+                .max_capacity(max_capacity)
+                .time_to_live(time_to_live)
+                .build(),
+        ))
+    }
+
+    /// Hashes the CBOR encoding of the challenge.
+    pub(crate) fn challenge_hash(challenge: &DLogCommitmentsShamir) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        ciborium::into_writer(challenge, &mut hasher).expect("Can serialize challenge");
+        hasher.finalize()
+    }
+
+    /// Stores a finished session.
+    pub(crate) async fn insert(&self, session_id: Uuid, entry: ReplayEntry) {
+        if let Some(cache) = &self.0 {
+            cache.insert(session_id, Arc::new(entry)).await;
+        }
+    }
+
+    /// Stores a finished session restored from the handoff file, see [`crate::services::session_handoff`].
+    pub(crate) async fn restore(&self, session_id: Uuid, entry: Arc<ReplayEntry>) {
+        if let Some(cache) = &self.0 {
+            cache.insert(session_id, entry).await;
+        }
+    }
+
+    /// Returns the finished session with the provided id, if it is still cached.
+    pub(crate) async fn get(&self, session_id: Uuid) -> Option<Arc<ReplayEntry>> {
+        self.0.as_ref()?.get(&session_id).await
+    }
+
+    /// Returns the (approximate) number of cached sessions.
+    #[cfg(feature = "jemalloc")]
+    pub(crate) fn entry_count(&self) -> u64 {
+        self.0.as_ref().map_or(0, Cache::entry_count)
+    }
+
+    /// Returns all cached sessions.
+    pub(crate) fn entries(&self) -> Vec<(Uuid, Arc<ReplayEntry>)> {
+        self.0.as_ref().map_or_else(Vec::new, |cache| {
+            cache
+                .iter()
+                .map(|(session_id, entry)| (*session_id, entry))
+                .collect()
+        })
+    }
+}
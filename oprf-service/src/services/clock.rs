@@ -0,0 +1,151 @@
+//! Clock of an OPRF node.
+//!
+//! Time-dependent logic of the sessions (expiry of cached authentications, the `issued_at` check of requests and the [`crate::config::OprfNodeServiceConfig::key_quota`]) reads the time from a [`Clock`] instead of calling `Instant::now()` directly, so tests can control it:
+//! - [`TokioClock`] (the default) reads [`tokio::time::Instant`], so it follows `tokio::time::pause` and `tokio::time::advance` in tests with a paused runtime.
+//! - `MockClock` (requires the `test-kit` feature) stands still until it is advanced. `MockClock::advance` works without a runtime, `MockClock::advance_runtime` also advances a paused tokio runtime, so sleeps and timeouts (e.g. the session lifetime) fire at the same time.
+//!
+//! Replace the clock of all modules with [`crate::OprfServiceBuilder::clock`].
+
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::time::Instant;
+
+#[cfg(any(test, feature = "test-kit"))]
+pub use mock::MockClock;
+
+/// Dynamic trait object for the clock.
+///
+/// Must be `Send + Sync` to work with async contexts (e.g., Axum).
+pub type ClockService = Arc<dyn Clock + Send + Sync>;
+
+/// Source of the current time, see the [module docs](self).
+pub trait Clock {
+    /// The current monotonic time.
+    fn now(&self) -> Instant;
+
+    /// The current unix time in seconds.
+    fn unix_now(&self) -> u64;
+}
+
+/// The clock of the tokio runtime and the system, see the [module docs](self).
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs())
+    }
+}
+
+#[cfg(any(test, feature = "test-kit"))]
+mod mock {
+    use std::{sync::Arc, time::Duration};
+
+    use parking_lot::Mutex;
+    use tokio::time::Instant;
+
+    use super::{Clock, TokioClock};
+
+    /// A clock for tests that only moves forward when it is advanced.
+    ///
+    /// The unix time starts at the time the clock was created and moves forward with the monotonic time, so both stay consistent. Clones share the same time.
+    #[derive(Debug, Clone)]
+    pub struct MockClock {
+        created: Instant,
+        unix_created: u64,
+        offset: Arc<Mutex<Duration>>,
+    }
+
+    impl Default for MockClock {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl MockClock {
+        /// Creates a clock at the current time.
+        #[must_use]
+        pub fn new() -> Self {
+            Self {
+                created: Instant::now(),
+                unix_created: TokioClock.unix_now(),
+                offset: Arc::new(Mutex::new(Duration::ZERO)),
+            }
+        }
+
+        /// Moves the clock forward by `duration`.
+        pub fn advance(&self, duration: Duration) {
+            *self.offset.lock() += duration;
+        }
+
+        /// Moves the clock and the paused tokio runtime forward by `duration`, see [`tokio::time::advance`].
+        ///
+        /// # Panics
+        ///
+        /// Panics if the runtime is not paused.
+        pub async fn advance_runtime(&self, duration: Duration) {
+            self.advance(duration);
+            tokio::time::advance(duration).await;
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.created + *self.offset.lock()
+        }
+
+        fn unix_now(&self) -> u64 {
+            let elapsed = self.now().saturating_duration_since(self.created);
+            self.unix_created + elapsed.as_secs()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn mock_clock_only_moves_when_advanced() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let unix_start = clock.unix_now();
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(clock.now(), start, "stands still");
+        clock.clone().advance(Duration::from_secs(5));
+        assert_eq!(
+            clock.now() - start,
+            Duration::from_secs(5),
+            "clones share the time"
+        );
+        assert_eq!(clock.unix_now() - unix_start, 5, "unix time advanced");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mock_clock_advances_paused_runtime() {
+        let clock = MockClock::new();
+        let sleep = tokio::time::sleep(Duration::from_secs(3));
+        clock.advance_runtime(Duration::from_secs(3)).await;
+        assert!(
+            futures::FutureExt::now_or_never(sleep).is_some(),
+            "sleep fired"
+        );
+        assert_eq!(
+            clock.now() - Instant::now(),
+            Duration::ZERO,
+            "clock and runtime moved together"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Committee-level health view.
+//!
+//! This optional service periodically polls the other nodes of the committee and aggregates their status into a [`CommitteeHealth`] report. For every peer it
+//!
+//! - queries `/health` to check whether the peer is up and healthy, and
+//! - compares the epochs of the OPRF keys currently cached at this node with the epochs the peer reports at `/oprf_pub/{id}`.
+//!
+//! A peer that reports an older epoch (or does not know the key at all) is listed as lagging for that key. To keep the polling lightweight, at most [`MAX_KEYS_PER_POLL`] keys are compared per round.
+//!
+//! The latest report is served at `/committee/health` (see [`crate::api::committee`]).
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use oprf_types::{OprfKeyId, ShareEpoch, api::OprfPublicKeyWithEpoch};
+use parking_lot::RwLock;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+use crate::{ExitReason, metrics, services::oprf_key_material_store::OprfKeyMaterialStore};
+
+/// Max number of cached keys whose epochs are compared with every peer per poll.
+pub(crate) const MAX_KEYS_PER_POLL: usize = 32;
+
+/// Aggregated health of the committee, as seen by this node.
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct CommitteeHealth {
+    /// Unix timestamp (seconds) of the last finished poll. `None` until the first poll finished.
+    pub(crate) last_poll: Option<u64>,
+    /// Number of peers that reported healthy.
+    pub(crate) healthy_peers: usize,
+    /// Number of configured peers.
+    pub(crate) total_peers: usize,
+    /// Status of every peer.
+    pub(crate) peers: Vec<PeerHealth>,
+}
+
+/// Health of a single peer.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PeerHealth {
+    /// Base URL of the peer.
+    pub(crate) url: String,
+    /// Whether the peer answered `/health` with a success status.
+    pub(crate) healthy: bool,
+    /// Error encountered while polling the peer, if any.
+    pub(crate) error: Option<String>,
+    /// Keys for which the peer reports an older epoch than this node.
+    pub(crate) lagging_keys: Vec<LaggingKey>,
+}
+
+/// A key for which a peer lags behind this node.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct LaggingKey {
+    /// The key in question.
+    pub(crate) oprf_key_id: OprfKeyId,
+    /// The epoch this node serves.
+    pub(crate) local_epoch: ShareEpoch,
+    /// The epoch the peer serves. `None` if the peer does not know the key.
+    pub(crate) peer_epoch: Option<ShareEpoch>,
+}
+
+/// Handle to the latest [`CommitteeHealth`] report.
+#[derive(Clone, Default)]
+pub(crate) struct CommitteeHealthService(Arc<RwLock<CommitteeHealth>>);
+
+impl CommitteeHealthService {
+    /// Spawns the polling task and returns a handle to its reports and the task.
+    ///
+    /// The task stops when `cancellation_token` is cancelled.
+    pub(crate) fn spawn(
+        peers: Vec<Url>,
+        client: reqwest::Client,
+        oprf_material_store: OprfKeyMaterialStore,
+        poll_interval: Duration,
+        cancellation_token: CancellationToken,
+    ) -> (Self, JoinHandle<ExitReason>) {
+        let service = Self::default();
+        let report = service.clone();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(poll_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    () = cancellation_token.cancelled() => break,
+                    _ = interval.tick() => {
+                        let health = poll_committee(&peers, &client, &oprf_material_store).await;
+                        metrics::committee::set_healthy_peers(health.healthy_peers);
+                        *report.0.write() = health;
+                    }
+                }
+            }
+            tracing::info!("committee health task stopped");
+            ExitReason::Cancelled
+        });
+        (service, task)
+    }
+
+    /// Returns the latest report.
+    pub(crate) fn report(&self) -> CommitteeHealth {
+        self.0.read().clone()
+    }
+}
+
+async fn poll_committee(
+    peers: &[Url],
+    client: &reqwest::Client,
+    oprf_material_store: &OprfKeyMaterialStore,
+) -> CommitteeHealth {
+    let local_epochs = oprf_material_store.cached_epochs(MAX_KEYS_PER_POLL);
+    let mut report = CommitteeHealth {
+        total_peers: peers.len(),
+        ..Default::default()
+    };
+    for peer in peers {
+        let peer_health = poll_peer(peer, client, &local_epochs).await;
+        if peer_health.healthy {
+            report.healthy_peers += 1;
+        }
+        report.peers.push(peer_health);
+    }
+    report.last_poll = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|since_epoch| since_epoch.as_secs());
+    tracing::debug!(
+        "committee health: {}/{} peers healthy",
+        report.healthy_peers,
+        report.total_peers
+    );
+    report
+}
+
+async fn poll_peer(
+    peer: &Url,
+    client: &reqwest::Client,
+    local_epochs: &[(OprfKeyId, ShareEpoch)],
+) -> PeerHealth {
+    let base = peer.as_str().trim_end_matches('/');
+    let mut peer_health = PeerHealth {
+        url: base.to_owned(),
+        healthy: false,
+        error: None,
+        lagging_keys: Vec::new(),
+    };
+    match client.get(format!("{base}/health")).send().await {
+        Ok(response) if response.status().is_success() => peer_health.healthy = true,
+        Ok(response) => {
+            peer_health.error = Some(format!("health returned {}", response.status()));
+        }
+        Err(err) => {
+            tracing::debug!(%err, "cannot reach peer {base}");
+            peer_health.error = Some(err.to_string());
+            return peer_health;
+        }
+    }
+
+    for (oprf_key_id, local_epoch) in local_epochs.iter().copied() {
+        let peer_epoch = match fetch_epoch(client, base, oprf_key_id).await {
+            Ok(peer_epoch) => peer_epoch,
+            Err(err) => {
+                tracing::debug!(%err, "cannot fetch epoch of {oprf_key_id} from {base}");
+                peer_health.error = Some(err.to_string());
+                continue;
+            }
+        };
+        if peer_epoch.is_none_or(|peer_epoch| peer_epoch < local_epoch) {
+            peer_health.lagging_keys.push(LaggingKey {
+                oprf_key_id,
+                local_epoch,
+                peer_epoch,
+            });
+        }
+    }
+    peer_health
+}
+
+/// Returns the epoch the peer serves for `oprf_key_id` or `None` if the peer does not know the key.
+async fn fetch_epoch(
+    client: &reqwest::Client,
+    base: &str,
+    oprf_key_id: OprfKeyId,
+) -> reqwest::Result<Option<ShareEpoch>> {
+    let response = client
+        .get(format!("{base}/oprf_pub/{oprf_key_id}"))
+        .send()
+        .await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let public_key = response
+        .error_for_status()?
+        .json::<OprfPublicKeyWithEpoch>()
+        .await?;
+    Ok(Some(public_key.epoch))
+}
@@ -0,0 +1,115 @@
+//! Committee as registered in the `OprfKeyRegistry` (requires the `registry` feature).
+//!
+//! This optional service reads the party ids and wallet addresses of all nodes from the `OprfKeyRegistry` contract and caches them as [`Committee`]. Afterwards, it polls the registry for `OprfPeerChanged` events and reloads the committee if a peer changed.
+//!
+//! If the registry cannot be read, the last loaded committee is kept and the service tries again in the next poll. The committee is served at `/committee` (see [`crate::api::committee`]).
+
+use std::{sync::Arc, time::Duration};
+
+use alloy::{primitives::Address, providers::Provider as _};
+use nodes_common::web3::HttpRpcProvider;
+use oprf_types::{
+    api::{Committee, CommitteeMember},
+    chain::OprfKeyRegistry::OprfKeyRegistryInstance,
+    crypto::PartyId,
+};
+use parking_lot::RwLock;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::ExitReason;
+
+/// Handle to the cached [`Committee`].
+#[derive(Clone, Default)]
+pub(crate) struct CommitteeRegistryService(Arc<RwLock<Option<Committee>>>);
+
+impl CommitteeRegistryService {
+    /// Spawns the task loading the committee and returns a handle to it and the task.
+    ///
+    /// The task stops when `cancellation_token` is cancelled.
+    pub(crate) fn spawn(
+        rpc_provider: HttpRpcProvider,
+        registry_address: Address,
+        poll_interval: Duration,
+        cancellation_token: CancellationToken,
+    ) -> (Self, JoinHandle<ExitReason>) {
+        let service = Self::default();
+        let committee = service.clone();
+        let task = tokio::spawn(async move {
+            let contract = OprfKeyRegistryInstance::new(registry_address, rpc_provider.inner());
+            let mut last_block = None;
+            let mut interval = tokio::time::interval(poll_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    () = cancellation_token.cancelled() => break,
+                    _ = interval.tick() => {
+                        match refresh(&contract, &committee, last_block).await {
+                            Ok(block) => last_block = Some(block),
+                            Err(err) => tracing::warn!(%err, "cannot refresh committee from registry"),
+                        }
+                    }
+                }
+            }
+            tracing::info!("committee registry task stopped");
+            ExitReason::Cancelled
+        });
+        (service, task)
+    }
+
+    /// Returns the cached committee, `None` until it was loaded once.
+    pub(crate) fn committee(&self) -> Option<Committee> {
+        self.0.read().clone()
+    }
+}
+
+/// Reloads the committee if it was never loaded or a peer changed since `last_block`. Returns the latest inspected block.
+async fn refresh(
+    contract: &OprfKeyRegistryInstance<alloy::providers::DynProvider>,
+    committee: &CommitteeRegistryService,
+    last_block: Option<u64>,
+) -> eyre::Result<u64> {
+    let latest_block = contract.provider().get_block_number().await?;
+    if let Some(last_block) = last_block {
+        if latest_block <= last_block {
+            return Ok(last_block);
+        }
+        let changes = contract
+            .OprfPeerChanged_filter()
+            .from_block(last_block + 1)
+            .to_block(latest_block)
+            .query()
+            .await?;
+        if changes.is_empty() {
+            return Ok(latest_block);
+        }
+        for (change, _) in &changes {
+            tracing::info!("party {} changed to {}", change.partyId, change.newPeer);
+        }
+    }
+    let loaded = load_committee(contract).await?;
+    tracing::info!("loaded committee with {} nodes", loaded.members.len());
+    *committee.0.write() = Some(loaded);
+    Ok(latest_block)
+}
+
+async fn load_committee(
+    contract: &OprfKeyRegistryInstance<alloy::providers::DynProvider>,
+) -> eyre::Result<Committee> {
+    let num_peers = contract.numPeers().call().await?;
+    let mut members = Vec::with_capacity(usize::from(num_peers));
+    for party_id in 0..num_peers {
+        let address = contract
+            .peerAddresses(alloy::primitives::U256::from(party_id))
+            .call()
+            .await?;
+        members.push(CommitteeMember {
+            party_id: PartyId(party_id),
+            address: address.to_checksum(None),
+        });
+    }
+    Ok(Committee { members })
+}
+
+#[cfg(test)]
+mod tests;
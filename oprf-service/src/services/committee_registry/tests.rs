@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use axum_test::TestServerBuilder;
+use oprf_types::crypto::PartyId;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    test_kit::MockAuthenticator,
+    test_utils::{builder_with_config, default_config},
+};
+
+#[tokio::test]
+async fn committee_registry_reloads_on_peer_change() {
+    use alloy::{
+        primitives::{Address, U64},
+        providers::mock::Asserter,
+        rpc::types::Log,
+        sol_types::{SolCall as _, SolEvent as _},
+    };
+    use oprf_types::{
+        api::Committee,
+        chain::OprfKeyRegistry::{OprfPeerChanged, numPeersCall, peerAddressesCall},
+    };
+
+    let registry_address = Address::repeat_byte(0xaa);
+    let peers = [Address::repeat_byte(1), Address::repeat_byte(2)];
+    let new_peer = Address::repeat_byte(3);
+    let asserter = Asserter::new();
+    let push_committee = |peers: &[Address]| {
+        asserter.push_success(&alloy::primitives::Bytes::from(
+            numPeersCall::abi_encode_returns(&u16::try_from(peers.len()).expect("fits")),
+        ));
+        for peer in peers {
+            asserter.push_success(&alloy::primitives::Bytes::from(
+                peerAddressesCall::abi_encode_returns(peer),
+            ));
+        }
+    };
+    // first poll loads the committee
+    asserter.push_success(&U64::from(10));
+    push_committee(&peers);
+    // second poll sees no changes
+    asserter.push_success(&U64::from(11));
+    asserter.push_success(&Vec::<Log>::new());
+    // third poll sees a changed peer and reloads
+    asserter.push_success(&U64::from(12));
+    asserter.push_success(&vec![Log {
+        inner: alloy::primitives::Log {
+            address: registry_address,
+            data: OprfPeerChanged {
+                partyId: 1,
+                oldPeer: peers[1],
+                newPeer: new_peer,
+            }
+            .encode_log_data(),
+        },
+        block_number: Some(12),
+        ..Default::default()
+    }]);
+    push_committee(&[peers[0], new_peer]);
+
+    let mut config = default_config();
+    config.committee_poll_interval = Duration::from_millis(20);
+    let cancellation_token = CancellationToken::new();
+    let server = TestServerBuilder::new()
+        .build(
+            builder_with_config(config)
+                .committee_registry(
+                    asserter.clone().into(),
+                    registry_address,
+                    cancellation_token.clone(),
+                )
+                .module("/test", MockAuthenticator::allow_all().into_service())
+                .build()
+                .expect("Can build node"),
+        )
+        .expect("Can build test-server");
+
+    let mut committee = None;
+    for _ in 0..100 {
+        let response = server.get("/committee").await;
+        if response.status_code().is_success() {
+            let current = response.json::<Committee>();
+            if current.party_id_of(&new_peer.to_checksum(None)).is_some() {
+                committee = Some(current);
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    cancellation_token.cancel();
+    let committee = committee.expect("should reload the committee after the peer changed");
+    assert_eq!(
+        committee.party_id_of(&peers[0].to_string().to_lowercase()),
+        Some(PartyId(0)),
+        "should keep the unchanged peer"
+    );
+    assert_eq!(
+        committee.party_id_of(&peers[1].to_checksum(None)),
+        None,
+        "should drop the replaced peer"
+    );
+}
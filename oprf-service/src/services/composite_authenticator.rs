@@ -0,0 +1,296 @@
+//! Composition of several [`OprfRequestAuthenticator`]s into one.
+//!
+//! [`crate::OprfServiceBuilder::module`] accepts a single authenticator per module. A [`CompositeAuthenticator`] chains several authenticators (e.g., an API key, a signature and an allowlist check) and is itself an authenticator, so compositions can be nested. The authenticators are called one after the other in the given order:
+//! - with [`CompositionMode::All`], every authenticator must accept the request and return the same [`OprfKeyId`]. The chain stops at the first rejection.
+//! - with [`CompositionMode::Any`], one authenticator must accept the request. The chain stops at the first acceptance.
+//!
+//! The composition is only cacheable (see [`OprfRequestAuthenticator::cacheable`]) if every authenticator is, for the shortest of their durations, and at most one of them has an [`AuthCacheInvalidator`].
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use axum::extract::ws::close_code;
+use oprf_types::{
+    OprfKeyId,
+    api::{
+        AuthCacheInvalidator, OprfRequest, OprfRequestAuthService, OprfRequestAuthenticator,
+        OprfRequestAuthenticatorError,
+    },
+    close_frame_message,
+};
+
+/// How a [`CompositeAuthenticator`] combines its authenticators, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompositionMode {
+    /// Every authenticator must accept the request.
+    All,
+    /// One authenticator must accept the request.
+    Any,
+}
+
+/// An [`OprfRequestAuthenticator`] that chains several authenticators, see the [module docs](self).
+pub struct CompositeAuthenticator<RequestAuth> {
+    mode: CompositionMode,
+    authenticators: Vec<OprfRequestAuthService<RequestAuth>>,
+}
+
+impl<RequestAuth> CompositeAuthenticator<RequestAuth> {
+    /// Combines `authenticators` with `mode`.
+    ///
+    /// # Panics
+    /// Panics if `authenticators` is empty.
+    #[must_use]
+    pub fn new(
+        mode: CompositionMode,
+        authenticators: Vec<OprfRequestAuthService<RequestAuth>>,
+    ) -> Self {
+        assert!(
+            !authenticators.is_empty(),
+            "composite authenticator needs at least one authenticator"
+        );
+        Self {
+            mode,
+            authenticators,
+        }
+    }
+
+    /// Requires every one of `authenticators` to accept the request, see [`CompositionMode::All`].
+    ///
+    /// # Panics
+    /// Panics if `authenticators` is empty.
+    #[must_use]
+    pub fn all(authenticators: Vec<OprfRequestAuthService<RequestAuth>>) -> Self {
+        Self::new(CompositionMode::All, authenticators)
+    }
+
+    /// Requires one of `authenticators` to accept the request, see [`CompositionMode::Any`].
+    ///
+    /// # Panics
+    /// Panics if `authenticators` is empty.
+    #[must_use]
+    pub fn any(authenticators: Vec<OprfRequestAuthService<RequestAuth>>) -> Self {
+        Self::new(CompositionMode::Any, authenticators)
+    }
+}
+
+impl<RequestAuth: Send + Sync + 'static> CompositeAuthenticator<RequestAuth> {
+    /// Wraps the authenticator as [`OprfRequestAuthService`] for [`crate::OprfServiceBuilder::module`].
+    #[must_use]
+    pub fn into_service(self) -> OprfRequestAuthService<RequestAuth> {
+        std::sync::Arc::new(self)
+    }
+}
+
+#[async_trait]
+impl<RequestAuth: Send + Sync> OprfRequestAuthenticator for CompositeAuthenticator<RequestAuth> {
+    type RequestAuth = RequestAuth;
+
+    /// Calls the authenticators in order until the outcome is decided.
+    ///
+    /// With [`CompositionMode::All`], returns the error of the first authenticator that rejects the request, or a policy violation if two authenticators return different keys. With [`CompositionMode::Any`], returns the error of the last authenticator if all of them reject the request.
+    async fn authenticate(
+        &self,
+        req: &OprfRequest<Self::RequestAuth>,
+    ) -> Result<OprfKeyId, OprfRequestAuthenticatorError> {
+        let mut authenticators = self.authenticators.iter();
+        let first = authenticators
+            .next()
+            .expect("checked to be non-empty at construction")
+            .authenticate(req)
+            .await;
+        match self.mode {
+            CompositionMode::All => {
+                let oprf_key_id = first?;
+                for authenticator in authenticators {
+                    if authenticator.authenticate(req).await? != oprf_key_id {
+                        return Err(OprfRequestAuthenticatorError::with_message(
+                            close_code::POLICY,
+                            close_frame_message!("authenticators disagree on the OPRF key"),
+                        ));
+                    }
+                }
+                Ok(oprf_key_id)
+            }
+            CompositionMode::Any => {
+                let mut result = first;
+                for authenticator in authenticators {
+                    if result.is_ok() {
+                        break;
+                    }
+                    result = authenticator.authenticate(req).await;
+                }
+                result
+            }
+        }
+    }
+
+    fn cacheable(&self) -> Option<Duration> {
+        let invalidators = self
+            .authenticators
+            .iter()
+            .filter(|authenticator| authenticator.cache_invalidator().is_some())
+            .count();
+        if invalidators > 1 {
+            return None;
+        }
+        self.authenticators
+            .iter()
+            .try_fold(Duration::MAX, |ttl, authenticator| {
+                authenticator.cacheable().map(|other| ttl.min(other))
+            })
+    }
+
+    fn cache_key(&self, req: &OprfRequest<Self::RequestAuth>) -> Option<Vec<u8>> {
+        let mut cache_key = Vec::new();
+        for authenticator in &self.authenticators {
+            let part = authenticator.cache_key(req)?;
+            // length-prefixed so the parts of different authenticators cannot shift into each other
+            cache_key.extend_from_slice(&part.len().to_le_bytes());
+            cache_key.extend_from_slice(&part);
+        }
+        Some(cache_key)
+    }
+
+    fn cache_invalidator(&self) -> Option<&AuthCacheInvalidator> {
+        self.authenticators
+            .iter()
+            .find_map(|authenticator| authenticator.cache_invalidator())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use ark_ec::AffineRepr as _;
+    use async_trait::async_trait;
+    use oprf_types::{
+        OprfKeyId,
+        api::{OprfRequest, OprfRequestAuthenticator, OprfRequestAuthenticatorError},
+    };
+    use uuid::Uuid;
+
+    use super::{CompositeAuthenticator, close_code};
+    use crate::test_kit::{MOCK_AUTH_DENIED, MockAuthenticator};
+
+    /// Accepts every request for a fixed key, cacheable for 30 seconds.
+    struct FixedKeyAuthenticator(OprfKeyId);
+
+    #[async_trait]
+    impl OprfRequestAuthenticator for FixedKeyAuthenticator {
+        type RequestAuth = OprfKeyId;
+
+        async fn authenticate(
+            &self,
+            _req: &OprfRequest<Self::RequestAuth>,
+        ) -> Result<OprfKeyId, OprfRequestAuthenticatorError> {
+            Ok(self.0)
+        }
+
+        fn cacheable(&self) -> Option<Duration> {
+            Some(Duration::from_secs(30))
+        }
+
+        fn cache_key(&self, _req: &OprfRequest<Self::RequestAuth>) -> Option<Vec<u8>> {
+            Some(b"fixed".to_vec())
+        }
+    }
+
+    fn request() -> OprfRequest<OprfKeyId> {
+        OprfRequest {
+            request_id: Uuid::new_v4(),
+            blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+            auth: OprfKeyId::from(42usize),
+            issued_at: None,
+            batch: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn all_stops_at_first_rejection() {
+        let allow = Arc::new(MockAuthenticator::allow_all());
+        let deny = Arc::new(MockAuthenticator::deny_all());
+        let last = Arc::new(MockAuthenticator::allow_all());
+        let composite =
+            CompositeAuthenticator::all(vec![allow.clone(), deny.clone(), last.clone()]);
+
+        let err = composite
+            .authenticate(&request())
+            .await
+            .expect_err("should reject");
+        assert_eq!(err.code(), MOCK_AUTH_DENIED);
+        assert_eq!((allow.calls(), deny.calls(), last.calls()), (1, 1, 0));
+
+        let composite = CompositeAuthenticator::all(vec![allow.clone(), last.clone()]);
+        assert_eq!(
+            composite.authenticate(&request()).await,
+            Ok(OprfKeyId::from(42usize))
+        );
+    }
+
+    #[tokio::test]
+    async fn all_rejects_disagreeing_keys() {
+        let composite = CompositeAuthenticator::all(vec![
+            Arc::new(MockAuthenticator::allow_all()),
+            Arc::new(FixedKeyAuthenticator(OprfKeyId::from(43usize))),
+        ]);
+        let err = composite
+            .authenticate(&request())
+            .await
+            .expect_err("should reject");
+        assert_eq!(err.code(), close_code::POLICY);
+    }
+
+    #[tokio::test]
+    async fn any_stops_at_first_acceptance() {
+        let deny = Arc::new(MockAuthenticator::deny_all());
+        let allow = Arc::new(MockAuthenticator::allow_all());
+        let last = Arc::new(MockAuthenticator::deny_all());
+        let composite =
+            CompositeAuthenticator::any(vec![deny.clone(), allow.clone(), last.clone()]);
+
+        assert_eq!(
+            composite.authenticate(&request()).await,
+            Ok(OprfKeyId::from(42usize))
+        );
+        assert_eq!((deny.calls(), allow.calls(), last.calls()), (1, 1, 0));
+
+        let composite = CompositeAuthenticator::any(vec![deny.clone(), last.clone()]);
+        let err = composite
+            .authenticate(&request())
+            .await
+            .expect_err("should reject");
+        assert_eq!(err.code(), MOCK_AUTH_DENIED);
+        assert_eq!((deny.calls(), last.calls()), (2, 1));
+    }
+
+    #[test]
+    fn cacheable_only_if_all_authenticators_are() {
+        let fixed = Arc::new(FixedKeyAuthenticator(OprfKeyId::from(42usize)));
+        let composite = CompositeAuthenticator::all(vec![
+            MockAuthenticator::allow_all()
+                .with_cache(Duration::from_mins(1))
+                .into_service(),
+            fixed.clone(),
+        ]);
+        assert_eq!(composite.cacheable(), Some(Duration::from_secs(30)));
+        assert!(composite.cache_key(&request()).is_some());
+        assert!(composite.cache_invalidator().is_some());
+
+        let composite =
+            CompositeAuthenticator::any(vec![MockAuthenticator::allow_all().into_service(), fixed]);
+        assert_eq!(composite.cacheable(), None);
+
+        // both mocks have their own invalidator
+        let composite = CompositeAuthenticator::any(vec![
+            MockAuthenticator::allow_all()
+                .with_cache(Duration::from_mins(1))
+                .into_service(),
+            MockAuthenticator::allow_all()
+                .with_cache(Duration::from_secs(10))
+                .into_service(),
+        ]);
+        assert_eq!(composite.cacheable(), None);
+    }
+}
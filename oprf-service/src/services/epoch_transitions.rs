@@ -0,0 +1,437 @@
+//! Epoch transition hook for host applications.
+//!
+//! Products deriving artifacts from the key material of an [`OprfKeyId`] (e.g., caches of evaluated queries) must invalidate them when the key is reshared. If a hook is set with [`crate::OprfServiceBuilder::epoch_transition_hook`], the node calls it for every epoch transition of a key, after the [`OprfKeyMaterialStore`](crate::oprf_key_material_store::OprfKeyMaterialStore) swapped to the share of the new epoch, i.e., sessions started afterwards already use the new share.
+//!
+//! # Delivery
+//!
+//! Transitions are delivered **at least once**:
+//! - A failing hook is called again with exponential backoff until it succeeds. Transitions are delivered one at a time in the order the store swapped the shares, so a failing hook delays all later transitions.
+//! - After a successful delivery, the node persists the delivered epoch of the key with its secret manager (see `SecretManager::store_delivered_epoch`). Reloading the same epoch of a key (e.g., after the key was evicted from the store or after a restart) is not delivered again.
+//! - On startup, the node loads every key with a persisted epoch. Keys that were reshared while the node was down are delivered with [`EpochTransition::previous`] set to the persisted epoch, so no transition is lost across restarts.
+//! - A transition is delivered again if the node stops between the delivery and persisting the epoch, or if persisting fails.
+//!
+//! Hooks must therefore be idempotent. Unlike the hook, the [`EpochChanged`] notifications of `/epoch_notifications` are best-effort: slow subscribers skip notifications.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use backon::BackoffBuilder as _;
+use oprf_types::{OprfKeyId, ShareEpoch, api::EpochChanged, retry::RetryPolicy};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use crate::{ExitReason, oprf_key_material_store::OprfKeyMaterialStore};
+
+/// Dynamic trait object for the epoch transition hook.
+///
+/// Must be `Send + Sync` to work with async contexts (e.g., Axum).
+pub type EpochTransitionHookService = Arc<dyn EpochTransitionHook + Send + Sync>;
+
+/// The transition of a key to a new epoch, passed to the [`EpochTransitionHook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EpochTransition {
+    /// The key that transitioned.
+    pub oprf_key_id: OprfKeyId,
+    /// The epoch delivered last for this key, also before a restart of the node. `None` if no epoch of this key was delivered yet.
+    pub previous: Option<ShareEpoch>,
+    /// The epoch the node now serves for this key.
+    pub epoch: ShareEpoch,
+}
+
+/// Called by the node for every epoch transition of a key, see the [module docs](self).
+#[async_trait]
+pub trait EpochTransitionHook {
+    /// Handles the transition of a key to a new epoch. Must be idempotent, as transitions are delivered at least once.
+    ///
+    /// # Errors
+    /// Errors are logged and the transition is delivered again after a backoff.
+    async fn on_epoch_transition(&self, transition: &EpochTransition) -> eyre::Result<()>;
+}
+
+/// Spawns the task delivering the epoch changes sent to `epoch_changes` to the `hook`, retrying failed deliveries with the backoff of `retry_policy` (without its max number of retries).
+///
+/// The task first loads the epochs delivered before the node started from the secret manager of the `store` and loads their keys into the `store`, which sends their current epochs to `epoch_changes`. Afterwards, it persists every delivered epoch with the secret manager.
+///
+/// The task stops when `cancellation_token` is cancelled.
+pub(crate) fn spawn(
+    hook: EpochTransitionHookService,
+    mut epoch_changes: mpsc::UnboundedReceiver<EpochChanged>,
+    store: OprfKeyMaterialStore,
+    retry_policy: RetryPolicy,
+    cancellation_token: CancellationToken,
+) -> JoinHandle<ExitReason> {
+    tokio::spawn(async move {
+        let Some(mut delivered) =
+            load_delivered_epochs(&store, &retry_policy, &cancellation_token).await
+        else {
+            tracing::info!("epoch transition hook task stopped");
+            return ExitReason::Cancelled;
+        };
+        // replay the keys delivered before the restart, to deliver their transitions while the node was down
+        let replayed = delivered.keys().copied().collect::<Vec<_>>();
+        let loaded = store.preload(&replayed).await;
+        tracing::debug!("replayed {loaded}/{} delivered keys", replayed.len());
+        loop {
+            let next = tokio::select! {
+                () = cancellation_token.cancelled() => break,
+                next = epoch_changes.recv() => next,
+            };
+            let Some(EpochChanged {
+                oprf_key_id,
+                previous,
+                epoch,
+            }) = next
+            else {
+                // the sender was dropped, no further transitions
+                break;
+            };
+            let delivered_epoch = delivered.get(&oprf_key_id).copied();
+            if delivered_epoch == Some(epoch) {
+                continue;
+            }
+            let transition = EpochTransition {
+                oprf_key_id,
+                previous: delivered_epoch.or(previous),
+                epoch,
+            };
+            if !deliver(
+                hook.as_ref(),
+                &transition,
+                &retry_policy,
+                &cancellation_token,
+            )
+            .await
+            {
+                break;
+            }
+            delivered.insert(oprf_key_id, epoch);
+            if let Err(err) = store
+                .secret_manager()
+                .store_delivered_epoch(oprf_key_id, epoch)
+                .await
+            {
+                tracing::warn!(
+                    ?err,
+                    "cannot persist delivered epoch {epoch} of {oprf_key_id} - is delivered again after a restart"
+                );
+            }
+        }
+        tracing::info!("epoch transition hook task stopped");
+        ExitReason::Cancelled
+    })
+}
+
+/// Loads the delivered epochs from the secret manager of the `store` until it succeeds. Returns `None` if `cancellation_token` was cancelled before.
+async fn load_delivered_epochs(
+    store: &OprfKeyMaterialStore,
+    retry_policy: &RetryPolicy,
+    cancellation_token: &CancellationToken,
+) -> Option<HashMap<OprfKeyId, ShareEpoch>> {
+    let mut delays = retry_policy.backoff().without_max_times().build();
+    loop {
+        match store.secret_manager().load_delivered_epochs().await {
+            Ok(delivered) => return Some(delivered),
+            Err(err) => {
+                let delay = delays.next().unwrap_or(Duration::from_secs(1));
+                tracing::warn!(?err, "cannot load delivered epochs - retrying in {delay:?}");
+                tokio::select! {
+                    () = cancellation_token.cancelled() => return None,
+                    () = tokio::time::sleep(delay) => {}
+                }
+            }
+        }
+    }
+}
+
+/// Calls the `hook` until it succeeds. Returns `false` if `cancellation_token` was cancelled before.
+async fn deliver(
+    hook: &(dyn EpochTransitionHook + Send + Sync),
+    transition: &EpochTransition,
+    retry_policy: &RetryPolicy,
+    cancellation_token: &CancellationToken,
+) -> bool {
+    let mut delays = retry_policy.backoff().without_max_times().build();
+    loop {
+        match hook.on_epoch_transition(transition).await {
+            Ok(()) => {
+                tracing::debug!(
+                    "delivered epoch {} of {}",
+                    transition.epoch,
+                    transition.oprf_key_id
+                );
+                return true;
+            }
+            Err(err) => {
+                let delay = delays.next().unwrap_or(Duration::from_secs(1));
+                tracing::warn!(
+                    %err,
+                    "epoch transition hook failed for epoch {} of {} - retrying in {delay:?}",
+                    transition.epoch,
+                    transition.oprf_key_id
+                );
+                tokio::select! {
+                    () = cancellation_token.cancelled() => return false,
+                    () = tokio::time::sleep(delay) => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_test::TestServerBuilder;
+    use parking_lot::Mutex;
+
+    use crate::{
+        BuilderError,
+        config::StoreEvictionPolicy,
+        test_kit::MockAuthenticator,
+        test_utils::{MockSecretManager, builder, builder_with_secret_manager, default_config},
+    };
+
+    use super::*;
+
+    /// Records the delivered transitions, failing the first `failures` calls.
+    #[derive(Default)]
+    struct RecordingHook {
+        failures: Mutex<usize>,
+        delivered: Mutex<Vec<EpochTransition>>,
+    }
+
+    #[async_trait]
+    impl EpochTransitionHook for RecordingHook {
+        async fn on_epoch_transition(&self, transition: &EpochTransition) -> eyre::Result<()> {
+            let mut failures = self.failures.lock();
+            if *failures > 0 {
+                *failures -= 1;
+                eyre::bail!("hook not ready");
+            }
+            self.delivered.lock().push(*transition);
+            Ok(())
+        }
+    }
+
+    fn changed(oprf_key_id: usize, previous: Option<u32>, epoch: u32) -> EpochChanged {
+        EpochChanged {
+            oprf_key_id: OprfKeyId::from(oprf_key_id),
+            previous: previous.map(ShareEpoch::new),
+            epoch: ShareEpoch::new(epoch),
+        }
+    }
+
+    fn transition(oprf_key_id: usize, previous: Option<u32>, epoch: u32) -> EpochTransition {
+        EpochTransition {
+            oprf_key_id: OprfKeyId::from(oprf_key_id),
+            previous: previous.map(ShareEpoch::new),
+            epoch: ShareEpoch::new(epoch),
+        }
+    }
+
+    fn store(secret_manager: Arc<MockSecretManager>) -> OprfKeyMaterialStore {
+        OprfKeyMaterialStore::new(
+            secret_manager,
+            16,
+            Duration::from_hours(1),
+            Duration::from_hours(1),
+            StoreEvictionPolicy::Lru,
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delivers_transitions_at_least_once() {
+        let hook = Arc::new(RecordingHook {
+            failures: Mutex::new(2),
+            ..Default::default()
+        });
+        let secret_manager = Arc::new(MockSecretManager::default());
+        let (tx, rx) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let task = spawn(
+            hook.clone(),
+            rx,
+            store(Arc::clone(&secret_manager)),
+            RetryPolicy::constant(0, Duration::from_secs(1)),
+            cancellation_token.clone(),
+        );
+        tx.send(changed(1, None, 0)).expect("task is running");
+        tx.send(changed(1, None, 0)).expect("task is running");
+        tx.send(changed(2, None, 3)).expect("task is running");
+        tx.send(changed(1, Some(0), 1)).expect("task is running");
+        drop(tx);
+        task.await.expect("task does not panic");
+
+        assert_eq!(
+            *hook.delivered.lock(),
+            vec![
+                transition(1, None, 0),
+                transition(2, None, 3),
+                transition(1, Some(0), 1),
+            ],
+            "should retry failed deliveries and skip delivered epochs"
+        );
+        assert_eq!(
+            secret_manager.delivered_epochs(),
+            HashMap::from([
+                (OprfKeyId::from(1usize), ShareEpoch::new(1)),
+                (OprfKeyId::from(2usize), ShareEpoch::new(3)),
+            ]),
+            "should persist the delivered epochs"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_epochs_delivered_before_restart() {
+        let hook = Arc::new(RecordingHook::default());
+        let secret_manager = Arc::new(
+            MockSecretManager::default()
+                .with_delivered_epoch(OprfKeyId::from(1usize), ShareEpoch::new(2)),
+        );
+        let (tx, rx) = mpsc::unbounded_channel();
+        let task = spawn(
+            hook.clone(),
+            rx,
+            store(secret_manager),
+            RetryPolicy::default(),
+            CancellationToken::new(),
+        );
+        tx.send(changed(1, None, 2)).expect("task is running");
+        tx.send(changed(1, Some(2), 3)).expect("task is running");
+        drop(tx);
+        task.await.expect("task does not panic");
+
+        assert_eq!(
+            *hook.delivered.lock(),
+            vec![transition(1, Some(2), 3)],
+            "should not deliver the persisted epoch again"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_retrying_on_cancellation() {
+        let hook = Arc::new(RecordingHook {
+            failures: Mutex::new(usize::MAX),
+            ..Default::default()
+        });
+        let (tx, rx) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let task = spawn(
+            hook.clone(),
+            rx,
+            store(Arc::new(MockSecretManager::default())),
+            RetryPolicy::default(),
+            cancellation_token.clone(),
+        );
+        tx.send(changed(1, None, 0)).expect("task is running");
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        cancellation_token.cancel();
+        assert!(matches!(
+            task.await.expect("task does not panic"),
+            ExitReason::Cancelled
+        ));
+        assert!(hook.delivered.lock().is_empty());
+    }
+
+    /// Forwards every delivered transition to the wrapped channel.
+    struct ForwardingHook(tokio::sync::mpsc::UnboundedSender<EpochTransition>);
+
+    #[async_trait]
+    impl EpochTransitionHook for ForwardingHook {
+        async fn on_epoch_transition(&self, transition: &EpochTransition) -> eyre::Result<()> {
+            self.0.send(*transition)?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_transitions_missed_while_down() {
+        let oprf_key_id = OprfKeyId::from(42usize);
+        let secret_manager = Arc::new(
+            MockSecretManager::fixed_key(ShareEpoch::new(4))
+                .with_delivered_epoch(oprf_key_id, ShareEpoch::new(3)),
+        );
+        let store = store(Arc::clone(&secret_manager));
+        let epoch_changes = store.forward_epoch_changes().expect("not forwarded yet");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let task = spawn(
+            Arc::new(ForwardingHook(tx)),
+            epoch_changes,
+            store,
+            RetryPolicy::default(),
+            cancellation_token.clone(),
+        );
+
+        let replayed = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("should replay the persisted key")
+            .expect("hook is alive");
+        assert_eq!(replayed, transition(42, Some(3), 4));
+        cancellation_token.cancel();
+        task.await.expect("task does not panic");
+        assert_eq!(
+            secret_manager.delivered_epochs(),
+            HashMap::from([(oprf_key_id, ShareEpoch::new(4))]),
+            "should persist the replayed epoch"
+        );
+    }
+
+    #[tokio::test]
+    async fn epoch_transition_hook_sees_loaded_key() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let secret_manager = Arc::new(MockSecretManager::fixed_key(ShareEpoch::new(3)));
+        let mut config = default_config();
+        config.store_ttl = Duration::from_millis(200);
+        let router = builder_with_secret_manager(config, Arc::clone(&secret_manager) as _)
+            .epoch_transition_hook(Arc::new(ForwardingHook(tx)), CancellationToken::new())
+            .module("/test", MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect("Can build");
+        let server = TestServerBuilder::new()
+            .http_transport()
+            .build(router)
+            .expect("Can build test-server");
+        let mut next_transition = async || {
+            tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("should call the hook")
+                .expect("hook is alive")
+        };
+
+        let oprf_key_id = OprfKeyId::from(42usize);
+        server
+            .get(&format!("/oprf_pub/{oprf_key_id}"))
+            .await
+            .assert_status_ok();
+        assert_eq!(next_transition().await, transition(42, None, 3));
+
+        secret_manager.reshare(ShareEpoch::new(4));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        server
+            .get(&format!("/oprf_pub/{oprf_key_id}"))
+            .await
+            .assert_status_ok();
+        assert_eq!(
+            next_transition().await,
+            transition(42, Some(3), 4),
+            "should deliver the swap with the previous epoch"
+        );
+    }
+
+    #[tokio::test]
+    async fn epoch_transition_hook_set_twice() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let hook = Arc::new(ForwardingHook(tx));
+        let err = builder()
+            .epoch_transition_hook(hook.clone(), CancellationToken::new())
+            .epoch_transition_hook(hook, CancellationToken::new())
+            .module("/test", MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect_err("should reject a second hook");
+        assert!(
+            matches!(err, BuilderError::InvalidConfig(_)),
+            "expected InvalidConfig, got {err:?}"
+        );
+    }
+}
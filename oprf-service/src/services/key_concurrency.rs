@@ -0,0 +1,309 @@
+//! Limit of concurrent sessions per OPRF key.
+//!
+//! A single popular key must not monopolize the CPU of a node. If [`crate::config::OprfNodeServiceConfig::key_concurrency`] is set, every [`OprfKeyId`] gets a semaphore with `max_sessions` permits, or the limit of the key in `overrides` (see [`KeyConcurrencyConfig`]). Every authenticated session takes one permit before it computes its commitments and holds it until the session ends, so a batch session takes a single permit.
+//!
+//! Sessions of a key without free permits are closed with [`oprf_types::api::oprf_error_codes::KEY_BUSY`] instead of waiting, so the client can retry at another time. Resumed sessions answered from the challenge replay cache do not take a permit, as they do not compute anything.
+//!
+//! The semaphores are node-local and shared by all OPRF modules of the node. Semaphores without sessions are dropped, so idle keys do not take up memory.
+//!
+//! Every key label creates new time series, so only the keys in `overrides` get their own `oprf_key_id` label: their utilization is exported as `taceo.oprf.node.request.key.utilization` and their rejected sessions as `taceo.oprf.node.request.key.busy`. The rejected sessions of all other keys share the label [`OTHER`], and the sessions holding a permit of any key are exported as `taceo.oprf.node.request.key.sessions`.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use oprf_types::OprfKeyId;
+use parking_lot::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{config::KeyConcurrencyConfig, metrics, services::key_labels::OTHER};
+
+/// Number of semaphores after which semaphores without sessions are dropped.
+const PRUNE_THRESHOLD: usize = 1024;
+
+#[derive(Debug)]
+struct Semaphores {
+    semaphores: HashMap<OprfKeyId, Arc<Semaphore>>,
+    prune_at: usize,
+}
+
+/// The semaphores of all keys, see the [module docs](self).
+#[derive(Clone)]
+pub(crate) struct KeyConcurrency {
+    config: Arc<KeyConcurrencyConfig>,
+    semaphores: Arc<Mutex<Semaphores>>,
+    sessions: Arc<AtomicUsize>,
+}
+
+/// A session of a key, releases its permit on drop.
+pub(crate) struct KeyPermit {
+    oprf_key_id: OprfKeyId,
+    limit: usize,
+    /// Whether the key has its own limit and with that its own label.
+    labeled: bool,
+    semaphore: Arc<Semaphore>,
+    sessions: Arc<AtomicUsize>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl KeyConcurrency {
+    pub(crate) fn new(config: KeyConcurrencyConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            semaphores: Arc::new(Mutex::new(Semaphores {
+                semaphores: HashMap::new(),
+                prune_at: PRUNE_THRESHOLD,
+            })),
+            sessions: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// The `oprf_key_id` label of the metrics of `oprf_key_id`: the key itself if it is in `overrides`, otherwise [`OTHER`].
+    pub(crate) fn label(&self, oprf_key_id: OprfKeyId) -> String {
+        if self.config.overrides.contains_key(&oprf_key_id) {
+            oprf_key_id.to_string()
+        } else {
+            OTHER.to_owned()
+        }
+    }
+
+    /// Takes a permit of `oprf_key_id` for a session.
+    ///
+    /// # Errors
+    ///
+    /// Returns the limit of the key if all its permits are taken.
+    pub(crate) fn try_acquire(&self, oprf_key_id: OprfKeyId) -> Result<KeyPermit, usize> {
+        let limit = self.config.max_sessions_of(oprf_key_id).get();
+        let mut semaphores = self.semaphores.lock();
+        if semaphores.semaphores.len() >= semaphores.prune_at {
+            Self::prune(&mut semaphores);
+        }
+        let semaphore = Arc::clone(
+            semaphores
+                .semaphores
+                .entry(oprf_key_id)
+                .or_insert_with(|| Arc::new(Semaphore::new(limit))),
+        );
+        let permit = Arc::clone(&semaphore)
+            .try_acquire_owned()
+            .map_err(|_| limit)?;
+        drop(semaphores);
+        let permit = KeyPermit {
+            oprf_key_id,
+            limit,
+            labeled: self.config.overrides.contains_key(&oprf_key_id),
+            semaphore,
+            sessions: Arc::clone(&self.sessions),
+            permit: Some(permit),
+        };
+        metrics::keys::set_sessions(permit.sessions.fetch_add(1, Ordering::Relaxed) + 1);
+        permit.record_utilization();
+        Ok(permit)
+    }
+
+    /// Drops the semaphores without sessions, as they behave like new semaphores.
+    fn prune(semaphores: &mut Semaphores) {
+        // only the map and the permits hold a semaphore
+        semaphores
+            .semaphores
+            .retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        semaphores.prune_at = (semaphores.semaphores.len() * 2).max(PRUNE_THRESHOLD);
+    }
+}
+
+impl KeyPermit {
+    fn record_utilization(&self) {
+        if self.labeled {
+            let in_use = self.limit - self.semaphore.available_permits();
+            metrics::keys::set_utilization(self.oprf_key_id, in_use, self.limit);
+        }
+    }
+}
+
+impl Drop for KeyPermit {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        metrics::keys::set_sessions(self.sessions.fetch_sub(1, Ordering::Relaxed) - 1);
+        self.record_utilization();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, time::Duration};
+
+    use ark_ec::AffineRepr as _;
+    use axum_test::TestServerBuilder;
+    use oprf_types::{
+        ShareEpoch,
+        api::{OprfRequest, oprf_error_codes},
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        test_kit::MockAuthenticator,
+        test_utils::{MockSecretManager, builder_with_secret_manager, default_config},
+    };
+
+    use super::*;
+
+    fn limit(max_sessions: usize) -> NonZeroUsize {
+        NonZeroUsize::new(max_sessions).expect("non-zero")
+    }
+
+    #[test]
+    fn permits_are_released_on_drop() {
+        let concurrency = KeyConcurrency::new(KeyConcurrencyConfig::new(limit(2)));
+        let key = OprfKeyId::from(42usize);
+        let first = concurrency.try_acquire(key).expect("first session");
+        let _second = concurrency.try_acquire(key).expect("second session");
+        assert_eq!(
+            concurrency.try_acquire(key).err(),
+            Some(2),
+            "limit is reached"
+        );
+        concurrency
+            .try_acquire(OprfKeyId::from(43usize))
+            .expect("other keys have their own limit");
+        drop(first);
+        concurrency.try_acquire(key).expect("released permit");
+    }
+
+    #[test]
+    fn overrides_replace_limit() {
+        let mut config = KeyConcurrencyConfig::new(limit(1));
+        let hot_key = OprfKeyId::from(42usize);
+        config.overrides.insert(hot_key, limit(2));
+        let concurrency = KeyConcurrency::new(config);
+        let _permits = [
+            concurrency.try_acquire(hot_key).expect("first session"),
+            concurrency
+                .try_acquire(hot_key)
+                .expect("override allows two"),
+        ];
+        let _permit = concurrency
+            .try_acquire(OprfKeyId::from(43usize))
+            .expect("first session");
+        assert_eq!(
+            concurrency.try_acquire(OprfKeyId::from(43usize)).err(),
+            Some(1),
+            "other keys use the default limit"
+        );
+        assert_eq!(
+            concurrency.label(hot_key),
+            hot_key.to_string(),
+            "keys with their own limit get their own label"
+        );
+        assert_eq!(
+            concurrency.label(OprfKeyId::from(43usize)),
+            OTHER,
+            "other keys share a label"
+        );
+        assert_eq!(
+            concurrency.sessions.load(Ordering::Relaxed),
+            3,
+            "should count the sessions of all keys"
+        );
+    }
+
+    #[test]
+    fn prune_keeps_semaphores_with_sessions() {
+        let concurrency = KeyConcurrency::new(KeyConcurrencyConfig::new(limit(1)));
+        let busy = concurrency
+            .try_acquire(OprfKeyId::from(0usize))
+            .expect("first session");
+        for key in 1..PRUNE_THRESHOLD {
+            drop(concurrency.try_acquire(OprfKeyId::from(key)));
+        }
+        // pruned on the next acquire
+        let _permit = concurrency
+            .try_acquire(OprfKeyId::from(PRUNE_THRESHOLD))
+            .expect("new key");
+        assert_eq!(
+            concurrency.semaphores.lock().semaphores.len(),
+            2,
+            "only keys with sessions are kept"
+        );
+        assert_eq!(
+            concurrency.try_acquire(OprfKeyId::from(0usize)).err(),
+            Some(1),
+            "busy key keeps its semaphore"
+        );
+        drop(busy);
+    }
+
+    #[tokio::test]
+    async fn key_concurrency_closes_busy_key() {
+        let mut config = default_config();
+        config.key_concurrency = Some(KeyConcurrencyConfig::new(
+            NonZeroUsize::new(1).expect("1 is non-zero"),
+        ));
+        let router = builder_with_secret_manager(
+            config,
+            Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+        )
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+        let server = TestServerBuilder::new()
+            .http_transport()
+            .build(router)
+            .expect("Can build test-server");
+        let open_session = async |oprf_key_id: usize| {
+            let mut ws = server
+                .get_websocket("/api/test/oprf?version=1.0.0")
+                .await
+                .into_websocket()
+                .await;
+            ws.send_json(&OprfRequest {
+                request_id: Uuid::new_v4(),
+                blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+                auth: OprfKeyId::from(oprf_key_id),
+                issued_at: None,
+                batch: Vec::new(),
+            })
+            .await;
+            let first_message = ws.receive_message().await;
+            (ws, first_message)
+        };
+
+        let (pending, first_message) = open_session(42).await;
+        assert!(
+            matches!(first_message, tungstenite::Message::Text(_)),
+            "first session should be answered, got {first_message:?}"
+        );
+        let (_, first_message) = open_session(42).await;
+        let tungstenite::Message::Close(Some(frame)) = &first_message else {
+            panic!("expected close frame, got {first_message:?}");
+        };
+        assert_eq!(
+            u16::from(frame.code),
+            oprf_error_codes::KEY_BUSY,
+            "second session of the key should be rejected"
+        );
+        let (_, first_message) = open_session(43).await;
+        assert!(
+            matches!(first_message, tungstenite::Message::Text(_)),
+            "other keys should have their own limit, got {first_message:?}"
+        );
+
+        pending.close().await;
+        let mut first_message = None;
+        for _ in 0..100 {
+            let (_, message) = open_session(42).await;
+            if matches!(message, tungstenite::Message::Text(_)) {
+                first_message = Some(message);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(
+            first_message.is_some(),
+            "closed session should release its permit"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Bounded `oprf_key_id` labels of the request metrics.
+//!
+//! If [`crate::config::OprfNodeServiceConfig::key_metrics`] is set, the duration histograms of part one and part two of the OPRF computation carry an `oprf_key_id` label, and the outcome of every session is counted per key as `taceo.oprf.node.request.key.outcome`. This identifies slow or failing relying parties.
+//!
+//! Every label value creates new time series, so the number of labeled keys is capped: the keys of the `allowlist` and the first `max_keys` other keys the node serves get their own label (see [`KeyMetricsConfig`]), all further keys share the label [`OTHER`]. The labeled keys are node-local, shared by all OPRF modules of the node and kept until the node restarts.
+
+use std::{collections::HashSet, sync::Arc};
+
+use oprf_types::OprfKeyId;
+use parking_lot::Mutex;
+
+use crate::config::KeyMetricsConfig;
+
+/// The label of all keys that do not get their own label.
+pub(crate) const OTHER: &str = "other";
+
+/// Assigns the `oprf_key_id` labels of the request metrics, see the [module docs](self).
+#[derive(Clone)]
+pub(crate) struct KeyLabels {
+    allowlist: Arc<HashSet<OprfKeyId>>,
+    max_keys: usize,
+    labeled: Arc<Mutex<HashSet<OprfKeyId>>>,
+}
+
+impl KeyLabels {
+    pub(crate) fn new(config: &KeyMetricsConfig) -> Self {
+        Self {
+            allowlist: Arc::new(config.allowlist.iter().copied().collect()),
+            max_keys: config.max_keys,
+            labeled: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// The label of `oprf_key_id`: the key itself if it is on the allowlist or one of the first `max_keys` other keys, otherwise [`OTHER`].
+    pub(crate) fn label(&self, oprf_key_id: OprfKeyId) -> String {
+        if self.allowlist.contains(&oprf_key_id) {
+            return oprf_key_id.to_string();
+        }
+        let mut labeled = self.labeled.lock();
+        if labeled.contains(&oprf_key_id)
+            || (labeled.len() < self.max_keys && labeled.insert(oprf_key_id))
+        {
+            oprf_key_id.to_string()
+        } else {
+            OTHER.to_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: usize) -> OprfKeyId {
+        OprfKeyId::from(id)
+    }
+
+    #[test]
+    fn labels_are_capped() {
+        let mut config = KeyMetricsConfig::new(2);
+        config.allowlist = vec![key(42)];
+        let labels = KeyLabels::new(&config);
+        assert_eq!(labels.label(key(1)), key(1).to_string());
+        assert_eq!(labels.label(key(2)), key(2).to_string());
+        assert_eq!(labels.label(key(3)), OTHER, "should cap the labeled keys");
+        assert_eq!(
+            labels.label(key(1)),
+            key(1).to_string(),
+            "should keep the label of a labeled key"
+        );
+        assert_eq!(
+            labels.label(key(42)),
+            key(42).to_string(),
+            "allowlisted keys do not count towards the cap"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Quota of evaluations per OPRF key.
+//!
+//! A relying party must not exhaust a node on its own. If [`crate::config::OprfNodeServiceConfig::key_quota`] is set, every [`OprfKeyId`] gets a token bucket that holds up to `burst` evaluations and refills with `max_evaluations` per `window` (see [`KeyQuotaConfig`]). Every blinded query of an authenticated request takes one token, so a batch session takes as many tokens as it has queries.
+//!
+//! Requests of a key with an empty bucket are closed with [`oprf_types::api::oprf_error_codes::QUOTA_EXCEEDED`] and the time after which enough tokens are available again. Resumed sessions are not charged, as they do not create new evaluations.
+//!
+//! The buckets are node-local and shared by all OPRF modules of the node. Buckets that are full again are dropped, so idle keys do not take up memory.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use oprf_types::OprfKeyId;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use crate::{config::KeyQuotaConfig, services::clock::ClockService};
+
+/// Number of buckets after which full buckets are dropped.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// The tokens of a single key.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    buckets: HashMap<OprfKeyId, Bucket>,
+    prune_at: usize,
+}
+
+/// The token buckets of all keys, see the [module docs](self).
+#[derive(Clone)]
+pub(crate) struct KeyQuota {
+    /// Max tokens of a bucket.
+    capacity: f64,
+    /// Tokens refilled per second.
+    refill_rate: f64,
+    buckets: Arc<Mutex<Buckets>>,
+    clock: ClockService,
+}
+
+impl KeyQuota {
+    pub(crate) fn new(config: KeyQuotaConfig, clock: ClockService) -> Self {
+        let capacity = f64::from(config.burst.unwrap_or(config.max_evaluations).get());
+        Self {
+            capacity,
+            refill_rate: f64::from(config.max_evaluations.get()) / config.window.as_secs_f64(),
+            buckets: Arc::new(Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                prune_at: PRUNE_THRESHOLD,
+            })),
+            clock,
+        }
+    }
+
+    /// Takes `evaluations` tokens from the bucket of `oprf_key_id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the time after which enough tokens are available if the bucket holds less than `evaluations` tokens, in which case no token is taken.
+    pub(crate) fn try_acquire(
+        &self,
+        oprf_key_id: OprfKeyId,
+        evaluations: usize,
+    ) -> Result<(), Duration> {
+        let now = self.clock.now();
+        let requested = evaluations as f64;
+        let mut buckets = self.buckets.lock();
+        if buckets.buckets.len() >= buckets.prune_at {
+            self.prune(&mut buckets, now);
+        }
+        let bucket = buckets.buckets.entry(oprf_key_id).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= requested {
+            bucket.tokens -= requested;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (requested - bucket.tokens) / self.refill_rate,
+            ))
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.refill_rate).min(self.capacity)
+    }
+
+    /// Drops the buckets that are full again, as they behave like new buckets.
+    fn prune(&self, buckets: &mut Buckets, now: Instant) {
+        buckets
+            .buckets
+            .retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+        buckets.prune_at = (buckets.buckets.len() * 2).max(PRUNE_THRESHOLD);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use ark_ec::AffineRepr as _;
+    use axum_test::TestServerBuilder;
+    use oprf_types::{
+        ShareEpoch,
+        api::{OprfRequest, oprf_error_codes},
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        services::clock::MockClock,
+        test_kit::MockAuthenticator,
+        test_utils::{MockSecretManager, builder_with_secret_manager, default_config},
+    };
+
+    use super::*;
+
+    fn quota(max_evaluations: u32, burst: Option<u32>) -> (KeyQuota, MockClock) {
+        let clock = MockClock::new();
+        let quota = KeyQuota::new(
+            KeyQuotaConfig {
+                max_evaluations: NonZeroU32::new(max_evaluations).expect("non-zero"),
+                window: Duration::from_secs(10),
+                burst: burst.map(|burst| NonZeroU32::new(burst).expect("non-zero")),
+            },
+            Arc::new(clock.clone()),
+        );
+        (quota, clock)
+    }
+
+    #[test]
+    fn bucket_refills_over_window() {
+        let (quota, clock) = quota(10, None);
+        let key = OprfKeyId::from(42usize);
+        quota
+            .try_acquire(key, 10)
+            .expect("full bucket allows max_evaluations");
+        let retry_after = quota.try_acquire(key, 1).expect_err("empty bucket");
+        assert_eq!(retry_after, Duration::from_secs(1), "one token per second");
+        quota
+            .try_acquire(OprfKeyId::from(43usize), 1)
+            .expect("other keys have their own bucket");
+        clock.advance(Duration::from_secs(5));
+        quota
+            .try_acquire(key, 5)
+            .expect("refilled after half the window");
+        quota
+            .try_acquire(key, 1)
+            .expect_err("refilled tokens are used up");
+    }
+
+    #[test]
+    fn burst_bounds_bucket() {
+        let (quota, clock) = quota(10, Some(2));
+        let key = OprfKeyId::from(42usize);
+        quota.try_acquire(key, 3).expect_err("batch exceeds burst");
+        clock.advance(Duration::from_mins(1));
+        quota
+            .try_acquire(key, 2)
+            .expect("rejected request takes no tokens");
+        quota
+            .try_acquire(key, 1)
+            .expect_err("bucket holds at most burst tokens");
+    }
+
+    #[tokio::test]
+    async fn key_quota_closes_exhausted_key() {
+        let mut config = default_config();
+        config.key_quota = Some(KeyQuotaConfig {
+            max_evaluations: NonZeroU32::new(1).expect("1 is non-zero"),
+            window: Duration::from_mins(1),
+            burst: None,
+        });
+        let clock = MockClock::new();
+        let router = builder_with_secret_manager(
+            config,
+            Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+        )
+        .clock(Arc::new(clock.clone()))
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+        let server = TestServerBuilder::new()
+            .http_transport()
+            .build(router)
+            .expect("Can build test-server");
+        let mut first_messages = Vec::new();
+        for (oprf_key_id, advance) in [
+            (42usize, Duration::ZERO),
+            (42, Duration::ZERO),
+            (43, Duration::ZERO),
+            (42, Duration::from_mins(1)),
+        ] {
+            clock.advance(advance);
+            let mut ws = server
+                .get_websocket("/api/test/oprf?version=1.0.0")
+                .await
+                .into_websocket()
+                .await;
+            ws.send_json(&OprfRequest {
+                request_id: Uuid::new_v4(),
+                blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+                auth: OprfKeyId::from(oprf_key_id),
+                issued_at: None,
+                batch: Vec::new(),
+            })
+            .await;
+            first_messages.push(ws.receive_message().await);
+        }
+        assert!(
+            matches!(first_messages[0], tungstenite::Message::Text(_)),
+            "first request should be answered, got {:?}",
+            first_messages[0]
+        );
+        let tungstenite::Message::Close(Some(frame)) = &first_messages[1] else {
+            panic!("expected close frame, got {:?}", first_messages[1]);
+        };
+        assert_eq!(
+            u16::from(frame.code),
+            oprf_error_codes::QUOTA_EXCEEDED,
+            "second request of the key should exceed the quota"
+        );
+        assert_eq!(frame.reason.as_str(), "retry after 60s");
+        assert!(
+            matches!(first_messages[2], tungstenite::Message::Text(_)),
+            "other keys should have their own quota, got {:?}",
+            first_messages[2]
+        );
+        assert!(
+            matches!(first_messages[3], tungstenite::Message::Text(_)),
+            "quota should refill after the window, got {:?}",
+            first_messages[3]
+        );
+    }
+}
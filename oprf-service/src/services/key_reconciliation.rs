@@ -0,0 +1,161 @@
+//! Reconciliation of the cached key material with the `OprfKeyRegistry` (requires the `registry` feature).
+//!
+//! The [`OprfKeyMaterialStore`] loads keys on first use and keeps them until they expire (see `store_ttl` and `store_tti` of [`crate::config::OprfNodeServiceConfig`]), so a cached key misses reshares and deletions until then. This optional service compares the cached keys with the `OprfKeyRegistry` every `key_reconciliation_interval`:
+//! - keys the registry reports as deleted or unknown are evicted,
+//! - keys with a newer epoch in the registry are reloaded from the secret manager. If the key-gen service of this node did not store the new share yet, the cached key is kept and reloaded in the next run.
+//!
+//! A run compares at most `key_reconciliation_max_keys` keys with `key_reconciliation_concurrency` concurrent registry calls. If more keys are cached, the next run continues after the last compared key in the order of their [`OprfKeyId`], so every cached key is compared within a few runs.
+//!
+//! Every run is reported to the `key_reconciliation` [`Subsystem`] at `/health/details`: it is healthy at the block number read at the start of the run if all keys of the run were compared, and degraded with the last error otherwise.
+//!
+//! The registry cannot list its keys, so keys that are not cached are not fetched ahead of time. They are loaded on first use, or at startup with `preload_oprf_key_ids`.
+
+use std::num::NonZeroUsize;
+
+use alloy::{
+    primitives::Address,
+    providers::{DynProvider, Provider as _},
+};
+use futures::{StreamExt as _, stream};
+use nodes_common::web3::HttpRpcProvider;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    chain::OprfKeyRegistry::{OprfKeyRegistryErrors, OprfKeyRegistryInstance},
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    ExitReason, config::OprfNodeServiceConfig, metrics,
+    oprf_key_material_store::OprfKeyMaterialStore, subsystem_health::Subsystem,
+};
+
+/// Spawns the task reconciling the cached keys of `store` every `key_reconciliation_interval` of `config`.
+///
+/// The task stops when `cancellation_token` is cancelled.
+pub(crate) fn spawn(
+    rpc_provider: HttpRpcProvider,
+    registry_address: Address,
+    store: OprfKeyMaterialStore,
+    config: &OprfNodeServiceConfig,
+    subsystem: Subsystem,
+    cancellation_token: CancellationToken,
+) -> JoinHandle<ExitReason> {
+    let concurrency = config.key_reconciliation_concurrency;
+    let max_keys = config.key_reconciliation_max_keys;
+    let mut interval = tokio::time::interval(config.key_reconciliation_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    tokio::spawn(async move {
+        let contract = OprfKeyRegistryInstance::new(registry_address, rpc_provider.inner());
+        let mut cursor = None;
+        loop {
+            tokio::select! {
+                () = cancellation_token.cancelled() => break,
+                _ = interval.tick() => {
+                    let keys = next_keys(store.cached_epochs(usize::MAX), cursor, max_keys);
+                    cursor = keys.last().map(|(oprf_key_id, _)| *oprf_key_id);
+                    reconcile(&contract, &store, keys, concurrency, &subsystem).await;
+                }
+            }
+        }
+        tracing::info!("key reconciliation task stopped");
+        ExitReason::Cancelled
+    })
+}
+
+/// Picks at most `max_keys` of the `cached` keys, starting after `cursor` in the order of their [`OprfKeyId`] and wrapping around.
+fn next_keys(
+    mut cached: Vec<(OprfKeyId, ShareEpoch)>,
+    cursor: Option<OprfKeyId>,
+    max_keys: NonZeroUsize,
+) -> Vec<(OprfKeyId, ShareEpoch)> {
+    cached.sort_unstable_by_key(|(oprf_key_id, _)| *oprf_key_id);
+    let start = cursor.map_or(0, |cursor| {
+        cached.partition_point(|(oprf_key_id, _)| *oprf_key_id <= cursor)
+    });
+    cached.rotate_left(start);
+    cached.truncate(max_keys.get());
+    cached
+}
+
+/// Compares the `keys` with the registry, with at most `concurrency` concurrent registry calls. Keys that cannot be compared are logged and compared again in a later run.
+async fn reconcile(
+    contract: &OprfKeyRegistryInstance<DynProvider>,
+    store: &OprfKeyMaterialStore,
+    keys: Vec<(OprfKeyId, ShareEpoch)>,
+    concurrency: NonZeroUsize,
+    subsystem: &Subsystem,
+) {
+    let block = match contract.provider().get_block_number().await {
+        Ok(block) => block,
+        Err(err) => {
+            tracing::warn!(%err, "cannot read block number from RPC");
+            subsystem.failed(format!("cannot read block number from RPC: {err}"));
+            return;
+        }
+    };
+    let mut results = stream::iter(keys)
+        .map(|(oprf_key_id, cached)| async move {
+            (
+                oprf_key_id,
+                reconcile_key(contract, store, oprf_key_id, cached).await,
+            )
+        })
+        .buffer_unordered(concurrency.get());
+    let mut last_error = None;
+    while let Some((oprf_key_id, result)) = results.next().await {
+        match result {
+            Ok(Some(outcome)) => metrics::secrets::inc_reconciled(outcome),
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!(%err, "cannot reconcile {oprf_key_id} with registry");
+                metrics::secrets::inc_reconciled("failed");
+                last_error = Some(format!("cannot reconcile {oprf_key_id}: {err}"));
+            }
+        }
+    }
+    match last_error {
+        Some(err) => subsystem.failed(err),
+        None => subsystem.succeeded_at_block(block),
+    }
+}
+
+/// Reloads or evicts the cached `oprf_key_id` if the registry moved on. Returns the metric label of the change, `None` if the key is unchanged.
+async fn reconcile_key(
+    contract: &OprfKeyRegistryInstance<DynProvider>,
+    store: &OprfKeyMaterialStore,
+    oprf_key_id: OprfKeyId,
+    cached: ShareEpoch,
+) -> eyre::Result<Option<&'static str>> {
+    let registered = match contract
+        .getOprfPublicKeyAndEpoch(oprf_key_id.into_inner())
+        .call()
+        .await
+    {
+        Ok(registered) => registered,
+        Err(err) => {
+            return match err.as_decoded_interface_error::<OprfKeyRegistryErrors>() {
+                Some(OprfKeyRegistryErrors::DeletedId(_) | OprfKeyRegistryErrors::UnknownId(_)) => {
+                    tracing::info!("evicting {oprf_key_id}, it is not registered anymore");
+                    store.evict(oprf_key_id).await;
+                    Ok(Some("evicted"))
+                }
+                _ => Err(err.into()),
+            };
+        }
+    };
+    let epoch = ShareEpoch::new(registered.epoch);
+    if epoch <= cached {
+        return Ok(None);
+    }
+    if store.reload(oprf_key_id, epoch).await? {
+        tracing::info!("reloaded {oprf_key_id} for epoch {epoch}");
+        Ok(Some("reloaded"))
+    } else {
+        tracing::debug!("secret manager does not hold epoch {epoch} of {oprf_key_id} yet");
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests;
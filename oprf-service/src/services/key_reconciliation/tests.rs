@@ -0,0 +1,126 @@
+use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+use axum_test::TestServerBuilder;
+use oprf_types::{OprfKeyId, ShareEpoch, api::LoadedOprfKey};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    test_kit::{MockAuthenticator, StaticSecretManager},
+    test_utils::{builder_with_secret_manager, default_config},
+};
+
+use super::next_keys;
+
+#[test]
+fn next_keys_rotates_through_cached_keys() {
+    let cached = [5usize, 1, 4, 2, 3]
+        .map(|key| (OprfKeyId::from(key), ShareEpoch::default()))
+        .to_vec();
+    let max_keys = NonZeroUsize::new(2).expect("is non-zero");
+    let ids = |keys: Vec<(OprfKeyId, ShareEpoch)>| {
+        keys.into_iter()
+            .map(|(oprf_key_id, _)| oprf_key_id)
+            .collect::<Vec<_>>()
+    };
+    let keys = |ids: &[usize]| ids.iter().copied().map(OprfKeyId::from).collect::<Vec<_>>();
+
+    assert_eq!(
+        ids(next_keys(cached.clone(), None, max_keys)),
+        keys(&[1, 2]),
+        "should start with the smallest keys"
+    );
+    assert_eq!(
+        ids(next_keys(
+            cached.clone(),
+            Some(OprfKeyId::from(2usize)),
+            max_keys
+        )),
+        keys(&[3, 4]),
+        "should continue after the cursor"
+    );
+    assert_eq!(
+        ids(next_keys(
+            cached.clone(),
+            Some(OprfKeyId::from(4usize)),
+            max_keys
+        )),
+        keys(&[5, 1]),
+        "should wrap around"
+    );
+    assert_eq!(
+        ids(next_keys(cached, Some(OprfKeyId::from(7usize)), max_keys)),
+        keys(&[1, 2]),
+        "should wrap around after an evicted cursor"
+    );
+}
+
+#[tokio::test]
+async fn key_reconciliation_evicts_deleted_keys() {
+    use alloy::sol_types::SolInterface as _;
+    use alloy::{
+        primitives::{Address, U64},
+        providers::mock::Asserter,
+        rpc::json_rpc::ErrorPayload,
+    };
+    use oprf_types::chain::OprfKeyRegistry::{DeletedId, OprfKeyRegistryErrors};
+
+    let oprf_key_id = OprfKeyId::from(42usize);
+    let asserter = Asserter::new();
+    // every run reads the block number first
+    asserter.push_success(&U64::from(1));
+    let revert = OprfKeyRegistryErrors::DeletedId(DeletedId {
+        id: oprf_key_id.into_inner(),
+    })
+    .abi_encode();
+    asserter.push_failure(ErrorPayload {
+        code: 3,
+        message: "execution reverted".into(),
+        data: Some(
+            serde_json::value::to_raw_value(&alloy::primitives::hex::encode_prefixed(revert))
+                .expect("can encode revert data"),
+        ),
+    });
+
+    let mut config = default_config();
+    config.key_reconciliation_interval = Duration::from_millis(20);
+    let cancellation_token = CancellationToken::new();
+    let server = TestServerBuilder::new()
+        .build(
+            builder_with_secret_manager(
+                config,
+                Arc::new(
+                    StaticSecretManager::single_node()
+                        .with_random_key(oprf_key_id, &mut rand::thread_rng()),
+                ),
+            )
+            .key_reconciliation(
+                asserter.clone().into(),
+                Address::repeat_byte(0xaa),
+                cancellation_token.clone(),
+            )
+            .module("/test", MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect("Can build node"),
+        )
+        .expect("Can build test-server");
+    server
+        .get(&format!("/oprf_pub/{oprf_key_id}"))
+        .await
+        .assert_status_ok();
+
+    let mut evicted = false;
+    for _ in 0..100 {
+        if server
+            .get("/oprf_keys")
+            .await
+            .json::<Vec<LoadedOprfKey>>()
+            .is_empty()
+        {
+            evicted = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    cancellation_token.cancel();
+    assert!(evicted, "should evict the key deleted in the registry");
+}
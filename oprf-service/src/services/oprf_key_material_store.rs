@@ -3,37 +3,66 @@
 //! Shares are loaded on demand from the secret manager and cached using a `moka` async cache
 //! with configurable capacity, TTL, and TTI eviction policies.
 //! Each OPRF key material is represented by [`OprfKeyMaterial`].
+//!
+//! Every load of a key whose epoch differs from the epoch the store saw last for this key is broadcast as an [`EpochChanged`] notification, see [`OprfKeyMaterialStore::subscribe_epoch_changes`], and forwarded to the epoch transition hook, if any (see [`crate::services::epoch_transitions`]). The last seen epochs are kept outside the cache, so a reshared key is notified when it is reloaded after an expiry or eviction, while reloading the same epoch is not notified again.
+//!
+//! Lookups of unknown and deleted keys can be cached as well (see [`OprfKeyMaterialStore::with_negative_cache`]), so clients enumerating key ids do not turn every request into a secret-manager lookup.
 
-use moka::future::Cache;
+use moka::{future::Cache, notification::RemovalCause, policy::EvictionPolicy};
 use oprf_core::{
     ddlog_equality::shamir::{
         DLogCommitmentsShamir, DLogProofShareShamir, DLogSessionShamir,
         PartialDLogCommitmentsShamir,
     },
+    dlog_equality::DLogEqualityProof,
     shamir,
 };
 use oprf_types::{
-    OprfKeyId,
-    api::OprfPublicKeyWithEpoch,
+    OprfKeyId, ShareEpoch,
+    api::{EpochChanged, LoadedOprfKey, OprfKeyParams, OprfPublicKeyWithEpoch},
     crypto::{OprfKeyMaterial, PartyId},
 };
-use std::{sync::Arc, time::Duration};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    num::NonZeroU16,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinSet,
+};
 use uuid::Uuid;
 
 use crate::{
+    config::StoreEvictionPolicy,
     metrics,
     secret_manager::{SecretManagerError, SecretManagerService},
+    subsystem_health::Subsystem,
 };
 
 /// Storage for [`OprfKeyMaterial`]s.
 #[derive(Clone)]
 pub struct OprfKeyMaterialStore {
     store: Cache<OprfKeyId, OprfKeyMaterial>,
+    negative: Option<Cache<OprfKeyId, Arc<SecretManagerError>>>,
     secret_manager: SecretManagerService,
+    seen_epochs: Arc<Mutex<HashMap<OprfKeyId, ShareEpoch>>>,
+    epoch_changes: broadcast::Sender<EpochChanged>,
+    epoch_transitions: Arc<OnceLock<mpsc::UnboundedSender<EpochChanged>>>,
+    subsystem: Option<Subsystem>,
 }
 
+/// Capacity of the [`EpochChanged`] broadcast channel. Slow subscribers lag behind and skip notifications.
+const EPOCH_CHANGES_CAPACITY: usize = 256;
+
 /// The session obtained after calling `partial_commit`. Doesn't implement `Debug/Clone` to not accidentally leak private data and prevent reusing the same session.
-pub(crate) struct OprfSession {
+///
+/// Is held by an [`OprfSessionStore`](crate::session_store::OprfSessionStore) between the two rounds of the protocol. Durable session stores serialize it, the serialized form contains the randomness and the share and must be encrypted at rest.
+#[derive(Serialize, Deserialize)]
+pub struct OprfSession {
     oprf_key_id: OprfKeyId,
     dlog_session: DLogSessionShamir,
     key_material: OprfKeyMaterial,
@@ -49,29 +78,280 @@ impl OprfSession {
     pub(crate) fn key_id(&self) -> OprfKeyId {
         self.oprf_key_id
     }
+
+    /// Returns the threshold of the key associated with this session, if it was stored with the key.
+    pub(crate) fn threshold(&self) -> Option<NonZeroU16> {
+        self.key_material.threshold()
+    }
+
+    /// Returns the blinded query of this session.
+    pub(crate) fn blinded_query(&self) -> ark_babyjubjub::EdwardsAffine {
+        self.dlog_session.blinded_query()
+    }
+
+    /// Computes the commitments sent in the first round again, e.g., to answer a resumed session.
+    pub(crate) fn commitments(&self) -> PartialDLogCommitmentsShamir {
+        self.dlog_session.commitments(&self.key_material.share())
+    }
+
+    /// Returns the public share of the share of this session, see [`ShareProof::public_share`](oprf_types::api::ShareProof::public_share).
+    pub(crate) fn public_share(&self) -> ark_babyjubjub::EdwardsAffine {
+        self.key_material.share().public_share()
+    }
+
+    /// Proves that the commitments of this session were computed with the share of this session.
+    pub(crate) fn prove_share(&self) -> DLogEqualityProof {
+        self.key_material
+            .share()
+            .prove_share(self.blinded_query(), &mut rand::thread_rng())
+    }
 }
 
 impl OprfKeyMaterialStore {
-    /// Creates a new storage instance with the provided initial shares.
+    /// Creates a new storage instance that evicts keys according to `eviction_policy`, see [`StoreEvictionPolicy`].
     #[must_use]
     pub fn new(
         secret_manager: SecretManagerService,
         max_capacity: u64,
         time_to_live: Duration,
         time_to_idle: Duration,
+        eviction_policy: StoreEvictionPolicy,
     ) -> Self {
-        let store = Cache::builder()
+        let eviction_policy = match eviction_policy {
+            StoreEvictionPolicy::TinyLfu => EvictionPolicy::tiny_lfu(),
+            StoreEvictionPolicy::Lru => EvictionPolicy::lru(),
+        };
+        let store = Self::build_store(max_capacity, time_to_live, time_to_idle, eviction_policy);
+
+        Self {
+            store,
+            negative: None,
+            secret_manager,
+            seen_epochs: Arc::new(Mutex::new(HashMap::new())),
+            epoch_changes: broadcast::channel(EPOCH_CHANGES_CAPACITY).0,
+            epoch_transitions: Arc::new(OnceLock::new()),
+            subsystem: None,
+        }
+    }
+
+    fn build_store(
+        max_capacity: u64,
+        time_to_live: Duration,
+        time_to_idle: Duration,
+        eviction_policy: EvictionPolicy,
+    ) -> Cache<OprfKeyId, OprfKeyMaterial> {
+        Cache::builder()
             .max_capacity(max_capacity)
             .time_to_live(time_to_live)
             .time_to_idle(time_to_idle)
+            .eviction_policy(eviction_policy)
             .eviction_listener(move |k, _, cause| {
                 tracing::trace!("removing OprfKeyId {k} because: {cause:?}");
+                match cause {
+                    RemovalCause::Size => metrics::secrets::inc_evicted("size"),
+                    RemovalCause::Expired => metrics::secrets::inc_evicted("expired"),
+                    RemovalCause::Explicit | RemovalCause::Replaced => {}
+                }
             })
-            .build();
+            .build()
+    }
 
-        Self {
-            store,
-            secret_manager,
+    /// Reports every load from the secret manager to `subsystem`, see [`crate::subsystem_health`]. Unknown and deleted keys count as successful loads.
+    pub(crate) fn with_subsystem(mut self, subsystem: Subsystem) -> Self {
+        self.subsystem = Some(subsystem);
+        self
+    }
+
+    /// Reports the outcome of a load from the secret manager to the subsystem, if any.
+    fn report_load(&self, err: Option<&SecretManagerError>) {
+        let Some(subsystem) = &self.subsystem else {
+            return;
+        };
+        match err {
+            Some(err @ SecretManagerError::Internal(_)) => subsystem.failed(err),
+            Some(_) | None => subsystem.succeeded(),
+        }
+    }
+
+    /// The secret manager the key material is loaded from.
+    pub(crate) fn secret_manager(&self) -> &SecretManagerService {
+        &self.secret_manager
+    }
+
+    /// Caches unknown and deleted keys for `time_to_live`, holding at most `max_capacity` keys and evicting the least recently used one.
+    ///
+    /// Every entry only holds the key id and the error, so the memory of the cache is bounded by roughly 100 bytes per entry. Keys created while they are cached as unknown are served after `time_to_live` at the latest. A `max_capacity` of `0` disables the cache.
+    #[must_use]
+    pub fn with_negative_cache(mut self, max_capacity: u64, time_to_live: Duration) -> Self {
+        self.negative = (max_capacity > 0).then(|| {
+            Cache::builder()
+                .max_capacity(max_capacity)
+                .time_to_live(time_to_live)
+                .eviction_policy(EvictionPolicy::lru())
+                .build()
+        });
+        self
+    }
+
+    /// Returns the (approximate) number of cached keys.
+    #[cfg(feature = "jemalloc")]
+    pub(crate) fn entry_count(&self) -> u64 {
+        self.store.entry_count()
+    }
+
+    /// Returns the (approximate) number of keys in the negative cache, `0` if it is disabled.
+    #[cfg(feature = "jemalloc")]
+    pub(crate) fn negative_entry_count(&self) -> u64 {
+        self.negative.as_ref().map_or(0, Cache::entry_count)
+    }
+
+    /// Returns the epochs of at most `limit` currently cached keys.
+    pub(crate) fn cached_epochs(&self, limit: usize) -> Vec<(OprfKeyId, ShareEpoch)> {
+        self.store
+            .iter()
+            .take(limit)
+            .map(|(oprf_key_id, key_material)| (*oprf_key_id, key_material.epoch()))
+            .collect()
+    }
+
+    /// Returns the public part of all currently cached keys, sorted by [`OprfKeyId`].
+    pub(crate) fn loaded_keys(&self) -> Vec<LoadedOprfKey> {
+        let mut keys = self
+            .store
+            .iter()
+            .map(|(oprf_key_id, key_material)| {
+                let OprfPublicKeyWithEpoch { key, epoch } = key_material.public_key_with_epoch();
+                LoadedOprfKey {
+                    oprf_key_id: *oprf_key_id,
+                    key,
+                    epoch,
+                }
+            })
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|key| key.oprf_key_id);
+        keys
+    }
+
+    /// Returns all currently cached keys, see [`crate::services::replica_snapshot`].
+    pub(crate) fn snapshot(&self) -> Vec<(OprfKeyId, OprfKeyMaterial)> {
+        self.store
+            .iter()
+            .map(|(oprf_key_id, key_material)| (*oprf_key_id, key_material))
+            .collect()
+    }
+
+    /// Caches the keys of a snapshot of a sibling replica. Returns the number of cached keys.
+    pub(crate) async fn insert_snapshot(
+        &self,
+        snapshot: Vec<(OprfKeyId, OprfKeyMaterial)>,
+    ) -> usize {
+        let len = snapshot.len();
+        for (oprf_key_id, key_material) in snapshot {
+            if let Some(negative) = &self.negative {
+                negative.invalidate(&oprf_key_id).await;
+            }
+            self.swap(oprf_key_id, key_material).await;
+        }
+        self.store.run_pending_tasks().await;
+        metrics::secrets::set(self.store.entry_count());
+        len
+    }
+
+    /// Loads the provided keys from the secret manager concurrently.
+    ///
+    /// Keys that cannot be loaded are logged and skipped, they are retried on first use. Returns the number of loaded keys.
+    pub(crate) async fn preload(&self, oprf_key_ids: &[OprfKeyId]) -> usize {
+        let mut tasks = JoinSet::new();
+        for oprf_key_id in oprf_key_ids.iter().copied() {
+            let store = self.clone();
+            tasks.spawn(async move { (oprf_key_id, store.try_get(oprf_key_id).await) });
+        }
+        let mut loaded = 0;
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok((_, Ok(_))) => loaded += 1,
+                Ok((oprf_key_id, Err(err))) => {
+                    tracing::warn!(%err, "cannot preload OprfKeyId {oprf_key_id}");
+                }
+                Err(err) => tracing::warn!(%err, "preload task failed"),
+            }
+        }
+        loaded
+    }
+
+    /// Replaces the cached key with the key of the secret manager if the secret manager holds at least `epoch`, see [`crate::services::key_reconciliation`].
+    ///
+    /// Returns whether the key was replaced. The cached key is kept if the secret manager does not hold `epoch` yet.
+    #[cfg(feature = "registry")]
+    pub(crate) async fn reload(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> Result<bool, SecretManagerError> {
+        let key_material = self
+            .secret_manager
+            .get_oprf_key_material(oprf_key_id)
+            .await
+            .inspect_err(|err| self.report_load(Some(err)))?;
+        self.report_load(None);
+        let loaded = key_material.epoch();
+        if loaded < epoch {
+            return Ok(false);
+        }
+        self.swap(oprf_key_id, key_material).await;
+        Ok(true)
+    }
+
+    /// Drops the cached key, e.g. after it was deleted in the `OprfKeyRegistry`.
+    #[cfg(feature = "registry")]
+    pub(crate) async fn evict(&self, oprf_key_id: OprfKeyId) {
+        self.store.invalidate(&oprf_key_id).await;
+        self.seen_epochs.lock().remove(&oprf_key_id);
+        self.store.run_pending_tasks().await;
+        metrics::secrets::set(self.store.entry_count());
+    }
+
+    /// Subscribes to [`EpochChanged`] notifications, sent whenever the store loads another epoch of a key than it saw last.
+    pub(crate) fn subscribe_epoch_changes(&self) -> broadcast::Receiver<EpochChanged> {
+        self.epoch_changes.subscribe()
+    }
+
+    /// Forwards every [`EpochChanged`] notification of this store and all its clones to the returned receiver, without skipping notifications like [`Self::subscribe_epoch_changes`]. Returns `None` if the notifications are already forwarded.
+    pub(crate) fn forward_epoch_changes(&self) -> Option<mpsc::UnboundedReceiver<EpochChanged>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.epoch_transitions.set(sender).ok()?;
+        Some(receiver)
+    }
+
+    /// Caches `key_material` for `oprf_key_id`, replacing the cached key, if any.
+    async fn swap(&self, oprf_key_id: OprfKeyId, key_material: OprfKeyMaterial) {
+        self.store
+            .entry(oprf_key_id)
+            .and_upsert_with(|_| {
+                // observed while holding the entry, so notifications follow the order of the swaps
+                self.observe_epoch(oprf_key_id, key_material.epoch());
+                std::future::ready(key_material)
+            })
+            .await;
+    }
+
+    /// Records that the store loaded `epoch` of `oprf_key_id` and notifies the subscribers and the forwarded receiver if the store saw another epoch last.
+    fn observe_epoch(&self, oprf_key_id: OprfKeyId, epoch: ShareEpoch) {
+        let mut seen_epochs = self.seen_epochs.lock();
+        let previous = seen_epochs.insert(oprf_key_id, epoch);
+        if previous == Some(epoch) {
+            return;
+        }
+        let epoch_changed = EpochChanged {
+            oprf_key_id,
+            previous,
+            epoch,
+        };
+        // no subscribers is not an error
+        self.epoch_changes.send(epoch_changed).ok();
+        if let Some(epoch_transitions) = self.epoch_transitions.get() {
+            // the receiver is only dropped on shutdown
+            epoch_transitions.send(epoch_changed).ok();
         }
     }
 
@@ -103,15 +383,41 @@ impl OprfKeyMaterialStore {
         Ok((session, commitment))
     }
 
+    /// Like [`Self::partial_commit`], but for every query of a batch session.
+    ///
+    /// All sessions use the same [`OprfKeyMaterial`], so they share the epoch even if the key is reshared in the meantime.
+    pub(crate) async fn partial_commit_batch(
+        &self,
+        points_b: impl Iterator<Item = ark_babyjubjub::EdwardsAffine>,
+        oprf_key_id: OprfKeyId,
+    ) -> Result<Vec<(OprfSession, PartialDLogCommitmentsShamir)>, Arc<SecretManagerError>> {
+        tracing::trace!("computing partial commitments for batch");
+        let key_material = self.try_get(oprf_key_id).await?;
+        let mut rng = rand::thread_rng();
+        Ok(points_b
+            .map(|point_b| {
+                let (dlog_session, commitment) =
+                    DLogSessionShamir::partial_commitments(point_b, key_material.share(), &mut rng);
+                let session = OprfSession {
+                    oprf_key_id,
+                    dlog_session,
+                    key_material: key_material.clone(),
+                };
+                (session, commitment)
+            })
+            .collect())
+    }
+
     /// Finalizes a proof share for a [`DLogCommitmentsShamir`] and an [`OprfSession`].
     ///
     /// Consumes the session to prevent reuse of the randomness.
-    /// The provided [`OprfKeyId`] identifies the used OPRF key.
+    /// The provided [`OprfKeyId`] identifies the used OPRF key. The proof share is bound to the `context` of the module (`0` for none).
     pub(crate) fn challenge(
         session_id: Uuid,
         my_party_id: PartyId,
         session: OprfSession,
         challenge: DLogCommitmentsShamir,
+        context: ark_babyjubjub::Fq,
     ) -> DLogProofShareShamir {
         tracing::trace!("finalizing proof share");
         let OprfSession {
@@ -129,6 +435,7 @@ impl OprfKeyMaterialStore {
             key_material.public_key().inner(),
             challenge,
             lagrange_coefficient,
+            context,
         )
     }
 
@@ -140,16 +447,54 @@ impl OprfKeyMaterialStore {
         Ok(self.try_get(oprf_key_id).await?.public_key_with_epoch())
     }
 
+    /// Returns the [`OprfKeyParams`], fetching from the secret manager on cache miss.
+    ///
+    /// Keys without a stored threshold use the provided `default_threshold` of the node.
+    pub(crate) async fn oprf_key_params(
+        &self,
+        oprf_key_id: OprfKeyId,
+        default_threshold: NonZeroU16,
+    ) -> Result<OprfKeyParams, Arc<SecretManagerError>> {
+        let key_material = self.try_get(oprf_key_id).await?;
+        Ok(OprfKeyParams {
+            key: key_material.public_key(),
+            epoch: key_material.epoch(),
+            threshold: key_material.threshold().unwrap_or(default_threshold),
+            max_message_size: None,
+            max_batch_size: None,
+        })
+    }
+
     async fn try_get(
         &self,
         oprf_key_id: OprfKeyId,
     ) -> Result<OprfKeyMaterial, Arc<SecretManagerError>> {
-        let key_material = self
+        if let Some(err) = self.cached_negative(oprf_key_id).await {
+            metrics::secrets::negative_hit();
+            return Err(err);
+        }
+        let key_material = match self
             .store
             .entry(oprf_key_id)
-            .or_try_insert_with(self.secret_manager.get_oprf_key_material(oprf_key_id))
-            .await?;
+            .or_try_insert_with(async {
+                let key_material = self
+                    .secret_manager
+                    .get_oprf_key_material(oprf_key_id)
+                    .await?;
+                self.observe_epoch(oprf_key_id, key_material.epoch());
+                Ok::<_, SecretManagerError>(key_material)
+            })
+            .await
+        {
+            Ok(key_material) => key_material,
+            Err(err) => {
+                self.report_load(Some(&err));
+                self.cache_negative(oprf_key_id, &err).await;
+                return Err(err);
+            }
+        };
         if key_material.is_fresh() {
+            self.report_load(None);
             self.store.run_pending_tasks().await;
             metrics::secrets::set(self.store.entry_count());
             metrics::secrets::miss();
@@ -158,4 +503,26 @@ impl OprfKeyMaterialStore {
         }
         Ok(key_material.into_value())
     }
+
+    async fn cached_negative(&self, oprf_key_id: OprfKeyId) -> Option<Arc<SecretManagerError>> {
+        self.negative.as_ref()?.get(&oprf_key_id).await
+    }
+
+    /// Caches unknown and deleted keys. Internal errors of the secret manager are not cached, so they are retried on the next request.
+    async fn cache_negative(&self, oprf_key_id: OprfKeyId, err: &Arc<SecretManagerError>) {
+        let Some(negative) = &self.negative else {
+            return;
+        };
+        if matches!(
+            **err,
+            SecretManagerError::UnknownOprfKeyId(_) | SecretManagerError::DeletedOprfKeyId(_)
+        ) {
+            negative.insert(oprf_key_id, Arc::clone(err)).await;
+            negative.run_pending_tasks().await;
+            metrics::secrets::set_negative(negative.entry_count());
+        }
+    }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,287 @@
+use std::{num::NonZeroU16, sync::Arc, time::Duration};
+
+use ark_ec::AffineRepr as _;
+use axum_test::TestServerBuilder;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{EpochChanged, OprfRequest, OprfResponse, oprf_error_codes},
+    crypto::PartyId,
+    service::NodeInformation,
+};
+use uuid::Uuid;
+
+use crate::{
+    BuilderError,
+    config::{OprfNodeServiceConfig, StoreEvictionPolicy},
+    services::oprf_key_material_store::OprfKeyMaterialStore,
+    test_kit::MockAuthenticator,
+    test_utils::{
+        MockSecretManager, builder, builder_with_config, builder_with_secret_manager,
+        default_config, fixed_key_material,
+    },
+};
+
+/// Requests an unknown key in the given environment and returns the close frame.
+#[tokio::test]
+async fn module_with_own_key_material_store() {
+    let config = default_config();
+    let store = crate::oprf_key_material_store::OprfKeyMaterialStore::new(
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+        config.store_max_capacity,
+        config.store_ttl,
+        config.store_tti,
+        config.store_eviction_policy,
+    );
+    let router = builder_with_config(config)
+        .module("/node", MockAuthenticator::allow_all().into_service())
+        .module_with_key_material_store(
+            "/tenant",
+            MockAuthenticator::allow_all().into_service(),
+            store,
+        )
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let request = OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+
+    let mut tenant = server
+        .get_websocket("/api/tenant/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    tenant.send_json(&request).await;
+    let _: OprfResponse = tenant.receive_json().await;
+
+    // the same key id and session id are unknown to the store of the node
+    let mut node = server
+        .get_websocket("/api/node/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    node.send_json(&request).await;
+    let tungstenite::Message::Close(Some(frame)) = node.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::UNKNOWN_OPRF_KEY_ID,
+        "should not serve keys of the other store"
+    );
+}
+
+/// Returns a store of the registry that assigned `node_information` to the node, which persists the party id binding.
+fn registry_store(
+    node_information: NodeInformation,
+) -> (Arc<MockSecretManager>, OprfKeyMaterialStore) {
+    let config = default_config();
+    let secret_manager = Arc::new(
+        MockSecretManager::fixed_key(ShareEpoch::default())
+            .with_node_information(node_information)
+            .with_binding_store(),
+    );
+    let store = OprfKeyMaterialStore::new(
+        Arc::clone(&secret_manager) as _,
+        config.store_max_capacity,
+        config.store_ttl,
+        config.store_tti,
+        config.store_eviction_policy,
+    );
+    (secret_manager, store)
+}
+
+#[tokio::test]
+async fn registry_module_serves_with_party_id_of_registry() {
+    let (secret_manager, store) = registry_store(NodeInformation::new(
+        PartyId(2),
+        "0x0000000000000000000000000000000000000000".to_owned(),
+        NonZeroU16::new(3).expect("3 is non-zero"),
+    ));
+    let router = builder()
+        .module("/node", MockAuthenticator::allow_all().into_service())
+        .registry_module(
+            "/registry",
+            MockAuthenticator::allow_all().into_service(),
+            store,
+            "0x1111111111111111111111111111111111111111",
+            1,
+        )
+        .await
+        .build()
+        .expect("Can build");
+    let binding = secret_manager
+        .binding()
+        .expect("should persist the binding");
+    assert_eq!(binding.party_id, PartyId(2));
+
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let request = OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+    let mut registry = server
+        .get_websocket("/api/registry/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    registry.send_json(&request).await;
+    let response: OprfResponse = registry.receive_json().await;
+    assert_eq!(
+        response.party_id,
+        PartyId(2),
+        "should answer with the party id of the registry, not of the node"
+    );
+}
+
+#[tokio::test]
+async fn registry_module_rejects_node_information_of_other_wallet() {
+    let (_, store) = registry_store(NodeInformation::new(
+        PartyId(2),
+        "0x2222222222222222222222222222222222222222".to_owned(),
+        NonZeroU16::new(3).expect("3 is non-zero"),
+    ));
+    let err = builder()
+        .registry_module(
+            "/registry",
+            MockAuthenticator::allow_all().into_service(),
+            store,
+            "0x1111111111111111111111111111111111111111",
+            1,
+        )
+        .await
+        .build()
+        .expect_err("should not build");
+    assert!(
+        matches!(err, BuilderError::ModuleNodeInformation { ref path, .. } if path == "/registry"),
+        "expected ModuleNodeInformation, got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn lru_store_evicts_least_recently_used_key() {
+    let secret_manager = Arc::new(MockSecretManager::fixed_key(ShareEpoch::new(1)));
+    let store = OprfKeyMaterialStore::new(
+        Arc::clone(&secret_manager) as _,
+        2,
+        Duration::from_hours(1),
+        Duration::from_hours(1),
+        StoreEvictionPolicy::Lru,
+    );
+    let loads = || secret_manager.lookups();
+    let commit = |key: usize| {
+        store.partial_commit(
+            ark_babyjubjub::EdwardsAffine::generator(),
+            OprfKeyId::from(key),
+        )
+    };
+
+    store.preload(&[OprfKeyId::from(1usize)]).await;
+    store.preload(&[OprfKeyId::from(2usize)]).await;
+    commit(1).await.expect("key 1 is cached");
+    store.preload(&[OprfKeyId::from(3usize)]).await;
+    assert_eq!(loads(), 3, "should load every key once");
+
+    commit(1).await.expect("key 1 is cached");
+    commit(3).await.expect("key 3 is cached");
+    assert_eq!(loads(), 3, "should serve recently used keys from the store");
+    commit(2).await.expect("key 2 is loaded again");
+    assert_eq!(
+        loads(),
+        4,
+        "should have evicted the least recently used key"
+    );
+}
+
+#[tokio::test]
+async fn loads_of_other_epochs_are_epoch_changes() {
+    let secret_manager = Arc::new(MockSecretManager::fixed_key(ShareEpoch::new(1)));
+    let store = OprfKeyMaterialStore::new(
+        Arc::clone(&secret_manager) as _,
+        1,
+        Duration::from_hours(1),
+        Duration::from_hours(1),
+        StoreEvictionPolicy::Lru,
+    );
+    let mut epoch_changes = store.subscribe_epoch_changes();
+    let mut transitions = store.forward_epoch_changes().expect("not forwarded yet");
+    let key_1 = OprfKeyId::from(1usize);
+    let key_2 = OprfKeyId::from(2usize);
+
+    store.preload(&[key_1]).await;
+    store.preload(&[key_2]).await;
+    store.preload(&[key_1]).await;
+    secret_manager.reshare(ShareEpoch::new(2));
+    store.preload(&[key_2]).await;
+    assert_eq!(secret_manager.lookups(), 4, "should reload evicted keys");
+    store
+        .insert_snapshot(vec![(key_1, fixed_key_material(ShareEpoch::new(2)))])
+        .await;
+    store
+        .insert_snapshot(vec![(key_1, fixed_key_material(ShareEpoch::new(2)))])
+        .await;
+
+    let changed = |oprf_key_id, previous: Option<u32>, epoch| EpochChanged {
+        oprf_key_id,
+        previous: previous.map(ShareEpoch::new),
+        epoch: ShareEpoch::new(epoch),
+    };
+    let expected = vec![
+        changed(key_1, None, 1),
+        changed(key_2, None, 1),
+        changed(key_2, Some(1), 2),
+        changed(key_1, Some(1), 2),
+    ];
+    let notified = std::iter::from_fn(|| epoch_changes.try_recv().ok()).collect::<Vec<_>>();
+    assert_eq!(
+        notified, expected,
+        "should notify every load of another epoch, including reloads after an eviction"
+    );
+    let forwarded = std::iter::from_fn(|| transitions.try_recv().ok()).collect::<Vec<_>>();
+    assert_eq!(forwarded, expected, "should forward every notification");
+}
+
+/// Requests the same unknown key three times and returns the number of secret-manager lookups.
+async fn unknown_key_lookups(config: OprfNodeServiceConfig) -> usize {
+    let secret_manager = Arc::new(MockSecretManager::default());
+    let router = builder_with_secret_manager(config, Arc::clone(&secret_manager) as _)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .build(router)
+        .expect("Can build test-server");
+    for _ in 0..3 {
+        server.get("/oprf_pub/42").await.assert_status_not_found();
+    }
+    secret_manager.lookups()
+}
+
+#[tokio::test]
+async fn negative_cache_bounds_unknown_key_lookups() {
+    let mut config = default_config();
+    config.store_negative_max_capacity = Some(10_000);
+    assert_eq!(
+        unknown_key_lookups(config).await,
+        1,
+        "should remember the unknown key"
+    );
+    assert_eq!(
+        unknown_key_lookups(default_config()).await,
+        3,
+        "should ask the secret manager every time without negative cache"
+    );
+}
@@ -0,0 +1,49 @@
+//! Detection of changed party ids.
+//!
+//! The Shamir index of the shares of a node is its party id. If the `OprfKeyRegistry` assigns a different party id to a node (e.g. after it re-registered) while the node still serves the old shares, the node would produce proof shares with the wrong index. To prevent this, the node persists its [`PartyIdBinding`] via the secret manager on the first start and compares it on every later start, see [`bind`].
+
+use crate::{
+    BuilderError,
+    secret_manager::{PartyIdBinding, SecretManagerService},
+};
+
+/// Compares the `current` binding of the node with the binding persisted by the `secret_manager` and persists `current` if there is none yet.
+///
+/// A changed binding is only accepted (and then persisted) if the operator explicitly set `accept_changed`.
+///
+/// # Errors
+/// Returns [`BuilderError::PartyIdChanged`] if the binding changed and `accept_changed` is not set, and [`BuilderError::PartyIdBindingUnavailable`] if the secret manager cannot load or store the binding.
+pub(crate) async fn bind(
+    secret_manager: &SecretManagerService,
+    current: PartyIdBinding,
+    accept_changed: bool,
+) -> Result<(), BuilderError> {
+    let persisted = secret_manager
+        .load_party_id_binding()
+        .await
+        .map_err(BuilderError::PartyIdBindingUnavailable)?;
+    match persisted {
+        Some(persisted) if persisted == current => {
+            tracing::info!("serving with persisted {current}");
+            return Ok(());
+        }
+        Some(persisted) if !accept_changed => {
+            tracing::error!("persisted {persisted} does not match {current}");
+            return Err(BuilderError::PartyIdChanged {
+                persisted: Box::new(persisted),
+                current: Box::new(current),
+            });
+        }
+        Some(persisted) => {
+            tracing::warn!("operator accepted change from {persisted} to {current}");
+        }
+        None => tracing::info!("persisting {current}"),
+    }
+    secret_manager
+        .store_party_id_binding(&current)
+        .await
+        .map_err(BuilderError::PartyIdBindingUnavailable)
+}
+
+#[cfg(test)]
+mod tests;
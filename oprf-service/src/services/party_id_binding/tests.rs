@@ -0,0 +1,73 @@
+use std::{num::NonZeroU16, sync::Arc};
+
+use oprf_types::{crypto::PartyId, service::NodeInformation};
+
+use crate::{
+    BuilderError, OprfServiceBuilder, StartedServices,
+    test_kit::MockAuthenticator,
+    test_utils::{MockSecretManager, default_config},
+};
+
+/// Builds a node with `party_id` that binds it via `secret_manager`.
+async fn bind_party_id(
+    secret_manager: crate::secret_manager::SecretManagerService,
+    party_id: u16,
+    accept_changed_party_id: bool,
+) -> Result<(), BuilderError> {
+    let mut config = default_config();
+    config.accept_changed_party_id = accept_changed_party_id;
+    OprfServiceBuilder::init(
+        config,
+        secret_manager,
+        StartedServices::default(),
+        &NodeInformation::new(
+            PartyId(party_id),
+            "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc".to_owned(),
+            NonZeroU16::new(2).expect("2 is non-zero"),
+        ),
+        "test".to_owned(),
+    )
+    .bind_party_id("0x5FbDB2315678afecb367f032d93F642f64180aa3", 31337)
+    .await
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .map(drop)
+}
+
+#[tokio::test]
+async fn changed_party_id_requires_override() {
+    let secret_manager = Arc::new(MockSecretManager::default().with_binding_store());
+    bind_party_id(secret_manager.clone(), 1, false)
+        .await
+        .expect("should persist the first binding");
+    assert_eq!(
+        secret_manager.binding().map(|binding| binding.party_id),
+        Some(PartyId(1)),
+        "should persist the party id"
+    );
+    bind_party_id(secret_manager.clone(), 1, false)
+        .await
+        .expect("should accept the same binding");
+
+    assert!(
+        matches!(
+            bind_party_id(secret_manager.clone(), 2, false).await,
+            Err(BuilderError::PartyIdChanged { .. })
+        ),
+        "should reject a changed party id"
+    );
+    bind_party_id(secret_manager.clone(), 2, true)
+        .await
+        .expect("should accept the change with the override");
+    bind_party_id(secret_manager.clone(), 2, false)
+        .await
+        .expect("should persist the accepted change");
+
+    assert!(
+        matches!(
+            bind_party_id(Arc::new(MockSecretManager::default()), 1, false).await,
+            Err(BuilderError::PartyIdBindingUnavailable(_))
+        ),
+        "should fail if the secret manager cannot persist the binding"
+    );
+}
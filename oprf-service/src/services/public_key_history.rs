@@ -0,0 +1,166 @@
+//! History of the public keys of every finalized epoch (requires the `registry` feature).
+//!
+//! This optional service polls the `OprfKeyRegistry` for `SecretGenFinalize` events, starting at a configured block. For every finalized epoch, it records the public key of the key (as stored by the key-gen service) in the secret manager (see [`SecretManager::record_public_key`](crate::secret_manager::SecretManager::record_public_key)), keeping the latest `public_key_history_retention` epochs per key. The history is served at `/oprf_pub/{id}/history` (see [`crate::api::info`]).
+//!
+//! The key-gen service of this node stores the finalized share only after the event was emitted. Events whose key cannot be loaded yet are retried in the next poll, together with all later events. Recording an epoch twice is harmless.
+//!
+//! After every poll, the last handled block is stored in the secret manager (see [`SecretManager::store_event_watcher_block`](crate::secret_manager::SecretManager::store_event_watcher_block)), keyed by the address of the registry. After a restart, the service resumes after the stored block, or at the configured block if it is later (or nothing was stored yet).
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use alloy::{
+    primitives::Address,
+    providers::{DynProvider, Provider as _},
+};
+use nodes_common::web3::HttpRpcProvider;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::OprfPublicKeyWithEpoch,
+    chain::{OprfKeyRegistry::OprfKeyRegistryInstance, logs::is_range_limit_error},
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::{ExitReason, secret_manager::SecretManagerService};
+
+/// Max number of blocks per `eth_getLogs` request. Halved if the provider rejects the range.
+const MAX_BLOCK_RANGE: u64 = 10_000;
+
+/// Spawns the task recording the public keys of finalized epochs, starting at `from_block` or after the last block stored for `registry_address`, whichever is later.
+///
+/// The task stops when `cancellation_token` is cancelled.
+pub(crate) fn spawn(
+    rpc_provider: HttpRpcProvider,
+    registry_address: Address,
+    secret_manager: SecretManagerService,
+    from_block: u64,
+    poll_interval: Duration,
+    retention: NonZeroUsize,
+    cancellation_token: CancellationToken,
+) -> JoinHandle<ExitReason> {
+    tokio::spawn(async move {
+        let contract = OprfKeyRegistryInstance::new(registry_address, rpc_provider.inner());
+        let contract_key = registry_address.to_string();
+        let mut next_block = resume_block(&secret_manager, &contract_key, from_block).await;
+        let mut block_range = MAX_BLOCK_RANGE;
+        let mut interval = tokio::time::interval(poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                () = cancellation_token.cancelled() => break,
+                _ = interval.tick() => {
+                    match poll(&contract, &secret_manager, next_block, block_range, retention).await {
+                        Ok(block) => {
+                            if block > next_block {
+                                store_handled_block(&secret_manager, &contract_key, block - 1).await;
+                            }
+                            next_block = block;
+                        }
+                        Err(err) if is_range_limit_error(&err.to_string()) && block_range > 1 => {
+                            block_range /= 2;
+                            tracing::debug!("provider rejected block range, retrying with {block_range} blocks");
+                        }
+                        Err(err) => tracing::warn!(%err, "cannot record public key history"),
+                    }
+                }
+            }
+        }
+        tracing::info!("public key history task stopped");
+        ExitReason::Cancelled
+    })
+}
+
+/// The first block to poll, i.e., the block after the stored last handled block of `contract`, but not before `from_block`.
+async fn resume_block(
+    secret_manager: &SecretManagerService,
+    contract: &str,
+    from_block: u64,
+) -> u64 {
+    match secret_manager.load_event_watcher_block(contract).await {
+        Ok(Some(block)) if block >= from_block => {
+            tracing::info!("resuming public key history after block {block}");
+            block + 1
+        }
+        Ok(_) => from_block,
+        Err(err) => {
+            tracing::warn!(%err, "cannot load last handled block - starting at block {from_block}");
+            from_block
+        }
+    }
+}
+
+/// Stores `block` as last handled block of `contract`. Failing to store is not fatal, the service only handles more blocks again after a restart.
+async fn store_handled_block(secret_manager: &SecretManagerService, contract: &str, block: u64) {
+    if let Err(err) = secret_manager
+        .store_event_watcher_block(contract, block)
+        .await
+    {
+        tracing::warn!(%err, "cannot store last handled block {block}");
+    }
+}
+
+/// Records the public keys of the epochs finalized in the blocks from `from_block` up to the latest block, at most `block_range` blocks at once. Returns the first block of the next poll.
+async fn poll(
+    contract: &OprfKeyRegistryInstance<DynProvider>,
+    secret_manager: &SecretManagerService,
+    from_block: u64,
+    block_range: u64,
+    retention: NonZeroUsize,
+) -> eyre::Result<u64> {
+    let latest_block = contract.provider().get_block_number().await?;
+    let mut from_block = from_block;
+    while from_block <= latest_block {
+        let to_block = latest_block.min(from_block + block_range - 1);
+        let mut finalized = contract
+            .SecretGenFinalize_filter()
+            .from_block(from_block)
+            .to_block(to_block)
+            .query()
+            .await?;
+        finalized.sort_by_key(|(_, log)| (log.block_number, log.log_index));
+        for (event, log) in finalized {
+            let oprf_key_id = OprfKeyId::new(event.oprfKeyId);
+            let epoch = ShareEpoch::new(event.epoch);
+            if let Err(err) = record(secret_manager, oprf_key_id, epoch, retention).await {
+                tracing::debug!(%err, "cannot record epoch {epoch} of {oprf_key_id} yet");
+                // retry this event and everything after it in the next poll
+                return Ok(log.block_number.unwrap_or(from_block));
+            }
+        }
+        from_block = to_block + 1;
+    }
+    Ok(from_block)
+}
+
+/// Records the public key of `oprf_key_id` for `epoch`. Reshares keep the public key, so the currently stored key material provides the public key of every epoch.
+async fn record(
+    secret_manager: &SecretManagerService,
+    oprf_key_id: OprfKeyId,
+    epoch: ShareEpoch,
+    retention: NonZeroUsize,
+) -> eyre::Result<()> {
+    let key_material = match secret_manager.get_oprf_key_material(oprf_key_id).await {
+        Ok(key_material) => key_material,
+        Err(crate::secret_manager::SecretManagerError::DeletedOprfKeyId(_)) => {
+            tracing::debug!("skipping epoch {epoch} of deleted {oprf_key_id}");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    eyre::ensure!(
+        key_material.epoch() >= epoch,
+        "key-gen did not store epoch {epoch} yet"
+    );
+    secret_manager
+        .record_public_key(
+            oprf_key_id,
+            OprfPublicKeyWithEpoch {
+                key: key_material.public_key(),
+                epoch,
+            },
+            retention,
+        )
+        .await?;
+    tracing::info!("recorded public key of {oprf_key_id} for epoch {epoch}");
+    Ok(())
+}
@@ -0,0 +1,197 @@
+//! Snapshots of the key-material store for bootstrapping replicas of the same node.
+//!
+//! A new replica of a node (same party, same wallet address) can fetch the key material cached at a healthy sibling instead of loading every key from the secret manager. Both replicas share a replica secret, from which two keys are derived:
+//!
+//! - The new replica sends a [`SnapshotRequest`] with its wallet address, a random nonce and the current time, authenticated with a keyed BLAKE3 MAC. The sibling only answers requests with a valid MAC, its own wallet address and a timestamp at most [`MAX_REQUEST_AGE`] old.
+//! - The sibling answers with its cached key material, encrypted with ChaCha20-Poly1305. The nonce and address of the request are the associated data, so only a sibling knowing the secret can produce a response the new replica accepts, and responses cannot be replayed for other requests.
+//!
+//! The snapshot only contains the keys the sibling currently caches. All other keys are loaded from the secret manager on first use, as usual.
+//!
+//! # Why not the wallet key
+//!
+//! Both replicas authenticate each other with the replica secret, not with the wallet key of the node. The wallet address is only bound into the request, so a sibling never serves replicas of another node that happen to share the secret. The wallet key is not used for two reasons:
+//!
+//! - The node cannot rely on holding it. None of the bundled secret managers can sign with it (see `SecretManager::sign_response`), only secret managers embedded by the operator that have access to it, and nodes signing with a remote signer would need a signing round-trip per snapshot.
+//! - Signatures only authenticate the peers. The snapshot contains the shares and must also be encrypted, which would need an additional key agreement on top of the signatures. A shared secret provides both with two derived keys.
+//!
+//! Operators therefore provision the replica secret next to the wallet key of the node, and rotating it requires restarting all replicas of the node.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chacha20poly1305::{
+    ChaCha20Poly1305, KeyInit as _, Nonce,
+    aead::{Aead as _, Payload},
+};
+use eyre::Context as _;
+use oprf_types::{OprfKeyId, crypto::OprfKeyMaterial};
+use rand::Rng as _;
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use zeroize::Zeroizing;
+
+/// Max age of a [`SnapshotRequest`], and how far its timestamp may be in the future.
+pub(crate) const MAX_REQUEST_AGE: Duration = Duration::from_mins(1);
+
+const MAC_CONTEXT: &str = "taceo-oprf replica snapshot request mac v1";
+const ENCRYPTION_CONTEXT: &str = "taceo-oprf replica snapshot encryption v1";
+const AEAD_NONCE_LEN: usize = 12;
+
+/// The cached key material of a node.
+pub(crate) type Snapshot = Vec<(OprfKeyId, OprfKeyMaterial)>;
+
+/// Errors when verifying a [`SnapshotRequest`].
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum SnapshotRequestError {
+    #[error("invalid MAC")]
+    InvalidMac,
+    #[error("requested by a different wallet address {0}")]
+    WrongAddress(String),
+    #[error("request timestamp {0} outside the accepted window")]
+    Expired(u64),
+}
+
+/// The request of a new replica for the [`Snapshot`] of a sibling.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SnapshotRequest {
+    address: String,
+    nonce: [u8; 32],
+    timestamp: u64,
+    mac: [u8; 32],
+}
+
+/// The keys derived from the replica secret.
+#[derive(Clone)]
+pub(crate) struct ReplicaKeys {
+    mac_key: Zeroizing<[u8; 32]>,
+    encryption_key: Zeroizing<[u8; 32]>,
+}
+
+impl ReplicaKeys {
+    /// Derives the keys from the replica secret shared by all replicas of a node.
+    pub(crate) fn derive(secret: &SecretString) -> Self {
+        let secret = secret.expose_secret().as_bytes();
+        Self {
+            mac_key: Zeroizing::new(blake3::derive_key(MAC_CONTEXT, secret)),
+            encryption_key: Zeroizing::new(blake3::derive_key(ENCRYPTION_CONTEXT, secret)),
+        }
+    }
+
+    fn mac(&self, address: &str, nonce: &[u8; 32], timestamp: u64) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new_keyed(&self.mac_key);
+        hasher.update(&(address.len() as u64).to_le_bytes());
+        hasher.update(address.as_bytes());
+        hasher.update(nonce);
+        hasher.update(&timestamp.to_le_bytes());
+        hasher.finalize()
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(self.encryption_key.as_ref().into())
+    }
+
+    /// Creates an authenticated request for the replica with wallet `address`.
+    pub(crate) fn request(&self, address: &str) -> SnapshotRequest {
+        let nonce = rand::thread_rng().r#gen();
+        let timestamp = unix_now();
+        SnapshotRequest {
+            address: address.to_owned(),
+            nonce,
+            timestamp,
+            mac: *self.mac(address, &nonce, timestamp).as_bytes(),
+        }
+    }
+
+    /// Verifies that the request was sent by a replica of the node with wallet `address`.
+    pub(crate) fn verify(
+        &self,
+        request: &SnapshotRequest,
+        address: &str,
+    ) -> Result<(), SnapshotRequestError> {
+        // blake3::Hash compares in constant time
+        if self.mac(&request.address, &request.nonce, request.timestamp) != request.mac {
+            return Err(SnapshotRequestError::InvalidMac);
+        }
+        if !request.address.eq_ignore_ascii_case(address) {
+            return Err(SnapshotRequestError::WrongAddress(request.address.clone()));
+        }
+        if unix_now().abs_diff(request.timestamp) > MAX_REQUEST_AGE.as_secs() {
+            return Err(SnapshotRequestError::Expired(request.timestamp));
+        }
+        Ok(())
+    }
+
+    /// Encrypts the snapshot for the requesting replica.
+    pub(crate) fn seal(
+        &self,
+        request: &SnapshotRequest,
+        snapshot: &Snapshot,
+    ) -> eyre::Result<Vec<u8>> {
+        let mut plaintext = Zeroizing::new(Vec::new());
+        ciborium::into_writer(snapshot, &mut *plaintext).context("while serializing snapshot")?;
+        let nonce: [u8; AEAD_NONCE_LEN] = rand::thread_rng().r#gen();
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &associated_data(request),
+                },
+            )
+            .map_err(|_| eyre::eyre!("cannot encrypt snapshot"))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    /// Decrypts the snapshot answering `request`.
+    pub(crate) fn open(&self, request: &SnapshotRequest, sealed: &[u8]) -> eyre::Result<Snapshot> {
+        if sealed.len() < AEAD_NONCE_LEN {
+            eyre::bail!("snapshot too short");
+        }
+        let (nonce, ciphertext) = sealed.split_at(AEAD_NONCE_LEN);
+        let nonce: [u8; AEAD_NONCE_LEN] = nonce.try_into().expect("split at nonce length");
+        let plaintext = Zeroizing::new(
+            self.cipher()
+                .decrypt(
+                    &Nonce::from(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: &associated_data(request),
+                    },
+                )
+                .map_err(|_| {
+                    eyre::eyre!("cannot decrypt snapshot, is the replica secret the same?")
+                })?,
+        );
+        ciborium::from_reader(plaintext.as_slice()).context("while deserializing snapshot")
+    }
+}
+
+/// Fetches the [`Snapshot`] from the sibling at `peer` for the replica with wallet `address`.
+pub(crate) async fn fetch(
+    peer: &Url,
+    keys: &ReplicaKeys,
+    address: &str,
+    client: &reqwest::Client,
+) -> eyre::Result<Snapshot> {
+    let request = keys.request(address);
+    let sealed = client
+        .post(peer.join("replica/snapshot")?)
+        .json(&request)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    keys.open(&request, &sealed)
+}
+
+fn associated_data(request: &SnapshotRequest) -> Vec<u8> {
+    [request.nonce.as_slice(), request.address.as_bytes()].concat()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
@@ -0,0 +1,69 @@
+//! Risk scoring interface for OPRF nodes.
+//!
+//! This module defines the [`RiskScorer`] trait, an optional hook for external fraud or risk systems. If a scorer is set with [`crate::OprfServiceBuilder::risk_scorer`], the node calls it for every request after the request was authenticated and before it computes anything for it. The scorer decides to:
+//! - [`RiskDecision::Allow`] the evaluation,
+//! - [`RiskDecision::Deny`] it, closing the connection with [`oprf_types::api::oprf_error_codes::RISK_DENIED`], or
+//! - [`RiskDecision::Throttle`] it, closing the connection with [`oprf_types::api::oprf_error_codes::RISK_THROTTLED`] and the time after which the client may retry.
+//!
+//! Every call is bounded by `risk_scorer_timeout`. If the scorer times out or fails, the node either allows (fail-open) or denies (fail-closed) the request, depending on `risk_scorer_fail_open` (see [`crate::config::OprfNodeServiceConfig`]).
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use oprf_types::OprfKeyId;
+use uuid::Uuid;
+
+/// Dynamic trait object for the risk scorer.
+///
+/// Must be `Send + Sync` to work with async contexts (e.g., Axum).
+pub type RiskScorerService = Arc<dyn RiskScorer + Send + Sync>;
+
+/// The metadata of an authenticated request, passed to the [`RiskScorer`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RiskRequest {
+    /// The id of the session.
+    pub request_id: Uuid,
+    /// The key the request was authenticated for.
+    pub oprf_key_id: OprfKeyId,
+    /// The path of the OPRF module that received the request (e.g., `/my-module`).
+    pub module: Arc<str>,
+    /// The issued-at timestamp of the request, if the client sent one.
+    pub issued_at: Option<u64>,
+}
+
+/// The decision of a [`RiskScorer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RiskDecision {
+    /// Evaluate the request.
+    Allow,
+    /// Reject the request.
+    Deny,
+    /// Reject the request for now, the client may retry after `retry_after`.
+    Throttle {
+        /// The time after which the client may retry.
+        retry_after: Duration,
+    },
+}
+
+impl RiskDecision {
+    /// The label of the decision in the `taceo.oprf.node.risk.decision` metric.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Deny => "deny",
+            Self::Throttle { .. } => "throttle",
+        }
+    }
+}
+
+/// Scores authenticated requests before the node evaluates them, see the [module docs](self).
+#[async_trait]
+pub trait RiskScorer {
+    /// Decides whether the node evaluates the request.
+    ///
+    /// # Errors
+    /// Errors are logged and handled like a timeout, i.e., depending on `risk_scorer_fail_open`.
+    async fn score(&self, request: &RiskRequest) -> eyre::Result<RiskDecision>;
+}
@@ -0,0 +1,148 @@
+//! Limits of an OPRF module that can be adjusted at runtime.
+//!
+//! Every module starts with the `ws_max_message_size`, `session_lifetime` and `version_req` of the [`crate::config::OprfNodeServiceConfig`]. If the admin API is enabled (see [`crate::OprfServiceBuilder::admin_api`]), operators can override them per module without a restart, e.g. to tighten the limits during an incident. Overrides are bounded by [`MAX_MESSAGE_SIZE_LIMIT`] and [`SESSION_LIFETIME_LIMIT`].
+//!
+//! The module reads the limits once per connection, so overrides only apply to new connections. Open connections keep the limits they started with.
+
+use std::{sync::Arc, time::Duration};
+
+use oprf_types::api::ModuleParams;
+use parking_lot::RwLock;
+use semver::VersionReq;
+use serde::Deserialize;
+
+/// The largest `max_message_size` an override may set.
+pub(crate) const MAX_MESSAGE_SIZE_LIMIT: usize = 1024 * 1024;
+/// The largest `session_lifetime` an override may set.
+pub(crate) const SESSION_LIFETIME_LIMIT: Duration = Duration::from_mins(10);
+
+/// The limits of a module that can be overridden at runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Limits {
+    /// Max size in bytes of a single message of a session.
+    pub(crate) max_message_size: usize,
+    /// Max time a session is valid.
+    pub(crate) session_lifetime: Duration,
+    /// The protocol versions accepted from clients.
+    pub(crate) version_req: VersionReq,
+}
+
+/// An override of the [`Limits`] of a module, as sent to the admin API. Fields that are not set keep their current value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LimitsUpdate {
+    #[serde(default)]
+    pub(crate) max_message_size: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    pub(crate) session_lifetime: Option<Duration>,
+    #[serde(default)]
+    pub(crate) version_req: Option<String>,
+}
+
+/// The current [`Limits`] of a module, shared between the module and the admin API, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub(crate) struct RuntimeLimits(Arc<RwLock<Limits>>);
+
+impl RuntimeLimits {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Self(Arc::new(RwLock::new(limits)))
+    }
+
+    /// The limits new connections use.
+    pub(crate) fn current(&self) -> Limits {
+        self.0.read().clone()
+    }
+
+    /// Applies the override and returns the new limits.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the first invalid value, in which case no value is changed.
+    pub(crate) fn update(&self, update: LimitsUpdate) -> Result<Limits, &'static str> {
+        if let Some(max_message_size) = update.max_message_size
+            && !(1..=MAX_MESSAGE_SIZE_LIMIT).contains(&max_message_size)
+        {
+            return Err("max_message_size must be in [1, 1 MiB]");
+        }
+        if let Some(session_lifetime) = update.session_lifetime
+            && (session_lifetime.is_zero() || session_lifetime > SESSION_LIFETIME_LIMIT)
+        {
+            return Err("session_lifetime must be in (0, 10 min]");
+        }
+        let version_req = update
+            .version_req
+            .map(|version_req| VersionReq::parse(&version_req))
+            .transpose()
+            .map_err(|_| "version_req must be a valid semver requirement")?;
+        let mut limits = self.0.write();
+        if let Some(max_message_size) = update.max_message_size {
+            limits.max_message_size = max_message_size;
+        }
+        if let Some(session_lifetime) = update.session_lifetime {
+            limits.session_lifetime = session_lifetime;
+        }
+        if let Some(version_req) = version_req {
+            limits.version_req = version_req;
+        }
+        Ok(limits.clone())
+    }
+}
+
+impl Limits {
+    /// The limits as reported to clients, together with the `max_batch_size` of the node.
+    pub(crate) fn params(&self, max_batch_size: usize) -> ModuleParams {
+        ModuleParams {
+            max_message_size: self.max_message_size,
+            session_lifetime_ms: u64::try_from(self.session_lifetime.as_millis())
+                .unwrap_or(u64::MAX),
+            version_req: self.version_req.to_string(),
+            max_batch_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> RuntimeLimits {
+        RuntimeLimits::new(Limits {
+            max_message_size: 1024,
+            session_lifetime: Duration::from_secs(30),
+            version_req: VersionReq::STAR,
+        })
+    }
+
+    #[test]
+    fn update_keeps_unset_values() {
+        let limits = limits();
+        let updated = limits
+            .update(LimitsUpdate {
+                max_message_size: Some(512),
+                ..Default::default()
+            })
+            .expect("valid update");
+        assert_eq!(updated.max_message_size, 512);
+        assert_eq!(updated.session_lifetime, Duration::from_secs(30));
+        assert_eq!(limits.current(), updated);
+    }
+
+    #[test]
+    fn update_rejects_values_above_limit() {
+        let limits = limits();
+        limits
+            .update(LimitsUpdate {
+                max_message_size: Some(256),
+                session_lifetime: Some(SESSION_LIFETIME_LIMIT + Duration::from_secs(1)),
+                ..Default::default()
+            })
+            .expect_err("session_lifetime too large");
+        limits
+            .update(LimitsUpdate {
+                max_message_size: Some(0),
+                ..Default::default()
+            })
+            .expect_err("max_message_size zero");
+        assert_eq!(limits.current().max_message_size, 1024, "nothing changed");
+    }
+}
@@ -1,18 +1,37 @@
 //! Secret manager interface for OPRF nodes.
 //!
 //! This module defines the [`SecretManager`] trait, which is used to
-//! persist and retrieve `OprfKeyMaterial`.
+//! persist and retrieve `OprfKeyMaterial`, the [`PartyIdBinding`] of the node, the history of the public keys and the epochs delivered to the epoch transition hook, and to sign responses with the wallet key of the node.
 //!
 //! Current `SecretManager` implementations:
 //! - Postgres
+//! - SQLite (behind the `sqlite` feature)
+//! - Google Cloud Secret Manager (behind the `gcp` feature)
+//! - Azure Key Vault (behind the `azure` feature)
+//! - `HashiCorp` Vault (behind the `vault` feature)
 
-use std::sync::Arc;
+use std::{collections::HashMap, fmt, num::NonZeroUsize, sync::Arc};
 
 use async_trait::async_trait;
-use oprf_types::{OprfKeyId, crypto::OprfKeyMaterial, service::NodeInformation};
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{OprfPublicKeyWithEpoch, ResponseSignature},
+    crypto::{OprfKeyMaterial, PartyId},
+    service::NodeInformation,
+};
 
+#[cfg(feature = "azure")]
+pub mod azure;
+#[cfg(feature = "gcp")]
+pub mod gcp;
 #[cfg(feature = "postgres")]
 pub mod postgres;
+#[cfg(any(feature = "gcp", feature = "azure", feature = "vault"))]
+pub mod remote;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "vault")]
+pub mod vault;
 
 /// Dynamic trait object for secret manager service.
 ///
@@ -36,6 +55,119 @@ pub enum SecretManagerError {
     Internal(#[from] eyre::Report),
 }
 
+/// The party id a node served with, together with the identity it was assigned to.
+///
+/// Persisted by the node on startup (see `OprfServiceBuilder::bind_party_id`), so a party id that changes between restarts is detected before the shares are used with the wrong Shamir index. Addresses are compared case-insensitively and therefore stored in lowercase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PartyIdBinding {
+    /// The wallet address of the node
+    pub address: String,
+    /// The party id assigned to `address`
+    pub party_id: PartyId,
+    /// The address of the `OprfKeyRegistry` that assigned the party id
+    pub oprf_key_registry: String,
+    /// The chain the `OprfKeyRegistry` is deployed on
+    pub chain_id: u64,
+}
+
+impl PartyIdBinding {
+    /// Creates a new binding, lowercasing the addresses.
+    #[must_use]
+    pub fn new(address: &str, party_id: PartyId, oprf_key_registry: &str, chain_id: u64) -> Self {
+        Self {
+            address: address.to_ascii_lowercase(),
+            party_id,
+            oprf_key_registry: oprf_key_registry.to_ascii_lowercase(),
+            chain_id,
+        }
+    }
+}
+
+impl fmt::Display for PartyIdBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "party id {} of {} at registry {} on chain {}",
+            self.party_id.0, self.address, self.oprf_key_registry, self.chain_id
+        )
+    }
+}
+
+/// A [`PartyIdBinding`] as stored in the `party_id_binding` table of the SQL secret managers.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+#[derive(Debug, sqlx::FromRow)]
+pub(crate) struct PartyIdBindingRow {
+    eth_address: String,
+    party_id: i32,
+    oprf_key_registry: String,
+    chain_id: i64,
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl TryFrom<PartyIdBindingRow> for PartyIdBinding {
+    type Error = eyre::Report;
+
+    fn try_from(row: PartyIdBindingRow) -> eyre::Result<Self> {
+        Ok(Self::new(
+            &row.eth_address,
+            PartyId(u16::try_from(row.party_id)?),
+            &row.oprf_key_registry,
+            u64::try_from(row.chain_id)?,
+        ))
+    }
+}
+
+/// An entry of the `public_key_history` table of the SQL secret managers.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+#[derive(Debug, sqlx::FromRow)]
+pub(crate) struct PublicKeyHistoryRow {
+    epoch: i64,
+    public_key: Vec<u8>,
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl TryFrom<PublicKeyHistoryRow> for OprfPublicKeyWithEpoch {
+    type Error = eyre::Report;
+
+    fn try_from(row: PublicKeyHistoryRow) -> eyre::Result<Self> {
+        use ark_serialize::CanonicalDeserialize as _;
+        use eyre::Context as _;
+        Ok(Self {
+            key: oprf_types::crypto::OprfPublicKey::deserialize_uncompressed_unchecked(
+                row.public_key.as_slice(),
+            )
+            .context("while deserializing public key from DB")?,
+            epoch: oprf_types::ShareEpoch::new(
+                u32::try_from(row.epoch).context("DB epoch value out of valid u32 range")?,
+            ),
+        })
+    }
+}
+
+/// An entry of the `delivered_epochs` table of the SQL secret managers.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+#[derive(Debug, sqlx::FromRow)]
+pub(crate) struct DeliveredEpochRow {
+    id: Vec<u8>,
+    epoch: i64,
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl TryFrom<DeliveredEpochRow> for (OprfKeyId, ShareEpoch) {
+    type Error = eyre::Report;
+
+    fn try_from(row: DeliveredEpochRow) -> eyre::Result<Self> {
+        use eyre::Context as _;
+        Ok((
+            OprfKeyId::from_le_slice(&row.id),
+            ShareEpoch::new(
+                u32::try_from(row.epoch).context("DB epoch value out of valid u32 range")?,
+            ),
+        ))
+    }
+}
+
 /// Trait that implementations of secret managers must provide.
 ///
 /// Handles persistence of `OprfKeyMaterial`.
@@ -44,6 +176,47 @@ pub trait SecretManager {
     /// Loads the [`NodeInformation`] for this node.
     async fn load_node_information(&self) -> eyre::Result<NodeInformation>;
 
+    /// Loads the [`PartyIdBinding`] stored with [`SecretManager::store_party_id_binding`], `None` if there is none yet.
+    async fn load_party_id_binding(&self) -> eyre::Result<Option<PartyIdBinding>>;
+
+    /// Stores the [`PartyIdBinding`] of this node, replacing the previous one.
+    async fn store_party_id_binding(&self, binding: &PartyIdBinding) -> eyre::Result<()>;
+
+    /// Loads the public keys recorded with [`SecretManager::record_public_key`] for the [`OprfKeyId`], oldest epoch first.
+    async fn load_public_key_history(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> eyre::Result<Vec<OprfPublicKeyWithEpoch>>;
+
+    /// Records the public key of a finalized epoch of the [`OprfKeyId`], replacing an entry of the same epoch. Afterwards, only the `retention` latest epochs of the key are kept.
+    async fn record_public_key(
+        &self,
+        oprf_key_id: OprfKeyId,
+        public_key: OprfPublicKeyWithEpoch,
+        retention: NonZeroUsize,
+    ) -> eyre::Result<()>;
+
+    /// Loads the last block the event watcher of the `contract` handled, stored with [`SecretManager::store_event_watcher_block`], `None` if there is none yet.
+    async fn load_event_watcher_block(&self, contract: &str) -> eyre::Result<Option<u64>>;
+
+    /// Stores the last block the event watcher of the `contract` handled, replacing the previous one.
+    async fn store_event_watcher_block(&self, contract: &str, block: u64) -> eyre::Result<()>;
+
+    /// Loads the epochs stored with [`SecretManager::store_delivered_epoch`], i.e., the epoch of every key delivered last to the epoch transition hook.
+    async fn load_delivered_epochs(&self) -> eyre::Result<HashMap<OprfKeyId, ShareEpoch>>;
+
+    /// Stores the epoch of the [`OprfKeyId`] delivered last to the epoch transition hook (see `OprfServiceBuilder::epoch_transition_hook`), replacing the previous one.
+    async fn store_delivered_epoch(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> eyre::Result<()>;
+
+    /// Signs the `message` of an [`OprfResponse`](oprf_types::api::OprfResponse) with the wallet key of this node as [EIP-191](https://eips.ethereum.org/EIPS/eip-191) message, see `OprfServiceBuilder::signed_responses`.
+    ///
+    /// Implementations holding the wallet key can use `ResponseSignature::sign` (see `oprf_types::signed_response`), remote signers must produce the same signature. Implementations without access to the wallet key fail.
+    async fn sign_response(&self, message: &[u8]) -> eyre::Result<ResponseSignature>;
+
     /// Returns the [`OprfKeyMaterial`] for the given [`OprfKeyId`] if it exists.
     async fn get_oprf_key_material(
         &self,
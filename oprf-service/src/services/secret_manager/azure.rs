@@ -0,0 +1,224 @@
+//! This module provides an implementation of [`SecretManager`](crate::secret_manager::SecretManager) that reads shares from Azure Key Vault.
+//!
+//! The secrets are written by the key-gen service (see its `azure` feature), see [`super::remote`] for their layout.
+//! The node sets its own `{prefix}-node-*` secrets, so its identity needs the `set` secret permission.
+//!
+//! Authentication uses managed identities, i.e., access tokens are fetched from the Azure Instance Metadata Service (IMDS).
+//! Throttled requests are retried after the `Retry-After` delay requested by Key Vault.
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use eyre::Context as _;
+use oprf_types::retry::RetryPolicy;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::Deserialize;
+use tracing::instrument;
+use zeroize::Zeroizing;
+
+use crate::secret_manager::remote::{
+    RemoteError, RemoteSecretManager, SecretStore, TokenCache, error_for_status,
+};
+
+/// The Key Vault REST API version used by this backend.
+const API_VERSION: &str = "7.4";
+
+/// The configuration for the Azure Key Vault backend.
+#[derive(Clone, Debug, Deserialize)]
+#[non_exhaustive]
+pub struct AzureConfig {
+    /// The URL of the vault, e.g., `https://my-vault.vault.azure.net`.
+    pub vault_url: String,
+    /// Prefix of the secrets. Must match the prefix of the key-gen service of this node.
+    #[serde(default = "AzureConfig::default_secret_prefix")]
+    pub secret_prefix: String,
+    /// The client ID of a user-assigned managed identity. Uses the system-assigned identity if not set.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// The Instance Metadata Service used to obtain managed-identity access tokens.
+    #[serde(default = "AzureConfig::default_imds_endpoint")]
+    pub imds_endpoint: String,
+    /// Timeout of a single request to Key Vault.
+    #[serde(default = "AzureConfig::default_request_timeout")]
+    #[serde(with = "humantime_serde")]
+    pub request_timeout: Duration,
+    /// Maximum number of attempts for a request that failed with a transient error.
+    #[serde(default = "AzureConfig::default_max_retries")]
+    pub max_retries: NonZeroUsize,
+    /// Delay before the first retry, doubled (with jitter) for every further retry. Throttled requests wait at least as long as requested by Key Vault.
+    #[serde(default = "AzureConfig::default_retry_delay")]
+    #[serde(with = "humantime_serde")]
+    pub retry_delay: Duration,
+}
+
+impl AzureConfig {
+    /// Default secret prefix (`oprf`)
+    fn default_secret_prefix() -> String {
+        "oprf".to_owned()
+    }
+
+    /// Default Instance Metadata Service endpoint
+    fn default_imds_endpoint() -> String {
+        "http://169.254.169.254".to_owned()
+    }
+
+    /// Default request timeout (`10 s`)
+    fn default_request_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    /// Default max retries
+    fn default_max_retries() -> NonZeroUsize {
+        NonZeroUsize::new(3).expect("Is non-zero")
+    }
+
+    /// Default retry delay (`1 s`)
+    fn default_retry_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Creates a config for the provided vault with default values.
+    #[must_use]
+    pub fn with_default_values(vault_url: String) -> Self {
+        Self {
+            vault_url,
+            secret_prefix: Self::default_secret_prefix(),
+            client_id: None,
+            imds_endpoint: Self::default_imds_endpoint(),
+            request_timeout: Self::default_request_timeout(),
+            max_retries: Self::default_max_retries(),
+            retry_delay: Self::default_retry_delay(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: SecretString,
+    // IMDS returns the lifetime as string
+    expires_in: String,
+}
+
+#[derive(Deserialize)]
+struct SecretBundle {
+    value: SecretString,
+}
+
+/// The Azure secret manager reading from Azure Key Vault.
+pub type AzureSecretManager = RemoteSecretManager<AzureSecretStore>;
+
+/// The Key Vault REST API as [`SecretStore`] of an [`AzureSecretManager`].
+pub struct AzureSecretStore {
+    client: reqwest::Client,
+    config: AzureConfig,
+    access_token: TokenCache,
+}
+
+impl RemoteSecretManager<AzureSecretStore> {
+    /// Initializes the `AzureSecretManager`.
+    ///
+    /// Checks that an access token can be obtained from the managed identity.
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client cannot be built or no access token can be obtained.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn init(config: AzureConfig) -> eyre::Result<Self> {
+        tracing::debug!(
+            "init Azure Key Vault secret manager for vault {} with prefix {}",
+            config.vault_url,
+            config.secret_prefix
+        );
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()?;
+        let secret_manager = Self::new(
+            AzureSecretStore {
+                client,
+                config: config.clone(),
+                access_token: TokenCache::new(),
+            },
+            config.secret_prefix,
+            RetryPolicy::new(config.max_retries.get(), config.retry_delay),
+        );
+        secret_manager
+            .with_retry("fetch access token", || {
+                secret_manager.store().access_token()
+            })
+            .await
+            .context("while fetching access token")?;
+        Ok(secret_manager)
+    }
+}
+
+impl AzureSecretStore {
+    fn secret_url(&self, secret_id: &str) -> String {
+        format!(
+            "{}/secrets/{secret_id}?api-version={API_VERSION}",
+            self.config.vault_url.trim_end_matches('/')
+        )
+    }
+
+    /// Returns a valid access token, refreshing it from IMDS if necessary.
+    async fn access_token(&self) -> Result<SecretString, RemoteError> {
+        self.access_token
+            .get(|| async {
+                let mut url = format!(
+                    "{}/metadata/identity/oauth2/token?api-version=2018-02-01&resource=https://vault.azure.net",
+                    self.config.imds_endpoint.trim_end_matches('/')
+                );
+                if let Some(client_id) = &self.config.client_id {
+                    url.push_str("&client_id=");
+                    url.push_str(client_id);
+                }
+                let response = self
+                    .client
+                    .get(url)
+                    .header("Metadata", "true")
+                    .send()
+                    .await?;
+                let response: TokenResponse = error_for_status(response).await?.json().await?;
+                let expires_in = response
+                    .expires_in
+                    .parse()
+                    .map_err(|_| RemoteError::InvalidPayload)?;
+                Ok((response.access_token, Duration::from_secs(expires_in)))
+            })
+            .await
+    }
+}
+
+impl SecretStore for AzureSecretStore {
+    /// Reads the current version of the secret, `None` if the secret does not exist.
+    async fn read(&self, secret_id: &str) -> Result<Option<Zeroizing<Vec<u8>>>, RemoteError> {
+        let response = self
+            .client
+            .get(self.secret_url(secret_id))
+            .bearer_auth(self.access_token().await?.expose_secret())
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response: SecretBundle = error_for_status(response).await?.json().await?;
+        Ok(Some(Zeroizing::new(
+            response.value.expose_secret().as_bytes().to_vec(),
+        )))
+    }
+
+    /// Sets a new version of the secret, creating the secret if it does not exist.
+    async fn write(&self, secret_id: &str, data: &[u8]) -> Result<(), RemoteError> {
+        let value = std::str::from_utf8(data).map_err(|_| RemoteError::InvalidPayload)?;
+        let response = self
+            .client
+            .put(self.secret_url(secret_id))
+            .bearer_auth(self.access_token().await?.expose_secret())
+            .json(&serde_json::json!({ "value": value }))
+            .send()
+            .await?;
+        error_for_status(response).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
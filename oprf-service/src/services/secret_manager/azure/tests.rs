@@ -0,0 +1,198 @@
+use std::{collections::HashMap, num::NonZeroU16, sync::Arc};
+
+use crate::secret_manager::{
+    PartyIdBinding, SecretManager, SecretManagerError,
+    azure::{AzureConfig, AzureSecretManager},
+};
+use ark_serialize::CanonicalSerialize;
+use axum::{Json, Router, extract::Path, http::StatusCode, routing::get};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    crypto::{OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use parking_lot::Mutex;
+use ruint::aliases::U160;
+
+fn to_base64<T: CanonicalSerialize>(t: &T) -> String {
+    let mut bytes = Vec::with_capacity(t.uncompressed_size());
+    t.serialize_uncompressed(&mut bytes).expect("Can serialize");
+    BASE64.encode(bytes)
+}
+
+/// Serves the provided secrets (as written by the key-gen service) like IMDS and the Key Vault API.
+async fn azure_secret_manager(
+    secrets: HashMap<String, serde_json::Value>,
+) -> eyre::Result<AzureSecretManager> {
+    let secrets = Arc::new(Mutex::new(secrets));
+    let written = Arc::clone(&secrets);
+    let router = Router::new()
+        .route(
+            "/metadata/identity/oauth2/token",
+            get(|| async {
+                Json(serde_json::json!({ "access_token": "token", "expires_in": "3600" }))
+            }),
+        )
+        .route(
+            "/secrets/{secret}",
+            get(move |Path(secret): Path<String>| async move {
+                let value = secrets
+                    .lock()
+                    .get(&secret)
+                    .map(|secret| serde_json::to_string(secret).expect("json"))
+                    .ok_or(StatusCode::NOT_FOUND)?;
+                Ok::<_, StatusCode>(Json(serde_json::json!({ "value": value })))
+            })
+            .put(
+                move |Path(secret): Path<String>, Json(body): Json<serde_json::Value>| async move {
+                    let value = body["value"]
+                        .as_str()
+                        .and_then(|value| serde_json::from_str(value).ok())
+                        .ok_or(StatusCode::BAD_REQUEST)?;
+                    written.lock().insert(secret, value);
+                    Ok::<_, StatusCode>(Json(serde_json::json!({})))
+                },
+            ),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(axum::serve(listener, router).into_future());
+    let mut config = AzureConfig::with_default_values(format!("http://{addr}"));
+    config.imds_endpoint = format!("http://{addr}");
+    AzureSecretManager::init(config).await
+}
+
+#[tokio::test]
+async fn load_node_information() -> eyre::Result<()> {
+    let should_address = "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc";
+    let secret_manager = azure_secret_manager(HashMap::from([(
+        "oprf-node-information".to_owned(),
+        serde_json::json!({ "eth_address": should_address, "party_id": 42, "threshold": 2 }),
+    )]))
+    .await?;
+    assert_eq!(
+        secret_manager.load_node_information().await?,
+        NodeInformation::new(
+            PartyId(42),
+            should_address.to_owned(),
+            NonZeroU16::new(2).expect("is non-zero")
+        )
+    );
+
+    let empty = azure_secret_manager(HashMap::new()).await?;
+    let report = empty
+        .load_node_information()
+        .await
+        .expect_err("should be an error");
+    assert_eq!(
+        report.to_string(),
+        "Cannot get node information from secret manager, maybe key-gen needs to start"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_oprf_key_material() -> eyre::Result<()> {
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let deleted_oprf_key_id = OprfKeyId::new(U160::from(43));
+    let removed_oprf_key_id = OprfKeyId::new(U160::from(44));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    let secret_manager = azure_secret_manager(HashMap::from([
+        (
+            format!("oprf-share-{oprf_key_id}"),
+            serde_json::json!({
+                "epoch": 42,
+                "share": to_base64(&share),
+                "public_key": to_base64(&public_key),
+                "deleted": false
+            }),
+        ),
+        (
+            format!("oprf-share-{deleted_oprf_key_id}"),
+            serde_json::json!({
+                "epoch": 42,
+                "share": null,
+                "public_key": to_base64(&public_key),
+                "deleted": true
+            }),
+        ),
+        // key-gen overwrites removed secrets with null
+        (
+            format!("oprf-share-{removed_oprf_key_id}"),
+            serde_json::Value::Null,
+        ),
+    ]))
+    .await?;
+
+    let key_material = secret_manager.get_oprf_key_material(oprf_key_id).await?;
+    assert_eq!(
+        ark_babyjubjub::Fr::from(key_material.share()),
+        ark_babyjubjub::Fr::from(share)
+    );
+    assert!(key_material.is_epoch(epoch));
+    assert_eq!(key_material.public_key(), public_key);
+
+    assert!(matches!(
+        secret_manager
+            .get_oprf_key_material(deleted_oprf_key_id)
+            .await,
+        Err(SecretManagerError::DeletedOprfKeyId(_))
+    ));
+    assert!(matches!(
+        secret_manager
+            .get_oprf_key_material(removed_oprf_key_id)
+            .await,
+        Err(SecretManagerError::UnknownOprfKeyId(_))
+    ));
+    assert!(matches!(
+        secret_manager
+            .get_oprf_key_material(OprfKeyId::new(U160::from(45)))
+            .await,
+        Err(SecretManagerError::UnknownOprfKeyId(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn persists_node_state() -> eyre::Result<()> {
+    let registry = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+    let secret_manager = azure_secret_manager(HashMap::new()).await?;
+    assert_eq!(secret_manager.load_party_id_binding().await?, None);
+    let binding = PartyIdBinding::new(
+        "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+        PartyId(1),
+        registry,
+        31337,
+    );
+    secret_manager.store_party_id_binding(&binding).await?;
+    assert_eq!(
+        secret_manager.load_party_id_binding().await?,
+        Some(binding),
+        "should create the secret"
+    );
+    let changed = PartyIdBinding::new(
+        "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+        PartyId(2),
+        registry,
+        31337,
+    );
+    secret_manager.store_party_id_binding(&changed).await?;
+    assert_eq!(
+        secret_manager.load_party_id_binding().await?,
+        Some(changed),
+        "should replace the secret"
+    );
+
+    secret_manager
+        .store_event_watcher_block(registry, 42)
+        .await?;
+    assert_eq!(
+        secret_manager.load_event_watcher_block(registry).await?,
+        Some(42)
+    );
+    Ok(())
+}
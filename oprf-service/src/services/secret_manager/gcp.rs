@@ -0,0 +1,255 @@
+//! This module provides an implementation of [`SecretManager`](crate::secret_manager::SecretManager) that reads shares from Google Cloud Secret Manager.
+//!
+//! The secrets are written by the key-gen service (see its `gcp` feature), see [`super::remote`] for their layout.
+//! The node adds versions to its own `{prefix}-node-*` secrets and creates them if necessary, so its service account needs the `secretmanager.secrets.create` and `secretmanager.versions.add` permissions for them.
+//!
+//! Authentication uses workload identity, i.e., access tokens are fetched from the GCE/GKE metadata server.
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use eyre::Context as _;
+use oprf_types::retry::RetryPolicy;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::Deserialize;
+use tracing::instrument;
+use zeroize::Zeroizing;
+
+use crate::secret_manager::remote::{
+    RemoteError, RemoteSecretManager, SecretStore, TokenCache, error_for_status,
+};
+
+/// The configuration for the Google Cloud Secret Manager backend.
+#[derive(Clone, Debug, Deserialize)]
+#[non_exhaustive]
+pub struct GcpConfig {
+    /// The GCP project that owns the secrets.
+    pub project_id: String,
+    /// Prefix of the secrets. Must match the prefix of the key-gen service of this node.
+    #[serde(default = "GcpConfig::default_secret_prefix")]
+    pub secret_prefix: String,
+    /// The Secret Manager API endpoint.
+    #[serde(default = "GcpConfig::default_endpoint")]
+    pub endpoint: String,
+    /// The metadata server used to obtain workload-identity access tokens.
+    #[serde(default = "GcpConfig::default_metadata_endpoint")]
+    pub metadata_endpoint: String,
+    /// Timeout of a single request to the Secret Manager API.
+    #[serde(default = "GcpConfig::default_request_timeout")]
+    #[serde(with = "humantime_serde")]
+    pub request_timeout: Duration,
+    /// Maximum number of attempts for a request that failed with a transient error.
+    #[serde(default = "GcpConfig::default_max_retries")]
+    pub max_retries: NonZeroUsize,
+    /// Delay before the first retry, doubled (with jitter) for every further retry.
+    #[serde(default = "GcpConfig::default_retry_delay")]
+    #[serde(with = "humantime_serde")]
+    pub retry_delay: Duration,
+}
+
+impl GcpConfig {
+    /// Default secret prefix (`oprf`)
+    fn default_secret_prefix() -> String {
+        "oprf".to_owned()
+    }
+
+    /// Default Secret Manager endpoint
+    fn default_endpoint() -> String {
+        "https://secretmanager.googleapis.com".to_owned()
+    }
+
+    /// Default metadata server
+    fn default_metadata_endpoint() -> String {
+        "http://metadata.google.internal".to_owned()
+    }
+
+    /// Default request timeout (`10 s`)
+    fn default_request_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    /// Default max retries
+    fn default_max_retries() -> NonZeroUsize {
+        NonZeroUsize::new(3).expect("Is non-zero")
+    }
+
+    /// Default retry delay (`1 s`)
+    fn default_retry_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Creates a config for the provided project with default values.
+    #[must_use]
+    pub fn with_default_values(project_id: String) -> Self {
+        Self {
+            project_id,
+            secret_prefix: Self::default_secret_prefix(),
+            endpoint: Self::default_endpoint(),
+            metadata_endpoint: Self::default_metadata_endpoint(),
+            request_timeout: Self::default_request_timeout(),
+            max_retries: Self::default_max_retries(),
+            retry_delay: Self::default_retry_delay(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: SecretString,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct AccessSecretVersionResponse {
+    payload: SecretPayload,
+}
+
+#[derive(Deserialize)]
+struct SecretPayload {
+    data: SecretString,
+}
+
+/// The GCP secret manager reading from Google Cloud Secret Manager.
+pub type GcpSecretManager = RemoteSecretManager<GcpSecretStore>;
+
+/// The Secret Manager REST API as [`SecretStore`] of a [`GcpSecretManager`].
+pub struct GcpSecretStore {
+    client: reqwest::Client,
+    config: GcpConfig,
+    access_token: TokenCache,
+}
+
+impl RemoteSecretManager<GcpSecretStore> {
+    /// Initializes the `GcpSecretManager`.
+    ///
+    /// Checks that an access token can be obtained from the metadata server.
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client cannot be built or no access token can be obtained.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn init(config: GcpConfig) -> eyre::Result<Self> {
+        tracing::debug!(
+            "init GCP secret manager for project {} with prefix {}",
+            config.project_id,
+            config.secret_prefix
+        );
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()?;
+        let secret_manager = Self::new(
+            GcpSecretStore {
+                client,
+                config: config.clone(),
+                access_token: TokenCache::new(),
+            },
+            config.secret_prefix,
+            RetryPolicy::new(config.max_retries.get(), config.retry_delay),
+        );
+        secret_manager
+            .with_retry("fetch access token", || {
+                secret_manager.store().access_token()
+            })
+            .await
+            .context("while fetching access token")?;
+        Ok(secret_manager)
+    }
+}
+
+impl GcpSecretStore {
+    fn secret_name(&self, secret_id: &str) -> String {
+        format!(
+            "{}/v1/projects/{}/secrets/{secret_id}",
+            self.config.endpoint.trim_end_matches('/'),
+            self.config.project_id
+        )
+    }
+
+    /// Returns a valid access token, refreshing it from the metadata server if necessary.
+    async fn access_token(&self) -> Result<SecretString, RemoteError> {
+        self.access_token
+            .get(|| async {
+                let response = self
+                    .client
+                    .get(format!(
+                        "{}/computeMetadata/v1/instance/service-accounts/default/token",
+                        self.config.metadata_endpoint.trim_end_matches('/')
+                    ))
+                    .header("Metadata-Flavor", "Google")
+                    .send()
+                    .await?;
+                let response: TokenResponse = error_for_status(response).await?.json().await?;
+                Ok((
+                    response.access_token,
+                    Duration::from_secs(response.expires_in),
+                ))
+            })
+            .await
+    }
+}
+
+impl SecretStore for GcpSecretStore {
+    /// Reads the latest version of the secret, `None` if the secret does not exist.
+    async fn read(&self, secret_id: &str) -> Result<Option<Zeroizing<Vec<u8>>>, RemoteError> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/versions/latest:access",
+                self.secret_name(secret_id)
+            ))
+            .bearer_auth(self.access_token().await?.expose_secret())
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response: AccessSecretVersionResponse =
+            error_for_status(response).await?.json().await?;
+        let data = BASE64
+            .decode(response.payload.data.expose_secret())
+            .map_err(|_| RemoteError::InvalidPayload)?;
+        Ok(Some(Zeroizing::new(data)))
+    }
+
+    /// Adds a new version to the secret, creating the secret if it does not exist.
+    async fn write(&self, secret_id: &str, data: &[u8]) -> Result<(), RemoteError> {
+        let data = Zeroizing::new(BASE64.encode(data));
+        let add_version = || async {
+            Ok::<_, RemoteError>(
+                self.client
+                    .post(format!("{}:addVersion", self.secret_name(secret_id)))
+                    .bearer_auth(self.access_token().await?.expose_secret())
+                    .json(&serde_json::json!({ "payload": { "data": data.as_str() } }))
+                    .send()
+                    .await?,
+            )
+        };
+        let response = add_version().await?;
+        let response = if response.status() == StatusCode::NOT_FOUND {
+            tracing::debug!("creating secret {secret_id}");
+            let created = self
+                .client
+                .post(format!(
+                    "{}/v1/projects/{}/secrets?secretId={secret_id}",
+                    self.config.endpoint.trim_end_matches('/'),
+                    self.config.project_id
+                ))
+                .bearer_auth(self.access_token().await?.expose_secret())
+                .json(&serde_json::json!({ "replication": { "automatic": {} } }))
+                .send()
+                .await?;
+            // someone else may have created the secret in the meantime
+            if created.status() != StatusCode::CONFLICT {
+                error_for_status(created).await?;
+            }
+            add_version().await?
+        } else {
+            response
+        };
+        error_for_status(response).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
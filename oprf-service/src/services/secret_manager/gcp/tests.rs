@@ -0,0 +1,221 @@
+use std::{collections::HashMap, num::NonZeroU16, sync::Arc};
+
+use crate::secret_manager::{
+    PartyIdBinding, SecretManager, SecretManagerError,
+    gcp::{GcpConfig, GcpSecretManager},
+};
+use ark_serialize::CanonicalSerialize;
+use axum::{
+    Json, Router,
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::{get, post},
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    crypto::{OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use parking_lot::Mutex;
+use ruint::aliases::U160;
+
+fn to_base64<T: CanonicalSerialize>(t: &T) -> String {
+    let mut bytes = Vec::with_capacity(t.uncompressed_size());
+    t.serialize_uncompressed(&mut bytes).expect("Can serialize");
+    BASE64.encode(bytes)
+}
+
+/// Serves the provided secrets (as written by the key-gen service) like the metadata server and the Secret Manager API.
+///
+/// Created secrets without versions are stored as `null`.
+async fn gcp_secret_manager(
+    secrets: HashMap<String, serde_json::Value>,
+) -> eyre::Result<GcpSecretManager> {
+    let secrets = Arc::new(Mutex::new(secrets));
+    let created = Arc::clone(&secrets);
+    let added = Arc::clone(&secrets);
+    let router = Router::new()
+        .route(
+            "/computeMetadata/v1/instance/service-accounts/default/token",
+            get(|| async {
+                Json(serde_json::json!({ "access_token": "token", "expires_in": 3600 }))
+            }),
+        )
+        .route(
+            "/v1/projects/{project}/secrets/{secret}/versions/latest:access",
+            get(
+                move |Path((_, secret)): Path<(String, String)>| async move {
+                    let data = secrets
+                        .lock()
+                        .get(&secret)
+                        .map(|secret| BASE64.encode(serde_json::to_vec(secret).expect("json")))
+                        .ok_or(StatusCode::NOT_FOUND)?;
+                    Ok::<_, StatusCode>(Json(serde_json::json!({ "payload": { "data": data } })))
+                },
+            ),
+        )
+        .route(
+            "/v1/projects/{project}/secrets",
+            post(
+                move |Query(query): Query<HashMap<String, String>>| async move {
+                    let secret = query.get("secretId").ok_or(StatusCode::BAD_REQUEST)?;
+                    let mut secrets = created.lock();
+                    if secrets.contains_key(secret) {
+                        return Err(StatusCode::CONFLICT);
+                    }
+                    secrets.insert(secret.clone(), serde_json::Value::Null);
+                    Ok(Json(serde_json::json!({})))
+                },
+            ),
+        )
+        .route(
+            "/v1/projects/{project}/secrets/{secret}",
+            post(
+                move |Path((_, secret)): Path<(String, String)>,
+                      Json(body): Json<serde_json::Value>| async move {
+                    let secret = secret
+                        .strip_suffix(":addVersion")
+                        .ok_or(StatusCode::NOT_FOUND)?;
+                    let value = body["payload"]["data"]
+                        .as_str()
+                        .and_then(|data| BASE64.decode(data).ok())
+                        .and_then(|data| serde_json::from_slice(&data).ok())
+                        .ok_or(StatusCode::BAD_REQUEST)?;
+                    let mut secrets = added.lock();
+                    let stored = secrets.get_mut(secret).ok_or(StatusCode::NOT_FOUND)?;
+                    *stored = value;
+                    Ok::<_, StatusCode>(Json(serde_json::json!({})))
+                },
+            ),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(axum::serve(listener, router).into_future());
+    let mut config = GcpConfig::with_default_values("test-project".to_owned());
+    config.endpoint = format!("http://{addr}");
+    config.metadata_endpoint = format!("http://{addr}");
+    GcpSecretManager::init(config).await
+}
+
+#[tokio::test]
+async fn load_node_information() -> eyre::Result<()> {
+    let should_address = "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc";
+    let secret_manager = gcp_secret_manager(HashMap::from([(
+        "oprf-node-information".to_owned(),
+        serde_json::json!({ "eth_address": should_address, "party_id": 42, "threshold": 2 }),
+    )]))
+    .await?;
+    assert_eq!(
+        secret_manager.load_node_information().await?,
+        NodeInformation::new(
+            PartyId(42),
+            should_address.to_owned(),
+            NonZeroU16::new(2).expect("is non-zero")
+        )
+    );
+
+    let empty = gcp_secret_manager(HashMap::new()).await?;
+    let report = empty
+        .load_node_information()
+        .await
+        .expect_err("should be an error");
+    assert_eq!(
+        report.to_string(),
+        "Cannot get node information from secret manager, maybe key-gen needs to start"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_oprf_key_material() -> eyre::Result<()> {
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let deleted_oprf_key_id = OprfKeyId::new(U160::from(43));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    let secret_manager = gcp_secret_manager(HashMap::from([
+        (
+            format!("oprf-share-{oprf_key_id}"),
+            serde_json::json!({
+                "epoch": 42,
+                "share": to_base64(&share),
+                "public_key": to_base64(&public_key),
+                "deleted": false
+            }),
+        ),
+        (
+            format!("oprf-share-{deleted_oprf_key_id}"),
+            serde_json::json!({
+                "epoch": 42,
+                "share": null,
+                "public_key": to_base64(&public_key),
+                "deleted": true
+            }),
+        ),
+    ]))
+    .await?;
+
+    let key_material = secret_manager.get_oprf_key_material(oprf_key_id).await?;
+    assert_eq!(
+        ark_babyjubjub::Fr::from(key_material.share()),
+        ark_babyjubjub::Fr::from(share)
+    );
+    assert!(key_material.is_epoch(epoch));
+    assert_eq!(key_material.public_key(), public_key);
+
+    assert!(matches!(
+        secret_manager
+            .get_oprf_key_material(deleted_oprf_key_id)
+            .await,
+        Err(SecretManagerError::DeletedOprfKeyId(_))
+    ));
+    assert!(matches!(
+        secret_manager
+            .get_oprf_key_material(OprfKeyId::new(U160::from(44)))
+            .await,
+        Err(SecretManagerError::UnknownOprfKeyId(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn persists_node_state() -> eyre::Result<()> {
+    let registry = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+    let secret_manager = gcp_secret_manager(HashMap::new()).await?;
+    assert_eq!(secret_manager.load_party_id_binding().await?, None);
+    let binding = PartyIdBinding::new(
+        "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+        PartyId(1),
+        registry,
+        31337,
+    );
+    secret_manager.store_party_id_binding(&binding).await?;
+    assert_eq!(
+        secret_manager.load_party_id_binding().await?,
+        Some(binding),
+        "should create the secret"
+    );
+    let changed = PartyIdBinding::new(
+        "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+        PartyId(2),
+        registry,
+        31337,
+    );
+    secret_manager.store_party_id_binding(&changed).await?;
+    assert_eq!(
+        secret_manager.load_party_id_binding().await?,
+        Some(changed),
+        "should replace the secret"
+    );
+
+    secret_manager
+        .store_event_watcher_block(registry, 42)
+        .await?;
+    assert_eq!(
+        secret_manager.load_event_watcher_block(registry).await?,
+        Some(42)
+    );
+    Ok(())
+}
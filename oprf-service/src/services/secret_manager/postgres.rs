@@ -1,32 +1,39 @@
 //! This module provides an implementation of [`SecretManager`] using a Postgres database to store shares.
 //!
-//! Additionally, fetches the node-provider's Ethereum address from the DB.
+//! Additionally, fetches the node-provider's Ethereum address from the DB and persists the [`PartyIdBinding`] of the node (see `OprfServiceBuilder::bind_party_id`), which requires write access to the `party_id_binding` table.
 
-use std::{num::NonZeroUsize, time::Duration};
+use std::{
+    collections::HashMap,
+    num::{NonZeroU16, NonZeroUsize},
+};
 
-use ark_serialize::CanonicalDeserialize;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize as _};
 use async_trait::async_trait;
-use backon::{BackoffBuilder as _, ConstantBackoff, ConstantBuilder, Retryable as _};
+use backon::Retryable as _;
 use eyre::Context as _;
 use nodes_common::postgres::{CreateSchema, PostgresConfig};
 use oprf_core::ddlog_equality::shamir::DLogShareShamir;
 use oprf_types::{
     OprfKeyId, ShareEpoch,
+    api::{OprfPublicKeyWithEpoch, ResponseSignature},
     crypto::{OprfKeyMaterial, OprfPublicKey},
+    retry::RetryPolicy,
     service::NodeInformation,
 };
 use sqlx::PgPool;
 use tracing::instrument;
 use zeroize::ZeroizeOnDrop;
 
-use crate::secret_manager::{SecretManager, SecretManagerError};
+use crate::secret_manager::{
+    DeliveredEpochRow, PartyIdBinding, PartyIdBindingRow, PublicKeyHistoryRow, SecretManager,
+    SecretManagerError,
+};
 
 /// The postgres secret manager wrapping a `PgPool`.
 #[derive(Debug)]
 pub struct PostgresSecretManager {
     pool: PgPool,
-    max_retries: NonZeroUsize,
-    retry_delay: Duration,
+    retry_policy: RetryPolicy,
 }
 
 #[derive(Debug, sqlx::FromRow, ZeroizeOnDrop)]
@@ -36,6 +43,7 @@ struct ShareRow {
     epoch: i64,
     public_key: Vec<u8>,
     deleted: bool,
+    threshold: Option<i32>,
 }
 
 impl PostgresSecretManager {
@@ -44,8 +52,7 @@ impl PostgresSecretManager {
     /// Connects to the Postgres database using the provided configuration. This version does **not** run migrations;
     /// it assumes the database is already set up.
     ///
-    /// Stores the max retries and retry delay from the configuration for
-    /// use in future database operations.
+    /// Retries future database operations with exponential backoff, starting at the retry delay of the configuration, up to its max retries.
     ///
     /// # Errors
     /// Returns an error if the connection to the database fails.
@@ -59,8 +66,7 @@ impl PostgresSecretManager {
         // TODO do we need to check version of the DB to fast crash if migrations don't match?
         Ok(Self {
             pool,
-            max_retries: config.max_retries,
-            retry_delay: config.retry_delay,
+            retry_policy: RetryPolicy::new(config.max_retries.get(), config.retry_delay),
         })
     }
 }
@@ -75,7 +81,7 @@ impl SecretManager for PostgresSecretManager {
             )
             .fetch_optional(&self.pool)
         })
-        .retry(self.backoff_strategy())
+        .retry(self.retry_policy.backoff())
         .sleep(tokio::time::sleep)
         .when(is_retryable_error)
         .notify(|err, duration| tracing::warn!(%err, "retrying load address after {duration:?}"))
@@ -86,6 +92,241 @@ impl SecretManager for PostgresSecretManager {
         Ok(node_information)
     }
 
+    #[instrument(level = "debug", skip_all)]
+    async fn load_party_id_binding(&self) -> eyre::Result<Option<PartyIdBinding>> {
+        let row: Option<PartyIdBindingRow> = (|| {
+            sqlx::query_as(
+                "SELECT eth_address,party_id,oprf_key_registry,chain_id FROM party_id_binding WHERE id = TRUE",
+            )
+            .fetch_optional(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying load party id binding after {duration:?}");
+        })
+        .await
+        .context("while loading party id binding")?;
+        row.map(PartyIdBinding::try_from).transpose()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_party_id_binding(&self, binding: &PartyIdBinding) -> eyre::Result<()> {
+        let chain_id = i64::try_from(binding.chain_id).context("chain id out of range")?;
+        (|| {
+            sqlx::query(
+                "
+                    INSERT INTO party_id_binding (id, eth_address, party_id, oprf_key_registry, chain_id)
+                    VALUES (TRUE, $1, $2, $3, $4)
+                    ON CONFLICT (id)
+                    DO UPDATE SET
+                        eth_address = excluded.eth_address,
+                        party_id = excluded.party_id,
+                        oprf_key_registry = excluded.oprf_key_registry,
+                        chain_id = excluded.chain_id
+                ",
+            )
+            .bind(&binding.address)
+            .bind(i32::from(binding.party_id.into_inner()))
+            .bind(&binding.oprf_key_registry)
+            .bind(chain_id)
+            .execute(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying store party id binding after {duration:?}");
+        })
+        .await
+        .context("while storing party id binding")?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_public_key_history(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> eyre::Result<Vec<OprfPublicKeyWithEpoch>> {
+        let rows: Vec<PublicKeyHistoryRow> = (|| {
+            sqlx::query_as(
+                "SELECT epoch,public_key FROM public_key_history WHERE id = $1 ORDER BY epoch",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .fetch_all(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying load public key history for {oprf_key_id} after {duration:?}");
+        })
+        .await
+        .context("while loading public key history")?;
+        rows.into_iter()
+            .map(OprfPublicKeyWithEpoch::try_from)
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_public_key(
+        &self,
+        oprf_key_id: OprfKeyId,
+        public_key: OprfPublicKeyWithEpoch,
+        retention: NonZeroUsize,
+    ) -> eyre::Result<()> {
+        let mut public_key_bytes = Vec::with_capacity(public_key.key.uncompressed_size());
+        public_key
+            .key
+            .serialize_uncompressed(&mut public_key_bytes)
+            .context("while serializing public key")?;
+        let retention = i64::try_from(retention.get()).unwrap_or(i64::MAX);
+        (|| async {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(
+                "
+                    INSERT INTO public_key_history (id, epoch, public_key)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT (id, epoch)
+                    DO UPDATE SET public_key = excluded.public_key
+                ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(i64::from(public_key.epoch.into_inner()))
+            .bind(&public_key_bytes)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+                "
+                    DELETE FROM public_key_history
+                    WHERE id = $1 AND epoch NOT IN (
+                        SELECT epoch FROM public_key_history
+                        WHERE id = $1
+                        ORDER BY epoch DESC
+                        LIMIT $2
+                    )
+                ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(retention)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying record public key for {oprf_key_id} after {duration:?}");
+        })
+        .await
+        .context("while recording public key")?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_event_watcher_block(&self, contract: &str) -> eyre::Result<Option<u64>> {
+        let block: Option<i64> = (|| {
+            sqlx::query_scalar("SELECT block FROM event_watcher_block WHERE contract = $1")
+                .bind(contract)
+                .fetch_optional(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying load event watcher block of {contract} after {duration:?}");
+        })
+        .await
+        .context("while loading event watcher block")?;
+        block
+            .map(|block| u64::try_from(block).context("DB block value out of valid u64 range"))
+            .transpose()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_event_watcher_block(&self, contract: &str, block: u64) -> eyre::Result<()> {
+        let block = i64::try_from(block).context("block out of range")?;
+        (|| {
+            sqlx::query(
+                "
+                    INSERT INTO event_watcher_block (contract, block)
+                    VALUES ($1, $2)
+                    ON CONFLICT (contract)
+                    DO UPDATE SET block = excluded.block, updated_at = now()
+                ",
+            )
+            .bind(contract)
+            .bind(block)
+            .execute(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying store event watcher block of {contract} after {duration:?}");
+        })
+        .await
+        .context("while storing event watcher block")?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_delivered_epochs(&self) -> eyre::Result<HashMap<OprfKeyId, ShareEpoch>> {
+        let rows: Vec<DeliveredEpochRow> =
+            (|| sqlx::query_as("SELECT id,epoch FROM delivered_epochs").fetch_all(&self.pool))
+                .retry(self.retry_policy.backoff())
+                .sleep(tokio::time::sleep)
+                .when(is_retryable_error)
+                .notify(|err, duration| {
+                    tracing::warn!(%err, "retrying load delivered epochs after {duration:?}");
+                })
+                .await
+                .context("while loading delivered epochs")?;
+        rows.into_iter()
+            .map(<(OprfKeyId, ShareEpoch)>::try_from)
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_delivered_epoch(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> eyre::Result<()> {
+        (|| {
+            sqlx::query(
+                "
+                    INSERT INTO delivered_epochs (id, epoch)
+                    VALUES ($1, $2)
+                    ON CONFLICT (id)
+                    DO UPDATE SET epoch = excluded.epoch, updated_at = now()
+                ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(i64::from(epoch.into_inner()))
+            .execute(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying store delivered epoch of {oprf_key_id} after {duration:?}");
+        })
+        .await
+        .context("while storing delivered epoch")?;
+        Ok(())
+    }
+
+    async fn sign_response(&self, message: &[u8]) -> eyre::Result<ResponseSignature> {
+        // the wallet key is not stored in the database
+        eyre::bail!(
+            "Postgres secret manager cannot sign responses ({} bytes)",
+            message.len()
+        )
+    }
+
     #[instrument(level = "debug", skip_all)]
     async fn get_oprf_key_material(
         &self,
@@ -99,7 +340,8 @@ impl SecretManager for PostgresSecretManager {
                         share,
                         epoch,
                         deleted,
-                        public_key
+                        public_key,
+                        threshold
                     FROM shares
                     WHERE id = $1
                 ",
@@ -107,7 +349,7 @@ impl SecretManager for PostgresSecretManager {
             .bind(oprf_key_id.to_le_bytes())
             .fetch_optional(&self.pool)
         })
-        .retry(self.backoff_strategy())
+        .retry(self.retry_policy.backoff())
         .sleep(tokio::time::sleep)
         .when(is_retryable_error)
         .notify(|err, duration| {
@@ -131,15 +373,7 @@ impl SecretManager for PostgresSecretManager {
     }
 }
 
-impl PostgresSecretManager {
-    #[inline]
-    fn backoff_strategy(&self) -> ConstantBackoff {
-        ConstantBuilder::new()
-            .with_delay(self.retry_delay)
-            .with_max_times(self.max_retries.get())
-            .build()
-    }
-}
+impl PostgresSecretManager {}
 
 #[inline]
 fn is_retryable_error(e: &sqlx::Error) -> bool {
@@ -157,8 +391,8 @@ fn is_retryable_error(e: &sqlx::Error) -> bool {
 #[inline]
 pub(crate) fn from_db_ark_deserialize_uncompressed<T: CanonicalDeserialize>(
     b: impl AsRef<[u8]>,
-) -> T {
-    T::deserialize_uncompressed_unchecked(b.as_ref()).expect("DB is sane")
+) -> eyre::Result<T> {
+    T::deserialize_uncompressed_unchecked(b.as_ref()).context("while deserializing value from DB")
 }
 
 /// Converts a row from the DB to an [`OprfKeyId`] and an associated [`OprfKeyMaterial`].
@@ -172,14 +406,22 @@ fn db_row_into_key_material(
         &row.share.as_ref().ok_or_else(|| {
             SecretManagerError::Internal(eyre::eyre!("share column is NONE for non deleted row"))
         })?,
-    );
+    )?;
     let epoch = ShareEpoch::new(
         row.epoch
             .try_into()
-            .expect("DB epoch value out of valid u32 range"),
+            .context("DB epoch value out of valid u32 range")?,
     );
-    let oprf_public_key = from_db_ark_deserialize_uncompressed::<OprfPublicKey>(&row.public_key);
-    Ok((id, OprfKeyMaterial::new(share, oprf_public_key, epoch)))
+    let oprf_public_key = from_db_ark_deserialize_uncompressed::<OprfPublicKey>(&row.public_key)?;
+    let key_material = OprfKeyMaterial::new(share, oprf_public_key, epoch);
+    let Some(threshold) = row.threshold else {
+        return Ok((id, key_material));
+    };
+    let threshold = u16::try_from(threshold)
+        .ok()
+        .and_then(NonZeroU16::new)
+        .ok_or_else(|| eyre::eyre!("DB threshold value out of valid range"))?;
+    Ok((id, key_material.with_threshold(threshold)))
 }
 
 #[cfg(test)]
@@ -0,0 +1,454 @@
+//! This module provides an implementation of [`SecretManager`] that reads shares from a remote secret store.
+//!
+//! Secret stores like Google Cloud Secret Manager ([`super::gcp`]), Azure Key Vault ([`super::azure`])
+//! and `HashiCorp` Vault ([`super::vault`]) are written by the key-gen service (see its `gcp`, `azure`
+//! and `vault` features), the OPRF node reads them:
+//! - `{prefix}-node-information` holds the node-provider's Ethereum address, party ID and threshold.
+//! - `{prefix}-share-{oprf_key_id}` holds the share, epoch and public key of one OPRF key.
+//!
+//! The node itself writes its state, which requires write access to these secrets:
+//! - `{prefix}-node-party-id-binding` holds the [`PartyIdBinding`] of the node.
+//! - `{prefix}-node-public-keys-{oprf_key_id}` holds the public key history of one OPRF key.
+//! - `{prefix}-node-event-watcher-block-{contract}` holds the last block the event watcher of the `contract` handled.
+//! - `{prefix}-node-delivered-epochs` holds the epoch of every key delivered last to the epoch transition hook. Replicas of the same node may overwrite each other's updates, which only leads to transitions being delivered again.
+//!
+//! Secrets are JSON documents. A secret with the value `null` is treated like a missing secret.
+
+use std::{
+    collections::HashMap,
+    num::{NonZeroU16, NonZeroUsize},
+    time::Duration,
+};
+
+use ark_serialize::CanonicalDeserialize;
+use async_trait::async_trait;
+use backon::Retryable as _;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use eyre::Context as _;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{OprfPublicKeyWithEpoch, ResponseSignature},
+    crypto::{OprfKeyMaterial, OprfPublicKey, PartyId},
+    retry::RetryPolicy,
+    service::NodeInformation,
+};
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Mutex, time::Instant};
+use tracing::instrument;
+use zeroize::Zeroizing;
+
+use crate::secret_manager::{PartyIdBinding, SecretManager, SecretManagerError};
+
+pub(crate) use store::{RemoteError, SecretStore};
+
+mod store {
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+    use zeroize::Zeroizing;
+
+    /// Errors of a [`SecretStore`].
+    #[derive(Debug, thiserror::Error)]
+    pub enum RemoteError {
+        #[error(transparent)]
+        Http(#[from] reqwest::Error),
+        #[error("secret store returned {status}: {body}")]
+        Status {
+            status: StatusCode,
+            body: String,
+            retry_after: Option<Duration>,
+        },
+        #[error("secret payload is not valid")]
+        InvalidPayload,
+    }
+
+    /// A remote key-value store for secrets. Sealed, implemented by the backend modules.
+    pub trait SecretStore: Send + Sync {
+        /// Reads the latest value of the secret, `None` if the secret does not exist.
+        fn read(
+            &self,
+            secret_id: &str,
+        ) -> impl Future<Output = Result<Option<Zeroizing<Vec<u8>>>, RemoteError>> + Send;
+
+        /// Stores a new value for the secret, creating the secret if necessary.
+        fn write(
+            &self,
+            secret_id: &str,
+            data: &[u8],
+        ) -> impl Future<Output = Result<(), RemoteError>> + Send;
+    }
+}
+
+/// A cached OAuth access token, e.g., from a workload or managed identity.
+pub(crate) struct TokenCache(Mutex<Option<(SecretString, Instant)>>);
+
+impl TokenCache {
+    pub(crate) fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    /// Returns the cached token, or fetches a new one with `fetch`, which returns the token and its lifetime.
+    pub(crate) async fn get<F, Fut>(&self, fetch: F) -> Result<SecretString, RemoteError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(SecretString, Duration), RemoteError>>,
+    {
+        let mut cached = self.0.lock().await;
+        if let Some((token, refresh_at)) = cached.as_ref()
+            && *refresh_at > Instant::now()
+        {
+            return Ok(token.clone());
+        }
+        let (token, expires_in) = fetch().await?;
+        // refresh a minute before the token expires
+        let refresh_at = Instant::now() + expires_in.saturating_sub(Duration::from_mins(1));
+        *cached = Some((token.clone(), refresh_at));
+        Ok(token)
+    }
+}
+
+/// Returns the response if it is successful, a [`RemoteError::Status`] otherwise.
+pub(crate) async fn error_for_status(
+    response: reqwest::Response,
+) -> Result<reqwest::Response, RemoteError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs);
+    let body = response.text().await.unwrap_or_default();
+    Err(RemoteError::Status {
+        status,
+        body,
+        retry_after,
+    })
+}
+
+/// The content of the `{prefix}-node-information` secret.
+#[derive(Deserialize)]
+struct StoredNodeInformation {
+    eth_address: String,
+    party_id: u16,
+    threshold: NonZeroU16,
+}
+
+/// The content of a `{prefix}-share-{oprf_key_id}` secret.
+#[derive(Deserialize)]
+struct StoredShare {
+    epoch: u32,
+    share: Option<SecretString>,
+    public_key: Option<String>,
+    deleted: bool,
+    #[serde(default)]
+    threshold: Option<NonZeroU16>,
+}
+
+/// The content of the `{prefix}-node-party-id-binding` secret.
+#[derive(Serialize, Deserialize)]
+struct StoredPartyIdBinding {
+    eth_address: String,
+    party_id: u16,
+    oprf_key_registry: String,
+    chain_id: u64,
+}
+
+/// An entry of the `{prefix}-node-delivered-epochs` secret.
+#[derive(Serialize, Deserialize)]
+struct StoredDeliveredEpoch {
+    oprf_key_id: OprfKeyId,
+    epoch: ShareEpoch,
+}
+
+/// The secret manager reading from a remote secret store.
+pub struct RemoteSecretManager<S> {
+    store: S,
+    secret_prefix: String,
+    retry_policy: RetryPolicy,
+}
+
+impl<S> std::fmt::Debug for RemoteSecretManager<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteSecretManager")
+            .field("secret_prefix", &self.secret_prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: SecretStore> RemoteSecretManager<S> {
+    pub(crate) fn new(store: S, secret_prefix: String, retry_policy: RetryPolicy) -> Self {
+        Self {
+            store,
+            secret_prefix,
+            retry_policy,
+        }
+    }
+
+    pub(crate) fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub(crate) async fn with_retry<F, Fut, T>(&self, op_name: &str, f: F) -> Result<T, RemoteError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, RemoteError>>,
+    {
+        f.retry(self.retry_policy.backoff())
+            .sleep(tokio::time::sleep)
+            .when(is_retryable_error)
+            // honor the store's throttling hints
+            .adjust(|err, delay| match err {
+                RemoteError::Status {
+                    retry_after: Some(retry_after),
+                    ..
+                } => delay.map(|delay| delay.max(*retry_after)),
+                _ => delay,
+            })
+            .notify(|err, duration| {
+                tracing::warn!(%err, "retrying {op_name} after {duration:?}");
+            })
+            .await
+    }
+
+    async fn load_json<T: for<'de> Deserialize<'de>>(
+        &self,
+        secret_id: &str,
+    ) -> eyre::Result<Option<T>> {
+        let Some(payload) = self
+            .with_retry(&format!("read {secret_id}"), || self.store.read(secret_id))
+            .await?
+        else {
+            return Ok(None);
+        };
+        serde_json::from_slice::<Option<T>>(&payload)
+            .with_context(|| format!("secret {secret_id} not sane"))
+    }
+
+    async fn store_json<T: Serialize>(&self, secret_id: &str, value: &T) -> eyre::Result<()> {
+        let payload = serde_json::to_vec(value).context("while serializing secret")?;
+        self.with_retry(&format!("write {secret_id}"), || {
+            self.store.write(secret_id, &payload)
+        })
+        .await?;
+        Ok(())
+    }
+
+    fn party_id_binding_secret(&self) -> String {
+        format!("{}-node-party-id-binding", self.secret_prefix)
+    }
+
+    fn public_keys_secret(&self, oprf_key_id: OprfKeyId) -> String {
+        format!("{}-node-public-keys-{oprf_key_id}", self.secret_prefix)
+    }
+
+    fn delivered_epochs_secret(&self) -> String {
+        format!("{}-node-delivered-epochs", self.secret_prefix)
+    }
+
+    fn event_watcher_block_secret(&self, contract: &str) -> String {
+        format!(
+            "{}-node-event-watcher-block-{}",
+            self.secret_prefix,
+            contract.to_ascii_lowercase()
+        )
+    }
+}
+
+#[async_trait]
+impl<S: SecretStore> SecretManager for RemoteSecretManager<S> {
+    #[instrument(level = "debug", skip_all)]
+    async fn load_node_information(&self) -> eyre::Result<NodeInformation> {
+        let node_information: StoredNodeInformation = self
+            .load_json(&format!("{}-node-information", self.secret_prefix))
+            .await?
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "Cannot get node information from secret manager, maybe key-gen needs to start"
+                )
+            })?;
+        Ok(NodeInformation::new(
+            PartyId(node_information.party_id),
+            node_information.eth_address,
+            node_information.threshold,
+        ))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_party_id_binding(&self) -> eyre::Result<Option<PartyIdBinding>> {
+        let stored: Option<StoredPartyIdBinding> = self
+            .load_json(&self.party_id_binding_secret())
+            .await
+            .context("while loading party id binding")?;
+        Ok(stored.map(|stored| {
+            PartyIdBinding::new(
+                &stored.eth_address,
+                PartyId(stored.party_id),
+                &stored.oprf_key_registry,
+                stored.chain_id,
+            )
+        }))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_party_id_binding(&self, binding: &PartyIdBinding) -> eyre::Result<()> {
+        let stored = StoredPartyIdBinding {
+            eth_address: binding.address.clone(),
+            party_id: binding.party_id.into_inner(),
+            oprf_key_registry: binding.oprf_key_registry.clone(),
+            chain_id: binding.chain_id,
+        };
+        self.store_json(&self.party_id_binding_secret(), &stored)
+            .await
+            .context("while storing party id binding")
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_public_key_history(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> eyre::Result<Vec<OprfPublicKeyWithEpoch>> {
+        Ok(self
+            .load_json(&self.public_keys_secret(oprf_key_id))
+            .await
+            .context("while loading public key history")?
+            .unwrap_or_default())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_public_key(
+        &self,
+        oprf_key_id: OprfKeyId,
+        public_key: OprfPublicKeyWithEpoch,
+        retention: NonZeroUsize,
+    ) -> eyre::Result<()> {
+        let mut history = self.load_public_key_history(oprf_key_id).await?;
+        history.retain(|recorded| recorded.epoch != public_key.epoch);
+        history.push(public_key);
+        history.sort_by_key(|recorded| recorded.epoch);
+        let outdated = history.len().saturating_sub(retention.get());
+        history.drain(..outdated);
+        self.store_json(&self.public_keys_secret(oprf_key_id), &history)
+            .await
+            .context("while recording public key")
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_event_watcher_block(&self, contract: &str) -> eyre::Result<Option<u64>> {
+        self.load_json(&self.event_watcher_block_secret(contract))
+            .await
+            .context("while loading event watcher block")
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_event_watcher_block(&self, contract: &str, block: u64) -> eyre::Result<()> {
+        self.store_json(&self.event_watcher_block_secret(contract), &block)
+            .await
+            .context("while storing event watcher block")
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_delivered_epochs(&self) -> eyre::Result<HashMap<OprfKeyId, ShareEpoch>> {
+        let stored: Vec<StoredDeliveredEpoch> = self
+            .load_json(&self.delivered_epochs_secret())
+            .await
+            .context("while loading delivered epochs")?
+            .unwrap_or_default();
+        Ok(stored
+            .into_iter()
+            .map(|stored| (stored.oprf_key_id, stored.epoch))
+            .collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_delivered_epoch(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> eyre::Result<()> {
+        let mut delivered = self.load_delivered_epochs().await?;
+        delivered.insert(oprf_key_id, epoch);
+        let mut stored = delivered
+            .into_iter()
+            .map(|(oprf_key_id, epoch)| StoredDeliveredEpoch { oprf_key_id, epoch })
+            .collect::<Vec<_>>();
+        stored.sort_by_key(|stored| stored.oprf_key_id);
+        self.store_json(&self.delivered_epochs_secret(), &stored)
+            .await
+            .context("while storing delivered epoch")
+    }
+
+    async fn sign_response(&self, message: &[u8]) -> eyre::Result<ResponseSignature> {
+        // the stores hold the shares, the wallet key is managed by the key-gen service
+        eyre::bail!(
+            "remote secret managers cannot sign responses ({} bytes)",
+            message.len()
+        )
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_oprf_key_material(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> Result<OprfKeyMaterial, SecretManagerError> {
+        let stored: Option<StoredShare> = self
+            .load_json(&format!("{}-share-{oprf_key_id}", self.secret_prefix))
+            .await
+            .context("while fetching share")?;
+        match stored {
+            Some(stored) if stored.deleted => {
+                tracing::trace!("requested deleted key-material");
+                Err(SecretManagerError::DeletedOprfKeyId(oprf_key_id))
+            }
+            Some(stored) => {
+                tracing::trace!("found key-material");
+                Ok(stored_share_into_key_material(&stored)?)
+            }
+            None => {
+                tracing::trace!("Cannot find share for requested key");
+                Err(SecretManagerError::UnknownOprfKeyId(oprf_key_id))
+            }
+        }
+    }
+}
+
+#[inline]
+fn is_retryable_error(e: &RemoteError) -> bool {
+    match e {
+        RemoteError::Http(err) => err.is_timeout() || err.is_connect() || err.is_request(),
+        RemoteError::Status { status, .. } => {
+            *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+        }
+        RemoteError::InvalidPayload => false,
+    }
+}
+
+/// Converts the stored secret to an [`OprfKeyMaterial`].
+fn stored_share_into_key_material(stored: &StoredShare) -> eyre::Result<OprfKeyMaterial> {
+    let share = stored
+        .share
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("share is missing for non deleted key"))?;
+    let share = Zeroizing::new(BASE64.decode(share.expose_secret())?);
+    let share = DLogShareShamir::deserialize_uncompressed_unchecked(share.as_slice())
+        .context("while deserializing share")?;
+    let public_key = BASE64.decode(
+        stored
+            .public_key
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("public key is missing for non deleted key"))?,
+    )?;
+    let public_key = OprfPublicKey::deserialize_uncompressed_unchecked(public_key.as_slice())
+        .context("while deserializing public key")?;
+    let key_material = OprfKeyMaterial::new(share, public_key, ShareEpoch::new(stored.epoch));
+    Ok(match stored.threshold {
+        Some(threshold) => key_material.with_threshold(threshold),
+        None => key_material,
+    })
+}
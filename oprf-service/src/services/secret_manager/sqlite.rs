@@ -0,0 +1,511 @@
+//! This module provides an implementation of [`SecretManager`] using a SQLite database file to store shares.
+//!
+//! The database file is created and migrated by the key-gen service (see its `sqlite` feature), the OPRF node only reads from it, except for its own state: the [`PartyIdBinding`] (see `OprfServiceBuilder::bind_party_id`), the public key history, the blocks of the event watchers and the epochs delivered to the epoch transition hook.
+//! Additionally, fetches the node-provider's Ethereum address from the DB.
+
+use std::{
+    collections::HashMap,
+    num::{NonZeroU16, NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize as _};
+use async_trait::async_trait;
+use backon::Retryable as _;
+use eyre::Context as _;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{OprfPublicKeyWithEpoch, ResponseSignature},
+    crypto::{OprfKeyMaterial, OprfPublicKey},
+    retry::RetryPolicy,
+    service::NodeInformation,
+};
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::Deserialize;
+use sqlx::{
+    SqlitePool,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
+use tracing::instrument;
+use zeroize::ZeroizeOnDrop;
+
+use crate::secret_manager::{
+    DeliveredEpochRow, PartyIdBinding, PartyIdBindingRow, PublicKeyHistoryRow, SecretManager,
+    SecretManagerError,
+};
+
+/// The configuration for the SQLite database file written by the key-gen service.
+#[derive(Clone, Debug, Deserialize)]
+#[non_exhaustive]
+pub struct SqliteConfig {
+    /// Path to the database file.
+    pub path: PathBuf,
+    /// Optional SQLCipher key. Must match the key used by the key-gen service. Treat this as a secret.
+    ///
+    /// Requires linking against SQLCipher (e.g. with the `bundled-sqlcipher` feature of `libsqlite3-sys`).
+    #[serde(default)]
+    pub encryption_key: Option<SecretString>,
+    /// Maximum number of connections in the connection pool.
+    #[serde(default = "SqliteConfig::default_max_connections")]
+    pub max_connections: NonZeroU32,
+    /// How long a statement waits for a lock held by the key-gen service before failing.
+    #[serde(default = "SqliteConfig::default_busy_timeout")]
+    #[serde(with = "humantime_serde")]
+    pub busy_timeout: Duration,
+    /// Maximum number of attempts for an operation that failed with a transient error.
+    #[serde(default = "SqliteConfig::default_max_retries")]
+    pub max_retries: NonZeroUsize,
+    /// Delay before the first retry, doubled (with jitter) for every further retry.
+    #[serde(default = "SqliteConfig::default_retry_delay")]
+    #[serde(with = "humantime_serde")]
+    pub retry_delay: Duration,
+}
+
+impl SqliteConfig {
+    /// Default max connections
+    fn default_max_connections() -> NonZeroU32 {
+        NonZeroU32::new(4).expect("Is non-zero")
+    }
+
+    /// Default busy timeout (`5 s`)
+    fn default_busy_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Default max retries
+    fn default_max_retries() -> NonZeroUsize {
+        NonZeroUsize::new(3).expect("Is non-zero")
+    }
+
+    /// Default retry delay (`1 s`)
+    fn default_retry_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Creates a config for the provided database file with default values and without encryption.
+    #[must_use]
+    pub fn with_default_values(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            encryption_key: None,
+            max_connections: Self::default_max_connections(),
+            busy_timeout: Self::default_busy_timeout(),
+            max_retries: Self::default_max_retries(),
+            retry_delay: Self::default_retry_delay(),
+        }
+    }
+}
+
+/// The SQLite secret manager wrapping a `SqlitePool`.
+#[derive(Debug)]
+pub struct SqliteSecretManager {
+    pool: SqlitePool,
+    retry_policy: RetryPolicy,
+}
+
+#[derive(Debug, sqlx::FromRow, ZeroizeOnDrop)]
+struct ShareRow {
+    share: Option<Vec<u8>>,
+    epoch: i64,
+    public_key: Vec<u8>,
+    deleted: bool,
+    threshold: Option<i32>,
+}
+
+impl SqliteSecretManager {
+    /// Initializes the `SqliteSecretManager`.
+    ///
+    /// Opens the existing SQLite database file from the provided configuration. This version does **not** create the file or run migrations;
+    /// it assumes the key-gen service already set up the database.
+    ///
+    /// # Errors
+    /// Returns an error if opening the database fails or if an encryption key is configured but the linked SQLite library is not SQLCipher.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn init(config: &SqliteConfig) -> eyre::Result<Self> {
+        tracing::debug!("init SqlitePool at: {}", config.path.display());
+        let mut options = SqliteConnectOptions::new()
+            .filename(&config.path)
+            .create_if_missing(false)
+            .busy_timeout(config.busy_timeout);
+        if let Some(key) = &config.encryption_key {
+            options = options.pragma(
+                "key",
+                format!("'{}'", key.expose_secret().replace('\'', "''")),
+            );
+        }
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections.get())
+            .connect_with(options)
+            .await
+            .context("while opening SQLite DB")?;
+        if config.encryption_key.is_some() {
+            let cipher_version: Option<String> = sqlx::query_scalar("PRAGMA cipher_version")
+                .fetch_optional(&pool)
+                .await?;
+            eyre::ensure!(
+                cipher_version.is_some(),
+                "encryption_key is set, but the linked SQLite library is not SQLCipher"
+            );
+        }
+        // we don't run migrations, we just read
+        Ok(Self {
+            pool,
+            retry_policy: RetryPolicy::new(config.max_retries.get(), config.retry_delay),
+        })
+    }
+}
+
+#[async_trait]
+impl SecretManager for SqliteSecretManager {
+    #[instrument(level = "debug", skip_all)]
+    async fn load_node_information(&self) -> eyre::Result<NodeInformation> {
+        let node_information: NodeInformation = (|| {
+            sqlx::query_as(
+                "SELECT eth_address,party_id,threshold FROM node_information WHERE id = TRUE",
+            )
+            .fetch_optional(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| tracing::warn!(%err, "retrying load address after {duration:?}"))
+        .await?
+        .ok_or_else(|| {
+            eyre::eyre!("Cannot get node information from DB, maybe key-gen needs to start")
+        })?;
+        Ok(node_information)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_party_id_binding(&self) -> eyre::Result<Option<PartyIdBinding>> {
+        let row: Option<PartyIdBindingRow> = (|| {
+            sqlx::query_as(
+                "SELECT eth_address,party_id,oprf_key_registry,chain_id FROM party_id_binding WHERE id = TRUE",
+            )
+            .fetch_optional(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying load party id binding after {duration:?}");
+        })
+        .await
+        .context("while loading party id binding")?;
+        row.map(PartyIdBinding::try_from).transpose()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_party_id_binding(&self, binding: &PartyIdBinding) -> eyre::Result<()> {
+        let chain_id = i64::try_from(binding.chain_id).context("chain id out of range")?;
+        (|| {
+            sqlx::query(
+                "
+                    INSERT INTO party_id_binding (id, eth_address, party_id, oprf_key_registry, chain_id)
+                    VALUES (TRUE, $1, $2, $3, $4)
+                    ON CONFLICT (id)
+                    DO UPDATE SET
+                        eth_address = excluded.eth_address,
+                        party_id = excluded.party_id,
+                        oprf_key_registry = excluded.oprf_key_registry,
+                        chain_id = excluded.chain_id
+                ",
+            )
+            .bind(&binding.address)
+            .bind(i32::from(binding.party_id.into_inner()))
+            .bind(&binding.oprf_key_registry)
+            .bind(chain_id)
+            .execute(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying store party id binding after {duration:?}");
+        })
+        .await
+        .context("while storing party id binding")?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_public_key_history(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> eyre::Result<Vec<OprfPublicKeyWithEpoch>> {
+        let rows: Vec<PublicKeyHistoryRow> = (|| {
+            sqlx::query_as(
+                "SELECT epoch,public_key FROM public_key_history WHERE id = $1 ORDER BY epoch",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .fetch_all(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying load public key history for {oprf_key_id} after {duration:?}");
+        })
+        .await
+        .context("while loading public key history")?;
+        rows.into_iter()
+            .map(OprfPublicKeyWithEpoch::try_from)
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_public_key(
+        &self,
+        oprf_key_id: OprfKeyId,
+        public_key: OprfPublicKeyWithEpoch,
+        retention: NonZeroUsize,
+    ) -> eyre::Result<()> {
+        let mut public_key_bytes = Vec::with_capacity(public_key.key.uncompressed_size());
+        public_key
+            .key
+            .serialize_uncompressed(&mut public_key_bytes)
+            .context("while serializing public key")?;
+        let retention = i64::try_from(retention.get()).unwrap_or(i64::MAX);
+        (|| async {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(
+                "
+                    INSERT INTO public_key_history (id, epoch, public_key)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT (id, epoch)
+                    DO UPDATE SET public_key = excluded.public_key
+                ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(i64::from(public_key.epoch.into_inner()))
+            .bind(&public_key_bytes)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+                "
+                    DELETE FROM public_key_history
+                    WHERE id = $1 AND epoch NOT IN (
+                        SELECT epoch FROM public_key_history
+                        WHERE id = $1
+                        ORDER BY epoch DESC
+                        LIMIT $2
+                    )
+                ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(retention)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying record public key for {oprf_key_id} after {duration:?}");
+        })
+        .await
+        .context("while recording public key")?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_event_watcher_block(&self, contract: &str) -> eyre::Result<Option<u64>> {
+        let block: Option<i64> = (|| {
+            sqlx::query_scalar("SELECT block FROM event_watcher_block WHERE contract = $1")
+                .bind(contract)
+                .fetch_optional(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying load event watcher block of {contract} after {duration:?}");
+        })
+        .await
+        .context("while loading event watcher block")?;
+        block
+            .map(|block| u64::try_from(block).context("DB block value out of valid u64 range"))
+            .transpose()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_event_watcher_block(&self, contract: &str, block: u64) -> eyre::Result<()> {
+        let block = i64::try_from(block).context("block out of range")?;
+        (|| {
+            sqlx::query(
+                "
+                    INSERT INTO event_watcher_block (contract, block)
+                    VALUES ($1, $2)
+                    ON CONFLICT (contract)
+                    DO UPDATE SET block = excluded.block, updated_at = CURRENT_TIMESTAMP
+                ",
+            )
+            .bind(contract)
+            .bind(block)
+            .execute(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying store event watcher block of {contract} after {duration:?}");
+        })
+        .await
+        .context("while storing event watcher block")?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn load_delivered_epochs(&self) -> eyre::Result<HashMap<OprfKeyId, ShareEpoch>> {
+        let rows: Vec<DeliveredEpochRow> =
+            (|| sqlx::query_as("SELECT id,epoch FROM delivered_epochs").fetch_all(&self.pool))
+                .retry(self.retry_policy.backoff())
+                .sleep(tokio::time::sleep)
+                .when(is_retryable_error)
+                .notify(|err, duration| {
+                    tracing::warn!(%err, "retrying load delivered epochs after {duration:?}");
+                })
+                .await
+                .context("while loading delivered epochs")?;
+        rows.into_iter()
+            .map(<(OprfKeyId, ShareEpoch)>::try_from)
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn store_delivered_epoch(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> eyre::Result<()> {
+        (|| {
+            sqlx::query(
+                "
+                    INSERT INTO delivered_epochs (id, epoch)
+                    VALUES ($1, $2)
+                    ON CONFLICT (id)
+                    DO UPDATE SET epoch = excluded.epoch, updated_at = CURRENT_TIMESTAMP
+                ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .bind(i64::from(epoch.into_inner()))
+            .execute(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying store delivered epoch of {oprf_key_id} after {duration:?}");
+        })
+        .await
+        .context("while storing delivered epoch")?;
+        Ok(())
+    }
+
+    async fn sign_response(&self, message: &[u8]) -> eyre::Result<ResponseSignature> {
+        // the wallet key is not stored in the database
+        eyre::bail!(
+            "SQLite secret manager cannot sign responses ({} bytes)",
+            message.len()
+        )
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_oprf_key_material(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> Result<OprfKeyMaterial, SecretManagerError> {
+        let maybe_row: Option<ShareRow> = (|| {
+            sqlx::query_as(
+                "
+                    SELECT
+                        share,
+                        epoch,
+                        deleted,
+                        public_key,
+                        threshold
+                    FROM shares
+                    WHERE id = $1
+                ",
+            )
+            .bind(oprf_key_id.to_le_bytes())
+            .fetch_optional(&self.pool)
+        })
+        .retry(self.retry_policy.backoff())
+        .sleep(tokio::time::sleep)
+        .when(is_retryable_error)
+        .notify(|err, duration| {
+            tracing::warn!(%err, "retrying get_oprf_key_material for {oprf_key_id} after {duration:?}");
+        })
+        .await
+        .context("while fetching previous share")?;
+        match maybe_row {
+            Some(row) if row.deleted => {
+                tracing::trace!("requested deleted key-material");
+                Err(SecretManagerError::DeletedOprfKeyId(oprf_key_id))
+            }
+            Some(row) => {
+                tracing::trace!("found key-material");
+                Ok(db_row_into_key_material(&row)?)
+            }
+            None => {
+                tracing::trace!("Cannot find share for requested key and epoch");
+                Err(SecretManagerError::UnknownOprfKeyId(oprf_key_id))
+            }
+        }
+    }
+}
+
+impl SqliteSecretManager {}
+
+#[inline]
+fn is_retryable_error(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::PoolTimedOut | sqlx::Error::Io(_) | sqlx::Error::WorkerCrashed => true,
+        // SQLITE_BUSY and SQLITE_LOCKED (and their extended codes), e.g. while key-gen holds the write lock
+        sqlx::Error::Database(db_err) => matches!(
+            db_err.code().as_deref(),
+            Some("5" | "6" | "261" | "262" | "517")
+        ),
+        _ => false,
+    }
+}
+
+/// Converts a row from the DB to an [`OprfKeyMaterial`].
+///
+/// This method assumes that the shares column is populated, otherwise it will return an error.
+fn db_row_into_key_material(row: &ShareRow) -> Result<OprfKeyMaterial, SecretManagerError> {
+    let share = from_db_ark_deserialize_uncompressed::<DLogShareShamir>(
+        row.share.as_ref().ok_or_else(|| {
+            SecretManagerError::Internal(eyre::eyre!("share column is NULL for non deleted row"))
+        })?,
+    )?;
+    let epoch = ShareEpoch::new(
+        row.epoch
+            .try_into()
+            .context("DB epoch value out of valid u32 range")?,
+    );
+    let oprf_public_key = from_db_ark_deserialize_uncompressed::<OprfPublicKey>(&row.public_key)?;
+    let key_material = OprfKeyMaterial::new(share, oprf_public_key, epoch);
+    let Some(threshold) = row.threshold else {
+        return Ok(key_material);
+    };
+    let threshold = u16::try_from(threshold)
+        .ok()
+        .and_then(NonZeroU16::new)
+        .ok_or_else(|| eyre::eyre!("DB threshold value out of valid range"))?;
+    Ok(key_material.with_threshold(threshold))
+}
+
+#[inline]
+fn from_db_ark_deserialize_uncompressed<T: CanonicalDeserialize>(
+    b: impl AsRef<[u8]>,
+) -> eyre::Result<T> {
+    T::deserialize_uncompressed_unchecked(b.as_ref()).context("while deserializing value from DB")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    num::{NonZeroU16, NonZeroUsize},
+    path::PathBuf,
+};
+
+use crate::secret_manager::{
+    PartyIdBinding, SecretManager, SecretManagerError,
+    sqlite::{SqliteConfig, SqliteSecretManager},
+};
+use ark_serialize::CanonicalSerialize;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::OprfPublicKeyWithEpoch,
+    crypto::{OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use ruint::aliases::U160;
+use sqlx::{SqlitePool, sqlite::SqliteConnectOptions};
+
+#[inline]
+fn to_db_ark_serialize_uncompressed<T: CanonicalSerialize>(t: &T) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(t.uncompressed_size());
+    t.serialize_uncompressed(&mut bytes).expect("Can serialize");
+    bytes
+}
+
+/// A database file in the temp dir that is removed (including the WAL files) on drop.
+struct TempDbFile(PathBuf);
+
+impl Drop for TempDbFile {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.0.clone().into_os_string();
+            path.push(suffix);
+            // the WAL files may not exist
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+/// Creates and migrates a DB file like the key-gen service does, and opens the secret manager on it.
+async fn sqlite_secret_manager() -> eyre::Result<(SqliteSecretManager, SqlitePool, TempDbFile)> {
+    let file = TempDbFile(
+        std::env::temp_dir().join(format!("oprf-service-{}.sqlite", uuid::Uuid::new_v4())),
+    );
+    let pool = SqlitePool::connect_with(
+        SqliteConnectOptions::new()
+            .filename(&file.0)
+            .create_if_missing(true),
+    )
+    .await?;
+    sqlx::migrate!("../oprf-key-gen/sqlite_migrations")
+        .run(&pool)
+        .await?;
+    let mgr = SqliteSecretManager::init(&SqliteConfig::with_default_values(&file.0)).await?;
+    Ok((mgr, pool, file))
+}
+
+#[tokio::test]
+async fn init_requires_existing_db() {
+    let config = SqliteConfig::with_default_values(
+        std::env::temp_dir().join(format!("oprf-service-{}.sqlite", uuid::Uuid::new_v4())),
+    );
+    let err = SqliteSecretManager::init(&config)
+        .await
+        .expect_err("must not create the DB");
+    assert!(
+        format!("{err:?}").contains("while opening SQLite DB"),
+        "unexpected error: {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn load_node_information() -> eyre::Result<()> {
+    let (secret_manager, pool, _file) = sqlite_secret_manager().await?;
+    let report = secret_manager
+        .load_node_information()
+        .await
+        .expect_err("should be an error");
+    assert_eq!(
+        report.to_string(),
+        "Cannot get node information from DB, maybe key-gen needs to start"
+    );
+
+    let should_address = "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc";
+    sqlx::query(
+        "
+            INSERT INTO node_information (id, eth_address, party_id, threshold)
+            VALUES (TRUE, $1, $2, $3)
+        ",
+    )
+    .bind(should_address)
+    .bind(42)
+    .bind(2)
+    .execute(&pool)
+    .await?;
+
+    let is_node_information = secret_manager.load_node_information().await?;
+    assert_eq!(
+        is_node_information,
+        NodeInformation::new(
+            PartyId(42),
+            should_address.to_string(),
+            NonZeroU16::new(2).expect("is non-zero")
+        )
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_oprf_key_material() -> eyre::Result<()> {
+    let (secret_manager, pool, _file) = sqlite_secret_manager().await?;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+
+    assert!(matches!(
+        secret_manager.get_oprf_key_material(oprf_key_id).await,
+        Err(SecretManagerError::UnknownOprfKeyId(_))
+    ));
+
+    sqlx::query(
+        "
+            INSERT INTO shares (id, share, epoch, public_key)
+            VALUES ($1, $2, $3, $4)
+        ",
+    )
+    .bind(oprf_key_id.to_le_bytes())
+    .bind(to_db_ark_serialize_uncompressed(&share))
+    .bind(i64::from(epoch.into_inner()))
+    .bind(to_db_ark_serialize_uncompressed(&public_key))
+    .execute(&pool)
+    .await?;
+
+    let key_material = secret_manager.get_oprf_key_material(oprf_key_id).await?;
+    assert_eq!(
+        ark_babyjubjub::Fr::from(key_material.share()),
+        ark_babyjubjub::Fr::from(share)
+    );
+    assert!(key_material.is_epoch(epoch));
+    assert_eq!(key_material.public_key(), public_key);
+
+    sqlx::query("UPDATE shares SET share = NULL, deleted = TRUE WHERE id = $1")
+        .bind(oprf_key_id.to_le_bytes())
+        .execute(&pool)
+        .await?;
+    assert!(matches!(
+        secret_manager.get_oprf_key_material(oprf_key_id).await,
+        Err(SecretManagerError::DeletedOprfKeyId(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn store_and_load_party_id_binding() -> eyre::Result<()> {
+    let (secret_manager, _pool, _file) = sqlite_secret_manager().await?;
+    assert_eq!(secret_manager.load_party_id_binding().await?, None);
+
+    let binding = PartyIdBinding::new(
+        "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+        PartyId(1),
+        "0x5FbDB2315678afecb367f032d93F642f64180aa3",
+        31337,
+    );
+    secret_manager.store_party_id_binding(&binding).await?;
+    assert_eq!(
+        secret_manager.load_party_id_binding().await?,
+        Some(binding.clone())
+    );
+
+    let changed = PartyIdBinding {
+        party_id: PartyId(2),
+        ..binding
+    };
+    secret_manager.store_party_id_binding(&changed).await?;
+    assert_eq!(
+        secret_manager.load_party_id_binding().await?,
+        Some(changed),
+        "should replace the binding"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn record_and_load_public_key_history() -> eyre::Result<()> {
+    let (secret_manager, _pool, _file) = sqlite_secret_manager().await?;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let other_oprf_key_id = OprfKeyId::new(U160::from(43));
+    let public_key = OprfPublicKey::new(rand::random());
+    let retention = NonZeroUsize::new(2).expect("is non-zero");
+    assert!(
+        secret_manager
+            .load_public_key_history(oprf_key_id)
+            .await?
+            .is_empty()
+    );
+
+    let entry = |epoch| OprfPublicKeyWithEpoch {
+        key: public_key,
+        epoch: ShareEpoch::new(epoch),
+    };
+    for epoch in [0, 1, 1, 2] {
+        secret_manager
+            .record_public_key(oprf_key_id, entry(epoch), retention)
+            .await?;
+    }
+    secret_manager
+        .record_public_key(other_oprf_key_id, entry(0), retention)
+        .await?;
+    assert_eq!(
+        secret_manager.load_public_key_history(oprf_key_id).await?,
+        vec![entry(1), entry(2)],
+        "should keep the latest epochs, oldest first"
+    );
+    assert_eq!(
+        secret_manager
+            .load_public_key_history(other_oprf_key_id)
+            .await?,
+        vec![entry(0)],
+        "retention is per key"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn store_and_load_event_watcher_block() -> eyre::Result<()> {
+    let (secret_manager, _pool, _file) = sqlite_secret_manager().await?;
+    let contract = "0x0000000000000000000000000000000000000042";
+    let other_contract = "0x0000000000000000000000000000000000000043";
+    assert_eq!(
+        secret_manager.load_event_watcher_block(contract).await?,
+        None
+    );
+
+    secret_manager
+        .store_event_watcher_block(contract, 41)
+        .await?;
+    secret_manager
+        .store_event_watcher_block(contract, 42)
+        .await?;
+    secret_manager
+        .store_event_watcher_block(other_contract, 7)
+        .await?;
+    assert_eq!(
+        secret_manager.load_event_watcher_block(contract).await?,
+        Some(42),
+        "should replace the block"
+    );
+    assert_eq!(
+        secret_manager
+            .load_event_watcher_block(other_contract)
+            .await?,
+        Some(7),
+        "block is per contract"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn store_and_load_delivered_epochs() -> eyre::Result<()> {
+    let (secret_manager, _pool, _file) = sqlite_secret_manager().await?;
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let other_key_id = OprfKeyId::new(U160::from(43));
+    assert!(secret_manager.load_delivered_epochs().await?.is_empty());
+
+    secret_manager
+        .store_delivered_epoch(oprf_key_id, ShareEpoch::new(1))
+        .await?;
+    secret_manager
+        .store_delivered_epoch(oprf_key_id, ShareEpoch::new(2))
+        .await?;
+    secret_manager
+        .store_delivered_epoch(other_key_id, ShareEpoch::new(7))
+        .await?;
+    assert_eq!(
+        secret_manager.load_delivered_epochs().await?,
+        HashMap::from([
+            (oprf_key_id, ShareEpoch::new(2)),
+            (other_key_id, ShareEpoch::new(7)),
+        ]),
+        "should replace the epoch per key"
+    );
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! This module provides an implementation of [`SecretManager`](crate::secret_manager::SecretManager) that reads shares from `HashiCorp` Vault.
+//!
+//! The secrets are written by the key-gen service (see its `vault` feature) to a KV version 2 secrets engine, see [`super::remote`] for their layout.
+//! Every value is stored in the `value` field of the KV secret. The node writes its own state (e.g. its party id binding) to the same engine, so its token needs write access to the `{prefix}-node-*` secrets.
+//!
+//! Authentication uses either a static token or an `AppRole`. Tokens are rotated before they expire: renewable static tokens are renewed,
+//! `AppRole` tokens are replaced by logging in again.
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use eyre::Context as _;
+use oprf_types::retry::RetryPolicy;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::Deserialize;
+use tracing::instrument;
+use zeroize::Zeroizing;
+
+use crate::secret_manager::remote::{
+    RemoteError, RemoteSecretManager, SecretStore, TokenCache, error_for_status,
+};
+
+/// Lifetime assumed for tokens that never expire (e.g., root tokens), after which the token is looked up again.
+const NON_EXPIRING_TOKEN_RECHECK: Duration = Duration::from_hours(1);
+
+/// The configuration for the `HashiCorp` Vault backend.
+///
+/// Exactly one of [`VaultConfig::token`] and the `AppRole` credentials must be set.
+#[derive(Clone, Debug, Deserialize)]
+#[non_exhaustive]
+pub struct VaultConfig {
+    /// The address of the Vault server, e.g., `https://vault.example.com:8200`.
+    pub address: String,
+    /// The mount path of the KV version 2 secrets engine.
+    #[serde(default = "VaultConfig::default_mount")]
+    pub mount: String,
+    /// The Vault Enterprise namespace, if any.
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Prefix of the secrets. Must match the prefix of the key-gen service of this node.
+    #[serde(default = "VaultConfig::default_secret_prefix")]
+    pub secret_prefix: String,
+    /// A static Vault token.
+    #[serde(default)]
+    pub token: Option<SecretString>,
+    /// The role ID for `AppRole` authentication.
+    #[serde(default)]
+    pub approle_role_id: Option<String>,
+    /// The secret ID for `AppRole` authentication.
+    #[serde(default)]
+    pub approle_secret_id: Option<SecretString>,
+    /// The mount path of the `AppRole` auth method.
+    #[serde(default = "VaultConfig::default_approle_mount")]
+    pub approle_mount: String,
+    /// Timeout of a single request to Vault.
+    #[serde(default = "VaultConfig::default_request_timeout")]
+    #[serde(with = "humantime_serde")]
+    pub request_timeout: Duration,
+    /// Maximum number of attempts for a request that failed with a transient error.
+    #[serde(default = "VaultConfig::default_max_retries")]
+    pub max_retries: NonZeroUsize,
+    /// Delay before the first retry, doubled (with jitter) for every further retry.
+    #[serde(default = "VaultConfig::default_retry_delay")]
+    #[serde(with = "humantime_serde")]
+    pub retry_delay: Duration,
+}
+
+impl VaultConfig {
+    /// Default KV mount (`secret`)
+    fn default_mount() -> String {
+        "secret".to_owned()
+    }
+
+    /// Default secret prefix (`oprf`)
+    fn default_secret_prefix() -> String {
+        "oprf".to_owned()
+    }
+
+    /// Default `AppRole` mount (`approle`)
+    fn default_approle_mount() -> String {
+        "approle".to_owned()
+    }
+
+    /// Default request timeout (`10 s`)
+    fn default_request_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    /// Default max retries
+    fn default_max_retries() -> NonZeroUsize {
+        NonZeroUsize::new(3).expect("Is non-zero")
+    }
+
+    /// Default retry delay (`1 s`)
+    fn default_retry_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Creates a config for the provided Vault server and token with default values.
+    #[must_use]
+    pub fn with_token(address: String, token: SecretString) -> Self {
+        Self {
+            address,
+            mount: Self::default_mount(),
+            namespace: None,
+            secret_prefix: Self::default_secret_prefix(),
+            token: Some(token),
+            approle_role_id: None,
+            approle_secret_id: None,
+            approle_mount: Self::default_approle_mount(),
+            request_timeout: Self::default_request_timeout(),
+            max_retries: Self::default_max_retries(),
+            retry_delay: Self::default_retry_delay(),
+        }
+    }
+
+    /// Creates a config for the provided Vault server and `AppRole` with default values.
+    #[must_use]
+    pub fn with_approle(address: String, role_id: String, secret_id: SecretString) -> Self {
+        Self {
+            token: None,
+            approle_role_id: Some(role_id),
+            approle_secret_id: Some(secret_id),
+            ..Self::with_token(address, SecretString::from(""))
+        }
+    }
+
+    fn auth(&self) -> eyre::Result<VaultAuth> {
+        match (&self.token, &self.approle_role_id, &self.approle_secret_id) {
+            (Some(token), None, None) => Ok(VaultAuth::Token(token.clone())),
+            (None, Some(role_id), Some(secret_id)) => Ok(VaultAuth::AppRole {
+                role_id: role_id.clone(),
+                secret_id: secret_id.clone(),
+            }),
+            _ => eyre::bail!(
+                "Vault needs either a token or an AppRole role id and secret id, but not both"
+            ),
+        }
+    }
+}
+
+/// How the backend authenticates at Vault.
+enum VaultAuth {
+    Token(SecretString),
+    AppRole {
+        role_id: String,
+        secret_id: SecretString,
+    },
+}
+
+#[derive(Deserialize)]
+struct AuthResponse {
+    auth: AuthInfo,
+}
+
+#[derive(Deserialize)]
+struct AuthInfo {
+    client_token: SecretString,
+    lease_duration: u64,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    data: LookupData,
+}
+
+#[derive(Deserialize)]
+struct LookupData {
+    ttl: u64,
+    renewable: bool,
+}
+
+#[derive(Deserialize)]
+struct KvResponse {
+    data: KvData,
+}
+
+#[derive(Deserialize)]
+struct KvData {
+    data: KvSecret,
+}
+
+#[derive(Deserialize)]
+struct KvSecret {
+    value: SecretString,
+}
+
+/// The Vault secret manager reading from a KV version 2 secrets engine.
+pub type VaultSecretManager = RemoteSecretManager<VaultSecretStore>;
+
+/// The Vault KV v2 API as [`SecretStore`] of a [`VaultSecretManager`].
+pub struct VaultSecretStore {
+    client: reqwest::Client,
+    config: VaultConfig,
+    auth: VaultAuth,
+    token: TokenCache,
+}
+
+impl RemoteSecretManager<VaultSecretStore> {
+    /// Initializes the `VaultSecretManager`.
+    ///
+    /// Checks that the configured credentials yield a valid token.
+    ///
+    /// # Errors
+    /// Returns an error if the configuration has no or ambiguous credentials, the HTTP client cannot be built, or Vault rejects the credentials.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn init(config: VaultConfig) -> eyre::Result<Self> {
+        tracing::debug!(
+            "init Vault secret manager for {} at mount {} with prefix {}",
+            config.address,
+            config.mount,
+            config.secret_prefix
+        );
+        let auth = config.auth()?;
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()?;
+        let secret_manager = Self::new(
+            VaultSecretStore {
+                client,
+                config: config.clone(),
+                auth,
+                token: TokenCache::new(),
+            },
+            config.secret_prefix,
+            RetryPolicy::new(config.max_retries.get(), config.retry_delay),
+        );
+        secret_manager
+            .with_retry("fetch Vault token", || secret_manager.store().token())
+            .await
+            .context("while fetching Vault token")?;
+        Ok(secret_manager)
+    }
+}
+
+impl VaultSecretStore {
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(
+            method,
+            format!("{}/v1/{path}", self.config.address.trim_end_matches('/')),
+        );
+        match &self.config.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        }
+    }
+
+    /// Returns a valid token, renewing or replacing it shortly before it expires.
+    async fn token(&self) -> Result<SecretString, RemoteError> {
+        self.token
+            .get(|| async {
+                match &self.auth {
+                    VaultAuth::Token(token) => self.renew_token(token).await,
+                    VaultAuth::AppRole { role_id, secret_id } => {
+                        self.login_approle(role_id, secret_id).await
+                    }
+                }
+            })
+            .await
+    }
+
+    /// Looks up the lifetime of the static `token` and renews it if possible.
+    async fn renew_token(
+        &self,
+        token: &SecretString,
+    ) -> Result<(SecretString, Duration), RemoteError> {
+        let response = self
+            .request(reqwest::Method::GET, "auth/token/lookup-self")
+            .header("X-Vault-Token", token.expose_secret())
+            .send()
+            .await?;
+        let lookup: LookupResponse = error_for_status(response).await?.json().await?;
+        if lookup.data.ttl == 0 {
+            return Ok((token.clone(), NON_EXPIRING_TOKEN_RECHECK));
+        }
+        if !lookup.data.renewable {
+            tracing::warn!(
+                "Vault token is not renewable and expires in {}s",
+                lookup.data.ttl
+            );
+            return Ok((token.clone(), Duration::from_secs(lookup.data.ttl)));
+        }
+        let response = self
+            .request(reqwest::Method::POST, "auth/token/renew-self")
+            .header("X-Vault-Token", token.expose_secret())
+            .json(&serde_json::json!({}))
+            .send()
+            .await?;
+        let renewed: AuthResponse = error_for_status(response).await?.json().await?;
+        Ok((
+            token.clone(),
+            Duration::from_secs(renewed.auth.lease_duration),
+        ))
+    }
+
+    /// Logs in with the `AppRole` credentials and returns the new token.
+    async fn login_approle(
+        &self,
+        role_id: &str,
+        secret_id: &SecretString,
+    ) -> Result<(SecretString, Duration), RemoteError> {
+        let response = self
+            .request(
+                reqwest::Method::POST,
+                &format!("auth/{}/login", self.config.approle_mount),
+            )
+            .json(&serde_json::json!({
+                "role_id": role_id,
+                "secret_id": secret_id.expose_secret(),
+            }))
+            .send()
+            .await?;
+        let login: AuthResponse = error_for_status(response).await?.json().await?;
+        Ok((
+            login.auth.client_token,
+            Duration::from_secs(login.auth.lease_duration),
+        ))
+    }
+}
+
+impl SecretStore for VaultSecretStore {
+    /// Reads the latest version of the secret, `None` if the secret does not exist.
+    async fn read(&self, secret_id: &str) -> Result<Option<Zeroizing<Vec<u8>>>, RemoteError> {
+        let response = self
+            .request(
+                reqwest::Method::GET,
+                &format!("{}/data/{secret_id}", self.config.mount),
+            )
+            .header("X-Vault-Token", self.token().await?.expose_secret())
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response: KvResponse = error_for_status(response).await?.json().await?;
+        Ok(Some(Zeroizing::new(
+            response.data.data.value.expose_secret().as_bytes().to_vec(),
+        )))
+    }
+
+    /// Writes a new version of the secret, creating the secret if it does not exist.
+    async fn write(&self, secret_id: &str, data: &[u8]) -> Result<(), RemoteError> {
+        let value = std::str::from_utf8(data).map_err(|_| RemoteError::InvalidPayload)?;
+        let response = self
+            .request(
+                reqwest::Method::POST,
+                &format!("{}/data/{secret_id}", self.config.mount),
+            )
+            .header("X-Vault-Token", self.token().await?.expose_secret())
+            .json(&serde_json::json!({ "data": { "value": value } }))
+            .send()
+            .await?;
+        error_for_status(response).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    num::{NonZeroU16, NonZeroUsize},
+    sync::Arc,
+};
+
+use crate::{
+    OprfServiceBuilder, StartedServices,
+    secret_manager::{
+        PartyIdBinding, SecretManager, SecretManagerError,
+        vault::{VaultConfig, VaultSecretManager},
+    },
+    test_kit::MockAuthenticator,
+    test_utils::default_config,
+};
+use ark_serialize::CanonicalSerialize;
+use axum::{
+    Json, Router,
+    extract::Path,
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::OprfPublicKeyWithEpoch,
+    crypto::{OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use parking_lot::Mutex;
+use ruint::aliases::U160;
+use secrecy::SecretString;
+
+type Secrets = Arc<Mutex<HashMap<String, serde_json::Value>>>;
+
+fn check_token(headers: &HeaderMap) -> Result<(), StatusCode> {
+    let token = headers.get("X-Vault-Token").ok_or(StatusCode::FORBIDDEN)?;
+    if token != "static-token" && token != "approle-token" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(())
+}
+
+fn to_base64<T: CanonicalSerialize>(t: &T) -> String {
+    let mut bytes = Vec::with_capacity(t.uncompressed_size());
+    t.serialize_uncompressed(&mut bytes).expect("Can serialize");
+    BASE64.encode(bytes)
+}
+
+/// Serves the provided secrets (as written by the key-gen service) like the Vault token and `AppRole` auth methods and a KV v2 engine.
+async fn serve(secrets: HashMap<String, serde_json::Value>) -> eyre::Result<String> {
+    serve_shared(Arc::new(Mutex::new(secrets))).await
+}
+
+/// Like [`serve`], but the secrets are shared with the caller and written secrets are stored in them.
+async fn serve_shared(secrets: Secrets) -> eyre::Result<String> {
+    let written = Arc::clone(&secrets);
+    let router = Router::new()
+        .route(
+            "/v1/auth/token/lookup-self",
+            get(|| async { Json(serde_json::json!({ "data": { "ttl": 0, "renewable": false } })) }),
+        )
+        .route(
+            "/v1/auth/approle/login",
+            post(|Json(body): Json<serde_json::Value>| async move {
+                if body["role_id"] != "role" || body["secret_id"] != "secret" {
+                    return Err(StatusCode::BAD_REQUEST);
+                }
+                Ok(Json(serde_json::json!({
+                    "auth": { "client_token": "approle-token", "lease_duration": 3600 }
+                })))
+            }),
+        )
+        .route(
+            "/v1/secret/data/{secret}",
+            get(
+                move |headers: HeaderMap, Path(secret): Path<String>| async move {
+                    check_token(&headers)?;
+                    let value = secrets
+                        .lock()
+                        .get(&secret)
+                        .map(|secret| serde_json::to_string(secret).expect("json"))
+                        .ok_or(StatusCode::NOT_FOUND)?;
+                    Ok::<_, StatusCode>(Json(serde_json::json!({
+                        "data": { "data": { "value": value }, "metadata": {} }
+                    })))
+                },
+            )
+            .post(
+                move |headers: HeaderMap,
+                      Path(secret): Path<String>,
+                      Json(body): Json<serde_json::Value>| async move {
+                    check_token(&headers)?;
+                    let value = body["data"]["value"]
+                        .as_str()
+                        .and_then(|value| serde_json::from_str(value).ok())
+                        .ok_or(StatusCode::BAD_REQUEST)?;
+                    written.lock().insert(secret, value);
+                    Ok::<_, StatusCode>(Json(serde_json::json!({ "data": { "version": 1 } })))
+                },
+            ),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(axum::serve(listener, router).into_future());
+    Ok(format!("http://{addr}"))
+}
+
+async fn vault_secret_manager(
+    secrets: HashMap<String, serde_json::Value>,
+) -> eyre::Result<VaultSecretManager> {
+    let config = VaultConfig::with_token(serve(secrets).await?, SecretString::from("static-token"));
+    VaultSecretManager::init(config).await
+}
+
+#[tokio::test]
+async fn load_node_information() -> eyre::Result<()> {
+    let should_address = "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc";
+    let secrets = HashMap::from([(
+        "oprf-node-information".to_owned(),
+        serde_json::json!({ "eth_address": should_address, "party_id": 42, "threshold": 2 }),
+    )]);
+    let should = NodeInformation::new(
+        PartyId(42),
+        should_address.to_owned(),
+        NonZeroU16::new(2).expect("is non-zero"),
+    );
+    let secret_manager = vault_secret_manager(secrets.clone()).await?;
+    assert_eq!(secret_manager.load_node_information().await?, should);
+
+    let approle = VaultSecretManager::init(VaultConfig::with_approle(
+        serve(secrets).await?,
+        "role".to_owned(),
+        SecretString::from("secret"),
+    ))
+    .await?;
+    assert_eq!(approle.load_node_information().await?, should);
+
+    let empty = vault_secret_manager(HashMap::new()).await?;
+    let report = empty
+        .load_node_information()
+        .await
+        .expect_err("should be an error");
+    assert_eq!(
+        report.to_string(),
+        "Cannot get node information from secret manager, maybe key-gen needs to start"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_oprf_key_material() -> eyre::Result<()> {
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let deleted_oprf_key_id = OprfKeyId::new(U160::from(43));
+    let public_key = OprfPublicKey::new(rand::random());
+    let epoch = ShareEpoch::new(42);
+    let share = DLogShareShamir::from(rand::random::<ark_babyjubjub::Fr>());
+    let secret_manager = vault_secret_manager(HashMap::from([
+        (
+            format!("oprf-share-{oprf_key_id}"),
+            serde_json::json!({
+                "epoch": 42,
+                "share": to_base64(&share),
+                "public_key": to_base64(&public_key),
+                "deleted": false
+            }),
+        ),
+        (
+            format!("oprf-share-{deleted_oprf_key_id}"),
+            serde_json::json!({
+                "epoch": 42,
+                "share": null,
+                "public_key": to_base64(&public_key),
+                "deleted": true
+            }),
+        ),
+    ]))
+    .await?;
+
+    let key_material = secret_manager.get_oprf_key_material(oprf_key_id).await?;
+    assert_eq!(
+        ark_babyjubjub::Fr::from(key_material.share()),
+        ark_babyjubjub::Fr::from(share)
+    );
+    assert!(key_material.is_epoch(epoch));
+    assert_eq!(key_material.public_key(), public_key);
+
+    assert!(matches!(
+        secret_manager
+            .get_oprf_key_material(deleted_oprf_key_id)
+            .await,
+        Err(SecretManagerError::DeletedOprfKeyId(_))
+    ));
+    assert!(matches!(
+        secret_manager
+            .get_oprf_key_material(OprfKeyId::new(U160::from(44)))
+            .await,
+        Err(SecretManagerError::UnknownOprfKeyId(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn startup_persists_node_state() -> eyre::Result<()> {
+    let registry = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+    let secrets = Arc::new(Mutex::new(HashMap::from([(
+        "oprf-node-information".to_owned(),
+        serde_json::json!({
+            "eth_address": "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+            "party_id": 1,
+            "threshold": 2
+        }),
+    )])));
+    let address = serve_shared(Arc::clone(&secrets)).await?;
+    let start = || async {
+        let secret_manager = Arc::new(
+            VaultSecretManager::init(VaultConfig::with_token(
+                address.clone(),
+                SecretString::from("static-token"),
+            ))
+            .await?,
+        );
+        let node_information = secret_manager.load_node_information().await?;
+        let _router = OprfServiceBuilder::init(
+            default_config(),
+            Arc::clone(&secret_manager) as _,
+            StartedServices::default(),
+            &node_information,
+            "test".to_owned(),
+        )
+        .bind_party_id(registry, 31337)
+        .await
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()?;
+        eyre::Ok(secret_manager)
+    };
+    let secret_manager = start().await?;
+    assert_eq!(
+        secret_manager.load_party_id_binding().await?,
+        Some(PartyIdBinding::new(
+            "0x9965507D1a55bcC2695C58ba16FB37d819B0A4dc",
+            PartyId(1),
+            registry,
+            31337
+        )),
+        "should persist the binding in Vault"
+    );
+    start().await.expect("should accept the persisted binding");
+
+    let oprf_key_id = OprfKeyId::new(U160::from(42));
+    let public_key = OprfPublicKey::new(rand::random());
+    for epoch in [1, 2, 3, 2] {
+        secret_manager
+            .record_public_key(
+                oprf_key_id,
+                OprfPublicKeyWithEpoch {
+                    key: public_key,
+                    epoch: ShareEpoch::new(epoch),
+                },
+                NonZeroUsize::new(2).expect("is non-zero"),
+            )
+            .await?;
+    }
+    let history = secret_manager.load_public_key_history(oprf_key_id).await?;
+    assert_eq!(
+        history
+            .iter()
+            .map(|recorded| recorded.epoch)
+            .collect::<Vec<_>>(),
+        [ShareEpoch::new(2), ShareEpoch::new(3)],
+        "should keep the latest epochs"
+    );
+
+    assert_eq!(
+        secret_manager.load_event_watcher_block(registry).await?,
+        None
+    );
+    secret_manager
+        .store_event_watcher_block(registry, 42)
+        .await?;
+    assert_eq!(
+        secret_manager.load_event_watcher_block(registry).await?,
+        Some(42)
+    );
+
+    let oprf_key_id = OprfKeyId::from(42usize);
+    secret_manager
+        .store_delivered_epoch(oprf_key_id, ShareEpoch::new(3))
+        .await?;
+    secret_manager
+        .store_delivered_epoch(oprf_key_id, ShareEpoch::new(4))
+        .await?;
+    assert_eq!(
+        secret_manager.load_delivered_epochs().await?,
+        HashMap::from([(oprf_key_id, ShareEpoch::new(4))])
+    );
+    Ok(())
+}
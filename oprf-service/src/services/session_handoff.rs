@@ -0,0 +1,222 @@
+//! Handoff of sessions across planned restarts of the node.
+//!
+//! Without a handoff, a restart drops all open sessions. With [`crate::OprfServiceBuilder::session_handoff`], the node writes a handoff file as soon as its cancellation token is cancelled (i.e., on graceful shutdown). The file contains:
+//! - the finished sessions of the [`ChallengeReplayCache`] (commitments, proof share and hash of the answered challenge), and
+//! - the metadata (session id, OPRF key id, start time) of all sessions still waiting for their challenge.
+//!
+//! Randomness is never written. The randomness of pending sessions dies with the old process.
+//!
+//! The next process reads and deletes the file at startup. The session id of the [`oprf_types::api::OprfRequest`] acts as resumption token:
+//! - Finished sessions are resumed within `session_lifetime` as before the restart, see [`crate::services::challenge_replay`].
+//! - Pending sessions can no longer be finished. Resuming them within `session_lifetime` is rejected with [`oprf_types::api::oprf_error_codes::SESSION_LOST`], so the client restarts the evaluation with a new request id right away.
+//!
+//! Files older than `session_handoff_grace_period` (see [`crate::config::OprfNodeServiceConfig`]) are stale and ignored. The handoff only covers modules that share their session ids (see [`crate::SessionNamespace`]).
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::Context as _;
+use moka::future::Cache;
+use oprf_types::OprfKeyId;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    ExitReason,
+    services::challenge_replay::{ChallengeReplayCache, ReplayEntry},
+};
+
+/// The version of the [`HandoffFile`] format.
+const HANDOFF_VERSION: u32 = 1;
+
+/// The content of the handoff file.
+#[derive(Serialize, Deserialize)]
+struct HandoffFile {
+    version: u32,
+    /// Unix time (in seconds) when the file was written.
+    written_at: u64,
+    finished: Vec<FinishedSession>,
+    pending: Vec<PendingSession>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FinishedSession {
+    session_id: Uuid,
+    entry: Arc<ReplayEntry>,
+}
+
+/// A session that was waiting for its challenge.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct PendingSession {
+    session_id: Uuid,
+    oprf_key_id: OprfKeyId,
+    /// Unix time (in seconds) when the session was created.
+    started_at: u64,
+}
+
+/// Tracks the pending sessions and rejects the sessions lost by the last restart, see the [module docs](self).
+#[derive(Clone)]
+pub(crate) struct SessionHandoff {
+    pending: Arc<Mutex<HashMap<Uuid, PendingSession>>>,
+    lost: Cache<Uuid, ()>,
+}
+
+/// Removes a session from the pending sessions when dropped.
+pub(crate) struct PendingGuard {
+    handoff: SessionHandoff,
+    session_id: Uuid,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.handoff.pending.lock().remove(&self.session_id);
+    }
+}
+
+impl SessionHandoff {
+    /// Restores the sessions from the handoff file at `path` and spawns the task writing it again when `cancellation_token` is cancelled.
+    ///
+    /// Restored sessions are resumable (or rejected as lost) for `session_lifetime`.
+    pub(crate) async fn spawn(
+        path: PathBuf,
+        grace_period: Duration,
+        session_lifetime: Duration,
+        challenge_replay_cache: ChallengeReplayCache,
+        cancellation_token: CancellationToken,
+    ) -> (Self, JoinHandle<ExitReason>) {
+        let handoff = Self {
+            pending: Arc::default(),
+            lost: Cache::builder().time_to_live(session_lifetime).build(),
+        };
+        match read(&path, grace_period).await {
+            Ok(Some(file)) => {
+                tracing::info!(
+                    "restoring {} finished and {} lost sessions from {}",
+                    file.finished.len(),
+                    file.pending.len(),
+                    path.display()
+                );
+                for FinishedSession { session_id, entry } in file.finished {
+                    challenge_replay_cache.restore(session_id, entry).await;
+                }
+                for pending in file.pending {
+                    handoff.lost.insert(pending.session_id, ()).await;
+                }
+            }
+            Ok(None) => {}
+            Err(err) => tracing::warn!(?err, "cannot restore sessions from handoff file"),
+        }
+        let pending = Arc::clone(&handoff.pending);
+        let task = tokio::spawn(async move {
+            cancellation_token.cancelled().await;
+            let pending = pending.lock().values().copied().collect::<Vec<_>>();
+            match write(&path, &challenge_replay_cache, pending).await {
+                Ok(()) => ExitReason::Cancelled,
+                Err(err) => {
+                    tracing::error!(?err, "cannot write session handoff file");
+                    ExitReason::Failed(err)
+                }
+            }
+        });
+        (handoff, task)
+    }
+
+    /// Marks the session as pending until the returned guard is dropped.
+    pub(crate) fn track(&self, session_id: Uuid, oprf_key_id: OprfKeyId) -> PendingGuard {
+        self.pending.lock().insert(
+            session_id,
+            PendingSession {
+                session_id,
+                oprf_key_id,
+                started_at: unix_now(),
+            },
+        );
+        PendingGuard {
+            handoff: self.clone(),
+            session_id,
+        }
+    }
+
+    /// Returns `true` if the session was pending during the last restart.
+    pub(crate) fn is_lost(&self, session_id: Uuid) -> bool {
+        self.lost.contains_key(&session_id)
+    }
+}
+
+/// Reads and deletes the handoff file. Returns `None` if there is no file or it is stale.
+async fn read(path: &Path, grace_period: Duration) -> eyre::Result<Option<HandoffFile>> {
+    let bytes = match tokio::fs::read(path).await {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            tracing::info!("no session handoff file at {}", path.display());
+            return Ok(None);
+        }
+        Err(err) => return Err(err).context("while reading handoff file"),
+    };
+    // a handoff file must only be restored once
+    tokio::fs::remove_file(path)
+        .await
+        .context("while removing handoff file")?;
+    let file: HandoffFile = serde_json::from_slice(&bytes).context("while parsing handoff file")?;
+    if file.version != HANDOFF_VERSION {
+        eyre::bail!("unsupported handoff file version {}", file.version);
+    }
+    let age = unix_now().saturating_sub(file.written_at);
+    if age > grace_period.as_secs() {
+        tracing::warn!(
+            "ignoring stale session handoff file written {age}s ago (grace period {grace_period:?})"
+        );
+        return Ok(None);
+    }
+    Ok(Some(file))
+}
+
+/// Writes the handoff file atomically by renaming a temporary file.
+async fn write(
+    path: &Path,
+    challenge_replay_cache: &ChallengeReplayCache,
+    pending: Vec<PendingSession>,
+) -> eyre::Result<()> {
+    let file = HandoffFile {
+        version: HANDOFF_VERSION,
+        written_at: unix_now(),
+        finished: challenge_replay_cache
+            .entries()
+            .into_iter()
+            .map(|(session_id, entry)| FinishedSession { session_id, entry })
+            .collect(),
+        pending,
+    };
+    let json = serde_json::to_vec(&file).context("while serializing handoff file")?;
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, json)
+        .await
+        .context("while writing handoff file")?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .context("while moving handoff file")?;
+    tracing::info!(
+        "handed off {} finished and {} pending sessions to {}",
+        file.finished.len(),
+        file.pending.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Current unix time in seconds.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
+
+#[cfg(test)]
+mod tests;
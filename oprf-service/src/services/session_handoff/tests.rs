@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use ark_ec::AffineRepr as _;
+use axum_test::TestServerBuilder;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{OprfRequest, OprfResponse, oprf_error_codes},
+    crypto::PartyId,
+};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    ExitReason,
+    test_kit::MockAuthenticator,
+    test_utils::{MockSecretManager, builder_with_secret_manager, challenge, default_config},
+};
+
+/// Builds a node handing off sessions via `path` and returns its test-server and tasks.
+async fn handoff_node(
+    path: &std::path::Path,
+    cancellation_token: CancellationToken,
+) -> (axum_test::TestServer, crate::OprfServiceTasks) {
+    let (router, tasks) = builder_with_secret_manager(
+        default_config(),
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .session_handoff(path, cancellation_token)
+    .await
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build_with_tasks()
+    .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    (server, tasks)
+}
+
+#[tokio::test]
+async fn session_handoff_survives_restart() {
+    let path = std::env::temp_dir().join(format!("oprf-handoff-{}.json", Uuid::new_v4()));
+    let request = |request_id| OprfRequest {
+        request_id,
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+    let finished = request(Uuid::new_v4());
+    let pending = request(Uuid::new_v4());
+
+    let cancellation_token = CancellationToken::new();
+    let (server, tasks) = handoff_node(&path, cancellation_token.clone()).await;
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&finished).await;
+    let response = ws.receive_json::<serde_json::Value>().await;
+    ws.send_json(&challenge(1)).await;
+    let proof_share = ws.receive_json::<serde_json::Value>().await;
+    drop(ws);
+    let mut pending_ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    pending_ws.send_json(&pending).await;
+    let _pending_response = pending_ws.receive_json::<serde_json::Value>().await;
+    cancellation_token.cancel();
+    assert!(
+        matches!(tasks.run().await, Some(ExitReason::Cancelled)),
+        "should write the handoff file on shutdown"
+    );
+    drop(pending_ws);
+    drop(server);
+
+    let (server, _tasks) = handoff_node(&path, CancellationToken::new()).await;
+    assert!(!path.exists(), "should remove the restored handoff file");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&finished).await;
+    assert_eq!(
+        ws.receive_json::<serde_json::Value>().await,
+        response,
+        "should send the commitments from before the restart"
+    );
+    ws.send_json(&challenge(1)).await;
+    assert_eq!(
+        ws.receive_json::<serde_json::Value>().await,
+        proof_share,
+        "should send the proof share from before the restart"
+    );
+    drop(ws);
+
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&pending).await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::SESSION_LOST,
+        "should reject the session that was pending during the restart"
+    );
+}
+
+#[tokio::test]
+async fn stale_session_handoff_is_ignored() {
+    let path = std::env::temp_dir().join(format!("oprf-handoff-{}.json", Uuid::new_v4()));
+    let pending = Uuid::new_v4();
+    std::fs::write(
+        &path,
+        serde_json::json!({
+            "version": 1,
+            "written_at": 0,
+            "finished": [],
+            "pending": [{ "session_id": pending, "oprf_key_id": "42", "started_at": 0 }],
+        })
+        .to_string(),
+    )
+    .expect("Can write handoff file");
+
+    let (server, _tasks) = handoff_node(&path, CancellationToken::new()).await;
+    assert!(!path.exists(), "should remove the stale handoff file");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: pending,
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    assert_eq!(
+        ws.receive_json::<OprfResponse>().await.party_id,
+        PartyId(0),
+        "should start a fresh session for ids of a stale handoff"
+    );
+}
@@ -0,0 +1,159 @@
+//! Session store interface for OPRF nodes.
+//!
+//! This module defines the [`OprfSessionStore`] trait, which manages the state of OPRF sessions between the two rounds of the protocol:
+//! - it reserves session-ids, as users are not allowed to use the same session-id over multiple requests because we use it as domain-separator for the Two-Nonce combiner hash (inspired by FROST2).
+//! - it holds the [`OprfSession`] created in the first round until the challenge of the user arrives.
+//!
+//! The default implementation is the [`LocalSessionStore`], which keeps everything in the memory of the node. Other implementations (e.g., shared between replicas) can be set with [`crate::OprfServiceBuilder::session_store`].
+//!
+//! Durable stores (see [`OprfSessionStore::is_durable`]) keep pending sessions across restarts of the node, so clients can resume them by sending the same request again. The [`postgres::PostgresSessionStore`] is such a store. As durability trades latency for every session, durable stores are only accepted with [`crate::config::SessionPersistence::Durable`].
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+use crate::{metrics, services::oprf_key_material_store::OprfSession};
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+/// Dynamic trait object for the session store.
+///
+/// Must be `Send + Sync` to work with async contexts (e.g., Axum).
+pub type OprfSessionStoreService = Arc<dyn OprfSessionStore + Send + Sync>;
+
+/// All errors that might occur when interacting with the [`OprfSessionStoreService`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum OprfSessionStoreError {
+    /// The session-id is already in use.
+    #[error("Session {0} already exists")]
+    SessionReuse(Uuid),
+    /// Implementation specific error.
+    #[error(transparent)]
+    Internal(#[from] eyre::Report),
+}
+
+/// Manages the state of OPRF sessions, see the [module docs](self).
+///
+/// The node calls the methods in this order for every session:
+/// 1. [`OprfSessionStore::reserve`] as soon as the request is read,
+/// 2. [`OprfSessionStore::store`] after the first round,
+/// 3. [`OprfSessionStore::take`] when the challenge arrives,
+/// 4. [`OprfSessionStore::release`] when the session ends, no matter if it succeeded.
+#[async_trait]
+pub trait OprfSessionStore {
+    /// Reserves the session-id for a new session.
+    ///
+    /// # Errors
+    /// Must return [`OprfSessionStoreError::SessionReuse`] if the session-id is reserved already.
+    async fn reserve(&self, session_id: Uuid) -> Result<(), OprfSessionStoreError>;
+
+    /// Stores the state of the reserved session after the first round.
+    async fn store(
+        &self,
+        session_id: Uuid,
+        session: OprfSession,
+    ) -> Result<(), OprfSessionStoreError>;
+
+    /// Removes and returns the state of the session, `None` if there is none.
+    ///
+    /// The session-id stays reserved until [`OprfSessionStore::release`] is called.
+    async fn take(&self, session_id: Uuid) -> Result<Option<OprfSession>, OprfSessionStoreError>;
+
+    /// Releases the session-id and drops all state of the session.
+    ///
+    /// Called from `Drop`, therefore must not block.
+    fn release(&self, session_id: Uuid);
+
+    /// Whether stored sessions survive a restart of the node.
+    ///
+    /// After a restart, a durable store must accept [`OprfSessionStore::reserve`] for session-ids reserved before the restart, and [`OprfSessionStore::take`] must return the sessions stored before the restart. The node then answers with the commitments of the stored session instead of creating new randomness.
+    fn is_durable(&self) -> bool {
+        false
+    }
+}
+
+/// The default [`OprfSessionStore`], keeping all sessions in the memory of this node.
+///
+/// Cloning is cheap and the clones share their sessions.
+#[derive(Clone, Default)]
+pub struct LocalSessionStore(Arc<Mutex<HashMap<Uuid, Option<OprfSession>>>>);
+
+impl LocalSessionStore {
+    /// Creates an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl OprfSessionStore for LocalSessionStore {
+    async fn reserve(&self, session_id: Uuid) -> Result<(), OprfSessionStoreError> {
+        let mut sessions = self.0.lock();
+        if sessions.contains_key(&session_id) {
+            return Err(OprfSessionStoreError::SessionReuse(session_id));
+        }
+        sessions.insert(session_id, None);
+        Ok(())
+    }
+
+    async fn store(
+        &self,
+        session_id: Uuid,
+        session: OprfSession,
+    ) -> Result<(), OprfSessionStoreError> {
+        match self.0.lock().get_mut(&session_id) {
+            Some(slot) => {
+                *slot = Some(session);
+                Ok(())
+            }
+            None => Err(eyre::eyre!("session {session_id} is not reserved").into()),
+        }
+    }
+
+    async fn take(&self, session_id: Uuid) -> Result<Option<OprfSession>, OprfSessionStoreError> {
+        Ok(self.0.lock().get_mut(&session_id).and_then(Option::take))
+    }
+
+    fn release(&self, session_id: Uuid) {
+        self.0.lock().remove(&session_id);
+    }
+}
+
+/// A guard for a reserved session.
+///
+/// As long as this guard exists, no other request can use the session-id wrapped in this guard. On drop, releases the session in the store.
+#[must_use]
+pub(crate) struct SessionGuard {
+    session_id: Uuid,
+    session_store: OprfSessionStoreService,
+}
+
+impl SessionGuard {
+    /// Reserves the session-id in the provided store.
+    pub(crate) async fn reserve(
+        session_store: &OprfSessionStoreService,
+        session_id: Uuid,
+    ) -> Result<Self, OprfSessionStoreError> {
+        session_store.reserve(session_id).await?;
+        metrics::sessions::inc();
+        Ok(Self {
+            session_id,
+            session_store: Arc::clone(session_store),
+        })
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.session_store.release(self.session_id);
+        metrics::sessions::dec();
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,267 @@
+//! A durable [`OprfSessionStore`] backed by Postgres.
+//!
+//! Sessions are kept in the `oprf_sessions` table (created by the migrations of the key-gen service), so that clients can resume pending sessions after a restart of the node or a broken connection by sending the same request again.
+//!
+//! The stored [`OprfSession`] contains the randomness of the session and the share of the key. It is encrypted with ChaCha20-Poly1305 under a key derived from a session secret, with the session-id as associated data, so stored sessions can neither be read nor moved to another session-id without the secret.
+//!
+//! Every store has a random owner id. Reservations of other owners are left-overs of a previous run of the node and are taken over by [`OprfSessionStore::reserve`]. Therefore, never share the table (i.e., the schema) between replicas of a node or between nodes.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chacha20poly1305::{
+    ChaCha20Poly1305, KeyInit as _, Nonce,
+    aead::{Aead as _, Payload},
+};
+use eyre::Context as _;
+use nodes_common::postgres::{CreateSchema, PostgresConfig};
+use rand::Rng as _;
+use secrecy::{ExposeSecret as _, SecretString};
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::{
+    services::oprf_key_material_store::OprfSession,
+    session_store::{OprfSessionStore, OprfSessionStoreError},
+};
+
+#[cfg(test)]
+mod tests;
+
+const ENCRYPTION_CONTEXT: &str = "taceo-oprf session store encryption v1";
+const AEAD_NONCE_LEN: usize = 12;
+
+/// The Postgres session store, see the [module docs](self).
+pub struct PostgresSessionStore {
+    pool: PgPool,
+    owner: Uuid,
+    max_age: Duration,
+    encryption_key: Zeroizing<[u8; 32]>,
+}
+
+impl PostgresSessionStore {
+    /// Initializes the `PostgresSessionStore`.
+    ///
+    /// Connects to the Postgres database using the provided configuration. This does **not** run migrations, it assumes the database is already set up. Drops all sessions older than `max_age`, which should be the `session_lifetime` of the node.
+    ///
+    /// Use a long random `session_secret` and keep it in your secret store. Sessions stored with another secret cannot be resumed.
+    ///
+    /// # Errors
+    /// Returns an error if the connection to the database fails.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn init(
+        config: &PostgresConfig,
+        session_secret: &SecretString,
+        max_age: Duration,
+    ) -> eyre::Result<Self> {
+        tracing::debug!("init PgPool with schema: {}", config.schema);
+        let pool = nodes_common::postgres::pg_pool_with_schema(config, CreateSchema::No)
+            .await
+            .context("while connecting to postgres DB")?;
+        let store = Self {
+            pool,
+            owner: Uuid::new_v4(),
+            max_age,
+            encryption_key: Zeroizing::new(blake3::derive_key(
+                ENCRYPTION_CONTEXT,
+                session_secret.expose_secret().as_bytes(),
+            )),
+        };
+        let expired = delete_expired(&store.pool, max_age)
+            .await
+            .context("while deleting expired sessions")?;
+        tracing::info!("dropped {expired} expired sessions");
+        Ok(store)
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(self.encryption_key.as_ref().into())
+    }
+
+    fn seal(&self, session_id: Uuid, session: &OprfSession) -> eyre::Result<Vec<u8>> {
+        let mut plaintext = Zeroizing::new(Vec::new());
+        ciborium::into_writer(session, &mut *plaintext).context("while serializing session")?;
+        let nonce: [u8; AEAD_NONCE_LEN] = rand::thread_rng().r#gen();
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: session_id.as_bytes(),
+                },
+            )
+            .map_err(|_| eyre::eyre!("cannot encrypt session"))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    fn open(&self, session_id: Uuid, sealed: &[u8]) -> eyre::Result<OprfSession> {
+        if sealed.len() < AEAD_NONCE_LEN {
+            eyre::bail!("session too short");
+        }
+        let (nonce, ciphertext) = sealed.split_at(AEAD_NONCE_LEN);
+        let nonce: [u8; AEAD_NONCE_LEN] = nonce.try_into().expect("split at nonce length");
+        let plaintext = Zeroizing::new(
+            self.cipher()
+                .decrypt(
+                    &Nonce::from(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: session_id.as_bytes(),
+                    },
+                )
+                .map_err(|_| {
+                    eyre::eyre!("cannot decrypt session, is the session secret the same?")
+                })?,
+        );
+        ciborium::from_reader(plaintext.as_slice()).context("while deserializing session")
+    }
+}
+
+#[async_trait]
+impl OprfSessionStore for PostgresSessionStore {
+    #[instrument(level = "debug", skip(self))]
+    async fn reserve(&self, session_id: Uuid) -> Result<(), OprfSessionStoreError> {
+        // takes over released reservations and reservations of previous runs of the node
+        let result = sqlx::query(
+            "
+                INSERT INTO oprf_sessions (id, owner)
+                VALUES ($1, $2)
+                ON CONFLICT (id)
+                DO UPDATE SET owner = excluded.owner
+                WHERE oprf_sessions.owner IS NULL OR oprf_sessions.owner <> excluded.owner
+            ",
+        )
+        .bind(session_id.as_bytes().as_slice())
+        .bind(self.owner.as_bytes().as_slice())
+        .execute(&self.pool)
+        .await
+        .context("while reserving session")?;
+        if result.rows_affected() == 0 {
+            return Err(OprfSessionStoreError::SessionReuse(session_id));
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip(self, session))]
+    async fn store(
+        &self,
+        session_id: Uuid,
+        session: OprfSession,
+    ) -> Result<(), OprfSessionStoreError> {
+        let sealed = self.seal(session_id, &session)?;
+        let result = sqlx::query(
+            "
+                UPDATE oprf_sessions
+                SET session = $3
+                WHERE id = $1 AND owner = $2
+            ",
+        )
+        .bind(session_id.as_bytes().as_slice())
+        .bind(self.owner.as_bytes().as_slice())
+        .bind(sealed)
+        .execute(&self.pool)
+        .await
+        .context("while storing session")?;
+        if result.rows_affected() == 0 {
+            return Err(eyre::eyre!("session {session_id} is not reserved").into());
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip(self))]
+    async fn take(&self, session_id: Uuid) -> Result<Option<OprfSession>, OprfSessionStoreError> {
+        // the row lock guarantees that a stored session is returned at most once
+        let sealed: Option<Option<Vec<u8>>> = sqlx::query_scalar(
+            "
+                UPDATE oprf_sessions
+                SET session = NULL
+                FROM (
+                    SELECT id, session FROM oprf_sessions
+                    WHERE id = $1 AND owner = $2
+                    FOR UPDATE
+                ) AS taken
+                WHERE oprf_sessions.id = taken.id
+                RETURNING taken.session
+            ",
+        )
+        .bind(session_id.as_bytes().as_slice())
+        .bind(self.owner.as_bytes().as_slice())
+        .fetch_optional(&self.pool)
+        .await
+        .context("while taking session")?;
+        Ok(sealed
+            .flatten()
+            .map(|sealed| self.open(session_id, &sealed))
+            .transpose()?)
+    }
+
+    /// Releases the reservation in a background task.
+    ///
+    /// Keeps a pending session (i.e., a stored session that was not taken), so that the client can resume it with a new connection. Expired sessions are dropped at the same time.
+    fn release(&self, session_id: Uuid) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("cannot release session {session_id} outside of a runtime");
+            return;
+        };
+        let pool = self.pool.clone();
+        let owner = self.owner;
+        let max_age = self.max_age;
+        runtime.spawn(async move {
+            if let Err(err) = release(&pool, session_id, owner, max_age).await {
+                tracing::warn!("cannot release session {session_id}: {err:?}");
+            }
+        });
+    }
+
+    fn is_durable(&self) -> bool {
+        true
+    }
+}
+
+async fn release(
+    pool: &PgPool,
+    session_id: Uuid,
+    owner: Uuid,
+    max_age: Duration,
+) -> eyre::Result<()> {
+    sqlx::query(
+        "
+            DELETE FROM oprf_sessions
+            WHERE id = $1 AND owner = $2 AND session IS NULL
+        ",
+    )
+    .bind(session_id.as_bytes().as_slice())
+    .bind(owner.as_bytes().as_slice())
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "
+            UPDATE oprf_sessions
+            SET owner = NULL
+            WHERE id = $1 AND owner = $2
+        ",
+    )
+    .bind(session_id.as_bytes().as_slice())
+    .bind(owner.as_bytes().as_slice())
+    .execute(pool)
+    .await?;
+    delete_expired(pool, max_age).await?;
+    Ok(())
+}
+
+/// Deletes all sessions older than `max_age` and returns how many were deleted.
+async fn delete_expired(pool: &PgPool, max_age: Duration) -> eyre::Result<u64> {
+    let result = sqlx::query(
+        "
+            DELETE FROM oprf_sessions
+            WHERE created_at < now() - make_interval(secs => $1)
+        ",
+    )
+    .bind(max_age.as_secs_f64())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
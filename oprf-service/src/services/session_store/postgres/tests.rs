@@ -0,0 +1,118 @@
+use std::{sync::Arc, time::Duration};
+
+use ark_ec::AffineRepr as _;
+use nodes_common::postgres::{PostgresConfig, SanitizedSchema};
+use oprf_types::OprfKeyId;
+use secrecy::SecretString;
+use uuid::Uuid;
+
+use crate::{
+    config::StoreEvictionPolicy,
+    oprf_key_material_store::{OprfKeyMaterialStore, OprfSession},
+    session_store::{OprfSessionStore, OprfSessionStoreError, postgres::PostgresSessionStore},
+    test_kit::StaticSecretManager,
+};
+
+async fn postgres_session_store() -> eyre::Result<(&'static str, SanitizedSchema)> {
+    let conn = nodes_common::test_utils::shared_postgres_testcontainer().await?;
+    let schema = nodes_common::test_utils::next_test_schema();
+    let mut pg_connection = nodes_common::test_utils::open_pg_connection(conn, &schema).await?;
+    sqlx::migrate!("../oprf-key-gen/migrations")
+        .run(&mut pg_connection)
+        .await?;
+    Ok((conn, schema))
+}
+
+async fn init(
+    conn: &str,
+    schema: &SanitizedSchema,
+    secret: &str,
+) -> eyre::Result<PostgresSessionStore> {
+    PostgresSessionStore::init(
+        &PostgresConfig::with_default_values(SecretString::from(conn.to_owned()), schema.clone()),
+        &SecretString::from(secret.to_owned()),
+        Duration::from_mins(5),
+    )
+    .await
+}
+
+async fn session() -> OprfSession {
+    let oprf_key_id = OprfKeyId::from(42usize);
+    let secret_manager =
+        StaticSecretManager::single_node().with_random_key(oprf_key_id, &mut rand::thread_rng());
+    let store = OprfKeyMaterialStore::new(
+        Arc::new(secret_manager),
+        10,
+        Duration::from_mins(5),
+        Duration::from_mins(5),
+        StoreEvictionPolicy::default(),
+    );
+    let (session, _) = store
+        .partial_commit(ark_babyjubjub::EdwardsAffine::generator(), oprf_key_id)
+        .await
+        .expect("can commit");
+    session
+}
+
+#[tokio::test]
+async fn store_and_take_session() -> eyre::Result<()> {
+    let (conn, schema) = postgres_session_store().await?;
+    let store = init(conn, &schema, "secret").await?;
+    let session_id = Uuid::new_v4();
+    let session = session().await;
+    let commitments = serde_json::to_value(session.commitments())?;
+
+    store.reserve(session_id).await?;
+    assert!(matches!(
+        store.reserve(session_id).await,
+        Err(OprfSessionStoreError::SessionReuse(_))
+    ));
+    store.store(session_id, session).await?;
+    let taken = store.take(session_id).await?.expect("session is stored");
+    assert_eq!(serde_json::to_value(taken.commitments())?, commitments);
+    assert!(store.take(session_id).await?.is_none(), "taken only once");
+    Ok(())
+}
+
+/// Stores a session with a store that is dropped without releasing the session, like in a crash.
+async fn store_and_crash(
+    conn: &str,
+    schema: &SanitizedSchema,
+    session_id: Uuid,
+    session: OprfSession,
+) -> eyre::Result<()> {
+    let store = init(conn, schema, "secret").await?;
+    store.reserve(session_id).await?;
+    store.store(session_id, session).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_session_after_restart() -> eyre::Result<()> {
+    let (conn, schema) = postgres_session_store().await?;
+    let session_id = Uuid::new_v4();
+    let session = session().await;
+    let commitments = serde_json::to_value(session.commitments())?;
+    store_and_crash(conn, &schema, session_id, session).await?;
+
+    let restarted = init(conn, &schema, "secret").await?;
+    restarted.reserve(session_id).await?;
+    let resumed = restarted
+        .take(session_id)
+        .await?
+        .expect("session survives the restart");
+    assert_eq!(serde_json::to_value(resumed.commitments())?, commitments);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_resume_session_with_other_secret() -> eyre::Result<()> {
+    let (conn, schema) = postgres_session_store().await?;
+    let session_id = Uuid::new_v4();
+    store_and_crash(conn, &schema, session_id, session().await).await?;
+
+    let restarted = init(conn, &schema, "other secret").await?;
+    restarted.reserve(session_id).await?;
+    assert!(restarted.take(session_id).await.is_err());
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use ark_ec::AffineRepr as _;
+use async_trait::async_trait;
+use axum_test::TestServerBuilder;
+use oprf_types::{OprfKeyId, ShareEpoch, api::OprfRequest};
+use uuid::Uuid;
+
+use crate::{
+    BuilderError,
+    config::{OprfNodeServiceConfig, SessionPersistence},
+    test_kit::MockAuthenticator,
+    test_utils::{
+        MockSecretManager, builder, builder_with_config, builder_with_secret_manager, challenge,
+        default_config,
+    },
+};
+
+use super::{OprfSession, OprfSessionStore, OprfSessionStoreError};
+
+/// A durable session store keeping the serialized sessions in a map shared by all its clones, while every clone has its own reservations, like a node process.
+#[derive(Default)]
+struct SharedSessionStore {
+    sessions: Arc<parking_lot::Mutex<std::collections::HashMap<Uuid, Vec<u8>>>>,
+    reserved: parking_lot::Mutex<std::collections::HashSet<Uuid>>,
+}
+
+impl SharedSessionStore {
+    fn restarted(&self) -> Self {
+        Self {
+            sessions: Arc::clone(&self.sessions),
+            reserved: parking_lot::Mutex::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl OprfSessionStore for SharedSessionStore {
+    async fn reserve(&self, session_id: Uuid) -> Result<(), OprfSessionStoreError> {
+        if self.reserved.lock().insert(session_id) {
+            Ok(())
+        } else {
+            Err(OprfSessionStoreError::SessionReuse(session_id))
+        }
+    }
+
+    async fn store(
+        &self,
+        session_id: Uuid,
+        session: OprfSession,
+    ) -> Result<(), OprfSessionStoreError> {
+        let serialized = serde_json::to_vec(&session).map_err(eyre::Report::from)?;
+        self.sessions.lock().insert(session_id, serialized);
+        Ok(())
+    }
+
+    async fn take(&self, session_id: Uuid) -> Result<Option<OprfSession>, OprfSessionStoreError> {
+        let serialized = self.sessions.lock().remove(&session_id);
+        Ok(serialized
+            .map(|serialized| serde_json::from_slice(&serialized))
+            .transpose()
+            .map_err(eyre::Report::from)?)
+    }
+
+    fn release(&self, session_id: Uuid) {
+        self.reserved.lock().remove(&session_id);
+    }
+
+    fn is_durable(&self) -> bool {
+        true
+    }
+}
+
+fn durable_node(session_store: SharedSessionStore) -> axum_test::TestServer {
+    let config = OprfNodeServiceConfig {
+        session_persistence: SessionPersistence::Durable,
+        ..default_config()
+    };
+    let router = builder_with_secret_manager(
+        config,
+        Arc::new(MockSecretManager::fixed_key(ShareEpoch::default())),
+    )
+    .session_store(Arc::new(session_store))
+    .module("/test", MockAuthenticator::allow_all().into_service())
+    .build()
+    .expect("Can build");
+    TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server")
+}
+
+#[test]
+fn durable_session_store_requires_session_persistence() {
+    let err = builder()
+        .session_store(Arc::new(SharedSessionStore::default()))
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect_err("Should reject durable store");
+    assert!(matches!(err, BuilderError::InvalidConfig(_)), "got {err:?}");
+
+    let config = OprfNodeServiceConfig {
+        session_persistence: SessionPersistence::Durable,
+        ..default_config()
+    };
+    let err = builder_with_config(config)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect_err("Should reject local store");
+    assert!(matches!(err, BuilderError::InvalidConfig(_)), "got {err:?}");
+}
+
+#[tokio::test]
+async fn durable_session_store_resumes_pending_session() {
+    let request = OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    };
+    let session_store = SharedSessionStore::default();
+    let restarted = session_store.restarted();
+
+    let server = durable_node(session_store);
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&request).await;
+    let response = ws.receive_json::<serde_json::Value>().await;
+    // the node restarts before the challenge arrives
+    drop(ws);
+    drop(server);
+
+    let server = durable_node(restarted);
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&request).await;
+    assert_eq!(
+        ws.receive_json::<serde_json::Value>().await,
+        response,
+        "should send the commitments from before the restart"
+    );
+    ws.send_json(&challenge(1)).await;
+    let tungstenite::Message::Text(_) = ws.receive_message().await else {
+        panic!("expected proof share");
+    };
+}
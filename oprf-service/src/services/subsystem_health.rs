@@ -0,0 +1,109 @@
+//! Readiness of the subsystems of a node, served at `/health/details`.
+//!
+//! `/health` only reports whether all [`StartedServices`](crate::StartedServices) started. Subsystems additionally report the outcome of their last operation to a [`Subsystem`] handle, e.g., the secret manager reports every key load and the key reconciliation (requires the `registry` feature) reports the last block of its RPC connection. Hosts can register their own subsystems, e.g., an event watcher, with [`crate::OprfServiceBuilder::subsystem`].
+//!
+//! A subsystem is
+//! - [`SubsystemState::Starting`] until it reported its first outcome,
+//! - [`SubsystemState::Healthy`] if its last operation succeeded, and
+//! - [`SubsystemState::Degraded`] if its last operation failed.
+//!
+//! The state is informational, a degraded subsystem does not fail `/health`.
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::RwLock;
+use serde::Serialize;
+
+/// State of a [`Subsystem`], see the [module docs](self).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SubsystemState {
+    /// The subsystem did not report an outcome yet.
+    #[default]
+    Starting,
+    /// The last operation of the subsystem succeeded.
+    Healthy,
+    /// The last operation of the subsystem failed.
+    Degraded,
+}
+
+/// Status of a [`Subsystem`] as served at `/health/details`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct SubsystemStatus {
+    /// Name the subsystem was registered with.
+    pub name: String,
+    /// Current state of the subsystem.
+    pub state: SubsystemState,
+    /// Error of the last failed operation. Kept after later successes.
+    pub last_error: Option<String>,
+    /// Unix timestamp (seconds) of the last successful operation.
+    pub last_success: Option<u64>,
+    /// Last block processed successfully, for subsystems that follow a chain.
+    pub last_block: Option<u64>,
+}
+
+/// Handle of a registered subsystem to report the outcome of its operations.
+#[derive(Debug, Clone)]
+pub struct Subsystem(Arc<RwLock<SubsystemStatus>>);
+
+impl Subsystem {
+    /// Reports a successful operation.
+    pub fn succeeded(&self) {
+        let mut status = self.0.write();
+        status.state = SubsystemState::Healthy;
+        status.last_success = Some(unix_now());
+    }
+
+    /// Reports a successful operation that processed the chain up to `block`.
+    pub fn succeeded_at_block(&self, block: u64) {
+        self.succeeded();
+        self.0.write().last_block = Some(block);
+    }
+
+    /// Reports a failed operation.
+    pub fn failed(&self, err: impl fmt::Display) {
+        let mut status = self.0.write();
+        status.state = SubsystemState::Degraded;
+        status.last_error = Some(err.to_string());
+    }
+
+    /// Returns the current status.
+    #[must_use]
+    pub fn status(&self) -> SubsystemStatus {
+        self.0.read().clone()
+    }
+}
+
+/// The registered subsystems of a node, in registration order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SubsystemRegistry(Arc<RwLock<Vec<Subsystem>>>);
+
+impl SubsystemRegistry {
+    /// Registers a subsystem in [`SubsystemState::Starting`].
+    pub(crate) fn register(&self, name: impl Into<String>) -> Subsystem {
+        let subsystem = Subsystem(Arc::new(RwLock::new(SubsystemStatus {
+            name: name.into(),
+            ..SubsystemStatus::default()
+        })));
+        self.0.write().push(subsystem.clone());
+        subsystem
+    }
+
+    /// Returns the status of every subsystem, in registration order.
+    pub(crate) fn statuses(&self) -> Vec<SubsystemStatus> {
+        self.0.read().iter().map(Subsystem::status).collect()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,33 @@
+//! Opt-in capture of protocol transcripts.
+//!
+//! If `transcript_dir` is set in the [`crate::config::OprfNodeServiceConfig`], the node records the messages of every session as a [`Transcript`] (without the authentication part of the request) and writes it to `{transcript_dir}/{request_id}-node-{party_id}.json` when the session ends.
+
+use std::{path::PathBuf, sync::Arc};
+
+use oprf_types::transcript::Transcript;
+
+/// Writes [`Transcript`]s to a directory.
+#[derive(Clone)]
+pub(crate) struct TranscriptWriter(Arc<PathBuf>);
+
+impl TranscriptWriter {
+    /// Returns a writer for `transcript_dir`, `None` if capturing transcripts is disabled.
+    pub(crate) fn new(transcript_dir: Option<PathBuf>) -> Option<Self> {
+        transcript_dir.map(|dir| Self(Arc::new(dir)))
+    }
+
+    /// Writes the transcript. Failures are logged, as transcripts are for debugging only.
+    pub(crate) async fn write(&self, transcript: &Transcript) {
+        let path = self.0.join(transcript.file_name());
+        let json = match serde_json::to_vec_pretty(transcript) {
+            Ok(json) => json,
+            Err(err) => {
+                tracing::warn!(%err, "cannot serialize transcript");
+                return;
+            }
+        };
+        if let Err(err) = tokio::fs::write(&path, json).await {
+            tracing::warn!(%err, "cannot write transcript to {}", path.display());
+        }
+    }
+}
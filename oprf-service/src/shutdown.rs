@@ -0,0 +1,156 @@
+//! Structured shutdown of an OPRF node.
+//!
+//! Cancelling the `CancellationToken` of the background tasks stops them at once, while clients may still have sessions open. A [`Shutdown`] (see [`crate::OprfServiceBuilder::build_with_shutdown`]) instead stops the node in phases:
+//!
+//! 1. **Stop accepting:** new web-socket upgrades (and gRPC calls) are rejected with `503 Service Unavailable`. Multiplexed connections reject new sessions with close code `1012` (service restart) and close once their open sessions finished.
+//! 2. **Drain:** waits until all open connections finished their sessions, at most `max_wait_time_shutdown`. Sessions end at the latest after `session_lifetime`.
+//! 3. **Stop the background tasks:** cancels the `CancellationToken` passed to the builder, which stops the key event watchers (e.g. `OprfServiceBuilder::public_key_history`, requires the `registry` feature) and the other background tasks registered with that token, and waits for them, again at most `max_wait_time_shutdown`.
+//!
+//! The progress is logged and reported in the `taceo.oprf.node.shutdown.phase` and `taceo.oprf.node.shutdown.open_connections` metrics. The HTTP server itself is not stopped, the host shuts it down after [`Shutdown::run`] returned.
+
+use std::time::{Duration, Instant};
+
+use tokio_util::{
+    sync::CancellationToken,
+    task::{TaskTracker, task_tracker::TrackedFuture},
+};
+
+use crate::{ExitReason, OprfServiceTasks, metrics};
+
+/// Interval in which the progress of draining is reported.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The shutdown state shared with the OPRF modules.
+#[derive(Clone, Default)]
+pub(crate) struct ShutdownSignal {
+    draining: CancellationToken,
+    connections: TaskTracker,
+}
+
+impl ShutdownSignal {
+    /// Whether the node stopped accepting new sessions.
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.is_cancelled()
+    }
+
+    /// Resolves when the node stops accepting new sessions.
+    pub(crate) async fn draining(&self) {
+        self.draining.cancelled().await;
+    }
+
+    /// Tracks the connection, so the node waits for it while draining.
+    pub(crate) fn track<F: Future>(&self, connection: F) -> TrackedFuture<F> {
+        self.connections.track_future(connection)
+    }
+}
+
+/// The outcome of [`Shutdown::run`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ShutdownReport {
+    /// The number of connections that were still open after `max_wait_time_shutdown`.
+    pub open_connections: usize,
+    /// The number of background tasks that did not stop within `max_wait_time_shutdown`.
+    pub pending_tasks: usize,
+    /// The errors of the background tasks that crashed instead of stopping.
+    pub failed_tasks: Vec<eyre::Report>,
+}
+
+impl ShutdownReport {
+    /// Whether all connections drained and all background tasks stopped cleanly.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.open_connections == 0 && self.pending_tasks == 0 && self.failed_tasks.is_empty()
+    }
+}
+
+/// Shuts the node down in phases, see the [module docs](self).
+pub struct Shutdown {
+    signal: ShutdownSignal,
+    tasks: OprfServiceTasks,
+    cancellation_token: CancellationToken,
+}
+
+impl Shutdown {
+    pub(crate) fn new(
+        signal: ShutdownSignal,
+        tasks: OprfServiceTasks,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        Self {
+            signal,
+            tasks,
+            cancellation_token,
+        }
+    }
+
+    /// Stops accepting new sessions, drains the open connections and stops the background tasks, waiting at most `max_wait_time_shutdown` for each.
+    pub async fn run(self, max_wait_time_shutdown: Duration) -> ShutdownReport {
+        let start = Instant::now();
+        self.signal.draining.cancel();
+        self.signal.connections.close();
+        metrics::shutdown::phase(metrics::shutdown::Phase::Draining);
+        tracing::info!(
+            "stopped accepting sessions, draining {} connections (max wait time {max_wait_time_shutdown:?})..",
+            self.signal.connections.len()
+        );
+        let drained =
+            tokio::time::timeout(max_wait_time_shutdown, drain(&self.signal.connections)).await;
+        let open_connections = self.signal.connections.len();
+        metrics::shutdown::open_connections(open_connections);
+        if drained.is_ok() {
+            tracing::info!("drained all connections in {:?}", start.elapsed());
+        } else {
+            tracing::warn!(
+                "{open_connections} connections still open after {max_wait_time_shutdown:?}"
+            );
+        }
+
+        metrics::shutdown::phase(metrics::shutdown::Phase::StoppingTasks);
+        tracing::info!("stopping {} background tasks..", self.tasks.tasks.len());
+        self.cancellation_token.cancel();
+        let mut pending_tasks = 0;
+        let mut failed_tasks = Vec::new();
+        let deadline = tokio::time::Instant::now() + max_wait_time_shutdown;
+        for task in self.tasks.tasks {
+            match tokio::time::timeout_at(deadline, task).await {
+                Ok(Ok(ExitReason::Cancelled)) => {}
+                Ok(Ok(ExitReason::Failed(err))) => failed_tasks.push(err),
+                Ok(Err(err)) => failed_tasks.push(err.into()),
+                Err(_) => pending_tasks += 1,
+            }
+        }
+        if pending_tasks > 0 {
+            tracing::warn!("{pending_tasks} background tasks did not stop in time");
+        }
+        for err in &failed_tasks {
+            tracing::error!("background task failed: {err:?}");
+        }
+
+        metrics::shutdown::phase(metrics::shutdown::Phase::Stopped);
+        tracing::info!("shutdown finished in {:?}", start.elapsed());
+        ShutdownReport {
+            open_connections,
+            pending_tasks,
+            failed_tasks,
+        }
+    }
+}
+
+/// Waits until all tracked connections finished, reporting the number of open connections every [`PROGRESS_INTERVAL`].
+async fn drain(connections: &TaskTracker) {
+    let wait = connections.wait();
+    tokio::pin!(wait);
+    loop {
+        metrics::shutdown::open_connections(connections.len());
+        tokio::select! {
+            () = &mut wait => return,
+            () = tokio::time::sleep(PROGRESS_INTERVAL) => {
+                tracing::info!("waiting for {} connections to drain..", connections.len());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
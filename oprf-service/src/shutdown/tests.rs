@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use axum_test::TestServerBuilder;
+use tokio_util::sync::CancellationToken;
+
+use crate::{test_kit::MockAuthenticator, test_utils::builder};
+
+#[tokio::test]
+async fn shutdown_drains_sessions_before_stopping_tasks() {
+    let cancellation_token = CancellationToken::new();
+    let (router, shutdown) = builder()
+        .committee_health(
+            Vec::new(),
+            reqwest::Client::new(),
+            cancellation_token.clone(),
+        )
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build_with_shutdown(cancellation_token.clone())
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+
+    let shutdown = tokio::spawn(shutdown.run(Duration::from_secs(10)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .assert_status_service_unavailable();
+    assert!(
+        !cancellation_token.is_cancelled(),
+        "background tasks should run until the session drained"
+    );
+
+    ws.close().await;
+    let report = shutdown.await.expect("shutdown does not panic");
+    assert!(report.is_clean(), "should drain cleanly: {report:?}");
+    assert!(
+        cancellation_token.is_cancelled(),
+        "background tasks should be stopped"
+    );
+}
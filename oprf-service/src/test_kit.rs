@@ -0,0 +1,356 @@
+//! Mocks and helpers for testing applications that embed an OPRF node.
+//!
+//! Enabled with the `test-kit` feature. Do not use this in production.
+//!
+//! - [`MockAuthenticator`] is a configurable [`OprfRequestAuthenticator`] that allows or denies requests, optionally after a delay or at random with a fail rate.
+//! - [`StaticSecretManager`] is a [`SecretManager`] serving key material from memory and keeping the state of the node in memory.
+//! - [`test_router`] builds the router of a single node that clients can evaluate keys of the [`StaticSecretManager`] against, e.g. with `axum_test`.
+
+use std::{
+    collections::HashMap,
+    num::{NonZeroU16, NonZeroUsize},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use ark_babyjubjub::{EdwardsAffine, Fr};
+use ark_ec::{AffineRepr as _, CurveGroup as _};
+use async_trait::async_trait;
+use axum::Router;
+use nodes_common::StartedServices;
+use oprf_core::ddlog_equality::shamir::DLogShareShamir;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{
+        AuthCacheInvalidator, OprfPublicKeyWithEpoch, OprfRequest, OprfRequestAuthService,
+        OprfRequestAuthenticator, OprfRequestAuthenticatorError, ResponseSignature,
+    },
+    close_frame_message,
+    crypto::{OprfKeyMaterial, OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    Environment, OprfServiceBuilder,
+    config::OprfNodeServiceConfig,
+    secret_manager::{PartyIdBinding, SecretManager, SecretManagerError},
+};
+
+/// The close code of requests denied by a [`MockAuthenticator`], if not set with [`MockAuthenticator::with_error`].
+pub const MOCK_AUTH_DENIED: u16 = 4500;
+
+/// A configurable [`OprfRequestAuthenticator`] for tests.
+///
+/// Uses the [`OprfKeyId`] sent as `auth` of the [`OprfRequest`] as the key to evaluate. Denied requests are closed with [`MOCK_AUTH_DENIED`] unless configured otherwise.
+#[derive(Debug)]
+pub struct MockAuthenticator {
+    fail_rate: f64,
+    delay: Option<Duration>,
+    error: OprfRequestAuthenticatorError,
+    calls: AtomicUsize,
+    cache_ttl: Option<Duration>,
+    invalidator: AuthCacheInvalidator,
+}
+
+impl MockAuthenticator {
+    /// Allows every request.
+    #[must_use]
+    pub fn allow_all() -> Self {
+        Self::with_fail_rate(0.0)
+    }
+
+    /// Denies every request.
+    #[must_use]
+    pub fn deny_all() -> Self {
+        Self::with_fail_rate(1.0)
+    }
+
+    /// Denies requests at random with probability `fail_rate`.
+    ///
+    /// # Panics
+    /// Panics if `fail_rate` is not in `[0, 1]`.
+    #[must_use]
+    pub fn with_fail_rate(fail_rate: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&fail_rate),
+            "fail rate must be in [0, 1], got {fail_rate}"
+        );
+        Self {
+            fail_rate,
+            delay: None,
+            error: OprfRequestAuthenticatorError::with_message(
+                MOCK_AUTH_DENIED,
+                close_frame_message!("denied by mock authenticator"),
+            ),
+            calls: AtomicUsize::new(0),
+            cache_ttl: None,
+            invalidator: AuthCacheInvalidator::default(),
+        }
+    }
+
+    /// Waits for `delay` before every decision, e.g. to test auth timeouts.
+    #[must_use]
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Denies requests with `error` instead of [`MOCK_AUTH_DENIED`].
+    #[must_use]
+    pub fn with_error(mut self, error: OprfRequestAuthenticatorError) -> Self {
+        self.error = error;
+        self
+    }
+
+    /// Lets the node reuse an authentication for `ttl` for requests with the same `auth` on one connection, see [`OprfRequestAuthenticator::cacheable`].
+    #[must_use]
+    pub fn with_cache(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the [`AuthCacheInvalidator`] to drop the authentications cached so far.
+    #[must_use]
+    pub fn invalidator(&self) -> &AuthCacheInvalidator {
+        &self.invalidator
+    }
+
+    /// Returns the number of `authenticate` calls so far.
+    #[must_use]
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Wraps the authenticator as [`OprfRequestAuthService`] for [`OprfServiceBuilder::module`].
+    #[must_use]
+    pub fn into_service(self) -> OprfRequestAuthService<OprfKeyId> {
+        Arc::new(self)
+    }
+}
+
+#[async_trait]
+impl OprfRequestAuthenticator for MockAuthenticator {
+    type RequestAuth = OprfKeyId;
+
+    async fn authenticate(
+        &self,
+        request: &OprfRequest<Self::RequestAuth>,
+    ) -> Result<OprfKeyId, OprfRequestAuthenticatorError> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+        if rand::thread_rng().gen_bool(self.fail_rate) {
+            Err(self.error.clone())
+        } else {
+            Ok(request.auth)
+        }
+    }
+
+    fn cacheable(&self) -> Option<Duration> {
+        self.cache_ttl
+    }
+
+    fn cache_key(&self, request: &OprfRequest<Self::RequestAuth>) -> Option<Vec<u8>> {
+        Some(request.auth.to_string().into_bytes())
+    }
+
+    fn cache_invalidator(&self) -> Option<&AuthCacheInvalidator> {
+        Some(&self.invalidator)
+    }
+}
+
+/// A [`SecretManager`] serving node information and key material from memory.
+///
+/// The state the node persists (party id binding, public key history, event watcher blocks) is kept in memory and shared between clones. Responses cannot be signed, as there is no wallet key.
+#[derive(Debug, Clone)]
+pub struct StaticSecretManager {
+    node_information: NodeInformation,
+    keys: HashMap<OprfKeyId, OprfKeyMaterial>,
+    state: Arc<parking_lot::Mutex<NodeState>>,
+}
+
+/// The state persisted by the node in a [`StaticSecretManager`].
+#[derive(Debug, Default)]
+struct NodeState {
+    party_id_binding: Option<PartyIdBinding>,
+    public_keys: HashMap<OprfKeyId, Vec<OprfPublicKeyWithEpoch>>,
+    event_watcher_blocks: HashMap<String, u64>,
+    delivered_epochs: HashMap<OprfKeyId, ShareEpoch>,
+}
+
+impl StaticSecretManager {
+    /// Creates a secret manager without keys for the node with `node_information`.
+    #[must_use]
+    pub fn new(node_information: NodeInformation) -> Self {
+        Self {
+            node_information,
+            keys: HashMap::new(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Creates a secret manager without keys for party 0 of a single-node deployment (threshold 1).
+    ///
+    /// With a single node, the share of a key is the secret, so clients can evaluate keys against this node alone.
+    #[must_use]
+    pub fn single_node() -> Self {
+        Self::new(NodeInformation::new(
+            PartyId(0),
+            "0x0000000000000000000000000000000000000000".to_owned(),
+            NonZeroU16::MIN,
+        ))
+    }
+
+    /// Adds the key material of `oprf_key_id`.
+    #[must_use]
+    pub fn with_key(mut self, oprf_key_id: OprfKeyId, key_material: OprfKeyMaterial) -> Self {
+        self.keys.insert(oprf_key_id, key_material);
+        self
+    }
+
+    /// Adds a random single-node key for `oprf_key_id`, i.e., the share is the secret of the public key.
+    #[must_use]
+    pub fn with_random_key<R: Rng + CryptoRng>(self, oprf_key_id: OprfKeyId, rng: &mut R) -> Self {
+        let secret: Fr = rng.r#gen();
+        let public_key = (EdwardsAffine::generator() * secret).into_affine();
+        self.with_key(
+            oprf_key_id,
+            OprfKeyMaterial::new(
+                DLogShareShamir::from(secret),
+                OprfPublicKey::new(public_key),
+                ShareEpoch::default(),
+            ),
+        )
+    }
+}
+
+#[async_trait]
+impl SecretManager for StaticSecretManager {
+    async fn load_node_information(&self) -> eyre::Result<NodeInformation> {
+        Ok(self.node_information.clone())
+    }
+
+    async fn get_oprf_key_material(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> Result<OprfKeyMaterial, SecretManagerError> {
+        self.keys
+            .get(&oprf_key_id)
+            .cloned()
+            .ok_or(SecretManagerError::UnknownOprfKeyId(oprf_key_id))
+    }
+
+    async fn load_party_id_binding(&self) -> eyre::Result<Option<PartyIdBinding>> {
+        Ok(self.state.lock().party_id_binding.clone())
+    }
+
+    async fn store_party_id_binding(&self, binding: &PartyIdBinding) -> eyre::Result<()> {
+        self.state.lock().party_id_binding = Some(binding.clone());
+        Ok(())
+    }
+
+    async fn load_public_key_history(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> eyre::Result<Vec<OprfPublicKeyWithEpoch>> {
+        Ok(self
+            .state
+            .lock()
+            .public_keys
+            .get(&oprf_key_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn record_public_key(
+        &self,
+        oprf_key_id: OprfKeyId,
+        public_key: OprfPublicKeyWithEpoch,
+        retention: NonZeroUsize,
+    ) -> eyre::Result<()> {
+        let mut state = self.state.lock();
+        let history = state.public_keys.entry(oprf_key_id).or_default();
+        history.retain(|recorded| recorded.epoch != public_key.epoch);
+        history.push(public_key);
+        history.sort_by_key(|recorded| recorded.epoch);
+        let outdated = history.len().saturating_sub(retention.get());
+        history.drain(..outdated);
+        Ok(())
+    }
+
+    async fn load_event_watcher_block(&self, contract: &str) -> eyre::Result<Option<u64>> {
+        Ok(self
+            .state
+            .lock()
+            .event_watcher_blocks
+            .get(&contract.to_ascii_lowercase())
+            .copied())
+    }
+
+    async fn store_event_watcher_block(&self, contract: &str, block: u64) -> eyre::Result<()> {
+        self.state
+            .lock()
+            .event_watcher_blocks
+            .insert(contract.to_ascii_lowercase(), block);
+        Ok(())
+    }
+
+    async fn load_delivered_epochs(&self) -> eyre::Result<HashMap<OprfKeyId, ShareEpoch>> {
+        Ok(self.state.lock().delivered_epochs.clone())
+    }
+
+    async fn store_delivered_epoch(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> eyre::Result<()> {
+        self.state
+            .lock()
+            .delivered_epochs
+            .insert(oprf_key_id, epoch);
+        Ok(())
+    }
+
+    async fn sign_response(&self, message: &[u8]) -> eyre::Result<ResponseSignature> {
+        eyre::bail!(
+            "static secret manager cannot sign responses ({} bytes)",
+            message.len()
+        )
+    }
+}
+
+/// Returns the config of a test node: [`Environment::Dev`], accepting every client version and default values otherwise.
+#[must_use]
+pub fn test_config() -> OprfNodeServiceConfig {
+    OprfNodeServiceConfig::with_default_values(Environment::Dev, semver::VersionReq::STAR)
+}
+
+/// Builds the router of a test node with [`test_config`] and a single OPRF module at `path`.
+///
+/// # Panics
+/// Panics if `path` is not a valid module path.
+pub fn test_router(
+    secret_manager: StaticSecretManager,
+    path: &str,
+    auth: OprfRequestAuthService<OprfKeyId>,
+) -> Router {
+    let node_information = secret_manager.node_information.clone();
+    OprfServiceBuilder::init(
+        test_config(),
+        Arc::new(secret_manager),
+        StartedServices::default(),
+        &node_information,
+        "test".to_owned(),
+    )
+    .module(path, auth)
+    .build()
+    .expect("valid module path")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use ark_ec::AffineRepr as _;
+use axum_test::TestServerBuilder;
+use oprf_types::{
+    OprfKeyId,
+    api::{OprfRequest, OprfResponse},
+    crypto::PartyId,
+};
+use uuid::Uuid;
+
+use crate::test_kit::{MOCK_AUTH_DENIED, MockAuthenticator, StaticSecretManager, test_router};
+
+#[tokio::test]
+async fn test_kit_mock_authenticator() {
+    let oprf_key_id = OprfKeyId::from(42usize);
+    let secret_manager =
+        StaticSecretManager::single_node().with_random_key(oprf_key_id, &mut rand::thread_rng());
+    let auth = Arc::new(MockAuthenticator::deny_all());
+    let router = test_router(secret_manager.clone(), "/test", auth.clone());
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: oprf_key_id,
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        MOCK_AUTH_DENIED,
+        "should deny the request"
+    );
+    assert_eq!(auth.calls(), 1, "should count the call");
+
+    let router = test_router(
+        secret_manager,
+        "/test",
+        MockAuthenticator::allow_all().into_service(),
+    );
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    let mut ws = server
+        .get_websocket("/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: oprf_key_id,
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let response = ws.receive_json::<OprfResponse>().await;
+    assert_eq!(response.party_id, PartyId(0), "should answer as party 0");
+}
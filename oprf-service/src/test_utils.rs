@@ -0,0 +1,255 @@
+//! Helpers shared by the tests of this crate.
+
+use std::{
+    collections::HashMap,
+    num::{NonZeroU16, NonZeroUsize},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use ark_ec::{AffineRepr as _, CurveGroup as _};
+use async_trait::async_trait;
+use oprf_core::ddlog_equality::shamir::{DLogCommitmentsShamir, DLogShareShamir};
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{OprfPublicKeyWithEpoch, ResponseSignature},
+    crypto::{OprfKeyMaterial, OprfPublicKey, PartyId},
+    service::NodeInformation,
+};
+
+use crate::{
+    Environment, OprfServiceBuilder, StartedServices,
+    config::OprfNodeServiceConfig,
+    secret_manager::{PartyIdBinding, SecretManager, SecretManagerError, SecretManagerService},
+};
+
+/// A configurable [`SecretManager`] for the tests of this crate.
+///
+/// Knows no key unless configured otherwise, counts the key lookups and keeps the party id binding and the delivered epochs in memory.
+#[derive(Default)]
+pub(crate) struct MockSecretManager {
+    node_information: Option<NodeInformation>,
+    key_material: parking_lot::Mutex<Option<OprfKeyMaterial>>,
+    wallet: Option<k256::ecdsa::SigningKey>,
+    binding: Option<parking_lot::Mutex<Option<PartyIdBinding>>>,
+    lookups: AtomicUsize,
+    delivered_epochs: parking_lot::Mutex<HashMap<OprfKeyId, ShareEpoch>>,
+}
+
+impl MockSecretManager {
+    /// Returns the same key material with `epoch` for every key id: the share is 42 and the public key is the generator.
+    pub(crate) fn fixed_key(epoch: ShareEpoch) -> Self {
+        Self {
+            key_material: parking_lot::Mutex::new(Some(fixed_key_material(epoch))),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the key of a single-node deployment for every key id, i.e., the share is `secret`.
+    pub(crate) fn single_node(secret: ark_babyjubjub::Fr) -> Self {
+        Self {
+            key_material: parking_lot::Mutex::new(Some(OprfKeyMaterial::new(
+                DLogShareShamir::from(secret),
+                OprfPublicKey::new(
+                    (ark_babyjubjub::EdwardsAffine::generator() * secret).into_affine(),
+                ),
+                ShareEpoch::default(),
+            ))),
+            ..Self::default()
+        }
+    }
+
+    /// Stores the key material with its own `threshold`.
+    pub(crate) fn with_threshold(mut self, threshold: u16) -> Self {
+        let threshold = NonZeroU16::new(threshold).expect("threshold is non-zero");
+        let key_material = self.key_material.get_mut();
+        *key_material = key_material
+            .take()
+            .map(|key_material| key_material.with_threshold(threshold));
+        self
+    }
+
+    /// Returns the key material of [`Self::fixed_key`] with `epoch` from now on, as if the key was reshared.
+    pub(crate) fn reshare(&self, epoch: ShareEpoch) {
+        *self.key_material.lock() = Some(fixed_key_material(epoch));
+    }
+
+    /// Returns `node_information` instead of failing to load it.
+    pub(crate) fn with_node_information(mut self, node_information: NodeInformation) -> Self {
+        self.node_information = Some(node_information);
+        self
+    }
+
+    /// Signs responses with `wallet` instead of failing to sign them.
+    pub(crate) fn with_wallet(mut self, wallet: k256::ecdsa::SigningKey) -> Self {
+        self.wallet = Some(wallet);
+        self
+    }
+
+    /// Persists the party id binding instead of failing to store it.
+    pub(crate) fn with_binding_store(mut self) -> Self {
+        self.binding = Some(parking_lot::Mutex::default());
+        self
+    }
+
+    /// Starts with `epoch` of `oprf_key_id` as delivered to the epoch transition hook, as if delivered before a restart.
+    pub(crate) fn with_delivered_epoch(
+        mut self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> Self {
+        self.delivered_epochs.get_mut().insert(oprf_key_id, epoch);
+        self
+    }
+
+    /// Returns the epochs persisted as delivered to the epoch transition hook.
+    pub(crate) fn delivered_epochs(&self) -> HashMap<OprfKeyId, ShareEpoch> {
+        self.delivered_epochs.lock().clone()
+    }
+
+    /// Returns the number of key lookups so far.
+    pub(crate) fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::SeqCst)
+    }
+
+    /// Returns the persisted party id binding.
+    pub(crate) fn binding(&self) -> Option<PartyIdBinding> {
+        self.binding
+            .as_ref()
+            .and_then(|binding| binding.lock().clone())
+    }
+}
+
+#[async_trait]
+impl SecretManager for MockSecretManager {
+    async fn load_node_information(&self) -> eyre::Result<NodeInformation> {
+        self.node_information
+            .clone()
+            .ok_or_else(|| eyre::eyre!("no node information in mock"))
+    }
+
+    async fn get_oprf_key_material(
+        &self,
+        oprf_key_id: OprfKeyId,
+    ) -> Result<OprfKeyMaterial, SecretManagerError> {
+        self.lookups.fetch_add(1, Ordering::SeqCst);
+        self.key_material
+            .lock()
+            .clone()
+            .ok_or(SecretManagerError::UnknownOprfKeyId(oprf_key_id))
+    }
+
+    async fn load_party_id_binding(&self) -> eyre::Result<Option<PartyIdBinding>> {
+        Ok(self.binding())
+    }
+
+    async fn store_party_id_binding(&self, binding: &PartyIdBinding) -> eyre::Result<()> {
+        let Some(stored) = &self.binding else {
+            eyre::bail!("cannot store the party id binding in mock");
+        };
+        *stored.lock() = Some(binding.clone());
+        Ok(())
+    }
+
+    async fn load_public_key_history(
+        &self,
+        _oprf_key_id: OprfKeyId,
+    ) -> eyre::Result<Vec<OprfPublicKeyWithEpoch>> {
+        Ok(Vec::new())
+    }
+
+    async fn record_public_key(
+        &self,
+        _oprf_key_id: OprfKeyId,
+        _public_key: OprfPublicKeyWithEpoch,
+        _retention: NonZeroUsize,
+    ) -> eyre::Result<()> {
+        eyre::bail!("cannot record public keys in mock");
+    }
+
+    async fn load_event_watcher_block(&self, _contract: &str) -> eyre::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    async fn store_event_watcher_block(&self, _contract: &str, _block: u64) -> eyre::Result<()> {
+        eyre::bail!("cannot store event watcher blocks in mock");
+    }
+
+    async fn load_delivered_epochs(&self) -> eyre::Result<HashMap<OprfKeyId, ShareEpoch>> {
+        Ok(self.delivered_epochs())
+    }
+
+    async fn store_delivered_epoch(
+        &self,
+        oprf_key_id: OprfKeyId,
+        epoch: ShareEpoch,
+    ) -> eyre::Result<()> {
+        self.delivered_epochs.lock().insert(oprf_key_id, epoch);
+        Ok(())
+    }
+
+    async fn sign_response(&self, message: &[u8]) -> eyre::Result<ResponseSignature> {
+        let Some(wallet) = &self.wallet else {
+            eyre::bail!("cannot sign responses in mock");
+        };
+        Ok(ResponseSignature::sign(wallet, message))
+    }
+}
+
+pub(crate) fn builder_with_config(config: OprfNodeServiceConfig) -> OprfServiceBuilder {
+    builder_with_secret_manager(config, Arc::new(MockSecretManager::default()))
+}
+
+pub(crate) fn builder_with_secret_manager(
+    config: OprfNodeServiceConfig,
+    secret_manager: SecretManagerService,
+) -> OprfServiceBuilder {
+    OprfServiceBuilder::init(
+        config,
+        secret_manager,
+        StartedServices::default(),
+        &NodeInformation::new(
+            PartyId(0),
+            "0x0000000000000000000000000000000000000000".to_owned(),
+            NonZeroU16::new(2).expect("2 is non-zero"),
+        ),
+        "test".to_owned(),
+    )
+}
+
+pub(crate) fn default_config() -> OprfNodeServiceConfig {
+    OprfNodeServiceConfig::with_default_values(
+        Environment::Dev,
+        "*".parse().expect("valid version req"),
+    )
+}
+
+pub(crate) fn builder() -> OprfServiceBuilder {
+    builder_with_config(default_config())
+}
+
+/// The key material of [`MockSecretManager::fixed_key`] with `epoch`.
+pub(crate) fn fixed_key_material(epoch: ShareEpoch) -> OprfKeyMaterial {
+    OprfKeyMaterial::new(
+        DLogShareShamir::from(ark_babyjubjub::Fr::from(42)),
+        OprfPublicKey::new(ark_babyjubjub::EdwardsAffine::generator()),
+        epoch,
+    )
+}
+
+/// A challenge for party 0 with a threshold of 2, distinguished by `scalar`.
+pub(crate) fn challenge(scalar: u64) -> DLogCommitmentsShamir {
+    let point = |scalar: u64| {
+        (ark_babyjubjub::EdwardsAffine::generator() * ark_babyjubjub::Fr::from(scalar)).into()
+    };
+    DLogCommitmentsShamir::new(
+        point(scalar),
+        point(2),
+        point(3),
+        point(4),
+        point(5),
+        vec![1, 2],
+    )
+}
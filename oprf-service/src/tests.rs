@@ -0,0 +1,336 @@
+use std::{num::NonZeroU16, sync::Arc};
+
+use ark_ec::AffineRepr as _;
+use axum_test::TestServerBuilder;
+use oprf_types::{
+    OprfKeyId, ShareEpoch,
+    api::{OprfRequest, oprf_error_codes},
+    crypto::PartyId,
+    service::NodeInformation,
+};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    BuilderError, Environment, ExitReason, OprfServiceBuilder, SessionNamespace, StartedServices,
+    config::OprfNodeServiceConfig,
+    session_store::{LocalSessionStore, OprfSessionStore as _, OprfSessionStoreError},
+    test_kit::MockAuthenticator,
+    test_utils::{MockSecretManager, builder, builder_with_config, default_config},
+};
+
+#[test]
+fn build_with_module() {
+    let router = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build with one module");
+    assert!(router.has_routes(), "router should have routes");
+}
+
+#[test]
+fn build_without_module() {
+    let err = builder().build().expect_err("Should fail without modules");
+    assert!(
+        matches!(err, BuilderError::NoModules),
+        "expected NoModules, got {err:?}"
+    );
+}
+
+#[test]
+fn build_with_invalid_module_path() {
+    for path in ["", "/", "test", "/{*rest}"] {
+        let err = builder()
+            .module(path, MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect_err("Should fail with invalid path");
+        assert!(
+            matches!(err, BuilderError::InvalidModulePath(_)),
+            "expected InvalidModulePath for {path:?}, got {err:?}"
+        );
+    }
+}
+
+#[test]
+fn build_with_duplicate_module_path() {
+    let err = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .module("/test/", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect_err("Should fail with duplicate path");
+    assert!(
+        matches!(err, BuilderError::DuplicateModulePath(_)),
+        "expected DuplicateModulePath, got {err:?}"
+    );
+}
+
+#[test]
+fn build_with_invalid_config() {
+    let mut config =
+        OprfNodeServiceConfig::with_default_values(Environment::Dev, semver::VersionReq::STAR);
+    config.ws_max_message_size = 0;
+    let err = builder_with_config(config)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect_err("Should fail with invalid config");
+    assert!(
+        matches!(err, BuilderError::InvalidConfig(_)),
+        "expected InvalidConfig, got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn isolated_session_namespace() {
+    let shared = LocalSessionStore::new();
+    let isolated = LocalSessionStore::new();
+    let request_id = Uuid::new_v4();
+
+    shared
+        .reserve(request_id)
+        .await
+        .expect("first use succeeds");
+    assert!(
+        matches!(
+            shared.clone().reserve(request_id).await,
+            Err(OprfSessionStoreError::SessionReuse(_))
+        ),
+        "shared namespace rejects reuse"
+    );
+    assert!(
+        isolated.reserve(request_id).await.is_ok(),
+        "isolated namespace does not see the shared session"
+    );
+    shared.release(request_id);
+    assert!(
+        shared.reserve(request_id).await.is_ok(),
+        "released session can be reserved again"
+    );
+
+    let router = builder()
+        .module("/shared", MockAuthenticator::allow_all().into_service())
+        .module_with_session_namespace(
+            "/isolated",
+            MockAuthenticator::allow_all().into_service(),
+            SessionNamespace::Isolated,
+        )
+        .build()
+        .expect("Can build with isolated module");
+    assert!(router.has_routes(), "router should have routes");
+}
+
+#[tokio::test]
+async fn tasks_report_exit_reason() {
+    let (_, tasks) = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build_with_tasks()
+        .expect("Can build");
+    assert!(tasks.run().await.is_none(), "no background task spawned");
+
+    let cancellation_token = CancellationToken::new();
+    let (_, tasks) = builder()
+        .committee_health(
+            Vec::new(),
+            reqwest::Client::new(),
+            cancellation_token.clone(),
+        )
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build_with_tasks()
+        .expect("Can build");
+    cancellation_token.cancel();
+    let exit_reason = tasks.run().await;
+    assert!(
+        matches!(exit_reason, Some(ExitReason::Cancelled)),
+        "committee health task should be cancelled, got {exit_reason:?}"
+    );
+}
+
+#[tokio::test]
+async fn root_path_prefixes_all_routes() {
+    let mut config = default_config();
+    config.root_path = Some("/oprf-node".to_owned());
+    let router = builder_with_config(config)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+
+    server.get("/oprf-node/health").await.assert_status_ok();
+    server.get("/oprf-node/version").await.assert_status_ok();
+    server
+        .get("/oprf-node/api/test/params")
+        .await
+        .assert_status_ok();
+    server.get("/health").await.assert_status_not_found();
+    server
+        .get("/api/test/params")
+        .await
+        .assert_status_not_found();
+
+    let mut ws = server
+        .get_websocket("/oprf-node/api/test/oprf?version=1.0.0")
+        .await
+        .into_websocket()
+        .await;
+    ws.send_json(&OprfRequest {
+        request_id: Uuid::new_v4(),
+        blinded_query: ark_babyjubjub::EdwardsAffine::generator(),
+        auth: OprfKeyId::from(42usize),
+        issued_at: None,
+        batch: Vec::new(),
+    })
+    .await;
+    let tungstenite::Message::Close(Some(frame)) = ws.receive_message().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(
+        u16::from(frame.code),
+        oprf_error_codes::UNKNOWN_OPRF_KEY_ID,
+        "should run the session below the root path"
+    );
+}
+
+#[test]
+fn invalid_root_path_is_rejected() {
+    for root_path in ["oprf-node", "/oprf-node/", "/", "/oprf//node"] {
+        let mut config = default_config();
+        config.root_path = Some(root_path.to_owned());
+        let err = builder_with_config(config)
+            .module("/test", MockAuthenticator::allow_all().into_service())
+            .build()
+            .expect_err("should fail");
+        assert!(
+            matches!(err, BuilderError::InvalidConfig(_)),
+            "should reject {root_path}, got {err:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn module_context_after_module() {
+    let err = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .module_context("/test", "relying-party")
+        .build()
+        .expect_err("should reject a context for an added module");
+    assert!(
+        matches!(err, BuilderError::InvalidConfig(_)),
+        "expected InvalidConfig, got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn load_preloads_configured_keys() {
+    let secret_manager = Arc::new(
+        MockSecretManager::fixed_key(ShareEpoch::new(1)).with_node_information(
+            NodeInformation::new(
+                PartyId(1),
+                "0x0000000000000000000000000000000000000001".to_owned(),
+                NonZeroU16::new(2).expect("2 is non-zero"),
+            ),
+        ),
+    );
+    let oprf_key_id = OprfKeyId::from(42usize);
+    let mut config = default_config();
+    config.preload_oprf_key_ids = vec![oprf_key_id, OprfKeyId::from(43usize)];
+    let builder = OprfServiceBuilder::load(
+        config,
+        Arc::clone(&secret_manager) as _,
+        StartedServices::default(),
+        "test".to_owned(),
+    )
+    .await
+    .expect("Can load");
+    assert_eq!(builder.party_id, PartyId(1), "should use loaded party id");
+    assert_eq!(secret_manager.lookups(), 2, "should preload both keys");
+
+    let router = builder
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .build(router)
+        .expect("Can build test-server");
+    server
+        .get(&format!("/oprf_pub/{oprf_key_id}"))
+        .await
+        .assert_status_ok();
+    assert_eq!(
+        secret_manager.lookups(),
+        2,
+        "should serve preloaded key from the store"
+    );
+}
+
+#[tokio::test]
+async fn load_fails_without_node_information() {
+    let err = OprfServiceBuilder::load(
+        default_config(),
+        Arc::new(MockSecretManager::default()),
+        StartedServices::default(),
+        "test".to_owned(),
+    )
+    .await
+    .err()
+    .expect("should fail");
+    assert_eq!(
+        err.to_string(),
+        "while loading node information",
+        "should report missing node information"
+    );
+}
+
+#[test]
+fn session_store_must_be_set_before_modules() {
+    let err = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .session_store(Arc::new(LocalSessionStore::new()))
+        .build()
+        .expect_err("should fail");
+    assert!(
+        matches!(err, BuilderError::InvalidConfig(_)),
+        "Should be InvalidConfig but is {err}"
+    );
+    let router = builder()
+        .session_store(Arc::new(LocalSessionStore::new()))
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build with custom session store");
+    assert!(router.has_routes(), "router should have routes");
+}
+
+#[tokio::test]
+async fn metrics_endpoint_serves_prometheus_metrics() {
+    let router = builder()
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    server
+        .get("/metrics")
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+
+    let mut config = default_config();
+    config.metrics_endpoint = true;
+    let router = builder_with_config(config)
+        .module("/test", MockAuthenticator::allow_all().into_service())
+        .build()
+        .expect("Can build");
+    let server = TestServerBuilder::new()
+        .http_transport()
+        .build(router)
+        .expect("Can build test-server");
+    crate::metrics::committee::set_healthy_peers(3);
+    let metrics = server.get("/metrics").await.text();
+    assert!(
+        metrics.contains("taceo_oprf_node_committee_healthy_peers 3"),
+        "should render recorded metrics, got {metrics}"
+    );
+}
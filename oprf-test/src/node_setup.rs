@@ -1,5 +1,5 @@
 use core::fmt;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::num::NonZeroU16;
 use std::{sync::Arc, time::Duration};
 
@@ -13,14 +13,15 @@ use nodes_common::{Environment, StartedServices, postgres::CreateSchema};
 use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use sqlx::{PgPool, migrate::Migrator};
-use taceo_oprf::client::Connector;
+use taceo_oprf::client::{Connector, OprfSessions};
 use taceo_oprf::core::{
     ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir, DLogShareShamir},
     oprf::BlindingFactor,
 };
 use taceo_oprf::service::{
     OprfServiceBuilder,
-    config::OprfNodeServiceConfig,
+    config::{ChaosConfig, CloseFrameVerbosity, OprfNodeServiceConfig},
+    listener::local_base_url,
     secret_manager::{SecretManager as _, postgres::PostgresSecretManager},
 };
 use taceo_oprf::types::{
@@ -97,6 +98,18 @@ impl fmt::Debug for TestNode {
     }
 }
 
+/// The config of all test nodes: a node in [`Environment::Dev`] with the default session lifetime of 10 seconds.
+fn test_config() -> OprfNodeServiceConfig {
+    let mut config = OprfNodeServiceConfig::with_default_values(
+        Environment::Dev,
+        taceo_oprf::client::VERSION.parse().expect("valid semver"),
+    );
+    config.session_lifetime = Duration::from_secs(10);
+    // the tests check the close frames clients see in production
+    config.close_frame_verbosity = Some(CloseFrameVerbosity::Generic);
+    config
+}
+
 impl TestNode {
     async fn create_websocket(&self) -> TestWebSocket {
         self.server
@@ -123,13 +136,59 @@ impl TestNode {
         session_lifetime: Duration,
         threshold: NonZeroU16,
     ) -> Self {
-        assert!(party_id < 5, "can only spawn 5 nodes");
-
-        let mut config = OprfNodeServiceConfig::with_default_values(
-            Environment::Dev,
-            taceo_oprf::client::VERSION.parse().expect("valid semver"),
-        );
+        let mut config = test_config();
         config.session_lifetime = session_lifetime;
+        Self::start_with_config(
+            party_id,
+            pool,
+            bind_port,
+            services,
+            secret_manager,
+            threshold,
+            config,
+        )
+    }
+
+    /// Like [`TestNode::start_with_secret_manager`], but every request to the OPRF module is delayed by `latency`, simulating the round trip to a node in a distant region.
+    ///
+    /// A `latency` of zero starts the node without delay.
+    pub fn start_with_latency(
+        party_id: usize,
+        pool: PgPool,
+        bind_port: u16,
+        services: Option<Vec<Uri>>,
+        secret_manager: PostgresSecretManager,
+        threshold: NonZeroU16,
+        latency: Duration,
+    ) -> Self {
+        let mut config = test_config();
+        if !latency.is_zero() {
+            let mut chaos = ChaosConfig::default();
+            chaos.latency = latency;
+            chaos.latency_rate = 1.0;
+            config.chaos = Some(chaos);
+        }
+        Self::start_with_config(
+            party_id,
+            pool,
+            bind_port,
+            services,
+            secret_manager,
+            threshold,
+            config,
+        )
+    }
+
+    fn start_with_config(
+        party_id: usize,
+        pool: PgPool,
+        bind_port: u16,
+        services: Option<Vec<Uri>>,
+        secret_manager: PostgresSecretManager,
+        threshold: NonZeroU16,
+        config: OprfNodeServiceConfig,
+    ) -> Self {
+        assert!(party_id < 5, "can only spawn 5 nodes");
 
         let started_services = StartedServices::new();
         let secret_manager = Arc::new(secret_manager);
@@ -150,7 +209,8 @@ impl TestNode {
             services.unwrap_or_default(), // we dont care about delegate if services is None
             Connector::Plain,
         )
-        .build();
+        .build()
+        .expect("Can build oprf service");
         let server = TestServer::builder()
             .http_transport_with_ip_port(Some(IpAddr::V4(Ipv4Addr::LOCALHOST)), Some(bind_port))
             .build(service)
@@ -375,15 +435,27 @@ fn generate_shamir_shares<R: Rng + CryptoRng>(
 pub async fn start_nodes_for_delegate(
     setup: DeploySetup,
     key_id: OprfKeyId,
+) -> eyre::Result<Vec<TestNode>> {
+    start_nodes_with_latencies(setup, key_id, &vec![Duration::ZERO; setup.num_peers()]).await
+}
+
+/// Like [`start_nodes_for_delegate`], but the requests to the OPRF module of node `i` are
+/// delayed by `latencies[i]`, e.g. the [`DeploySetup::multi_region_latencies`]. Use
+/// [`assert_selected`] to check which nodes a client picked.
+pub async fn start_nodes_with_latencies(
+    setup: DeploySetup,
+    key_id: OprfKeyId,
+    latencies: &[Duration],
 ) -> eyre::Result<Vec<TestNode>> {
     let n = setup.num_peers();
+    assert_eq!(latencies.len(), n, "need a latency for every node");
     let threshold = usize::from(u16::from(setup.threshold()));
     let ports: Vec<u16> = (0..n)
         .map(|_| nodes_common::test_utils::random_port().expect("Can find random port"))
         .collect();
     let base_urls: Vec<String> = ports
         .iter()
-        .map(|port| format!("http://127.0.0.1:{port}"))
+        .map(|port| local_base_url(SocketAddr::from((Ipv4Addr::LOCALHOST, *port))))
         .collect();
     let services = taceo_oprf::client::to_oprf_uri_many(&base_urls, "test")?;
 
@@ -391,17 +463,17 @@ pub async fn start_nodes_for_delegate(
     let (public_key, shares) = generate_shamir_shares(threshold, n, &mut rand::thread_rng());
 
     let mut nodes = Vec::with_capacity(n);
-    for (party_id, port) in ports.into_iter().enumerate() {
+    for (party_id, (port, latency)) in ports.into_iter().zip(latencies).enumerate() {
         let (pool, secret_manager) = migrated_pool_and_secret_manager().await?;
 
-        let node = TestNode::start_with_secret_manager(
+        let node = TestNode::start_with_latency(
             party_id,
             pool,
             port,
             Some(services.clone()),
             secret_manager,
-            Duration::from_secs(10),
             setup.threshold(),
+            *latency,
         );
         node.add_key_material_with_id_epoch_and_share(
             key_id,
@@ -415,6 +487,28 @@ pub async fn start_nodes_for_delegate(
     Ok(nodes)
 }
 
+/// Returns the party ids of the `threshold` nodes with the smallest `latencies`, sorted.
+pub fn fastest_parties(latencies: &[Duration], threshold: NonZeroU16) -> Vec<PartyId> {
+    let mut parties = (0..latencies.len()).collect::<Vec<_>>();
+    parties.sort_by_key(|party_id| latencies[*party_id]);
+    let mut fastest = parties
+        .into_iter()
+        .take(usize::from(threshold.get()))
+        .map(|party_id| PartyId(u16::try_from(party_id).expect("party id must be u16")))
+        .collect::<Vec<_>>();
+    fastest.sort_unstable();
+    fastest
+}
+
+/// Asserts that the client opened its `sessions` with exactly the nodes `expected`, regardless of the order.
+pub fn assert_selected(sessions: &OprfSessions, expected: &[PartyId]) {
+    let mut selected = sessions.party_ids().to_vec();
+    selected.sort_unstable();
+    let mut expected = expected.to_vec();
+    expected.sort_unstable();
+    assert_eq!(selected, expected, "client selected unexpected nodes");
+}
+
 pub fn assert_close_frame(is_message: tungstenite::Message, should_close_frame: &CloseFrame) {
     match is_message {
         tungstenite::Message::Close(Some(is_close_frame)) => {
@@ -439,6 +533,8 @@ pub fn request_with_id<R: Rng + CryptoRng>(
         request_id: Uuid::new_v4(),
         blinded_query: blinded_request.blinded_query(),
         auth: ConfigurableTestRequestAuth(oprf_key_id),
+        issued_at: Some(taceo_oprf::client::unix_timestamp()),
+        batch: Vec::new(),
     }
 }
 
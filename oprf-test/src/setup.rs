@@ -1,4 +1,4 @@
-use std::{num::NonZeroU16, path::PathBuf};
+use std::{num::NonZeroU16, path::PathBuf, time::Duration};
 
 use alloy::{
     eips::BlockNumberOrTag,
@@ -25,6 +25,16 @@ use crate::{TEST_TIMEOUT, to_db_ark_serialize_uncompressed};
 /// number of peer key/address slots [`TestSetup`] derives from Anvil.
 const MAX_PEERS: usize = 5;
 
+/// Artificial round trip times of the peers in [`DeploySetup::multi_region_latencies`]. The gaps
+/// are large enough that the order in which the nodes answer is stable on slow CI runners.
+const MULTI_REGION_LATENCIES: [Duration; MAX_PEERS] = [
+    Duration::from_millis(400),
+    Duration::from_millis(10),
+    Duration::from_millis(800),
+    Duration::from_millis(200),
+    Duration::from_millis(600),
+];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DeploySetup {
     TwoThree,
@@ -100,6 +110,13 @@ impl DeploySetup {
         }
     }
 
+    /// Distinct artificial round trip times for the nodes of the setup, simulating nodes spread
+    /// over multiple regions. Node `i` gets the `i`-th latency, see
+    /// [`crate::node_setup::start_nodes_with_latencies`].
+    pub fn multi_region_latencies(&self) -> Vec<Duration> {
+        MULTI_REGION_LATENCIES[..self.num_peers()].to_vec()
+    }
+
     pub fn threshold(&self) -> NonZeroU16 {
         match self {
             DeploySetup::TwoThree => NonZeroU16::new(2).expect("2 is non-zero"),
@@ -3,7 +3,7 @@ use std::time::Duration;
 
 use alloy::{primitives::U160, sol_types::SolEvent};
 use eyre::Context as _;
-use oprf_key_gen::event_cursor_store::ChainCursorStorage as _;
+use oprf_key_gen::{ExitReason, event_cursor_store::ChainCursorStorage as _};
 use taceo_oprf::types::{OprfKeyId, ShareEpoch, chain::OprfKeyRegistry};
 use taceo_oprf_test::{
     TEST_TIMEOUT,
@@ -247,10 +247,13 @@ async fn key_gen_dies_on_cancellation() -> eyre::Result<()> {
     let setup = TestSetup::new(DeploySetup::TwoThree).await?;
     let key_gen = TestKeyGen::start(0, &setup).await?;
     key_gen.cancellation_token.cancel();
-    tokio::time::timeout(TEST_TIMEOUT, key_gen.key_gen_task.join())
+    let exit_reason = tokio::time::timeout(TEST_TIMEOUT, key_gen.key_gen_task.run())
         .await
-        .expect("Can shutdown in time")
-        .expect("Was a graceful shutdown");
+        .expect("Can shutdown in time");
+    assert!(
+        matches!(exit_reason, ExitReason::Cancelled),
+        "Was a graceful shutdown, got {exit_reason:?}"
+    );
     Ok(())
 }
 
@@ -12,7 +12,7 @@ use taceo_oprf::core::oprf::BlindingFactor;
 use taceo_oprf::service::secret_manager::SecretManager as _;
 use taceo_oprf::types::{
     OprfKeyId, ShareEpoch,
-    api::{OprfResponse, oprf_error_codes},
+    api::{OprfResponse, oprf_error_codes, oprf_error_messages},
 };
 use taceo_oprf_test::node_setup::ConfigurableTestRequestAuth;
 use taceo_oprf_test::{
@@ -224,7 +224,7 @@ async fn session_timeout_no_message() -> eyre::Result<()> {
         .await;
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::TIMEOUT.into(),
-        reason: "timeout".into(),
+        reason: oprf_error_messages::TIMEOUT.into(),
     };
     let is_message = tokio::time::timeout(taceo_oprf_test::TEST_TIMEOUT, ws.receive_message())
         .await
@@ -259,7 +259,7 @@ async fn message_too_large_inner(node: &TestNode, format: WireFormat) -> eyre::R
 
     let should_close_frame = CloseFrame {
         code: CloseCode::Size,
-        reason: "size exceeds max frame length".into(),
+        reason: "message exceeds max message size of 1024 bytes".into(),
     };
     let is_message = ws.receive_message().await;
     node_setup::assert_close_frame(is_message, &should_close_frame);
@@ -318,7 +318,7 @@ async fn session_timeout_after_init_inner(format: WireFormat) -> eyre::Result<()
         .await;
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::TIMEOUT.into(),
-        reason: "timeout".into(),
+        reason: oprf_error_messages::TIMEOUT.into(),
     };
     let is_message = tokio::time::timeout(taceo_oprf_test::TEST_TIMEOUT, ws.receive_message())
         .await
@@ -340,7 +340,7 @@ async fn switch_encoding_failed_inner(
     let challenge = node_setup::random_challenge(&mut rand::thread_rng(), vec![42]);
     let should_close_frame = CloseFrame {
         code: close_code::UNSUPPORTED.into(),
-        reason: "unexpected ws message".into(),
+        reason: oprf_error_messages::UNEXPECTED_MESSAGE.into(),
     };
 
     node.challenge_expect_error(&mut ws, challenge, challenge_format, &should_close_frame)
@@ -384,7 +384,7 @@ async fn delete_oprf_key() -> eyre::Result<()> {
     node.doesnt_have_key(key_id).await?;
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::DELETED_OPRF_KEY_ID.into(),
-        reason: "OPRF key already deleted".into(),
+        reason: oprf_error_messages::DELETED_OPRF_KEY_ID.into(),
     };
 
     for format in [WireFormat::Json, WireFormat::Cbor] {
@@ -411,7 +411,7 @@ async fn init_session_reuse_inner(node: &TestNode, format: WireFormat) -> eyre::
 
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::SESSION_REUSE.into(),
-        reason: "session already in use".into(),
+        reason: oprf_error_messages::SESSION_REUSE.into(),
     };
     node.init_expect_error(request1, format, &should_close_frame)
         .await;
@@ -426,7 +426,7 @@ async fn init_bad_blinded_query_inner(node: &TestNode, format: WireFormat) -> ey
 
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::BLINDED_QUERY_IS_IDENTITY.into(),
-        reason: "blinded query must not be identity".into(),
+        reason: oprf_error_messages::BLINDED_QUERY_IS_IDENTITY.into(),
     };
 
     node.init_expect_error(request, format, &should_close_frame)
@@ -455,8 +455,8 @@ async fn init_bad_request_inner(node: &TestNode, format: WireFormat) -> eyre::Re
                 oprf_error_codes::CORRUPTED_MESSAGE.into()
             );
             let expected_reason = match format {
-                WireFormat::Json => "invalid json",
-                WireFormat::Cbor => "invalid cbor",
+                WireFormat::Json => oprf_error_messages::INVALID_JSON,
+                WireFormat::Cbor => oprf_error_messages::INVALID_CBOR,
             };
             assert_eq!(is_close_frame.reason.to_string(), expected_reason);
         }
@@ -480,8 +480,8 @@ async fn challenge_bad_request_inner(node: &TestNode, format: WireFormat) -> eyr
                 oprf_error_codes::CORRUPTED_MESSAGE.into()
             );
             let expected_reason = match format {
-                WireFormat::Json => "invalid json",
-                WireFormat::Cbor => "invalid cbor",
+                WireFormat::Json => oprf_error_messages::INVALID_JSON,
+                WireFormat::Cbor => oprf_error_messages::INVALID_CBOR,
             };
             assert_eq!(is_close_frame.reason.to_string(), expected_reason);
         }
@@ -502,7 +502,7 @@ async fn challenge_bad_contributing_parties_inner(
 
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::COEFFICIENTS_DOES_NOT_EQUAL_THRESHOLD.into(),
-        reason: "not exactly threshold many contributions".into(),
+        reason: oprf_error_messages::COEFFICIENTS_DOES_NOT_EQUAL_THRESHOLD.into(),
     };
 
     node.challenge_expect_error(&mut ws, challenge, format, &should_close_frame)
@@ -523,7 +523,7 @@ async fn challenge_challenge_not_contributing_party_inner(
 
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::MISSING_MY_COEFFICIENT.into(),
-        reason: "contributing parties does not contain my coefficient".into(),
+        reason: oprf_error_messages::MISSING_MY_COEFFICIENT.into(),
     };
 
     node.challenge_expect_error(&mut ws, challenge, format, &should_close_frame)
@@ -543,7 +543,7 @@ async fn challenge_contributing_parties_not_sorted_inner(
 
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::UNSORTED_CONTRIBUTING_PARTIES.into(),
-        reason: "contributing parties are not sorted".into(),
+        reason: oprf_error_messages::UNSORTED_CONTRIBUTING_PARTIES.into(),
     };
 
     node.challenge_expect_error(&mut ws, challenge, format, &should_close_frame)
@@ -562,7 +562,7 @@ async fn challenge_duplicate_contributions_inner(
 
     let should_close_frame = CloseFrame {
         code: oprf_error_codes::DUPLICATE_COEFFICIENT.into(),
-        reason: "contributing parties contains duplicate coefficients".into(),
+        reason: oprf_error_messages::DUPLICATE_COEFFICIENT.into(),
     };
 
     node.challenge_expect_error(&mut ws, challenge, format, &should_close_frame)
@@ -631,6 +631,7 @@ async fn delegate_happy_path() -> eyre::Result<()> {
         u16::from(DeploySetup::TwoThree.threshold()) as usize,
         node_setup::OPRF_KEY_ID.into(),
         &client,
+        &taceo_oprf::client::RetryPolicy::default(),
     )
     .await?
     .expect("setup should have this key");
@@ -713,3 +714,36 @@ async fn delegate_errors() -> eyre::Result<()> {
     response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
     Ok(())
 }
+
+/// Tests that a client opens its sessions with the fastest nodes of a 3-of-5 cluster spread over
+/// multiple (simulated) regions.
+#[tokio::test]
+async fn client_selects_fastest_nodes() -> eyre::Result<()> {
+    let setup = DeploySetup::ThreeFive;
+    let latencies = setup.multi_region_latencies();
+    let nodes =
+        node_setup::start_nodes_with_latencies(setup, node_setup::OPRF_KEY_ID.into(), &latencies)
+            .await?;
+    let services = nodes
+        .iter()
+        .map(|n| n.server.server_address().expect("Server has address"))
+        .collect::<Vec<_>>();
+    let services = taceo_oprf::client::to_oprf_uri_many(&services, "test")?;
+
+    let request = node_setup::request(&mut rand::thread_rng());
+    let sessions = taceo_oprf::client::init_sessions(
+        request.request_id,
+        &services,
+        usize::from(setup.threshold().get()),
+        request,
+        taceo_oprf::client::Connector::Plain,
+    )
+    .await
+    .expect("can init sessions");
+
+    node_setup::assert_selected(
+        &sessions,
+        &node_setup::fastest_parties(&latencies, setup.threshold()),
+    );
+    Ok(())
+}
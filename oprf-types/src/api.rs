@@ -6,12 +6,25 @@
 //!
 //! Additionally, it defines the [`OprfRequestAuthenticator`] trait to define an authentication module for TACEO:OPRF.
 
-use std::{borrow::Cow, fmt, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
 use async_trait::async_trait;
+use base64::Engine as _;
 use http::HeaderName;
-use oprf_core::ddlog_equality::shamir::{
-    DLogCommitmentsShamir, DLogProofShareShamir, PartialDLogCommitmentsShamir,
+use oprf_core::{
+    ddlog_equality::shamir::{
+        DLogCommitmentsShamir, DLogProofShareShamir, PartialDLogCommitmentsShamir,
+    },
+    dlog_equality::DLogEqualityProof,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -45,6 +58,7 @@ macro_rules! close_frame_message {
 
 /// The [`OprfPublicKey`] with its latest [`ShareEpoch`].
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct OprfPublicKeyWithEpoch {
     /// The key
     pub key: OprfPublicKey,
@@ -52,10 +66,339 @@ pub struct OprfPublicKeyWithEpoch {
     pub epoch: ShareEpoch,
 }
 
+/// The public parameters of an OPRF key: the [`OprfPublicKey`] with its latest [`ShareEpoch`] and the threshold of the key.
+///
+/// Clients need `threshold` many nodes to evaluate the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct OprfKeyParams {
+    /// The key
+    pub key: OprfPublicKey,
+    /// The current epoch
+    pub epoch: ShareEpoch,
+    /// The threshold of the key
+    pub threshold: std::num::NonZeroU16,
+    /// The max size in bytes of a single web-socket message accepted by the node, if reported. Larger messages are rejected with close code `1009` (message too big).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_message_size: Option<usize>,
+    /// The max number of blinded queries per session accepted by the node, if reported (see [`OprfRequest::batch`]). Nodes that do not report it only accept single queries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_batch_size: Option<usize>,
+}
+
+impl OprfKeyParams {
+    /// Returns the [`OprfPublicKeyWithEpoch`] of the key.
+    #[must_use]
+    pub fn public_key_with_epoch(&self) -> OprfPublicKeyWithEpoch {
+        OprfPublicKeyWithEpoch {
+            key: self.key,
+            epoch: self.epoch,
+        }
+    }
+
+    /// Returns `false` if a message of `len` bytes exceeds the reported [`OprfKeyParams::max_message_size`], e.g. to validate an authentication payload before connecting.
+    #[must_use]
+    pub fn accepts_message_size(&self, len: usize) -> bool {
+        self.max_message_size.is_none_or(|max| len <= max)
+    }
+
+    /// Returns the reported [`OprfKeyParams::max_batch_size`], `1` if the node did not report it.
+    #[must_use]
+    pub fn batch_size(&self) -> usize {
+        self.max_batch_size.unwrap_or(1)
+    }
+}
+
+/// Notification pushed by a node to subscribed clients when it loads another epoch of an [`OprfKeyId`] than it saw last.
+///
+/// The first load of a key after the node started is notified as well (with `previous` set to `None`), and every node notifies on its own. Clients should compare the `epoch` with the epoch they know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct EpochChanged {
+    /// The key that was loaded.
+    pub oprf_key_id: OprfKeyId,
+    /// The epoch the node saw last for this key, `None` for the first load since the node started.
+    #[serde(default)]
+    pub previous: Option<ShareEpoch>,
+    /// The epoch the node now serves for this key.
+    pub epoch: ShareEpoch,
+}
+
+/// A node of the committee as registered in the `OprfKeyRegistry` contract.
+///
+/// The contract has no notion of a suspended or unhealthy participant, so every registered node is listed here. Clients that want to avoid an unresponsive node rely on contacting more than `threshold` nodes and using the first responses, see `init_sessions` in the client.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CommitteeMember {
+    /// The party id of the node.
+    pub party_id: PartyId,
+    /// The registered wallet address of the node as `0x`-prefixed hex string.
+    pub address: String,
+}
+
+/// The committee of OPRF nodes as registered in the `OprfKeyRegistry` contract, served by nodes at `/committee`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Committee {
+    /// The registered nodes, sorted by party id.
+    pub members: Vec<CommitteeMember>,
+}
+
+impl Committee {
+    /// Returns the party id registered for the wallet `address`, compared case-insensitively.
+    #[must_use]
+    pub fn party_id_of(&self, address: &str) -> Option<PartyId> {
+        self.members
+            .iter()
+            .find(|member| member.address.eq_ignore_ascii_case(address))
+            .map(|member| member.party_id)
+    }
+}
+
 /// The name of the oprf-protocol-version header.
 pub static OPRF_PROTOCOL_VERSION_HEADER: HeaderName =
     http::HeaderName::from_static("x-taceo-oprf-protocol-version");
 
+/// The name of the header in the web-socket upgrade response that advertises the max size in bytes of a single message accepted by the node.
+pub static OPRF_MAX_MESSAGE_SIZE_HEADER: HeaderName =
+    http::HeaderName::from_static("x-taceo-oprf-max-message-size");
+
+/// Canonical definition of the messages exchanged during an OPRF session, in the order they are sent.
+///
+/// This must be updated whenever a wire-visible detail of one of these messages changes (field names, field types, encodings, optional fields). The [`SchemaFingerprint`] is derived from it.
+pub const OPRF_SCHEMA_DEFINITION: &str = "\
+client->node OprfRequest{request_id:uuid,blinded_query:babyjubjub_affine,auth:auth,issued_at:option<u64>,batch:option<vec<babyjubjub_affine>>}
+node->client OprfResponse{commitments:PartialDLogCommitmentsShamir{c:babyjubjub_affine,d1:babyjubjub_affine,d2:babyjubjub_affine,e1:babyjubjub_affine,e2:babyjubjub_affine},party_id:u16,oprf_pub_key_with_epoch:OprfPublicKeyWithEpoch{key:babyjubjub_affine,epoch:u32},batch_commitments:option<vec<PartialDLogCommitmentsShamir>>,share_proof:option<ShareProof{public_share:babyjubjub_affine,proofs:vec<DLogEqualityProof>}>,signature:option<bytes>}
+client->node DLogCommitmentsShamir{c:babyjubjub_affine,d1:babyjubjub_affine,d2:babyjubjub_affine,e1:babyjubjub_affine,e2:babyjubjub_affine,contributing_parties:vec<u16>}
+node->client DLogProofShareShamir{babyjubjub_fr}
+batch client->node OprfBatchChallenge{challenges:vec<DLogCommitmentsShamir>}
+batch node->client OprfBatchProofShares{proof_shares:vec<DLogProofShareShamir>}
+delegate DelegateOprfResponse{challenge:DLogCommitmentsShamir,responses:vec<DLogProofShareShamir>,oprf_pub_key_with_epoch:OprfPublicKeyWithEpoch}
+aggregator AggregatedOprfResponse{blinded_response:babyjubjub_affine,dlog_proof:DLogEqualityProof{e:babyjubjub_fq,s:babyjubjub_fr},oprf_pub_key_with_epoch:OprfPublicKeyWithEpoch}
+multiplexed client->node MultiplexedFrame{request_id:uuid,payload:OprfRequest|DLogCommitmentsShamir}
+multiplexed node->client MultiplexedFrame{request_id:uuid,payload:MultiplexedNodeMessage{message:OprfResponse|DLogProofShareShamir|close:{code:u16,reason:string}}}
+";
+
+/// Fingerprint of the [`OPRF_SCHEMA_DEFINITION`] a build implements.
+///
+/// Clients send it alongside their protocol version, so nodes can reject builds with a diverging message schema explicitly instead of failing with deserialization errors mid-session. Displayed and serialized as 16 lower-case hex characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SchemaFingerprint(u64);
+
+impl SchemaFingerprint {
+    /// The fingerprint of the schema implemented by this build.
+    pub const CURRENT: Self = Self::of(OPRF_SCHEMA_DEFINITION);
+
+    /// Computes the fingerprint (64-bit FNV-1a) of a canonical schema definition.
+    #[must_use]
+    pub const fn of(definition: &str) -> Self {
+        let bytes = definition.as_bytes();
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+            i += 1;
+        }
+        Self(hash)
+    }
+}
+
+impl fmt::Display for SchemaFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl std::str::FromStr for SchemaFingerprint {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Self)
+    }
+}
+
+impl Serialize for SchemaFingerprint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for SchemaFingerprint {
+    fn schema_name() -> Cow<'static, str> {
+        "SchemaFingerprint".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "The fingerprint of the message schema as 16 lower-case hex characters.",
+            "type": "string",
+            "pattern": "^[0-9a-f]{16}$"
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemaFingerprint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = Cow::<'de, str>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Build information served by a node at `/info`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct NodeInfo {
+    /// The [`SchemaFingerprint`] of the node.
+    pub schema_fingerprint: SchemaFingerprint,
+    /// The client protocol versions the node accepts, as semver requirement.
+    pub version_req: String,
+}
+
+impl NodeInfo {
+    /// Creates the info for a node accepting the provided client versions, using [`SchemaFingerprint::CURRENT`].
+    #[must_use]
+    pub fn new(version_req: String) -> Self {
+        Self {
+            schema_fingerprint: SchemaFingerprint::CURRENT,
+            version_req,
+        }
+    }
+}
+
+/// Build provenance of a service, served at `/buildinfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct BuildInfo {
+    /// Name, version and git hash of the service, as logged at startup (see `nodes_common::version_info!`).
+    pub version: String,
+    /// Versions of the TACEO:OPRF crates the service was built with, by package name.
+    pub crates: BTreeMap<String, String>,
+    /// The git commit the service was built from, `GIT_HASH` at compile time or the hash of the checkout.
+    pub git_sha: String,
+    /// The `BUILD_TIMESTAMP` at compile time, if set by the build.
+    pub build_timestamp: Option<String>,
+    /// The enabled cargo features of the service crate.
+    pub features: Vec<String>,
+    /// The client protocol the service implements, if it serves OPRF clients.
+    pub protocol: Option<NodeInfo>,
+}
+
+impl BuildInfo {
+    /// Creates the build info of a service, including the version of this crate.
+    #[must_use]
+    pub fn new(
+        version: String,
+        git_sha: String,
+        build_timestamp: Option<String>,
+        features: &[&str],
+    ) -> Self {
+        Self {
+            version,
+            crates: BTreeMap::from([(
+                env!("CARGO_PKG_NAME").to_owned(),
+                env!("CARGO_PKG_VERSION").to_owned(),
+            )]),
+            git_sha,
+            build_timestamp,
+            features: features.iter().map(ToString::to_string).collect(),
+            protocol: None,
+        }
+    }
+
+    /// Adds the version of a crate the service was built with.
+    #[must_use]
+    pub fn with_crate(mut self, name: &str, version: &str) -> Self {
+        self.crates.insert(name.to_owned(), version.to_owned());
+        self
+    }
+
+    /// Sets the client protocol the service implements.
+    #[must_use]
+    pub fn with_protocol(mut self, protocol: NodeInfo) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+}
+
+/// An OPRF key currently loaded by a node, served at `/oprf_keys`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct LoadedOprfKey {
+    /// The [`OprfKeyId`] of the key.
+    pub oprf_key_id: OprfKeyId,
+    /// The public key.
+    pub key: OprfPublicKey,
+    /// The epoch of the share loaded by the node.
+    pub epoch: ShareEpoch,
+}
+
+/// The limits of an OPRF module, served by a node at `/api/{path}/params`.
+///
+/// Operators can override the limits of a module at runtime, so they may differ from the values of the node at `/info` and `/oprf_params/{id}`. The limits apply to connections opened after the change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ModuleParams {
+    /// The max size in bytes of a single message accepted by the module.
+    pub max_message_size: usize,
+    /// The max lifetime of a session in milliseconds.
+    pub session_lifetime_ms: u64,
+    /// The client protocol versions the module accepts, as semver requirement.
+    pub version_req: String,
+    /// The max number of blinded queries per session (see [`OprfRequest::batch`]).
+    pub max_batch_size: usize,
+}
+
+/// Memory statistics served by a node at `/debug/memory`, if enabled.
+///
+/// Meant to be sampled over long-running soak tests, so slow growth can be attributed to the allocator or to one of the subsystems of the node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// The resident set size of the process in bytes, `None` if the platform does not report it.
+    pub rss_bytes: Option<u64>,
+    /// The statistics of the jemalloc allocator.
+    pub allocator: AllocatorStats,
+    /// The number of entries held by the subsystems of the node.
+    pub subsystems: SubsystemStats,
+}
+
+/// Statistics of the jemalloc allocator, see the `stats.*` entries of `mallctl`.
+///
+/// All values are zero if the host does not use jemalloc as global allocator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocatorStats {
+    /// Bytes allocated by the application.
+    pub allocated_bytes: u64,
+    /// Bytes in active pages, at least `allocated_bytes`.
+    pub active_bytes: u64,
+    /// Bytes in physically resident pages, including metadata.
+    pub resident_bytes: u64,
+    /// Bytes in active extents mapped by the allocator.
+    pub mapped_bytes: u64,
+    /// Bytes in virtual memory mappings retained instead of returned to the OS.
+    pub retained_bytes: u64,
+    /// Bytes dedicated to allocator metadata.
+    pub metadata_bytes: u64,
+    /// The number of arenas.
+    pub arenas: u32,
+}
+
+/// The number of entries held by the subsystems of a node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubsystemStats {
+    /// Sessions that reserved a session-id and did not finish yet.
+    pub open_sessions: u64,
+    /// Keys in the key-material store.
+    pub key_material_entries: u64,
+    /// Unknown or deleted keys in the negative cache of the key-material store.
+    pub negative_cache_entries: u64,
+    /// Finished sessions kept for resumed sessions.
+    pub replay_cache_entries: u64,
+}
+
 /// TACEO:OPRF specific websocket error codes.
 ///
 /// Error codes are split into two ranges:
@@ -85,6 +428,81 @@ pub mod oprf_error_codes {
     pub const DUPLICATE_COEFFICIENT: u16 = 4009;
     /// Requested deleted OPRF-key ID
     pub const DELETED_OPRF_KEY_ID: u16 = 4010;
+    /// The issued-at timestamp of the request is missing or outside the window accepted by the node
+    pub const STALE_QUERY: u16 = 4011;
+    /// The authentication of the request did not finish within the time configured by the node
+    pub const AUTH_TIMEOUT: u16 = 4012;
+    /// A resumed session sent a different challenge than the one the node answered before
+    pub const CHALLENGE_MISMATCH: u16 = 4013;
+    /// The risk scorer of the node denied the request (or could not be reached and the node fails closed)
+    pub const RISK_DENIED: u16 = 4014;
+    /// The risk scorer of the node throttled the request, the client may retry later
+    pub const RISK_THROTTLED: u16 = 4015;
+    /// The session was still pending when the node restarted, its randomness is gone. The client must restart the evaluation with a new request id
+    pub const SESSION_LOST: u16 = 4016;
+    /// The request carries more blinded queries than the `max_batch_size` of the node
+    pub const BATCH_TOO_LARGE: u16 = 4017;
+    /// The OPRF key of the request exhausted its quota of evaluations at the node, the client may retry later
+    pub const QUOTA_EXCEEDED: u16 = 4018;
+    /// The OPRF key of the request already runs as many concurrent sessions as the node allows, the client may retry later
+    pub const KEY_BUSY: u16 = 4019;
+    /// The subscriber of the epoch notifications fell behind and missed notifications. The client must drop the epochs it learned and resubscribe
+    pub const NOTIFICATIONS_LAGGED: u16 = 4020;
+}
+
+/// The reasons of the close frames sent by the OPRF service.
+///
+/// Nodes send these fixed reasons with the matching [`oprf_error_codes`] (or RFC 6455 code), unless they are configured to send the detailed error instead. Clients that match on a reason should use these constants instead of copying the wording.
+pub mod oprf_error_messages {
+    /// Sent with [`super::oprf_error_codes::TIMEOUT`].
+    pub const TIMEOUT: &str = "timeout";
+    /// Sent with [`super::oprf_error_codes::CORRUPTED_MESSAGE`] if the message is not valid JSON.
+    pub const INVALID_JSON: &str = "invalid json";
+    /// Sent with [`super::oprf_error_codes::CORRUPTED_MESSAGE`] if the message is not valid CBOR.
+    pub const INVALID_CBOR: &str = "invalid cbor";
+    /// Sent with [`super::oprf_error_codes::CORRUPTED_MESSAGE`] if the node requires canonical CBOR and the message is not canonically encoded.
+    pub const NON_CANONICAL_CBOR: &str = "non-canonical cbor";
+    /// Sent with [`super::oprf_error_codes::CORRUPTED_MESSAGE`] if a batch challenge does not match the size of the batch.
+    pub const BATCH_SIZE_MISMATCH: &str = "number of challenges does not match batch";
+    /// Sent with [`super::oprf_error_codes::SESSION_REUSE`].
+    pub const SESSION_REUSE: &str = "session already in use";
+    /// Sent with [`super::oprf_error_codes::UNKNOWN_OPRF_KEY_ID`].
+    pub const UNKNOWN_OPRF_KEY_ID: &str = "unknown OPRF key id";
+    /// Sent with [`super::oprf_error_codes::BLINDED_QUERY_IS_IDENTITY`].
+    pub const BLINDED_QUERY_IS_IDENTITY: &str = "blinded query must not be identity";
+    /// Sent with [`super::oprf_error_codes::COEFFICIENTS_DOES_NOT_EQUAL_THRESHOLD`].
+    pub const COEFFICIENTS_DOES_NOT_EQUAL_THRESHOLD: &str =
+        "not exactly threshold many contributions";
+    /// Sent with [`super::oprf_error_codes::MISSING_MY_COEFFICIENT`].
+    pub const MISSING_MY_COEFFICIENT: &str = "contributing parties does not contain my coefficient";
+    /// Sent with [`super::oprf_error_codes::UNSORTED_CONTRIBUTING_PARTIES`].
+    pub const UNSORTED_CONTRIBUTING_PARTIES: &str = "contributing parties are not sorted";
+    /// Sent with [`super::oprf_error_codes::DUPLICATE_COEFFICIENT`].
+    pub const DUPLICATE_COEFFICIENT: &str = "contributing parties contains duplicate coefficients";
+    /// Sent with [`super::oprf_error_codes::DELETED_OPRF_KEY_ID`].
+    pub const DELETED_OPRF_KEY_ID: &str = "OPRF key already deleted";
+    /// Sent with [`super::oprf_error_codes::STALE_QUERY`].
+    pub const STALE_QUERY: &str = "stale query";
+    /// Sent with [`super::oprf_error_codes::AUTH_TIMEOUT`].
+    pub const AUTH_TIMEOUT: &str = "authentication timed out";
+    /// Sent with [`super::oprf_error_codes::CHALLENGE_MISMATCH`].
+    pub const CHALLENGE_MISMATCH: &str = "challenge does not match resumed session";
+    /// Sent with [`super::oprf_error_codes::RISK_DENIED`].
+    pub const RISK_DENIED: &str = "denied by risk scoring";
+    /// Sent with [`super::oprf_error_codes::KEY_BUSY`].
+    pub const KEY_BUSY: &str = "OPRF key busy";
+    /// Sent with [`super::oprf_error_codes::NOTIFICATIONS_LAGGED`].
+    pub const NOTIFICATIONS_LAGGED: &str = "epoch notifications lagged behind";
+    /// Sent with [`super::oprf_error_codes::SESSION_LOST`].
+    pub const SESSION_LOST: &str = "session lost on node restart";
+    /// Sent with the RFC 6455 unsupported code (1003) if the client sent a PING/PONG or switched the encoding between messages.
+    pub const UNEXPECTED_MESSAGE: &str = "unexpected ws message";
+    /// Sent with the RFC 6455 size code (1009) if a frame exceeds the max frame length of the node.
+    pub const FRAME_TOO_LARGE: &str = "size exceeds max frame length";
+    /// Sent with the RFC 6455 error code (1011) for internal errors of the node.
+    pub const UNEXPECTED_ERROR: &str = "unexpected error";
+    /// Sent with the RFC 6455 restart code (1012) when a node closes a multiplexed connection during shutdown.
+    pub const SHUTTING_DOWN: &str = "node is shutting down";
 }
 
 /// A typed classification of an OPRF WebSocket close code.
@@ -115,6 +533,26 @@ pub enum OprfErrorKind {
     DuplicateCoefficient,
     /// The requested OPRF key id is deleted. Corresponds to [`oprf_error_codes::DELETED_OPRF_KEY_ID`].
     DeletedOprfKeyId,
+    /// The issued-at timestamp of the request was missing or outside the accepted window. Corresponds to [`oprf_error_codes::STALE_QUERY`].
+    StaleQuery,
+    /// The node could not authenticate the request in time. Corresponds to [`oprf_error_codes::AUTH_TIMEOUT`].
+    AuthTimeout,
+    /// A resumed session sent a different challenge. Corresponds to [`oprf_error_codes::CHALLENGE_MISMATCH`].
+    ChallengeMismatch,
+    /// The risk scorer of the node denied the request. Corresponds to [`oprf_error_codes::RISK_DENIED`].
+    RiskDenied,
+    /// The risk scorer of the node throttled the request. Corresponds to [`oprf_error_codes::RISK_THROTTLED`].
+    RiskThrottled,
+    /// The session was pending during a restart of the node. Corresponds to [`oprf_error_codes::SESSION_LOST`].
+    SessionLost,
+    /// The request carried more queries than the node accepts per session. Corresponds to [`oprf_error_codes::BATCH_TOO_LARGE`].
+    BatchTooLarge,
+    /// The OPRF key of the request exhausted its quota at the node. Corresponds to [`oprf_error_codes::QUOTA_EXCEEDED`].
+    QuotaExceeded,
+    /// The OPRF key of the request runs too many concurrent sessions at the node. Corresponds to [`oprf_error_codes::KEY_BUSY`].
+    KeyBusy,
+    /// The subscriber of the epoch notifications missed notifications. Corresponds to [`oprf_error_codes::NOTIFICATIONS_LAGGED`].
+    NotificationsLagged,
     /// Authentication failed. Close code was in the 4500–4999 range defined by the [`OprfRequestAuthenticator`].
     Auth,
     /// Away code as specified in RFC 6455 (1001)
@@ -169,6 +607,16 @@ impl fmt::Display for OprfErrorKind {
             Self::Internal => f.write_str("internal error"),
             Self::Again => f.write_str("try again later"),
             Self::DeletedOprfKeyId => f.write_str("deleted OPRF key id"),
+            Self::StaleQuery => f.write_str("stale query"),
+            Self::AuthTimeout => f.write_str("authentication timed out"),
+            Self::ChallengeMismatch => f.write_str("challenge mismatch"),
+            Self::RiskDenied => f.write_str("denied by risk scoring"),
+            Self::RiskThrottled => f.write_str("throttled by risk scoring"),
+            Self::SessionLost => f.write_str("session lost on node restart"),
+            Self::BatchTooLarge => f.write_str("batch too large"),
+            Self::QuotaExceeded => f.write_str("key quota exceeded"),
+            Self::KeyBusy => f.write_str("key busy"),
+            Self::NotificationsLagged => f.write_str("notifications lagged"),
             Self::Unknown => f.write_str("unknown error"),
         }
     }
@@ -189,6 +637,16 @@ impl From<u16> for OprfErrorKind {
             oprf_error_codes::UNSORTED_CONTRIBUTING_PARTIES => Self::UnsortedContributingParties,
             oprf_error_codes::DUPLICATE_COEFFICIENT => Self::DuplicateCoefficient,
             oprf_error_codes::DELETED_OPRF_KEY_ID => Self::DeletedOprfKeyId,
+            oprf_error_codes::STALE_QUERY => Self::StaleQuery,
+            oprf_error_codes::AUTH_TIMEOUT => Self::AuthTimeout,
+            oprf_error_codes::CHALLENGE_MISMATCH => Self::ChallengeMismatch,
+            oprf_error_codes::RISK_DENIED => Self::RiskDenied,
+            oprf_error_codes::RISK_THROTTLED => Self::RiskThrottled,
+            oprf_error_codes::SESSION_LOST => Self::SessionLost,
+            oprf_error_codes::BATCH_TOO_LARGE => Self::BatchTooLarge,
+            oprf_error_codes::QUOTA_EXCEEDED => Self::QuotaExceeded,
+            oprf_error_codes::KEY_BUSY => Self::KeyBusy,
+            oprf_error_codes::NOTIFICATIONS_LAGGED => Self::NotificationsLagged,
             4500..=4999 => Self::Auth,
             1001 => Self::Away,
             1002 => Self::Protocol,
@@ -205,38 +663,220 @@ impl From<u16> for OprfErrorKind {
 
 /// A request sent by a client to perform an OPRF evaluation.
 #[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct OprfRequest<OprfRequestAuth> {
     /// Unique ID of the request (used to correlate responses).
     pub request_id: Uuid,
     /// Input point `B` of the OPRF, serialized as a `BabyJubJub` affine point.
     #[serde(with = "babyjubjub::affine")]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::BabyJubJubAffine")
+    )]
     pub blinded_query: ark_babyjubjub::EdwardsAffine,
     /// The additional authentication info for this request
     pub auth: OprfRequestAuth,
+    /// Unix timestamp (in seconds) when the client created the blinded query.
+    ///
+    /// Nodes configured with a maximum query age reject requests without this timestamp or with a timestamp outside the accepted window. Authentication modules that sign the request should cover this field, otherwise it can be refreshed by an attacker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_at: Option<u64>,
+    /// Further blinded queries evaluated in the same session, after `blinded_query`.
+    ///
+    /// A non-empty batch makes the session a batch session: the node answers with one commitment per query (see [`OprfResponse::batch_commitments`]), expects an [`OprfBatchChallenge`] instead of a single challenge and answers with [`OprfBatchProofShares`]. Nodes accept at most `max_batch_size` queries per session, including `blinded_query` (see [`OprfKeyParams::max_batch_size`]). Authentication modules that bind the request to its query should cover this field as well.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        with = "babyjubjub::affine_seq"
+    )]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Vec<crate::schema::BabyJubJubAffine>")
+    )]
+    pub batch: Vec<ark_babyjubjub::EdwardsAffine>,
+}
+
+impl<OprfRequestAuth> OprfRequest<OprfRequestAuth> {
+    /// The number of blinded queries of the request, i.e., `blinded_query` and the [`OprfRequest::batch`].
+    #[must_use]
+    pub fn num_queries(&self) -> usize {
+        1 + self.batch.len()
+    }
+
+    /// All blinded queries of the request, starting with `blinded_query`.
+    pub fn blinded_queries(&self) -> impl Iterator<Item = ark_babyjubjub::EdwardsAffine> + '_ {
+        std::iter::once(self.blinded_query).chain(self.batch.iter().copied())
+    }
 }
 
 /// Server response to an [`OprfRequest`].
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct OprfResponse {
     /// Server’s partial commitments for the discrete log equality proof.
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::PartialDLogCommitments")
+    )]
     pub commitments: PartialDLogCommitmentsShamir,
     /// The party ID of the node
     pub party_id: PartyId,
     /// The [`OprfPublicKeyWithEpoch`].
     pub oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch,
+    /// The commitments for the [`OprfRequest::batch`] of a batch session, in the order of the queries. Empty for single queries.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Vec<crate::schema::PartialDLogCommitments>")
+    )]
+    pub batch_commitments: Vec<PartialDLogCommitmentsShamir>,
+    /// Proves that the commitments were computed with the share of the node, if the node is configured to send share proofs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share_proof: Option<ShareProof>,
+    /// Binds the response to the wallet of the node, if the node is configured to sign its responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schemars", schemars(with = "Option<String>"))]
+    pub signature: Option<ResponseSignature>,
+}
+
+/// Proof that the commitments of an [`OprfResponse`] were computed with the share of the node.
+///
+/// Clients verify every proof against `public_share` before counting the session toward the threshold, and check that the public shares of the chosen nodes combine to the [`OprfPublicKey`] of the epoch. A node with a wrong or missing share is thus detected before the client sends its challenge, instead of failing the combined proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ShareProof {
+    /// The public share `G·x_i` of the share `x_i` of the node for the epoch of the response.
+    #[serde(with = "babyjubjub::affine")]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::BabyJubJubAffine")
+    )]
+    pub public_share: ark_babyjubjub::EdwardsAffine,
+    /// Per query, in the order of [`OprfRequest::blinded_queries`], a `DLog` equality proof that `C = B·x_i` and `public_share` have the same dlog.
+    #[cfg_attr(feature = "schemars", schemars(with = "Vec<crate::schema::DLogProof>"))]
+    pub proofs: Vec<DLogEqualityProof>,
+}
+
+/// Size in bytes of a [`ResponseSignature`].
+pub const RESPONSE_SIGNATURE_SIZE: usize = 65;
+
+/// ECDSA signature of the wallet key of a node over the session id, commitments, party id and epoch of an [`OprfResponse`].
+///
+/// The signature is an [EIP-191](https://eips.ethereum.org/EIPS/eip-191) signature (`r || s || v`) of the message returned by `OprfResponse::signing_message`, so clients can recover the signer and compare it with the wallet address registered for the party id. Signing and verification are available with the `signed-responses` feature (see the `signed_response` module). Encoded like an [`OprfAuthBlob`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ResponseSignature(OprfAuthBlob<RESPONSE_SIGNATURE_SIZE>);
+
+impl ResponseSignature {
+    /// Wraps the bytes `r || s || v` of a signature.
+    ///
+    /// # Panics
+    /// Never, the bytes have exactly the max size of the blob.
+    #[must_use]
+    pub fn new(bytes: [u8; RESPONSE_SIGNATURE_SIZE]) -> Self {
+        Self(OprfAuthBlob::new(bytes.to_vec()).expect("has max size"))
+    }
+
+    /// Returns the bytes of the signature. Received signatures may be shorter than [`RESPONSE_SIGNATURE_SIZE`] and fail to verify.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+/// The challenges a client sends in a batch session (see [`OprfRequest::batch`]), one per query in the order of the queries of the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct OprfBatchChallenge {
+    /// The `DLog` equality challenges.
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Vec<crate::schema::DLogCommitments>")
+    )]
+    pub challenges: Vec<DLogCommitmentsShamir>,
+}
+
+/// Server response to an [`OprfBatchChallenge`], one proof share per query in the order of the queries of the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct OprfBatchProofShares {
+    /// The `DLog` equality proof shares.
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Vec<crate::schema::DLogProofShare>")
+    )]
+    pub proof_shares: Vec<DLogProofShareShamir>,
 }
 
 /// Server response to a delegate [`OprfRequest`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct DelegateOprfResponse {
     /// The `DLog` equality challenge based on the commitments received from the services.
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::DLogCommitments")
+    )]
     pub challenge: DLogCommitmentsShamir,
     /// The `DLog` equality proof shares received from the services.
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Vec<crate::schema::DLogProofShare>")
+    )]
     pub responses: Vec<DLogProofShareShamir>,
     /// The [`OprfPublicKeyWithEpoch`].
     pub oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch,
 }
 
+/// Response of an aggregator to a blinded [`OprfRequest`], see `oprf_client::aggregator`.
+///
+/// The aggregator runs the distributed OPRF protocol on behalf of a thin client, combines the proof shares of the nodes and verifies the proof. The client only unblinds the response, the blinding factor never leaves the client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AggregatedOprfResponse {
+    /// The combined blinded response `C = x·B`.
+    #[serde(with = "babyjubjub::affine")]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::BabyJubJubAffine")
+    )]
+    pub blinded_response: ark_babyjubjub::EdwardsAffine,
+    /// The combined `DLog` equality proof that `C` was computed with the key of `oprf_pub_key_with_epoch`.
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DLogProof"))]
+    pub dlog_proof: DLogEqualityProof,
+    /// The [`OprfPublicKeyWithEpoch`].
+    pub oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch,
+}
+
+/// A frame of a multiplexed connection, which carries many interleaved sessions over a single web-socket (`/oprf/multiplex`).
+///
+/// The client sends the [`OprfRequest`] and later the [`DLogCommitmentsShamir`] of a session as `payload`, the node answers with a [`MultiplexedNodeMessage`]. The first frame of an unknown `request_id` opens a new session, the `request_id` of the frame must match the `request_id` of the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct MultiplexedFrame<T> {
+    /// The session this frame belongs to.
+    pub request_id: Uuid,
+    /// The message of the session.
+    pub payload: T,
+}
+
+/// The payload of a [`MultiplexedFrame`] sent by the node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum MultiplexedNodeMessage<T> {
+    /// The next message of the session, i.e., the [`OprfResponse`] or the [`DLogProofShareShamir`].
+    Message(T),
+    /// The node closed the session. Carries the code and reason of the close frame that would end a single-session connection (see [`oprf_error_codes`]).
+    Close {
+        /// The close code
+        code: u16,
+        /// The reason
+        reason: String,
+    },
+}
+
 impl<OprfReqestAuth> fmt::Debug for OprfRequest<OprfReqestAuth> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("OprfRequest")
@@ -277,6 +917,44 @@ pub trait OprfRequestAuthenticator: Send + Sync {
         &self,
         req: &OprfRequest<Self::RequestAuth>,
     ) -> Result<OprfKeyId, OprfRequestAuthenticatorError>;
+
+    /// How long a successful [`Self::authenticate`] may be reused for later requests on the same connection that carry the same credentials (see [`Self::cache_key`]).
+    ///
+    /// Only return `Some` if the result of `authenticate` depends on nothing but the credentials, e.g. not on the blinded query or the request id. Defaults to `None`, which authenticates every request.
+    fn cacheable(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Identifies the credentials of `req` for [`Self::cacheable`]. Requests with the same key share one authentication.
+    ///
+    /// Defaults to `None`, which never caches.
+    fn cache_key(&self, _req: &OprfRequest<Self::RequestAuth>) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// The [`AuthCacheInvalidator`] the authenticator uses to drop cached authentications before they expire, e.g. after revoking credentials.
+    ///
+    /// Defaults to `None`, cached authentications then only expire after [`Self::cacheable`].
+    fn cache_invalidator(&self) -> Option<&AuthCacheInvalidator> {
+        None
+    }
+}
+
+/// Lets an [`OprfRequestAuthenticator`] invalidate all authentications cached by the node, see [`OprfRequestAuthenticator::cache_invalidator`].
+#[derive(Debug, Clone, Default)]
+pub struct AuthCacheInvalidator(Arc<AtomicU64>);
+
+impl AuthCacheInvalidator {
+    /// Invalidates all authentications cached so far. Requests afterwards are authenticated again.
+    pub fn invalidate(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of invalidations so far. Cached authentications are only valid for the generation they were created in.
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
 }
 
 /// Represents an authentication error returned by an [`OprfRequestAuthenticator`].
@@ -413,6 +1091,134 @@ impl OprfRequestAuthenticatorError {
     }
 }
 
+/// Default max size in bytes of an [`OprfAuthBlob`].
+pub const DEFAULT_AUTH_BLOB_MAX_SIZE: usize = 16 * 1024;
+
+/// An opaque binary authentication payload, e.g. a hardware attestation or a large token.
+///
+/// Use this as `RequestAuth` of an [`OprfRequestAuthenticator`] if the authentication data is not a
+/// serde type. The bytes are handed to [`OprfRequestAuthenticator::authenticate`] untouched.
+///
+/// In human-readable formats (JSON, `Text` frames) the blob is encoded as a standard base64 string, in binary formats (CBOR, `Binary` frames) as a byte string.
+///
+/// Deserialization fails if the blob exceeds `MAX_SIZE` bytes. Note that the node also enforces its
+/// `ws_max_message_size` on the whole frame, so it must be configured large enough for the expected blobs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct OprfAuthBlob<const MAX_SIZE: usize = DEFAULT_AUTH_BLOB_MAX_SIZE>(Vec<u8>);
+
+/// Error returned when an [`OprfAuthBlob`] exceeds its max size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs, reason = "Simple error for a single use")]
+pub struct AuthBlobTooLarge {
+    /// The size of the rejected blob.
+    pub size: usize,
+    /// The max allowed size.
+    pub max_size: usize,
+}
+
+impl std::error::Error for AuthBlobTooLarge {}
+
+impl fmt::Display for AuthBlobTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "auth blob has {} bytes, but at most {} are allowed",
+            self.size, self.max_size
+        )
+    }
+}
+
+impl<const MAX_SIZE: usize> OprfAuthBlob<MAX_SIZE> {
+    /// Creates a new blob.
+    ///
+    /// # Errors
+    /// Returns [`AuthBlobTooLarge`] if `bytes` exceeds `MAX_SIZE`.
+    pub fn new(bytes: Vec<u8>) -> Result<Self, AuthBlobTooLarge> {
+        if bytes.len() > MAX_SIZE {
+            Err(AuthBlobTooLarge {
+                size: bytes.len(),
+                max_size: MAX_SIZE,
+            })
+        } else {
+            Ok(Self(bytes))
+        }
+    }
+
+    /// Returns the raw bytes.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Consumes the blob and returns the raw bytes.
+    #[must_use]
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl<const MAX_SIZE: usize> fmt::Debug for OprfAuthBlob<MAX_SIZE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // don't print the content, it might be a credential
+        write!(f, "OprfAuthBlob({} bytes)", self.0.len())
+    }
+}
+
+impl<const MAX_SIZE: usize> Serialize for OprfAuthBlob<MAX_SIZE> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de, const MAX_SIZE: usize> Deserialize<'de> for OprfAuthBlob<MAX_SIZE> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BlobVisitor<const MAX_SIZE: usize>;
+
+        impl<const MAX_SIZE: usize> serde::de::Visitor<'_> for BlobVisitor<MAX_SIZE> {
+            type Value = OprfAuthBlob<MAX_SIZE>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "a base64 string or byte string of at most {MAX_SIZE} bytes"
+                )
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                // reject early before decoding obviously too large blobs
+                if v.len() / 4 * 3 > MAX_SIZE + 2 {
+                    return Err(E::custom(AuthBlobTooLarge {
+                        size: v.len() / 4 * 3,
+                        max_size: MAX_SIZE,
+                    }));
+                }
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(v)
+                    .map_err(E::custom)?;
+                OprfAuthBlob::new(bytes).map_err(E::custom)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                self.visit_byte_buf(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                OprfAuthBlob::new(v).map_err(E::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BlobVisitor::<MAX_SIZE>)
+        } else {
+            deserializer.deserialize_byte_buf(BlobVisitor::<MAX_SIZE>)
+        }
+    }
+}
+
 /// Dynamic trait object for `OprfRequestAuthenticator` service.
 pub type OprfRequestAuthService<RequestAuth> =
     Arc<dyn OprfRequestAuthenticator<RequestAuth = RequestAuth>>;
@@ -421,6 +1227,73 @@ pub type OprfRequestAuthService<RequestAuth> =
 mod tests {
     use super::*;
 
+    #[test]
+    fn schema_fingerprint_is_pinned() {
+        // if this fails, the schema definition changed: make sure this is intended and update the value
+        assert_eq!(
+            SchemaFingerprint::CURRENT.to_string(),
+            "3546f45cb61c7244",
+            "schema fingerprint changed"
+        );
+        let json = serde_json::to_string(&SchemaFingerprint::CURRENT).expect("Can serialize");
+        assert_eq!(json, "\"3546f45cb61c7244\"");
+        let decoded: SchemaFingerprint = serde_json::from_str(&json).expect("Can deserialize");
+        assert_eq!(decoded, SchemaFingerprint::CURRENT);
+        assert_ne!(
+            SchemaFingerprint::of("client->node OprfRequest{}"),
+            SchemaFingerprint::CURRENT,
+            "different definitions have different fingerprints"
+        );
+    }
+
+    #[test]
+    fn auth_blob_json_is_base64() {
+        let blob = OprfAuthBlob::<8>::new(vec![0, 1, 2, 255]).expect("fits");
+        let json = serde_json::to_string(&blob).expect("Can serialize");
+        assert_eq!(json, "\"AAEC/w==\"");
+        let decoded: OprfAuthBlob<8> = serde_json::from_str(&json).expect("Can deserialize");
+        assert_eq!(decoded, blob);
+    }
+
+    #[test]
+    fn auth_blob_cbor_is_bytes() {
+        let blob = OprfAuthBlob::<8>::new(vec![0, 1, 2, 255]).expect("fits");
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&blob, &mut cbor).expect("Can serialize");
+        // major type 2 (byte string) with length 4
+        assert_eq!(cbor, [0x44, 0, 1, 2, 255]);
+        let decoded: OprfAuthBlob<8> =
+            ciborium::from_reader(cbor.as_slice()).expect("Can deserialize");
+        assert_eq!(decoded, blob);
+    }
+
+    #[test]
+    fn auth_blob_rejects_too_large() {
+        assert_eq!(
+            OprfAuthBlob::<2>::new(vec![0; 3]),
+            Err(AuthBlobTooLarge {
+                size: 3,
+                max_size: 2
+            })
+        );
+        let json = serde_json::to_string(&OprfAuthBlob::<64>::new(vec![0; 64]).expect("fits"))
+            .expect("Can serialize");
+        assert!(
+            serde_json::from_str::<OprfAuthBlob<8>>(&json).is_err(),
+            "should reject too large base64 blob"
+        );
+        let mut cbor = Vec::new();
+        ciborium::into_writer(
+            &OprfAuthBlob::<64>::new(vec![0; 9]).expect("fits"),
+            &mut cbor,
+        )
+        .expect("Can serialize");
+        assert!(
+            ciborium::from_reader::<OprfAuthBlob<8>, _>(cbor.as_slice()).is_err(),
+            "should reject too large binary blob"
+        );
+    }
+
     #[test]
     fn oprf_error_kind_from_service_codes() {
         assert_eq!(
@@ -459,6 +1332,46 @@ mod tests {
             OprfErrorKind::from(oprf_error_codes::DUPLICATE_COEFFICIENT),
             OprfErrorKind::DuplicateCoefficient
         );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::STALE_QUERY),
+            OprfErrorKind::StaleQuery
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::AUTH_TIMEOUT),
+            OprfErrorKind::AuthTimeout
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::CHALLENGE_MISMATCH),
+            OprfErrorKind::ChallengeMismatch
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::RISK_DENIED),
+            OprfErrorKind::RiskDenied
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::RISK_THROTTLED),
+            OprfErrorKind::RiskThrottled
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::SESSION_LOST),
+            OprfErrorKind::SessionLost
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::BATCH_TOO_LARGE),
+            OprfErrorKind::BatchTooLarge
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::QUOTA_EXCEEDED),
+            OprfErrorKind::QuotaExceeded
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::KEY_BUSY),
+            OprfErrorKind::KeyBusy
+        );
+        assert_eq!(
+            OprfErrorKind::from(oprf_error_codes::NOTIFICATIONS_LAGGED),
+            OprfErrorKind::NotificationsLagged
+        );
     }
 
     #[test]
@@ -483,7 +1396,7 @@ mod tests {
     #[test]
     fn oprf_error_kind_from_unknown_codes() {
         // Gap between service codes and auth range
-        assert_eq!(OprfErrorKind::from(4011), OprfErrorKind::Unknown);
+        assert_eq!(OprfErrorKind::from(4021), OprfErrorKind::Unknown);
         assert_eq!(OprfErrorKind::from(4499), OprfErrorKind::Unknown);
         // RFC 6455 codes not explicitly mapped
         assert_eq!(OprfErrorKind::from(1000), OprfErrorKind::Unknown);
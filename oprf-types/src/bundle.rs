@@ -0,0 +1,252 @@
+//! Committee bundles: the configuration of an entire committee as one signed artifact.
+//!
+//! A [`CommitteeBundle`] contains everything needed to talk to an environment: the chain id, the address of the `OprfKeyRegistry`, the threshold and the party id, wallet address and URL of every node. It is produced by an admin from the chain state (see the `export-bundle` command of the dev client) and signed with the admin wallet as [EIP-191](https://eips.ethereum.org/EIPS/eip-191) message, so it can be shared over untrusted channels.
+//!
+//! Consumers only trust a bundle after checking the signature against the admin address they know:
+//!
+//! ```ignore
+//! let signed: SignedCommitteeBundle = serde_json::from_str(&file)?;
+//! let bundle = signed.verify(trusted_admin)?;
+//! ```
+
+use std::{collections::HashSet, fmt, num::NonZeroU16};
+
+use alloy_primitives::Signature;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{Committee, CommitteeMember},
+    crypto::PartyId,
+};
+
+pub use alloy_primitives::Address;
+
+/// Prefixed to the signed message, so a bundle signature cannot be mistaken for any other message signed by the admin.
+const SIGNING_DOMAIN: &[u8] = b"taceo-oprf committee bundle v1\n";
+
+/// A node of a [`CommitteeBundle`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleMember {
+    /// The party id registered for the node
+    pub party_id: PartyId,
+    /// The registered wallet address of the node
+    pub address: Address,
+    /// The base URL of the node, e.g., `https://node0.example.com`
+    pub url: String,
+}
+
+/// The configuration of a committee, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitteeBundle {
+    /// The chain the `OprfKeyRegistry` is deployed on
+    pub chain_id: u64,
+    /// The address of the `OprfKeyRegistry`
+    pub oprf_key_registry: Address,
+    /// The number of nodes needed to evaluate a key
+    pub threshold: NonZeroU16,
+    /// All nodes of the committee, ordered by party id
+    pub members: Vec<BundleMember>,
+}
+
+/// A [`CommitteeBundle`] with the signature of the admin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedCommitteeBundle {
+    /// The bundle
+    pub bundle: CommitteeBundle,
+    /// The EIP-191 signature of [`CommitteeBundle::signing_message`]
+    pub signature: Signature,
+}
+
+/// Errors when verifying a [`SignedCommitteeBundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BundleError {
+    /// The bundle was signed by someone other than the trusted admin.
+    WrongSigner {
+        /// The trusted admin
+        expected: Address,
+        /// The recovered signer, `None` if the signature is invalid
+        actual: Option<Address>,
+    },
+    /// The bundle itself is inconsistent.
+    Invalid(&'static str),
+}
+
+impl std::error::Error for BundleError {}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::WrongSigner {
+                expected,
+                actual: Some(actual),
+            } => write!(f, "bundle signed by {actual}, expected {expected}"),
+            BundleError::WrongSigner {
+                expected,
+                actual: None,
+            } => write!(f, "invalid bundle signature, expected signer {expected}"),
+            BundleError::Invalid(reason) => write!(f, "invalid bundle: {reason}"),
+        }
+    }
+}
+
+impl CommitteeBundle {
+    /// Returns the message the admin signs: a domain separator followed by the JSON encoding of the bundle.
+    ///
+    /// # Panics
+    /// Never, the bundle only contains types that serialize to JSON infallibly.
+    #[must_use]
+    pub fn signing_message(&self) -> Vec<u8> {
+        let json = serde_json::to_vec(self).expect("bundle can be serialized");
+        [SIGNING_DOMAIN, &json].concat()
+    }
+
+    /// Checks that the party ids are `0..n` in order, the wallet addresses are unique and the threshold is at most the number of nodes.
+    ///
+    /// # Errors
+    /// Returns [`BundleError::Invalid`] naming the first violated rule.
+    pub fn validate(&self) -> Result<(), BundleError> {
+        if usize::from(self.threshold.get()) > self.members.len() {
+            return Err(BundleError::Invalid("threshold exceeds number of nodes"));
+        }
+        if !self
+            .members
+            .iter()
+            .enumerate()
+            .all(|(i, member)| usize::from(member.party_id.0) == i)
+        {
+            return Err(BundleError::Invalid("party ids must be 0..n in order"));
+        }
+        let addresses = self
+            .members
+            .iter()
+            .map(|member| member.address)
+            .collect::<HashSet<_>>();
+        if addresses.len() != self.members.len() {
+            return Err(BundleError::Invalid("duplicate wallet address"));
+        }
+        Ok(())
+    }
+
+    /// Returns the base URLs of the nodes, ordered by party id.
+    #[must_use]
+    pub fn node_urls(&self) -> Vec<&str> {
+        self.members
+            .iter()
+            .map(|member| member.url.as_str())
+            .collect()
+    }
+
+    /// Returns the [`Committee`] as served by the nodes at `/committee`.
+    #[must_use]
+    pub fn committee(&self) -> Committee {
+        Committee {
+            members: self
+                .members
+                .iter()
+                .map(|member| CommitteeMember {
+                    party_id: member.party_id,
+                    address: member.address.to_checksum(None),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl SignedCommitteeBundle {
+    /// Returns the bundle if it is valid and was signed by `admin`.
+    ///
+    /// # Errors
+    /// Returns [`BundleError::WrongSigner`] if the signature is invalid or by someone else, and [`BundleError::Invalid`] if the bundle is inconsistent.
+    pub fn verify(&self, admin: Address) -> Result<&CommitteeBundle, BundleError> {
+        let signer = self
+            .signature
+            .recover_address_from_msg(self.bundle.signing_message())
+            .ok();
+        if signer != Some(admin) {
+            return Err(BundleError::WrongSigner {
+                expected: admin,
+                actual: signer,
+            });
+        }
+        self.bundle.validate()?;
+        Ok(&self.bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{B256, eip191_hash_message};
+    use k256::ecdsa::SigningKey;
+
+    use super::*;
+
+    fn bundle() -> CommitteeBundle {
+        CommitteeBundle {
+            chain_id: 31337,
+            oprf_key_registry: Address::repeat_byte(0x42),
+            threshold: NonZeroU16::new(2).expect("non-zero"),
+            members: (0..3)
+                .map(|i| BundleMember {
+                    party_id: PartyId(i),
+                    address: Address::repeat_byte(u8::try_from(i).expect("fits") + 1),
+                    url: format!("http://127.0.0.1:1000{i}"),
+                })
+                .collect(),
+        }
+    }
+
+    fn sign(bundle: CommitteeBundle, key: &SigningKey) -> SignedCommitteeBundle {
+        let hash: B256 = eip191_hash_message(bundle.signing_message());
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(hash.as_slice())
+            .expect("can sign");
+        SignedCommitteeBundle {
+            bundle,
+            signature: Signature::from_signature_and_parity(signature, recovery_id.is_y_odd()),
+        }
+    }
+
+    #[test]
+    fn verify_checks_signer_and_content() {
+        let admin = SigningKey::from_slice(&[1; 32]).expect("valid key");
+        let admin_address = Address::from_private_key(&admin);
+        let signed = sign(bundle(), &admin);
+
+        let json = serde_json::to_string(&signed).expect("can serialize");
+        let parsed: SignedCommitteeBundle = serde_json::from_str(&json).expect("can deserialize");
+        assert_eq!(
+            parsed.verify(admin_address),
+            Ok(&bundle()),
+            "should accept the bundle of the admin"
+        );
+
+        let other = Address::repeat_byte(0xff);
+        assert_eq!(
+            parsed.verify(other),
+            Err(BundleError::WrongSigner {
+                expected: other,
+                actual: Some(admin_address)
+            }),
+            "should reject other signers"
+        );
+
+        let mut tampered = parsed.clone();
+        tampered.bundle.threshold = NonZeroU16::MIN;
+        assert!(
+            matches!(
+                tampered.verify(admin_address),
+                Err(BundleError::WrongSigner { .. })
+            ),
+            "should reject modified bundles"
+        );
+
+        let mut invalid = bundle();
+        invalid.members[1].party_id = PartyId(2);
+        assert_eq!(
+            sign(invalid, &admin).verify(admin_address),
+            Err(BundleError::Invalid("party ids must be 0..n in order")),
+            "should reject inconsistent bundles"
+        );
+    }
+}
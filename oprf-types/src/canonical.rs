@@ -0,0 +1,255 @@
+//! Canonical JSON encoding for signatures and audit logs.
+//!
+//! Signatures over JSON and byte-for-byte comparisons of audit records need an encoding that only depends on the value, not on field order or the formatting of a serializer version. [`to_vec`] and [`to_string`] encode any `Serialize` type following [RFC 8785](https://www.rfc-editor.org/rfc/rfc8785) (JSON Canonicalization Scheme):
+//!
+//! - No whitespace.
+//! - Object members sorted by the UTF-16 code units of their names.
+//! - Strings escape only `"`, `\` and control characters (as `\b`, `\t`, `\n`, `\f`, `\r` or lower-case `\u00xx`).
+//! - Numbers formatted like ECMAScript's `Number.prototype.toString`, e.g., `1e+21`, `0.000001` or `1e-7`.
+//!
+//! Unlike RFC 8785, integers outside the I-JSON range (`±2^53`) are written with all digits instead of being rounded to a double.
+//!
+//! The encoding of the wire types is pinned by tests, so it stays stable across versions of this crate.
+
+use std::io::Write as _;
+
+use serde::Serialize;
+use serde_json::{Number, Value};
+
+/// Encodes `value` as canonical JSON, see the [module docs](self).
+///
+/// # Errors
+/// Returns an error if `value` cannot be represented as JSON, e.g., a map with non-string keys.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    let mut out = Vec::new();
+    write_value(&mut out, &value)?;
+    Ok(out)
+}
+
+/// Encodes `value` as canonical JSON string, see the [module docs](self).
+///
+/// # Errors
+/// See [`to_vec`].
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let bytes = to_vec(value)?;
+    String::from_utf8(bytes).map_err(serde::ser::Error::custom)
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Null => out.extend_from_slice(b"null"),
+        Value::Bool(true) => out.extend_from_slice(b"true"),
+        Value::Bool(false) => out.extend_from_slice(b"false"),
+        Value::Number(number) => write_number(out, number),
+        // serde_json escapes exactly the characters RFC 8785 requires
+        Value::String(string) => serde_json::to_writer(&mut *out, string)?,
+        Value::Array(values) => {
+            out.push(b'[');
+            for (idx, value) in values.iter().enumerate() {
+                if idx > 0 {
+                    out.push(b',');
+                }
+                write_value(out, value)?;
+            }
+            out.push(b']');
+        }
+        Value::Object(members) => {
+            let mut members = members.iter().collect::<Vec<_>>();
+            members.sort_by(|(lhs, _), (rhs, _)| lhs.encode_utf16().cmp(rhs.encode_utf16()));
+            out.push(b'{');
+            for (idx, (name, value)) in members.into_iter().enumerate() {
+                if idx > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, name)?;
+                out.push(b':');
+                write_value(out, value)?;
+            }
+            out.push(b'}');
+        }
+    }
+    Ok(())
+}
+
+fn write_number(out: &mut Vec<u8>, number: &Number) {
+    if let Some(int) = number.as_u64() {
+        write!(out, "{int}").expect("can write to vec");
+    } else if let Some(int) = number.as_i64() {
+        write!(out, "{int}").expect("can write to vec");
+    } else {
+        // serde_json only creates finite floats
+        let float = number.as_f64().expect("is a float");
+        out.extend_from_slice(format_float(float).as_bytes());
+    }
+}
+
+/// Formats a finite `float` like ECMAScript's `Number.prototype.toString`.
+fn format_float(float: f64) -> String {
+    if float == 0.0 {
+        // also covers -0
+        return "0".to_owned();
+    }
+    let sign = if float < 0.0 { "-" } else { "" };
+    // shortest round-trip digits, e.g., `1.2345e-7`
+    let scientific = format!("{:e}", float.abs());
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("scientific notation has exponent");
+    let digits = mantissa.replace('.', "");
+    let exponent = exponent.parse::<i32>().expect("exponent is an integer");
+    // the value is `0.{digits} * 10^n`
+    let n = exponent + 1;
+    let k = i32::try_from(digits.len()).expect("few digits");
+
+    let formatted = if k <= n && n <= 21 {
+        format!("{digits}{}", "0".repeat((n - k).unsigned_abs() as usize))
+    } else if 0 < n && n <= 21 {
+        let (int, frac) = digits.split_at(n.unsigned_abs() as usize);
+        format!("{int}.{frac}")
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{digits}", "0".repeat(n.unsigned_abs() as usize))
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let exponent_sign = if n - 1 < 0 { '-' } else { '+' };
+        let fraction = if rest.is_empty() {
+            String::new()
+        } else {
+            format!(".{rest}")
+        };
+        format!(
+            "{first}{fraction}e{exponent_sign}{}",
+            (n - 1).unsigned_abs()
+        )
+    };
+    format!("{sign}{formatted}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        OprfKeyId, ShareEpoch,
+        api::OprfRequest,
+        crypto::PartyId,
+        transcript::{FrameDirection, Transcript, TranscriptMessage, TranscriptParticipant},
+    };
+
+    #[test]
+    fn sorts_members_by_utf16_code_units() {
+        let value = json!({
+            "\u{20ac}": "Euro Sign",
+            "\r": "Carriage Return",
+            "\u{fb33}": "Hebrew Letter Dalet With Dagesh",
+            "1": "One",
+            "\u{1f600}": "Emoji: Grinning Face",
+            "\u{80}": "Control",
+            "\u{f6}": "Latin Small Letter O With Diaeresis",
+        });
+        assert_eq!(
+            to_string(&value).expect("can encode"),
+            "{\"\\r\":\"Carriage Return\",\"1\":\"One\",\"\u{80}\":\"Control\",\"\u{f6}\":\"Latin Small Letter O With Diaeresis\",\"\u{20ac}\":\"Euro Sign\",\"\u{1f600}\":\"Emoji: Grinning Face\",\"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}",
+            "members are sorted like RFC 8785 section 3.2.3"
+        );
+    }
+
+    #[test]
+    fn escapes_strings_and_drops_whitespace() {
+        let value =
+            json!({"b": [1, "a\u{1}\t\"\\/\u{7f}é"], "a": null, "c": {"y": true, "x": false}});
+        assert_eq!(
+            to_string(&value).expect("can encode"),
+            "{\"a\":null,\"b\":[1,\"a\\u0001\\t\\\"\\\\/\u{7f}é\"],\"c\":{\"x\":false,\"y\":true}}",
+            "only required characters are escaped"
+        );
+    }
+
+    #[test]
+    fn formats_numbers_like_ecmascript() {
+        let cases: [(f64, &str); 12] = [
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-1.5, "-1.5"),
+            (0.000_001, "0.000001"),
+            (1e-7, "1e-7"),
+            (1e21, "1e+21"),
+            (1e20, "100000000000000000000"),
+            (123_456_789_012_345_680_000.0, "123456789012345680000"),
+            (4.5, "4.5"),
+            (0.002, "0.002"),
+            (5e-324, "5e-324"),
+            (1.797_693_134_862_315_7e308, "1.7976931348623157e+308"),
+        ];
+        for (float, expected) in cases {
+            assert_eq!(format_float(float), expected, "formatting {float:e}");
+        }
+        assert_eq!(
+            to_string(&json!([u64::MAX, i64::MIN, 0.5])).expect("can encode"),
+            "[18446744073709551615,-9223372036854775808,0.5]",
+            "integers keep all digits"
+        );
+    }
+
+    #[test]
+    fn rejects_non_string_keys() {
+        let map = BTreeMap::from([(vec![1_u8], 1_u8)]);
+        assert!(to_vec(&map).is_err(), "keys must be strings");
+    }
+
+    // The encodings below are used for signatures and audit logs. Changing them breaks existing signatures, so they must stay byte-stable across versions.
+
+    #[test]
+    fn request_encoding_is_stable() {
+        let request = OprfRequest {
+            request_id: Uuid::from_u128(1),
+            blinded_query: ark_babyjubjub::EdwardsAffine::default(),
+            auth: json!({"token": "abc", "key": OprfKeyId::new(ruint::aliases::U160::from(7))}),
+            issued_at: Some(1_700_000_000),
+            batch: Vec::new(),
+        };
+        assert_eq!(
+            to_string(&request).expect("can encode"),
+            "{\"auth\":{\"key\":\"0x7\",\"token\":\"abc\"},\"blinded_query\":[\"0\",\"1\"],\"issued_at\":1700000000,\"request_id\":\"00000000-0000-0000-0000-000000000001\"}",
+            "canonical encoding of OprfRequest changed"
+        );
+    }
+
+    #[test]
+    fn transcript_encoding_is_stable() {
+        let mut transcript =
+            Transcript::new(Uuid::from_u128(2), TranscriptParticipant::Node(PartyId(1)));
+        transcript.oprf_key_id = Some(OprfKeyId::new(ruint::aliases::U160::from(42)));
+        transcript.record(
+            FrameDirection::Received,
+            TranscriptParticipant::Client,
+            TranscriptMessage::Request {
+                blinded_query: ark_babyjubjub::EdwardsAffine::default(),
+                issued_at: None,
+                batch: Vec::new(),
+            },
+        );
+        transcript.record(
+            FrameDirection::Sent,
+            TranscriptParticipant::Client,
+            TranscriptMessage::Close {
+                code: 4001,
+                reason: "unknown \"key\"".to_owned(),
+            },
+        );
+        assert_eq!(
+            to_string(&transcript).expect("can encode"),
+            "{\"frames\":[{\"direction\":\"received\",\"message\":{\"request\":{\"blinded_query\":[\"0\",\"1\"],\"issued_at\":null}},\"peer\":\"client\"},{\"direction\":\"sent\",\"message\":{\"close\":{\"code\":4001,\"reason\":\"unknown \\\"key\\\"\"}},\"peer\":\"client\"}],\"oprf_key_id\":\"0x2a\",\"recorded_by\":{\"node\":1},\"request_id\":\"00000000-0000-0000-0000-000000000002\"}",
+            "canonical encoding of Transcript changed"
+        );
+        assert_eq!(
+            to_string(&ShareEpoch::new(3)).expect("can encode"),
+            "3",
+            "canonical encoding of ShareEpoch changed"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Canonical CBOR encoding for replay protection and signatures over requests.
+//!
+//! `ciborium` writes struct fields in declaration order and other encoders (e.g., in other languages) may pick different orders or indefinite lengths, so the same request can be encoded to different bytes. [`to_vec`] encodes any `Serialize` type following the core deterministic encoding of [RFC 8949, section 4.2.1](https://www.rfc-editor.org/rfc/rfc8949#section-4.2.1):
+//!
+//! - Integers, lengths and tags use the shortest form.
+//! - Floats use the shortest of half, single and double precision that keeps the value.
+//! - Arrays, maps, byte and text strings use definite lengths.
+//! - Map entries are sorted by the bytewise lexicographic order of the canonical encoding of their keys. Duplicate keys are rejected.
+//!
+//! [`is_canonical`] checks whether received bytes follow this encoding, e.g., for nodes that only accept canonical requests.
+//!
+//! The encoding of the wire types is pinned by tests, so it stays stable across versions of this crate.
+
+use ciborium::{Value, ser::Error};
+
+/// Encodes `value` as canonical CBOR, see the [module docs](self).
+///
+/// # Errors
+/// Returns an error if `value` cannot be represented as CBOR or contains a map with duplicate keys.
+pub fn to_vec<T: serde::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error<std::io::Error>> {
+    let value = Value::serialized(value).map_err(|err| Error::Value(err.to_string()))?;
+    let mut out = Vec::new();
+    ciborium::into_writer(&canonicalize(value)?, &mut out)?;
+    Ok(out)
+}
+
+/// Returns `true` if `bytes` hold exactly one CBOR item in canonical encoding, see the [module docs](self).
+#[must_use]
+pub fn is_canonical(bytes: &[u8]) -> bool {
+    let Ok(value) = ciborium::from_reader::<Value, _>(bytes) else {
+        return false;
+    };
+    // decoding drops the encoding details (lengths, key order, trailing bytes), so re-encoding only reproduces canonical input
+    to_vec(&value).is_ok_and(|canonical| canonical == bytes)
+}
+
+/// Sorts the entries of all maps in `value` by the canonical encoding of their keys.
+fn canonicalize(value: Value) -> Result<Value, Error<std::io::Error>> {
+    let value = match value {
+        Value::Tag(tag, value) => Value::Tag(tag, Box::new(canonicalize(*value)?)),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(canonicalize)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Map(entries) => {
+            let mut entries = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = canonicalize(key)?;
+                    let mut encoded = Vec::new();
+                    ciborium::into_writer(&key, &mut encoded)?;
+                    Ok((encoded, key, canonicalize(value)?))
+                })
+                .collect::<Result<Vec<_>, Error<std::io::Error>>>()?;
+            entries.sort_by(|(lhs, _, _), (rhs, _, _)| lhs.cmp(rhs));
+            if entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+                return Err(Error::Value("duplicate map key".to_owned()));
+            }
+            Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(_, key, value)| (key, value))
+                    .collect(),
+            )
+        }
+        value => value,
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{OprfKeyId, api::OprfRequest};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut out, byte| {
+            write!(out, "{byte:02x}").expect("can write to string");
+            out
+        })
+    }
+
+    #[test]
+    fn sorts_map_keys_bytewise() {
+        let map = Value::Map(vec![
+            (Value::Text("bb".to_owned()), Value::Integer(1.into())),
+            (Value::Integer(10.into()), Value::Integer(2.into())),
+            (Value::Text("a".to_owned()), Value::Integer(3.into())),
+            (Value::Integer((-1).into()), Value::Integer(4.into())),
+        ]);
+        // 0x0a < 0x20 (-1) < 0x61 "a" < 0x62 "bb"
+        assert_eq!(
+            hex(&to_vec(&map).expect("can encode")),
+            "a40a02200461610362626201",
+            "keys are sorted by their encoding"
+        );
+    }
+
+    #[test]
+    fn uses_shortest_floats_and_definite_lengths() {
+        assert_eq!(
+            hex(&to_vec(&vec![1.5_f64, 0.1]).expect("can encode")),
+            "82f93e00fb3fb999999999999a",
+            "floats use the shortest lossless precision"
+        );
+        // indefinite-length array [1]
+        assert!(!is_canonical(&[0x9f, 0x01, 0xff]), "indefinite length");
+        assert!(is_canonical(&[0x81, 0x01]), "definite length");
+        // 1 encoded in one additional byte
+        assert!(!is_canonical(&[0x18, 0x01]), "integer not in shortest form");
+        assert!(!is_canonical(&[0x01, 0x01]), "trailing bytes");
+        assert!(
+            !is_canonical(&[0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02]),
+            "unsorted keys"
+        );
+        assert!(
+            !is_canonical(&[0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02]),
+            "duplicate keys"
+        );
+    }
+
+    #[test]
+    fn ciborium_encoding_of_structs_is_not_canonical() {
+        let request = request();
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&request, &mut cbor).expect("can encode");
+        assert!(!is_canonical(&cbor), "fields are in declaration order");
+        let canonical = to_vec(&request).expect("can encode");
+        assert!(is_canonical(&canonical), "canonical encoding is canonical");
+        let decoded: OprfRequest<OprfKeyId> =
+            ciborium::from_reader(canonical.as_slice()).expect("can decode");
+        assert_eq!(decoded.request_id, request.request_id, "round trip");
+    }
+
+    fn request() -> OprfRequest<OprfKeyId> {
+        OprfRequest {
+            request_id: Uuid::from_u128(1),
+            blinded_query: ark_babyjubjub::EdwardsAffine::default(),
+            auth: OprfKeyId::new(ruint::aliases::U160::from(7)),
+            issued_at: Some(1_700_000_000),
+            batch: Vec::new(),
+        }
+    }
+
+    // The encoding below is used for replay protection and signatures. Changing it breaks existing hashes, so it must stay byte-stable across versions.
+
+    #[test]
+    fn request_encoding_is_stable() {
+        assert_eq!(
+            hex(&to_vec(&request()).expect("can encode")),
+            "a46461757468540000000000000000000000000000000000000007696973737565645f61741a6553f1006a726571756573745f696450000000000000000000000000000000016d626c696e6465645f717565727958200100000000000000000000000000000000000000000000000000000000000000",
+            "canonical encoding of OprfRequest changed"
+        );
+    }
+}
@@ -3,7 +3,7 @@
 //! This module defines the events emitted by the blockchain
 //! and the contributions submitted in response to these events.
 //!
-//! Use these types to encode the payloads that nodes send and receive on-chain.
+//! Use these types to encode the payloads that nodes send and receive on-chain. See [`logs`] for processing past events deterministically.
 
 // we need this because the sol macro is angry otherwise
 
@@ -24,6 +24,8 @@ use crate::{
     },
 };
 
+pub mod logs;
+
 // Codegen from ABI file to interact with the contract.
 sol!(
     #[allow(
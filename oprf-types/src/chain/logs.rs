@@ -0,0 +1,149 @@
+//! Deterministic processing of past events.
+//!
+//! RPC providers return the results of `eth_getLogs` in slightly different orders, especially near the boundaries of the requested block range, and some of them reject ranges above a provider-specific maximum. To process past events the same way on every provider:
+//! - sort and deduplicate every batch with [`normalize_logs`],
+//! - drop logs of overlapping block ranges that were processed already with a [`LogWatermark`],
+//! - shrink the requested range if [`is_range_limit_error`] detects that the provider rejected it.
+
+use alloy::rpc::types::Log;
+
+/// The position of a mined log on chain, ordered by block number, transaction index and log index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LogPosition {
+    /// The number of the block that contains the log.
+    pub block_number: u64,
+    /// The index of the transaction that emitted the log within its block.
+    pub transaction_index: u64,
+    /// The index of the log within its block.
+    pub log_index: u64,
+}
+
+impl LogPosition {
+    /// Returns the position of the log, `None` for pending logs.
+    #[must_use]
+    pub fn of(log: &Log) -> Option<Self> {
+        Some(Self {
+            block_number: log.block_number?,
+            transaction_index: log.transaction_index?,
+            log_index: log.log_index?,
+        })
+    }
+}
+
+/// Sorts the logs by their [`LogPosition`] and removes duplicates. Pending logs (without a position) are dropped.
+#[must_use]
+pub fn normalize_logs(logs: Vec<Log>) -> Vec<Log> {
+    let mut logs = logs
+        .into_iter()
+        .filter_map(|log| Some((LogPosition::of(&log)?, log)))
+        .collect::<Vec<_>>();
+    logs.sort_by_key(|(position, _)| *position);
+    logs.dedup_by_key(|(position, _)| *position);
+    logs.into_iter().map(|(_, log)| log).collect()
+}
+
+/// The position of the last processed log.
+///
+/// Used to skip logs that are returned again because consecutive block ranges overlap.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogWatermark(Option<LogPosition>);
+
+impl LogWatermark {
+    /// Returns `true` and advances the watermark if the log is after the last processed one. Returns `false` for logs processed already and for pending logs.
+    pub fn advance(&mut self, log: &Log) -> bool {
+        let Some(position) = LogPosition::of(log) else {
+            return false;
+        };
+        if self.0.is_some_and(|last| position <= last) {
+            return false;
+        }
+        self.0 = Some(position);
+        true
+    }
+}
+
+/// Returns `true` if the error message of an `eth_getLogs` call indicates that the requested block range or result set exceeds the limits of the provider.
+///
+/// Providers do not agree on an error code for this, so this matches the messages of common providers (e.g., "block range is too large", "query returned more than 10000 results", "exceed maximum block range").
+#[must_use]
+pub fn is_range_limit_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    [
+        "block range",
+        "range too large",
+        "range is too large",
+        "returned more than",
+        "too many results",
+        "response size exceeded",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(block_number: u64, transaction_index: u64, log_index: u64) -> Log {
+        Log {
+            block_number: Some(block_number),
+            transaction_index: Some(transaction_index),
+            log_index: Some(log_index),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn normalize_sorts_and_dedups() {
+        let logs = normalize_logs(vec![
+            log(2, 0, 3),
+            log(1, 1, 1),
+            log(1, 0, 0),
+            log(2, 0, 3),
+            Log::default(),
+        ]);
+        assert_eq!(
+            logs.iter().filter_map(LogPosition::of).collect::<Vec<_>>(),
+            vec![
+                LogPosition::of(&log(1, 0, 0)).expect("mined"),
+                LogPosition::of(&log(1, 1, 1)).expect("mined"),
+                LogPosition::of(&log(2, 0, 3)).expect("mined"),
+            ],
+            "should be sorted without duplicates"
+        );
+        assert_eq!(logs.len(), 3, "should drop pending logs");
+    }
+
+    #[test]
+    fn watermark_skips_processed_logs() {
+        let mut watermark = LogWatermark::default();
+        assert!(watermark.advance(&log(1, 0, 0)), "first log is new");
+        assert!(watermark.advance(&log(1, 1, 1)), "later log is new");
+        assert!(!watermark.advance(&log(1, 1, 1)), "same log is processed");
+        assert!(
+            !watermark.advance(&log(1, 0, 0)),
+            "earlier log is processed"
+        );
+        assert!(
+            !watermark.advance(&Log::default()),
+            "pending log is skipped"
+        );
+        assert!(watermark.advance(&log(2, 0, 0)), "next block is new");
+    }
+
+    #[test]
+    fn detects_range_limit_errors() {
+        assert!(
+            is_range_limit_error("eth_getLogs block range is too large, max 2000"),
+            "should detect range errors"
+        );
+        assert!(
+            is_range_limit_error("Query returned more than 10000 results"),
+            "should detect result limits"
+        );
+        assert!(
+            !is_range_limit_error("connection reset by peer"),
+            "should ignore other errors"
+        );
+    }
+}
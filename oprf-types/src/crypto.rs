@@ -11,7 +11,7 @@
 //! * [`SecretGenCommitment`]
 //! * `SecretGenCiphertexts` / `SecretGenCiphertext` (requires the `chain` feature)
 
-use std::fmt;
+use std::{fmt, num::NonZeroU16};
 
 use ark_serde_compat::babyjubjub;
 use ark_serialize::CanonicalDeserialize;
@@ -25,15 +25,22 @@ use crate::{ShareEpoch, api::OprfPublicKeyWithEpoch};
 
 /// The party id of the OPRF node.
 #[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct PartyId(pub u16);
 
 /// The ephemeral public key of an OPRF node.
 ///
 /// Can only be constructed if on curve and on correct subgroup.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(transparent)]
 pub struct EphemeralEncryptionPublicKey(
-    #[serde(with = "babyjubjub::affine")] ark_babyjubjub::EdwardsAffine,
+    #[serde(with = "babyjubjub::affine")]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::BabyJubJubAffine")
+    )]
+    ark_babyjubjub::EdwardsAffine,
 );
 
 /// The OPRF public-key.
@@ -51,8 +58,16 @@ pub struct EphemeralEncryptionPublicKey(
     CanonicalSerialize,
     CanonicalDeserialize,
 )]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(transparent)]
-pub struct OprfPublicKey(#[serde(with = "babyjubjub::affine")] ark_babyjubjub::EdwardsAffine);
+pub struct OprfPublicKey(
+    #[serde(with = "babyjubjub::affine")]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::BabyJubJubAffine")
+    )]
+    ark_babyjubjub::EdwardsAffine,
+);
 
 /// The public contribution of one OPRF node for the first round of the OPRF-nullifier generation protocol.
 ///
@@ -64,11 +79,17 @@ pub struct OprfPublicKey(#[serde(with = "babyjubjub::affine")] ark_babyjubjub::E
 /// See [Appendix B.2 of our design document](https://github.com/TaceoLabs/nullifier-oracle-service/blob/491416de204dcad8d46ee1296d59b58b5be54ed9/docs/oprf.pdf)
 /// for more information about the OPRF-nullifier generation protocol.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SecretGenCommitment {
     #[serde(with = "babyjubjub::affine")]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::BabyJubJubAffine")
+    )]
     /// The commitment to the random value sampled by the node.
     pub comm_share: ark_babyjubjub::EdwardsAffine,
     #[serde(with = "ark_serde_compat::field")]
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::FieldElement"))]
     /// The commitment to the polynomial used to hide the sampled secret.
     pub comm_coeffs: ark_babyjubjub::Fq,
     /// The ephemeral public key for this key generation.
@@ -82,8 +103,10 @@ pub struct SecretGenCommitment {
 /// is sorted according to their respective party ID.
 #[cfg(feature = "chain")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SecretGenCiphertexts {
     /// The proof that the ciphertexts were computed correctly
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::Groth16Proof"))]
     pub proof: Proof<Bn254>,
     /// All ciphers for nodes (including node itself).
     pub ciphers: Vec<SecretGenCiphertext>,
@@ -94,14 +117,21 @@ pub struct SecretGenCiphertexts {
 /// Contains the [`EphemeralEncryptionPublicKey`] of the sender, the ciphertext itself, and a nonce.
 #[cfg(feature = "chain")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SecretGenCiphertext {
     #[serde(with = "ark_serde_compat::field")]
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::FieldElement"))]
     /// The nonce used during encryption.
     pub nonce: ark_babyjubjub::Fq,
     #[serde(with = "ark_serde_compat::field")]
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::FieldElement"))]
     /// The ciphertext.
     pub cipher: ark_babyjubjub::Fq,
     #[serde(with = "babyjubjub::affine")]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "crate::schema::BabyJubJubAffine")
+    )]
     /// The commitment to the encrypted value. Computed as xG, where x
     /// is the plaintext and G the generator of `BabyJubJub`.
     pub commitment: ark_babyjubjub::EdwardsAffine,
@@ -238,11 +268,14 @@ impl From<PartyId> for u16 {
 /// * The [`DLogShareShamir`].
 /// * The [`OprfPublicKey`].
 /// * The [`ShareEpoch`].
+/// * The threshold of the key, if it was stored with the share.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct OprfKeyMaterial {
     share: DLogShareShamir,
     oprf_public_key: OprfPublicKey,
     epoch: ShareEpoch,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<NonZeroU16>,
 }
 
 impl fmt::Debug for OprfKeyMaterial {
@@ -251,6 +284,7 @@ impl fmt::Debug for OprfKeyMaterial {
             .field("share", &"[redacted]")
             .field("oprf_public_key", &self.oprf_public_key)
             .field("epoch", &self.epoch)
+            .field("threshold", &self.threshold)
             .finish()
     }
 }
@@ -263,9 +297,25 @@ impl OprfKeyMaterial {
             share,
             oprf_public_key,
             epoch,
+            threshold: None,
         }
     }
 
+    /// Sets the threshold of the key.
+    #[must_use]
+    pub fn with_threshold(mut self, threshold: NonZeroU16) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Returns the threshold of the key.
+    ///
+    /// `None` for keys generated before the threshold was stored per key, these use the threshold of the node.
+    #[must_use]
+    pub fn threshold(&self) -> Option<NonZeroU16> {
+        self.threshold
+    }
+
     /// Returns the latest [`ShareEpoch`].
     #[must_use]
     pub fn epoch(&self) -> ShareEpoch {
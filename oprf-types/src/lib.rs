@@ -31,6 +31,17 @@
 //! * On-chain contribution types exchanged during key generation (see the
 //!   `chain` module, available with the `chain` feature).
 //! * API versioned types for client/server communication (see [`api`] module).
+//! * Signed committee bundles describing an entire environment (see the
+//!   `bundle` module, available with the `bundle` feature).
+//! * Wallet signatures binding responses to the node that sent them (see the
+//!   `signed_response` module, available with the `signed-responses` feature).
+//! * Protocol transcripts for debugging sessions (see [`transcript`] module).
+//! * Canonical JSON for signatures and audit logs and canonical CBOR for
+//!   replay protection of requests (see the `canonical` and `canonical_cbor`
+//!   modules, available with the `canonical` feature).
+//! * The retry policy shared by nodes, key-gen and client (see [`retry`] module).
+//! * JSON Schemas of the wire types (see the `schema` module, available with
+//!   the `schemars` feature).
 //!
 //! Use these types to pass, store, and (de)serialize identifiers and
 //! cryptographic values in a type-safe way throughout your application.
@@ -45,23 +56,39 @@ pub use ark_babyjubjub;
 pub use async_trait;
 
 pub mod api;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+#[cfg(feature = "canonical")]
+pub mod canonical;
+#[cfg(feature = "canonical")]
+pub mod canonical_cbor;
 #[cfg(feature = "chain")]
 pub mod chain;
 pub mod crypto;
+pub mod retry;
+#[cfg(feature = "schemars")]
+pub mod schema;
 #[cfg(feature = "service")]
 pub mod service;
+#[cfg(feature = "signed-responses")]
+pub mod signed_response;
+pub mod transcript;
 
 /// Represents an epoch for the `DLog` secret-share.
 #[derive(
     Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Default,
 )]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(transparent)]
 pub struct ShareEpoch(u32);
 
 /// The id of a relying party.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(transparent)]
-pub struct OprfKeyId(U160);
+pub struct OprfKeyId(
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::U160Hex"))] U160,
+);
 
 impl ShareEpoch {
     /// Converts the key epoch to an u32
@@ -0,0 +1,150 @@
+//! Retry policy shared by the OPRF nodes, the key-gen and the client.
+//!
+//! A [`RetryPolicy`] describes how often and how long to wait before retrying a failed operation, e.g. a secret-manager read, a DB query or an HTTP request to a node. Which errors are retried is decided by the caller, as only it knows which errors are transient:
+//!
+//! ```ignore
+//! operation
+//!     .retry(policy.backoff())
+//!     .sleep(tokio::time::sleep)
+//!     .when(is_retryable_error)
+//!     .await
+//! ```
+
+use std::time::Duration;
+
+use backon::ExponentialBuilder;
+
+/// Exponential backoff with jitter and a maximum number of retries.
+///
+/// The `n`-th retry waits `initial_delay * factor^(n-1)`, capped at `max_delay`. With jitter, a random delay of up to the same length is added, so that many callers failing at the same time do not retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    factor: f32,
+    max_retries: usize,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    /// 3 retries, starting at `1 s`, doubling up to `30 s`, with jitter.
+    fn default() -> Self {
+        Self::new(3, Duration::from_secs(1))
+    }
+}
+
+impl RetryPolicy {
+    /// Doubles the delay after every retry, starting at `initial_delay`, up to `30 s`, with jitter.
+    #[must_use]
+    pub fn new(max_retries: usize, initial_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            max_delay: Duration::from_secs(30),
+            factor: 2.0,
+            max_retries,
+            jitter: true,
+        }
+    }
+
+    /// Waits the same `delay` before every retry, without jitter, e.g. for polling.
+    #[must_use]
+    pub fn constant(max_retries: usize, delay: Duration) -> Self {
+        Self {
+            initial_delay: delay,
+            max_delay: delay,
+            factor: 1.0,
+            max_retries,
+            jitter: false,
+        }
+    }
+
+    /// Caps the delay between two attempts at `max_delay`.
+    #[must_use]
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Multiplies the delay by `factor` after every retry.
+    #[must_use]
+    pub fn with_factor(mut self, factor: f32) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Disables the jitter, e.g. for deterministic tests.
+    #[must_use]
+    pub fn without_jitter(mut self) -> Self {
+        self.jitter = false;
+        self
+    }
+
+    /// Returns the maximum number of retries after the first attempt.
+    #[must_use]
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
+    }
+
+    /// Returns the backoff for [`backon::Retryable::retry`].
+    #[must_use]
+    pub fn backoff(&self) -> ExponentialBuilder {
+        let builder = ExponentialBuilder::new()
+            .with_min_delay(self.initial_delay)
+            .with_max_delay(self.max_delay)
+            .with_factor(self.factor)
+            .with_max_times(self.max_retries);
+        if self.jitter {
+            builder.with_jitter()
+        } else {
+            builder
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backon::BackoffBuilder as _;
+
+    use super::*;
+
+    #[test]
+    fn exponential_delays_are_capped() {
+        let delays = RetryPolicy::new(5, Duration::from_secs(1))
+            .with_max_delay(Duration::from_secs(5))
+            .without_jitter()
+            .backoff()
+            .build()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [1, 2, 4, 5, 5].map(Duration::from_secs),
+            "should double up to the max delay"
+        );
+    }
+
+    #[test]
+    fn constant_delays() {
+        let delays = RetryPolicy::constant(3, Duration::from_millis(10))
+            .backoff()
+            .build()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [Duration::from_millis(10); 3],
+            "should wait the same delay before every retry"
+        );
+    }
+
+    #[test]
+    fn jitter_stays_within_twice_the_delay() {
+        let policy = RetryPolicy::new(4, Duration::from_secs(1));
+        for (n, delay) in policy.backoff().build().enumerate() {
+            let base = Duration::from_secs(1 << n);
+            assert!(
+                delay >= base && delay <= 2 * base,
+                "retry {n} waits {delay:?}, expected between {base:?} and {:?}",
+                2 * base
+            );
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! JSON Schemas of the wire types (requires the `schemars` feature).
+//!
+//! With the `schemars` feature enabled, the messages in [`crate::api`], the identifiers of this crate and (with the `chain` feature) the on-chain contribution types in [`crate::crypto`] implement [`schemars::JsonSchema`]. The schemas describe the JSON encoding of the types, the CBOR encoding uses raw bytes for curve points instead.
+//!
+//! Some fields hold types of `ark` or `oprf-core`, which do not implement [`schemars::JsonSchema`]. This module provides schema-only stand-ins for them. They are never constructed, they only describe how the wrapped types are serialized. [`DLogCommitments`] and [`DLogProofShare`] also describe the challenge and proof share messages of the protocol.
+//!
+//! The `json-schema` binary of the dev-client writes the schemas of all messages for the current API version.
+
+use std::borrow::Cow;
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+
+/// A `BabyJubJub` point in affine representation, serialized as `[x, y]` with decimal coordinates.
+pub struct BabyJubJubAffine;
+
+impl JsonSchema for BabyJubJubAffine {
+    fn schema_name() -> Cow<'static, str> {
+        "BabyJubJubAffine".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A BabyJubJub point in affine representation as [x, y], both coordinates are decimal strings.",
+            "type": "array",
+            "items": { "type": "string", "pattern": "^[0-9]+$" },
+            "minItems": 2,
+            "maxItems": 2
+        })
+    }
+}
+
+/// An element of a prime field (e.g., the base or scalar field of `BabyJubJub`), serialized as decimal string.
+pub struct FieldElement;
+
+impl JsonSchema for FieldElement {
+    fn schema_name() -> Cow<'static, str> {
+        "FieldElement".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A prime field element as decimal string.",
+            "type": "string",
+            "pattern": "^[0-9]+$"
+        })
+    }
+}
+
+/// A 160-bit unsigned integer, serialized as `0x`-prefixed hex string.
+pub struct U160Hex;
+
+impl JsonSchema for U160Hex {
+    fn schema_name() -> Cow<'static, str> {
+        "U160".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A 160-bit unsigned integer as 0x-prefixed hex string.",
+            "type": "string",
+            "pattern": "^0x[0-9a-fA-F]{1,40}$"
+        })
+    }
+}
+
+/// Stand-in for `oprf_core::ddlog_equality::shamir::PartialDLogCommitmentsShamir`, the commitments a node sends in its [`crate::api::OprfResponse`].
+#[derive(JsonSchema)]
+#[schemars(rename = "PartialDLogCommitmentsShamir")]
+pub struct PartialDLogCommitments {
+    /// The share of the result `C = B·x`.
+    pub c: BabyJubJubAffine,
+    /// The share of `G·d1`.
+    pub d1: BabyJubJubAffine,
+    /// The share of `G·d2`.
+    pub d2: BabyJubJubAffine,
+    /// The share of `G·e1`.
+    pub e1: BabyJubJubAffine,
+    /// The share of `G·e2`.
+    pub e2: BabyJubJubAffine,
+}
+
+/// Stand-in for `oprf_core::ddlog_equality::shamir::DLogCommitmentsShamir`, the challenge a client sends to the nodes.
+#[derive(JsonSchema)]
+#[schemars(rename = "DLogCommitmentsShamir")]
+pub struct DLogCommitments {
+    /// The combined result `C`.
+    pub c: BabyJubJubAffine,
+    /// The combined `G·d1`.
+    pub d1: BabyJubJubAffine,
+    /// The combined `G·d2`.
+    pub d2: BabyJubJubAffine,
+    /// The combined `G·e1`.
+    pub e1: BabyJubJubAffine,
+    /// The combined `G·e2`.
+    pub e2: BabyJubJubAffine,
+    /// The sorted ids (party id + 1) of the nodes that contributed to the commitments.
+    pub contributing_parties: Vec<u16>,
+}
+
+/// Stand-in for `oprf_core::ddlog_equality::shamir::DLogProofShareShamir`, the proof share a node sends after the challenge.
+#[derive(JsonSchema)]
+#[schemars(rename = "DLogProofShareShamir", transparent)]
+pub struct DLogProofShare(pub FieldElement);
+
+/// Stand-in for `oprf_core::dlog_equality::DLogEqualityProof`, the combined proof an aggregator sends.
+#[derive(JsonSchema)]
+#[schemars(rename = "DLogEqualityProof")]
+pub struct DLogProof {
+    /// The Fiat-Shamir challenge.
+    pub e: FieldElement,
+    /// The response.
+    pub s: FieldElement,
+}
+
+/// Stand-in for a Circom Groth16 proof over BN254.
+#[cfg(feature = "chain")]
+#[derive(JsonSchema)]
+#[schemars(rename = "Groth16Proof")]
+pub struct Groth16Proof {
+    /// Proof element A in G1 as projective `[x, y, z]`.
+    pub pi_a: [FieldElement; 3],
+    /// Proof element B in G2 as projective `[x, y, z]`, every coordinate is a pair `[c0, c1]`.
+    pub pi_b: [[FieldElement; 2]; 3],
+    /// Proof element C in G1 as projective `[x, y, z]`.
+    pub pi_c: [FieldElement; 3],
+    /// The protocol of the proof (always `"groth16"`).
+    pub protocol: String,
+    /// The curve of the proof.
+    pub curve: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::OprfResponse;
+
+    #[test]
+    fn response_schema_describes_wire_format() {
+        let schema = serde_json::to_value(schemars::schema_for!(OprfResponse)).expect("json");
+        let required = schema["required"].as_array().expect("required fields");
+        for field in ["commitments", "party_id", "oprf_pub_key_with_epoch"] {
+            assert!(
+                required.iter().any(|r| r == field),
+                "{field} should be required"
+            );
+        }
+        assert_eq!(
+            schema["$defs"]["BabyJubJubAffine"]["maxItems"], 2,
+            "points should be coordinate pairs"
+        );
+        assert_eq!(
+            schema["$defs"]["ShareEpoch"]["type"], "integer",
+            "epochs should be plain integers"
+        );
+    }
+}
@@ -1,7 +1,7 @@
 //! Types for communication between key-gen and nodes.
 use std::num::NonZeroU16;
 
-use sqlx::{Row, postgres::PgRow};
+use sqlx::{ColumnIndex, Decode, Row, Type};
 
 use crate::crypto::PartyId;
 
@@ -13,8 +13,14 @@ pub struct NodeInformation {
     threshold: NonZeroU16,
 }
 
-impl<'r> sqlx::FromRow<'r, PgRow> for NodeInformation {
-    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+impl<'r, R> sqlx::FromRow<'r, R> for NodeInformation
+where
+    R: Row,
+    &'static str: ColumnIndex<R>,
+    i32: Decode<'r, R::Database> + Type<R::Database>,
+    String: Decode<'r, R::Database> + Type<R::Database>,
+{
+    fn from_row(row: &'r R) -> Result<Self, sqlx::Error> {
         let party_id: i32 = row.try_get("party_id")?;
         let address: String = row.try_get("eth_address")?;
         let threshold: i32 = row.try_get("threshold")?;
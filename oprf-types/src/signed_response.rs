@@ -0,0 +1,155 @@
+//! Signed responses: binds an [`OprfResponse`] to the wallet of the node that sent it.
+//!
+//! A node configured to sign its responses attaches a [`ResponseSignature`] of its wallet key to every [`OprfResponse`]. The signed message is a domain separator followed by the canonical CBOR encoding (see [`crate::canonical_cbor`]) of the session id, the commitments (including the commitments of a batch session), the party id and the epoch of the response, signed as [EIP-191](https://eips.ethereum.org/EIPS/eip-191) message.
+//!
+//! Clients recover the signer and compare it with the wallet address registered for the party id, e.g., in the [`Committee`](crate::api::Committee) read from the `OprfKeyRegistry`:
+//!
+//! ```ignore
+//! let signer = response.signer(request_id);
+//! assert_eq!(signer, Some(registered_address));
+//! ```
+
+use alloy_primitives::{B256, Signature, eip191_hash_message};
+use k256::ecdsa::SigningKey;
+use oprf_core::ddlog_equality::shamir::PartialDLogCommitmentsShamir;
+use uuid::Uuid;
+
+use crate::{
+    ShareEpoch,
+    api::{OprfResponse, RESPONSE_SIGNATURE_SIZE, ResponseSignature},
+    canonical_cbor,
+    crypto::PartyId,
+};
+
+pub use alloy_primitives::Address;
+
+/// Prefixed to the signed message, so a response signature cannot be mistaken for any other message signed by the wallet of the node.
+const SIGNING_DOMAIN: &[u8] = b"taceo-oprf response v1\n";
+
+impl OprfResponse {
+    /// Returns the message a node signs for the response to the session `request_id`, see the [module docs](self).
+    #[must_use]
+    pub fn signing_message(&self, request_id: Uuid) -> Vec<u8> {
+        signing_message(
+            request_id,
+            &self.commitments,
+            &self.batch_commitments,
+            self.party_id,
+            self.oprf_pub_key_with_epoch.epoch,
+        )
+    }
+
+    /// Recovers the wallet address that signed the response to the session `request_id`.
+    ///
+    /// Returns `None` if the response carries no signature or the signature is invalid. Any other result must be compared with the wallet address registered for [`OprfResponse::party_id`].
+    #[must_use]
+    pub fn signer(&self, request_id: Uuid) -> Option<Address> {
+        self.signature
+            .as_ref()?
+            .recover_signer(&self.signing_message(request_id))
+    }
+}
+
+/// Returns the message a node signs for a response with the provided parts, see [`OprfResponse::signing_message`].
+///
+/// # Panics
+/// Never, the parts only contain types that serialize to CBOR infallibly.
+#[must_use]
+pub fn signing_message(
+    request_id: Uuid,
+    commitments: &PartialDLogCommitmentsShamir,
+    batch_commitments: &[PartialDLogCommitmentsShamir],
+    party_id: PartyId,
+    epoch: ShareEpoch,
+) -> Vec<u8> {
+    let cbor =
+        canonical_cbor::to_vec(&(request_id, commitments, batch_commitments, party_id, epoch))
+            .expect("response can be serialized");
+    [SIGNING_DOMAIN, &cbor].concat()
+}
+
+impl ResponseSignature {
+    /// Signs `message` with the wallet `key` as EIP-191 message.
+    ///
+    /// # Panics
+    /// Never, signing a prehashed message with a valid key does not fail.
+    #[must_use]
+    pub fn sign(key: &SigningKey, message: &[u8]) -> Self {
+        let hash: B256 = eip191_hash_message(message);
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(hash.as_slice())
+            .expect("can sign");
+        let signature = Signature::from_signature_and_parity(signature, recovery_id.is_y_odd());
+        Self::new(signature.as_bytes())
+    }
+
+    /// Recovers the wallet address that signed `message`, `None` if the signature is invalid.
+    #[must_use]
+    pub fn recover_signer(&self, message: &[u8]) -> Option<Address> {
+        let bytes: &[u8; RESPONSE_SIGNATURE_SIZE] = self.as_bytes().try_into().ok()?;
+        Signature::from_raw_array(bytes)
+            .ok()?
+            .recover_address_from_msg(message)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oprf_core::ddlog_equality::shamir::{DLogSessionShamir, DLogShareShamir};
+
+    use super::*;
+    use crate::{api::OprfPublicKeyWithEpoch, crypto::OprfPublicKey};
+
+    fn response() -> OprfResponse {
+        let (_, commitments) = DLogSessionShamir::partial_commitments(
+            ark_babyjubjub::EdwardsAffine::default(),
+            DLogShareShamir::from(ark_babyjubjub::Fr::from(1337)),
+            &mut rand::thread_rng(),
+        );
+        OprfResponse {
+            commitments,
+            party_id: PartyId(1),
+            oprf_pub_key_with_epoch: OprfPublicKeyWithEpoch {
+                key: OprfPublicKey::new(ark_babyjubjub::EdwardsAffine::default()),
+                epoch: ShareEpoch::from(3),
+            },
+            batch_commitments: Vec::new(),
+            share_proof: None,
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn signer_is_recovered_from_signed_parts() {
+        let wallet = SigningKey::from_slice(&[1; 32]).expect("valid key");
+        let request_id = Uuid::new_v4();
+        let mut response = response();
+        assert_eq!(response.signer(request_id), None, "unsigned response");
+
+        response.signature = Some(ResponseSignature::sign(
+            &wallet,
+            &response.signing_message(request_id),
+        ));
+        let cbor = canonical_cbor::to_vec(&response).expect("can serialize");
+        let mut response: OprfResponse =
+            ciborium::from_reader(cbor.as_slice()).expect("can deserialize");
+        assert_eq!(
+            response.signer(request_id),
+            Some(Address::from_private_key(&wallet)),
+            "should recover the wallet of the node"
+        );
+
+        assert_ne!(
+            response.signer(Uuid::new_v4()),
+            Some(Address::from_private_key(&wallet)),
+            "should bind the session id"
+        );
+        response.party_id = PartyId(2);
+        assert_ne!(
+            response.signer(request_id),
+            Some(Address::from_private_key(&wallet)),
+            "should bind the party id"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Protocol transcripts for debugging.
+//!
+//! A [`Transcript`] records the messages of a single OPRF session as seen by one participant, either a client or a node. Nodes and clients only capture transcripts if explicitly enabled, and write them to a file named by [`Transcript::file_name`] for offline analysis (e.g., with the `transcript-verify` tool of the dev-client).
+//!
+//! Transcripts contain the public messages of the protocol only. The authentication part of the [`crate::api::OprfRequest`] is never recorded, as it may contain credentials.
+
+use ark_serde_compat::babyjubjub;
+use oprf_core::ddlog_equality::shamir::{DLogCommitmentsShamir, DLogProofShareShamir};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    OprfKeyId,
+    api::{OprfBatchChallenge, OprfBatchProofShares, OprfResponse},
+    crypto::PartyId,
+};
+
+/// The messages of a single OPRF session as seen by one participant.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Transcript {
+    /// The id of the session.
+    pub request_id: Uuid,
+    /// The participant that recorded the transcript.
+    pub recorded_by: TranscriptParticipant,
+    /// The key the node authenticated the request for. Only known to nodes.
+    pub oprf_key_id: Option<OprfKeyId>,
+    /// The recorded frames, in the order they were sent or received.
+    pub frames: Vec<TranscriptFrame>,
+}
+
+impl Transcript {
+    /// Creates an empty transcript.
+    #[must_use]
+    pub fn new(request_id: Uuid, recorded_by: TranscriptParticipant) -> Self {
+        Self {
+            request_id,
+            recorded_by,
+            oprf_key_id: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Appends a frame to the transcript.
+    pub fn record(
+        &mut self,
+        direction: FrameDirection,
+        peer: TranscriptParticipant,
+        message: TranscriptMessage,
+    ) {
+        self.frames.push(TranscriptFrame {
+            direction,
+            peer,
+            message,
+        });
+    }
+
+    /// The file name of this transcript, `{request_id}-{recorded_by}.json`.
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        format!("{}-{}.json", self.request_id, self.recorded_by)
+    }
+}
+
+/// A participant of an OPRF session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum TranscriptParticipant {
+    /// The client (or a delegate acting as client).
+    Client,
+    /// The node with the provided party id.
+    Node(PartyId),
+}
+
+impl std::fmt::Display for TranscriptParticipant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Client => f.write_str("client"),
+            Self::Node(party_id) => write!(f, "node-{}", party_id.0),
+        }
+    }
+}
+
+/// Whether a frame was sent or received by the participant that recorded the transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum FrameDirection {
+    /// The frame was sent to the peer.
+    Sent,
+    /// The frame was received from the peer.
+    Received,
+}
+
+/// A single recorded frame.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TranscriptFrame {
+    /// Whether the frame was sent or received.
+    pub direction: FrameDirection,
+    /// The other side of the frame.
+    pub peer: TranscriptParticipant,
+    /// The content of the frame.
+    pub message: TranscriptMessage,
+}
+
+/// The content of a recorded frame.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum TranscriptMessage {
+    /// The [`crate::api::OprfRequest`] without its authentication part.
+    Request {
+        /// The blinded query of the request.
+        #[serde(with = "babyjubjub::affine")]
+        blinded_query: ark_babyjubjub::EdwardsAffine,
+        /// The issued-at timestamp of the request.
+        issued_at: Option<u64>,
+        /// The further blinded queries of a batch session.
+        #[serde(
+            default,
+            skip_serializing_if = "Vec::is_empty",
+            with = "babyjubjub::affine_seq"
+        )]
+        batch: Vec<ark_babyjubjub::EdwardsAffine>,
+    },
+    /// The [`OprfResponse`] of a node.
+    Response(OprfResponse),
+    /// The challenge sent by the client.
+    Challenge(DLogCommitmentsShamir),
+    /// The proof share of a node.
+    ProofShare(DLogProofShareShamir),
+    /// The challenges of a batch session sent by the client.
+    BatchChallenge(OprfBatchChallenge),
+    /// The proof shares of a node in a batch session.
+    BatchProofShares(OprfBatchProofShares),
+    /// The close frame that ended the session with an error.
+    Close {
+        /// The close code, see [`crate::api::oprf_error_codes`].
+        code: u16,
+        /// The reason of the close frame.
+        reason: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcript_roundtrip() {
+        let mut transcript = Transcript::new(Uuid::nil(), TranscriptParticipant::Node(PartyId(2)));
+        transcript.record(
+            FrameDirection::Received,
+            TranscriptParticipant::Client,
+            TranscriptMessage::Request {
+                blinded_query: ark_babyjubjub::EdwardsAffine::default(),
+                issued_at: Some(42),
+                batch: Vec::new(),
+            },
+        );
+        assert_eq!(
+            transcript.file_name(),
+            "00000000-0000-0000-0000-000000000000-node-2.json",
+            "file name should contain request id and participant"
+        );
+        let json = serde_json::to_string(&transcript).expect("Can serialize");
+        let parsed = serde_json::from_str::<Transcript>(&json).expect("Can deserialize");
+        assert_eq!(
+            parsed.recorded_by, transcript.recorded_by,
+            "same participant"
+        );
+        assert!(
+            matches!(
+                parsed.frames.as_slice(),
+                [TranscriptFrame {
+                    direction: FrameDirection::Received,
+                    peer: TranscriptParticipant::Client,
+                    message: TranscriptMessage::Request {
+                        issued_at: Some(42),
+                        ..
+                    },
+                }]
+            ),
+            "should keep the frame"
+        );
+    }
+}
@@ -32,6 +32,8 @@
 //!   (requires the `service` feature).
 //! - [`types`] – shared types and structs across OPRF crates
 //!   (requires the `types` feature).
+//! - [`supervisor`] – ordered shutdown of co-hosted services, e.g. the OPRF
+//!   service and the key-gen service in one process (requires the `supervisor` feature).
 //! - [`anvil`] – test helpers for deploying the OPRF contracts to a local
 //!   Anvil devnet (requires the `anvil` feature; not included in `full`).
 //!
@@ -58,6 +60,10 @@
 //! | Umbrella feature | Forwarded to            | Notes                               |
 //! |------------------|-------------------------|-------------------------------------|
 //! | `postgres`       | `oprf-service/postgres` | On by default via `full`            |
+//! | `sqlite`         | `oprf-service/sqlite`   | Opt-in, not part of `full`          |
+//! | `gcp`            | `oprf-service/gcp`      | Opt-in, not part of `full`          |
+//! | `azure`          | `oprf-service/azure`    | Opt-in, not part of `full`          |
+//! | `vault`          | `oprf-service/vault`    | Opt-in, not part of `full`          |
 //! | `chain`          | `oprf-types/chain`      | On by default via `full`            |
 //!
 //! The `anvil` feature is not forwarded from a sub-crate; it enables the
@@ -96,5 +102,8 @@ pub mod types {
     pub use oprf_types::*;
 }
 
+#[cfg(feature = "supervisor")]
+pub mod supervisor;
+
 #[cfg(feature = "anvil")]
 pub mod anvil;
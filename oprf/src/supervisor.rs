@@ -0,0 +1,346 @@
+//! Ordered shutdown for co-hosted OPRF services.
+//!
+//! When the OPRF service and the key-gen service run in the same process, the
+//! order in which their background tasks stop matters: the chain watchers must
+//! stop before the secret managers they write to are closed, and the HTTP
+//! servers should drain before either of them.
+//!
+//! [`ServiceSupervisor`] owns one [`CancellationToken`] per shutdown stage and
+//! the join handles of every task registered in that stage. Stages are shut
+//! down strictly in the order they were added by [`ServiceSupervisor::add_stage`];
+//! a stage is only cancelled after every task of the previous stage finished (or
+//! the overall deadline elapsed).
+//!
+//! ```no_run
+//! # async fn example(
+//! #     key_gen_tasks: oprf_key_gen_stub::KeyGenTasks,
+//! #     pool: oprf_key_gen_stub::Pool,
+//! # ) -> eyre::Result<()> {
+//! use std::time::Duration;
+//! use taceo_oprf::supervisor::ServiceSupervisor;
+//!
+//! let mut supervisor = ServiceSupervisor::new();
+//! let watchers = supervisor.add_stage("watchers");
+//! let secret_managers = supervisor.add_stage("secret-managers");
+//!
+//! supervisor.spawn(watchers, "key-gen", key_gen_tasks.join());
+//! supervisor.on_shutdown(secret_managers, "postgres", move || async move {
+//!     pool.close().await;
+//!     Ok(())
+//! });
+//!
+//! supervisor.shutdown_requested().cancelled().await;
+//! supervisor.shutdown(Duration::from_secs(10)).await?;
+//! # Ok(())
+//! # }
+//! # mod oprf_key_gen_stub {
+//! #     pub struct KeyGenTasks;
+//! #     impl KeyGenTasks { pub async fn join(self) -> eyre::Result<()> { Ok(()) } }
+//! #     pub struct Pool;
+//! #     impl Pool { pub async fn close(&self) {} }
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use tokio::{task::JoinHandle, time::Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Handle to a shutdown stage registered at a [`ServiceSupervisor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageId(usize);
+
+/// A task that did not shut down cleanly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FailedTask {
+    /// The name of the stage the task was registered in.
+    pub stage: &'static str,
+    /// The name of the task.
+    pub task: &'static str,
+    /// Why the task failed.
+    pub reason: String,
+}
+
+/// Error returned by [`ServiceSupervisor::shutdown`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ShutdownError {
+    /// The deadline elapsed before all tasks finished. The tasks still running
+    /// at that point were aborted.
+    #[error("shutdown deadline elapsed, aborted {} task(s)", aborted.len())]
+    DeadlineElapsed {
+        /// The `(stage, task)` names of the aborted tasks.
+        aborted: Vec<(&'static str, &'static str)>,
+        /// Tasks that finished with an error before the deadline elapsed.
+        failed: Vec<FailedTask>,
+    },
+    /// All tasks finished before the deadline, but some of them returned an error or panicked.
+    #[error("{} task(s) failed during shutdown", .0.len())]
+    TasksFailed(Vec<FailedTask>),
+}
+
+struct Stage {
+    name: &'static str,
+    cancellation_token: CancellationToken,
+    tasks: Vec<(&'static str, JoinHandle<eyre::Result<()>>)>,
+}
+
+/// Owns the cancellation tokens and join handles of co-hosted services and shuts
+/// them down in a fixed order.
+///
+/// See the [module docs](self) for an example.
+pub struct ServiceSupervisor {
+    stages: Vec<Stage>,
+    shutdown_requested: CancellationToken,
+}
+
+impl Default for ServiceSupervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for ServiceSupervisor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServiceSupervisor")
+            .field(
+                "stages",
+                &self
+                    .stages
+                    .iter()
+                    .map(|stage| stage.name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl ServiceSupervisor {
+    /// Creates a supervisor without any stages.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+            shutdown_requested: CancellationToken::new(),
+        }
+    }
+
+    /// Adds a new stage. Stages are shut down in the order they are added.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn add_stage(&mut self, name: &'static str) -> StageId {
+        let cancellation_token = CancellationToken::new();
+        // a service cancelling its own token (e.g. because a watcher died) requests
+        // the shutdown of the whole process, but must not skip the ordering
+        let stage_token = cancellation_token.clone();
+        let shutdown_requested = self.shutdown_requested.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                () = stage_token.cancelled() => shutdown_requested.cancel(),
+                () = shutdown_requested.cancelled() => {}
+            }
+        });
+        self.stages.push(Stage {
+            name,
+            cancellation_token,
+            tasks: Vec::new(),
+        });
+        StageId(self.stages.len() - 1)
+    }
+
+    /// The [`CancellationToken`] of the provided stage.
+    ///
+    /// Pass this token to the services started in this stage. Cancelling it from
+    /// within a service cancels [`Self::shutdown_requested`].
+    #[must_use]
+    pub fn cancellation_token(&self, stage: StageId) -> CancellationToken {
+        self.stage(stage).cancellation_token.clone()
+    }
+
+    /// A token that is cancelled as soon as any stage token is cancelled or
+    /// [`Self::shutdown`] is called.
+    ///
+    /// Wait on this token (alongside e.g. `SIGTERM`) to decide when to call
+    /// [`Self::shutdown`].
+    #[must_use]
+    pub fn shutdown_requested(&self) -> CancellationToken {
+        self.shutdown_requested.clone()
+    }
+
+    /// Registers an already spawned task in the provided stage.
+    pub fn add_task(
+        &mut self,
+        stage: StageId,
+        name: &'static str,
+        handle: JoinHandle<eyre::Result<()>>,
+    ) {
+        self.stage_mut(stage).tasks.push((name, handle));
+    }
+
+    /// Spawns the future and registers it in the provided stage.
+    ///
+    /// The future is expected to finish on its own once the stage's
+    /// [`CancellationToken`] is cancelled.
+    pub fn spawn<F>(&mut self, stage: StageId, name: &'static str, future: F)
+    where
+        F: Future<Output = eyre::Result<()>> + Send + 'static,
+    {
+        let handle = tokio::spawn(future);
+        self.add_task(stage, name, handle);
+    }
+
+    /// Runs `f` once the provided stage is cancelled.
+    ///
+    /// Use this for resources without a background task of their own, e.g.
+    /// closing a secret manager's connection pool.
+    pub fn on_shutdown<F, Fut>(&mut self, stage: StageId, name: &'static str, f: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = eyre::Result<()>> + Send,
+    {
+        let cancellation_token = self.cancellation_token(stage);
+        self.spawn(stage, name, async move {
+            cancellation_token.cancelled().await;
+            f().await
+        });
+    }
+
+    /// Shuts down all stages in order, waiting at most `deadline` in total.
+    ///
+    /// For every stage, its [`CancellationToken`] is cancelled and all its tasks
+    /// are joined before moving on to the next stage. If the deadline elapses, the
+    /// remaining tasks of all stages are aborted.
+    ///
+    /// # Errors
+    /// Returns a [`ShutdownError`] if the deadline elapsed or any task returned an
+    /// error or panicked.
+    pub async fn shutdown(self, deadline: Duration) -> Result<(), ShutdownError> {
+        let deadline = Instant::now() + deadline;
+        self.shutdown_requested.cancel();
+        let mut failed = Vec::new();
+        let mut aborted = Vec::new();
+        for stage in self.stages {
+            tracing::info!("shutting down stage {}", stage.name);
+            stage.cancellation_token.cancel();
+            for (task, mut handle) in stage.tasks {
+                if !aborted.is_empty() {
+                    handle.abort();
+                    aborted.push((stage.name, task));
+                    continue;
+                }
+                let reason = match tokio::time::timeout_at(deadline, &mut handle).await {
+                    Ok(Ok(Ok(()))) => continue,
+                    Ok(Ok(Err(err))) => format!("{err:?}"),
+                    Ok(Err(err)) => err.to_string(),
+                    Err(_) => {
+                        tracing::warn!("{}/{task} did not stop before the deadline", stage.name);
+                        handle.abort();
+                        aborted.push((stage.name, task));
+                        continue;
+                    }
+                };
+                tracing::warn!("{}/{task} failed during shutdown: {reason}", stage.name);
+                failed.push(FailedTask {
+                    stage: stage.name,
+                    task,
+                    reason,
+                });
+            }
+        }
+        if !aborted.is_empty() {
+            Err(ShutdownError::DeadlineElapsed { aborted, failed })
+        } else if !failed.is_empty() {
+            Err(ShutdownError::TasksFailed(failed))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn stage(&self, stage: StageId) -> &Stage {
+        &self.stages[stage.0]
+    }
+
+    fn stage_mut(&mut self, stage: StageId) -> &mut Stage {
+        &mut self.stages[stage.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn stages_stop_in_order() {
+        let mut supervisor = ServiceSupervisor::new();
+        let watchers = supervisor.add_stage("watchers");
+        let secret_managers = supervisor.add_stage("secret-managers");
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let secret_manager_order = Arc::clone(&order);
+        supervisor.on_shutdown(secret_managers, "postgres", move || async move {
+            secret_manager_order
+                .lock()
+                .expect("not poisoned")
+                .push("postgres");
+            Ok(())
+        });
+        let watcher_token = supervisor.cancellation_token(watchers);
+        let watcher_order = Arc::clone(&order);
+        supervisor.spawn(watchers, "key-event-watcher", async move {
+            watcher_token.cancelled().await;
+            // the secret manager must not be closed while the watcher is still running
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            watcher_order.lock().expect("not poisoned").push("watcher");
+            Ok(())
+        });
+
+        supervisor
+            .shutdown(Duration::from_secs(5))
+            .await
+            .expect("clean shutdown");
+        assert_eq!(
+            *order.lock().expect("not poisoned"),
+            ["watcher", "postgres"],
+            "watchers must stop before secret managers"
+        );
+    }
+
+    #[tokio::test]
+    async fn stage_cancellation_requests_shutdown() {
+        let mut supervisor = ServiceSupervisor::new();
+        let service = supervisor.add_stage("service");
+        supervisor.cancellation_token(service).cancel();
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            supervisor.shutdown_requested().cancelled(),
+        )
+        .await
+        .expect("shutdown requested");
+    }
+
+    #[tokio::test]
+    async fn deadline_aborts_remaining_tasks() {
+        let mut supervisor = ServiceSupervisor::new();
+        let watchers = supervisor.add_stage("watchers");
+        let secret_managers = supervisor.add_stage("secret-managers");
+        supervisor.spawn(watchers, "stuck", std::future::pending());
+        supervisor.on_shutdown(secret_managers, "postgres", || async { Ok(()) });
+
+        let err = supervisor
+            .shutdown(Duration::from_millis(50))
+            .await
+            .expect_err("deadline should elapse");
+        let ShutdownError::DeadlineElapsed { aborted, failed } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(
+            aborted,
+            [("watchers", "stuck"), ("secret-managers", "postgres")],
+            "all remaining tasks are aborted"
+        );
+        assert!(failed.is_empty(), "no task failed");
+    }
+}